@@ -1,8 +1,11 @@
-use crate::commands::{Direction, MoveCursor};
+use crate::commands::{Case, Direction, MoveCursor};
 use crate::mouse::MouseMove;
+use crate::row::Newline;
+use crate::status::Level;
 use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GuiAction {
     DecFontSize,
     IncFontSize,
@@ -11,42 +14,154 @@ pub enum GuiAction {
     SetLineHeight(f32),
     SetCharacterWidth(f32),
     UpdateSize(Vec2, Vec2), // FIXME: should be a window action, not entire app
+    ToggleMinimap,
+    SetFont(String),
     DumpFlameGraph,
+    // Shows how long each frame takes, over everything else
+    ToggleProfiler,
     PrintInfo,
     Quit,
+    // Quits without asking about unsaved changes
+    ForceQuit,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum WindowAction {
     SaveFile,           // FIXME: move to buffer actions
     SaveFileAs(String), // FIXME: this isn't a _window_ action surely?
+    SaveFileCreatingDirectory,
+    // Saves to the new name, then deletes the file it was saved as before
+    RenameFile(String),
     FocusPane(Direction),
+    // Right and Down grow the focused pane, Left and Up shrink it
+    ResizePane(Direction),
     ToggleFullscreen,
     SplitVertically,
+    SplitHorizontally,
+    ClosePane,
+    // Closes the focused pane without asking about its unsaved changes
+    ForceClosePane,
+    ReopenClosedPane,
+    // Tabs each have their own panes, working in their own directory
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    ChangeDirectory(String),
+    InspectChar,
+    NextBuffer,
+    PrevBuffer,
+    PickBuffer,
+    ListBindings,
+    ShowStats,
+    PreviewReplaceInFiles(String, String),
+    ApplyReplaceInFiles,
+    PickTheme,
+    SetTheme(String),
+    CommandPalette,
+    // Runs the action named, as it would be bound in the config file
+    RunCommand(String),
+    CommandLine,
+    RunExCommand(String),
+    OpenFile(String),
+    // A setting and its value, as they'd be in the config file
+    SetOption(String, String),
+    // Ask the focused buffer's language server about the text at the cursor
+    GotoDefinition,
+    ShowHover,
+    // Shell commands fed the selected rows, or the whole buffer, whose
+    // output replaces them or opens in a buffer of its own
+    FilterThroughCommand(String),
+    CommandIntoBuffer(String),
+    // Looks through every file under the working directory, listing the
+    // hits in a buffer of their own
+    GrepInFiles(String),
+    // A "file:line:col", as grep prints them
+    GotoLocation(String),
+    // Lowercase marks are the focused file's own, uppercase ones can be
+    // jumped to from anywhere
+    SetMark(char),
+    JumpToMark(char),
+    // Something a pane has to say, like a search finding nothing
+    ShowMessage(Level, String),
+    // Every message so far, in a buffer of their own
+    ShowMessages,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PaneAction {
     UpdateSize(Vec2, Vec2),
     MouseScroll(MouseMove),
     MouseClick(Vec2),
+    MouseShiftClick(Vec2),
+    // Starts a block selection there, to drag out
+    MouseAltClick(Vec2),
+    MouseDoubleClick(Vec2),
+    MouseTripleClick(Vec2),
+    MouseDrag(Vec2),
+    MouseRelease,
+    ScrollToFraction(f32),
+    ToggleFollow,
+    CycleLineNumbers,
     PrintDebugInfo,
+    StartReplaceInFiles,
+    PickFiletype,
+    StartGotoLine,
+    StartOpenFile,
+    StartSaveAs,
+    StartRename,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BufferAction {
     InsertNewlineAndReturn,
     InsertChar(char),
+    Paste(String),
     InsertTypedChar,
+    InsertIndent,
     DeleteChar(Direction),
+    DeleteWord(Direction),
     CloneCursor,
+    AddCursorAtNextMatch,
+    ClearCursors,
+    JumpToMatchingBracket,
+    ToggleComment,
+    // On the selected lines, or the cursor's line without a selection
+    DuplicateLines,
+    MoveLines(Direction),
+    JoinLines,
+    // Of the selection, or the word at the cursor without one
+    ChangeCase(Case),
+    // Adds to the number at or after the cursor, or takes away if negative
+    IncrementNumber(i64),
+    // Offer words to finish the one at the cursor with
+    Complete,
+    // Highlights all of a buffer that was too big to, when it was opened
+    HighlightFully,
+    // Back to the last place to fill in of the snippet Tab put in
+    PrevSnippetStop,
     MoveCursor(MoveCursor),
     SetFilename(String),
     SetFiletype(String),
     StartSearch,
+    StartSearchInSelection,
+    ToggleRegexSearch,
+    ToggleSearchAllBuffers,
+    // Stops showing the matches of the last search, like vim's :noh
+    ClearSearchHighlight,
+    StartReplace,
+    ToggleSelection,
+    // Selecting a rectangle, to type into or take out of every row at once
+    ToggleBlockSelection,
+    CutBlock,
+    PasteBlock,
+    ToggleVirtualEdit,
+    SetNewline(Newline),
+    Undo,
+    Redo,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Action {
     OnGui(GuiAction),
     OnWindow(WindowAction),
@@ -27,11 +27,15 @@ impl Animation {
         }
     }
 
-    pub fn add_duration(&mut self, duration: Duration) {
+    // Returns true if that moved it on to its next state
+    pub fn add_duration(&mut self, duration: Duration) -> bool {
         self.time_in_state += duration;
         if self.time_in_state > self.transition_time {
             self.time_in_state = Duration::default();
             self.next_state();
+            true
+        } else {
+            false
         }
     }
 
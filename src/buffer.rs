@@ -1,16 +1,72 @@
-use crate::commands::SearchDirection;
-use crate::cursor::{CursorT, CursorWithHistory};
+use crate::changed_rows::ChangedRows;
+use crate::commands::{Case, Direction, SearchDirection};
+use crate::cursor::{Cursor, CursorT, CursorWithHistory};
+use crate::encoding::Encoding;
+use crate::hex;
+use crate::highlight::Highlight;
+use crate::inspect::describe_char;
+use crate::loader::{Loaded, Loader};
+use crate::location;
+use crate::lsp::Diagnostic;
+use crate::marks::Marks;
+use crate::options::{IndentOptions, Indentation};
+use crate::paths;
+use crate::regex::Regex;
 use crate::row::{Row, DEFAULT_NEWLINE, DEFAULT_NEWLINE_STR, DOS_NEWLINE, UNIX_NEWLINE};
+use crate::selection::Selection;
+use crate::stats::EditStats;
+use crate::swap;
 use crate::syntax::{Syntax, SYNTAXES};
+use crate::template::{self, Template};
+use crate::undo::{Edit, EditKind, UndoHistory};
+use crate::utils::char_position_to_byte_position;
+use crate::watch::DiskStamp;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
 
+// Files smaller than this are quick enough to read all at once
+const BACKGROUND_LOAD_LEN: u64 = 1024 * 1024;
+// How long loading gets to hand over rows each time it's polled, so
+// frames don't go to waiting on it
+const LOAD_TIME_PER_POLL: Duration = Duration::from_millis(8);
+// How much of the start of a file is looked at for signs it's binary
+const BINARY_SNIFF_LEN: u64 = 8 * 1024;
+// A hex view stops this far into the file
+const HEX_VIEW_LEN: u64 = 16 * 1024 * 1024;
+// How far an edit's highlighting carries on down a buffer that's only
+// highlighted as it's shown, like when a comment's opened
+const LAZY_HIGHLIGHT_ROWS: usize = 200;
+// Looking for a matching bracket gives up after this many rows
+const MAX_BRACKET_ROWS: usize = 5_000;
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+const QUOTES: [char; 2] = ['"', '\''];
+
+// Why opening a file was held back to ask about first
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OpenWarning {
+    Large(u64),
+    Binary,
+}
+
+// How to go on with opening a file that was held back
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OpenAs {
+    ReadOnly,
+    Hex,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum FileSaveStatus {
     // FileExists,
     NoFilename,
+    // Nothing's saved until the directory's been created
+    MissingDirectory(PathBuf),
     Saved(usize),
 }
 
@@ -20,8 +76,56 @@ pub struct Buffer<'a> {
     pub rows: Vec<Row<'a>>,
     syntax: Rc<Option<&'a Syntax<'a>>>,
     pub cursor: CursorWithHistory,
+    // More places that edits happen at, as well as the cursor
+    extra_cursors: Vec<Cursor>,
+    // The bracket at the cursor and its match, as highlighted
+    matched_brackets: Vec<(usize, usize)>,
+    pub selection: Option<Selection>,
     dirty: i32,
+    revision: u64,
+    virtual_edit: bool,
+    indent_options: IndentOptions,
+    // Typed brackets and quotes get their closers put in after the cursor,
+    // which are where auto_closed says, so typing over them skips them
+    auto_close: bool,
+    auto_closed: Vec<(usize, usize)>,
+    trim_trailing_whitespace: bool,
+    // Which rows have been edited since the last save, as saving trims those
+    edited_rows: Vec<bool>,
+    undo: UndoHistory,
     newline: &'a str,
+    file_len: u64,
+    read_only: bool,
+    on_disk: bool,
+    encoding: Encoding,
+    loader: Option<Loader>,
+    load_error: Option<String>,
+    // Where the cursor's going once the rows it's going to have loaded
+    goto_after_load: Option<(usize, usize)>,
+    disk_stamp: Option<DiskStamp>,
+    // The swap file's been looked at, and whether it's ours to write to
+    swap_checked: bool,
+    owns_swap: bool,
+    swap_revision: Option<u64>,
+    stats: EditStats,
+    // Set by guard_open, so big or binary files are asked about first
+    guard_open: bool,
+    large_file_len: Option<u64>,
+    held_open: Option<OpenWarning>,
+    // Only opened to look at, so it's not to be saved over the file
+    opened_as: Option<OpenAs>,
+    // Rows to highlight again since the pane last took them
+    changed_rows: Option<ChangedRows>,
+    // What the language server last said was wrong with the file
+    diagnostics: Vec<Diagnostic>,
+    // Which search this lists the hits of, one to a row
+    results: Option<usize>,
+    // Files over lazy_highlight_len bytes are too big to highlight all of,
+    // so only rows about to be shown are
+    lazy_highlight_len: Option<u64>,
+    lazy_highlight: bool,
+    // Named places to go back to, which move with the rows they're on
+    marks: Marks,
 }
 
 impl<'a> Buffer<'a> {
@@ -29,6 +133,116 @@ impl<'a> Buffer<'a> {
         self.dirty.is_positive()
     }
 
+    // Not allowed to write to the file
+    pub fn is_read_only(&self) -> bool {
+        self.read_only || self.opened_as.is_some()
+    }
+
+    // Hasn't been saved to (or opened from) a file yet
+    pub fn is_new(&self) -> bool {
+        !self.on_disk
+    }
+
+    // Whether this is path's buffer, however either of them was written
+    pub fn is_for_path(&self, path: &Path) -> bool {
+        let filename = match self.filename.as_ref() {
+            Some(filename) => Path::new(filename),
+            None => return false,
+        };
+        match (fs::canonicalize(filename), fs::canonicalize(path)) {
+            (Ok(filename), Ok(path)) => filename == path,
+            _ => filename == path,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match (&self.filename, self.results) {
+            (Some(filename), _) => paths::display_path(filename),
+            (None, Some(_)) => String::from("[Results]"),
+            (None, None) => String::from("[No Name]"),
+        }
+    }
+
+    // The name with markers for what state the buffer's in, for anywhere
+    // that shows buffers by name, so they all agree
+    pub fn title(&self) -> String {
+        let mut title = self.name();
+        if self.is_new() && self.filename.is_some() {
+            title.push_str(" [New]");
+        }
+        if self.opened_as == Some(OpenAs::Hex) {
+            title.push_str(" [Hex]");
+        }
+        if self.is_read_only() {
+            title.push_str(" [RO]");
+        }
+        if self.lazy_highlight {
+            title.push_str(" [Large]");
+        }
+        if self.is_dirty() {
+            title.push_str(" [+]");
+        }
+        title
+    }
+
+    pub fn stats(&self) -> &EditStats {
+        &self.stats
+    }
+
+    pub fn add_time_focused(&mut self, duration: Duration) {
+        self.stats.add_time_focused(duration);
+    }
+
+    // Counts every edit, and unlike dirty isn't reset by saving
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+    }
+
+    pub fn marks(&self) -> &Marks {
+        &self.marks
+    }
+
+    pub fn mark(&self, name: char) -> Option<Cursor> {
+        self.marks.get(name)
+    }
+
+    // Marks where the cursor is
+    pub fn set_mark(&mut self, name: char) {
+        self.marks.set(name, self.cursor.current());
+    }
+
+    pub fn set_mark_at(&mut self, name: char, at: Cursor) {
+        self.marks.set(name, at);
+    }
+
+    pub fn remove_mark(&mut self, name: char) -> Option<Cursor> {
+        self.marks.remove(name)
+    }
+
+    // Goes to the mark, if it's set, as a jump that can be gone back from
+    pub fn jump_to_mark(&mut self, name: char) -> bool {
+        match self.marks.get(name) {
+            Some(at) => {
+                self.goto(at.text_row().max(0) as usize, at.text_col().max(0) as usize);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn mark_dirty(&mut self) {
+        self.dirty += 1;
+        self.revision += 1;
+    }
+
     pub fn num_lines(&self) -> usize {
         self.rows.len()
     }
@@ -37,6 +251,27 @@ impl<'a> Buffer<'a> {
         self.rows.get(line_num as usize).map(|row| row.size)
     }
 
+    // Rows that take up more than max_len columns on screen
+    pub fn long_lines(&self, max_len: usize) -> Vec<usize> {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.render_len() > max_len)
+            .map(|(row_idx, _)| row_idx)
+            .collect()
+    }
+
+    pub fn render_len(&self, line_num: i32) -> Option<usize> {
+        self.rows.get(line_num as usize).map(|row| row.render_len())
+    }
+
+    pub fn render_cursor_to_text(&self, render_x: usize, cursor_y: i32) -> i32 {
+        self.rows
+            .get(cursor_y as usize)
+            .map(|row| row.render_cursor_to_text(render_x) as i32)
+            .unwrap_or(0)
+    }
+
     // TODO: maybe introduce a RenderCursor and return it without params
     // we will still need to translate positions in the text to render
     // positions probably, but this just returns the column... it doesn't
@@ -44,16 +279,232 @@ impl<'a> Buffer<'a> {
     pub fn text_cursor_to_render(&self, cursor_x: i32, cursor_y: i32) -> i32 {
         self.rows
             .get(cursor_y as usize)
-            .map(|row| row.text_cursor_to_render(cursor_x))
+            .map(|row| {
+                // With virtual edit the cursor can sit past the end of the line
+                let past_end = cursor_x - row.size as i32;
+                if past_end > 0 {
+                    row.text_cursor_to_render(row.size as i32) + past_end
+                } else {
+                    row.text_cursor_to_render(cursor_x)
+                }
+            })
             .unwrap_or(0)
     }
 
+    // Which character of the row's rendered string the cursor is on, which
+    // isn't its column when there are tabs or wide characters before it
+    pub fn text_cursor_to_render_idx(&self, cursor_x: i32, cursor_y: i32) -> Option<usize> {
+        self.rows
+            .get(cursor_y as usize)
+            .and_then(|row| row.text_cursor_to_render_idx(cursor_x as usize))
+    }
+
+    pub fn inspect_char_at_cursor(&self) -> Option<String> {
+        let text_row = self.cursor.text_row() as usize;
+        let text_col = self.cursor.text_col() as usize;
+        let row = self.rows.get(text_row)?;
+        let row_text = row.as_str();
+        let byte_in_row = char_position_to_byte_position(row_text, text_col);
+        if byte_in_row >= row_text.len() {
+            return None;
+        }
+        let rows_before = self.rows.iter().take(text_row).map(Row::as_str);
+        let char_offset = rows_before
+            .clone()
+            .map(|row| row.chars().count())
+            .sum::<usize>()
+            + text_col;
+        let byte_offset = rows_before.map(str::len).sum::<usize>() + byte_in_row;
+        describe_char(&row_text[byte_in_row..], char_offset, byte_offset)
+    }
+
+    pub fn virtual_edit(&self) -> bool {
+        self.virtual_edit
+    }
+
+    // Lets the cursor move past the end of lines, padding with spaces on edit
+    pub fn set_virtual_edit(&mut self, virtual_edit: bool) {
+        self.virtual_edit = virtual_edit;
+        if !virtual_edit {
+            self.check_cursor();
+        }
+    }
+
+    pub fn indent_options(&self) -> &IndentOptions {
+        &self.indent_options
+    }
+
+    pub fn set_indent_options(&mut self, indent_options: IndentOptions) {
+        self.indent_options = indent_options;
+        self.set_syntax();
+    }
+
+    // How this buffer's filetype is indented
+    pub fn indentation(&self) -> Indentation {
+        self.indent_options.for_syntax(*self.syntax)
+    }
+
+    pub fn auto_close(&self) -> bool {
+        self.auto_close
+    }
+
+    pub fn trim_trailing_whitespace(&self) -> bool {
+        self.trim_trailing_whitespace
+    }
+
+    pub fn set_trim_trailing_whitespace(&mut self, trim: bool) {
+        self.trim_trailing_whitespace = trim;
+    }
+
+    pub fn set_auto_close(&mut self, auto_close: bool) {
+        self.auto_close = auto_close;
+        self.auto_closed.clear();
+    }
+
+    fn cursor_past_line_end(&self) -> i32 {
+        let row_len = self.line_len(self.cursor.text_row()).unwrap_or(0) as i32;
+        self.cursor.text_col() - row_len
+    }
+
+    // Fills the gap between the end of the line and a virtual cursor with spaces
+    fn pad_to_cursor(&mut self) {
+        let row = self.cursor.text_row();
+        let row_len = self.line_len(row).unwrap_or(0) as i32;
+        for col in row_len..self.cursor.text_col() {
+            self.insert_char(' ', col, row);
+        }
+    }
+
+    // Delete forwards from at or beyond the end of a line, pulling the next
+    // line up to the cursor
+    pub fn join_next_row_at_cursor(&mut self) {
+        let at = self.cursor.text_row() as usize + 1;
+        if at < self.num_lines() {
+            self.pad_to_cursor();
+            self.join_row(at);
+        }
+    }
+
+    // Keeps everything that goes by row in step once old_rows rows from row
+    // onwards have been replaced by new_rows rows
+    fn track_replaced_rows(&mut self, row: usize, old_rows: usize, new_rows: usize) {
+        let num_lines_before = self.num_lines() + old_rows - new_rows;
+        self.note_changed_rows(row, old_rows, new_rows, num_lines_before);
+        self.marks
+            .rows_replaced(row, old_rows, new_rows, num_lines_before);
+        self.edited_rows.resize(num_lines_before, false);
+        let end = (row + old_rows).min(num_lines_before);
+        self.edited_rows
+            .splice(row.min(end)..end, std::iter::repeat_n(false, new_rows));
+    }
+
+    fn row_texts(&self, at: usize, count: usize) -> Vec<String> {
+        self.rows
+            .iter()
+            .skip(at)
+            .take(count)
+            .map(|row| row.as_str().to_string())
+            .collect()
+    }
+
+    // Call after an edit that replaced the rows from row onwards, which used
+    // to read before, with after_count rows
+    fn record_edit(
+        &mut self,
+        kind: EditKind,
+        row: usize,
+        col: usize,
+        before: Vec<String>,
+        after_count: usize,
+    ) {
+        self.stats.record_edit(kind, before.len(), after_count);
+        for edited in self.edited_rows.iter_mut().skip(row).take(after_count) {
+            *edited = true;
+        }
+        let edit = Edit {
+            kind,
+            row,
+            col,
+            before,
+            after: self.row_texts(row, after_count),
+        };
+        self.undo.record(edit, self.cursor.current());
+    }
+
+    // Puts texts where count rows from at used to be, without recording it
+    fn replace_rows(&mut self, at: usize, count: usize, texts: &[String]) {
+        let end = (at + count).min(self.num_lines());
+        self.rows.drain(at..end);
+        for (i, text) in texts.iter().enumerate() {
+            let row = Row::new(
+                text,
+                Rc::downgrade(&self.syntax),
+                self.indentation().tab_width,
+            );
+            self.rows.insert(at + i, row);
+        }
+        self.track_replaced_rows(at, end - at, texts.len());
+        for row in at..at + texts.len().max(1) {
+            self.update_from(row);
+        }
+        self.mark_dirty();
+    }
+
+    // Groups of typing are undone together, moving the cursor ends a group
+    pub fn seal_undo_step(&mut self) {
+        self.undo.seal();
+    }
+
+    // Extra cursors aren't part of the history, so they're dropped
+    pub fn undo(&mut self) -> bool {
+        match self.undo.undo(self.cursor.current()) {
+            Some(step) => {
+                self.extra_cursors.clear();
+                self.auto_closed.clear();
+                for edit in step.edits.iter().rev() {
+                    self.replace_rows(edit.row, edit.after.len(), &edit.before);
+                }
+                self.move_cursor_to(step.cursor_before);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.undo.redo() {
+            Some(step) => {
+                self.extra_cursors.clear();
+                self.auto_closed.clear();
+                for edit in step.edits.iter() {
+                    self.replace_rows(edit.row, edit.before.len(), &edit.after);
+                }
+                self.move_cursor_to(step.cursor_after);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn move_cursor_to(&mut self, to: Cursor) {
+        self.cursor.change(|cursor| {
+            cursor.text_row = to.text_row;
+            cursor.text_col = to.text_col;
+        });
+        self.check_cursor();
+    }
+
     fn insert_row(&mut self, at: usize, text: &str) {
         if at <= self.num_lines() {
-            let row = Row::new(text, Rc::downgrade(&self.syntax));
+            let row = Row::new(
+                text,
+                Rc::downgrade(&self.syntax),
+                self.indentation().tab_width,
+            );
             self.rows.insert(at, row);
+            self.track_replaced_rows(at, 0, 1);
             self.update_from(at);
-            self.dirty += 1;
+            self.mark_dirty();
         }
     }
 
@@ -64,30 +515,187 @@ impl<'a> Buffer<'a> {
     }
 
     pub fn clear(&mut self) {
+        let num_lines = self.num_lines();
+        self.note_changed_rows(0, num_lines, 0, num_lines);
         self.rows.clear();
-        self.dirty += 1;
+        self.undo.clear();
+        self.extra_cursors.clear();
+        self.auto_closed.clear();
+        self.mark_dirty();
     }
 
     fn update_newline(&mut self) {
         if self.newline == "" {
-            if self.rows.len() > 0 {
-                if self.rows[0].as_str().ends_with(UNIX_NEWLINE) {
-                    self.newline = UNIX_NEWLINE;
-                } else if self.rows[0].as_str().ends_with(DOS_NEWLINE) {
-                    self.newline = DOS_NEWLINE;
+            self.newline = self.dominant_newline();
+        }
+    }
+
+    // Whichever line ending most rows have, so one stray line in a file
+    // doesn't decide it
+    fn dominant_newline(&self) -> &'static str {
+        let dos = self
+            .rows
+            .iter()
+            .filter(|row| row.as_str().ends_with(DOS_NEWLINE))
+            .count();
+        let unix = self
+            .rows
+            .iter()
+            .filter(|row| row.as_str().ends_with(UNIX_NEWLINE))
+            .count()
+            - dos;
+        if dos > unix {
+            DOS_NEWLINE
+        } else if unix > 0 {
+            UNIX_NEWLINE
+        } else {
+            DEFAULT_NEWLINE_STR
+        }
+    }
+
+    // What new rows end with, before anything's set the buffer's newlines
+    fn newline_or_default(&self) -> &'a str {
+        if self.newline.is_empty() {
+            DEFAULT_NEWLINE_STR
+        } else {
+            self.newline
+        }
+    }
+
+    pub fn newline_name(&self) -> &'static str {
+        let newline = if self.newline.is_empty() {
+            DEFAULT_NEWLINE_STR
+        } else {
+            self.newline
+        };
+        if newline == DOS_NEWLINE {
+            "CRLF"
+        } else {
+            "LF"
+        }
+    }
+
+    // Rewrites every line ending as newline, which can be undone in one go
+    pub fn convert_newlines(&mut self, newline: &'a str) {
+        self.newline = newline;
+        let before = self.row_texts(0, self.num_lines());
+        let after = before
+            .iter()
+            .map(|text| {
+                let line = text.trim_end_matches(['\r', '\n']);
+                if line.len() == text.len() {
+                    text.clone()
                 } else {
-                    self.newline = DEFAULT_NEWLINE_STR;
+                    format!("{}{}", line, newline)
                 }
-            } else {
-                self.newline = DEFAULT_NEWLINE_STR;
-            }
+            })
+            .collect::<Vec<_>>();
+        if before == after {
+            return;
         }
+        self.seal_undo_step();
+        self.replace_rows(0, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, 0, 0, before, after.len());
+        self.seal_undo_step();
+    }
+
+    fn note_changed_rows(&mut self, at: usize, old_rows: usize, new_rows: usize, num_lines: usize) {
+        self.changed_rows = Some(ChangedRows::record(
+            self.changed_rows,
+            at,
+            old_rows,
+            new_rows,
+            num_lines,
+        ));
+    }
+
+    // The rows that have changed since this was last called, or None if
+    // nothing has
+    pub fn take_changed_rows(&mut self) -> Option<ChangedRows> {
+        self.changed_rows.take()
     }
 
     fn update_syntax_highlighting(&mut self) {
-        self.rows
+        let num_lines = self.num_lines();
+        self.note_changed_rows(0, num_lines, num_lines, num_lines);
+        if self.lazy_highlight {
+            for row in self.rows.iter_mut() {
+                row.clear_highlight();
+            }
+            return;
+        }
+        // Edits carry on highlighting from the row before them, so each row
+        // has to remember whether it left a comment open
+        self.rows.iter_mut().fold(false, |prev, row| {
+            row.hl_open_comment = row.update_syntax_highlight(prev);
+            row.hl_open_comment
+        });
+        self.highlight_subject_line();
+    }
+
+    // From now on a file over len bytes is only highlighted as it's shown
+    pub fn highlight_lazily_over(&mut self, len: Option<u64>) {
+        self.lazy_highlight_len = len;
+    }
+
+    fn check_lazy_highlight(&mut self, file_len: u64) {
+        self.lazy_highlight = self
+            .lazy_highlight_len
+            .is_some_and(|max_len| file_len > max_len);
+    }
+
+    pub fn highlights_lazily(&self) -> bool {
+        self.lazy_highlight
+    }
+
+    // Highlights whichever of rows haven't been yet, carrying on from
+    // wherever the row before them was left, which is close enough
+    pub fn highlight_rows(&mut self, rows: Range<usize>) {
+        let mut in_comment = rows
+            .start
+            .checked_sub(1)
+            .and_then(|row| self.rows.get(row))
+            .map(|row| row.hl_open_comment)
+            .unwrap_or(false);
+        let mut first_highlighted = false;
+        for (idx, row) in self
+            .rows
             .iter_mut()
-            .fold(false, |prev, row| row.update_syntax_highlight(prev));
+            .enumerate()
+            .skip(rows.start)
+            .take(rows.len())
+        {
+            if row.is_highlighted() {
+                in_comment = row.hl_open_comment;
+                continue;
+            }
+            in_comment = row.update_syntax_highlight(in_comment);
+            row.hl_open_comment = in_comment;
+            first_highlighted |= idx == 0;
+        }
+        if first_highlighted {
+            self.highlight_subject_line();
+        }
+    }
+
+    // Goes back to highlighting the whole buffer, however long it takes
+    pub fn highlight_fully(&mut self) {
+        if self.lazy_highlight {
+            self.lazy_highlight = false;
+            self.update_syntax_highlighting();
+        }
+    }
+
+    // Commit messages want a short first line, so whatever's past the
+    // limit is shown as a warning
+    fn highlight_subject_line(&mut self) {
+        let max_len = match self.syntax.and_then(|syntax| syntax.subject_line_length) {
+            Some(max_len) => max_len,
+            None => return,
+        };
+        if let Some(row) = self.rows.first_mut() {
+            row.highlight_from(max_len, Highlight::Warning);
+        }
     }
 
     fn update(&mut self) {
@@ -104,15 +712,27 @@ impl<'a> Buffer<'a> {
         } else {
             false
         };
-        for row in self.rows.iter_mut().skip(at) {
+        let mut updated = 0;
+        let max_rows = if self.lazy_highlight {
+            LAZY_HIGHLIGHT_ROWS
+        } else {
+            usize::MAX
+        };
+        for row in self.rows.iter_mut().skip(at).take(max_rows) {
             let prev_ml_comment = row.hl_open_comment;
             in_comment = row.update_syntax_highlight(in_comment);
+            updated += 1;
             if in_comment != prev_ml_comment {
                 row.hl_open_comment = in_comment;
             } else {
                 break;
             }
         }
+        let num_lines = self.num_lines();
+        self.note_changed_rows(at, updated, updated, num_lines);
+        if at == 0 {
+            self.highlight_subject_line();
+        }
     }
 
     fn select_syntax(&mut self) {
@@ -130,24 +750,44 @@ impl<'a> Buffer<'a> {
     }
 
     pub fn open_file(&mut self, file: File) {
+        let mut bytes = vec![];
+        let _ = BufReader::new(file).read_to_end(&mut bytes);
+        self.open_bytes(&bytes);
+        self.on_disk = true;
+    }
+
+    // Text that didn't come from a file, like whatever was piped into bim
+    pub fn open_bytes(&mut self, bytes: &[u8]) {
         self.clear();
+        self.loader = None;
+        self.load_error = None;
 
-        let mut reader = BufReader::new(file);
-        loop {
-            let mut line = String::new();
-            let read_info = reader.read_line(&mut line);
-            match read_info {
-                Ok(bytes_read) if bytes_read > 0 => {
-                    self.append_row(&line);
-                }
-                _ => break,
-            }
+        self.file_len = bytes.len() as u64;
+        self.check_lazy_highlight(self.file_len);
+        self.encoding = Encoding::detect(bytes);
+        for line in self.encoding.decode(bytes).split_inclusive(UNIX_NEWLINE) {
+            self.append_row(line);
         }
+        self.newline = self.dominant_newline();
         self.dirty = 0;
+        self.revision = 0;
+        self.on_disk = false;
 
         self.select_syntax();
     }
 
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    // The file's saved in encoding from now on, which is a change to it
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if encoding != self.encoding {
+            self.encoding = encoding;
+            self.mark_dirty();
+        }
+    }
+
     pub fn get_filetype(&self) -> String {
         self.syntax
             .map(|x| x.filetype.to_string())
@@ -156,536 +796,4066 @@ impl<'a> Buffer<'a> {
 
     pub fn open(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
         let f = File::open(filename)?;
-        self.filename = Some(filename.to_string());
+        if let Some(warning) = self.open_warning(filename)? {
+            return Ok(self.hold_open(filename, warning)?);
+        }
+        self.held_open = None;
+        self.opened_as = None;
+        self.remove_swap();
+        self.swap_checked = false;
+        self.read_only = f.metadata()?.permissions().readonly();
+        self.filename = Some(absolute_filename(filename));
+        self.record_disk_stamp();
         self.open_file(f);
         self.select_syntax();
         Ok(())
     }
 
-    pub fn set_filename(&mut self, filename: String) {
-        self.filename = Some(filename);
-        self.select_syntax();
+    // Opens the file again, keeping the cursor where it was as far as
+    // the file allows
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        let filename = match self.filename.clone() {
+            Some(filename) => filename,
+            None => return Ok(()),
+        };
+        let cursor = self.cursor.current();
+        self.open(&filename)?;
+        self.cursor.move_to(cursor.text_row(), cursor.text_col());
+        self.check_cursor();
+        Ok(())
     }
 
-    pub fn save_file(&mut self) -> Result<FileSaveStatus, Box<dyn Error>> {
-        if let Some(filename) = self.filename.clone() {
-            let mut bytes_saved: usize = 0;
-            let mut buffer = BufWriter::new(File::create(filename)?);
-            for line in &self.rows {
-                bytes_saved += buffer.write(line.as_str().as_bytes())?;
-            }
-            buffer.flush()?;
-            self.dirty = 0;
-            Ok(FileSaveStatus::Saved(bytes_saved))
+    fn record_disk_stamp(&mut self) {
+        self.disk_stamp = self
+            .filename
+            .as_ref()
+            .and_then(|filename| DiskStamp::of(Path::new(filename)));
+    }
+
+    // How the file looks now, if something else has written to it since
+    // it was opened or saved
+    pub fn disk_change(&self) -> Option<DiskStamp> {
+        let filename = self.filename.as_ref()?;
+        let stamp = DiskStamp::of(Path::new(filename))?;
+        if self.disk_stamp.is_some() && self.disk_stamp != Some(stamp) {
+            Some(stamp)
         } else {
-            Ok(FileSaveStatus::NoFilename)
+            None
         }
     }
 
-    pub fn search_for(
+    // Sticks with what's in the buffer, so saving writes over the change
+    pub fn keep_in_memory(&mut self) {
+        self.record_disk_stamp();
+    }
+
+    pub fn open_or_new(
         &mut self,
-        last_match: Option<(usize, usize)>,
-        direction: SearchDirection,
-        needle: &str,
-    ) -> Option<(usize, usize)> {
-        self.clear_search_overlay();
-        let first_row = if direction == SearchDirection::Backwards {
-            1
+        filename: &str,
+        templates: &[Template],
+    ) -> Result<(), Box<dyn Error>> {
+        if Path::new(filename).exists() {
+            self.open(filename)
         } else {
-            0
-        };
-        let add_amount = last_match.map(|(_, l)| l as i32 + 1).unwrap_or(first_row);
-        let num_rows = self.num_lines() as i32;
-        let lines = match direction {
-            SearchDirection::Forwards => (0..num_rows)
-                .map(|i| (i + add_amount) % num_rows)
-                .collect::<Vec<_>>(),
-            SearchDirection::Backwards => (0..num_rows)
-                .map(|i| (i + add_amount - 1) % num_rows)
-                .rev()
-                .collect::<Vec<_>>(),
-        };
-        let mut found_match = None;
-        for y in lines {
-            assert!(y < num_rows, "num_rows = {}, y = {}", num_rows, y);
-            let row = &mut self.rows[y as usize];
-            if let Some(rx) = row.index_of(needle) {
-                let x = row.render_cursor_to_text(rx);
-                row.set_overlay_search(x, x + needle.len());
-                found_match = Some((x, y as usize));
-                break;
-            }
-        }
-        if let Some((x, y)) = found_match {
-            self.cursor.change(|cursor| {
-                cursor.text_col = x as i32;
-                cursor.text_row = y as i32;
-            });
+            self.new_file(filename, templates);
+            Ok(())
         }
-        found_match
     }
 
-    pub fn set_syntax(&mut self) {
-        for row in self.rows.iter_mut() {
-            row.set_syntax(Rc::downgrade(&self.syntax));
+    // Like open, but a big file is read on another thread and only shows up
+    // as poll_loading takes its rows
+    pub fn open_in_background(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let f = File::open(filename)?;
+        let metadata = f.metadata()?;
+        if metadata.len() < BACKGROUND_LOAD_LEN {
+            return self.open(filename);
         }
-        self.update();
-    }
-
-    pub fn append_row(&mut self, text: &str) {
-        let at = self.num_lines();
-        self.insert_row(at, text);
-        if at == 0 {
-            self.update_newline();
+        if let Some(warning) = self.open_warning(filename)? {
+            return Ok(self.hold_open(filename, warning)?);
         }
+        self.held_open = None;
+        self.opened_as = None;
+        self.remove_swap();
+        self.swap_checked = false;
+        self.read_only = metadata.permissions().readonly();
+        self.filename = Some(absolute_filename(filename));
+        self.record_disk_stamp();
+        self.clear();
+        self.check_lazy_highlight(metadata.len());
+        self.loader = Some(Loader::spawn(f)?);
+        self.load_error = None;
+        self.newline = "";
+        self.file_len = 0;
+        self.dirty = 0;
+        self.revision = 0;
+        self.on_disk = true;
+        self.select_syntax();
+        Ok(())
     }
 
-    pub fn insert_newline(&mut self, row: usize, col: usize) -> i32 {
-        let newline = self
+    pub fn open_or_new_in_background(
+        &mut self,
+        filename: &str,
+        templates: &[Template],
+    ) -> Result<(), Box<dyn Error>> {
+        let (filename, location) = location::split_location(filename);
+        if Path::new(filename).exists() {
+            self.open_in_background(filename)?;
+        } else {
+            self.new_file(filename, templates);
+        }
+        if let Some((row, col)) = location {
+            self.goto(row, col);
+        }
+        Ok(())
+    }
+
+    // From now on a file over large_file_len bytes, or one that looks
+    // binary, isn't opened straight away. The buffer's left empty with
+    // held_open saying why, until open_held or cancel_held.
+    pub fn guard_open(&mut self, large_file_len: Option<u64>) {
+        self.guard_open = true;
+        self.large_file_len = large_file_len;
+    }
+
+    pub fn held_open(&self) -> Option<OpenWarning> {
+        self.held_open
+    }
+
+    fn open_warning(&self, filename: &str) -> io::Result<Option<OpenWarning>> {
+        if !self.guard_open {
+            return Ok(None);
+        }
+        let file = File::open(filename)?;
+        let len = file.metadata()?.len();
+        if self.large_file_len.is_some_and(|max_len| len > max_len) {
+            return Ok(Some(OpenWarning::Large(len)));
+        }
+        let mut start = vec![];
+        file.take(BINARY_SNIFF_LEN).read_to_end(&mut start)?;
+        if Encoding::looks_binary(&start) {
+            Ok(Some(OpenWarning::Binary))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn hold_open(&mut self, filename: &str, warning: OpenWarning) -> io::Result<()> {
+        let read_only = fs::metadata(filename)?.permissions().readonly();
+        self.remove_swap();
+        self.clear();
+        self.loader = None;
+        self.load_error = None;
+        self.filename = Some(absolute_filename(filename));
+        self.read_only = read_only;
+        // Nothing's been read, so there's nothing to notice changing or
+        // to recover
+        self.disk_stamp = None;
+        self.swap_checked = true;
+        self.dirty = 0;
+        self.revision = 0;
+        self.on_disk = true;
+        self.opened_as = None;
+        self.held_open = Some(warning);
+        Ok(())
+    }
+
+    // Goes on with opening the file that was held back, either as it is
+    // but without saving, or as a hex dump of its start
+    pub fn open_held(&mut self, how: OpenAs) -> Result<(), Box<dyn Error>> {
+        let filename = match (self.held_open, self.filename.clone()) {
+            (Some(_), Some(filename)) => filename,
+            _ => return Ok(()),
+        };
+        match how {
+            OpenAs::ReadOnly => {
+                self.guard_open = false;
+                let opened = self.open_in_background(&filename);
+                self.guard_open = true;
+                opened?;
+            }
+            OpenAs::Hex => {
+                self.open_hex(&filename)?;
+                // Rows in the dump aren't the file's rows
+                self.goto_after_load = None;
+            }
+        }
+        self.held_open = None;
+        self.opened_as = Some(how);
+        if let (false, Some((row, col))) = (self.is_loading(), self.goto_after_load.take()) {
+            self.goto(row, col);
+        }
+        self.swap_checked = true;
+        Ok(())
+    }
+
+    fn open_hex(&mut self, filename: &str) -> io::Result<()> {
+        let file = File::open(filename)?;
+        let len = file.metadata()?.len();
+        let mut bytes = vec![];
+        file.take(HEX_VIEW_LEN).read_to_end(&mut bytes)?;
+        self.clear();
+        *Rc::make_mut(&mut self.syntax) = None;
+        for row in hex::dump(&bytes) {
+            self.append_row(&row);
+        }
+        if len > bytes.len() as u64 {
+            self.append_row(&format!(
+                "... and {} more bytes\n",
+                len - bytes.len() as u64
+            ));
+        }
+        self.set_syntax();
+        self.encoding = Encoding::Utf8;
+        self.newline = self.dominant_newline();
+        self.file_len = len;
+        self.dirty = 0;
+        self.revision = 0;
+        self.record_disk_stamp();
+        Ok(())
+    }
+
+    // Doesn't open the held back file after all
+    pub fn cancel_held(&mut self) {
+        if self.held_open.take().is_some() {
+            self.filename = None;
+            self.goto_after_load = None;
+            self.read_only = false;
+            self.on_disk = false;
+            *Rc::make_mut(&mut self.syntax) = None;
+        }
+    }
+
+    // Moves the cursor, waiting for the file to finish loading first
+    pub fn goto(&mut self, row: usize, col: usize) {
+        if self.is_loading() || self.held_open.is_some() {
+            self.goto_after_load = Some((row, col));
+            return;
+        }
+        let last_row = self.num_lines().saturating_sub(1);
+        self.cursor.move_to(row.min(last_row) as i32, col as i32);
+        self.check_cursor();
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    // Takes whatever rows have been read since last time, returning the
+    // number of rows added. Loading is over once is_loading says so.
+    pub fn poll_loading(&mut self) -> usize {
+        let rows_before = self.num_lines();
+        let dirty = self.dirty;
+        let started = Instant::now();
+        while started.elapsed() < LOAD_TIME_PER_POLL {
+            let loader = match self.loader.as_mut() {
+                Some(loader) => loader,
+                None => break,
+            };
+            match loader.try_recv() {
+                Ok(Loaded::Started(encoding)) => self.encoding = encoding,
+                Ok(Loaded::Rows(rows)) => {
+                    for row in rows {
+                        self.append_row(&row);
+                    }
+                }
+                Ok(Loaded::Failed(err)) => {
+                    self.load_error = Some(err);
+                    self.loader = None;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.file_len = loader.file_len();
+                    self.newline = self.dominant_newline();
+                    self.loader = None;
+                    if let Some((row, col)) = self.goto_after_load.take() {
+                        self.goto(row, col);
+                    }
+                }
+            }
+        }
+        // Rows that came from the file aren't changes
+        self.dirty = dirty;
+        self.num_lines() - rows_before
+    }
+
+    // What to say about loading while it's going on (or if it went wrong)
+    pub fn loading_status(&self) -> Option<String> {
+        if let Some(loader) = &self.loader {
+            Some(format!("loading {}%", loader.percent_loaded()))
+        } else {
+            self.load_error
+                .as_ref()
+                .map(|err| format!("loading failed: {}", err))
+        }
+    }
+
+    pub fn new_file(&mut self, filename: &str, templates: &[Template]) {
+        // Insert before the syntax is known so auto-indent leaves the template alone
+        if let Some(template) = template::for_filename(templates, filename) {
+            let (text, cursor) = template.expand(filename);
+            self.insert_text_at_cursor(&text);
+            if let Some((row, col)) = cursor {
+                self.cursor.move_to(row as i32, col as i32);
+            }
+        }
+        self.set_filename(filename.to_string());
+    }
+
+    pub fn set_filename(&mut self, filename: String) {
+        let filename = absolute_filename(&filename);
+        self.remove_swap();
+        self.filename = Some(filename);
+        // Saving somewhere else doesn't touch the file it was opened from
+        self.opened_as = None;
+        // Whatever's there already isn't a change made behind our back
+        self.disk_stamp = None;
+        self.select_syntax();
+    }
+
+    pub fn save_file(&mut self) -> Result<FileSaveStatus, Box<dyn Error>> {
+        // Saving part of a file would throw the rest of it away
+        if self.is_loading() || self.load_error.is_some() || self.held_open.is_some() {
+            return Err(format!("{} hasn't finished loading", self.name()).into());
+        }
+        if self.opened_as.is_some() {
+            return Err(format!("{} was opened read-only", self.name()).into());
+        }
+        // Only once, saving again writes over whatever the change was
+        if self.disk_change().is_some() {
+            self.record_disk_stamp();
+            return Err(format!(
+                "{} changed on disk since it was opened, save again to overwrite it",
+                self.name()
+            )
+            .into());
+        }
+        if let Some(dir) = self.missing_directory() {
+            return Ok(FileSaveStatus::MissingDirectory(dir));
+        }
+        if let Some(filename) = self.filename.clone() {
+            if self.trim_trailing_whitespace {
+                self.trim_edited_rows();
+            }
+            let mut buffer = BufWriter::new(File::create(filename)?);
+            let bytes_saved = self.write_text(&mut buffer)?;
+            buffer.flush()?;
+            self.edited_rows.clear();
+            self.dirty = 0;
+            self.on_disk = true;
+            self.read_only = false;
+            self.file_len = bytes_saved as u64;
+            self.record_disk_stamp();
+            self.remove_swap();
+            Ok(FileSaveStatus::Saved(bytes_saved))
+        } else {
+            Ok(FileSaveStatus::NoFilename)
+        }
+    }
+
+    // Takes the spaces and tabs off the ends of the rows edited since the
+    // last save, as one step to undo
+    fn trim_edited_rows(&mut self) {
+        let rows = self
+            .edited_rows
+            .iter()
+            .enumerate()
+            .filter(|(row, edited)| {
+                **edited
+                    && self
+                        .rows
+                        .get(*row)
+                        .is_some_and(|line| line.trailing_whitespace_start() < line.size)
+            })
+            .map(|(row, _)| row)
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return;
+        }
+        self.undo.start_group();
+        for row in rows {
+            let before = self.row_texts(row, 1);
+            let line = &before[0];
+            let text = line.trim_end_matches(['\r', '\n']);
+            let after = vec![format!(
+                "{}{}",
+                text.trim_end_matches([' ', '\t']),
+                &line[text.len()..]
+            )];
+            self.replace_rows(row, 1, &after);
+            self.record_edit(EditKind::ReplaceRows, row, 0, before, 1);
+        }
+        self.undo.end_group();
+        let row_len = self.line_len(self.cursor.text_row()).unwrap_or(0) as i32;
+        if !self.virtual_edit && self.cursor.text_col() > row_len {
+            self.cursor.change(|cursor| cursor.text_col = row_len);
+        }
+    }
+
+    // The directory the file would be saved in, if it doesn't exist yet
+    pub fn missing_directory(&self) -> Option<PathBuf> {
+        let dir = Path::new(self.filename.as_ref()?).parent()?;
+        if dir.as_os_str().is_empty() || dir.exists() {
+            None
+        } else {
+            Some(dir.to_path_buf())
+        }
+    }
+
+    // Creates the directory the file's to be saved in, returning it if it
+    // had to be created
+    pub fn create_missing_directory(&self) -> io::Result<Option<PathBuf>> {
+        match self.missing_directory() {
+            Some(dir) => fs::create_dir_all(&dir).map(|_| Some(dir)),
+            None => Ok(None),
+        }
+    }
+
+    // A swap file left by a bim that didn't get to save its changes, rather
+    // than one another bim has the file open with. Each file is only looked
+    // for once, after it's finished loading.
+    pub fn leftover_swap(&mut self) -> Option<PathBuf> {
+        if self.swap_checked || self.is_loading() {
+            return None;
+        }
+        self.swap_checked = true;
+        let path = swap::swap_path(self.filename.as_ref()?);
+        if path.exists() && !swap::held_elsewhere(&path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    // Writes any changes made since last time to the swap file, as long
+    // as it's not one that's still waiting to be recovered
+    pub fn write_swap(&mut self) -> io::Result<()> {
+        let filename = match self.filename.clone() {
+            Some(filename)
+                if self.is_dirty()
+                    && self.swap_checked
+                    && !self.is_loading()
+                    && self.opened_as.is_none() =>
+            {
+                filename
+            }
+            _ => return Ok(()),
+        };
+        let path = swap::swap_path(&filename);
+        if self.swap_revision == Some(self.revision) || (!self.owns_swap && path.exists()) {
+            return Ok(());
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        swap::write_header(&mut file)?;
+        self.write_text(&mut file)?;
+        file.flush()?;
+        self.owns_swap = true;
+        self.swap_revision = Some(self.revision);
+        Ok(())
+    }
+
+    // Swaps the text for what's in the swap file, as one edit so it can
+    // be undone back to what was on disk
+    pub fn recover_swap(&mut self) -> Result<(), Box<dyn Error>> {
+        let filename = match self.filename.clone() {
+            Some(filename) => filename,
+            None => return Ok(()),
+        };
+        let path = swap::swap_path(&filename);
+        let bytes = fs::read(&path)?;
+        let (_, text) = swap::split_header(&bytes);
+        let after = self
+            .encoding
+            .decode(text)
+            .split_inclusive(UNIX_NEWLINE)
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let before = self.row_texts(0, self.num_lines());
+        self.seal_undo_step();
+        self.replace_rows(0, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, 0, 0, before, after.len());
+        self.seal_undo_step();
+        self.check_cursor();
+        self.owns_swap = !swap::held_elsewhere(&path);
+        self.swap_revision = None;
+        Ok(())
+    }
+
+    // Gets rid of a leftover swap file without recovering it, unless
+    // another bim's started using it since
+    pub fn discard_swap(&mut self) {
+        let held = self
+            .filename
+            .as_ref()
+            .is_some_and(|filename| swap::held_elsewhere(&swap::swap_path(filename)));
+        if !held {
+            self.owns_swap = true;
+            self.remove_swap();
+        }
+    }
+
+    // Once the changes are saved (or thrown away) the swap file can go
+    pub fn remove_swap(&mut self) {
+        if let (true, Some(filename)) = (self.owns_swap, self.filename.as_ref()) {
+            let _ = fs::remove_file(swap::swap_path(filename));
+        }
+        self.owns_swap = false;
+        self.swap_revision = None;
+    }
+
+    // Writes the text out in the buffer's encoding, returning how many
+    // bytes that took
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<usize> {
+        if self.encoding == Encoding::Utf8 {
+            let mut bytes_written = 0;
+            for row in &self.rows {
+                out.write_all(row.as_str().as_bytes())?;
+                bytes_written += row.as_str().len();
+            }
+            Ok(bytes_written)
+        } else {
+            let bytes = self.encoding.encode(&self.contents());
+            out.write_all(&bytes)?;
+            Ok(bytes.len())
+        }
+    }
+
+    // Reads anything written to the end of the file since we last looked,
+    // returning the number of rows added. A file that shrank is reloaded.
+    pub fn read_appended(&mut self) -> Result<usize, Box<dyn Error>> {
+        let filename = match self.filename.clone() {
+            Some(filename) if !self.is_loading() => filename,
+            _ => return Ok(0),
+        };
+        let mut file = File::open(&filename)?;
+        let file_len = file.metadata()?.len();
+        if file_len < self.file_len {
+            self.record_disk_stamp();
+            self.open_file(file);
+            return Ok(self.num_lines());
+        }
+        if file_len == self.file_len {
+            return Ok(0);
+        }
+
+        let dirty = self.dirty;
+        let rows_before = self.num_lines();
+        file.seek(SeekFrom::Start(self.file_len))?;
+        let mut bytes = vec![];
+        self.file_len += BufReader::new(file).read_to_end(&mut bytes)? as u64;
+        for line in self.encoding.decode(&bytes).split_inclusive(UNIX_NEWLINE) {
+            let last_row_open = self
+                .rows
+                .last()
+                .map(|row| !row.as_str().ends_with(UNIX_NEWLINE))
+                .unwrap_or(false);
+            if last_row_open {
+                let last = self.num_lines() - 1;
+                self.rows[last].append_text(line);
+                self.track_replaced_rows(last, 1, 1);
+                self.update_from(last);
+            } else {
+                self.append_row(line);
+            }
+        }
+        self.dirty = dirty;
+        self.record_disk_stamp();
+        Ok(self.num_lines() - rows_before)
+    }
+
+    // Trims up to count rows starting at from, returning where to carry on
+    // from next time (wrapping back to the start once the end is reached)
+    pub fn trim_rows(&mut self, from: usize, count: usize) -> usize {
+        if from >= self.num_lines() {
+            self.rows.shrink_to_fit();
+            return 0;
+        }
+        for row in self.rows.iter_mut().skip(from).take(count) {
+            row.trim_capacity();
+        }
+        from + count
+    }
+
+    pub fn start_selection(&mut self) {
+        self.selection = Some(Selection::new(self.cursor.current()));
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+
+    pub fn toggle_selection(&mut self) {
+        if self.selection.is_some() {
+            self.clear_selection();
+        } else {
+            self.start_selection();
+        }
+    }
+
+    // Selects the word the cursor is in (or just after), leaving the cursor
+    // at its end. Returns false if there's no word there.
+    pub fn select_word_at_cursor(&mut self) -> bool {
+        let row = self.cursor.text_row();
+        let (start, end) = match self.word_at(row, self.cursor.text_col()) {
+            Some(word) => word,
+            None => {
+                self.clear_selection();
+                return false;
+            }
+        };
+        self.selection = Some(Selection::new(Cursor::new(row, start as i32)));
+        self.cursor.move_to_without_history(row, end as i32);
+        self.update_selection();
+        true
+    }
+
+    // Selects the whole of the cursor's line, with its newline if it has one
+    pub fn select_line_at_cursor(&mut self) {
+        let row = self.cursor.text_row();
+        self.selection = Some(Selection::new(Cursor::new(row, 0)));
+        if (row as usize) + 1 < self.num_lines() {
+            self.cursor.move_to_without_history(row + 1, 0);
+        } else {
+            let row_len = self.line_len(row).unwrap_or(0) as i32;
+            self.cursor.move_to_without_history(row, row_len);
+        }
+        self.update_selection();
+    }
+
+    pub fn start_block_selection(&mut self) {
+        self.extra_cursors.clear();
+        self.selection = Some(Selection::new_block(self.cursor.current()));
+    }
+
+    // The rows and columns a block selection covers
+    fn block(&self) -> Option<(Range<usize>, Range<usize>)> {
+        let selection = self.selection.filter(Selection::is_block)?;
+        let first = selection.start().text_row().max(0) as usize;
+        let last =
+            (selection.end().text_row().max(0) as usize).min(self.num_lines().checked_sub(1)?);
+        let (first_col, last_col) = selection.block_cols();
+        Some((
+            first..last + 1,
+            first_col.max(0) as usize..last_col.max(0) as usize,
+        ))
+    }
+
+    // Takes whatever's inside a block selection out, all in one undo step,
+    // and leaves a cursor on each of its rows at its left edge. With pad,
+    // rows too short to reach that far are filled out with spaces, so what's
+    // typed next lines up. Gives back what was taken, a row at a time.
+    pub fn take_block(&mut self, pad: bool) -> Option<Vec<String>> {
+        let (rows, cols) = self.block()?;
+        self.selection = None;
+        let before = self.row_texts(rows.start, rows.len());
+        let mut taken = vec![];
+        let mut cursor_cols = vec![];
+        let after = before
+            .iter()
+            .map(|text| {
+                let line = text.trim_end_matches(['\r', '\n']);
+                let chars = line.chars().collect::<Vec<_>>();
+                let first = cols.start.min(chars.len());
+                let last = cols.end.min(chars.len());
+                taken.push(chars[first..last].iter().collect::<String>());
+                let mut kept = chars[..first].iter().collect::<String>();
+                if pad && chars.len() < cols.start {
+                    kept.push_str(&" ".repeat(cols.start - chars.len()));
+                }
+                cursor_cols.push(kept.chars().count());
+                kept.extend(&chars[last..]);
+                format!("{}{}", kept, &text[line.len()..])
+            })
+            .collect::<Vec<_>>();
+        if after != before {
+            self.seal_undo_step();
+            self.replace_rows(rows.start, before.len(), &after);
+            self.record_edit(
+                EditKind::ReplaceRows,
+                rows.start,
+                cols.start,
+                before,
+                after.len(),
+            );
+            self.seal_undo_step();
+        }
+        self.extra_cursors.clear();
+        for (row, col) in rows.clone().zip(cursor_cols.iter()).skip(1) {
+            self.add_cursor(row as i32, *col as i32);
+        }
+        self.move_cursor_to(Cursor::new(rows.start as i32, cursor_cols[0] as i32));
+        Some(taken)
+    }
+
+    // Puts lines in at the cursor's column, one to a row going down, as a
+    // block taken out with take_block would have been. Short rows are
+    // padded out to the column and rows are added past the end if need be.
+    pub fn insert_block(&mut self, lines: &[String]) {
+        if lines.is_empty() {
+            return;
+        }
+        let row = self.cursor.text_row().max(0) as usize;
+        let col = self.cursor.text_col().max(0) as usize;
+        let before = self.row_texts(row, lines.len());
+        let after = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let text = before.get(i).map(String::as_str).unwrap_or_default();
+                let existing = text.trim_end_matches(['\r', '\n']);
+                let newline = match &text[existing.len()..] {
+                    "" if i + 1 < lines.len() || i >= before.len() => self.newline_or_default(),
+                    newline => newline,
+                };
+                let chars = existing.chars().collect::<Vec<_>>();
+                let at = col.min(chars.len());
+                format!(
+                    "{}{}{}{}{}",
+                    chars[..at].iter().collect::<String>(),
+                    " ".repeat(col - at),
+                    line,
+                    chars[at..].iter().collect::<String>(),
+                    newline
+                )
+            })
+            .collect::<Vec<_>>();
+        self.seal_undo_step();
+        self.replace_rows(row, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, row, col, before, after.len());
+        self.seal_undo_step();
+        let last = &lines[lines.len() - 1];
+        self.move_cursor_to(Cursor::new(
+            (row + lines.len() - 1) as i32,
+            (col + last.chars().count()) as i32,
+        ));
+    }
+
+    // The cursor is the moving end of the selection
+    pub fn update_selection(&mut self) {
+        let cursor = self.cursor.current();
+        if let Some(selection) = self.selection.as_mut() {
+            selection.extend_to(cursor);
+        }
+    }
+
+    pub fn search_for(
+        &mut self,
+        last_match: Option<(usize, usize)>,
+        direction: SearchDirection,
+        needle: &str,
+    ) -> Option<(usize, usize)> {
+        self.search_within(last_match, direction, needle, None)
+    }
+
+    pub fn search_within(
+        &mut self,
+        last_match: Option<(usize, usize)>,
+        direction: SearchDirection,
+        needle: &str,
+        scope: Option<Selection>,
+    ) -> Option<(usize, usize)> {
+        self.search_rows(last_match, direction, scope, |row| {
+            needle_matches(row, needle)
+        })
+    }
+
+    // Like search_within, but the cursor lands on (and only highlights)
+    // the first capture group if the regex has one
+    pub fn search_regex_within(
+        &mut self,
+        last_match: Option<(usize, usize)>,
+        direction: SearchDirection,
+        regex: &Regex,
+        scope: Option<Selection>,
+    ) -> Option<(usize, usize)> {
+        self.search_rows(last_match, direction, scope, |row| {
+            let text = row.as_str().trim_end_matches(['\r', '\n']);
+            regex
+                .find_all(text)
+                .iter()
+                .map(|found| found.focus())
+                .collect()
+        })
+    }
+
+    // Goes through the rows after (or before) the last match's row, looking
+    // for the first row where find_in_row turns up a match inside scope.
+    // find_in_row gives the start and end columns of every match in a row.
+    fn search_rows<F>(
+        &mut self,
+        last_match: Option<(usize, usize)>,
+        direction: SearchDirection,
+        scope: Option<Selection>,
+        find_in_row: F,
+    ) -> Option<(usize, usize)>
+    where
+        F: Fn(&Row<'a>) -> Vec<(usize, usize)>,
+    {
+        self.clear_search_overlay();
+        let forwards = direction == SearchDirection::Forwards;
+        let num_rows = self.num_lines();
+        let nth_row = |from: usize, i: usize| {
+            if forwards {
+                (from + i) % num_rows
+            } else {
+                (from + num_rows - i) % num_rows
+            }
+        };
+        // The rows to look through in turn, with the columns a match can
+        // start at in each. The rest of the last match's row comes first,
+        // then the other rows, then back round to the start of that row.
+        let mut rows = Vec::with_capacity(num_rows + 1);
+        match last_match {
+            Some((x, last_row)) if last_row < num_rows => {
+                let (rest, before) = if forwards {
+                    (x + 1..usize::MAX, 0..x + 1)
+                } else {
+                    (0..x, x..usize::MAX)
+                };
+                rows.push((last_row, rest));
+                rows.extend((1..num_rows).map(|i| (nth_row(last_row, i), 0..usize::MAX)));
+                rows.push((last_row, before));
+            }
+            _ => {
+                let first_row = if forwards {
+                    0
+                } else {
+                    num_rows.saturating_sub(1)
+                };
+                rows.extend((0..num_rows).map(|i| (nth_row(first_row, i), 0..usize::MAX)));
+            }
+        }
+        let mut found_match = None;
+        for (y, cols) in rows {
+            if scope.is_some_and(|scope| !scope.contains_row(y as i32)) {
+                continue;
+            }
+            let row = &mut self.rows[y];
+            let mut in_scope = find_in_row(row).into_iter().filter(|(start, end)| {
+                cols.contains(start)
+                    && scope.is_none_or(|scope| {
+                        scope.contains_range(y as i32, *start as i32, *end as i32)
+                    })
+            });
+            let found = if forwards {
+                in_scope.next()
+            } else {
+                in_scope.next_back()
+            };
+            if let Some((x, end)) = found {
+                row.set_overlay_search(x, end);
+                found_match = Some((x, y));
+                break;
+            }
+        }
+        if let Some((x, y)) = found_match {
+            self.cursor.change(|cursor| {
+                cursor.text_col = x as i32;
+                cursor.text_row = y as i32;
+            });
+        }
+        found_match
+    }
+
+    // Where needle (or regex, when there is one) turns up in row y, leaving
+    // out any matches outside scope
+    fn search_matches(
+        &self,
+        y: usize,
+        needle: &str,
+        regex: Option<&Regex>,
+        scope: Option<Selection>,
+    ) -> Vec<(usize, usize)> {
+        let row = match self.rows.get(y) {
+            Some(row) if scope.is_none_or(|scope| scope.contains_row(y as i32)) => row,
+            _ => return vec![],
+        };
+        let matches = match regex {
+            Some(regex) => regex
+                .find_all(row.as_str().trim_end_matches(['\r', '\n']))
+                .iter()
+                .map(|found| found.focus())
+                .collect(),
+            None => needle_matches(row, needle),
+        };
+        matches
+            .into_iter()
+            .filter(|(start, end)| {
+                scope.is_none_or(|scope| scope.contains_range(y as i32, *start as i32, *end as i32))
+            })
+            .collect()
+    }
+
+    // Marks every match in rows as well as the one the search is on, so
+    // they can all be seen at once
+    pub fn highlight_matches(
+        &mut self,
+        rows: Range<usize>,
+        needle: &str,
+        regex: Option<&Regex>,
+        scope: Option<Selection>,
+    ) {
+        for y in rows.start..rows.end.min(self.num_lines()) {
+            let matches = self.search_matches(y, needle, regex, scope);
+            let row = &mut self.rows[y];
+            row.clear_overlay_search();
+            for (start, end) in matches {
+                row.add_overlay_search(start, end);
+            }
+        }
+    }
+
+    // Which match the one at current (as col and row) is, counting from 1,
+    // and how many there are altogether
+    pub fn count_matches(
+        &self,
+        needle: &str,
+        regex: Option<&Regex>,
+        scope: Option<Selection>,
+        current: Option<(usize, usize)>,
+    ) -> (usize, usize) {
+        let (mut index, mut total) = (0, 0);
+        for y in 0..self.num_lines() {
+            for (start, _) in self.search_matches(y, needle, regex, scope) {
+                total += 1;
+                if current.is_some_and(|(x, row)| (y, start) <= (row, x)) {
+                    index = total;
+                }
+            }
+        }
+        (index, total)
+    }
+
+    // The first match for replacing that starts at or after col on row, or
+    // on a later row, as its row, start and end. Unlike searching, regexes
+    // match as a whole and there's no wrapping around to the top. The
+    // match gets highlighted and the cursor moves onto it.
+    pub fn find_replace_match(
+        &mut self,
+        row: usize,
+        col: usize,
+        needle: &str,
+        regex: Option<&Regex>,
+    ) -> Option<(usize, usize, usize)> {
+        self.clear_search_overlay();
+        for y in row..self.num_lines() {
+            let from = if y == row { col } else { 0 };
+            let found = replace_matches(&self.rows[y], needle, regex)
+                .into_iter()
+                .find(|(start, _)| *start >= from);
+            if let Some((start, end)) = found {
+                self.rows[y].set_overlay_search(start, end);
+                self.move_cursor_to(Cursor::new(y as i32, start as i32));
+                return Some((y, start, end));
+            }
+        }
+        None
+    }
+
+    // Replaces the text between start and end of row, in its own undo step
+    pub fn replace_text(&mut self, row: usize, start: usize, end: usize, replacement: &str) {
+        let before = self.row_texts(row, 1);
+        let text = match before.first() {
+            Some(text) => text,
+            None => return,
+        };
+        let after = format!(
+            "{}{}{}",
+            &text[..char_position_to_byte_position(text, start)],
+            replacement,
+            &text[char_position_to_byte_position(text, end)..]
+        );
+        self.seal_undo_step();
+        self.replace_rows(row, 1, &[after]);
+        self.record_edit(EditKind::ReplaceRows, row, start, before, 1);
+        self.seal_undo_step();
+    }
+
+    // Replaces every match from col on row onwards, all in one undo step,
+    // and says how many there were
+    pub fn replace_all_from(
+        &mut self,
+        row: usize,
+        col: usize,
+        needle: &str,
+        regex: Option<&Regex>,
+        replacement: &str,
+    ) -> usize {
+        self.clear_search_overlay();
+        let before = self.row_texts(row, self.num_lines().saturating_sub(row));
+        let mut count = 0;
+        let mut last_changed = None;
+        let mut after = before.clone();
+        for (i, text) in after.iter_mut().enumerate() {
+            let from = if i == 0 { col } else { 0 };
+            let matches = replace_matches(&self.rows[row + i], needle, regex)
+                .into_iter()
+                .filter(|(start, _)| *start >= from)
+                .collect::<Vec<_>>();
+            if matches.is_empty() {
+                continue;
+            }
+            // Going backwards keeps the earlier columns where they were
+            for (start, end) in matches.iter().rev() {
+                let start = char_position_to_byte_position(text, *start);
+                let end = char_position_to_byte_position(text, *end);
+                text.replace_range(start..end, replacement);
+            }
+            count += matches.len();
+            last_changed = Some(i);
+        }
+        if let Some(last_changed) = last_changed {
+            let before = before[..=last_changed].to_vec();
+            let after = after[..=last_changed].to_vec();
+            self.seal_undo_step();
+            self.replace_rows(row, before.len(), &after);
+            self.record_edit(EditKind::ReplaceRows, row, col, before, after.len());
+            self.seal_undo_step();
+        }
+        count
+    }
+
+    // Replaces needle everywhere it appears in the given rows, all in one
+    // undo step, and says how many there were
+    pub fn replace_in_rows(&mut self, rows: &[usize], needle: &str, replacement: &str) -> usize {
+        let (first, last) = match (rows.iter().min(), rows.iter().max()) {
+            (Some(first), Some(last)) if *last < self.num_lines() && !needle.is_empty() => {
+                (*first, *last)
+            }
+            _ => return 0,
+        };
+        let before = self.row_texts(first, last - first + 1);
+        let mut after = before.clone();
+        let mut count = 0;
+        for row in rows {
+            let text = &mut after[row - first];
+            count += text.matches(needle).count();
+            *text = text.replace(needle, replacement);
+        }
+        if count > 0 {
+            self.seal_undo_step();
+            self.replace_rows(first, before.len(), &after);
+            self.record_edit(EditKind::ReplaceRows, first, 0, before, after.len());
+            self.seal_undo_step();
+        }
+        count
+    }
+
+    // The rows a command filters: the ones the selection's on, or else all
+    // of them. A selection ending at the start of a row leaves that row out.
+    pub fn filter_rows(&self) -> Range<usize> {
+        let selection = match self.selection {
+            Some(selection) if !selection.is_empty() => selection,
+            _ => return 0..self.num_lines(),
+        };
+        let (start, end) = (selection.start(), selection.end());
+        let first = start.text_row().max(0) as usize;
+        let last = end.text_row().max(0) as usize;
+        let last = if last > first && end.text_col() == 0 {
+            last
+        } else {
+            last + 1
+        };
+        first..last.min(self.num_lines())
+    }
+
+    // The text of rows, newlines and all
+    pub fn rows_text(&self, rows: Range<usize>) -> String {
+        self.row_texts(rows.start, rows.len()).concat()
+    }
+
+    // Swaps rows for the lines of text, in their own undo step, keeping to
+    // the buffer's newlines. The cursor goes to the first of them.
+    pub fn replace_rows_with_text(&mut self, rows: Range<usize>, text: &str) {
+        let rows = rows.start.min(self.num_lines())..rows.end.min(self.num_lines());
+        let before = self.row_texts(rows.start, rows.len());
+        let ends_with_newline = before
+            .last()
+            .map(|row| row.ends_with(UNIX_NEWLINE))
+            .unwrap_or(true);
+        let mut after = text
+            .lines()
+            .map(|line| format!("{}{}", line, self.newline_or_default()))
+            .collect::<Vec<_>>();
+        if !ends_with_newline {
+            if let Some(last) = after.last_mut() {
+                last.truncate(last.len() - self.newline_or_default().len());
+            }
+        }
+        self.clear_selection();
+        self.seal_undo_step();
+        self.replace_rows(rows.start, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, rows.start, 0, before, after.len());
+        self.seal_undo_step();
+        let row = rows.start.min(self.num_lines().saturating_sub(1));
+        self.move_cursor_to(Cursor::new(row as i32, 0));
+    }
+
+    // All of the text, as it would be saved if it were UTF-8
+    pub fn contents(&self) -> String {
+        self.rows.iter().map(Row::as_str).collect()
+    }
+
+    pub fn set_syntax(&mut self) {
+        let tab_width = self.indentation().tab_width;
+        for row in self.rows.iter_mut() {
+            row.set_tab_width(tab_width);
+            row.set_syntax(Rc::downgrade(&self.syntax));
+        }
+        self.update();
+    }
+
+    // A buffer showing text that isn't from a file, so there's nothing to save
+    pub fn with_text(text: &str) -> Self {
+        let mut buffer = Buffer::default();
+        for line in text.lines() {
+            buffer.append_row(&format!("{}{}", line, DEFAULT_NEWLINE_STR));
+        }
+        buffer.dirty = 0;
+        buffer
+    }
+
+    // What a command printed, to look at rather than save
+    pub fn with_output(text: &str) -> Self {
+        let mut buffer = Buffer::with_text(text);
+        buffer.read_only = true;
+        buffer
+    }
+
+    // An empty list for the hits of search number id to be added to
+    pub fn with_results(id: usize) -> Self {
+        let mut buffer = Buffer::with_output("");
+        buffer.results = Some(id);
+        buffer
+    }
+
+    pub fn results(&self) -> Option<usize> {
+        self.results
+    }
+
+    // Adding hits as they're found isn't an edit, so there's nothing to save
+    pub fn append_results(&mut self, lines: &[String]) {
+        let dirty = self.dirty;
+        for line in lines {
+            self.append_row(&format!("{}{}", line, DEFAULT_NEWLINE_STR));
+        }
+        self.dirty = dirty;
+    }
+
+    pub fn append_row(&mut self, text: &str) {
+        let at = self.num_lines();
+        self.insert_row(at, text);
+        if at == 0 {
+            self.update_newline();
+        }
+    }
+
+    pub fn insert_newline(&mut self, row: usize, col: usize) -> i32 {
+        let before = self.row_texts(row, 1);
+        let after_count = before.len() + 1;
+        let indent = self.split_row(row, col);
+        self.record_edit(EditKind::InsertNewline, row, col, before, after_count);
+        indent
+    }
+
+    fn split_row(&mut self, row: usize, col: usize) -> i32 {
+        let newline = self
+            .rows
+            .get(row)
+            .map(|r| r.newline())
+            .unwrap_or_else(|| DEFAULT_NEWLINE.to_string());
+        if col == 0 {
+            self.insert_row(row, &newline);
+            0
+        } else {
+            let new_line_text = self.rows[row].truncate(col);
+            let (indent, text) = if self.syntax.is_some() {
+                let text = new_line_text.trim_start_matches([' ', '\t']);
+                (self.indent_for(row + 1, text), text)
+            } else {
+                (String::new(), new_line_text.as_str())
+            };
+            self.insert_row(row + 1, &format!("{}{}", indent, text));
+            self.track_replaced_rows(row, 2, 2);
+            self.update_from(row);
+            self.update_from(row + 1);
+            indent.chars().count() as i32
+        }
+    }
+
+    // A level of indentation: a tab, or spaces
+    pub fn indent_unit(&self) -> String {
+        let indentation = self.indentation();
+        if indentation.expand_tabs {
+            " ".repeat(indentation.indent_width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    // How a line at row starting with text should be indented, going by
+    // the nearest line above with anything on it: as far in as that, a
+    // level further after one that opens a block, and a level out for a
+    // line that closes one
+    fn indent_for(&self, row: usize, text: &str) -> String {
+        let syntax = match *self.syntax {
+            Some(syntax) => syntax,
+            None => return String::new(),
+        };
+        let above = (0..row.min(self.num_lines())).rev().find(|at| {
+            !self.rows[*at]
+                .as_str()
+                .trim_end_matches(['\r', '\n'])
+                .is_empty()
+        });
+        let mut indent = above
+            .map(|at| self.rows[at].indent_text().to_string())
+            .unwrap_or_default();
+        let unit = self.indent_unit();
+        if above.is_some_and(|at| syntax.indents_after(&self.code_in_row(at))) {
+            indent.push_str(&unit);
+        }
+        if syntax.dedent_start(text).is_some() {
+            if indent.ends_with(&unit) {
+                indent.truncate(indent.len() - unit.len());
+            } else {
+                indent.pop();
+            }
+        }
+        indent
+    }
+
+    // The row without its comments, to see whether it opens a block
+    fn code_in_row(&self, row: usize) -> String {
+        self.rows[row]
+            .chars_with_highlights()
+            .into_iter()
+            .filter(|(_, hl)| *hl != Highlight::Comment && *hl != Highlight::MultilineComment)
+            .map(|(c, _)| c)
+            .collect()
+    }
+
+    // Swaps the row's indentation for indent, keeping the cursor with its
+    // text if it's on the row
+    fn reindent_row(&mut self, row: usize, indent: &str) {
+        let before = self.row_texts(row, 1);
+        let line = match before.first() {
+            Some(line) => line,
+            None => return,
+        };
+        let text = line.trim_start_matches([' ', '\t']);
+        let old_len = (line.len() - text.len()) as i32;
+        let after = vec![format!("{}{}", indent, text)];
+        let moved = indent.chars().count() as i32 - old_len;
+        self.replace_rows(row, 1, &after);
+        self.record_edit(EditKind::ReplaceRows, row, 0, before, 1);
+        for at in self.auto_closed.iter_mut() {
+            if at.0 == row {
+                at.1 = (at.1 as i32 + moved).max(0) as usize;
+            }
+        }
+        if self.cursor.text_row() == row as i32 {
+            self.cursor
+                .change(|cursor| cursor.text_col = (cursor.text_col + moved).max(0));
+        }
+    }
+
+    // Typing the end of what closes a block, like a "}" at the start of a
+    // line, takes the line out a level
+    fn dedent_typed(&mut self, row: usize, col: usize) {
+        let syntax = match (*self.syntax, self.rows.get(row)) {
+            (Some(syntax), Some(_)) => syntax,
+            _ => return,
+        };
+        let line = self.rows[row].as_str();
+        let indent = self.rows[row].indent_text();
+        let typed = line
+            .chars()
+            .take(col)
+            .skip(indent.chars().count())
+            .collect::<String>();
+        if syntax.dedent_start(&typed) != Some(typed.as_str()) {
+            return;
+        }
+        let wanted = self.indent_for(row, &typed);
+        if wanted.chars().count() < indent.chars().count() {
+            self.reindent_row(row, &wanted);
+        }
+    }
+
+    pub fn insert_newline_and_return(&mut self) {
+        let past_end = self.cursor_past_line_end();
+        if past_end > 0 {
+            // Splitting in virtual space leaves the line as it is, no padding
+            self.cursor.change(|cursor| cursor.text_col -= past_end);
+        }
+        let row = self.cursor.text_row() as usize;
+        let indent = self.insert_newline(row, self.cursor.text_col() as usize);
+        self.cursor.change(|cursor| {
+            cursor.text_row += 1;
+            cursor.text_col = indent;
+        });
+        // Return between a block's brackets leaves the closing one on a line
+        // of its own, with an indented line between them for the cursor
+        let closes = self.syntax.is_some_and(|syntax| {
+            let moved = self.rows[row + 1].as_str().trim_start_matches([' ', '\t']);
+            syntax.indents_after(&self.code_in_row(row)) && syntax.dedent_start(moved).is_some()
+        });
+        if closes {
+            self.insert_newline(row + 1, 0);
+            let inner = self.indent_for(row + 1, "");
+            self.reindent_row(row + 1, &inner);
+            self.cursor
+                .change(|cursor| cursor.text_col = inner.chars().count() as i32);
+        }
+    }
+
+    pub fn join_row(&mut self, at: usize) -> bool {
+        if at > 0 && at < self.num_lines() {
+            let before = self.row_texts(at - 1, 2);
+            let col = self.line_len(at as i32 - 1).unwrap_or(0);
+            let row = self.rows.remove(at);
+            if let Some(previous_row) = self.rows.get_mut(at - 1) {
+                previous_row.append_text(row.as_str());
+            }
+            self.track_replaced_rows(at - 1, 2, 1);
+            self.mark_dirty();
+            self.update_from(at - 1);
+            self.record_edit(EditKind::JoinRow, at - 1, col, before, 1);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn delete_char(&mut self, x: i32, y: i32) {
+        let before = self.row_texts(y as usize, 1);
+        self.rows[y as usize].delete_char((x - 1) as usize);
+        let (row, col) = (y as usize, x as usize - 1);
+        self.auto_closed.retain(|at| *at != (row, col));
+        for at in self.auto_closed.iter_mut() {
+            if at.0 == row && at.1 > col {
+                at.1 -= 1;
+            }
+        }
+        self.track_replaced_rows(y as usize, 1, 1);
+        self.update_from(y as usize);
+        self.record_edit(EditKind::DeleteChar, y as usize, x as usize, before, 1);
+    }
+
+    pub fn delete_char_at_cursor(&mut self) {
+        let num_rows = self.num_lines() as i32;
+        if self.cursor.text_row() >= num_rows {
+            return;
+        }
+        if self.cursor_past_line_end() > 0 {
+            // Nothing to delete in virtual space, just move back towards the text
+            self.cursor.change(|cursor| cursor.text_col -= 1);
+            return;
+        }
+        if self.cursor.text_col() > 0 {
+            self.delete_char(self.cursor.text_col(), self.cursor.text_row());
+            self.cursor.change(|cursor| cursor.text_col -= 1);
+            self.mark_dirty();
+        } else if self.cursor.text_row() > 0 && self.cursor.text_col() == 0 {
+            let at = self.cursor.text_row();
+            let new_col = self.line_len(at - 1).unwrap_or(0) as i32;
+            self.join_row(at as usize);
+            self.cursor.change(|cursor| {
+                cursor.text_col = new_col;
+                cursor.text_row -= 1;
+            });
+        }
+    }
+
+    pub fn insert_char(&mut self, character: char, cursor_x: i32, cursor_y: i32) {
+        if self.newline == "" {
+            self.update_newline();
+        }
+        let before = self.row_texts(cursor_y as usize, 1);
+        if cursor_y == self.rows.len() as i32 {
+            let tab_width = self.indentation().tab_width;
+            self.rows.push(Row::new(
+                self.newline,
+                Rc::downgrade(&self.syntax),
+                tab_width,
+            ));
+            self.track_replaced_rows(cursor_y as usize, 0, 1);
+        }
+        self.rows[cursor_y as usize].insert_char(cursor_x as usize, character);
+        for at in self.auto_closed.iter_mut() {
+            if at.0 == cursor_y as usize && at.1 >= cursor_x as usize {
+                at.1 += 1;
+            }
+        }
+        self.track_replaced_rows(cursor_y as usize, 1, 1);
+        self.mark_dirty();
+        self.update_from(cursor_y as usize);
+        let (row, col) = (cursor_y as usize, cursor_x as usize);
+        self.record_edit(EditKind::InsertChar, row, col, before, 1);
+    }
+
+    pub fn insert_char_at_cursor(&mut self, character: char) {
+        self.pad_to_cursor();
+        self.insert_char(character, self.cursor.text_col(), self.cursor.text_row());
+        self.cursor.change(|cursor| cursor.text_col += 1);
+    }
+
+    // What the Tab key does: a tab character, or spaces out to the next indent
+    pub fn insert_indent_at_cursor(&mut self) {
+        let indentation = self.indentation();
+        if !indentation.expand_tabs {
+            self.insert_char_at_cursor('\t');
+            return;
+        }
+        let render_col = self.text_cursor_to_render(self.cursor.text_col(), self.cursor.text_row());
+        let spaces = indentation.indent_width - render_col as usize % indentation.indent_width;
+        for _ in 0..spaces {
+            self.insert_char_at_cursor(' ');
+        }
+    }
+
+    // Inserts a character the way typing it does, closing brackets and
+    // quotes when auto_close is on
+    pub fn type_char_at_cursor(&mut self, character: char) {
+        if self.auto_close && self.skip_auto_closed(character) {
+            return;
+        }
+        self.pad_to_cursor();
+        let (row, col) = (self.cursor.text_row(), self.cursor.text_col());
+        self.insert_char(character, col, row);
+        self.cursor.change(|cursor| cursor.text_col += 1);
+        self.dedent_typed(row as usize, col as usize + 1);
+        let col = self.cursor.text_col() - 1;
+        if !self.auto_close {
+            return;
+        }
+        if let Some(closer) = self.closer_for(character, row as usize, col as usize) {
+            self.insert_char(closer, col + 1, row);
+            self.auto_closed.push((row as usize, col as usize + 1));
+        }
+    }
+
+    // Typing the closer that was put in for us steps over it instead
+    fn skip_auto_closed(&mut self, character: char) -> bool {
+        let rows = &self.rows;
+        let char_at =
+            |(row, col): (usize, usize)| rows.get(row).and_then(|at| at.as_str().chars().nth(col));
+        // Other edits can leave these pointing at something else
+        self.auto_closed
+            .retain(|at| char_at(*at).is_some_and(is_closer));
+        let at = (
+            self.cursor.text_row() as usize,
+            self.cursor.text_col() as usize,
+        );
+        match self.auto_closed.iter().position(|closed| *closed == at) {
+            Some(idx) if char_at(at) == Some(character) => {
+                self.auto_closed.remove(idx);
+                self.cursor.change(|cursor| cursor.text_col += 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // What the bracket or quote just typed at col should be closed with.
+    // Nothing is closed inside strings and comments, or right before a
+    // word, and quotes after a word are apostrophes (or lifetimes).
+    fn closer_for(&self, character: char, row: usize, col: usize) -> Option<char> {
+        let chars = self.rows.get(row)?.chars_with_highlights();
+        if let Some((next, _)) = chars.get(col + 1) {
+            if !next.is_whitespace() && !is_closer(*next) {
+                return None;
+            }
+        }
+        if let Some((_, close, true)) = bracket_pair(character) {
+            let (_, hl) = chars.get(col)?;
+            return Some(close).filter(|_| is_in_code(*hl));
+        }
+        if !QUOTES.contains(&character) {
+            return None;
+        }
+        match col.checked_sub(1).and_then(|prev| chars.get(prev)) {
+            Some((prev, _)) if is_word_char(*prev) => None,
+            Some(('&', _)) | Some(('<', _)) if character == '\'' => None,
+            Some((_, hl)) if !is_in_code(*hl) => None,
+            None if row > 0 && self.rows[row - 1].hl_open_comment => None,
+            _ => Some(character),
+        }
+    }
+
+    pub fn insert_text_at_cursor(&mut self, text: &str) {
+        for character in text.chars() {
+            match character {
+                '\r' => {}
+                '\n' => {
+                    let row = self.cursor.text_row() as usize;
+                    let col = self.cursor.text_col() as usize;
+                    self.insert_newline(row, col);
+                    self.cursor.change(|cursor| {
+                        cursor.text_row += 1;
+                        cursor.text_col = 0;
+                    });
+                }
+                _ => self.insert_char_at_cursor(character),
+            }
+        }
+    }
+
+    // Puts pasted text in at the cursor all at once, as one undo step and
+    // one pass of highlighting, without auto-indenting or closing brackets
+    // the way typing it in would
+    pub fn paste_at_cursor(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.newline.is_empty() {
+            self.update_newline();
+        }
+        self.pad_to_cursor();
+        let row = self.cursor.text_row() as usize;
+        let col = self.cursor.text_col() as usize;
+        let before = self.row_texts(row, 1);
+        // Past the last row there's a new one to paste into
+        let line = before
+            .first()
+            .cloned()
+            .unwrap_or_else(|| self.newline.to_string());
+        let (head, tail) = line.split_at(char_position_to_byte_position(&line, col));
+        let line_end = &tail[tail.trim_end_matches(['\r', '\n']).len()..];
+        let tail = &tail[..tail.len() - line_end.len()];
+        let text = text.replace('\r', "");
+        let combined = format!("{}{}{}", head, text, tail);
+        let mut after = combined
+            .split('\n')
+            .map(|line| format!("{}{}", line, self.newline))
+            .collect::<Vec<_>>();
+        if let Some(last) = after.last_mut() {
+            last.truncate(last.len() - self.newline.len());
+            last.push_str(line_end);
+        }
+
+        self.seal_undo_step();
+        self.replace_rows(row, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, row, col, before, after.len());
+        self.seal_undo_step();
+        let (new_row, new_col) = match text.rfind('\n') {
+            Some(at) => (
+                row + text.matches('\n').count(),
+                text[at + 1..].chars().count(),
+            ),
+            None => (row, col + text.chars().count()),
+        };
+        self.cursor.move_to(new_row as i32, new_col as i32);
+    }
+
+    // Deletes forwards, joining the next row on at the end of a line
+    pub fn delete_char_forward_at_cursor(&mut self) {
+        let row = self.cursor.text_row();
+        let row_len = self.line_len(row).unwrap_or(0) as i32;
+        if self.cursor.text_col() >= row_len && self.virtual_edit {
+            self.join_next_row_at_cursor();
+        } else if self.cursor.text_col() < row_len {
+            self.cursor.change(|cursor| cursor.text_col += 1);
+            self.delete_char_at_cursor();
+        } else if row + 1 < self.num_lines() as i32 {
+            self.cursor.change(|cursor| {
+                cursor.text_row += 1;
+                cursor.text_col = 0;
+            });
+            self.delete_char_at_cursor();
+        }
+    }
+
+    // Where moving a word from (row, col) ends up: right to the end of the
+    // next word, or left to the start of the last one. A run of punctuation
+    // counts as a word, and the ends of lines as space.
+    pub fn word_boundary(&self, row: i32, col: i32, direction: Direction) -> (i32, i32) {
+        let row_chars = |row: i32| -> Vec<char> {
+            let len = self.line_len(row).unwrap_or(0);
+            self.rows[row as usize].as_str().chars().take(len).collect()
+        };
+        if self.line_len(row).is_none() {
+            return (row, col);
+        }
+        let mut row = row;
+        let mut chars = row_chars(row);
+        let mut col = (col.max(0) as usize).min(chars.len());
+        if direction == Direction::Left {
+            loop {
+                if col == 0 {
+                    if row == 0 {
+                        return (0, 0);
+                    }
+                    row -= 1;
+                    chars = row_chars(row);
+                    col = chars.len();
+                } else if char_class(chars[col - 1]) == CharClass::Space {
+                    col -= 1;
+                } else {
+                    break;
+                }
+            }
+            let class = char_class(chars[col - 1]);
+            while col > 0 && char_class(chars[col - 1]) == class {
+                col -= 1;
+            }
+        } else {
+            let last_row = self.num_lines() as i32 - 1;
+            loop {
+                if col == chars.len() {
+                    if row == last_row {
+                        return (row, col as i32);
+                    }
+                    row += 1;
+                    chars = row_chars(row);
+                    col = 0;
+                } else if char_class(chars[col]) == CharClass::Space {
+                    col += 1;
+                } else {
+                    break;
+                }
+            }
+            let class = char_class(chars[col]);
+            while col < chars.len() && char_class(chars[col]) == class {
+                col += 1;
+            }
+        }
+        (row, col as i32)
+    }
+
+    // Where the word the cursor's at the end of (or in the middle of)
+    // starts, and the part of it before the cursor
+    pub fn word_before_cursor(&self) -> (usize, String) {
+        let row = self.cursor.text_row();
+        let chars = self.row_chars(row);
+        let col = (self.cursor.text_col().max(0) as usize).min(chars.len());
+        let start = col
+            - chars[..col]
+                .iter()
+                .rev()
+                .take_while(|c| is_word_char(**c))
+                .count();
+        (start, chars[start..col].iter().collect())
+    }
+
+    // Swaps the whole word at the cursor, after it as well as before, for
+    // word, leaving the cursor at its end
+    pub fn complete_word_at_cursor(&mut self, word: &str) {
+        let row = self.cursor.text_row();
+        let chars = self.row_chars(row);
+        let (start, _) = self.word_before_cursor();
+        let col = (self.cursor.text_col().max(0) as usize).min(chars.len());
+        let end = col
+            + chars[col..]
+                .iter()
+                .take_while(|c| is_word_char(**c))
+                .count();
+        self.replace_text(row.max(0) as usize, start, end, word);
+        self.cursor
+            .move_to(row, (start + word.chars().count()) as i32);
+    }
+
+    // Swaps what's on the cursor's row from start up to the cursor for text,
+    // which can run over several lines, in one undo step. Leaves the cursor
+    // at start.
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        let row = self.cursor.text_row().max(0) as usize;
+        let chars = self.row_chars(row as i32);
+        let col = (self.cursor.text_col().max(0) as usize).min(chars.len());
+        let start = start.min(col);
+        let before = self.row_texts(row, 1);
+        let line_end = before
+            .first()
+            .map(|line| &line[line.trim_end_matches(['\r', '\n']).len()..])
+            .unwrap_or_default()
+            .to_string();
+        let newline = self.newline_or_default();
+        let combined = format!(
+            "{}{}{}",
+            chars[..start].iter().collect::<String>(),
+            text.replace('\r', ""),
+            chars[col..].iter().collect::<String>()
+        );
+        let mut after = combined
+            .split('\n')
+            .map(|line| format!("{}{}", line, newline))
+            .collect::<Vec<_>>();
+        if let Some(last) = after.last_mut() {
+            last.truncate(last.len() - newline.len());
+            last.push_str(&line_end);
+        }
+        self.seal_undo_step();
+        self.replace_rows(row, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, row, start, before, after.len());
+        self.seal_undo_step();
+        self.move_cursor_to(Cursor::new(row as i32, start as i32));
+    }
+
+    // The filetype's keywords, to complete words with
+    pub fn keywords(&self) -> Vec<&'a str> {
+        self.syntax
+            .map(|syntax| syntax.all_keywords())
+            .unwrap_or_default()
+    }
+
+    fn row_chars(&self, row: i32) -> Vec<char> {
+        let len = self.line_len(row).unwrap_or(0);
+        self.rows
+            .get(row.max(0) as usize)
+            .map(|line| line.as_str().chars().take(len).collect())
+            .unwrap_or_default()
+    }
+
+    // Deletes from the cursor to wherever moving a word would take it
+    pub fn delete_word_at_cursor(&mut self, direction: Direction) {
+        let row = self.cursor.text_row();
+        let col = match self.line_len(row) {
+            Some(len) => self.cursor.text_col().min(len as i32),
+            None => return,
+        };
+        let to = self.word_boundary(row, col, direction);
+        let ((start_row, start_col), (end_row, end_col)) = if direction == Direction::Left {
+            (to, (row, col))
+        } else {
+            ((row, col), to)
+        };
+        if (start_row, start_col) == (end_row, end_col) {
+            return;
+        }
+        let before = self.row_texts(start_row as usize, (end_row - start_row) as usize + 1);
+        let (first, last) = match (before.first(), before.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        let after = format!(
+            "{}{}",
+            &first[..char_position_to_byte_position(first, start_col as usize)],
+            &last[char_position_to_byte_position(last, end_col as usize)..]
+        );
+        self.replace_rows(start_row as usize, before.len(), &[after]);
+        self.record_edit(
+            EditKind::ReplaceRows,
+            start_row as usize,
+            start_col as usize,
+            before,
+            1,
+        );
+        self.cursor.change(|cursor| {
+            cursor.text_row = start_row;
+            cursor.text_col = start_col;
+        });
+    }
+
+    // Comments out the rows from first_row to last_row with the syntax's line
+    // comments, or takes them out if every row is already commented. The
+    // comment markers line up with the least indented row, and blank rows
+    // are left alone. Without line comments nothing happens.
+    pub fn toggle_comment(&mut self, first_row: usize, last_row: usize) -> bool {
+        let marker = match *self.syntax {
+            Some(syntax) if !syntax.singleline_comment_start.is_empty() => {
+                syntax.singleline_comment_start
+            }
+            _ => return false,
+        };
+        if first_row >= self.num_lines() || last_row < first_row {
+            return false;
+        }
+        let before = self.row_texts(first_row, last_row - first_row + 1);
+        let indent_len = |text: &str| text.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+        let non_blank = || before.iter().filter(|text| !text.trim().is_empty());
+        let uncomment = non_blank().all(|text| text.trim_start().starts_with(marker));
+        let indent = match non_blank().map(|text| indent_len(text)).min() {
+            Some(indent) => indent,
+            None => return false,
+        };
+        let cursor_row = self.cursor.text_row() as usize;
+        let mut cursor_col = self.cursor.text_col();
+        let after: Vec<String> = before
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                if text.trim().is_empty() {
+                    return text.clone();
+                }
+                let (at, removed, added) = if uncomment {
+                    let at = indent_len(text);
+                    let rest = &text[char_position_to_byte_position(text, at) + marker.len()..];
+                    let removed = marker.chars().count() + rest.starts_with(' ') as usize;
+                    (at, removed, "")
+                } else {
+                    (indent, 0, marker)
+                };
+                let at_byte = char_position_to_byte_position(text, at);
+                let after_byte = char_position_to_byte_position(text, at + removed);
+                let added = if added.is_empty() {
+                    String::new()
+                } else {
+                    format!("{} ", added)
+                };
+                if first_row + i == cursor_row && cursor_col >= at as i32 {
+                    let shift = added.chars().count() as i32 - removed as i32;
+                    cursor_col = (cursor_col + shift).max(at as i32);
+                }
+                format!("{}{}{}", &text[..at_byte], added, &text[after_byte..])
+            })
+            .collect();
+        self.replace_rows(first_row, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, first_row, 0, before, after.len());
+        self.cursor.change(|cursor| cursor.text_col = cursor_col);
+        true
+    }
+
+    // Puts a copy of the rows from first_row to last_row under them, and
+    // moves the cursor and selection down onto the copy
+    pub fn duplicate_rows(&mut self, first_row: usize, last_row: usize) -> bool {
+        if last_row < first_row || last_row >= self.num_lines() {
+            return false;
+        }
+        let before = self.row_texts(first_row, last_row - first_row + 1);
+        let mut after = before.clone();
+        // The last row of the file might not have a newline to copy
+        if let Some(last) = after.last_mut() {
+            if split_line_end(last).1.is_empty() {
+                last.push_str(self.newline);
+            }
+        }
+        after.extend(before.iter().cloned());
+        self.replace_rows(first_row, before.len(), &after);
+        let count = before.len();
+        self.record_edit(EditKind::ReplaceRows, first_row, 0, before, after.len());
+        self.shift_cursor_rows(count as i32);
+        true
+    }
+
+    // Swaps the rows from first_row to last_row with the row above or below
+    // them, taking the cursor and selection along. Line endings stay where
+    // they were, so the last row of the file keeps not having one.
+    pub fn move_rows(&mut self, first_row: usize, last_row: usize, direction: Direction) -> bool {
+        if last_row < first_row || last_row >= self.num_lines() {
+            return false;
+        }
+        let (at, shift) = match direction {
+            Direction::Up if first_row > 0 => (first_row - 1, -1),
+            Direction::Down if last_row + 1 < self.num_lines() => (first_row, 1),
+            _ => return false,
+        };
+        let before = self.row_texts(at, last_row - first_row + 2);
+        let mut lines: Vec<&str> = before.iter().map(|text| split_line_end(text).0).collect();
+        if shift < 0 {
+            lines.rotate_left(1);
+        } else {
+            lines.rotate_right(1);
+        }
+        let after: Vec<String> = lines
+            .iter()
+            .zip(before.iter())
+            .map(|(line, text)| format!("{}{}", line, split_line_end(text).1))
+            .collect();
+        self.replace_rows(at, before.len(), &after);
+        self.record_edit(EditKind::ReplaceRows, at, 0, before, after.len());
+        self.shift_cursor_rows(shift);
+        true
+    }
+
+    // Joins the rows from first_row to last_row into one, or first_row and
+    // the row after it when they're the same row. Where each pair meets the
+    // whitespace is collapsed to a single space, or none beside an empty
+    // row, and the cursor's left at the last join.
+    pub fn join_rows(&mut self, first_row: usize, last_row: usize) -> bool {
+        let last_row = last_row.max(first_row + 1);
+        if last_row >= self.num_lines() {
+            return false;
+        }
+        let before = self.row_texts(first_row, last_row - first_row + 1);
+        let mut joined = split_line_end(&before[0]).0.to_string();
+        let mut col = 0;
+        for text in &before[1..] {
+            let rest = split_line_end(text).0.trim_start();
+            joined.truncate(joined.trim_end().len());
+            col = joined.chars().count();
+            if !joined.is_empty() && !rest.is_empty() {
+                joined.push(' ');
+            }
+            joined.push_str(rest);
+        }
+        let line_end = before.last().map_or("", |text| split_line_end(text).1);
+        joined.push_str(line_end);
+        self.replace_rows(first_row, before.len(), &[joined]);
+        self.record_edit(EditKind::ReplaceRows, first_row, 0, before, 1);
+        self.cursor.change(|cursor| {
+            cursor.text_row = first_row as i32;
+            cursor.text_col = col as i32;
+        });
+        true
+    }
+
+    // Changes the case of the selection, or else the word at the cursor
+    pub fn change_case(&mut self, case: Case) -> bool {
+        match self.selection.filter(|selection| !selection.is_empty()) {
+            Some(selection) if selection.is_block() => {
+                let (first_col, last_col) = selection.block_cols();
+                let (first_row, last_row) = (selection.start().text_row, selection.end().text_row);
+                self.change_case_in(first_row, last_row, |_| (first_col, last_col), case)
+            }
+            Some(selection) => {
+                let (start, end) = (selection.start(), selection.end());
+                self.change_case_in(
+                    start.text_row,
+                    end.text_row,
+                    |row| {
+                        let from = if row == start.text_row {
+                            start.text_col
+                        } else {
+                            0
+                        };
+                        let to = if row == end.text_row {
+                            end.text_col
+                        } else {
+                            i32::MAX
+                        };
+                        (from, to)
+                    },
+                    case,
+                )
+            }
+            None => {
+                let row = self.cursor.text_row();
+                match self.word_at(row, self.cursor.text_col()) {
+                    Some((start, end)) => {
+                        self.change_case_in(row, row, |_| (start as i32, end as i32), case)
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+
+    // Changes the case of the columns cols gives for each row. Some letters
+    // change length, like ß going to SS, so whatever's after the change on
+    // the last row is moved along to stay with its text.
+    fn change_case_in(
+        &mut self,
+        first_row: i32,
+        last_row: i32,
+        cols: impl Fn(i32) -> (i32, i32),
+        case: Case,
+    ) -> bool {
+        if first_row < 0 || last_row < first_row || last_row as usize >= self.num_lines() {
+            return false;
+        }
+        let before = self.row_texts(first_row as usize, (last_row - first_row + 1) as usize);
+        let mut shift = (0, 0);
+        let after: Vec<String> = before
+            .iter()
+            .zip(first_row..)
+            .map(|(text, row)| {
+                let (line, line_end) = split_line_end(text);
+                let chars: Vec<char> = line.chars().collect();
+                let (from, to) = cols(row);
+                let to = (to.max(0) as usize).min(chars.len());
+                let from = (from.max(0) as usize).min(to);
+                let in_word = from > 0 && is_word_char(chars[from - 1]);
+                let changed = change_case_of(&chars[from..to], case, in_word);
+                if row == last_row {
+                    shift = (
+                        to as i32,
+                        changed.chars().count() as i32 - (to - from) as i32,
+                    );
+                }
+                let head: String = chars[..from].iter().collect();
+                let tail: String = chars[to..].iter().collect();
+                format!("{}{}{}{}", head, changed, tail, line_end)
+            })
+            .collect();
+        if after == before {
+            return false;
+        }
+        self.replace_rows(first_row as usize, before.len(), &after);
+        self.record_edit(
+            EditKind::ReplaceRows,
+            first_row as usize,
+            0,
+            before,
+            after.len(),
+        );
+        let (col, cols) = shift;
+        if cols != 0 {
+            self.cursor.change(|cursor| {
+                if cursor.text_row == last_row && cursor.text_col >= col {
+                    cursor.text_col += cols;
+                }
+            });
+            if let Some(selection) = self.selection.as_mut() {
+                selection.shift_cols(last_row, col, cols);
+            }
+        }
+        true
+    }
+
+    // Adds amount to the number at or after the cursor on its row, and
+    // leaves the cursor on the number's last digit
+    pub fn increment_number(&mut self, amount: i64) -> bool {
+        let row = self.cursor.text_row() as usize;
+        let col = self.cursor.text_col().max(0) as usize;
+        let (text, last_col) = match self
+            .rows
+            .get(row)
+            .and_then(|row| row.add_to_number(col, amount))
+        {
+            Some(changed) => changed,
+            None => return false,
+        };
+        let before = self.row_texts(row, 1);
+        self.replace_rows(row, 1, &[text]);
+        self.record_edit(EditKind::ReplaceRows, row, 0, before, 1);
+        self.cursor
+            .change(|cursor| cursor.text_col = last_col as i32);
+        true
+    }
+
+    fn shift_cursor_rows(&mut self, rows: i32) {
+        self.cursor.change(|cursor| cursor.text_row += rows);
+        if let Some(selection) = self.selection.as_mut() {
+            selection.shift_rows(rows);
+        }
+    }
+
+    // Where the bracket matching the one at col (or failing that, just
+    // before it) is, and where that one is. Brackets in strings and
+    // comments only match each other, so they don't throw the code off.
+    pub fn matching_bracket(
+        &self,
+        row: usize,
+        col: usize,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let chars = self.rows.get(row)?.chars_with_highlights();
+        let (col, bracket, hl) =
+            [Some(col), col.checked_sub(1)]
+                .iter()
+                .flatten()
+                .find_map(|col| match chars.get(*col) {
+                    Some((c, hl)) if bracket_pair(*c).is_some() => Some((*col, *c, *hl)),
+                    _ => None,
+                })?;
+        let (open, close, forwards) = bracket_pair(bracket)?;
+        let in_code = is_in_code(hl);
+        let (same, other) = if forwards {
+            (open, close)
+        } else {
+            (close, open)
+        };
+        let mut depth = 0;
+        for step in 0..MAX_BRACKET_ROWS {
+            let at_row = if forwards {
+                row + step
+            } else {
+                row.checked_sub(step)?
+            };
+            let row_chars = match self.rows.get(at_row) {
+                Some(_) if step == 0 => chars.clone(),
+                Some(at) => at.chars_with_highlights(),
+                None => return None,
+            };
+            let cols: Box<dyn Iterator<Item = usize>> = match (forwards, step) {
+                (true, 0) => Box::new(col..row_chars.len()),
+                (true, _) => Box::new(0..row_chars.len()),
+                (false, 0) => Box::new((0..=col).rev()),
+                (false, _) => Box::new((0..row_chars.len()).rev()),
+            };
+            for at_col in cols {
+                let (c, hl) = row_chars[at_col];
+                if is_in_code(hl) != in_code {
+                    continue;
+                }
+                if c == same {
+                    depth += 1;
+                } else if c == other {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(((row, col), (at_row, at_col)));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Highlights the bracket at the cursor and the one it matches, saying
+    // whether that changed what's shown
+    pub fn update_matching_bracket(&mut self) -> bool {
+        let matched = self
+            .matching_bracket(
+                self.cursor.text_row() as usize,
+                self.cursor.text_col() as usize,
+            )
+            .map(|(at, other)| vec![at, other])
+            .unwrap_or_default();
+        // Editing a row takes the overlay off it
+        let shown = self.matched_brackets.iter().all(|(row, col)| {
+            self.rows
+                .get(*row)
+                .is_some_and(|at| at.overlay_at(*col) == Some(Highlight::MatchingBracket))
+        });
+        if matched == self.matched_brackets && shown {
+            return false;
+        }
+        for (row, _) in self.matched_brackets.drain(..) {
+            if let Some(at) = self.rows.get_mut(row) {
+                at.clear_overlay_highlight(Highlight::MatchingBracket);
+            }
+        }
+        for (row, col) in matched.iter() {
+            self.rows[*row].set_overlay_at(*col, Highlight::MatchingBracket);
+        }
+        self.matched_brackets = matched;
+        true
+    }
+
+    pub fn extra_cursors(&self) -> &[Cursor] {
+        &self.extra_cursors
+    }
+
+    pub fn add_cursor(&mut self, row: i32, col: i32) {
+        let cursor = Cursor::new(row, col);
+        if !self.extra_cursors.contains(&cursor) {
+            self.extra_cursors.push(cursor);
+        }
+    }
+
+    pub fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+    }
+
+    // Makes an edit at the cursor and at every extra cursor. They're done
+    // from the bottom of the buffer up, so an edit only ever moves the
+    // cursors that have already had theirs. Counting those from the end of
+    // the buffer and the end of their row, they don't move at all.
+    pub fn at_every_cursor<F>(&mut self, mut edit: F)
+    where
+        F: FnMut(&mut Self),
+    {
+        if self.extra_cursors.is_empty() {
+            edit(self);
+            return;
+        }
+        let main = self.cursor.current();
+        let mut cursors = self.extra_cursors.clone();
+        cursors.push(main);
+        cursors.sort_by_key(|cursor| (cursor.text_row, cursor.text_col));
+        cursors.dedup();
+        let mut from_end = vec![];
+        self.undo.start_group();
+        for cursor in cursors.into_iter().rev() {
+            self.cursor
+                .move_to_without_history(cursor.text_row, cursor.text_col);
+            edit(self);
+            let row = self.cursor.text_row();
+            let row_len = self.line_len(row).unwrap_or(0) as i32;
+            from_end.push((
+                self.num_lines() as i32 - row,
+                row_len - self.cursor.text_col(),
+                cursor == main,
+            ));
+        }
+        self.undo.end_group();
+        self.extra_cursors.clear();
+        for (rows_from_end, cols_from_end, is_main) in from_end {
+            let row = self.num_lines() as i32 - rows_from_end;
+            let col = self.line_len(row).unwrap_or(0) as i32 - cols_from_end;
+            if is_main {
+                self.cursor.move_to_without_history(row, col);
+            } else {
+                self.add_cursor(row, col);
+            }
+        }
+        // Cursors that ran into each other are just the one now
+        let main = self.cursor.current();
+        self.extra_cursors.retain(|cursor| *cursor != main);
+    }
+
+    // The start and end columns of the word at (or just before) col
+    fn word_at(&self, row: i32, col: i32) -> Option<(usize, usize)> {
+        let row_len = self.line_len(row)?;
+        let chars = self.rows[row as usize]
+            .as_str()
+            .chars()
+            .take(row_len)
+            .collect::<Vec<_>>();
+        let is_word = |idx: usize| chars.get(idx).is_some_and(|c| is_word_char(*c));
+        let col = col.max(0) as usize;
+        let mut start = if is_word(col) {
+            col
+        } else if col > 0 && is_word(col - 1) {
+            col - 1
+        } else {
+            return None;
+        };
+        while start > 0 && is_word(start - 1) {
+            start -= 1;
+        }
+        let mut end = start;
+        while is_word(end) {
+            end += 1;
+        }
+        Some((start, end))
+    }
+
+    // Adds a cursor at the next place the word under the cursor turns up
+    // (after the last one added), at the same spot within the word
+    pub fn add_cursor_at_next_match(&mut self) -> bool {
+        let main = self.cursor.current();
+        let (start, end) = match self.word_at(main.text_row, main.text_col) {
+            Some(word) => word,
+            None => return false,
+        };
+        let word = self.rows[main.text_row as usize]
+            .as_str()
+            .chars()
+            .skip(start)
+            .take(end - start)
+            .collect::<String>();
+        let into_word = main.text_col as usize - start;
+        let from = self.extra_cursors.last().copied().unwrap_or(main);
+        let num_lines = self.num_lines();
+        // Round to the row it started from, for matches before it there
+        for step in 0..=num_lines {
+            let row = (from.text_row as usize + step) % num_lines;
+            for (match_start, match_end) in needle_matches(&self.rows[row], &word) {
+                let col = (match_start + into_word) as i32;
+                if step == 0 && col <= from.text_col {
+                    continue;
+                }
+                let cursor = Cursor::new(row as i32, col);
+                let whole_word =
+                    self.word_at(row as i32, match_start as i32) == Some((match_start, match_end));
+                if whole_word && cursor != main && !self.extra_cursors.contains(&cursor) {
+                    self.extra_cursors.push(cursor);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn check_cursor(&mut self) {
+        let current_cursor = self.cursor.current();
+        let mut new_cursor = self.cursor.current();
+        if new_cursor.text_row < 0 {
+            new_cursor.text_row = 0;
+        }
+
+        if new_cursor.text_row > self.num_lines() as i32 {
+            new_cursor.text_row = self.num_lines() as i32;
+        }
+
+        if new_cursor.text_col < 0 {
+            new_cursor.text_col = 0;
+        }
+
+        let row_len = self.line_len(new_cursor.text_row).unwrap_or(0);
+
+        if !self.virtual_edit && new_cursor.text_col > row_len as i32 {
+            new_cursor.text_col = row_len as i32;
+        }
+
+        if current_cursor != new_cursor {
+            self.cursor.change(|cursor| {
+                cursor.text_col = new_cursor.text_col();
+                cursor.text_row = new_cursor.text_row();
+            });
+        }
+    }
+}
+
+// Title case needs to know whether the text starts partway through a word
+// Filenames are kept whole, so they still mean the same file once the
+// working directory's changed
+fn absolute_filename(filename: &str) -> String {
+    paths::absolute_path(Path::new(filename))
+        .to_string_lossy()
+        .to_string()
+}
+
+fn change_case_of(chars: &[char], case: Case, mut in_word: bool) -> String {
+    let mut changed = String::new();
+    for c in chars {
+        match case {
+            Case::Upper => changed.extend(c.to_uppercase()),
+            Case::Lower => changed.extend(c.to_lowercase()),
+            Case::Title if in_word => changed.extend(c.to_lowercase()),
+            Case::Title => changed.extend(c.to_uppercase()),
+            Case::Toggle if c.is_lowercase() => changed.extend(c.to_uppercase()),
+            Case::Toggle => changed.extend(c.to_lowercase()),
+        }
+        in_word = is_word_char(*c);
+    }
+    changed
+}
+
+// A row's text and its line ending, which the last row might not have
+fn split_line_end(text: &str) -> (&str, &str) {
+    let line = text.trim_end_matches(['\r', '\n']);
+    (line, &text[line.len()..])
+}
+
+// The pair c is in, and whether it's the opening one
+fn bracket_pair(c: char) -> Option<(char, char, bool)> {
+    BRACKETS.iter().find_map(|(open, close)| {
+        if c == *open || c == *close {
+            Some((*open, *close, c == *open))
+        } else {
+            None
+        }
+    })
+}
+
+fn is_closer(c: char) -> bool {
+    QUOTES.contains(&c) || matches!(bracket_pair(c), Some((_, _, false)))
+}
+
+fn is_in_code(hl: Highlight) -> bool {
+    !matches!(
+        hl,
+        Highlight::String | Highlight::Comment | Highlight::MultilineComment
+    )
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Moving by words stops wherever one of these changes to another
+#[derive(Copy, Clone, PartialEq)]
+enum CharClass {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    } else if is_word_char(c) {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+// Where needle appears in row, as start and end columns of the text
+fn needle_matches(row: &Row<'_>, needle: &str) -> Vec<(usize, usize)> {
+    let needle_len = needle.chars().count();
+    row.indices_of(needle)
+        .into_iter()
+        .map(|rx| row.render_idx_to_text(rx))
+        .map(|x| (x, x + needle_len))
+        .collect()
+}
+
+fn replace_matches(row: &Row<'_>, needle: &str, regex: Option<&Regex>) -> Vec<(usize, usize)> {
+    match regex {
+        Some(regex) => regex
+            .find_all(row.as_str().trim_end_matches(['\r', '\n']))
+            .iter()
+            .map(|found| (found.start, found.end))
+            .collect(),
+        None if needle.is_empty() => vec![],
+        None => needle_matches(row, needle),
+    }
+}
+
+#[test]
+fn test_join_row() {
+    let mut buffer = Buffer::default();
+
+    buffer.append_row("this is the first line. \r\n");
+    buffer.append_row("this is the second line.\r\n");
+    buffer.dirty = 0;
+    assert_eq!(2, buffer.num_lines());
+
+    buffer.join_row(1);
+    assert_eq!(1, buffer.dirty);
+    assert_eq!(1, buffer.num_lines());
+    let first_row = buffer.rows.get(0).clone().unwrap();
+    assert_eq!(
+        "this is the first line. this is the second line.\r\n",
+        first_row.as_str()
+    );
+}
+
+#[test]
+fn test_insert_newline() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("what a good first line.\r\n");
+    buffer.append_row("not a bad second line\r\n");
+    buffer.dirty = 0;
+    assert_eq!(2, buffer.num_lines());
+
+    buffer.insert_newline(1, 0);
+
+    assert_eq!(3, buffer.num_lines());
+    assert_eq!(1, buffer.dirty);
+    assert_eq!(
+        vec![
+            "what a good first line.\r\n",
+            "\r\n",
+            "not a bad second line\r\n",
+        ],
+        buffer
+            .rows
+            .iter()
+            .map(|r| r.as_str().clone())
+            .collect::<Vec<_>>()
+    );
+
+    buffer.insert_newline(2, 4);
+
+    assert_eq!(4, buffer.num_lines());
+    assert_eq!(2, buffer.dirty);
+    assert_eq!(
+        vec![
+            "what a good first line.\r\n",
+            "\r\n",
+            "not \r\n",
+            "a bad second line\r\n",
+        ],
+        buffer
+            .rows
+            .iter()
+            .map(|r| r.as_str().clone())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![
+            "what a good first line.\n",
+            "\n",
+            "not \n",
+            "a bad second line\n"
+        ],
+        buffer
+            .rows
+            .iter()
+            .map(|r| r.rendered_str().clone())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_insert_newline_default() {
+    let mut buffer = Buffer::default();
+    buffer.insert_newline(0, 0);
+    assert_eq!(1, buffer.dirty);
+    assert_eq!(1, buffer.num_lines());
+    assert_eq!(DEFAULT_NEWLINE_STR, buffer.rows[0].as_str());
+}
+
+#[test]
+fn test_insert_newline_after_firstline() {
+    let mut buffer = Buffer::default();
+    buffer.insert_char('1', 0, 0);
+    assert_eq!(1, buffer.dirty);
+    buffer.insert_newline(0, 1);
+    assert_eq!(2, buffer.dirty);
+    assert_eq!(2, buffer.num_lines());
+    println!("{:?}", buffer.rows[0].as_str());
+    assert!(buffer.rows[0].as_str().ends_with(DEFAULT_NEWLINE_STR));
+    println!("{:?}", buffer.rows[1].as_str());
+    assert!(buffer.rows[1].as_str().ends_with(DEFAULT_NEWLINE_STR));
+}
+
+#[test]
+fn test_insert_char() {
+    let mut buffer = Buffer::default();
+    assert_eq!(0, buffer.dirty);
+    buffer.insert_char('£', 0, 0);
+    assert_eq!(1, buffer.dirty);
+    buffer.insert_char('1', 1, 0);
+    assert_eq!(2, buffer.dirty);
+    let expected = format!("£1{}", DEFAULT_NEWLINE_STR);
+    assert_eq!(
+        vec![&expected],
+        buffer
+            .rows
+            .iter()
+            .map(|r| r.as_str().clone())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_search_backwards_beyond_beginning_of_the_buffer() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("nothing interesting here\r\n");
+    buffer.append_row("nothing again\r\n");
+    assert_eq!(
+        Some((0, 1)),
+        buffer.search_for(None, SearchDirection::Backwards, "nothing")
+    );
+    assert_eq!(
+        Some((0, 1)),
+        buffer.search_for(Some((0, 0)), SearchDirection::Backwards, "nothing")
+    );
+}
+
+#[test]
+fn test_search_clearing_previous_overlays() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("#define _SOMETHING\r\n");
+    buffer.append_row("#define _WOOT\r\n");
+    buffer.append_row("#define _123\r\n");
+    for row in &buffer.rows {
+        assert_eq!(None, row.overlay.iter().find(|item| item.is_some()));
+    }
+    let mut last_match = buffer.search_for(None, SearchDirection::Forwards, "define");
+    assert!(buffer.rows[0]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_some());
+    assert!(buffer.rows[1]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    assert!(buffer.rows[2]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
+    assert!(buffer.rows[0]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    assert!(buffer.rows[1]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_some());
+    assert!(buffer.rows[2]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
+    assert!(buffer.rows[0]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    assert!(buffer.rows[1]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    assert!(buffer.rows[2]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_some());
+    buffer.search_for(last_match, SearchDirection::Forwards, "define");
+    assert!(buffer.rows[0]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_some());
+    assert!(buffer.rows[1]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+    assert!(buffer.rows[2]
+        .overlay
+        .iter()
+        .find(|item| item.is_some())
+        .is_none());
+}
+
+#[test]
+fn test_move_cursor_to_search_match() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("#define _SOMETHING\r\n");
+    buffer.append_row("#define _123\r\n");
+    buffer.append_row("123 #define _INDENT\r\n");
+    let mut last_match = buffer.search_for(None, SearchDirection::Forwards, "define");
+    assert_eq!(0, buffer.cursor.text_row());
+    assert_eq!(1, buffer.cursor.text_col());
+    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
+    assert_eq!(1, buffer.cursor.text_row());
+    assert_eq!(1, buffer.cursor.text_col());
+    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
+    assert_eq!(2, buffer.cursor.text_row());
+    assert_eq!(5, buffer.cursor.text_col());
+    buffer.search_for(last_match, SearchDirection::Forwards, "define");
+    assert_eq!(0, buffer.cursor.text_row());
+    assert_eq!(1, buffer.cursor.text_col());
+}
+
+#[test]
+fn test_move_cursor() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("\t£lots");
+    assert_eq!(0, buffer.cursor.text_col());
+    assert_eq!(
+        0,
+        buffer.text_cursor_to_render(buffer.cursor.text_col(), buffer.cursor.text_row())
+    );
+    buffer.cursor.change(|cursor| cursor.text_col += 1);
+    assert_eq!(1, buffer.cursor.text_col());
+    assert_eq!(
+        8,
+        buffer.text_cursor_to_render(buffer.cursor.text_col(), buffer.cursor.text_row())
+    );
+    buffer.cursor.change(|cursor| cursor.text_col += 1);
+    assert_eq!(2, buffer.cursor.text_col());
+    assert_eq!(
+        9,
+        buffer.text_cursor_to_render(buffer.cursor.text_col(), buffer.cursor.text_row())
+    );
+}
+
+#[test]
+fn test_move_cursor_with_inserted_text() {
+    use crate::cursor::Cursor;
+
+    let mut buffer = Buffer::default();
+    assert_eq!(Cursor::default(), buffer.cursor.current());
+    assert_eq!(0, buffer.cursor.text_row());
+    assert_eq!(0, buffer.cursor.text_col());
+
+    buffer.insert_char_at_cursor('H');
+    assert_eq!(Cursor::new(0, 1), buffer.cursor.current());
+    assert_eq!(0, buffer.cursor.text_row());
+    assert_eq!(1, buffer.cursor.text_col());
+    assert_eq!(1, buffer.text_cursor_to_render(1, 0));
+}
+
+#[test]
+fn test_basic_auto_indent_on_return_no_syntax() {
+    let mut buffer = Buffer::default();
+    let file_contents = vec!["void main() {", "  int a_var = 10;"];
+    for line in file_contents {
+        for c in line.chars() {
+            buffer.insert_char_at_cursor(c);
+        }
+        buffer.insert_newline_and_return();
+    }
+    assert_eq!(DEFAULT_NEWLINE_STR, buffer.rows[2].as_str());
+    assert_eq!(0, buffer.cursor.text_col());
+    assert_eq!(2, buffer.cursor.text_row());
+}
+
+#[test]
+fn test_indenting_blocks_on_return() {
+    let mut buffer = Buffer::default();
+    buffer.set_filetype("Rust");
+    buffer.set_auto_close(true);
+    let type_in = |buffer: &mut Buffer<'_>, text: &str| {
+        for c in text.chars() {
+            match c {
+                '\n' => buffer.insert_newline_and_return(),
+                _ => buffer.type_char_at_cursor(c),
+            }
+        }
+    };
+    let texts = |buffer: &Buffer<'_>| {
+        buffer
             .rows
-            .get(row)
-            .map(|r| r.newline())
-            .unwrap_or_else(|| DEFAULT_NEWLINE.to_string());
-        if col == 0 {
-            self.insert_row(row, &newline);
-            0
-        } else {
-            let new_line_text = self.rows[row].truncate(col);
-            let prev_indent = self.rows[row].get_indent();
-            self.insert_row(row + 1, &new_line_text);
-            self.rows[row + 1].set_indent(prev_indent);
-            self.update_from(row);
-            self.update_from(row + 1);
-            prev_indent
+            .iter()
+            .map(|row| row.as_str().trim_end_matches('\n').to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Return between the braces opens up a line in between
+    type_in(&mut buffer, "fn main() {\nif x { // then\ny();\n");
+    assert_eq!(
+        vec![
+            "fn main() {",
+            "    if x { // then",
+            "        y();",
+            "        ",
+            "    }",
+            "}"
+        ],
+        texts(&buffer)
+    );
+    assert_eq!(Cursor::new(3, 8), buffer.cursor.current());
+
+    // Typing a closing brace takes the line back out a level
+    type_in(&mut buffer, "}");
+    assert_eq!(vec!["    }", "    }", "}"], texts(&buffer)[3..]);
+    assert_eq!(Cursor::new(3, 5), buffer.cursor.current());
+
+    // Tabs, for filetypes that are indented with them
+    let mut buffer = Buffer::default();
+    buffer.set_filetype("C");
+    buffer.indent_options.global.expand_tabs = false;
+    type_in(&mut buffer, "\tint f() {\nreturn 1;");
+    assert_eq!(vec!["\tint f() {", "\t\treturn 1;"], texts(&buffer));
+}
+
+#[test]
+fn test_basic_auto_indent_on_return_c_syntax() {
+    let mut buffer = Buffer::default();
+    let file_contents = vec!["void main() {", "  int a_var = 10;", "  int b_var = 20;"];
+    for line in file_contents {
+        for c in line.chars() {
+            buffer.insert_char_at_cursor(c);
         }
+        buffer.insert_newline_and_return();
+    }
+    buffer.set_filetype("C");
+
+    buffer.cursor.change(|cursor| {
+        cursor.text_row = 1;
+        cursor.text_col = 17;
+    });
+    buffer.insert_newline_and_return();
+
+    use crate::highlight::Highlight::*;
+
+    // Nothing but the indent, which is trailing until it's typed after
+    assert_eq!(
+        vec![TrailingWhitespace, TrailingWhitespace, Normal],
+        buffer.rows[2].hl
+    );
+
+    let line_to_type = "int c_var = 5;";
+    for c in line_to_type.chars() {
+        buffer.insert_char_at_cursor(c);
+    }
+
+    let expected_contents = vec![
+        format!("void main() {{{}", DEFAULT_NEWLINE_STR),
+        format!("  int a_var = 10;{}", DEFAULT_NEWLINE_STR),
+        format!("  int c_var = 5;{}", DEFAULT_NEWLINE_STR),
+        format!("  int b_var = 20;{}", DEFAULT_NEWLINE_STR),
+    ];
+    for (i, row) in expected_contents.iter().enumerate() {
+        assert_eq!(&row, &buffer.rows[i].as_str(), "Line {} is WRONG!", i);
+    }
+    assert_eq!(16, buffer.cursor.text_col());
+    assert_eq!(2, buffer.cursor.text_row());
+
+    let mut hl = vec![Normal; 2];
+    hl.extend_from_slice(&[Keyword2; 3]);
+    hl.extend_from_slice(&[Normal; 9]);
+    hl.extend_from_slice(&[Number; 1]);
+    hl.extend_from_slice(&[Normal; 2]);
+    assert_eq!(hl, buffer.rows[2].hl);
+}
+
+// TODO: need a case for auto indent (or not) when inserting newline in the middle of a statement
+// TODO: case for tab indents
+
+#[test]
+fn test_select_word_and_line_at_cursor() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("let x = foo_bar;\n");
+    buffer.append_row("last");
+    buffer.cursor.move_to_without_history(0, 10);
+    assert!(buffer.select_word_at_cursor());
+    let selection = buffer.selection.unwrap();
+    assert_eq!(
+        (0, 8),
+        (selection.start().text_row(), selection.start().text_col())
+    );
+    assert_eq!(
+        (0, 15),
+        (selection.end().text_row(), selection.end().text_col())
+    );
+
+    buffer.cursor.move_to_without_history(0, 6);
+    assert!(!buffer.select_word_at_cursor());
+    assert!(buffer.selection.is_none());
+
+    buffer.cursor.move_to_without_history(1, 2);
+    buffer.select_line_at_cursor();
+    let selection = buffer.selection.unwrap();
+    assert_eq!(
+        (1, 0),
+        (selection.start().text_row(), selection.start().text_col())
+    );
+    assert_eq!(
+        (1, 4),
+        (selection.end().text_row(), selection.end().text_col())
+    );
+}
+
+#[test]
+fn test_search_within_selection() {
+    use crate::cursor::Cursor;
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("needle outside\r\n");
+    buffer.append_row("a needle, another needle\r\n");
+    buffer.append_row("needle outside again\r\n");
+    let mut selection = Selection::new(Cursor::new(1, 5));
+    selection.extend_to(Cursor::new(2, 3));
+
+    assert_eq!(
+        Some((18, 1)),
+        buffer.search_within(None, SearchDirection::Forwards, "needle", Some(selection))
+    );
+    assert_eq!(
+        Some((18, 1)),
+        buffer.search_within(
+            Some((18, 1)),
+            SearchDirection::Forwards,
+            "needle",
+            Some(selection)
+        )
+    );
+    assert_eq!(
+        None,
+        buffer.search_within(None, SearchDirection::Forwards, "outside", Some(selection))
+    );
+}
+
+#[test]
+fn test_insert_text_at_cursor() {
+    let mut buffer = Buffer::default();
+    buffer.insert_text_at_cursor("one\ntwo\r\n\nthree");
+    assert_eq!(
+        vec![
+            format!("one{}", DEFAULT_NEWLINE_STR),
+            format!("two{}", DEFAULT_NEWLINE_STR),
+            DEFAULT_NEWLINE_STR.to_string(),
+            format!("three{}", DEFAULT_NEWLINE_STR),
+        ],
+        buffer
+            .rows
+            .iter()
+            .map(|r| r.as_str().to_string())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(3, buffer.cursor.text_row());
+    assert_eq!(5, buffer.cursor.text_col());
+}
+
+#[test]
+fn test_new_file_from_template() {
+    let templates = vec![Template::new(".h", "#ifndef {guard}\n{cursor}\n#endif\n")];
+    let mut buffer = Buffer::default();
+    buffer.new_file("pane.h", &templates);
+    assert!(buffer.is_dirty());
+    assert_eq!("pane.h", buffer.name());
+    assert_eq!("C", buffer.get_filetype());
+    assert_eq!(4, buffer.num_lines());
+    assert!(buffer.rows[0].as_str().starts_with("#ifndef PANE_H_"));
+    assert_eq!(1, buffer.cursor.text_row());
+    assert_eq!(0, buffer.cursor.text_col());
+
+    let mut buffer = Buffer::default();
+    buffer.new_file("notes.txt", &templates);
+    assert!(!buffer.is_dirty());
+    assert_eq!(0, buffer.num_lines());
+}
+
+#[test]
+fn test_read_appended() {
+    use std::fs::OpenOptions;
+
+    let path = crate::utils::temp_path("bim-follow", ".log");
+    let filename = path.to_string_lossy().to_string();
+    std::fs::write(&path, "first\nsecond, part").unwrap();
+
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    assert_eq!(2, buffer.num_lines());
+    assert_eq!(0, buffer.read_appended().unwrap());
+
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b" two\nthird\n").unwrap();
+    assert_eq!(1, buffer.read_appended().unwrap());
+    assert_eq!("second, part two\n", buffer.rows[1].as_str());
+    assert_eq!("third\n", buffer.rows[2].as_str());
+    assert!(!buffer.is_dirty());
+
+    std::fs::write(&path, "rotated\n").unwrap();
+    assert_eq!(1, buffer.read_appended().unwrap());
+    assert_eq!("rotated\n", buffer.rows[0].as_str());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_trim_rows_wraps_around() {
+    let mut buffer = Buffer::default();
+    for _ in 0..5 {
+        buffer.append_row("some text\n");
+    }
+    let revision = buffer.revision();
+    assert_eq!(3, buffer.trim_rows(0, 3));
+    assert_eq!(6, buffer.trim_rows(3, 3));
+    assert_eq!(0, buffer.trim_rows(6, 3));
+    assert_eq!(revision, buffer.revision(), "trimming isn't an edit");
+}
+
+#[test]
+fn test_virtual_edit_pads_line() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("ab\n");
+    buffer.append_row("cd\n");
+    buffer.set_virtual_edit(true);
+    buffer.cursor.change(|cursor| cursor.text_col = 5);
+    buffer.check_cursor();
+    assert_eq!(5, buffer.cursor.text_col());
+    assert_eq!(5, buffer.text_cursor_to_render(5, 0));
+
+    buffer.insert_char_at_cursor('x');
+    assert_eq!("ab   x\n", buffer.rows[0].as_str());
+    assert_eq!(6, buffer.cursor.text_col());
+
+    buffer.cursor.change(|cursor| cursor.text_col = 8);
+    buffer.delete_char_at_cursor();
+    assert_eq!("ab   x\n", buffer.rows[0].as_str());
+    assert_eq!(7, buffer.cursor.text_col());
+
+    buffer.join_next_row_at_cursor();
+    assert_eq!("ab   x cd\n", buffer.rows[0].as_str());
+    assert_eq!(1, buffer.num_lines());
+
+    buffer.set_virtual_edit(false);
+    buffer.cursor.change(|cursor| cursor.text_col = 20);
+    buffer.check_cursor();
+    assert_eq!(9, buffer.cursor.text_col());
+}
+
+#[cfg(test)]
+fn rows_text(buffer: &Buffer) -> String {
+    buffer.rows.iter().map(|row| row.as_str()).collect()
+}
+
+#[test]
+fn test_inspect_char_at_cursor() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("£1\n");
+    buffer.append_row("a€\n");
+    buffer.cursor.change(|cursor| {
+        cursor.text_row = 1;
+        cursor.text_col = 1;
+    });
+    assert_eq!(
+        Some("'€' U+20AC dec 8364 hex 0x20ac | UTF-8: e2 82 ac | char 4 byte 5".to_string()),
+        buffer.inspect_char_at_cursor()
+    );
+    buffer.cursor.change(|cursor| cursor.text_col = 3);
+    assert_eq!(None, buffer.inspect_char_at_cursor());
+}
+
+#[test]
+fn test_undo_redo_typing() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("hello\n");
+    buffer.cursor.change(|cursor| cursor.text_col = 5);
+    for c in " world".chars() {
+        buffer.insert_char_at_cursor(c);
     }
+    buffer.insert_newline_and_return();
+    buffer.insert_char_at_cursor('!');
+    assert_eq!("hello world\n!\n", rows_text(&buffer));
+
+    assert!(buffer.undo());
+    assert_eq!("hello world\n\n", rows_text(&buffer));
+    assert!(buffer.undo());
+    assert_eq!("hello world\n", rows_text(&buffer));
+    assert!(buffer.undo());
+    assert_eq!("hello\n", rows_text(&buffer));
+    assert_eq!(5, buffer.cursor.text_col());
+    assert!(!buffer.undo());
+
+    assert!(buffer.redo());
+    assert_eq!("hello world\n", rows_text(&buffer));
+    assert_eq!(11, buffer.cursor.text_col());
+    assert!(buffer.redo());
+    assert!(buffer.redo());
+    assert_eq!("hello world\n!\n", rows_text(&buffer));
+    assert!(!buffer.redo());
+}
+
+#[test]
+fn test_paste_is_one_undo_step() {
+    let mut buffer = Buffer::default();
+    buffer.set_auto_close(true);
+    buffer.append_row("fn main() {}\n");
+    buffer.append_row("// end\n");
+    buffer.cursor.change(|cursor| cursor.text_col = 11);
+    buffer.paste_at_cursor("\r\n    println!(\"hi\");\r\n    (\n");
+    // Nothing's indented or closed for us, unlike typing
+    assert_eq!(
+        "fn main() {\n    println!(\"hi\");\n    (\n}\n// end\n",
+        rows_text(&buffer)
+    );
+    assert_eq!((3, 0), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    buffer.paste_at_cursor("a日");
+    assert_eq!((3, 2), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    assert_eq!("a日}\n", buffer.rows[3].as_str());
+
+    assert!(buffer.undo());
+    assert!(buffer.undo());
+    assert_eq!("fn main() {}\n// end\n", rows_text(&buffer));
+    assert_eq!(11, buffer.cursor.text_col());
+    assert!(!buffer.undo());
+
+    // Past the end, where there's no row yet
+    buffer.cursor.change(|cursor| cursor.text_row = 2);
+    buffer.cursor.change(|cursor| cursor.text_col = 0);
+    buffer.paste_at_cursor("x\ny");
+    assert_eq!("fn main() {}\n// end\nx\ny\n", rows_text(&buffer));
+}
+
+#[test]
+fn test_undo_backspace_and_join() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("one\n");
+    buffer.append_row("two\n");
+    buffer.cursor.change(|cursor| {
+        cursor.text_row = 1;
+        cursor.text_col = 2;
+    });
+    buffer.delete_char_at_cursor();
+    buffer.delete_char_at_cursor();
+    buffer.delete_char_at_cursor();
+    assert_eq!("oneo\n", rows_text(&buffer));
+
+    assert!(buffer.undo());
+    assert_eq!("one\no\n", rows_text(&buffer));
+    assert!(buffer.undo());
+    assert_eq!("one\ntwo\n", rows_text(&buffer));
+    assert_eq!((1, 2), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+}
+
+#[test]
+fn test_convert_newlines() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("one\n");
+    buffer.append_row("two\n");
+    buffer.append_row("three");
+    assert_eq!("LF", buffer.newline_name());
+
+    buffer.convert_newlines(DOS_NEWLINE);
+    assert_eq!("CRLF", buffer.newline_name());
+    assert_eq!("one\r\ntwo\r\nthree", rows_text(&buffer));
+    assert!(buffer.is_dirty());
+
+    assert!(buffer.undo());
+    assert_eq!("one\ntwo\nthree", rows_text(&buffer));
+}
+
+#[test]
+fn test_search_regex_lands_on_first_group() {
+    use crate::highlight::Highlight;
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("// fn commented_out()\n");
+    buffer.append_row("pub fn main() {\n");
+    buffer.append_row("fn helper(x: u8) {}\n");
+    let regex = Regex::new(r"^(?:pub )?fn (\w+)").unwrap();
+
+    let last_match = buffer.search_regex_within(None, SearchDirection::Forwards, &regex, None);
+    assert_eq!(Some((7, 1)), last_match);
+    assert_eq!(Some(Highlight::SearchMatch), buffer.rows[1].overlay[7]);
+    assert_eq!(None, buffer.rows[1].overlay[11]);
+    assert_eq!((1, 7), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    let last_match =
+        buffer.search_regex_within(last_match, SearchDirection::Forwards, &regex, None);
+    assert_eq!(Some((3, 2)), last_match);
+    assert_eq!(
+        Some((7, 1)),
+        buffer.search_regex_within(last_match, SearchDirection::Backwards, &regex, None)
+    );
+}
+
+#[test]
+fn test_replacing_matches() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("one two one\n");
+    buffer.append_row("two\n");
+    buffer.append_row("one\n");
+    buffer.seal_undo_step();
+
+    assert_eq!(
+        Some((0, 8, 11)),
+        buffer.find_replace_match(0, 1, "one", None)
+    );
+    buffer.replace_text(0, 8, 11, "1");
+    assert_eq!("one two 1\n", buffer.rows[0].as_str());
+    assert_eq!(
+        Some((2, 0, 3)),
+        buffer.find_replace_match(0, 9, "one", None)
+    );
+
+    assert_eq!(2, buffer.replace_all_from(0, 0, "one", None, "uno"));
+    assert_eq!("uno two 1\n", buffer.rows[0].as_str());
+    assert_eq!("uno\n", buffer.rows[2].as_str());
+    buffer.undo();
+    assert_eq!("one two 1\n", buffer.rows[0].as_str());
+    assert_eq!("one\n", buffer.rows[2].as_str());
+
+    let regex = Regex::new("t[a-z]+").unwrap();
+    assert_eq!(2, buffer.replace_all_from(0, 0, "", Some(&regex), "2"));
+    assert_eq!("one 2 1\n", buffer.rows[0].as_str());
+    assert_eq!("2\n", buffer.rows[1].as_str());
+    assert!(buffer.is_dirty());
+}
+
+#[test]
+fn test_buffer_with_text_is_clean() {
+    let buffer = Buffer::with_text("C-f  StartSearch\nC-z  Undo");
+    assert_eq!(2, buffer.num_lines());
+    assert_eq!("C-z  Undo", buffer.rows[1].as_str().trim_end());
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn test_title_markers() {
+    let mut buffer = Buffer::default();
+    assert_eq!("[No Name]", buffer.title());
+    buffer.insert_char_at_cursor('x');
+    assert_eq!("[No Name] [+]", buffer.title());
+
+    buffer.set_filename("notes.txt".to_string());
+    assert_eq!("notes.txt [New] [+]", buffer.title());
+    buffer.on_disk = true;
+    buffer.read_only = true;
+    buffer.dirty = 0;
+    assert_eq!("notes.txt [RO]", buffer.title());
+}
+
+#[test]
+fn test_encoding_is_kept_when_saving() {
+    let path = crate::utils::temp_path("bim-latin1", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    std::fs::write(&path, b"caf\xE9\nna\xEFve\n").unwrap();
 
-    pub fn insert_newline_and_return(&mut self) {
-        let indent = self.insert_newline(
-            self.cursor.text_row() as usize,
-            self.cursor.text_col() as usize,
-        );
-        self.cursor.change(|cursor| {
-            cursor.text_row += 1;
-            cursor.text_col = indent;
-        });
-    }
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    assert_eq!(Encoding::Latin1, buffer.encoding());
+    assert_eq!(2, buffer.num_lines());
+    assert_eq!("caf\u{e9}\n", buffer.rows[0].as_str());
 
-    pub fn join_row(&mut self, at: usize) -> bool {
-        if at > 0 && at < self.num_lines() {
-            let row = self.rows.remove(at);
-            if let Some(previous_row) = self.rows.get_mut(at - 1) {
-                previous_row.append_text(row.as_str());
-            }
-            self.dirty += 1;
-            self.update_from(at - 1);
-            true
-        } else {
-            false
-        }
-    }
+    buffer.cursor.move_to(1, 0);
+    buffer.insert_char_at_cursor('\u{e0}');
+    assert_eq!(FileSaveStatus::Saved(12), buffer.save_file().unwrap());
+    assert_eq!(
+        b"caf\xE9\n\xE0na\xEFve\n".to_vec(),
+        std::fs::read(&path).unwrap()
+    );
 
-    fn delete_char(&mut self, x: i32, y: i32) {
-        self.rows[y as usize].delete_char((x - 1) as usize);
-        self.update_from(y as usize);
-    }
+    std::fs::remove_file(&path).unwrap();
+}
 
-    pub fn delete_char_at_cursor(&mut self) {
-        let num_rows = self.num_lines() as i32;
-        if self.cursor.text_row() >= num_rows {
-            return;
-        }
-        if self.cursor.text_col() > 0 {
-            self.delete_char(self.cursor.text_col(), self.cursor.text_row());
-            self.cursor.change(|cursor| cursor.text_col -= 1);
-            self.dirty += 1;
-        } else if self.cursor.text_row() > 0 && self.cursor.text_col() == 0 {
-            let at = self.cursor.text_row();
-            let new_col = self.line_len(at - 1).unwrap_or(0) as i32;
-            self.join_row(at as usize);
-            self.cursor.change(|cursor| {
-                cursor.text_col = new_col;
-                cursor.text_row -= 1;
-            });
+#[test]
+fn test_trimming_edited_rows_on_save() {
+    let path = crate::utils::temp_path("bim-trim", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    std::fs::write(&path, "one  \ntwo\nthree \t\n").unwrap();
+
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    buffer.set_trim_trailing_whitespace(true);
+    buffer.cursor.move_to(1, 3);
+    buffer.insert_char_at_cursor(' ');
+    buffer.cursor.move_to(2, 0);
+    buffer.insert_char_at_cursor('-');
+    buffer.save_file().unwrap();
+    // Only the rows that were edited
+    assert_eq!(
+        "one  \ntwo\n-three\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+    assert_eq!(Cursor::new(2, 1), buffer.cursor.current());
+
+    // Undoing it puts the spaces back
+    assert!(buffer.undo());
+    assert_eq!("-three \t\n", buffer.rows[2].as_str());
+    assert_eq!("two \n", buffer.rows[1].as_str());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_newline_is_what_most_rows_use() {
+    let path = crate::utils::temp_path("bim-newlines", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    std::fs::write(&path, "stray\none\r\ntwo\r\nthree").unwrap();
+
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    assert_eq!("CRLF", buffer.newline_name());
+
+    std::fs::write(&path, "one\ntwo\r\nthree\n").unwrap();
+    buffer.open(&filename).unwrap();
+    assert_eq!("LF", buffer.newline_name());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_open_in_background() {
+    let path = crate::utils::temp_path("bim-background", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    let text = (0..10_000)
+        .map(|line| format!("caf\u{e9} {:0>100}\r\n", line))
+        .collect::<String>()
+        + "no newline";
+    std::fs::write(&path, &text).unwrap();
+
+    // The cursor waits for its line to load
+    let mut buffer = Buffer::default();
+    buffer
+        .open_or_new_in_background(&format!("{}:9000:3", filename), &[])
+        .unwrap();
+    assert!(buffer.is_loading());
+    assert!(buffer.save_file().is_err());
+    while buffer.is_loading() {
+        assert!(buffer.loading_status().unwrap().starts_with("loading "));
+        if buffer.poll_loading() == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
         }
     }
+    assert_eq!(None, buffer.loading_status());
+    assert!(!buffer.is_dirty());
+    assert_eq!("CRLF", buffer.newline_name());
+    assert_eq!(
+        (8_999, 2),
+        (buffer.cursor.text_row(), buffer.cursor.text_col())
+    );
 
-    pub fn insert_char(&mut self, character: char, cursor_x: i32, cursor_y: i32) {
-        if self.newline == "" {
-            self.update_newline();
-        }
-        if cursor_y == self.rows.len() as i32 {
-            self.rows
-                .push(Row::new(self.newline, Rc::downgrade(&self.syntax)));
+    let mut opened = Buffer::default();
+    opened.open(&filename).unwrap();
+    assert_eq!(opened.num_lines(), buffer.num_lines());
+    assert_eq!(
+        format!("caf\u{e9} {:0>100}\r\n", 9999),
+        buffer.rows[9_999].as_str()
+    );
+    assert_eq!("no newline", buffer.rows[10_000].as_str());
+    assert_eq!(
+        FileSaveStatus::Saved(text.len()),
+        buffer.save_file().unwrap()
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_open_in_background_detects_encoding_from_the_whole_file() {
+    let path = crate::utils::temp_path("bim-background-latin1", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    let line = format!("{}\n", "plain ascii ".repeat(50));
+    let mut bytes = line.repeat(2_000).into_bytes();
+    bytes.extend_from_slice(b"caf\xE9\n");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let mut buffer = Buffer::default();
+    buffer.open_in_background(&filename).unwrap();
+    while buffer.is_loading() {
+        if buffer.poll_loading() == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
         }
-        self.rows[cursor_y as usize].insert_char(cursor_x as usize, character);
-        self.dirty += 1;
-        self.update_from(cursor_y as usize);
     }
+    assert_eq!(Encoding::Latin1, buffer.encoding);
+    assert_eq!("caf\u{e9}\n", buffer.rows[2_000].as_str());
+    assert_eq!(
+        FileSaveStatus::Saved(bytes.len()),
+        buffer.save_file().unwrap()
+    );
+    assert_eq!(bytes, std::fs::read(&path).unwrap());
 
-    pub fn insert_char_at_cursor(&mut self, character: char) {
-        self.insert_char(character, self.cursor.text_col(), self.cursor.text_row());
-        self.cursor.change(|cursor| cursor.text_col += 1);
-    }
+    std::fs::remove_file(&path).unwrap();
+}
 
-    pub fn check_cursor(&mut self) {
-        let current_cursor = self.cursor.current();
-        let mut new_cursor = self.cursor.current();
-        if new_cursor.text_row < 0 {
-            new_cursor.text_row = 0;
-        }
+#[test]
+fn test_piped_text_is_written_back() {
+    let mut buffer = Buffer::default();
+    buffer.open_bytes(b"\xFF\xFEo\0n\0e\0\n\0");
+    assert_eq!(Encoding::Utf16LeBom, buffer.encoding());
+    assert_eq!("one\n", buffer.rows[0].as_str());
+    assert!(buffer.is_new());
+    assert!(!buffer.is_dirty());
 
-        if new_cursor.text_row > self.num_lines() as i32 {
-            new_cursor.text_row = self.num_lines() as i32;
-        }
+    buffer.cursor.move_to(0, 0);
+    buffer.insert_char_at_cursor('d');
+    let mut out = vec![];
+    assert_eq!(12, buffer.write_text(&mut out).unwrap());
+    assert_eq!(b"\xFF\xFEd\0o\0n\0e\0\n\0".to_vec(), out);
+}
 
-        if new_cursor.text_col < 0 {
-            new_cursor.text_col = 0;
-        }
+#[test]
+fn test_utf16_without_a_bom_is_written_back_without_one() {
+    let mut buffer = Buffer::default();
+    buffer.open_bytes(b"o\0n\0e\0\n\0");
+    assert_eq!(Encoding::Utf16Le, buffer.encoding());
 
-        let row_len = self.line_len(new_cursor.text_row).unwrap_or(0);
+    buffer.cursor.move_to(0, 0);
+    buffer.insert_char_at_cursor('d');
+    let mut out = vec![];
+    assert_eq!(10, buffer.write_text(&mut out).unwrap());
+    assert_eq!(b"d\0o\0n\0e\0\n\0".to_vec(), out);
+}
 
-        if new_cursor.text_col > row_len as i32 {
-            new_cursor.text_col = row_len as i32;
-        }
+#[test]
+fn test_long_commit_subject_is_a_warning() {
+    let mut buffer = Buffer::default();
+    buffer.set_filename(".git/COMMIT_EDITMSG".to_string());
+    assert_eq!("gitcommit", buffer.get_filetype());
+    let subject = "x".repeat(52);
+    buffer.insert_text_at_cursor(&format!("{}\n\nFixes #12\n# comment\n", subject));
 
-        if current_cursor != new_cursor {
-            self.cursor.change(|cursor| {
-                cursor.text_col = new_cursor.text_col();
-                cursor.text_row = new_cursor.text_row();
-            });
-        }
-    }
+    assert_eq!(Highlight::Normal, buffer.rows[0].hl[49]);
+    assert_eq!(Highlight::Warning, buffer.rows[0].hl[50]);
+    assert_eq!(Highlight::Warning, buffer.rows[0].hl[51]);
+    assert_eq!(Highlight::Normal, buffer.rows[2].hl[6]);
+    assert_eq!(Highlight::Comment, buffer.rows[3].hl[0]);
+
+    buffer.cursor.move_to(0, 52);
+    buffer.delete_char_at_cursor();
+    buffer.delete_char_at_cursor();
+    assert!(!buffer.rows[0].hl.contains(&Highlight::Warning));
 }
 
 #[test]
-fn test_join_row() {
+fn test_changes_on_disk_are_noticed() {
+    let path = crate::utils::temp_path("bim-changed", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
     let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    assert_eq!(None, buffer.disk_change());
 
-    buffer.append_row("this is the first line. \r\n");
-    buffer.append_row("this is the second line.\r\n");
-    buffer.dirty = 0;
-    assert_eq!(2, buffer.num_lines());
+    std::fs::write(&path, "one\nchanged\n").unwrap();
+    assert!(buffer.disk_change().is_some());
+    buffer.cursor.move_to(2, 3);
+    buffer.reload().unwrap();
+    assert_eq!(None, buffer.disk_change());
+    assert_eq!("changed\n", buffer.rows[1].as_str());
+    assert_eq!((2, 0), (buffer.cursor.text_row(), buffer.cursor.text_col()));
 
-    buffer.join_row(1);
-    assert_eq!(1, buffer.dirty);
-    assert_eq!(1, buffer.num_lines());
-    let first_row = buffer.rows.get(0).clone().unwrap();
+    // Saving over a change only goes through the second time
+    std::fs::write(&path, "changed again\n").unwrap();
+    buffer.insert_char_at_cursor('!');
+    assert!(buffer.save_file().is_err());
+    assert_eq!("changed again\n", std::fs::read_to_string(&path).unwrap());
+    assert!(buffer.save_file().is_ok());
+    assert_eq!(None, buffer.disk_change());
+
+    std::fs::write(&path, "and again\n").unwrap();
+    buffer.keep_in_memory();
+    assert!(buffer.save_file().is_ok());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_swap_files_are_written_and_recovered() {
+    let path = crate::utils::temp_path("bim-swap", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    let swap_path = swap::swap_path(&filename);
+    std::fs::write(&path, "saved\n").unwrap();
+
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    assert_eq!(None, buffer.leftover_swap());
+    buffer.write_swap().unwrap();
+    assert!(
+        !swap_path.exists(),
+        "nothing to keep until there's a change"
+    );
+    buffer.insert_char_at_cursor('!');
+    buffer.write_swap().unwrap();
     assert_eq!(
-        "this is the first line. this is the second line.\r\n",
-        first_row.as_str()
+        format!("bim-swap {}\n!saved\n", std::process::id()),
+        std::fs::read_to_string(&swap_path).unwrap()
     );
+
+    // As if the first bim had crashed
+    let mut recovered = Buffer::default();
+    recovered.open(&filename).unwrap();
+    assert_eq!(Some(swap_path.clone()), recovered.leftover_swap());
+    assert_eq!(None, recovered.leftover_swap());
+    recovered.recover_swap().unwrap();
+    assert_eq!("!saved\n", recovered.rows[0].as_str());
+    assert!(recovered.is_dirty());
+
+    recovered.save_file().unwrap();
+    assert!(!swap_path.exists());
+
+    std::fs::remove_file(&path).unwrap();
 }
 
+#[cfg(unix)]
 #[test]
-fn test_insert_newline() {
+fn test_another_running_bims_swap_file_is_left_alone() {
+    let path = crate::utils::temp_path("bim-held-swap", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    let swap_path = swap::swap_path(&filename);
+    std::fs::write(&path, "saved\n").unwrap();
+    let mut other = std::process::Command::new("sleep")
+        .arg("30")
+        .spawn()
+        .unwrap();
+    std::fs::write(&swap_path, format!("bim-swap {}\nchanged\n", other.id())).unwrap();
+
     let mut buffer = Buffer::default();
-    buffer.append_row("what a good first line.\r\n");
-    buffer.append_row("not a bad second line\r\n");
-    buffer.dirty = 0;
-    assert_eq!(2, buffer.num_lines());
+    buffer.open(&filename).unwrap();
+    assert_eq!(None, buffer.leftover_swap());
+    buffer.discard_swap();
+    buffer.insert_char_at_cursor('!');
+    buffer.write_swap().unwrap();
+    buffer.remove_swap();
+    assert_eq!(
+        format!("bim-swap {}\nchanged\n", other.id()),
+        std::fs::read_to_string(&swap_path).unwrap()
+    );
 
-    buffer.insert_newline(1, 0);
+    // Once it's gone the swap file's there to recover
+    other.kill().unwrap();
+    other.wait().unwrap();
+    let mut recovered = Buffer::default();
+    recovered.open(&filename).unwrap();
+    assert_eq!(Some(swap_path.clone()), recovered.leftover_swap());
+    recovered.recover_swap().unwrap();
+    assert_eq!("changed\n", recovered.rows[0].as_str());
+    recovered.discard_swap();
+    assert!(!swap_path.exists());
 
-    assert_eq!(3, buffer.num_lines());
-    assert_eq!(1, buffer.dirty);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_replace_in_rows() {
+    let mut buffer = Buffer::with_text("old one\nold two\nold old three\n");
+    assert_eq!(3, buffer.replace_in_rows(&[0, 2], "old", "new"));
+    assert_eq!("new one\n", buffer.rows[0].as_str());
+    assert_eq!("old two\n", buffer.rows[1].as_str());
+    assert_eq!("new new three\n", buffer.rows[2].as_str());
+    assert_eq!(0, buffer.replace_in_rows(&[0], "old", "new"));
+    assert_eq!(0, buffer.replace_in_rows(&[9], "old", "new"));
+
+    // It's all one step to undo
+    assert!(buffer.undo());
+    assert_eq!("old one\n", buffer.rows[0].as_str());
+    assert_eq!("old old three\n", buffer.rows[2].as_str());
+}
+
+#[test]
+fn test_filtering_rows() {
+    let mut buffer = Buffer::with_text("pear\napple\nfig\nkiwi\n");
+    assert_eq!(0..4, buffer.filter_rows());
+
+    // Rows 1 and 2, as the selection stops at the start of row 3
+    buffer.selection = Some(Selection::new(Cursor::new(1, 2)));
+    buffer.cursor.move_to_without_history(3, 0);
+    buffer.update_selection();
+    assert_eq!(1..3, buffer.filter_rows());
+    assert_eq!("apple\nfig\n", buffer.rows_text(1..3));
+
+    buffer.replace_rows_with_text(1..3, "FIG\nAPPLE\nAPPLE\n");
     assert_eq!(
-        vec![
-            "what a good first line.\r\n",
-            "\r\n",
-            "not a bad second line\r\n",
-        ],
-        buffer
-            .rows
-            .iter()
-            .map(|r| r.as_str().clone())
-            .collect::<Vec<_>>()
+        "pear\nFIG\nAPPLE\nAPPLE\nkiwi\n",
+        buffer.rows_text(0..buffer.num_lines())
     );
+    assert!(buffer.selection.is_none());
+    assert_eq!(1, buffer.cursor.text_row());
 
-    buffer.insert_newline(2, 4);
+    assert!(buffer.undo());
+    assert_eq!("apple\n", buffer.rows[1].as_str());
 
-    assert_eq!(4, buffer.num_lines());
-    assert_eq!(2, buffer.dirty);
+    // The last row keeps to not having a newline
+    let mut buffer = Buffer::default();
+    buffer.append_row("one\n");
+    buffer.append_row("two");
+    buffer.replace_rows_with_text(1..2, "TWO\n");
+    assert_eq!("TWO", buffer.rows[1].as_str());
+}
+
+#[test]
+fn test_taking_and_inserting_blocks() {
+    let mut buffer = Buffer::with_text("let one = 1;\nlet\nlet three = 3;\n");
+    buffer.cursor.move_to_without_history(0, 4);
+    buffer.start_block_selection();
+    buffer.cursor.move_to_without_history(2, 7);
+    buffer.update_selection();
+
+    // The short row in the middle gives up nothing, and is padded out
     assert_eq!(
-        vec![
-            "what a good first line.\r\n",
-            "\r\n",
-            "not \r\n",
-            "a bad second line\r\n",
-        ],
-        buffer
-            .rows
-            .iter()
-            .map(|r| r.as_str().clone())
-            .collect::<Vec<_>>()
+        Some(vec!["one".to_string(), "".to_string(), "thr".to_string()]),
+        buffer.take_block(true)
     );
     assert_eq!(
-        vec![
-            "what a good first line.\n",
-            "\n",
-            "not \n",
-            "a bad second line\n"
-        ],
-        buffer
-            .rows
-            .iter()
-            .map(|r| r.rendered_str().clone())
-            .collect::<Vec<_>>()
+        "let  = 1;\nlet \nlet ee = 3;\n",
+        buffer.rows_text(0..buffer.num_lines())
     );
+    assert_eq!((0, 4), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    assert_eq!(2, buffer.extra_cursors.len());
+    assert!(buffer.selection.is_none());
+    assert_eq!(None, buffer.take_block(true));
+
+    // Going in past the last row adds rows for it
+    buffer.extra_cursors.clear();
+    buffer.cursor.move_to_without_history(2, 2);
+    buffer.insert_block(&["ab".to_string(), "cd".to_string()]);
+    assert_eq!(
+        "let  = 1;\nlet \nleabt ee = 3;\n  cd\n",
+        buffer.rows_text(0..buffer.num_lines())
+    );
+    assert_eq!((3, 4), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    assert!(buffer.undo());
+    assert_eq!(3, buffer.num_lines());
+    assert!(buffer.undo());
+    assert_eq!("let one = 1;\n", buffer.rows[0].as_str());
 }
 
 #[test]
-fn test_insert_newline_default() {
+fn test_big_files_are_highlighted_lazily() {
+    let path = crate::utils::temp_path("bim-lazy", ".c");
+    std::fs::write(&path, "/* one\ntwo */\nint three;\nint four;\n").unwrap();
     let mut buffer = Buffer::default();
-    buffer.insert_newline(0, 0);
-    assert_eq!(1, buffer.dirty);
-    assert_eq!(1, buffer.num_lines());
-    assert_eq!(DEFAULT_NEWLINE_STR, buffer.rows[0].as_str());
+    buffer.highlight_lazily_over(Some(16));
+    buffer.open(&path.to_string_lossy()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(buffer.highlights_lazily());
+    assert!(buffer.title().ends_with(" [Large]"));
+    assert!(buffer.rows.iter().all(|row| !row.is_highlighted()));
+
+    buffer.highlight_rows(0..2);
+    assert!(buffer.rows[1].is_highlighted());
+    assert!(buffer.rows[0].hl_open_comment);
+    assert!(!buffer.rows[2].is_highlighted());
+
+    // Carries on from the comment the row before was left in
+    buffer.highlight_rows(1..3);
+    assert_eq!(Highlight::MultilineComment, buffer.rows[1].hl[0]);
+    assert_eq!(Highlight::Keyword2, buffer.rows[2].hl[0]);
+
+    buffer.highlight_fully();
+    assert!(!buffer.highlights_lazily());
+    assert!(buffer.rows.iter().all(|row| row.is_highlighted()));
+    assert!(!buffer.title().contains("[Large]"));
 }
 
 #[test]
-fn test_insert_newline_after_firstline() {
-    let mut buffer = Buffer::default();
-    buffer.insert_char('1', 0, 0);
-    assert_eq!(1, buffer.dirty);
-    buffer.insert_newline(0, 1);
-    assert_eq!(2, buffer.dirty);
-    assert_eq!(2, buffer.num_lines());
-    println!("{:?}", buffer.rows[0].as_str());
-    assert!(buffer.rows[0].as_str().ends_with(DEFAULT_NEWLINE_STR));
-    println!("{:?}", buffer.rows[1].as_str());
-    assert!(buffer.rows[1].as_str().ends_with(DEFAULT_NEWLINE_STR));
+fn test_saving_into_a_missing_directory() {
+    let dir = crate::utils::temp_path("bim-missing", "");
+    let path = dir.join("nested").join("notes.txt");
+    let mut buffer = Buffer::with_text("notes\n");
+    buffer.set_filename(path.to_string_lossy().to_string());
+    assert_eq!(
+        FileSaveStatus::MissingDirectory(dir.join("nested")),
+        buffer.save_file().unwrap()
+    );
+    assert!(!dir.exists());
+
+    assert_eq!(
+        Some(dir.join("nested")),
+        buffer.create_missing_directory().unwrap()
+    );
+    assert_eq!(None, buffer.create_missing_directory().unwrap());
+    assert_eq!(FileSaveStatus::Saved(6), buffer.save_file().unwrap());
+    assert_eq!("notes\n", std::fs::read_to_string(&path).unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
 
 #[test]
-fn test_insert_char() {
-    let mut buffer = Buffer::default();
-    assert_eq!(0, buffer.dirty);
-    buffer.insert_char('£', 0, 0);
-    assert_eq!(1, buffer.dirty);
-    buffer.insert_char('1', 1, 0);
-    assert_eq!(2, buffer.dirty);
-    let expected = format!("£1{}", DEFAULT_NEWLINE_STR);
+fn test_editing_at_every_cursor() {
+    let mut buffer = Buffer::with_text("ab\nab\nab\n");
+    buffer.cursor.move_to(0, 1);
+    buffer.add_cursor(0, 2);
+    buffer.add_cursor(2, 1);
+    buffer.at_every_cursor(|buffer| buffer.insert_char_at_cursor('x'));
+    assert_eq!("axbx\n", buffer.rows[0].as_str());
+    assert_eq!("ab\n", buffer.rows[1].as_str());
+    assert_eq!("axb\n", buffer.rows[2].as_str());
+    assert_eq!((0, 2), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    assert_eq!(
+        &[Cursor::new(2, 2), Cursor::new(0, 4)],
+        buffer.extra_cursors()
+    );
+
+    // Splitting rows moves the cursors below down
+    buffer.at_every_cursor(Buffer::insert_newline_and_return);
+    assert_eq!(
+        vec!["ax\n", "bx\n", "\n", "ab\n", "ax\n", "b\n"],
+        buffer
+            .rows
+            .iter()
+            .map(|row| row.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!((1, 0), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    buffer.at_every_cursor(Buffer::delete_char_at_cursor);
+    buffer.at_every_cursor(Buffer::delete_char_at_cursor);
     assert_eq!(
-        vec![&expected],
+        vec!["ab\n", "ab\n", "ab\n"],
         buffer
             .rows
             .iter()
-            .map(|r| r.as_str().clone())
+            .map(|row| row.as_str())
             .collect::<Vec<_>>()
     );
+    assert_eq!(2, buffer.extra_cursors().len());
+
+    // Cursors that end up in the same place become one
+    buffer.at_every_cursor(Buffer::delete_char_at_cursor);
+    assert_eq!("\n", buffer.rows[0].as_str());
+    assert_eq!("b\n", buffer.rows[2].as_str());
+    assert_eq!(&[Cursor::new(2, 0)], buffer.extra_cursors());
+
+    // The edit at each cursor comes undone together, and the extra cursors
+    // go with it
+    assert!(buffer.undo());
+    assert_eq!("ab\n", buffer.rows[0].as_str());
+    assert_eq!("ab\n", buffer.rows[2].as_str());
+    assert!(buffer.extra_cursors().is_empty());
 }
 
 #[test]
-fn test_search_backwards_beyond_beginning_of_the_buffer() {
+fn test_add_cursor_at_next_match() {
+    let mut buffer = Buffer::with_text("let foo = 1;\nfoobar(foo);\nfoo\n");
+    buffer.cursor.move_to(0, 5);
+    assert!(buffer.add_cursor_at_next_match());
+    assert_eq!(&[Cursor::new(1, 8)], buffer.extra_cursors());
+    assert!(buffer.add_cursor_at_next_match());
+    assert_eq!(Cursor::new(2, 1), buffer.extra_cursors()[1]);
+    // Nowhere left to go once it's been all the way round
+    assert!(!buffer.add_cursor_at_next_match());
+    assert_eq!(2, buffer.extra_cursors().len());
+
+    buffer.at_every_cursor(|buffer| buffer.insert_char_at_cursor('o'));
+    assert_eq!("let fooo = 1;\n", buffer.rows[0].as_str());
+    assert_eq!("foobar(fooo);\n", buffer.rows[1].as_str());
+    assert_eq!("fooo\n", buffer.rows[2].as_str());
+
+    buffer.cursor.move_to(0, 3);
+    assert!(!buffer.add_cursor_at_next_match());
+}
+
+#[test]
+fn test_big_and_binary_files_are_held_back() {
+    let binary = crate::utils::temp_path("bim-held", ".bin");
+    std::fs::write(&binary, b"\x7FELF\x02\x01\x01\0hi there\n!").unwrap();
+    let filename = binary.to_string_lossy().to_string();
+
+    // Nothing's held back unless the buffer's guarded
     let mut buffer = Buffer::default();
-    buffer.append_row("nothing interesting here\r\n");
-    buffer.append_row("nothing again\r\n");
-    assert_eq!(
-        Some((0, 1)),
-        buffer.search_for(None, SearchDirection::Backwards, "nothing")
-    );
+    buffer.open(&filename).unwrap();
+    assert_eq!(None, buffer.held_open());
+
+    let mut buffer = Buffer::default();
+    buffer.guard_open(None);
+    buffer.open(&filename).unwrap();
+    assert_eq!(Some(OpenWarning::Binary), buffer.held_open());
+    assert_eq!(0, buffer.num_lines());
+    assert!(buffer.save_file().is_err());
+
+    buffer.open_held(OpenAs::Hex).unwrap();
+    assert_eq!(None, buffer.held_open());
+    assert_eq!(2, buffer.num_lines());
+    assert!(buffer.rows[0].as_str().starts_with("00000000  7f 45 4c 46"));
+    assert!(buffer.title().ends_with(" [Hex] [RO]"));
+    assert!(buffer.save_file().is_err());
     assert_eq!(
-        Some((0, 1)),
-        buffer.search_for(Some((0, 0)), SearchDirection::Backwards, "nothing")
+        b"\x7FELF\x02\x01\x01\0hi there\n!".to_vec(),
+        std::fs::read(&binary).unwrap()
     );
+
+    let text = crate::utils::temp_path("bim-held", ".txt");
+    std::fs::write(&text, "more than ten bytes\n").unwrap();
+    let filename = text.to_string_lossy().to_string();
+    let mut buffer = Buffer::default();
+    buffer.guard_open(Some(10));
+    buffer
+        .open_or_new_in_background(&format!("{}:1:6", filename), &[])
+        .unwrap();
+    assert_eq!(Some(OpenWarning::Large(20)), buffer.held_open());
+    buffer.open_held(OpenAs::ReadOnly).unwrap();
+    assert_eq!("more than ten bytes\n", buffer.rows[0].as_str());
+    assert_eq!((0, 5), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    assert!(buffer.is_read_only());
+    assert!(buffer.save_file().is_err());
+
+    let mut buffer = Buffer::default();
+    buffer.guard_open(Some(10));
+    buffer.open(&filename).unwrap();
+    buffer.cancel_held();
+    assert_eq!(None, buffer.held_open());
+    assert_eq!(None, buffer.filename);
+
+    std::fs::remove_file(&binary).unwrap();
+    std::fs::remove_file(&text).unwrap();
 }
 
 #[test]
-fn test_search_clearing_previous_overlays() {
-    let mut buffer = Buffer::default();
-    buffer.append_row("#define _SOMETHING\r\n");
-    buffer.append_row("#define _WOOT\r\n");
-    buffer.append_row("#define _123\r\n");
-    for row in &buffer.rows {
-        assert_eq!(None, row.overlay.iter().find(|item| item.is_some()));
-    }
-    let mut last_match = buffer.search_for(None, SearchDirection::Forwards, "define");
-    assert!(buffer.rows[0]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_some());
-    assert!(buffer.rows[1]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    assert!(buffer.rows[2]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
-    assert!(buffer.rows[0]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    assert!(buffer.rows[1]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_some());
-    assert!(buffer.rows[2]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
-    assert!(buffer.rows[0]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    assert!(buffer.rows[1]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    assert!(buffer.rows[2]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_some());
-    buffer.search_for(last_match, SearchDirection::Forwards, "define");
-    assert!(buffer.rows[0]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_some());
-    assert!(buffer.rows[1]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
-    assert!(buffer.rows[2]
-        .overlay
-        .iter()
-        .find(|item| item.is_some())
-        .is_none());
+fn test_word_boundary() {
+    let buffer = Buffer::with_text("let café = foo.bar(1);\n  日本語 x\n");
+    let right = |row, col| buffer.word_boundary(row, col, Direction::Right);
+    let left = |row, col| buffer.word_boundary(row, col, Direction::Left);
+    assert_eq!((0, 3), right(0, 0));
+    assert_eq!((0, 8), right(0, 3));
+    assert_eq!((0, 10), right(0, 8));
+    assert_eq!((0, 14), right(0, 10));
+    assert_eq!((0, 15), right(0, 14));
+    assert_eq!((1, 5), right(0, 22));
+    assert_eq!((1, 7), right(1, 5));
+    assert_eq!((1, 7), right(1, 7));
+
+    assert_eq!((1, 2), left(1, 5));
+    assert_eq!((0, 20), left(1, 2));
+    assert_eq!((0, 4), left(0, 8));
+    assert_eq!((0, 0), left(0, 3));
+    assert_eq!((0, 0), left(0, 0));
 }
 
 #[test]
-fn test_move_cursor_to_search_match() {
-    let mut buffer = Buffer::default();
-    buffer.append_row("#define _SOMETHING\r\n");
-    buffer.append_row("#define _123\r\n");
-    buffer.append_row("123 #define _INDENT\r\n");
-    let mut last_match = buffer.search_for(None, SearchDirection::Forwards, "define");
-    assert_eq!(0, buffer.cursor.text_row());
-    assert_eq!(1, buffer.cursor.text_col());
-    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
-    assert_eq!(1, buffer.cursor.text_row());
-    assert_eq!(1, buffer.cursor.text_col());
-    last_match = buffer.search_for(last_match, SearchDirection::Forwards, "define");
-    assert_eq!(2, buffer.cursor.text_row());
-    assert_eq!(5, buffer.cursor.text_col());
-    buffer.search_for(last_match, SearchDirection::Forwards, "define");
-    assert_eq!(0, buffer.cursor.text_row());
-    assert_eq!(1, buffer.cursor.text_col());
+fn test_delete_word_at_cursor() {
+    let mut buffer = Buffer::with_text("let café = 1;\nnext line\n");
+    buffer.cursor.move_to(0, 8);
+    buffer.delete_word_at_cursor(Direction::Left);
+    assert_eq!("let  = 1;\n", buffer.rows[0].as_str());
+    assert_eq!((0, 4), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    buffer.delete_word_at_cursor(Direction::Right);
+    assert_eq!("let  1;\n", buffer.rows[0].as_str());
+
+    // Deleting over the end of a line joins the lines
+    buffer.cursor.move_to(1, 0);
+    buffer.delete_word_at_cursor(Direction::Left);
+    assert_eq!(vec!["let  1next line\n"], buffer.row_texts(0, 2));
+    assert_eq!((0, 6), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    assert!(buffer.undo());
+    assert_eq!(vec!["let  1;\n", "next line\n"], buffer.row_texts(0, 2));
 }
 
 #[test]
-fn test_move_cursor() {
-    let mut buffer = Buffer::default();
-    buffer.append_row("\t£lots");
-    assert_eq!(0, buffer.cursor.text_col());
-    assert_eq!(
-        0,
-        buffer.text_cursor_to_render(buffer.cursor.text_col(), buffer.cursor.text_row())
-    );
-    buffer.cursor.change(|cursor| cursor.text_col += 1);
-    assert_eq!(1, buffer.cursor.text_col());
+fn test_matching_bracket() {
+    let mut buffer = Buffer::with_text("fn f(a: [u8; 2]) {\n    g(\")\"); // (\n}\n");
+    buffer.set_filetype("Rust");
+    assert_eq!(Some(((0, 4), (0, 15))), buffer.matching_bracket(0, 4));
+    // Just after a bracket counts too
+    assert_eq!(Some(((0, 15), (0, 4))), buffer.matching_bracket(0, 16));
+    assert_eq!(Some(((0, 8), (0, 14))), buffer.matching_bracket(0, 8));
+    // The ones in the string and the comment are left out
+    assert_eq!(Some(((0, 17), (2, 0))), buffer.matching_bracket(0, 17));
+    assert_eq!(Some(((2, 0), (0, 17))), buffer.matching_bracket(2, 0));
+    assert_eq!(Some(((1, 5), (1, 9))), buffer.matching_bracket(1, 5));
+    assert_eq!(None, buffer.matching_bracket(0, 1));
+
+    buffer.cursor.move_to(0, 4);
+    assert!(buffer.update_matching_bracket());
     assert_eq!(
-        8,
-        buffer.text_cursor_to_render(buffer.cursor.text_col(), buffer.cursor.text_row())
+        Some(Highlight::MatchingBracket),
+        buffer.rows[0].overlay_at(4)
     );
-    buffer.cursor.change(|cursor| cursor.text_col += 1);
-    assert_eq!(2, buffer.cursor.text_col());
     assert_eq!(
-        9,
-        buffer.text_cursor_to_render(buffer.cursor.text_col(), buffer.cursor.text_row())
+        Some(Highlight::MatchingBracket),
+        buffer.rows[0].overlay_at(15)
     );
+    assert!(!buffer.update_matching_bracket());
+
+    buffer.cursor.move_to(0, 2);
+    assert!(buffer.update_matching_bracket());
+    assert_eq!(None, buffer.rows[0].overlay_at(4));
+    assert_eq!(None, buffer.rows[0].overlay_at(15));
 }
 
 #[test]
-fn test_move_cursor_with_inserted_text() {
-    use crate::cursor::Cursor;
+fn test_insert_indent_at_cursor() {
+    let mut buffer = Buffer::with_text("ab\n");
+    buffer.cursor.move_to(0, 1);
+    buffer.insert_indent_at_cursor();
+    assert_eq!("a   b\n", buffer.rows[0].as_str());
+    assert_eq!(4, buffer.cursor.text_col());
 
-    let mut buffer = Buffer::default();
-    assert_eq!(Cursor::default(), buffer.cursor.current());
-    assert_eq!(0, buffer.cursor.text_row());
-    assert_eq!(0, buffer.cursor.text_col());
+    let mut indent_options = IndentOptions::default();
+    indent_options.for_filetype_mut("Rust").expand_tabs = false;
+    indent_options.for_filetype_mut("Rust").tab_width = 2;
+    buffer.set_indent_options(indent_options);
+    buffer.set_filetype("Rust");
+    buffer.insert_indent_at_cursor();
+    assert_eq!("a   \tb\n", buffer.rows[0].as_str());
+    assert_eq!("a     b\n", buffer.rows[0].rendered_str());
+}
 
-    buffer.insert_char_at_cursor('H');
-    assert_eq!(Cursor::new(0, 1), buffer.cursor.current());
-    assert_eq!(0, buffer.cursor.text_row());
-    assert_eq!(1, buffer.cursor.text_col());
-    assert_eq!(1, buffer.text_cursor_to_render(1, 0));
+#[test]
+fn test_toggle_comment() {
+    let mut buffer = Buffer::with_text("fn f() {\n    let a = 1;\n\n        a\n}\n");
+    // Nothing to comment with until there's a syntax
+    assert!(!buffer.toggle_comment(0, 0));
+    buffer.set_filetype("Rust");
+    buffer.cursor.move_to(1, 8);
+    assert!(buffer.toggle_comment(1, 3));
+    assert_eq!("    // let a = 1;\n", buffer.rows[1].as_str());
+    assert_eq!("\n", buffer.rows[2].as_str());
+    assert_eq!("    //     a\n", buffer.rows[3].as_str());
+    assert_eq!(11, buffer.cursor.text_col());
+
+    // Only some of them are commented, so they all get commented
+    assert!(buffer.toggle_comment(0, 1));
+    assert_eq!("// fn f() {\n", buffer.rows[0].as_str());
+    assert_eq!("//     // let a = 1;\n", buffer.rows[1].as_str());
+    buffer.undo();
+    assert_eq!("fn f() {\n", buffer.rows[0].as_str());
+
+    assert!(buffer.toggle_comment(1, 3));
+    assert_eq!("    let a = 1;\n", buffer.rows[1].as_str());
+    assert_eq!("        a\n", buffer.rows[3].as_str());
 }
 
 #[test]
-fn test_basic_auto_indent_on_return_no_syntax() {
-    let mut buffer = Buffer::default();
-    let file_contents = vec!["void main() {", "  int a_var = 10;"];
-    for line in file_contents {
-        for c in line.chars() {
-            buffer.insert_char_at_cursor(c);
-        }
-        buffer.insert_newline_and_return();
+fn test_auto_close() {
+    let mut buffer = Buffer::with_text("\n");
+    buffer.set_filetype("Rust");
+    buffer.set_auto_close(true);
+    for c in "f(a[0".chars() {
+        buffer.type_char_at_cursor(c);
     }
-    assert_eq!(DEFAULT_NEWLINE_STR, buffer.rows[2].as_str());
-    assert_eq!(0, buffer.cursor.text_col());
-    assert_eq!(2, buffer.cursor.text_row());
+    assert_eq!("f(a[0])\n", buffer.rows[0].as_str());
+    assert_eq!(5, buffer.cursor.text_col());
+    // The closers that were put in are typed over, not doubled up
+    for c in "]);".chars() {
+        buffer.type_char_at_cursor(c);
+    }
+    assert_eq!("f(a[0]);\n", buffer.rows[0].as_str());
+    assert_eq!(8, buffer.cursor.text_col());
+
+    // Quotes close in code, but not inside a string or after a word
+    for c in " \"(".chars() {
+        buffer.type_char_at_cursor(c);
+    }
+    assert_eq!("f(a[0]); \"(\"\n", buffer.rows[0].as_str());
+    buffer.type_char_at_cursor('"');
+    buffer.type_char_at_cursor('x');
+    buffer.type_char_at_cursor('\'');
+    assert_eq!("f(a[0]); \"(\"x'\n", buffer.rows[0].as_str());
+
+    // Nor before a word, or in a comment
+    buffer.cursor.move_to(0, 0);
+    buffer.type_char_at_cursor('(');
+    assert_eq!("(f(a[0]); \"(\"x'\n", buffer.rows[0].as_str());
+    let mut buffer = Buffer::with_text("// \n");
+    buffer.set_filetype("Rust");
+    buffer.set_auto_close(true);
+    buffer.cursor.move_to(0, 3);
+    buffer.type_char_at_cursor('{');
+    assert_eq!("// {\n", buffer.rows[0].as_str());
+
+    // Turned off, it's just typing
+    let mut buffer = Buffer::with_text("\n");
+    for c in "(]".chars() {
+        buffer.type_char_at_cursor(c);
+    }
+    assert_eq!("(]\n", buffer.rows[0].as_str());
 }
 
 #[test]
-fn test_basic_auto_indent_on_return_c_syntax() {
-    let mut buffer = Buffer::default();
-    let file_contents = vec!["void main() {", "  int a_var = 10;", "  int b_var = 20;"];
-    for line in file_contents {
-        for c in line.chars() {
-            buffer.insert_char_at_cursor(c);
-        }
-        buffer.insert_newline_and_return();
-    }
+fn test_changed_rows() {
+    let text: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+    let mut buffer = Buffer::with_text(&text);
     buffer.set_filetype("C");
+    buffer.take_changed_rows();
 
-    buffer.cursor.change(|cursor| {
-        cursor.text_row = 1;
-        cursor.text_col = 17;
-    });
-    buffer.insert_newline_and_return();
+    buffer.insert_char('x', 0, 500);
+    let changed = buffer.take_changed_rows().unwrap();
+    assert_eq!((500, 501), (changed.first, changed.end));
+    assert_eq!(None, buffer.take_changed_rows());
 
-    use crate::highlight::Highlight::*;
+    buffer.insert_newline(700, 2);
+    buffer.join_row(300);
+    let changed = buffer.take_changed_rows().unwrap();
+    assert_eq!((299, 701), (changed.first, changed.end));
+    assert_eq!(701, changed.old_end(buffer.num_lines()));
 
-    assert_eq!(vec![Normal; 3], buffer.rows[2].hl);
+    // Opening a comment has every row after it highlighted again
+    buffer.insert_char('/', 0, 900);
+    buffer.insert_char('*', 1, 900);
+    let changed = buffer.take_changed_rows().unwrap();
+    assert_eq!((900, 1000), (changed.first, changed.end));
+}
 
-    let line_to_type = "int c_var = 5;";
-    for c in line_to_type.chars() {
-        buffer.insert_char_at_cursor(c);
-    }
+#[test]
+fn test_duplicate_rows() {
+    let mut buffer = Buffer::with_text("one\ntwo\n");
+    buffer.append_row("three");
+    buffer.cursor.move_to(0, 2);
+    assert!(buffer.duplicate_rows(0, 0));
+    assert_eq!(
+        "one\none\ntwo\nthree",
+        buffer.rows_text(0..buffer.num_lines())
+    );
+    assert_eq!((1, 2), (buffer.cursor.text_row(), buffer.cursor.text_col()));
 
-    let expected_contents = vec![
-        format!("void main() {{{}", DEFAULT_NEWLINE_STR),
-        format!("  int a_var = 10;{}", DEFAULT_NEWLINE_STR),
-        format!("  int c_var = 5;{}", DEFAULT_NEWLINE_STR),
-        format!("  int b_var = 20;{}", DEFAULT_NEWLINE_STR),
-    ];
-    for (i, row) in expected_contents.iter().enumerate() {
-        assert_eq!(&row, &buffer.rows[i].as_str(), "Line {} is WRONG!", i);
-    }
-    assert_eq!(16, buffer.cursor.text_col());
+    // The last row gets a newline between it and its copy, and the
+    // selection moves onto the copy
+    let mut selection = Selection::new(Cursor::new(2, 0));
+    selection.extend_to(Cursor::new(3, 5));
+    buffer.selection = Some(selection);
+    assert!(buffer.duplicate_rows(2, 3));
+    assert_eq!(
+        "one\none\ntwo\nthree\ntwo\nthree",
+        buffer.rows_text(0..buffer.num_lines())
+    );
+    let selection = buffer.selection.unwrap();
+    assert_eq!(Cursor::new(4, 0), selection.start());
+    assert_eq!(Cursor::new(5, 5), selection.end());
+
+    assert!(buffer.undo());
+    assert_eq!(4, buffer.num_lines());
+    assert!(!buffer.duplicate_rows(4, 4));
+}
+
+#[test]
+fn test_move_rows() {
+    let mut buffer = Buffer::with_text("/*\nint a;\n*/\n");
+    buffer.append_row("int b;");
+    buffer.set_filetype("C");
+    assert_eq!(Highlight::MultilineComment, buffer.rows[1].hl[0]);
+    assert!(!buffer.move_rows(0, 0, Direction::Up));
+    assert!(!buffer.move_rows(3, 3, Direction::Down));
+
+    // Moving the end of the comment up takes the row after it out of the
+    // comment
+    buffer.cursor.move_to(2, 1);
+    assert!(buffer.move_rows(2, 2, Direction::Up));
+    assert_eq!(
+        "/*\n*/\nint a;\nint b;",
+        buffer.rows_text(0..buffer.num_lines())
+    );
+    assert_eq!((1, 1), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    assert_eq!(Highlight::Keyword2, buffer.rows[2].hl[0]);
+
+    // Line endings stay put, so the last row still hasn't got one
+    assert!(buffer.move_rows(1, 2, Direction::Down));
+    assert_eq!(
+        "/*\nint b;\n*/\nint a;",
+        buffer.rows_text(0..buffer.num_lines())
+    );
     assert_eq!(2, buffer.cursor.text_row());
+    assert_eq!(Highlight::MultilineComment, buffer.rows[1].hl[0]);
 
-    let mut hl = vec![Normal; 2];
-    hl.extend_from_slice(&[Keyword2; 3]);
-    hl.extend_from_slice(&[Normal; 9]);
-    hl.extend_from_slice(&[Number; 1]);
-    hl.extend_from_slice(&[Normal; 2]);
-    assert_eq!(hl, buffer.rows[2].hl);
+    assert!(buffer.undo());
+    assert_eq!(
+        "/*\n*/\nint a;\nint b;",
+        buffer.rows_text(0..buffer.num_lines())
+    );
 }
 
-// TODO: need a case for auto indent (or not) when inserting newline in the middle of a statement
-// TODO: case for tab indents
+#[test]
+fn test_join_rows() {
+    let mut buffer = Buffer::with_text("let a =   \n    1 +\n\n  2;\nlast\n");
+    assert!(buffer.join_rows(0, 0));
+    assert_eq!("let a = 1 +\n", buffer.rows[0].as_str());
+    assert_eq!((0, 7), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+
+    // No space is left next to an empty row
+    assert!(buffer.join_rows(0, 2));
+    assert_eq!("let a = 1 + 2;\n", buffer.rows[0].as_str());
+    assert_eq!("last\n", buffer.rows[1].as_str());
+    assert_eq!(11, buffer.cursor.text_col());
+
+    assert!(buffer.undo());
+    assert_eq!("let a = 1 +\n", buffer.rows[0].as_str());
+    assert_eq!(4, buffer.num_lines());
+    assert!(!buffer.join_rows(3, 3));
+}
+
+#[test]
+fn test_change_case() {
+    let mut buffer = Buffer::with_text("let größe = 1;\nÉCOLE normale\n");
+    buffer.cursor.move_to(0, 5);
+    assert!(buffer.change_case(Case::Upper));
+    assert_eq!("let GRÖSSE = 1;\n", buffer.rows[0].as_str());
+    assert_eq!(5, buffer.cursor.text_col());
+    // Already in that case, so there's nothing to undo
+    assert!(!buffer.change_case(Case::Upper));
+    assert!(buffer.undo());
+    assert_eq!("let größe = 1;\n", buffer.rows[0].as_str());
+
+    // From partway through a word and across rows
+    let mut selection = Selection::new(Cursor::new(0, 6));
+    selection.extend_to(Cursor::new(1, 7));
+    buffer.selection = Some(selection);
+    buffer.cursor.move_to(1, 7);
+    assert!(buffer.change_case(Case::Title));
+    assert_eq!("let größe = 1;\n", buffer.rows[0].as_str());
+    assert_eq!("École Normale\n", buffer.rows[1].as_str());
+    assert!(buffer.change_case(Case::Toggle));
+    assert_eq!("let grÖSSE = 1;\n", buffer.rows[0].as_str());
+    assert_eq!("éCOLE normale\n", buffer.rows[1].as_str());
+    assert!(buffer.undo());
+
+    // The end of the selection stays on the same letter when the text
+    // before it gets longer
+
+    let mut selection = Selection::new(Cursor::new(0, 4));
+    selection.extend_to(Cursor::new(0, 9));
+    buffer.selection = Some(selection);
+    buffer.cursor.move_to(0, 9);
+    assert!(buffer.change_case(Case::Upper));
+    assert_eq!("let GRÖSSE = 1;\n", buffer.rows[0].as_str());
+    assert_eq!(10, buffer.cursor.text_col());
+    assert_eq!(Cursor::new(0, 10), buffer.selection.unwrap().end());
+
+    // A block only changes its columns
+    let mut selection = Selection::new_block(Cursor::new(0, 0));
+    selection.extend_to(Cursor::new(1, 3));
+    buffer.selection = Some(selection);
+    assert!(buffer.change_case(Case::Upper));
+    assert_eq!("LET GRÖSSE = 1;\n", buffer.rows[0].as_str());
+    assert_eq!("ÉCOle Normale\n", buffer.rows[1].as_str());
+
+    buffer.selection = None;
+    buffer.cursor.move_to(0, 11);
+    assert!(!buffer.change_case(Case::Lower));
+}
+
+#[test]
+fn test_increment_number() {
+    let mut buffer = Buffer::with_text("let width = 0x0f;\nlet x = -1;\n");
+    buffer.cursor.move_to(0, 2);
+    assert!(buffer.increment_number(1));
+    assert_eq!("let width = 0x10;\n", buffer.rows[0].as_str());
+    assert_eq!(15, buffer.cursor.text_col());
+
+    buffer.cursor.move_to(1, 0);
+    assert!(buffer.increment_number(3));
+    assert_eq!("let x = 2;\n", buffer.rows[1].as_str());
+    assert_eq!((1, 8), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    buffer.cursor.move_to(1, 9);
+    assert!(!buffer.increment_number(1));
+
+    assert!(buffer.undo());
+    assert_eq!("let x = -1;\n", buffer.rows[1].as_str());
+}
+
+#[test]
+fn test_highlight_and_count_matches() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("one two one\r\n");
+    buffer.append_row("two\r\n");
+    buffer.append_row("one\r\n");
+    buffer.highlight_matches(0..3, "one", None, None);
+    let highlighted = |row: &Row| row.overlay.iter().filter(|item| item.is_some()).count();
+    assert_eq!(6, highlighted(&buffer.rows[0]));
+    assert_eq!(0, highlighted(&buffer.rows[1]));
+    assert_eq!(3, highlighted(&buffer.rows[2]));
+    assert_eq!((0, 3), buffer.count_matches("one", None, None, None));
+    assert_eq!(
+        (2, 3),
+        buffer.count_matches("one", None, None, Some((8, 0)))
+    );
+    assert_eq!(
+        (3, 3),
+        buffer.count_matches("one", None, None, Some((0, 2)))
+    );
+    assert_eq!((0, 0), buffer.count_matches("three", None, None, None));
+}
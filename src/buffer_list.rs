@@ -0,0 +1,115 @@
+// The buffers open in a pane, in the order they were opened. The one being
+// shown lives in the pane itself, the rest are parked here.
+pub struct BufferList<T> {
+    parked: Vec<T>,
+    current: usize,
+}
+
+impl<T> Default for BufferList<T> {
+    fn default() -> Self {
+        Self {
+            parked: Vec::new(),
+            current: 0,
+        }
+    }
+}
+
+impl<T> BufferList<T> {
+    // Including the one being shown
+    pub fn len(&self) -> usize {
+        self.parked.len() + 1
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    // Parks another buffer after all the others
+    pub fn add(&mut self, item: T) {
+        self.parked.push(item);
+    }
+
+    // The parked buffer at index, or None if that's the one being shown
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.current {
+            self.parked.get(index)
+        } else if index > self.current {
+            self.parked.get(index - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn parked(&self) -> impl Iterator<Item = &T> {
+        self.parked.iter()
+    }
+
+    pub fn parked_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.parked.iter_mut()
+    }
+
+    pub fn next_index(&self) -> usize {
+        (self.current + 1) % self.len()
+    }
+
+    pub fn prev_index(&self) -> usize {
+        (self.current + self.len() - 1) % self.len()
+    }
+
+    // Drops the one being shown and gives back the parked one to show in its
+    // place: the one after it, or the one before if it was the last. None
+    // when there's nothing else to show.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.parked.is_empty() {
+            return None;
+        }
+        if self.current == self.parked.len() {
+            self.current -= 1;
+        }
+        Some(self.parked.remove(self.current))
+    }
+
+    // Parks shown and returns the buffer at index to be shown instead, or
+    // gives shown back if it's already the one at index
+    pub fn switch_to(&mut self, index: usize, shown: T) -> T {
+        if index == self.current || index >= self.len() {
+            return shown;
+        }
+        self.parked.insert(self.current, shown);
+        self.current = index;
+        self.parked.remove(index)
+    }
+}
+
+#[test]
+fn test_buffer_list_switching() {
+    let mut list = BufferList::default();
+    list.add("b");
+    list.add("c");
+    assert_eq!(3, list.len());
+    assert_eq!(1, list.next_index());
+    assert_eq!(2, list.prev_index());
+
+    let shown = list.switch_to(2, "a");
+    assert_eq!("c", shown);
+    assert_eq!(2, list.current());
+    assert_eq!(Some(&"a"), list.get(0));
+    assert_eq!(Some(&"b"), list.get(1));
+    assert_eq!(None, list.get(2));
+    assert_eq!(0, list.next_index());
+
+    let shown = list.switch_to(0, shown);
+    assert_eq!("a", shown);
+    assert_eq!(Some(&"b"), list.get(1));
+    assert_eq!(Some(&"c"), list.get(2));
+    assert_eq!("a", list.switch_to(0, shown));
+
+    // Whatever's after the one being shown takes its place
+    assert_eq!(Some("b"), list.remove_current());
+    assert_eq!(0, list.current());
+    assert_eq!("c", list.switch_to(1, "b"));
+    // Or before it, when that was the last
+    assert_eq!(Some("b"), list.remove_current());
+    assert_eq!(0, list.current());
+    assert_eq!(None, list.remove_current());
+}
@@ -0,0 +1,70 @@
+// The rows of a buffer that have changed (in text or highlighting) since
+// whatever draws it last caught up, so only those need looking at again.
+// first and end are in today's row numbers. The rows before first are as
+// they were, and so are the rows from end on, just moved along by however
+// many rows have come or gone since there were old_num_lines of them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChangedRows {
+    pub first: usize,
+    pub end: usize,
+    pub old_num_lines: usize,
+}
+
+impl ChangedRows {
+    // Where the changed rows ended back when there were old_num_lines rows
+    pub fn old_end(&self, num_lines: usize) -> usize {
+        (self.end + self.old_num_lines).saturating_sub(num_lines)
+    }
+
+    // Adds on an edit that's just put new_rows rows where old_rows rows
+    // from at used to be, out of num_lines before the edit
+    pub fn record(
+        changed: Option<Self>,
+        at: usize,
+        old_rows: usize,
+        new_rows: usize,
+        num_lines: usize,
+    ) -> Self {
+        let edit_end = at + new_rows;
+        match changed {
+            None => Self {
+                first: at,
+                end: edit_end,
+                old_num_lines: num_lines,
+            },
+            Some(changed) => {
+                // Where the end of what had already changed has moved to
+                let end = if changed.end <= at {
+                    changed.end
+                } else if changed.end >= at + old_rows {
+                    changed.end + new_rows - old_rows
+                } else {
+                    edit_end
+                };
+                Self {
+                    first: changed.first.min(at),
+                    end: end.max(edit_end),
+                    old_num_lines: changed.old_num_lines,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_record_changed_rows() {
+    // Typing on row 5 of 10
+    let changed = ChangedRows::record(None, 5, 1, 1, 10);
+    assert_eq!((5, 6), (changed.first, changed.end));
+    assert_eq!(6, changed.old_end(10));
+
+    // Then splitting row 2 in two pushes the first change down a row
+    let changed = ChangedRows::record(Some(changed), 2, 1, 2, 10);
+    assert_eq!((2, 7), (changed.first, changed.end));
+    assert_eq!(6, changed.old_end(11));
+
+    // Deleting rows 8 and 9 of 11 leaves an empty change after the others
+    let changed = ChangedRows::record(Some(changed), 8, 2, 0, 11);
+    assert_eq!((2, 8), (changed.first, changed.end));
+    assert_eq!(9, changed.old_end(9));
+}
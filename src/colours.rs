@@ -38,6 +38,20 @@ impl Colour {
         )
     }
 
+    // Written like #rrggbb
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#')?;
+        if digits.len() != 6 || !digits.is_ascii() {
+            return None;
+        }
+        let channel = |at: usize| i32::from_str_radix(&digits[at..at + 2], 16).ok();
+        Some(Self::rgb_from_int_tuple((
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+        )))
+    }
+
     pub fn red(&self) -> f32 {
         self.rgba[0]
     }
@@ -135,6 +149,12 @@ impl Colour {
         hsl[2] = f32::max(0.0, hsl[2] - percentage);
         Colour::from_hsl(hsl)
     }
+
+    // Moves amount of the way towards other, keeping this colour's alpha
+    pub fn mix(&self, other: &Colour, amount: f32) -> Colour {
+        let channel = |i: usize| self.rgba[i] + (other.rgba[i] - self.rgba[i]) * amount;
+        Colour::new(channel(0), channel(1), channel(2), self.rgba[3])
+    }
 }
 
 #[cfg(test)]
@@ -231,4 +251,23 @@ mod test {
         let blue = Colour::rgb_from_int_tuple((0, 0, 255));
         assert_eq!(blue, Colour::from_hsl(blue.to_hsl()));
     }
+
+    #[test]
+    fn test_mix() {
+        let black = Colour::new(0.0, 0.0, 0.0, 1.0);
+        let white = Colour::new(1.0, 1.0, 1.0, 0.5);
+        assert_eq!(Colour::new(0.5, 0.5, 0.5, 1.0), black.mix(&white, 0.5));
+        assert_eq!(Colour::new(0.75, 0.75, 0.75, 0.5), white.mix(&black, 0.25));
+    }
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(
+            Some(Colour::rgb_from_int_tuple((255, 128, 0))),
+            Colour::from_hex("#ff8000")
+        );
+        assert_eq!(None, Colour::from_hex("ff8000"));
+        assert_eq!(None, Colour::from_hex("#ff80"));
+        assert_eq!(None, Colour::from_hex("#gg8000"));
+    }
 }
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -21,7 +22,7 @@ impl Default for SearchDirection {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -29,16 +30,29 @@ pub enum Direction {
     Right,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MoveUnit {
     Cols,
+    Words,
     Rows,
     Pages,
     Start,
     End,
+    Percent,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+// What the case commands turn the selection or word into
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Case {
+    Upper,
+    Lower,
+    // A capital to start each word and lowercase after that
+    Title,
+    // Capitals go lowercase and lowercase letters go to capitals
+    Toggle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MoveCursor {
     pub direction: Direction,
     pub unit: MoveUnit,
@@ -78,6 +92,22 @@ impl MoveCursor {
         }
     }
 
+    pub fn word_left(amount: usize) -> Self {
+        MoveCursor {
+            direction: Direction::Left,
+            unit: MoveUnit::Words,
+            amount,
+        }
+    }
+
+    pub fn word_right(amount: usize) -> Self {
+        MoveCursor {
+            direction: Direction::Right,
+            unit: MoveUnit::Words,
+            amount,
+        }
+    }
+
     pub fn up(amount: usize) -> Self {
         MoveCursor {
             direction: Direction::Up,
@@ -109,4 +139,20 @@ impl MoveCursor {
             amount,
         }
     }
+
+    pub fn percent(amount: usize) -> Self {
+        MoveCursor {
+            direction: Direction::Down,
+            unit: MoveUnit::Percent,
+            amount,
+        }
+    }
+
+    pub fn top() -> Self {
+        Self::percent(0)
+    }
+
+    pub fn bottom() -> Self {
+        Self::percent(100)
+    }
 }
@@ -0,0 +1,130 @@
+// Finishing the word being typed, with words from the rest of the buffer and
+// the filetype's keywords
+
+// How many completions the popup shows at once
+#[cfg(feature = "gui")]
+pub const COMPLETION_ROWS: usize = 8;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The words to offer for prefix, those in lines nearest cursor_row first,
+// then keywords. The prefix itself isn't one, as there'd be nothing to add.
+pub fn candidates(
+    lines: &[&str],
+    cursor_row: usize,
+    keywords: &[&str],
+    prefix: &str,
+) -> Vec<String> {
+    let mut by_distance = (0..lines.len()).collect::<Vec<_>>();
+    by_distance.sort_by_key(|row| (*row as isize - cursor_row as isize).abs());
+    let mut words: Vec<String> = vec![];
+    let mut offer = |word: &str| {
+        if word.len() > prefix.len()
+            && word.starts_with(prefix)
+            && !words.iter().any(|offered| offered == word)
+        {
+            words.push(word.to_string());
+        }
+    };
+    for row in by_distance {
+        for word in lines[row].split(|c: char| !is_word_char(c)) {
+            offer(word);
+        }
+    }
+    for keyword in keywords
+        .iter()
+        .filter(|keyword| keyword.chars().all(is_word_char))
+    {
+        offer(keyword);
+    }
+    words
+}
+
+// The words on offer for what's before the cursor, and which is picked
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    pub prefix: String,
+    pub words: Vec<String>,
+    selected: usize,
+}
+
+impl Completion {
+    // Nothing to show if there's nothing to offer
+    pub fn new(prefix: &str, words: Vec<String>) -> Option<Self> {
+        if words.is_empty() {
+            None
+        } else {
+            Some(Self {
+                prefix: prefix.to_string(),
+                words,
+                selected: 0,
+            })
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn chosen(&self) -> &str {
+        &self.words[self.selected]
+    }
+
+    // Going past either end wraps around to the other
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.words.len();
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = (self.selected + self.words.len() - 1) % self.words.len();
+    }
+
+    // The words that fit in the popup, keeping the selected one in view,
+    // and where they start in words
+    #[cfg(feature = "gui")]
+    pub fn shown(&self) -> (usize, &[String]) {
+        let first = (self.selected + 1).saturating_sub(COMPLETION_ROWS);
+        let last = (first + COMPLETION_ROWS).min(self.words.len());
+        (first, &self.words[first..last])
+    }
+}
+
+#[test]
+fn test_candidates_nearest_first() {
+    let lines = ["let counter = 0;", "", "count(); let country = 1;", "coun"];
+    assert_eq!(
+        vec!["count", "country", "counter"],
+        candidates(&lines, 3, &["const", "continue"], "coun")
+    );
+    assert_eq!(
+        vec!["counter", "count", "country", "coun", "const", "continue"],
+        candidates(&lines, 0, &["const", "continue", "&str"], "co")
+    );
+    assert!(candidates(&lines, 0, &[], "zebra").is_empty());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_completion_selection_wraps() {
+    let words = vec!["counter".to_string(), "country".to_string()];
+    let mut completion = Completion::new("coun", words).unwrap();
+    assert_eq!("counter", completion.chosen());
+    completion.select_prev();
+    assert_eq!("country", completion.chosen());
+    completion.select_next();
+    assert_eq!(0, completion.selected());
+    assert_eq!(None, Completion::new("x", vec![]));
+
+    let words = (0..20).map(|n| format!("word{}", n)).collect();
+    let mut long = Completion::new("w", words).unwrap();
+    for _ in 0..10 {
+        long.select_next();
+    }
+    let (first, shown) = long.shown();
+    assert_eq!(3, first);
+    assert_eq!(COMPLETION_ROWS, shown.len());
+    assert_eq!("word10", shown[COMPLETION_ROWS - 1]);
+}
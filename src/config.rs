@@ -1,3 +1,18 @@
+use crate::fonts::find_font;
+use crate::gutter::LineNumbers;
+use crate::keymap::{parse_action, parse_chord};
+use crate::options::{CursorStyle, Indentation, Options, ShowWhitespace};
+use crate::paths;
+use crate::snippet;
+use crate::status_line::parse_status_format;
+use crate::syntax::SYNTAXES;
+use crate::template;
+use crate::theme::Theme;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
 pub const TAB_STOP: usize = 8;
 pub const BIM_QUIT_TIMES: i8 = 3;
 
@@ -13,3 +28,494 @@ impl Default for RunConfig {
         RunConfig::Run
     }
 }
+
+// bim/bimrc in the config directory, if there's one to look in
+pub fn rc_path() -> Option<PathBuf> {
+    paths::rc_file()
+}
+
+// Applies the settings in the config file on top of options, which is meant to
+// happen before the command line flags get their turn so that they win.
+// Not having a config file is fine; the problems with each line of the
+// one there is are given back.
+pub fn load_rc(options: &mut Options) -> Vec<String> {
+    let path = match rc_path() {
+        Some(path) => path,
+        None => return vec![],
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => apply_rc(options, &contents)
+            .into_iter()
+            .map(|problem| format!("{}: {}", path.display(), problem))
+            .collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+        Err(err) => vec![format!("Can't read {}: {}", path.display(), err)],
+    }
+}
+
+// Adds the snippets in the snippets directory to the built in ones, giving
+// back what was wrong with any files that couldn't be used
+pub fn load_snippets(options: &mut Options) -> Vec<String> {
+    let dir = match paths::snippets_dir() {
+        Some(dir) => dir,
+        None => return vec![],
+    };
+    let (snippets, problems) = snippet::load_dir(&dir);
+    options.snippets.extend(snippets);
+    problems
+}
+
+// Adds the templates in the templates directory, which take the place of
+// the built in ones for the same files
+pub fn load_templates(options: &mut Options) -> Vec<String> {
+    let dir = match paths::templates_dir() {
+        Some(dir) => dir,
+        None => return vec![],
+    };
+    let (templates, problems) = template::load_dir(&dir);
+    template::add_templates(&mut options.templates, templates);
+    problems
+}
+
+// Lines are "setting = value", "bind keys = action" or "unbind keys",
+// and a # at the start of a line or after a space starts a comment
+pub fn apply_rc(options: &mut Options, contents: &str) -> Vec<String> {
+    let mut problems = vec![];
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let result = if let Some(binding) = line.strip_prefix("bind ") {
+            // Keys can be =, so the action is after the last one
+            match binding.rfind('=') {
+                Some(at) => bind(options, &binding[..at], binding[at + 1..].trim()),
+                None => Err(format!("expected bind keys = action, got {:?}", line)),
+            }
+        } else if let Some(chord) = line.strip_prefix("unbind ") {
+            unbind(options, chord)
+        } else {
+            match line.find('=') {
+                Some(at) => apply_setting(options, line[..at].trim(), line[at + 1..].trim()),
+                None => Err(format!("expected setting = value, got {:?}", line)),
+            }
+        };
+        if let Err(problem) = result {
+            problems.push(format!("line {}: {}", line_idx + 1, problem));
+        }
+    }
+    problems
+}
+
+// A # that's part of a value, like the one in C-#, isn't a comment
+fn strip_comment(line: &str) -> &str {
+    let mut before = None;
+    for (at, c) in line.char_indices() {
+        if c == '#' && before.is_none_or(char::is_whitespace) {
+            return &line[..at];
+        }
+        before = Some(c);
+    }
+    line
+}
+
+pub fn apply_setting(options: &mut Options, setting: &str, value: &str) -> Result<(), String> {
+    match setting {
+        "font_size" => options.font_size = parse_positive(value)?,
+        "quit_warning" => options.no_quit_warning = !parse_bool(value)?,
+        "vsplit" => options.vsplit = parse_bool(value)?,
+        "virtual_edit" => options.virtual_edit = parse_bool(value)?,
+        "auto_close" => options.auto_close = parse_bool(value)?,
+        "whitespace" => {
+            options.show_whitespace = match value {
+                "off" => ShowWhitespace::Off,
+                "trailing" => ShowWhitespace::Trailing,
+                "all" => ShowWhitespace::All,
+                _ => return Err(format!("whitespace can't be {:?}", value)),
+            }
+        }
+        "trim_trailing_whitespace" => options.trim_trailing_whitespace = parse_bool(value)?,
+        "cursor" => {
+            options.cursor_style = match value {
+                "block" => CursorStyle::Block,
+                "bar" => CursorStyle::Bar,
+                "underline" => CursorStyle::Underline,
+                _ => return Err(format!("cursor can't be {:?}", value)),
+            }
+        }
+        "cursor_blink" => {
+            options.cursor_blink = match value {
+                "off" | "0" => None,
+                _ => Some(parse_millis(value)?),
+            }
+        }
+        "line_numbers" => {
+            options.line_numbers = match value {
+                "off" => LineNumbers::Off,
+                "absolute" => LineNumbers::Absolute,
+                "relative" => LineNumbers::Relative,
+                _ => return Err(format!("line_numbers can't be {:?}", value)),
+            }
+        }
+        "dim_unfocused" => options.dim_unfocused = parse_bool(value)?,
+        "minimap" => options.minimap = parse_bool(value)?,
+        "restore_session" => options.restore_session = parse_bool(value)?,
+        "hide_mouse_while_typing" => options.hide_mouse_while_typing = parse_bool(value)?,
+        "single_instance" => options.single_instance = parse_bool(value)?,
+        "log_stats" => options.log_stats = parse_bool(value)?,
+        "create_dirs" => options.create_dirs = parse_bool(value)?,
+        "max_line_length" => {
+            options.max_line_length = match value {
+                "off" | "0" => None,
+                _ => Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("expected a line length or off, got {:?}", value))?,
+                ),
+            }
+        }
+        "complete_after" => {
+            options.complete_after = match value {
+                "off" | "0" => None,
+                _ => Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("expected a word length or off, got {:?}", value))?,
+                ),
+            }
+        }
+        "large_file_mb" => options.large_file_len = parse_megabytes(value)?,
+        "lazy_highlight_mb" => options.lazy_highlight_len = parse_megabytes(value)?,
+        "save_power_after" => {
+            options.save_power_after = match value {
+                "off" | "0" => None,
+                _ => Some(Duration::from_secs(value.parse().map_err(|_| {
+                    format!("expected seconds or off, got {:?}", value)
+                })?)),
+            }
+        }
+        "theme" => options.theme = Theme::load(value)?,
+        "font" => options.font = Some(parse_font(value)?),
+        "fallback_fonts" => {
+            options.fallback_fonts = value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(parse_font)
+                .collect::<Result<_, _>>()?
+        }
+        "status_line" => options.status_format = parse_status_format(value)?,
+        "line_spacing" => options.spacing.line_spacing = parse_positive(value)?,
+        "padding" => {
+            let padding = parse_number(value)?;
+            options.spacing.left_padding = padding;
+            options.spacing.top_padding = padding;
+        }
+        "left_padding" => options.spacing.left_padding = parse_number(value)?,
+        "top_padding" => options.spacing.top_padding = parse_number(value)?,
+        "tooltip_delay" => options.tooltip_delay = parse_millis(value)?,
+        "escape_chord" => {
+            let mut chars = value.chars();
+            options.escape_chord = match (chars.next(), chars.next(), chars.next()) {
+                (Some(first), Some(second), None) => Some((first, second)),
+                (None, ..) => None,
+                _ => {
+                    return Err(format!(
+                        "escape_chord needs two characters, got {:?}",
+                        value
+                    ))
+                }
+            }
+        }
+        "escape_chord_timeout" => options.escape_chord_timeout = parse_millis(value)?,
+        "guides" => options.guides.global = parse_guides(value)?,
+        "expand_tabs" | "tab_width" | "indent_width" => {
+            set_indentation(&mut options.indent.global, setting, value)?
+        }
+        _ => match setting.split_once('.') {
+            // Like rust.tab_width or rust.language_server, just for that filetype
+            Some((filetype, setting)) => {
+                let filetype = SYNTAXES
+                    .iter()
+                    .find(|syntax| syntax.filetype.eq_ignore_ascii_case(filetype))
+                    .map(|syntax| syntax.filetype)
+                    .ok_or_else(|| format!("unknown filetype {:?}", filetype))?;
+                if setting == "language_server" {
+                    set_language_server(options, filetype, value);
+                } else if setting == "guides" {
+                    let guides = parse_guides(value)?;
+                    options
+                        .guides
+                        .filetypes
+                        .insert(filetype.to_string(), guides);
+                } else {
+                    set_indentation(options.indent.for_filetype_mut(filetype), setting, value)?
+                }
+            }
+            None => return Err(format!("unknown setting {:?}", setting)),
+        },
+    }
+    Ok(())
+}
+
+fn set_indentation(
+    indentation: &mut Indentation,
+    setting: &str,
+    value: &str,
+) -> Result<(), String> {
+    match setting {
+        "expand_tabs" => indentation.expand_tabs = parse_bool(value)?,
+        "tab_width" => indentation.tab_width = parse_width(value)?,
+        "indent_width" => indentation.indent_width = parse_width(value)?,
+        _ => return Err(format!("unknown setting {:?}", setting)),
+    }
+    Ok(())
+}
+
+fn set_language_server(options: &mut Options, filetype: &str, value: &str) {
+    if value.is_empty() || value == "off" {
+        options.language_servers.remove(filetype);
+    } else {
+        options
+            .language_servers
+            .insert(filetype.to_string(), value.to_string());
+    }
+}
+
+fn bind(options: &mut Options, chord: &str, action: &str) -> Result<(), String> {
+    let keys = parse_chord(chord)?;
+    let action = parse_action(action)?;
+    options.keymap.bind(&keys, action);
+    Ok(())
+}
+
+fn unbind(options: &mut Options, chord: &str) -> Result<(), String> {
+    if options.keymap.unbind(&parse_chord(chord)?) {
+        Ok(())
+    } else {
+        Err(format!("nothing is bound to {}", chord.trim()))
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected true or false, got {:?}", value)),
+    }
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number, got {:?}", value))
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match parse_number(value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("expected a number above 0, got {:?}", value)),
+    }
+}
+
+fn parse_width(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|width| *width > 0)
+        .ok_or_else(|| format!("expected a width of at least 1, got {:?}", value))
+}
+
+// Columns like "80, 100", or "off" for none
+fn parse_guides(value: &str) -> Result<Vec<usize>, String> {
+    if value == "off" {
+        return Ok(vec![]);
+    }
+    value
+        .split([',', ' '])
+        .filter(|col| !col.is_empty())
+        .map(|col| {
+            parse_width(col).map_err(|_| format!("expected columns or off, got {:?}", value))
+        })
+        .collect()
+}
+
+// In bytes, with off or 0 for no limit at all
+pub fn parse_megabytes(value: &str) -> Result<Option<u64>, String> {
+    match value {
+        "off" | "0" => Ok(None),
+        _ => value
+            .parse::<u64>()
+            .map_err(|_| format!("expected megabytes or off, got {:?}", value))?
+            .checked_mul(1024 * 1024)
+            .map(Some)
+            .ok_or_else(|| format!("{} megabytes is too many", value)),
+    }
+}
+
+fn parse_millis(value: &str) -> Result<Duration, String> {
+    value
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("expected milliseconds, got {:?}", value))
+}
+
+// A font file, or the family name of an installed font
+fn parse_font(value: &str) -> Result<String, String> {
+    match find_font(value) {
+        Some(_) => Ok(value.to_string()),
+        None => Err(format!("can't find a font called {:?}", value)),
+    }
+}
+
+#[test]
+fn test_apply_rc() {
+    let mut options = Options::default();
+    let problems = apply_rc(
+        &mut options,
+        "# comment\n\
+         font_size = 18\n\
+         quit_warning = off  # trailing comment\n\
+         \n\
+         line_numbers = relative\n\
+         line_spacing = 1.2\n\
+         escape_chord = jk\n\
+         max_line_length = 100\n\
+         complete_after = 3\n\
+         large_file_mb = 64\n\
+         lazy_highlight_mb = off\n\
+         save_power_after = off\n\
+         auto_close = no\n\
+         whitespace = all\n\
+         guides = 72, 100\n\
+         cursor = bar\n\
+         cursor_blink = off\n\
+         rust.guides = off\n\
+         trim_trailing_whitespace = yes\n\
+         tab_width = 4\n\
+         ruby.expand_tabs = false\n\
+         rust.language_server = rust-analyzer\n\
+         status_line = filename, dirty, percent\n",
+    );
+    assert!(problems.is_empty(), "{:?}", problems);
+    assert_eq!(18.0, options.font_size);
+    assert!(!options.show_quit_warning());
+    assert_eq!(LineNumbers::Relative, options.line_numbers);
+    assert_eq!(1.2, options.spacing.line_spacing);
+    assert_eq!(Some(('j', 'k')), options.escape_chord);
+    assert_eq!(Some(100), options.max_line_length);
+    assert_eq!(Some(3), options.complete_after);
+    assert_eq!(Some(64 * 1024 * 1024), options.large_file_len);
+    assert_eq!(None, options.lazy_highlight_len);
+    assert_eq!(None, options.save_power_after);
+    assert!(!options.auto_close);
+    assert_eq!(ShowWhitespace::All, options.show_whitespace);
+    assert!(options.trim_trailing_whitespace);
+    assert_eq!(&[72, 100], options.guides.for_filetype("C"));
+    assert_eq!(CursorStyle::Bar, options.cursor_style);
+    assert_eq!(None, options.cursor_blink);
+    assert!(options.guides.for_filetype("Rust").is_empty());
+    assert_eq!(4, options.indent.global.tab_width);
+    assert_eq!(3, options.status_format.len());
+    assert_eq!(
+        Some("rust-analyzer"),
+        options.language_servers.get("Rust").map(String::as_str)
+    );
+    let ruby = options
+        .indent
+        .for_syntax(crate::syntax::Syntax::for_filetype("Ruby"));
+    assert!(!ruby.expand_tabs);
+    assert_eq!(2, ruby.indent_width);
+    let c = options
+        .indent
+        .for_syntax(crate::syntax::Syntax::for_filetype("C"));
+    assert_eq!(options.indent.global, c);
+}
+
+#[test]
+fn test_apply_rc_reports_bad_lines() {
+    let mut options = Options::default();
+    let problems = apply_rc(
+        &mut options,
+        "font_size = big\nvsplit = yes\nnonsense\ncolour = red\nguides = wide\n\
+         large_file_mb = 99999999999999\n",
+    );
+    assert_eq!(
+        vec![
+            "line 1: expected a number, got \"big\"".to_string(),
+            "line 3: expected setting = value, got \"nonsense\"".to_string(),
+            "line 4: unknown setting \"colour\"".to_string(),
+            "line 5: expected columns or off, got \"wide\"".to_string(),
+            "line 6: 99999999999999 megabytes is too many".to_string(),
+        ],
+        problems
+    );
+    assert_eq!(Options::default().font_size, options.font_size);
+    assert_eq!(Options::default().large_file_len, options.large_file_len);
+    assert!(options.vsplit);
+}
+
+#[test]
+fn test_apply_rc_fonts() {
+    let font_path = crate::utils::temp_path("bim-config-font", ".ttf");
+    fs::write(&font_path, b"").unwrap();
+    let font = font_path.to_string_lossy().to_string();
+    let mut options = Options::default();
+    let problems = apply_rc(
+        &mut options,
+        &format!(
+            "font = {}\nfallback_fonts = {}, {}\nfont = No Such Font Anywhere\n",
+            font, font, font
+        ),
+    );
+    fs::remove_file(&font_path).unwrap();
+    assert_eq!(
+        vec!["line 3: can't find a font called \"No Such Font Anywhere\"".to_string()],
+        problems
+    );
+    assert_eq!(Some(font.clone()), options.font);
+    assert_eq!(vec![font.clone(), font], options.fallback_fonts);
+}
+
+#[test]
+fn test_apply_rc_bindings() {
+    use crate::action::{Action, BufferAction, WindowAction};
+    use crate::keycodes::Key;
+    use crate::keymap::MapOrAction;
+
+    let mut options = Options::default();
+    let problems = apply_rc(
+        &mut options,
+        "bind C-= = SplitVertically\n\
+         bind C-# = SplitHorizontally # not a comment until here\n\
+         bind C-x C-c = Explode\n\
+         unbind C-t\n\
+         unbind C-t\n",
+    );
+    assert_eq!(
+        vec![
+            "line 3: unknown action \"Explode\"".to_string(),
+            "line 5: nothing is bound to C-t".to_string(),
+        ],
+        problems
+    );
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnWindow(
+            WindowAction::SplitVertically
+        ))),
+        options.keymap.lookup(&Key::Control(Some('=')))
+    );
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnWindow(
+            WindowAction::SplitHorizontally
+        ))),
+        options.keymap.lookup(&Key::Control(Some('#')))
+    );
+    // The binding that was there before is left alone
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnBuffer(
+            BufferAction::IncrementNumber(-1)
+        ))),
+        options.keymap.lookup(&Key::Control(Some('x')))
+    );
+}
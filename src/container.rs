@@ -0,0 +1,1088 @@
+use crate::action::{BufferAction, GuiAction, PaneAction, WindowAction};
+use crate::buffer::{Buffer, FileSaveStatus};
+use crate::commands::Direction;
+use crate::cursor::{Cursor, CursorT};
+#[cfg(feature = "gui")]
+use crate::idle::PowerMode;
+#[cfg(feature = "gui")]
+use crate::layout::Divider;
+use crate::layout::{self, Arrangement, Layout};
+#[cfg(feature = "gui")]
+use crate::mouse::{ClickCount, MouseMove};
+use crate::options::Options;
+use crate::palette::Palette;
+use crate::pane::Pane;
+use crate::rect::Rect;
+#[cfg(feature = "gui")]
+use crate::rect::RectBuilder;
+#[cfg(feature = "gui")]
+use crate::renderer::{Renderer, Text};
+use crate::session::Session;
+use crate::stats::EditStats;
+use crate::theme::Theme;
+use glam::{vec2, Vec2};
+use std::error::Error;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg(feature = "gui")]
+const DIVIDER_LABEL_BG: [f32; 3] = [0.1, 0.1, 0.1];
+const MAX_CLOSED_PANES: usize = 10;
+// How close to a divider a click has to be to pick it up
+#[cfg(feature = "gui")]
+const DIVIDER_GRAB: f32 = 4.0;
+// Neither dragging a divider nor resizing from the keyboard makes a pane
+// smaller than this, and each key press resizes it by RESIZE_STEP
+const MIN_PANE_SIZE: f32 = 60.0;
+const RESIZE_STEP: f32 = 40.0;
+
+// Enough to bring a closed pane back, split the way it was
+#[derive(Clone, Debug, PartialEq)]
+struct ClosedPane {
+    filename: String,
+    cursor: Cursor,
+    arrangement: Arrangement,
+}
+
+// A divider being dragged. The panes are only resized when it's let go,
+// until then it's drawn where it would end up.
+#[cfg(feature = "gui")]
+struct DividerDrag {
+    divider: Divider,
+    pointer: Vec2,
+}
+
+pub struct Container<'a> {
+    focused_idx: usize,
+    panes: Vec<Pane<'a>>,
+    closed_panes: Vec<ClosedPane>,
+    bounds: Vec2,
+    position: Vec2,
+    layout: Layout,
+    #[cfg(feature = "gui")]
+    divider_drag: Option<DividerDrag>,
+    theme: Rc<Theme>,
+    // Something besides a pane's own contents has changed, like the layout,
+    // so everything's drawn again
+    damaged: bool,
+}
+
+impl<'a> Default for Container<'a> {
+    fn default() -> Self {
+        Self {
+            focused_idx: 0,
+            panes: Vec::new(),
+            closed_panes: Vec::new(),
+            bounds: vec2(0.0, 0.0),
+            position: vec2(0.0, 0.0),
+            layout: Layout::default(),
+            #[cfg(feature = "gui")]
+            divider_drag: None,
+            theme: Rc::new(Theme::default()),
+            damaged: true,
+        }
+    }
+}
+
+impl<'a> Container<'a> {
+    pub fn single(bounds: Vec2, position: Vec2, pane: Pane<'a>) -> Self {
+        Self {
+            bounds,
+            position,
+            panes: vec![pane],
+            layout: Layout::Pane(0),
+            ..Container::default()
+        }
+    }
+
+    // A single empty pane the same size as this, set up like its focused one
+    pub fn empty_like(&self) -> Self {
+        let mut container = Self::single(
+            self.bounds,
+            self.position,
+            self.new_pane(Buffer::default(), true),
+        );
+        container.theme = self.theme.clone();
+        container
+    }
+
+    fn set_focused_idx(&mut self, idx: usize) {
+        self.focused_idx = idx;
+    }
+
+    // Adds the pane next to the focused one and returns its index
+    fn split_focused_pane(&mut self, pane: Pane<'a>, arrangement: Arrangement) -> usize {
+        let pane_idx = self.panes.len();
+        self.panes.push(pane);
+        if !self.layout.split(self.focused_idx, pane_idx, arrangement) {
+            self.layout = Layout::Pane(pane_idx);
+        }
+        pane_idx
+    }
+
+    fn pane_rects(&self) -> Vec<(usize, Rect)> {
+        self.layout.pane_rects(self.position, self.bounds)
+    }
+
+    fn recalculate_layout(&mut self) {
+        for (pane_idx, rect) in self.pane_rects() {
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.do_action(PaneAction::UpdateSize(rect.bounds, rect.top_left));
+            }
+        }
+    }
+
+    fn new_pane(&self, mut buffer: Buffer<'a>, focused: bool) -> Pane<'a> {
+        if let Some(pane) = self.panes.get(self.focused_idx) {
+            buffer.set_virtual_edit(pane.buffer.virtual_edit());
+            buffer.set_auto_close(pane.buffer.auto_close());
+            buffer.set_trim_trailing_whitespace(pane.buffer.trim_trailing_whitespace());
+            buffer.set_indent_options(pane.buffer.indent_options().clone());
+            let mut new_pane = Pane::new(pane.font_size, pane.ui_scale, buffer, focused);
+            new_pane.set_line_numbers(pane.line_numbers());
+            new_pane.set_spacing(pane.spacing());
+            new_pane.set_dim_unfocused(pane.dim_unfocused());
+            new_pane.set_show_minimap(pane.show_minimap());
+            new_pane.set_max_line_length(pane.max_line_length());
+            new_pane.set_complete_after(pane.complete_after());
+            new_pane.set_show_whitespace(pane.show_whitespace());
+            new_pane.set_cursor_style(pane.cursor_style());
+            new_pane.set_cursor_blink(pane.cursor_blink());
+            new_pane.set_guides(pane.guides().clone());
+            new_pane.set_snippets(pane.snippets());
+            new_pane.set_status_format(pane.status_format());
+            new_pane.set_theme(self.theme.clone());
+            new_pane
+        } else {
+            // FIXME: Where to get the default font_size and ui_scale from?
+            let mut new_pane = Pane::new(12.0, 1.0, buffer, focused);
+            new_pane.set_theme(self.theme.clone());
+            new_pane
+        }
+    }
+
+    pub fn num_panes(&self) -> usize {
+        self.panes.len()
+    }
+
+    pub fn focused_pane_stats(&self) -> Vec<(String, EditStats)> {
+        self.panes
+            .get(self.focused_idx)
+            .map(|pane| pane.buffer_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn all_stats(&self) -> Vec<(String, EditStats)> {
+        self.panes
+            .iter()
+            .flat_map(|pane| pane.buffer_stats())
+            .collect()
+    }
+
+    // The buffer with path open in any pane
+    pub fn buffer_for_path_mut(&mut self, path: &Path) -> Option<&mut Buffer<'a>> {
+        self.panes
+            .iter_mut()
+            .find_map(|pane| pane.buffer_for_path_mut(path))
+    }
+
+    pub fn results_buffer_mut(&mut self, id: usize) -> Option<&mut Buffer<'a>> {
+        self.panes
+            .iter_mut()
+            .find_map(|pane| pane.results_buffer_mut(id))
+    }
+
+    pub fn remove_swap_files(&mut self) {
+        for pane in self.panes.iter_mut() {
+            pane.remove_swap_files();
+        }
+    }
+
+    pub fn focused_buffer(&self) -> Option<&Buffer<'a>> {
+        self.panes.get(self.focused_idx).map(|pane| &pane.buffer)
+    }
+
+    pub fn focused_buffer_mut(&mut self) -> Option<&mut Buffer<'a>> {
+        self.panes
+            .get_mut(self.focused_idx)
+            .map(|pane| &mut pane.buffer)
+    }
+
+    // The buffer each pane is showing
+    pub fn shown_buffers(&self) -> impl Iterator<Item = &Buffer<'a>> {
+        self.panes.iter().map(|pane| &pane.buffer)
+    }
+
+    // Shows path in the focused pane if it's open there, parked or not
+    pub fn switch_to_path(&mut self, path: &Path) -> bool {
+        self.panes
+            .get_mut(self.focused_idx)
+            .map(|pane| pane.switch_to_path(path))
+            .unwrap_or(false)
+    }
+
+    pub fn goto(&mut self, row: usize, col: usize) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.goto(row, col);
+        }
+    }
+
+    pub fn jump_to_mark(&mut self, name: char) -> bool {
+        self.panes
+            .get_mut(self.focused_idx)
+            .map(|pane| pane.jump_to_mark(name))
+            .unwrap_or(false)
+    }
+
+    pub fn replace_rows_with_text(&mut self, rows: Range<usize>, text: &str) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.replace_rows_with_text(rows, text);
+        }
+    }
+
+    pub fn update_gui(&mut self, action: GuiAction) {
+        if let GuiAction::UpdateSize(bounds, position) = action {
+            self.bounds = bounds;
+            self.position = position;
+            self.recalculate_layout();
+        } else {
+            for pane in self.panes.iter_mut() {
+                pane.update_gui(action.clone());
+            }
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn render(&self, renderer: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        for rect in self.layout.borders(self.position, self.bounds) {
+            renderer.draw_quad(self.theme.pane_border.rgb(), rect, 0.5);
+        }
+
+        for (pane_idx, pane) in self.panes.iter().enumerate() {
+            pane.render(renderer, pane_idx == self.focused_idx)?;
+        }
+
+        self.render_divider_preview(renderer)?;
+
+        Ok(())
+    }
+
+    // The layout as it would be if the divider being dragged was let go
+    #[cfg(feature = "gui")]
+    fn preview_layout(&self) -> Option<(Layout, &DividerDrag)> {
+        let drag = self.divider_drag.as_ref()?;
+        let mut layout = self.layout.clone();
+        layout.move_divider(
+            &drag.divider,
+            drag.divider.along(drag.pointer),
+            self.min_pane_size(),
+        );
+        Some((layout, drag))
+    }
+
+    // The size each pane would be, for the panes the drag resizes
+    #[cfg(feature = "gui")]
+    fn divider_preview_label(&self, preview: &Layout) -> String {
+        let current = self.pane_rects();
+        preview
+            .pane_rects(self.position, self.bounds)
+            .into_iter()
+            .filter(|(pane_idx, rect)| {
+                current
+                    .iter()
+                    .any(|(idx, old)| idx == pane_idx && old.bounds != rect.bounds)
+            })
+            .filter_map(|(pane_idx, rect)| {
+                let (cols, rows) = self.panes.get(pane_idx)?.text_size_in(rect.bounds);
+                Some(format!("{}x{}", cols, rows))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_divider_preview(&self, renderer: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        let (preview, drag) = match self.preview_layout() {
+            Some(preview) => preview,
+            None => return Ok(()),
+        };
+        let _guard = flame::start_guard("render divider preview");
+
+        if let Some(divider) = preview
+            .dividers(self.position, self.bounds)
+            .into_iter()
+            .find(|divider| divider.is_same(&drag.divider))
+        {
+            let thickness = match divider.arrangement {
+                Arrangement::VSplit => vec2(2.0, 0.0),
+                Arrangement::HSplit => vec2(0.0, 2.0),
+            };
+            let rect = RectBuilder::new()
+                .top_left(divider.rect.top_left - thickness / 2.0)
+                .bounds(divider.rect.bounds + thickness)
+                .build();
+            renderer.draw_quad(self.theme.divider_preview.rgb(), rect, 0.4);
+        }
+
+        let label = self.divider_preview_label(&preview);
+        let font_scale = match self.panes.get(self.focused_idx) {
+            Some(pane) => pane.font_size * pane.ui_scale,
+            None => return Ok(()),
+        };
+        if label.is_empty() {
+            return Ok(());
+        }
+        let label_pos = drag.pointer + vec2(16.0, 16.0);
+        let label_text = Text {
+            bounds: self.position + self.bounds - label_pos,
+            position: label_pos,
+            scale: font_scale,
+            z: 0.3,
+            ..Text::plain(&label, self.theme.popup_fg.rgba())
+        };
+        if let Some(text_bounds) = renderer.measure(&label_text) {
+            let label_rect = RectBuilder::new()
+                .top_left(label_pos - vec2(4.0, 4.0))
+                .bounds(text_bounds + vec2(8.0, 8.0))
+                .build();
+            renderer.draw_quad(DIVIDER_LABEL_BG, label_rect, 0.35);
+        }
+        renderer.draw_text(label_text)
+    }
+
+    #[cfg(feature = "gui")]
+    fn which_pane_is_location(&self, location: Vec2) -> Option<usize> {
+        self.pane_rects()
+            .into_iter()
+            .find(|(_, rect)| {
+                let offset = location - rect.top_left;
+                offset.x() >= 0.0
+                    && offset.y() >= 0.0
+                    && offset.x() < rect.bounds.x()
+                    && offset.y() < rect.bounds.y()
+            })
+            .map(|(pane_idx, _)| pane_idx)
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn mouse_scroll(&mut self, mouse_location: Vec2, delta: MouseMove) {
+        if let Some(pane_idx) = self.which_pane_is_location(mouse_location) {
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.do_action(PaneAction::MouseScroll(delta));
+            }
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn absolute_position_to_pane_relative(&self, pane_idx: usize, location: Vec2) -> Vec2 {
+        match self.pane_rects().iter().find(|(idx, _)| *idx == pane_idx) {
+            Some((_, rect)) => location - rect.top_left,
+            None => location,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn mouse_click(
+        &mut self,
+        location: Vec2,
+        extend_selection: bool,
+        block_select: bool,
+        count: ClickCount,
+    ) {
+        if let Some(divider) =
+            self.layout
+                .divider_at(self.position, self.bounds, location, DIVIDER_GRAB)
+        {
+            self.divider_drag = Some(DividerDrag {
+                divider,
+                pointer: location,
+            });
+            return;
+        }
+        if let Some(pane_idx) = self.which_pane_is_location(location) {
+            self.focus_pane_index(pane_idx);
+            let pane_location = self.absolute_position_to_pane_relative(pane_idx, location);
+            println!(
+                "abs location: {:?}, pane_local: {:?}",
+                location, pane_location
+            );
+            let action = match count {
+                _ if extend_selection => PaneAction::MouseShiftClick(pane_location),
+                _ if block_select => PaneAction::MouseAltClick(pane_location),
+                ClickCount::Single => PaneAction::MouseClick(pane_location),
+                ClickCount::Double => PaneAction::MouseDoubleClick(pane_location),
+                ClickCount::Triple => PaneAction::MouseTripleClick(pane_location),
+            };
+            if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+                pane.do_action(action);
+            }
+        }
+    }
+
+    // Drags belong to the pane the button was pressed in, wherever they go
+    #[cfg(feature = "gui")]
+    pub fn mouse_drag(&mut self, location: Vec2) {
+        self.damaged = true;
+        if let Some(drag) = self.divider_drag.as_mut() {
+            drag.pointer = location;
+            return;
+        }
+        let pane_location = self.absolute_position_to_pane_relative(self.focused_idx, location);
+        self.do_pane_action(PaneAction::MouseDrag(pane_location));
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn mouse_release(&mut self) {
+        if let Some(drag) = self.divider_drag.take() {
+            let at = drag.divider.along(drag.pointer);
+            if self
+                .layout
+                .move_divider(&drag.divider, at, self.min_pane_size())
+            {
+                self.recalculate_layout();
+            }
+            return;
+        }
+        self.do_pane_action(PaneAction::MouseRelease);
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn hover_info(&self, location: Vec2) -> Option<String> {
+        let pane_idx = self.which_pane_is_location(location)?;
+        let pane_location = self.absolute_position_to_pane_relative(pane_idx, location);
+        self.panes
+            .get(pane_idx)
+            .and_then(|pane| pane.hover_info(pane_location))
+    }
+
+    pub fn update_dt(&mut self, dt: Duration) {
+        for pane in self.panes.iter_mut() {
+            pane.update_dt(dt);
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Rc<Theme>) {
+        for pane in self.panes.iter_mut() {
+            pane.set_theme(theme.clone());
+        }
+        self.theme = theme;
+    }
+
+    pub fn pick_theme(&mut self, names: &[String]) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.pick_theme(names);
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn set_power_mode(&mut self, power_mode: PowerMode) {
+        for pane in self.panes.iter_mut() {
+            pane.set_power_mode(power_mode);
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn is_busy(&self) -> bool {
+        self.panes.iter().any(|pane| pane.is_busy())
+    }
+
+    pub fn damage(&mut self) {
+        self.damaged = true;
+    }
+
+    // Whether anything's changed since it was last drawn
+    pub fn needs_redraw(&self) -> bool {
+        self.damaged || self.panes.iter().any(|pane| pane.is_damaged())
+    }
+
+    pub fn clear_damage(&mut self) {
+        self.damaged = false;
+        for pane in self.panes.iter_mut() {
+            pane.clear_damage();
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn idle_work(&mut self) {
+        for pane in self.panes.iter_mut() {
+            pane.idle_work();
+        }
+    }
+
+    pub fn do_pane_action(&mut self, action: PaneAction) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.do_action(action);
+        }
+    }
+
+    pub fn inspect_char(&self) -> Option<String> {
+        self.panes
+            .get(self.focused_idx)
+            .and_then(|pane| pane.buffer.inspect_char_at_cursor())
+    }
+
+    pub fn current_revision(&self) -> u64 {
+        self.panes
+            .get(self.focused_idx)
+            .map(|pane| pane.buffer.revision())
+            .unwrap_or(0)
+    }
+
+    pub fn update_current_buffer(&mut self, action: BufferAction) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.update_buffer(action);
+        }
+    }
+
+    // Opens buffer in the focused pane, behind the one it's showing
+    pub fn add_buffer(&mut self, buffer: Buffer<'a>) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.add_buffer(buffer);
+        }
+    }
+
+    pub fn show_buffer(&mut self, buffer: Buffer<'a>) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.show_buffer(buffer);
+        }
+    }
+
+    pub fn next_buffer(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.next_buffer();
+        }
+    }
+
+    pub fn prev_buffer(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.prev_buffer();
+        }
+    }
+
+    pub fn start_command_line(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.start_command_line();
+        }
+    }
+
+    // Passes on the settings each pane and buffer has its own copy of
+    pub fn apply_options(&mut self, options: &Options) {
+        let snippets = Rc::new(options.snippets.clone());
+        for pane in self.panes.iter_mut() {
+            pane.set_snippets(snippets.clone());
+            pane.set_line_numbers(options.line_numbers);
+            pane.set_spacing(options.spacing);
+            pane.set_dim_unfocused(options.dim_unfocused);
+            pane.set_show_minimap(options.minimap);
+            pane.set_max_line_length(options.max_line_length);
+            pane.set_complete_after(options.complete_after);
+            pane.set_show_whitespace(options.show_whitespace);
+            pane.set_cursor_style(options.cursor_style);
+            pane.set_cursor_blink(options.cursor_blink);
+            pane.set_guides(options.guides.clone());
+            pane.set_status_format(&options.status_format);
+            pane.buffer.set_virtual_edit(options.virtual_edit);
+            pane.buffer.set_auto_close(options.auto_close);
+            pane.buffer
+                .set_trim_trailing_whitespace(options.trim_trailing_whitespace);
+            pane.buffer.set_indent_options(options.indent.clone());
+        }
+    }
+
+    pub fn start_command_palette(&mut self, palette: Palette) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.start_command_palette(palette);
+        }
+    }
+
+    pub fn pick_buffer(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.pick_buffer();
+        }
+    }
+
+    pub fn split_vertically(&mut self, filename: Option<&str>) -> Result<(), Box<dyn Error>> {
+        self.split(filename, Arrangement::VSplit)
+    }
+
+    pub fn split_horizontally(&mut self, filename: Option<&str>) -> Result<(), Box<dyn Error>> {
+        self.split(filename, Arrangement::HSplit)
+    }
+
+    fn split(
+        &mut self,
+        filename: Option<&str>,
+        arrangement: Arrangement,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut buffer = Buffer::default();
+        if let Some(filename) = filename {
+            buffer.open_in_background(filename)?;
+        }
+        self.split_with_buffer(buffer, arrangement);
+        Ok(())
+    }
+
+    pub fn split_vertically_with_buffer(&mut self, buffer: Buffer<'a>) {
+        self.split_with_buffer(buffer, Arrangement::VSplit);
+    }
+
+    fn split_with_buffer(&mut self, buffer: Buffer<'a>, arrangement: Arrangement) {
+        let new_pane = self.new_pane(buffer, false);
+        self.split_focused_pane(new_pane, arrangement);
+        self.recalculate_layout();
+    }
+
+    // The buffers in the focused pane with changes that would be lost with
+    // it, which are the ones with no other pane showing their file
+    pub fn focused_pane_unsaved(&self) -> Vec<String> {
+        let pane = match self.panes.get(self.focused_idx) {
+            Some(pane) => pane,
+            None => return vec![],
+        };
+        let open_elsewhere = |filename: &String| {
+            self.panes
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| *idx != self.focused_idx)
+                .flat_map(|(_, other)| other.all_buffers())
+                .any(|buffer| buffer.filename.as_ref() == Some(filename))
+        };
+        pane.all_buffers()
+            .filter(|buffer| buffer.is_dirty())
+            .filter(|buffer| !buffer.filename.as_ref().is_some_and(open_elsewhere))
+            .map(Buffer::name)
+            .collect()
+    }
+
+    pub fn confirm_close_pane(&mut self, names: &[String]) {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            pane.confirm_close(names);
+        }
+    }
+
+    fn ui_scale(&self) -> f32 {
+        self.panes
+            .get(self.focused_idx)
+            .map(|pane| pane.ui_scale)
+            .unwrap_or(1.0)
+    }
+
+    fn min_pane_size(&self) -> f32 {
+        MIN_PANE_SIZE * self.ui_scale()
+    }
+
+    // Right and Down make the focused pane wider or taller, Left and Up
+    // narrower or shorter. False if it isn't split that way.
+    pub fn resize_focused_pane(&mut self, direction: Direction) -> bool {
+        let (arrangement, grow) = match direction {
+            Direction::Right => (Arrangement::VSplit, true),
+            Direction::Left => (Arrangement::VSplit, false),
+            Direction::Down => (Arrangement::HSplit, true),
+            Direction::Up => (Arrangement::HSplit, false),
+        };
+        let (divider, far) = match self.layout.divider_beside(
+            self.focused_idx,
+            arrangement,
+            self.position,
+            self.bounds,
+        ) {
+            Some(beside) => beside,
+            None => return false,
+        };
+        // Growing pushes the divider on its far side away, or pulls the one
+        // on its near side back
+        let step = RESIZE_STEP * self.ui_scale();
+        let at = if grow == far {
+            divider.position() + step
+        } else {
+            divider.position() - step
+        };
+        if self.layout.move_divider(&divider, at, self.min_pane_size()) {
+            self.recalculate_layout();
+        }
+        true
+    }
+
+    pub fn close_focused_pane(&mut self) -> bool {
+        if self.panes.len() <= 1 {
+            return false;
+        }
+        let pane_idx = self.focused_idx;
+        let arrangement = self.layout.arrangement_of(pane_idx).unwrap_or_default();
+        let pane = self.panes.remove(pane_idx);
+        self.layout.remove(pane_idx);
+        if let Some(filename) = pane.buffer.filename.clone() {
+            self.closed_panes.push(ClosedPane {
+                filename,
+                cursor: pane.buffer.cursor.current(),
+                arrangement,
+            });
+            if self.closed_panes.len() > MAX_CLOSED_PANES {
+                self.closed_panes.remove(0);
+            }
+        }
+        self.focus_pane_index(usize::min(pane_idx, self.panes.len() - 1));
+        self.recalculate_layout();
+        true
+    }
+
+    // Brings back the most recently closed pane next to the focused one
+    pub fn reopen_closed_pane(&mut self) -> Result<bool, Box<dyn Error>> {
+        let closed = match self.closed_panes.pop() {
+            Some(closed) => closed,
+            None => return Ok(false),
+        };
+        let mut buffer = Buffer::default();
+        buffer.open(&closed.filename)?;
+        buffer
+            .cursor
+            .move_to(closed.cursor.text_row(), closed.cursor.text_col());
+        buffer.check_cursor();
+        let pane = self.new_pane(buffer, false);
+        let pane_idx = self.split_focused_pane(pane, closed.arrangement);
+        self.focus_pane_index(pane_idx);
+        self.recalculate_layout();
+        Ok(true)
+    }
+
+    // The panes with files open and how they're split, leaving out the rest
+    pub fn session(&self) -> Option<Session> {
+        let mut layout = self.layout.clone();
+        let mut panes = vec![];
+        let mut focused = 0;
+        for (pane_idx, pane) in self.panes.iter().enumerate().rev() {
+            match pane.session() {
+                Some(saved) => {
+                    if pane_idx == self.focused_idx {
+                        focused = pane_idx;
+                    }
+                    panes.push(saved);
+                }
+                None => {
+                    layout.remove(pane_idx);
+                    if pane_idx < focused {
+                        focused -= 1;
+                    }
+                }
+            }
+        }
+        if panes.is_empty() {
+            return None;
+        }
+        panes.reverse();
+        Some(Session {
+            panes,
+            layout,
+            focused,
+        })
+    }
+
+    // Swaps the panes for the ones in session, skipping files that can't be
+    // opened any more. Returns false, leaving things as they were, when none
+    // of them can be.
+    pub fn restore_session(&mut self, session: &Session) -> bool {
+        let mut layout = session.layout.clone();
+        if !layout.fits(session.panes.len()) {
+            layout = Layout::even(
+                Arrangement::default(),
+                (0..session.panes.len()).map(Layout::Pane).collect(),
+            );
+        }
+        let mut focused = session.focused;
+        let mut panes = vec![];
+        for (pane_idx, saved) in session.panes.iter().enumerate().rev() {
+            let mut buffer = Buffer::default();
+            match buffer.open(&saved.filename) {
+                Ok(_) => {
+                    let mut pane = self.new_pane(buffer, false);
+                    pane.restore_session(saved);
+                    panes.push(pane);
+                }
+                Err(e) => {
+                    println!("Not restoring {}: {}", saved.filename, e);
+                    layout.remove(pane_idx);
+                    if pane_idx < focused {
+                        focused -= 1;
+                    }
+                }
+            }
+        }
+        if panes.is_empty() {
+            return false;
+        }
+        panes.reverse();
+        self.panes = panes;
+        self.layout = layout;
+        self.focus_pane_index(usize::min(focused, self.panes.len() - 1));
+        self.recalculate_layout();
+        true
+    }
+
+    pub fn check(&mut self) -> Vec<WindowAction> {
+        let mut actions = vec![];
+
+        for pane in self.panes.iter_mut() {
+            actions.append(&mut pane.check());
+        }
+
+        actions
+    }
+
+    fn focus_pane_index(&mut self, pane_idx: usize) {
+        self.set_focused_idx(pane_idx);
+        for (idx, pane) in self.panes.iter_mut().enumerate() {
+            pane.set_focused(idx == pane_idx);
+        }
+    }
+
+    pub fn focus_pane(&mut self, direction: Direction) {
+        if let Some(pane_idx) = layout::neighbour(&self.pane_rects(), self.focused_idx, direction) {
+            self.focus_pane_index(pane_idx);
+        }
+    }
+
+    pub fn save_file(&mut self) -> Option<Result<FileSaveStatus, Box<dyn Error>>> {
+        if let Some(pane) = self.panes.get_mut(self.focused_idx) {
+            Some(pane.save_file())
+        } else {
+            None
+        }
+    }
+
+    pub fn create_missing_directory(&self) -> Option<io::Result<Option<PathBuf>>> {
+        self.focused_buffer()
+            .map(|buffer| buffer.create_missing_directory())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.panes
+            .iter()
+            .fold(false, |dirty, pane| dirty || pane.is_dirty())
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_which_pane_is_location() {
+    let buffer = Buffer::default();
+    let bounds = vec2(10.0, 10.0);
+    let position = vec2(0.0, 0.0);
+    let gui_pane = Pane::new(12.0, 1.0, buffer, true);
+    let mut container = Container::single(bounds, position, gui_pane);
+    assert_eq!(Some(0), container.which_pane_is_location(vec2(0.0, 0.0)));
+    let _ = container.split_vertically(None);
+    assert_eq!(Some(0), container.which_pane_is_location(vec2(0.0, 0.0)));
+    assert_eq!(Some(0), container.which_pane_is_location(vec2(0.0, 9.9)));
+    assert_eq!(Some(1), container.which_pane_is_location(vec2(9.0, 0.0)));
+    assert_eq!(Some(1), container.which_pane_is_location(vec2(5.0, 0.0)));
+    assert_eq!(Some(1), container.which_pane_is_location(vec2(5.0, 9.9)));
+}
+
+#[test]
+fn test_close_and_reopen_pane() {
+    let path = crate::utils::temp_path("bim-reopen", ".txt");
+    let filename = path.to_string_lossy().to_string();
+    std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
+    let mut container = Container::single(
+        vec2(10.0, 10.0),
+        vec2(0.0, 0.0),
+        Pane::new(12.0, 1.0, Buffer::default(), true),
+    );
+    assert!(!container.close_focused_pane(), "the last pane stays open");
+
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    buffer.cursor.move_to(2, 3);
+    container.split_vertically_with_buffer(buffer);
+    container.focus_pane_index(1);
+    assert!(container.close_focused_pane());
+    assert_eq!(1, container.panes.len());
+    assert_eq!(0, container.focused_idx);
+
+    assert!(container.reopen_closed_pane().unwrap());
+    assert_eq!(2, container.panes.len());
+    assert_eq!(1, container.focused_idx);
+    let reopened = &container.panes[1].buffer;
+    assert_eq!(Some(filename), reopened.filename);
+    assert_eq!(Cursor::new(2, 3), reopened.cursor.current());
+    assert!(!container.reopen_closed_pane().unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_horizontal_split_inside_vertical_split() {
+    let mut container = Container::single(
+        vec2(100.0, 100.0),
+        vec2(0.0, 0.0),
+        Pane::new(12.0, 1.0, Buffer::default(), true),
+    );
+    container.split_vertically(None).unwrap();
+    container.focus_pane(Direction::Right);
+    assert_eq!(1, container.focused_idx);
+    container.split_horizontally(None).unwrap();
+
+    assert_eq!(Some(0), container.which_pane_is_location(vec2(10.0, 90.0)));
+    assert_eq!(Some(1), container.which_pane_is_location(vec2(60.0, 10.0)));
+    assert_eq!(Some(2), container.which_pane_is_location(vec2(60.0, 60.0)));
+    assert_eq!(
+        vec2(10.0, 10.0),
+        container.absolute_position_to_pane_relative(2, vec2(60.0, 60.0))
+    );
+
+    container.focus_pane(Direction::Down);
+    assert_eq!(2, container.focused_idx);
+    container.focus_pane(Direction::Left);
+    assert_eq!(0, container.focused_idx);
+
+    container.focus_pane_index(1);
+    assert!(container.close_focused_pane());
+    assert_eq!(Some(1), container.which_pane_is_location(vec2(60.0, 10.0)));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_dragging_a_divider() {
+    let mut container = Container::single(
+        vec2(100.0, 100.0),
+        vec2(0.0, 0.0),
+        Pane::new(12.0, 1.0, Buffer::default(), true),
+    );
+    container.split_vertically(None).unwrap();
+    for pane in container.panes.iter_mut() {
+        pane.update_gui(GuiAction::SetLineHeight(10.0));
+        pane.update_gui(GuiAction::SetCharacterWidth(2.0));
+    }
+
+    container.mouse_click(vec2(51.0, 40.0), false, false, ClickCount::Single);
+    container.mouse_drag(vec2(70.0, 45.0));
+    let (preview, _) = container.preview_layout().unwrap();
+    let label = container.divider_preview_label(&preview);
+    // Nothing moves until the divider is let go
+    assert_eq!(Some(1), container.which_pane_is_location(vec2(60.0, 10.0)));
+
+    container.mouse_release();
+    assert!(container.divider_drag.is_none());
+    assert_eq!(Some(0), container.which_pane_is_location(vec2(60.0, 10.0)));
+    let sizes = container
+        .pane_rects()
+        .iter()
+        .map(|(pane_idx, rect)| {
+            let (cols, rows) = container.panes[*pane_idx].text_size_in(rect.bounds);
+            format!("{}x{}", cols, rows)
+        })
+        .collect::<Vec<_>>()
+        .join(" | ");
+    assert_eq!(sizes, label);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_save_and_restore_session() {
+    let dir = crate::utils::temp_path("bim-session", "");
+    std::fs::create_dir_all(&dir).unwrap();
+    let first = dir.join("first.txt").to_string_lossy().to_string();
+    let second = dir.join("second.txt").to_string_lossy().to_string();
+    std::fs::write(&first, "one\ntwo\nthree\n").unwrap();
+    std::fs::write(&second, "four\nfive\n").unwrap();
+
+    let mut container = Container::single(
+        vec2(100.0, 100.0),
+        vec2(0.0, 0.0),
+        Pane::new(12.0, 1.0, Buffer::default(), true),
+    );
+    let mut buffer = Buffer::default();
+    buffer.open(&first).unwrap();
+    buffer.cursor.move_to(2, 3);
+    container.split_vertically_with_buffer(buffer);
+    container.focus_pane_index(1);
+    let mut buffer = Buffer::default();
+    buffer.open(&second).unwrap();
+    container.split_with_buffer(buffer, Arrangement::HSplit);
+    container.focus_pane_index(2);
+
+    // The pane without a file is left out
+    let session = container.session().unwrap();
+    assert_eq!(
+        vec![first.clone(), second.clone()],
+        session
+            .panes
+            .iter()
+            .map(|pane| pane.filename.clone())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        (2, 3),
+        (session.panes[0].cursor_row, session.panes[0].cursor_col)
+    );
+    assert_eq!(
+        Layout::even(Arrangement::HSplit, vec![Layout::Pane(0), Layout::Pane(1)]),
+        session.layout
+    );
+    assert_eq!(1, session.focused);
+    let yaml = serde_yaml::to_string(&session).unwrap();
+    assert_eq!(session, serde_yaml::from_str(&yaml).unwrap());
+
+    let mut restored = Container::single(
+        vec2(100.0, 100.0),
+        vec2(0.0, 0.0),
+        Pane::new(12.0, 1.0, Buffer::default(), true),
+    );
+    assert!(restored.restore_session(&session));
+    assert_eq!(2, restored.panes.len());
+    assert_eq!(1, restored.focused_idx);
+    assert_eq!(session.layout, restored.layout);
+    assert_eq!(2, restored.panes[0].buffer.cursor.text_row());
+    assert_eq!(3, restored.panes[0].buffer.cursor.text_col());
+    assert_eq!(Some(1), restored.which_pane_is_location(vec2(10.0, 60.0)));
+
+    // Files that have gone are skipped, and if they all have nothing changes
+    std::fs::remove_file(&first).unwrap();
+    assert!(restored.restore_session(&session));
+    assert_eq!(1, restored.panes.len());
+    assert_eq!(Layout::Pane(0), restored.layout);
+    assert_eq!(0, restored.focused_idx);
+    std::fs::remove_file(&second).unwrap();
+    assert!(!restored.restore_session(&session));
+    assert_eq!(1, restored.panes.len());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_resizing_the_focused_pane() {
+    let mut container = Container::single(
+        vec2(400.0, 300.0),
+        vec2(0.0, 0.0),
+        Pane::new(12.0, 1.0, Buffer::default(), true),
+    );
+    assert!(!container.resize_focused_pane(Direction::Right));
+    container.split_vertically(None).unwrap();
+    let width_of = |container: &Container<'_>, pane_idx: usize| {
+        container
+            .pane_rects()
+            .iter()
+            .find(|(idx, _)| *idx == pane_idx)
+            .map(|(_, rect)| rect.bounds.x().round())
+            .unwrap()
+    };
+
+    container.focus_pane_index(0);
+    assert!(container.resize_focused_pane(Direction::Right));
+    assert_eq!(240.0, width_of(&container, 0));
+    // The last pane grows from its near side
+    container.focus_pane_index(1);
+    assert!(container.resize_focused_pane(Direction::Right));
+    assert_eq!(200.0, width_of(&container, 1));
+    assert!(!container.resize_focused_pane(Direction::Down));
+
+    // It stops short of squashing a pane smaller than MIN_PANE_SIZE
+    for _ in 0..10 {
+        container.resize_focused_pane(Direction::Left);
+    }
+    assert_eq!(MIN_PANE_SIZE, width_of(&container, 1));
+}
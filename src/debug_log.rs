@@ -1,6 +1,8 @@
+use crate::paths;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::Write;
+use std::path::Path;
 use time::now;
 
 pub struct DebugLog<'a> {
@@ -26,6 +28,7 @@ impl<'a> DebugLog<'a> {
     }
 
     pub fn start(&self) -> io::Result<()> {
+        paths::create_parent(Path::new(self.filename))?;
         let mut file = OpenOptions::new()
             .create(true)
             .write(true)
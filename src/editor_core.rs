@@ -0,0 +1,1705 @@
+use crate::action::{Action, BufferAction, GuiAction, WindowAction};
+use crate::buffer::{Buffer, FileSaveStatus};
+use crate::buffer_list::BufferList;
+use crate::config::{apply_setting, RunConfig, BIM_QUIT_TIMES};
+use crate::container::Container;
+use crate::cursor::{Cursor, CursorT};
+use crate::ex::parse_ex;
+use crate::external::{Destination, External};
+use crate::grep::{Grep, Hit, Pattern};
+use crate::keycodes::Key;
+use crate::keymap::{parse_action, KeyCapabilities, KeyDispatcher, TypedChord};
+use crate::location;
+use crate::lsp::{self, Client, LanguageServer, LspEvent};
+use crate::marks;
+use crate::options::Options;
+use crate::palette::Palette;
+use crate::pane::Pane;
+use crate::paths;
+use crate::recorder::Recorder;
+use crate::replace_in_files::{self, FileMatches};
+use crate::session::Session;
+use crate::stats::EditStats;
+use crate::status::{Level, Messages, Status};
+use crate::tabs::TabLabel;
+use crate::theme::Theme;
+use glam::{vec2, Vec2};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
+
+// A tab that isn't being shown, with the panes it had and the directory it
+// was working in
+struct ParkedTab<'a> {
+    container: Container<'a>,
+    dir: PathBuf,
+}
+
+// What a search through the project's files is for
+enum GrepFor {
+    // Listing the hits in the results buffer with this id
+    Results(usize),
+    // Previewing replacing needle, a file's matches at a time
+    Replace {
+        needle: String,
+        replacement: String,
+        matches: Vec<FileMatches>,
+    },
+}
+
+// Everything bim does with keys and actions, short of drawing it: the
+// buffers, the panes showing them and the keymap. The window drives one of
+// these, as can tests or another program, with no window at all.
+pub struct EditorCore<'a> {
+    pub(crate) container: Container<'a>,
+    pub(crate) keys: KeyDispatcher,
+    pub(crate) options: Options,
+    pub(crate) theme: Rc<Theme>,
+    quit_times: i8,
+    status_message: Option<Status>,
+    // Everything that's been in the status message, to look back through
+    messages: Messages,
+    recorder: Option<Recorder>,
+    // Actions only a frontend can carry out, like going fullscreen, and
+    // settings it might need to catch up with
+    frontend_actions: Vec<Action>,
+    // What was done in panes that have since closed, for the frontend to log
+    closed_stats: Vec<(String, EditStats)>,
+    // Running language servers by filetype, and the filetypes whose server
+    // wouldn't start, so it isn't tried again every frame
+    language_servers: HashMap<String, LanguageServer>,
+    failed_servers: HashSet<String>,
+    // Shell commands still running, whose output's yet to be put anywhere
+    running: Vec<External>,
+    // The search through the project's files that's going on, if there is
+    // one, and how many have been started so each gets its own results
+    grep: Option<(GrepFor, Grep)>,
+    greps_started: usize,
+    // Which file each uppercase mark is in, and where it was set in case
+    // the file's been closed since
+    global_marks: HashMap<char, (String, Cursor)>,
+    // The tabs that aren't being shown. The one that is has container, and
+    // is working in tab_dir.
+    tabs: BufferList<ParkedTab<'a>>,
+    tab_dir: PathBuf,
+    // What the frontend last said about its size and font, for laying out
+    // the tabs under the tab bar and setting up new ones
+    bounds: Vec2,
+    line_height: f32,
+    character_width: f32,
+}
+
+impl<'a> EditorCore<'a> {
+    // Shows buffer in a pane filling bounds, and opens the rest of the files
+    // in options
+    pub fn new(
+        buffer: Buffer<'a>,
+        options: Options,
+        bounds: Vec2,
+        ui_scale: f32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut pane = Pane::new(options.font_size, ui_scale, buffer, true);
+        pane.set_line_numbers(options.line_numbers);
+        pane.set_spacing(options.spacing);
+        pane.set_dim_unfocused(options.dim_unfocused);
+        pane.set_show_minimap(options.minimap);
+        pane.set_max_line_length(options.max_line_length);
+        pane.set_complete_after(options.complete_after);
+        pane.set_show_whitespace(options.show_whitespace);
+        pane.set_cursor_style(options.cursor_style);
+        pane.set_cursor_blink(options.cursor_blink);
+        pane.set_guides(options.guides.clone());
+        pane.set_snippets(Rc::new(options.snippets.clone()));
+        pane.set_status_format(&options.status_format);
+        let theme = Rc::new(options.theme.clone());
+        let mut keys = KeyDispatcher::new(options.keymap.clone(), KeyCapabilities::Full);
+        keys.set_typed_chord(options.escape_chord.map(|(first, second)| TypedChord {
+            first,
+            second,
+            key: Key::Escape,
+            timeout: options.escape_chord_timeout,
+        }));
+        let mut core = Self {
+            container: Container::single(bounds, vec2(0.0, 0.0), pane),
+            keys,
+            theme,
+            quit_times: BIM_QUIT_TIMES + 1,
+            status_message: None,
+            messages: Messages::default(),
+            recorder: match &options.record_actions {
+                Some(filename) => Some(Recorder::create(filename)?),
+                None => None,
+            },
+            frontend_actions: vec![],
+            closed_stats: vec![],
+            language_servers: HashMap::new(),
+            failed_servers: HashSet::new(),
+            running: vec![],
+            grep: None,
+            greps_started: 0,
+            global_marks: HashMap::new(),
+            tabs: BufferList::default(),
+            tab_dir: env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            bounds,
+            line_height: 0.0,
+            character_width: 0.0,
+            options,
+        };
+        core.container.set_theme(core.theme.clone());
+        core.open_files()?;
+        core.restore_session();
+        Ok(core)
+    }
+
+    fn open_files(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut files = Vec::new();
+        if let RunConfig::RunOpenFiles(ref filenames) = self.options.run_type {
+            // The first file is already open, unless something was piped in
+            let first = if self.options.piped_in.is_some() {
+                0
+            } else {
+                1
+            };
+            if filenames.len() > first {
+                for filename in &filenames[first..] {
+                    files.push(String::from(filename));
+                }
+            }
+        }
+        for file in files {
+            if self.options.vsplit {
+                self.split_vertically_with_filename(&file)?;
+            } else {
+                let mut buffer = Buffer::default();
+                buffer.guard_open(self.options.large_file_len);
+                buffer.highlight_lazily_over(self.options.lazy_highlight_len);
+                buffer.open_or_new_in_background(&file, &self.options.templates)?;
+                self.container.add_buffer(buffer);
+            }
+        }
+        Ok(())
+    }
+
+    fn restore_session(&mut self) {
+        let opened_files = self.options.piped_in.is_some()
+            || matches!(self.options.run_type, RunConfig::RunOpenFiles(_));
+        if !self.options.restore_session || opened_files {
+            return;
+        }
+        if let Some(session) = Session::restore() {
+            self.container.restore_session(&session);
+        }
+    }
+
+    pub fn save_session(&self) {
+        if let Some(session) = self.container.session() {
+            session.save();
+        }
+    }
+
+    // Opens filename in the focused pane, in front of what it's showing
+    fn open_file(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut buffer = Buffer::default();
+        buffer.guard_open(self.options.large_file_len);
+        buffer.highlight_lazily_over(self.options.lazy_highlight_len);
+        buffer.open_or_new_in_background(filename, &self.options.templates)?;
+        self.container.show_buffer(buffer);
+        Ok(())
+    }
+
+    pub fn split_vertically_with_filename(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut buffer = Buffer::default();
+        buffer.guard_open(self.options.large_file_len);
+        buffer.highlight_lazily_over(self.options.lazy_highlight_len);
+        buffer.open_or_new_in_background(filename, &self.options.templates)?;
+        self.container.split_vertically_with_buffer(buffer);
+        Ok(())
+    }
+
+    // Changes a setting while bim's running, the same way the config file
+    // would have set it
+    fn set_option(&mut self, setting: &str, value: &str) {
+        let mut options = self.options.clone();
+        if let Err(err) = apply_setting(&mut options, setting, value) {
+            self.show_message(Level::Error, format!("Can't set {}! {}", setting, err));
+            return;
+        }
+        self.options = options;
+        let options = self.options.clone();
+        for container in self.containers_mut() {
+            container.apply_options(&options);
+        }
+        if setting == "theme" {
+            self.theme = Rc::new(self.options.theme.clone());
+            self.set_theme_everywhere();
+        }
+        self.frontend_actions
+            .push(Action::OnWindow(WindowAction::SetOption(
+                setting.to_string(),
+                value.to_string(),
+            )));
+    }
+
+    // Runs whatever key is bound to, as if it had been typed
+    pub fn handle_key(&mut self, key: Key) {
+        for action in self.keys.dispatch(key) {
+            self.run_action(action);
+        }
+        self.check();
+    }
+
+    // Moves the clock on for typed chords, status messages and animations
+    pub fn tick(&mut self, duration: Duration) {
+        if let Some(status) = self.status_message.as_mut() {
+            if !status.is_valid() {
+                self.status_message = None;
+                self.container.damage();
+            }
+        }
+        for action in self.keys.tick(duration) {
+            self.run_action(action);
+        }
+        self.container.update_dt(duration);
+        self.sync_language_servers();
+        self.poll_language_servers();
+        self.finish_commands();
+        self.poll_grep();
+    }
+
+    // Starts the language servers for the filetypes being shown that have
+    // one set, and sends them whatever's changed in their buffers
+    fn sync_language_servers(&mut self) {
+        if self.options.language_servers.is_empty() {
+            return;
+        }
+        let mut problems = vec![];
+        for buffer in self.container.shown_buffers() {
+            let filename = match &buffer.filename {
+                Some(filename) if !buffer.is_loading() => filename,
+                _ => continue,
+            };
+            let filetype = buffer.get_filetype();
+            let command = match self.options.language_servers.get(&filetype) {
+                Some(command) => command,
+                None => continue,
+            };
+            if !self.language_servers.contains_key(&filetype) {
+                if self.failed_servers.contains(&filetype) {
+                    continue;
+                }
+                let root = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+                match LanguageServer::spawn(command, &root) {
+                    Ok(server) => {
+                        self.language_servers.insert(filetype.clone(), server);
+                    }
+                    Err(err) => {
+                        problems.push(format!("Can't start {}: {}", command, err));
+                        self.failed_servers.insert(filetype);
+                        continue;
+                    }
+                }
+            }
+            if let Some(server) = self.language_servers.get_mut(&filetype) {
+                server.client().sync(
+                    &paths::absolute_path(Path::new(filename)),
+                    &filetype.to_lowercase(),
+                    buffer.revision(),
+                    || buffer.contents(),
+                );
+            }
+        }
+        for problem in problems {
+            self.set_status_msg(problem);
+        }
+    }
+
+    fn poll_language_servers(&mut self) {
+        let events = self
+            .language_servers
+            .values_mut()
+            .flat_map(|server| server.poll())
+            .collect::<Vec<_>>();
+        for event in events {
+            self.handle_lsp_event(event);
+        }
+    }
+
+    fn handle_lsp_event(&mut self, event: LspEvent) {
+        self.container.damage();
+        match event {
+            LspEvent::Diagnostics(path, diagnostics) => {
+                if let Some(buffer) = self.container.buffer_for_path_mut(&path) {
+                    buffer.set_diagnostics(diagnostics);
+                }
+            }
+            LspEvent::Definition(path, row, utf16_col) => {
+                if !self.show_path(&path) {
+                    return;
+                }
+                let col = self
+                    .focused_buffer()
+                    .and_then(|buffer| buffer.rows.get(row))
+                    .map(|line| lsp::utf16_to_char_col(line.as_str(), utf16_col))
+                    .unwrap_or(utf16_col);
+                self.container.goto(row, col);
+            }
+            LspEvent::Hover(text) => {
+                // The status line has room for one line, which is usually the
+                // signature, after any code fence
+                let line = text
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty() && !line.starts_with("```"))
+                    .unwrap_or_default()
+                    .to_string();
+                self.set_status_msg(line);
+            }
+            LspEvent::NotFound(what) => self.set_status_msg(format!("No {} found", what)),
+            LspEvent::Failed(err) => {
+                self.show_message(Level::Error, format!("Language server: {}", err))
+            }
+        }
+    }
+
+    // Asks the focused buffer's language server about the text at the
+    // cursor, once it's up to date with the buffer
+    fn ask_language_server(&mut self, ask: fn(&mut Client, &Path, usize, usize)) {
+        self.sync_language_servers();
+        let buffer = match self.container.focused_buffer() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let filename = match &buffer.filename {
+            Some(filename) => paths::absolute_path(Path::new(filename)),
+            None => return self.set_status_msg("Save the file first".to_string()),
+        };
+        let filetype = buffer.get_filetype();
+        let row = buffer.cursor.text_row().max(0) as usize;
+        let col = buffer
+            .rows
+            .get(row)
+            .map(|line| lsp::char_to_utf16_col(line.as_str(), buffer.cursor.text_col() as usize))
+            .unwrap_or(0);
+        match self.language_servers.get_mut(&filetype) {
+            Some(server) => ask(server.client(), &filename, row, col),
+            None => self.set_status_msg(format!("No language server for {}", filetype)),
+        }
+    }
+
+    // Feeds command the focused buffer's selected rows, or all of them if
+    // nothing's selected. It's left to run, and finish_commands picks up
+    // what it printed.
+    fn run_command(&mut self, command: &str, replace: bool) {
+        let buffer = match self.container.focused_buffer() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let rows = buffer.filter_rows();
+        let input = buffer.rows_text(rows.clone());
+        let destination = if replace {
+            Destination::Replace {
+                name: buffer.name(),
+                revision: buffer.revision(),
+                rows,
+            }
+        } else {
+            Destination::NewBuffer
+        };
+        match External::spawn(command, input, destination) {
+            Ok(external) => {
+                self.running.push(external);
+                self.set_status_msg(format!("Running {}...", command));
+            }
+            Err(err) => self.show_message(Level::Error, format!("Can't run {}: {}", command, err)),
+        }
+    }
+
+    fn finish_commands(&mut self) {
+        let mut index = 0;
+        while index < self.running.len() {
+            let result = match self.running[index].try_finish() {
+                Some(result) => result,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let external = self.running.remove(index);
+            let command = external.command();
+            let printed = match result {
+                Ok(printed) => printed,
+                Err(err) => {
+                    self.show_message(Level::Error, format!("{} {}", command, err));
+                    continue;
+                }
+            };
+            // Warnings it printed go in the history, under where it's
+            // said to have run
+            if !printed.errors.is_empty() {
+                let warnings = format!("{} printed:\n{}", command, printed.errors);
+                self.messages.push(Level::Warn, &warnings);
+            }
+            let output = printed.output;
+            match external.destination() {
+                Destination::NewBuffer => {
+                    self.container.show_buffer(Buffer::with_output(&output));
+                    self.set_status_msg(format!("Ran {}", command));
+                }
+                Destination::Replace {
+                    name,
+                    revision,
+                    rows,
+                } => {
+                    let unchanged = self
+                        .container
+                        .focused_buffer()
+                        .map(|buffer| &buffer.name() == name && buffer.revision() == *revision);
+                    if unchanged == Some(true) {
+                        self.container.replace_rows_with_text(rows.clone(), &output);
+                        self.set_status_msg(format!("Ran {}", command));
+                    } else {
+                        self.show_message(
+                            Level::Warn,
+                            format!(
+                                "{} changed while {} ran, so it's been left as it was",
+                                name, command
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Shows path in the focused pane, opening it if it isn't open there
+    fn show_path(&mut self, path: &Path) -> bool {
+        if self.container.switch_to_path(path) {
+            return true;
+        }
+        let filename = path.to_string_lossy().to_string();
+        match self.open_file(&filename) {
+            Ok(()) => true,
+            Err(err) => {
+                self.show_message(Level::Error, format!("Couldn't open {}: {}", filename, err));
+                false
+            }
+        }
+    }
+
+    fn goto_location(&mut self, text: &str) {
+        let (filename, location) = location::split_location(text);
+        if self.show_path(Path::new(filename)) {
+            if let Some((row, col)) = location {
+                self.container.goto(row, col);
+            }
+        }
+    }
+
+    // An uppercase mark can only be in one file, so setting it takes it out
+    // of whichever it was in before
+    fn set_mark(&mut self, name: char) {
+        if !marks::is_settable(name) {
+            return self.set_status_msg(format!("Marks are letters, not {:?}", name));
+        }
+        let (filename, at) = match self.container.focused_buffer() {
+            Some(buffer) => (buffer.filename.clone(), buffer.cursor.current()),
+            None => return,
+        };
+        if marks::is_global(name) {
+            let filename = match filename {
+                Some(filename) => filename,
+                None => {
+                    return self.set_status_msg(format!(
+                        "Mark {} needs a file to be in, save this first",
+                        name
+                    ))
+                }
+            };
+            if let Some((previous, _)) = self.global_marks.insert(name, (filename, at)) {
+                if let Some(buffer) = self.container.buffer_for_path_mut(Path::new(&previous)) {
+                    buffer.remove_mark(name);
+                }
+            }
+        }
+        if let Some(buffer) = self.container.focused_buffer_mut() {
+            buffer.set_mark(name);
+        }
+        self.set_status_msg(format!("Marked {} at line {}", name, at.text_row() + 1));
+    }
+
+    fn jump_to_mark(&mut self, name: char) {
+        if let Some((filename, at)) = self.global_marks.get(&name).cloned() {
+            if !self.show_path(Path::new(&filename)) {
+                return;
+            }
+            // Opened again since it was set, so it's lost track of it
+            if let Some(buffer) = self.container.focused_buffer_mut() {
+                if buffer.mark(name).is_none() {
+                    buffer.set_mark_at(name, at);
+                }
+            }
+        }
+        if !self.container.jump_to_mark(name) {
+            self.set_status_msg(format!("Mark {} isn't set", name));
+        }
+    }
+
+    // Starts a new search, so the last one's hits stop coming in, and shows
+    // the buffer this one's will be listed in
+    fn grep_in_files(&mut self, text: &str) {
+        let pattern = match Pattern::parse(text) {
+            Ok(pattern) => pattern,
+            Err(err) => return self.show_message(Level::Error, err),
+        };
+        self.greps_started += 1;
+        let id = self.greps_started;
+        self.container.show_buffer(Buffer::with_results(id));
+        let grep = Grep::spawn(Path::new("."), pattern);
+        self.grep = Some((GrepFor::Results(id), grep));
+        self.set_status_msg(format!("Searching for {}...", text));
+    }
+
+    fn poll_grep(&mut self) {
+        let (grep_for, grep) = match self.grep.as_mut() {
+            Some((grep_for, grep)) => (grep_for, grep),
+            None => return,
+        };
+        let mut found = vec![];
+        let finished = loop {
+            match grep.try_recv() {
+                Ok(hits) => found.push(hits),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        let (hits, files) = grep.found();
+        let id = match grep_for {
+            GrepFor::Results(id) => *id,
+            GrepFor::Replace { matches, .. } => {
+                matches.extend(found.iter().filter_map(|hits| FileMatches::from_hits(hits)));
+                if finished {
+                    if let Some((
+                        GrepFor::Replace {
+                            needle,
+                            replacement,
+                            matches,
+                        },
+                        _,
+                    )) = self.grep.take()
+                    {
+                        self.show_replace_preview(&needle, &replacement, &matches);
+                    }
+                }
+                return;
+            }
+        };
+        let lines = found.iter().flatten().map(Hit::to_line).collect::<Vec<_>>();
+        if !lines.is_empty() {
+            self.container.damage();
+            match self.container.results_buffer_mut(id) {
+                Some(buffer) => buffer.append_results(&lines),
+                // Nowhere to show them any more, so there's no point going on
+                None => {
+                    self.grep = None;
+                    return;
+                }
+            }
+        }
+        if finished {
+            self.grep = None;
+            match hits {
+                0 => self.show_message(Level::Warn, "No matches".to_string()),
+                _ => self.set_status_msg(format!(
+                    "{} matches in {} files, Enter on one to go to it",
+                    hits, files
+                )),
+            }
+        }
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = recorder.record(&action, self.container.current_revision()) {
+                println!("Failed to record action {:?}: {}", action, err);
+            }
+        }
+
+        self.container.damage();
+        match action {
+            Action::OnGui(GuiAction::Quit) => self.try_quit(),
+            Action::OnGui(GuiAction::ForceQuit) => self.quit_times = 0,
+            Action::OnGui(GuiAction::ToggleMinimap) => {
+                self.container.update_gui(GuiAction::ToggleMinimap)
+            }
+            Action::OnGui(_) => self.frontend_actions.push(action),
+            Action::OnWindow(window_action) => self.do_window_action(window_action),
+            Action::OnPane(pane_action) => self.container.do_pane_action(pane_action),
+            Action::OnBuffer(buffer_action) => self.handle_buffer_action(buffer_action),
+        }
+    }
+
+    // Runs what the panes want done once they've had their actions, like
+    // saving when a prompt for a filename is finished
+    pub fn check(&mut self) {
+        let actions = self.container.check();
+        for action in actions {
+            self.do_window_action(action);
+        }
+    }
+
+    fn do_window_action(&mut self, window_action: WindowAction) {
+        match window_action {
+            WindowAction::SaveFile => {
+                self.save_file(self.options.create_dirs);
+            }
+            WindowAction::SaveFileCreatingDirectory => {
+                self.save_file(true);
+            }
+            WindowAction::SaveFileAs(filename) => {
+                self.save_file_as(filename);
+            }
+            WindowAction::RenameFile(filename) => self.rename_file(filename),
+            WindowAction::FocusPane(direction) => self.container.focus_pane(direction),
+            WindowAction::ResizePane(direction) => {
+                if !self.container.resize_focused_pane(direction) {
+                    self.set_status_msg("The pane isn't split that way".to_string());
+                }
+            }
+            WindowAction::ToggleFullscreen => self
+                .frontend_actions
+                .push(Action::OnWindow(WindowAction::ToggleFullscreen)),
+            WindowAction::SplitVertically => {
+                let _ = self.container.split_vertically(None);
+            }
+            WindowAction::SplitHorizontally => {
+                let _ = self.container.split_horizontally(None);
+            }
+            WindowAction::ClosePane => {
+                let unsaved = self.container.focused_pane_unsaved();
+                if unsaved.is_empty() {
+                    self.close_pane();
+                } else {
+                    self.container.confirm_close_pane(&unsaved);
+                }
+            }
+            WindowAction::ForceClosePane => self.close_pane(),
+            WindowAction::NewTab => self.new_tab(),
+            WindowAction::CloseTab => self.close_tab(),
+            WindowAction::NextTab => self.switch_tab(self.tabs.next_index()),
+            WindowAction::PrevTab => self.switch_tab(self.tabs.prev_index()),
+            WindowAction::ChangeDirectory(dir) => self.change_directory(&dir),
+            WindowAction::InspectChar => {
+                let msg = self
+                    .container
+                    .inspect_char()
+                    .unwrap_or_else(|| "No character under the cursor".to_string());
+                self.set_status_msg(msg);
+            }
+            WindowAction::NextBuffer => self.container.next_buffer(),
+            WindowAction::PrevBuffer => self.container.prev_buffer(),
+            WindowAction::PickBuffer => self.container.pick_buffer(),
+            WindowAction::ShowMessage(level, msg) => self.show_message(level, msg),
+            WindowAction::ShowMessages => {
+                let history = self.messages.describe();
+                self.container.show_buffer(Buffer::with_output(&history));
+            }
+            WindowAction::ListBindings => {
+                let bindings = self.keys.keymap().describe().join("\n");
+                self.container.show_buffer(Buffer::with_text(&bindings));
+            }
+            WindowAction::ShowStats => {
+                let msg = self
+                    .container
+                    .focused_buffer()
+                    .map(|buffer| format!("{}: {}", buffer.name(), buffer.stats()))
+                    .unwrap_or_default();
+                self.set_status_msg(msg);
+            }
+            WindowAction::PreviewReplaceInFiles(needle, replacement) => {
+                self.preview_replace_in_files(&needle, &replacement)
+            }
+            WindowAction::ApplyReplaceInFiles => self.apply_replace_in_files(),
+            WindowAction::PickTheme => self.container.pick_theme(&Theme::names()),
+            WindowAction::CommandPalette => self
+                .container
+                .start_command_palette(Palette::new(self.keys.keymap())),
+            WindowAction::RunCommand(name) => match parse_action(&name) {
+                Ok(action) => self.run_action(action),
+                Err(err) => self.set_status_msg(err),
+            },
+            WindowAction::CommandLine => self.container.start_command_line(),
+            WindowAction::RunExCommand(line) => match parse_ex(&line) {
+                Ok(actions) => {
+                    for action in actions {
+                        self.run_action(action);
+                    }
+                }
+                Err(err) => self.show_message(Level::Error, err),
+            },
+            WindowAction::OpenFile(filename) => {
+                let filename = paths::expand_tilde(&filename);
+                let is_new = !Path::new(location::split_location(&filename).0).exists();
+                match self.open_file(&filename) {
+                    Ok(()) if is_new => self.set_status_msg(format!("New file {}", filename)),
+                    Ok(()) => {}
+                    Err(err) => self
+                        .show_message(Level::Error, format!("Couldn't open {}: {}", filename, err)),
+                }
+            }
+            WindowAction::SetOption(setting, value) => self.set_option(&setting, &value),
+            WindowAction::GotoDefinition => self.ask_language_server(Client::definition),
+            WindowAction::ShowHover => self.ask_language_server(Client::hover),
+            WindowAction::FilterThroughCommand(command) => self.run_command(&command, true),
+            WindowAction::CommandIntoBuffer(command) => self.run_command(&command, false),
+            WindowAction::GrepInFiles(text) => self.grep_in_files(&text),
+            WindowAction::GotoLocation(text) => self.goto_location(&text),
+            WindowAction::SetMark(name) => self.set_mark(name),
+            WindowAction::JumpToMark(name) => self.jump_to_mark(name),
+            WindowAction::SetTheme(name) => match Theme::load(&name) {
+                Ok(theme) => {
+                    self.theme = Rc::new(theme);
+                    self.set_theme_everywhere();
+                }
+                Err(err) => self.show_message(Level::Error, format!("Can't load theme! {}", err)),
+            },
+            WindowAction::ReopenClosedPane => match self.container.reopen_closed_pane() {
+                Ok(true) => {}
+                Ok(false) => self.set_status_msg("No closed panes to reopen".to_string()),
+                Err(err) => {
+                    self.show_message(Level::Error, format!("Can't reopen pane! Error: {}", err))
+                }
+            },
+        }
+    }
+
+    // Looks through every file under the working directory on the grep
+    // worker, and shows the preview once it's been through them all
+    fn preview_replace_in_files(&mut self, needle: &str, replacement: &str) {
+        if needle.is_empty() {
+            return;
+        }
+        let grep = Grep::spawn(Path::new("."), Pattern::Literal(needle.to_string()));
+        let grep_for = GrepFor::Replace {
+            needle: needle.to_string(),
+            replacement: replacement.to_string(),
+            matches: vec![],
+        };
+        self.grep = Some((grep_for, grep));
+        self.set_status_msg(format!("Looking for {} to replace...", needle));
+    }
+
+    fn show_replace_preview(&mut self, needle: &str, replacement: &str, matches: &[FileMatches]) {
+        if matches.is_empty() {
+            self.show_message(Level::Warn, format!("No matches for {}", needle));
+            return;
+        }
+        let count = matches.iter().map(|file| file.rows.len()).sum::<usize>();
+        let preview = replace_in_files::preview(needle, replacement, matches);
+        self.container.show_buffer(Buffer::with_text(&preview));
+        self.set_status_msg(format!(
+            "{} lines in {} files, untick any to leave out then Ctrl-G R",
+            count,
+            matches.len()
+        ));
+    }
+
+    // Applies the preview being shown to each file's buffer, opening the
+    // ones that aren't open yet. Nothing is saved, so it can all be looked
+    // over (or undone) first.
+    fn apply_replace_in_files(&mut self) {
+        let plan = match self
+            .container
+            .focused_buffer()
+            .map(|buffer| buffer.contents())
+        {
+            Some(text) => replace_in_files::parse_preview(&text),
+            None => return,
+        };
+        let plan = match plan {
+            Ok(plan) => plan,
+            Err(err) => return self.set_status_msg(err),
+        };
+        let mut replaced = 0;
+        let mut files = 0;
+        let mut problems = vec![];
+        for (path, rows) in &plan.files {
+            let count = match self.container.buffer_for_path_mut(path) {
+                Some(buffer) => buffer.replace_in_rows(rows, &plan.needle, &plan.replacement),
+                None => {
+                    let mut buffer = Buffer::default();
+                    if let Err(err) = buffer.open(&path.to_string_lossy()) {
+                        problems.push(format!("{}: {}", path.display(), err));
+                        continue;
+                    }
+                    let count = buffer.replace_in_rows(rows, &plan.needle, &plan.replacement);
+                    self.container.add_buffer(buffer);
+                    count
+                }
+            };
+            if count == 0 {
+                problems.push(format!("{}: nothing left to replace", path.display()));
+            } else {
+                replaced += count;
+                files += 1;
+            }
+        }
+        let mut msg = format!("Replaced {} in {} files", replaced, files);
+        if !problems.is_empty() {
+            msg = format!("{}, couldn't change {}", msg, problems.join(", "));
+        }
+        self.set_status_msg(msg);
+    }
+
+    fn handle_buffer_action(&mut self, action: BufferAction) {
+        self.container.update_current_buffer(action);
+    }
+
+    fn save_file_as(&mut self, filename: String) -> bool {
+        let filename = paths::expand_tilde(&filename);
+        self.container
+            .update_current_buffer(BufferAction::SetFilename(filename));
+        self.save_file(self.options.create_dirs)
+    }
+
+    // Only once it's been saved under the new name is the old file deleted
+    fn rename_file(&mut self, filename: String) {
+        let old = match self
+            .focused_buffer()
+            .and_then(|buffer| buffer.filename.clone())
+        {
+            Some(old) => old,
+            None => return self.set_status_msg("Nothing to rename, save it instead".to_string()),
+        };
+        if !self.save_file_as(filename) {
+            return;
+        }
+        let new = self
+            .focused_buffer()
+            .and_then(|buffer| buffer.filename.clone())
+            .unwrap_or_default();
+        if new == old || !Path::new(&old).exists() {
+            return;
+        }
+        match fs::remove_file(&old) {
+            Ok(()) => self.set_status_msg(format!("Renamed {} to {}", old, new)),
+            Err(err) => self.show_message(
+                Level::Warn,
+                format!("Saved as {} but couldn't delete {}: {}", new, old, err),
+            ),
+        }
+    }
+
+    // True if the file was written
+    fn save_file(&mut self, create_dirs: bool) -> bool {
+        let mut created = None;
+        if create_dirs {
+            match self.container.create_missing_directory() {
+                Some(Ok(dir)) => created = dir,
+                Some(Err(err)) => {
+                    self.show_message(
+                        Level::Error,
+                        format!("Can't create directory! Error: {}", err),
+                    );
+                    return false;
+                }
+                None => {}
+            }
+        }
+        match self.container.save_file() {
+            Some(Ok(FileSaveStatus::Saved(bytes_saved))) => {
+                match created {
+                    Some(dir) => self.set_status_msg(format!(
+                        "Created {}, {} bytes written to disk",
+                        dir.display(),
+                        bytes_saved
+                    )),
+                    None => self.set_status_msg(format!("{} bytes written to disk", bytes_saved)),
+                }
+                true
+            }
+            Some(Err(err)) => {
+                self.show_message(Level::Error, format!("Can't save! Error: {}", err));
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Closing the last pane closes its tab, and the window once it's the
+    // last tab. Anything unsaved in the pane has already been asked about.
+    fn close_pane(&mut self) {
+        let stats = self.container.focused_pane_stats();
+        if self.container.close_focused_pane() {
+            self.closed_stats.extend(stats);
+        } else if let Some(tab) = self.tabs.remove_current() {
+            self.closed_stats.extend(stats);
+            self.show_tab(tab);
+            self.layout_tabs();
+        } else {
+            self.quit_times = 0;
+        }
+    }
+
+    // The shown tab's container, then the parked ones'
+    fn containers_mut(&mut self) -> impl Iterator<Item = &mut Container<'a>> {
+        std::iter::once(&mut self.container)
+            .chain(self.tabs.parked_mut().map(|tab| &mut tab.container))
+    }
+
+    fn set_theme_everywhere(&mut self) {
+        let theme = self.theme.clone();
+        for container in self.containers_mut() {
+            container.set_theme(theme.clone());
+        }
+    }
+
+    // Sizes and fonts change for every tab, not just the one being shown
+    pub fn update_gui(&mut self, action: GuiAction) {
+        match action {
+            GuiAction::UpdateSize(bounds, _) => {
+                self.bounds = bounds;
+                return self.layout_tabs();
+            }
+            GuiAction::SetLineHeight(line_height) => self.line_height = line_height,
+            GuiAction::SetCharacterWidth(width) => self.character_width = width,
+            _ => {}
+        }
+        for container in self.containers_mut() {
+            container.update_gui(action.clone());
+        }
+        if let GuiAction::SetLineHeight(_) = action {
+            self.layout_tabs();
+        }
+    }
+
+    // The bar's only there once there's more than one tab
+    pub fn tab_bar_height(&self) -> f32 {
+        if self.tabs.len() > 1 {
+            self.line_height
+        } else {
+            0.0
+        }
+    }
+
+    // Each tab's panes fill the window under the tab bar
+    fn layout_tabs(&mut self) {
+        let top = self.tab_bar_height();
+        let size = GuiAction::UpdateSize(self.bounds - vec2(0.0, top), vec2(0.0, top));
+        for container in self.containers_mut() {
+            container.update_gui(size.clone());
+        }
+        self.container.damage();
+    }
+
+    pub fn tab_labels(&self) -> Vec<TabLabel> {
+        (0..self.tabs.len())
+            .map(|idx| match self.tabs.get(idx) {
+                Some(tab) => TabLabel::new(idx + 1, &tab.dir, tab.container.is_dirty(), false),
+                None => TabLabel::new(idx + 1, &self.tab_dir, self.container.is_dirty(), true),
+            })
+            .collect()
+    }
+
+    // Opens a tab after the others with an empty pane, working where this
+    // one is
+    fn new_tab(&mut self) {
+        let mut container = self.container.empty_like();
+        if self.line_height > 0.0 {
+            container.update_gui(GuiAction::SetLineHeight(self.line_height));
+        }
+        if self.character_width > 0.0 {
+            container.update_gui(GuiAction::SetCharacterWidth(self.character_width));
+        }
+        self.tabs.add(ParkedTab {
+            container,
+            dir: self.tab_dir.clone(),
+        });
+        self.switch_tab(self.tabs.len() - 1);
+        self.layout_tabs();
+    }
+
+    fn switch_tab(&mut self, index: usize) {
+        if index == self.tabs.current() || index >= self.tabs.len() {
+            return;
+        }
+        let shown = ParkedTab {
+            container: std::mem::take(&mut self.container),
+            dir: self.tab_dir.clone(),
+        };
+        let tab = self.tabs.switch_to(index, shown);
+        self.show_tab(tab);
+    }
+
+    fn show_tab(&mut self, tab: ParkedTab<'a>) {
+        self.container = tab.container;
+        self.set_tab_dir(tab.dir);
+        self.container.damage();
+    }
+
+    // Anything unsaved has to be saved or closed first, there's no asking
+    // about a whole tab's worth of buffers
+    fn close_tab(&mut self) {
+        if self.container.is_dirty() {
+            return self.show_message(
+                Level::Warn,
+                "The tab has unsaved changes, save them or close their panes first".to_string(),
+            );
+        }
+        match self.tabs.remove_current() {
+            Some(tab) => {
+                self.closed_stats.extend(self.container.all_stats());
+                self.show_tab(tab);
+                self.layout_tabs();
+            }
+            None => self.set_status_msg("That's the last tab".to_string()),
+        }
+    }
+
+    // Relative to where the tab's working already
+    fn change_directory(&mut self, dir: &str) {
+        let dir = self.tab_dir.join(paths::expand_tilde(dir));
+        match dir.canonicalize() {
+            Ok(dir) if dir.is_dir() => {
+                self.set_tab_dir(dir);
+                self.set_status_msg(format!("Working in {}", self.tab_dir.display()));
+            }
+            Ok(_) => {
+                self.show_message(Level::Error, format!("{} isn't a directory", dir.display()))
+            }
+            Err(err) => self.show_message(
+                Level::Error,
+                format!("Can't work in {}! {}", dir.display(), err),
+            ),
+        }
+    }
+
+    // Relative paths are opened from the shown tab's directory
+    fn set_tab_dir(&mut self, dir: PathBuf) {
+        if let Err(err) = env::set_current_dir(&dir) {
+            self.show_message(
+                Level::Warn,
+                format!("Can't work in {}! {}", dir.display(), err),
+            );
+        }
+        self.tab_dir = dir;
+    }
+
+    pub fn tab_dir(&self) -> &Path {
+        &self.tab_dir
+    }
+
+    // Whether any buffer in any tab has unsaved changes
+    pub fn is_dirty(&self) -> bool {
+        self.container.is_dirty() || self.tabs.parked().any(|tab| tab.container.is_dirty())
+    }
+
+    pub fn all_stats(&self) -> Vec<(String, EditStats)> {
+        let mut stats = self.container.all_stats();
+        for tab in self.tabs.parked() {
+            stats.extend(tab.container.all_stats());
+        }
+        stats
+    }
+
+    pub fn remove_swap_files(&mut self) {
+        for container in self.containers_mut() {
+            container.remove_swap_files();
+        }
+    }
+
+    fn try_quit(&mut self) {
+        if self.options.show_quit_warning() && self.is_dirty() {
+            self.quit_times -= 1;
+            self.show_message(
+                Level::Warn,
+                format!(
+                    "{} {} {} {}",
+                    "WARNING! File has unsaved changes.",
+                    "Press Ctrl-Q",
+                    self.quit_times,
+                    "more times to quit"
+                ),
+            );
+        } else {
+            self.quit_times = 0;
+        }
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit_times <= 0
+    }
+
+    pub fn set_status_msg(&mut self, msg: String) {
+        self.show_message(Level::Info, msg);
+    }
+
+    // Shows msg for a while, and keeps it in the history for after that
+    pub fn show_message(&mut self, level: Level, msg: String) {
+        self.messages.push(level, &msg);
+        self.container.damage();
+        let status = Status::new_with_timeout(msg, Duration::from_secs(5)).with_level(level);
+        self.status_message = Some(status);
+    }
+
+    pub fn status_message(&self) -> Option<&str> {
+        self.status_message.as_ref().map(Status::summary)
+    }
+
+    // Whether there's anything new to draw
+    pub fn needs_redraw(&self) -> bool {
+        self.container.needs_redraw()
+    }
+
+    pub fn clear_damage(&mut self) {
+        self.container.clear_damage();
+    }
+
+    // What the OS window's called, after the focused buffer
+    pub fn window_title(&self) -> String {
+        match self.focused_buffer() {
+            Some(buffer) => format!("{} - bim", buffer.title()),
+            None => String::from("bim"),
+        }
+    }
+
+    pub fn status_level(&self) -> Option<Level> {
+        self.status_message.as_ref().map(|status| status.level)
+    }
+
+    pub fn take_frontend_actions(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.frontend_actions)
+    }
+
+    pub fn take_closed_stats(&mut self) -> Vec<(String, EditStats)> {
+        std::mem::take(&mut self.closed_stats)
+    }
+
+    pub fn focused_buffer(&self) -> Option<&Buffer<'a>> {
+        self.container.focused_buffer()
+    }
+
+    // The text of the buffer the focused pane is showing
+    pub fn contents(&self) -> Option<String> {
+        self.focused_buffer().map(|buffer| buffer.contents())
+    }
+
+    pub fn num_panes(&self) -> usize {
+        self.container.num_panes()
+    }
+}
+
+#[test]
+fn test_driving_the_editor_with_keys() {
+    let options = Options::default();
+    let mut editor = EditorCore::new(Buffer::default(), options, vec2(800.0, 600.0), 1.0).unwrap();
+    for c in "hello".chars() {
+        editor.handle_key(Key::Other(c));
+    }
+    editor.handle_key(Key::Return);
+    editor.handle_key(Key::Other('x'));
+    assert_eq!(Some("hello\nx\n".to_string()), editor.contents());
+
+    // Chords go through the keymap the same as in the window
+    editor.handle_key(Key::Control(Some('w')));
+    editor.handle_key(Key::Other('v'));
+    assert_eq!(2, editor.num_panes());
+
+    // The command line, without needing anything drawn
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(
+        "set line_numbers=sideways".to_string(),
+    )));
+    assert!(editor.status_message().unwrap().contains("sideways"));
+    editor.run_action(Action::OnGui(GuiAction::IncFontSize));
+    editor.run_action(Action::OnWindow(WindowAction::ToggleFullscreen));
+    assert_eq!(
+        vec![
+            Action::OnGui(GuiAction::IncFontSize),
+            Action::OnWindow(WindowAction::ToggleFullscreen)
+        ],
+        editor.take_frontend_actions()
+    );
+    assert!(editor.take_frontend_actions().is_empty());
+
+    editor.run_action(Action::OnGui(GuiAction::ForceQuit));
+    assert!(editor.should_quit());
+}
+
+#[test]
+fn test_language_server_events() {
+    use crate::lsp::{Diagnostic, Severity};
+
+    let path = std::env::temp_dir().join("bim-lsp-events.rs");
+    std::fs::write(&path, "fn main() {\n    helper();\n}\n").unwrap();
+    let mut buffer = Buffer::default();
+    buffer.open(&path.to_string_lossy()).unwrap();
+    let mut editor = EditorCore::new(buffer, Options::default(), vec2(800.0, 600.0), 1.0).unwrap();
+
+    let diagnostic = Diagnostic {
+        row: 1,
+        start_col: 4,
+        end_col: Some(10),
+        severity: Severity::Error,
+        message: "cannot find function `helper`".to_string(),
+    };
+    editor.handle_lsp_event(LspEvent::Diagnostics(
+        path.clone(),
+        vec![diagnostic.clone()],
+    ));
+    assert_eq!(
+        &[diagnostic],
+        editor.focused_buffer().unwrap().diagnostics()
+    );
+
+    editor.handle_lsp_event(LspEvent::Definition(path.clone(), 1, 4));
+    let cursor = &editor.focused_buffer().unwrap().cursor;
+    assert_eq!((1, 4), (cursor.text_row(), cursor.text_col()));
+
+    editor.handle_lsp_event(LspEvent::Hover(
+        "```rust\nfn helper()\n```\n---\nDoes things".to_string(),
+    ));
+    assert_eq!(Some("fn helper()"), editor.status_message());
+
+    // Nothing's set to run for Rust
+    editor.run_action(Action::OnWindow(WindowAction::GotoDefinition));
+    assert_eq!(Some("No language server for Rust"), editor.status_message());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_running_shell_commands() {
+    let buffer = Buffer::with_text("pear\napple\nfig\n");
+    let mut editor = EditorCore::new(buffer, Options::default(), vec2(800.0, 600.0), 1.0).unwrap();
+    let finish = |editor: &mut EditorCore, line: &str| {
+        editor.run_action(Action::OnWindow(WindowAction::RunExCommand(
+            line.to_string(),
+        )));
+        while !editor.running.is_empty() {
+            std::thread::sleep(Duration::from_millis(5));
+            editor.tick(Duration::from_millis(5));
+        }
+    };
+
+    finish(&mut editor, ":!sort");
+    assert_eq!(Some("apple\nfig\npear\n".to_string()), editor.contents());
+    assert_eq!(Some("Ran sort"), editor.status_message());
+
+    finish(&mut editor, ":w !wc -l");
+    assert_eq!("3", editor.contents().unwrap().trim());
+    assert!(editor.focused_buffer().unwrap().is_read_only());
+
+    finish(&mut editor, ":!exit 2");
+    assert_eq!(Some("exit 2 exited with 2"), editor.status_message());
+    assert_eq!("3", editor.contents().unwrap().trim());
+}
+
+#[test]
+fn test_only_redrawing_when_something_changed() {
+    let mut editor = EditorCore::new(
+        Buffer::with_text("text\n"),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+    assert!(editor.needs_redraw());
+    editor.clear_damage();
+    editor.tick(Duration::from_millis(10));
+    assert!(!editor.needs_redraw());
+
+    editor.handle_key(Key::Other('x'));
+    assert!(editor.needs_redraw());
+    editor.clear_damage();
+
+    editor.set_status_msg("Saved".to_string());
+    assert!(editor.needs_redraw());
+}
+
+#[test]
+fn test_message_levels_and_history() {
+    let buffer = Buffer::with_text("pear\napple\n");
+    let mut editor = EditorCore::new(buffer, Options::default(), vec2(800.0, 600.0), 1.0).unwrap();
+
+    editor.handle_key(Key::Control(Some('f')));
+    for c in "kiwi".chars() {
+        editor.handle_key(Key::Other(c));
+    }
+    editor.handle_key(Key::Return);
+    assert_eq!(Some("Not found: kiwi"), editor.status_message());
+    assert_eq!(Some(Level::Warn), editor.status_level());
+
+    let command = "echo oops >&2; echo more >&2; exit 1";
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(format!(
+        ":!{}",
+        command
+    ))));
+    while !editor.running.is_empty() {
+        std::thread::sleep(Duration::from_millis(5));
+        editor.tick(Duration::from_millis(5));
+    }
+    // Only the first line's shown, the rest is kept for later
+    let failed = format!("{} exited with 1: oops", command);
+    assert_eq!(Some(failed.as_str()), editor.status_message());
+    assert_eq!(Some(Level::Error), editor.status_level());
+
+    let dir = env::temp_dir().to_string_lossy().to_string();
+    editor.run_action(Action::OnWindow(WindowAction::SaveFileAs(dir)));
+    assert!(editor.status_message().unwrap().starts_with("Can't save!"));
+    assert_eq!(Some(Level::Error), editor.status_level());
+
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(
+        ":messages".to_string(),
+    )));
+    let history = editor.contents().unwrap();
+    let lines = history.lines().collect::<Vec<_>>();
+    assert_eq!("warn: Not found: kiwi", lines[0]);
+    assert_eq!(format!("info: Running {}...", command), lines[1]);
+    assert_eq!(format!("error: {}", failed), lines[2]);
+    assert_eq!("    more", lines[3]);
+    assert!(lines[4].starts_with("error: Can't save!"));
+    assert!(editor.focused_buffer().unwrap().is_read_only());
+}
+
+#[test]
+fn test_grep_and_jump_to_a_hit() {
+    let needle = format!("needle-{}-grep", std::process::id());
+    let filename = format!("bim-{}.txt", needle);
+    std::fs::write(&filename, format!("first\nfound {} here\n", needle)).unwrap();
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(format!(
+        ":grep {}",
+        needle
+    ))));
+    while editor.grep.is_some() {
+        std::thread::sleep(Duration::from_millis(5));
+        editor.tick(Duration::from_millis(5));
+    }
+    let results = editor.focused_buffer().unwrap();
+    assert_eq!("[Results]", results.name());
+    assert!(!results.is_dirty());
+    assert_eq!(
+        format!("{}:2:7: found {} here\n", filename, needle),
+        results.contents()
+    );
+    assert_eq!(
+        Some("1 matches in 1 files, Enter on one to go to it"),
+        editor.status_message()
+    );
+
+    editor.run_action(Action::OnBuffer(BufferAction::InsertNewlineAndReturn));
+    editor.check();
+    let buffer = editor.focused_buffer().unwrap();
+    assert_eq!(filename, buffer.name());
+    assert_eq!((1, 6), (buffer.cursor.text_row(), buffer.cursor.text_col()));
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_preview_replace_in_files_on_the_grep_worker() {
+    let needle = format!("needle-{}-replace", std::process::id());
+    let filename = format!("bim-{}.txt", needle);
+    std::fs::write(&filename, format!("first\n{} here\n", needle)).unwrap();
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+
+    editor.run_action(Action::OnWindow(WindowAction::PreviewReplaceInFiles(
+        needle.clone(),
+        "pin".to_string(),
+    )));
+    assert!(editor.grep.is_some());
+    while editor.grep.is_some() {
+        std::thread::sleep(Duration::from_millis(5));
+        editor.tick(Duration::from_millis(5));
+    }
+    let preview = editor.focused_buffer().unwrap().contents();
+    assert!(preview.starts_with(&format!("Replace: {}\nWith: pin\n", needle)));
+    assert!(preview.ends_with(&format!("{}\n[x] 2: {} here\n", filename, needle)));
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_marks_across_files() {
+    use crate::commands::MoveCursor;
+
+    let dir = crate::utils::temp_path("bim-marks", "");
+    std::fs::create_dir_all(&dir).unwrap();
+    let first = dir.join("first.txt").to_string_lossy().to_string();
+    let second = dir.join("second.txt").to_string_lossy().to_string();
+    std::fs::write(&first, "one\ntwo\nthree\n").unwrap();
+    std::fs::write(&second, "uno\ndos\n").unwrap();
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+    let open = |editor: &mut EditorCore<'_>, filename: &str| {
+        editor.run_action(Action::OnWindow(WindowAction::OpenFile(
+            filename.to_string(),
+        )));
+        while editor.focused_buffer().unwrap().is_loading() {
+            std::thread::sleep(Duration::from_millis(5));
+            editor.tick(Duration::from_millis(5));
+        }
+    };
+    let at = |editor: &EditorCore<'_>| {
+        let buffer = editor.focused_buffer().unwrap();
+        (
+            buffer.filename.clone().unwrap_or_default(),
+            buffer.cursor.text_row(),
+        )
+    };
+
+    // Uppercase marks need a file
+    editor.handle_key(Key::Control(Some('k')));
+    editor.handle_key(Key::Other('A'));
+    assert!(editor.status_message().unwrap().contains("needs a file"));
+
+    open(&mut editor, &first);
+    editor.run_action(Action::OnBuffer(BufferAction::MoveCursor(
+        MoveCursor::down(2),
+    )));
+    editor.handle_key(Key::Control(Some('k')));
+    editor.handle_key(Key::Other('A'));
+    assert_eq!(Some("Marked A at line 3"), editor.status_message());
+
+    open(&mut editor, &second);
+    editor.run_action(Action::OnWindow(WindowAction::SetMark('a')));
+    editor.run_action(Action::OnBuffer(BufferAction::MoveCursor(
+        MoveCursor::down(1),
+    )));
+    editor.handle_key(Key::Control(Some('j')));
+    editor.handle_key(Key::Other('A'));
+    assert_eq!((first.clone(), 2), at(&editor));
+
+    // The lowercase one is second's own
+    editor.run_action(Action::OnWindow(WindowAction::JumpToMark('a')));
+    assert_eq!(Some("Mark a isn't set"), editor.status_message());
+    editor.run_action(Action::OnWindow(WindowAction::GotoLocation(second.clone())));
+    editor.run_action(Action::OnWindow(WindowAction::JumpToMark('a')));
+    assert_eq!((second.clone(), 0), at(&editor));
+
+    // Moving A to second takes it out of first
+    editor.run_action(Action::OnWindow(WindowAction::SetMark('A')));
+    assert_eq!(
+        None,
+        editor
+            .container
+            .buffer_for_path_mut(Path::new(&first))
+            .unwrap()
+            .mark('A')
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_open_file_prompt() {
+    let dir = crate::utils::temp_path("bim-open", "");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("existing.txt"), "hello\n").unwrap();
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+    let type_text = |editor: &mut EditorCore<'_>, text: &str| {
+        for c in text.chars() {
+            editor.handle_key(Key::Other(c));
+        }
+    };
+    let open = |editor: &mut EditorCore<'_>| {
+        editor.handle_key(Key::Return);
+        editor.tick(Duration::from_millis(5));
+        while editor.focused_buffer().unwrap().is_loading() {
+            std::thread::sleep(Duration::from_millis(5));
+            editor.tick(Duration::from_millis(5));
+        }
+    };
+
+    // Tab finishes off the name
+    editor.handle_key(Key::Control(Some('o')));
+    type_text(&mut editor, &format!("{}/ex", dir.display()));
+    editor.handle_key(Key::Tab);
+    open(&mut editor);
+    let buffer = editor.focused_buffer().unwrap();
+    let existing = dir.join("existing.txt").to_string_lossy().to_string();
+    assert_eq!(Some(existing), buffer.filename);
+    assert_eq!("hello\n", buffer.rows[0].as_str());
+
+    // A file that isn't there yet is a new buffer, to be saved to make it
+    editor.handle_key(Key::Control(Some('o')));
+    type_text(&mut editor, &format!("{}/new.txt", dir.display()));
+    open(&mut editor);
+    let new = dir.join("new.txt").to_string_lossy().to_string();
+    assert_eq!(Some(new.clone()), editor.focused_buffer().unwrap().filename);
+    assert_eq!(
+        Some(format!("New file {}", new).as_str()),
+        editor.status_message()
+    );
+    assert!(!dir.join("new.txt").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_rename_file() {
+    let dir = crate::utils::temp_path("bim-rename", "");
+    std::fs::create_dir_all(&dir).unwrap();
+    let old = dir.join("notes.txt").to_string_lossy().to_string();
+    let new = dir.join("notes.rs").to_string_lossy().to_string();
+    std::fs::write(&old, "fn main() {}\n").unwrap();
+    let mut buffer = Buffer::default();
+    buffer.open(&old).unwrap();
+    let mut editor = EditorCore::new(buffer, Options::default(), vec2(800.0, 600.0), 1.0).unwrap();
+    assert_eq!(format!("{} - bim", old), editor.window_title());
+
+    editor.run_action(Action::OnWindow(WindowAction::RenameFile(new.clone())));
+    assert!(!Path::new(&old).exists());
+    assert_eq!("fn main() {}\n", std::fs::read_to_string(&new).unwrap());
+    assert_eq!(
+        Some(format!("Renamed {} to {}", old, new).as_str()),
+        editor.status_message()
+    );
+    assert_eq!(format!("{} - bim", new), editor.window_title());
+
+    // Saving as a new name leaves the old file where it was
+    let copy = dir.join("copy.rs").to_string_lossy().to_string();
+    editor.run_action(Action::OnWindow(WindowAction::SaveFileAs(copy.clone())));
+    assert!(Path::new(&new).exists());
+    assert!(Path::new(&copy).exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_closing_panes_with_unsaved_changes() {
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+    let close = Action::OnWindow(WindowAction::ClosePane);
+    editor.run_action(Action::OnWindow(WindowAction::SplitVertically));
+    assert_eq!(2, editor.container.num_panes());
+    editor.handle_key(Key::Other('x'));
+
+    // It asks first, and saying no leaves it open
+    editor.run_action(close.clone());
+    editor.handle_key(Key::Other('n'));
+    editor.handle_key(Key::Return);
+    editor.tick(Duration::from_millis(5));
+    assert_eq!(2, editor.container.num_panes());
+
+    editor.run_action(close.clone());
+    editor.handle_key(Key::Other('y'));
+    editor.handle_key(Key::Return);
+    editor.tick(Duration::from_millis(5));
+    assert_eq!(1, editor.container.num_panes());
+    assert!(!editor.should_quit());
+
+    // Closing the last one closes the window
+    editor.run_action(close);
+    assert!(editor.should_quit());
+}
+
+#[test]
+fn test_closing_the_last_pane_of_a_tab() {
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+    let close = Action::OnWindow(WindowAction::ClosePane);
+    editor.handle_key(Key::Other('x'));
+    editor.run_action(Action::OnWindow(WindowAction::NewTab));
+
+    // Only the tab goes, the other one's unsaved changes are still there
+    editor.run_action(close.clone());
+    assert!(!editor.should_quit());
+    assert_eq!(1, editor.tab_labels().len());
+    assert!(editor.is_dirty());
+
+    // The last tab's pane still asks before the window closes
+    editor.run_action(close);
+    assert!(!editor.should_quit());
+    editor.handle_key(Key::Other('y'));
+    editor.handle_key(Key::Return);
+    editor.tick(Duration::from_millis(5));
+    assert!(editor.should_quit());
+}
+
+#[test]
+fn test_tabs() {
+    let mut editor = EditorCore::new(
+        Buffer::default(),
+        Options::default(),
+        vec2(800.0, 600.0),
+        1.0,
+    )
+    .unwrap();
+    editor.update_gui(GuiAction::SetLineHeight(20.0));
+    assert_eq!(1, editor.tab_labels().len());
+    assert_eq!(0.0, editor.tab_bar_height());
+
+    // A new tab has a pane of its own, under the tab bar
+    editor.run_action(Action::OnWindow(WindowAction::NewTab));
+    editor.run_action(Action::OnWindow(WindowAction::SplitVertically));
+    editor.handle_key(Key::Other('x'));
+    assert_eq!(20.0, editor.tab_bar_height());
+    let dir = editor.tab_dir().to_path_buf();
+    assert_eq!(
+        vec![
+            TabLabel::new(1, &dir, false, false),
+            TabLabel::new(2, &dir, true, true)
+        ],
+        editor.tab_labels()
+    );
+    assert_eq!(2, editor.container.num_panes());
+    editor.run_action(Action::OnWindow(WindowAction::NextTab));
+    assert_eq!(1, editor.container.num_panes());
+    assert!(editor.is_dirty());
+    editor.run_action(Action::OnWindow(WindowAction::PrevTab));
+    assert_eq!(2, editor.container.num_panes());
+
+    // Tabs with unsaved changes stay open
+    editor.run_action(Action::OnWindow(WindowAction::CloseTab));
+    assert_eq!(2, editor.tab_labels().len());
+    editor.run_action(Action::OnWindow(WindowAction::PrevTab));
+    editor.run_action(Action::OnWindow(WindowAction::CloseTab));
+    assert_eq!(
+        vec![TabLabel::new(1, &dir, true, true)],
+        editor.tab_labels()
+    );
+    assert_eq!(2, editor.container.num_panes());
+    assert_eq!(0.0, editor.tab_bar_height());
+
+    editor.run_action(Action::OnWindow(WindowAction::ChangeDirectory(
+        "no/such/dir".to_string(),
+    )));
+    assert_eq!(Some(Level::Error), editor.status_level());
+    assert_eq!(dir, editor.tab_dir());
+}
@@ -0,0 +1,360 @@
+use std::fmt;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+// How much of a file to look at when guessing at UTF-16 without a BOM
+const SNIFF_LEN: usize = 1024;
+
+// How a file's text is stored on disk. Buffers always hold UTF-8, so
+// files are decoded on the way in and encoded back on the way out.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16LeBom,
+    Utf16Be,
+    Utf16BeBom,
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 BOM",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16LeBom => "UTF-16LE BOM",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16BeBom => "UTF-16BE BOM",
+            Encoding::Latin1 => "Latin-1",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Encoding {
+    // An encoding's name as typed, ignoring case, spaces and dashes, so
+    // "utf-16le" and "UTF16 LE" are the same
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_lowercase();
+        match name.as_str() {
+            "utf8" => Some(Encoding::Utf8),
+            "utf8bom" => Some(Encoding::Utf8Bom),
+            "utf16le" => Some(Encoding::Utf16Le),
+            "utf16lebom" => Some(Encoding::Utf16LeBom),
+            "utf16be" => Some(Encoding::Utf16Be),
+            "utf16bebom" => Some(Encoding::Utf16BeBom),
+            "latin1" | "iso88591" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    // A BOM settles it. Otherwise lots of zero bytes on one side of each
+    // pair means UTF-16, and anything that isn't valid UTF-8 is taken to
+    // be Latin-1, which any bytes at all can be read as.
+    pub fn detect(bytes: &[u8]) -> Self {
+        Self::detect_from(bytes, true)
+    }
+
+    // The same, but for just the start of a file, which can stop partway
+    // through a character
+    pub fn detect_start(bytes: &[u8]) -> Self {
+        Self::detect_from(bytes, false)
+    }
+
+    fn detect_from(bytes: &[u8], complete: bool) -> Self {
+        if bytes.starts_with(UTF8_BOM) {
+            Encoding::Utf8Bom
+        } else if bytes.starts_with(UTF16LE_BOM) {
+            Encoding::Utf16LeBom
+        } else if bytes.starts_with(UTF16BE_BOM) {
+            Encoding::Utf16BeBom
+        } else if let Some(encoding) = Self::sniff_utf16(bytes) {
+            encoding
+        } else {
+            match std::str::from_utf8(bytes) {
+                Ok(_) => Encoding::Utf8,
+                Err(err) if !complete && err.error_len().is_none() => Encoding::Utf8,
+                Err(_) => Encoding::Latin1,
+            }
+        }
+    }
+
+    fn is_utf16(self) -> bool {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                true
+            }
+            Encoding::Utf8 | Encoding::Utf8Bom | Encoding::Latin1 => false,
+        }
+    }
+
+    fn is_little_endian(self) -> bool {
+        self == Encoding::Utf16Le || self == Encoding::Utf16LeBom
+    }
+
+    // Zero bytes don't turn up in text, apart from in UTF-16, so a file
+    // with them in that isn't UTF-16 is most likely something else
+    pub fn looks_binary(bytes: &[u8]) -> bool {
+        !Self::detect_start(bytes).is_utf16() && bytes.contains(&0)
+    }
+
+    fn sniff_utf16(bytes: &[u8]) -> Option<Self> {
+        let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+        let pairs = sample.len() / 2;
+        if pairs == 0 {
+            return None;
+        }
+        let zeros_at = |offset: usize| {
+            sample
+                .chunks_exact(2)
+                .filter(|pair| pair[offset] == 0)
+                .count()
+        };
+        // Mostly ASCII text in UTF-16 has a zero in (nearly) every pair
+        let (even, odd) = (zeros_at(0), zeros_at(1));
+        if odd * 10 >= pairs * 7 && even * 10 < pairs {
+            Some(Encoding::Utf16Le)
+        } else if even * 10 >= pairs * 7 && odd * 10 < pairs {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
+    }
+
+    // Skipped over at the start of a file if it's there
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Latin1 => &[],
+            Encoding::Utf8 | Encoding::Utf8Bom => UTF8_BOM,
+            Encoding::Utf16Le | Encoding::Utf16LeBom => UTF16LE_BOM,
+            Encoding::Utf16Be | Encoding::Utf16BeBom => UTF16BE_BOM,
+        }
+    }
+
+    // Anything that can't be decoded comes out as U+FFFD
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let bytes = bytes.strip_prefix(self.bom()).unwrap_or(bytes);
+        self.decode_without_bom(bytes)
+    }
+
+    fn decode_without_bom(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let units = bytes
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        if self.is_little_endian() {
+                            u16::from_le_bytes([pair[0], pair[1]])
+                        } else {
+                            u16::from_be_bytes([pair[0], pair[1]])
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                String::from_utf16_lossy(&units)
+            }
+            Encoding::Latin1 => bytes.iter().map(|byte| *byte as char).collect(),
+        }
+    }
+
+    // Text that has no place in Latin-1 is saved as ?. A BOM is only
+    // written for the encodings that say they have one.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Utf8Bom => [UTF8_BOM, text.as_bytes()].concat(),
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let mut bytes = match self {
+                    Encoding::Utf16LeBom | Encoding::Utf16BeBom => self.bom().to_vec(),
+                    _ => vec![],
+                };
+                for unit in text.encode_utf16() {
+                    if self.is_little_endian() {
+                        bytes.extend_from_slice(&unit.to_le_bytes());
+                    } else {
+                        bytes.extend_from_slice(&unit.to_be_bytes());
+                    }
+                }
+                bytes
+            }
+            Encoding::Latin1 => text
+                .chars()
+                .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+                .collect(),
+        }
+    }
+}
+
+// Decodes a file a piece at a time, keeping back the end of a piece when
+// it stops partway through a character
+pub struct Decoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            pending: vec![],
+            started: false,
+        }
+    }
+
+    // last says there's nothing more to come, so nothing is kept back
+    pub fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut input = std::mem::take(&mut self.pending);
+        if !self.started {
+            let bom_len = if input.starts_with(self.encoding.bom()) {
+                self.encoding.bom().len()
+            } else {
+                0
+            };
+            input.drain(..bom_len);
+            self.started = true;
+        }
+        let keep = if last {
+            0
+        } else {
+            self.incomplete_tail(&input)
+        };
+        self.pending = input.split_off(input.len() - keep);
+        self.encoding.decode_without_bom(&input)
+    }
+
+    fn incomplete_tail(&self, bytes: &[u8]) -> usize {
+        match self.encoding {
+            Encoding::Utf8 | Encoding::Utf8Bom => {
+                // Find where the last character starts and see if all of
+                // it is there
+                for from_end in 1..=bytes.len().min(3) {
+                    let byte = bytes[bytes.len() - from_end];
+                    if byte & 0xC0 != 0x80 {
+                        let char_len = match byte {
+                            0xF0..=0xFF => 4,
+                            0xE0..=0xEF => 3,
+                            0xC0..=0xDF => 2,
+                            _ => 1,
+                        };
+                        return if char_len > from_end { from_end } else { 0 };
+                    }
+                }
+                0
+            }
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let odd = bytes.len() % 2;
+                let whole = &bytes[..bytes.len() - odd];
+                let last_unit = whole.rchunks_exact(2).next().map(|pair| {
+                    if self.encoding.is_little_endian() {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                });
+                // The first half of a surrogate pair waits for the second
+                match last_unit {
+                    Some(0xD800..=0xDBFF) => odd + 2,
+                    _ => odd,
+                }
+            }
+            Encoding::Latin1 => 0,
+        }
+    }
+}
+
+#[test]
+fn test_detect_encoding() {
+    assert_eq!(Encoding::Utf8, Encoding::detect(b"plain text\n"));
+    assert_eq!(Encoding::Utf8, Encoding::detect("caf\u{e9}\n".as_bytes()));
+    assert_eq!(Encoding::Utf8Bom, Encoding::detect(b"\xEF\xBB\xBFhi\n"));
+    assert_eq!(Encoding::Utf16LeBom, Encoding::detect(b"\xFF\xFEh\0i\0"));
+    assert_eq!(Encoding::Utf16Be, Encoding::detect(b"\0h\0i\0\n"));
+    assert_eq!(Encoding::Utf16Le, Encoding::detect(b"h\0i\0\n\0"));
+    assert_eq!(Encoding::Latin1, Encoding::detect(b"caf\xE9\n"));
+    // A whole file can't end partway through a character
+    assert_eq!(Encoding::Latin1, Encoding::detect(b"caf\xC3"));
+    assert_eq!(Encoding::Utf8, Encoding::detect_start(b"caf\xC3"));
+}
+
+#[test]
+fn test_parse_encoding_names() {
+    assert_eq!(Some(Encoding::Utf16Le), Encoding::parse("utf-16le"));
+    assert_eq!(Some(Encoding::Utf8Bom), Encoding::parse("UTF-8 BOM"));
+    assert_eq!(Some(Encoding::Latin1), Encoding::parse(" latin1 "));
+    for encoding in [
+        Encoding::Utf8,
+        Encoding::Utf8Bom,
+        Encoding::Utf16Le,
+        Encoding::Utf16LeBom,
+        Encoding::Utf16Be,
+        Encoding::Utf16BeBom,
+        Encoding::Latin1,
+    ] {
+        assert_eq!(Some(encoding), Encoding::parse(&encoding.to_string()));
+    }
+    assert_eq!(None, Encoding::parse("ebcdic"));
+}
+
+#[test]
+fn test_looks_binary() {
+    assert!(!Encoding::looks_binary(b"plain text\n"));
+    assert!(!Encoding::looks_binary(b"caf\xE9\n"));
+    assert!(!Encoding::looks_binary(b"h\0i\0\n\0"));
+    assert!(!Encoding::looks_binary(b""));
+    assert!(Encoding::looks_binary(b"\x7FELF\x02\x01\x01\0\0\0\0"));
+}
+
+#[test]
+fn test_encodings_round_trip() {
+    let text = "caf\u{e9} na\u{ef}ve\r\n";
+    for encoding in [
+        Encoding::Utf8,
+        Encoding::Utf8Bom,
+        Encoding::Utf16Le,
+        Encoding::Utf16LeBom,
+        Encoding::Utf16Be,
+        Encoding::Utf16BeBom,
+        Encoding::Latin1,
+    ]
+    .iter()
+    {
+        let bytes = encoding.encode(text);
+        assert_eq!(*encoding, Encoding::detect(&bytes));
+        assert_eq!(text, encoding.decode(&bytes));
+    }
+    assert_eq!(b"snow ?".to_vec(), Encoding::Latin1.encode("snow \u{2603}"));
+    assert_eq!(b"h\0i\0".to_vec(), Encoding::Utf16Le.encode("hi"));
+    assert_eq!(
+        b"\xFE\xFF\0h\0i".to_vec(),
+        Encoding::Utf16BeBom.encode("hi")
+    );
+}
+
+#[test]
+fn test_decoding_in_pieces() {
+    let text = "\u{1F600} caf\u{e9}\n";
+    for encoding in [Encoding::Utf8, Encoding::Utf8Bom, Encoding::Utf16LeBom].iter() {
+        let bytes = encoding.encode(text);
+        let mut decoder = Decoder::new(*encoding);
+        let decoded = bytes
+            .chunks(3)
+            .map(|piece| decoder.decode(piece, false))
+            .collect::<String>()
+            + &decoder.decode(&[], true);
+        assert_eq!(text, decoded);
+    }
+    assert_eq!(
+        Encoding::Utf8,
+        Encoding::detect_start(&"caf\u{e9}".as_bytes()[..4])
+    );
+}
@@ -0,0 +1,201 @@
+use crate::action::{Action, BufferAction, GuiAction, WindowAction};
+
+// Turns a vim style command line, like "w notes.txt" or "set minimap=on",
+// into the actions it stands for. The leading ':' is optional.
+pub fn parse_ex(line: &str) -> Result<Vec<Action>, String> {
+    let line = line.trim();
+    let line = line.strip_prefix(':').unwrap_or(line).trim_start();
+    if let Some(command) = line.strip_prefix('!') {
+        return shell_command(command, WindowAction::FilterThroughCommand);
+    }
+    let (command, argument) = match line.find(char::is_whitespace) {
+        Some(at) => (&line[..at], line[at..].trim()),
+        None => (line, ""),
+    };
+    let argument = Some(argument).filter(|argument| !argument.is_empty());
+
+    let actions = match (command, argument) {
+        ("w", None) | ("write", None) => vec![Action::OnWindow(WindowAction::SaveFile)],
+        ("w", Some(command)) | ("write", Some(command)) if command.starts_with('!') => {
+            return shell_command(&command[1..], WindowAction::CommandIntoBuffer);
+        }
+        ("w", Some(filename)) | ("write", Some(filename)) => vec![Action::OnWindow(
+            WindowAction::SaveFileAs(filename.to_string()),
+        )],
+        ("saveas", Some(filename)) => vec![Action::OnWindow(WindowAction::SaveFileAs(
+            filename.to_string(),
+        ))],
+        ("rename", Some(filename)) | ("mv", Some(filename)) => vec![Action::OnWindow(
+            WindowAction::RenameFile(filename.to_string()),
+        )],
+        ("saveas", None) => return Err("save as what?".to_string()),
+        ("rename", None) | ("mv", None) => return Err("rename to what?".to_string()),
+        ("q", None) | ("quit", None) => vec![Action::OnGui(GuiAction::Quit)],
+        ("clo", None) | ("close", None) => vec![Action::OnWindow(WindowAction::ClosePane)],
+        ("clo!", None) | ("close!", None) => vec![Action::OnWindow(WindowAction::ForceClosePane)],
+        ("q!", None) | ("quit!", None) => vec![Action::OnGui(GuiAction::ForceQuit)],
+        ("wq", None) | ("x", None) => vec![
+            Action::OnWindow(WindowAction::SaveFile),
+            Action::OnGui(GuiAction::Quit),
+        ],
+        ("e", Some(filename)) | ("edit", Some(filename)) => {
+            vec![Action::OnWindow(WindowAction::OpenFile(
+                filename.to_string(),
+            ))]
+        }
+        ("e", None) | ("edit", None) => return Err("edit which file?".to_string()),
+        ("grep", Some(pattern)) => vec![Action::OnWindow(WindowAction::GrepInFiles(
+            pattern.to_string(),
+        ))],
+        ("grep", None) => return Err("grep for what? e.g. grep /fn \\w+/".to_string()),
+        ("set", Some(setting)) => vec![Action::OnWindow(parse_set(setting)?)],
+        ("set", None) => return Err("set what? e.g. set minimap=on".to_string()),
+        ("tabnew", None) => vec![Action::OnWindow(WindowAction::NewTab)],
+        ("tabnew", Some(dir)) => vec![
+            Action::OnWindow(WindowAction::NewTab),
+            Action::OnWindow(WindowAction::ChangeDirectory(dir.to_string())),
+        ],
+        ("tabc", None) | ("tabclose", None) => vec![Action::OnWindow(WindowAction::CloseTab)],
+        ("tabn", None) | ("tabnext", None) => vec![Action::OnWindow(WindowAction::NextTab)],
+        ("tabp", None) | ("tabprevious", None) => vec![Action::OnWindow(WindowAction::PrevTab)],
+        // Like the shell, cd on its own goes home
+        ("cd", dir) => vec![Action::OnWindow(WindowAction::ChangeDirectory(
+            dir.unwrap_or("~").to_string(),
+        ))],
+        ("noh", None) | ("nohlsearch", None) => {
+            vec![Action::OnBuffer(BufferAction::ClearSearchHighlight)]
+        }
+        ("messages", None) | ("mes", None) => vec![Action::OnWindow(WindowAction::ShowMessages)],
+        ("", _) => vec![],
+        (_, _) => return Err(format!("not a command: {}", line)),
+    };
+    Ok(actions)
+}
+
+// "!sort" filters the text through sort, and "w !wc" shows what wc says
+// about it
+fn shell_command(command: &str, action: fn(String) -> WindowAction) -> Result<Vec<Action>, String> {
+    match command.trim() {
+        "" => Err("run which command?".to_string()),
+        command => Ok(vec![Action::OnWindow(action(command.to_string()))]),
+    }
+}
+
+// "name=value", or "name" and "noname" to turn a setting on and off
+fn parse_set(setting: &str) -> Result<WindowAction, String> {
+    let (name, value) = match setting.find('=') {
+        Some(at) => (setting[..at].trim(), setting[at + 1..].trim()),
+        None => match setting.strip_prefix("no") {
+            Some(name) => (name, "off"),
+            None => (setting, "on"),
+        },
+    };
+    if name.is_empty() {
+        return Err(format!("set what? got {:?}", setting));
+    }
+    Ok(WindowAction::SetOption(name.to_string(), value.to_string()))
+}
+
+#[test]
+fn test_parse_ex() {
+    let save = Action::OnWindow(WindowAction::SaveFile);
+    let quit = Action::OnGui(GuiAction::Quit);
+    assert_eq!(Ok(vec![save.clone()]), parse_ex(":w"));
+    assert_eq!(Ok(vec![save.clone()]), parse_ex("write "));
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::SaveFileAs(
+            "my notes.txt".to_string()
+        ))]),
+        parse_ex(":w my notes.txt")
+    );
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::RenameFile(
+            "src/lib.rs".to_string()
+        ))]),
+        parse_ex(":mv src/lib.rs")
+    );
+    assert_eq!(Err("rename to what?".to_string()), parse_ex(":rename"));
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::ForceClosePane)]),
+        parse_ex(":close!")
+    );
+    assert_eq!(Ok(vec![quit.clone()]), parse_ex(":q"));
+    assert_eq!(
+        Ok(vec![Action::OnGui(GuiAction::ForceQuit)]),
+        parse_ex(": q!")
+    );
+    assert_eq!(Ok(vec![save, quit]), parse_ex(":wq"));
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::OpenFile(
+            "src/main.rs".to_string()
+        ))]),
+        parse_ex(":e src/main.rs")
+    );
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::ShowMessages)]),
+        parse_ex(":messages")
+    );
+    assert_eq!(
+        Ok(vec![
+            Action::OnWindow(WindowAction::NewTab),
+            Action::OnWindow(WindowAction::ChangeDirectory("../notes".to_string()))
+        ]),
+        parse_ex(":tabnew ../notes")
+    );
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::ChangeDirectory(
+            "~".to_string()
+        ))]),
+        parse_ex(":cd")
+    );
+    assert_eq!(Ok(vec![]), parse_ex(":"));
+    assert!(parse_ex(":e").is_err());
+    assert_eq!(
+        Err("not a command: frobnicate now".to_string()),
+        parse_ex(":frobnicate now")
+    );
+}
+
+#[test]
+fn test_parse_ex_shell_commands() {
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::FilterThroughCommand(
+            "sort -u".to_string()
+        ))]),
+        parse_ex(":!sort -u")
+    );
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::CommandIntoBuffer(
+            "grep -n fn".to_string()
+        ))]),
+        parse_ex(":w !grep -n fn")
+    );
+    assert!(parse_ex(":! ").is_err());
+    assert_eq!(
+        Ok(vec![Action::OnWindow(WindowAction::GrepInFiles(
+            "/fn \\w+/".to_string()
+        ))]),
+        parse_ex(":grep /fn \\w+/")
+    );
+    assert!(parse_ex(":grep").is_err());
+    assert!(parse_ex(":w !").is_err());
+}
+
+#[test]
+fn test_parse_ex_set() {
+    let set = |name: &str, value: &str| {
+        Ok(vec![Action::OnWindow(WindowAction::SetOption(
+            name.to_string(),
+            value.to_string(),
+        ))])
+    };
+    assert_eq!(
+        set("line_numbers", "relative"),
+        parse_ex(":set line_numbers=relative")
+    );
+    assert_eq!(set("font_size", "14"), parse_ex(":set font_size = 14"));
+    assert_eq!(set("minimap", "on"), parse_ex(":set minimap"));
+    assert_eq!(set("minimap", "off"), parse_ex(":set nominimap"));
+    assert!(parse_ex(":set").is_err());
+    assert!(parse_ex(":set =on").is_err());
+}
@@ -0,0 +1,192 @@
+// Runs a shell command with some text on its stdin, like `sort` over the
+// selection or `rustfmt` over the buffer, on another thread so a slow one
+// doesn't freeze everything while it runs
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+// Where a command's output goes once it's done
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    // Over the rows it was given, if the buffer they're from hasn't been
+    // changed (or swapped for another) while it ran
+    Replace {
+        name: String,
+        revision: u64,
+        rows: Range<usize>,
+    },
+    NewBuffer,
+}
+
+// What a command that succeeded printed, with anything it warned about on
+// stderr kept apart from its output
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Printed {
+    pub output: String,
+    pub errors: String,
+}
+
+pub struct External {
+    command: String,
+    destination: Destination,
+    receiver: Receiver<Result<Printed, String>>,
+}
+
+impl External {
+    pub fn spawn(command: &str, input: String, destination: Destination) -> io::Result<Self> {
+        let mut child = shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(run(&mut child, input));
+        });
+        Ok(Self {
+            command: command.to_string(),
+            destination,
+            receiver,
+        })
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn destination(&self) -> &Destination {
+        &self.destination
+    }
+
+    // What it printed, or why it failed, once it's finished
+    pub fn try_finish(&mut self) -> Option<Result<Printed, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("stopped without a result".to_string())),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+// The input's written from its own thread, as a command that prints a lot
+// before it's read everything would otherwise block with us waiting on it.
+// A failure's reason is the first line on stderr, followed by the rest of it.
+fn run(child: &mut Child, input: String) -> Result<Printed, String> {
+    let mut stdin = child.stdin.take().ok_or("no stdin")?;
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let mut stderr = child.stderr.take().ok_or("no stderr")?;
+    let errors = thread::spawn(move || {
+        let mut errors = vec![];
+        let _ = stderr.read_to_end(&mut errors);
+        errors
+    });
+    let mut output = vec![];
+    if let Some(stdout) = child.stdout.as_mut() {
+        stdout
+            .read_to_end(&mut output)
+            .map_err(|err| err.to_string())?;
+    }
+    let status = child.wait().map_err(|err| err.to_string())?;
+    // A command that doesn't read its input closes the pipe on us, which
+    // is only a problem if it went on to fail
+    let written = writer.join().unwrap_or(Ok(()));
+    let errors = errors.join().unwrap_or_default();
+    let errors = String::from_utf8_lossy(&errors).trim().to_string();
+    if !status.success() {
+        let mut lines = errors.lines();
+        let reason = lines
+            .next()
+            .map(|line| format!(": {}", line.trim()))
+            .unwrap_or_default();
+        let rest = lines.collect::<Vec<_>>().join("\n");
+        let rest = Some(rest)
+            .filter(|rest| !rest.is_empty())
+            .map(|rest| format!("\n{}", rest))
+            .unwrap_or_default();
+        return Err(match status.code() {
+            Some(code) => format!("exited with {}{}{}", code, reason, rest),
+            None => format!("was killed{}{}", reason, rest),
+        });
+    }
+    if let Err(err) = written {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            return Err(err.to_string());
+        }
+    }
+    Ok(Printed {
+        output: String::from_utf8_lossy(&output).into_owned(),
+        errors,
+    })
+}
+
+#[cfg(test)]
+fn wait(mut external: External) -> Result<Printed, String> {
+    loop {
+        if let Some(result) = external.try_finish() {
+            return result;
+        }
+        thread::sleep(std::time::Duration::from_millis(5));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_running_commands() {
+    let sort = External::spawn("sort", "pear\napple\n".to_string(), Destination::NewBuffer);
+    assert_eq!(
+        Ok("apple\npear\n".to_string()),
+        wait(sort.unwrap()).map(|printed| printed.output)
+    );
+
+    let fails = External::spawn(
+        "echo oh no >&2; exit 3",
+        String::new(),
+        Destination::NewBuffer,
+    );
+    assert_eq!(
+        Err("exited with 3: oh no".to_string()),
+        wait(fails.unwrap())
+    );
+    let fails = External::spawn(
+        "echo oh >&2; echo ' no' >&2; exit 3",
+        String::new(),
+        Destination::NewBuffer,
+    );
+    assert_eq!(
+        Err("exited with 3: oh\n no".to_string()),
+        wait(fails.unwrap())
+    );
+    let warns = External::spawn("echo careful >&2", String::new(), Destination::NewBuffer);
+    assert_eq!(
+        Ok(Printed {
+            output: String::new(),
+            errors: "careful".to_string()
+        }),
+        wait(warns.unwrap())
+    );
+
+    // Doesn't read what it's given
+    let big = "x".repeat(1024 * 1024);
+    let echo = External::spawn("echo hi", big, Destination::NewBuffer);
+    assert_eq!(
+        Ok("hi\n".to_string()),
+        wait(echo.unwrap()).map(|printed| printed.output)
+    );
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+const POLL_INTERVAL: u64 = 250;
+const NEW_LINE_HIGHLIGHT: u64 = 1500;
+
+// State for a pane that is tailing its file, like `tail -f`
+pub struct Follow {
+    poll_interval: Duration,
+    since_poll: Duration,
+    highlight_for: Duration,
+    recent_rows: Vec<(usize, Duration)>,
+}
+
+impl Default for Follow {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(POLL_INTERVAL),
+            since_poll: Duration::default(),
+            highlight_for: Duration::from_millis(NEW_LINE_HIGHLIGHT),
+            recent_rows: Vec::new(),
+        }
+    }
+}
+
+impl Follow {
+    // Advances the clocks, returning true when the file should be checked again
+    pub fn tick(&mut self, duration: Duration) -> bool {
+        for (_, age) in self.recent_rows.iter_mut() {
+            *age += duration;
+        }
+        let highlight_for = self.highlight_for;
+        self.recent_rows.retain(|(_, age)| *age < highlight_for);
+
+        self.since_poll += duration;
+        if self.since_poll >= self.poll_interval {
+            self.since_poll = Duration::default();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn mark_new_rows(&mut self, first_row: usize, num_rows: usize) {
+        for row in first_row..first_row + num_rows {
+            self.recent_rows.push((row, Duration::default()));
+        }
+    }
+
+    pub fn recent_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.recent_rows.iter().map(|(row, _)| *row)
+    }
+}
+
+#[test]
+fn test_follow_polls_on_interval() {
+    let mut follow = Follow::default();
+    assert!(!follow.tick(Duration::from_millis(100)));
+    assert!(!follow.tick(Duration::from_millis(100)));
+    assert!(follow.tick(Duration::from_millis(100)));
+    assert!(!follow.tick(Duration::from_millis(100)));
+}
+
+#[test]
+fn test_follow_new_rows_fade() {
+    let mut follow = Follow::default();
+    follow.mark_new_rows(10, 2);
+    assert_eq!(vec![10, 11], follow.recent_rows().collect::<Vec<_>>());
+    follow.tick(Duration::from_millis(1000));
+    follow.mark_new_rows(12, 1);
+    follow.tick(Duration::from_millis(1000));
+    assert_eq!(vec![12], follow.recent_rows().collect::<Vec<_>>());
+}
@@ -0,0 +1,97 @@
+use crate::paths;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Font files that can be drawn with. Collections (.ttc) hold more than one
+// font, so aren't any use.
+const FONT_EXTENSIONS: [&str; 2] = ["ttf", "otf"];
+
+// Family names are matched against file names, leaving out case, spaces and
+// dashes, so "DejaVu Sans Mono" finds DejaVuSansMono.ttf
+fn simplify(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| FONT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// The file of the family's regular style (or its only style), looking
+// through dirs in order, and the directories inside them
+fn find_family_in(family: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let family = simplify(family);
+    let regular = format!("{}regular", family);
+    for dir in dirs {
+        let mut entries = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>(),
+            Err(_) => continue,
+        };
+        entries.sort();
+        let (subdirs, files): (Vec<_>, Vec<_>) =
+            entries.into_iter().partition(|path| path.is_dir());
+        let found = files.into_iter().find(|path| {
+            let stem = path
+                .file_stem()
+                .map(|stem| simplify(&stem.to_string_lossy()))
+                .unwrap_or_default();
+            is_font_file(path) && (stem == family || stem == regular)
+        });
+        if let Some(path) = found.or_else(|| find_family_in(&family, &subdirs)) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+// A font can be given by the path to its file, or its family name to look
+// for among the installed fonts
+pub fn find_font(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+    if path.is_file() {
+        return Some(path);
+    }
+    find_family_in(name, &paths::font_dirs())
+}
+
+pub fn read_font(name: &str) -> Result<Vec<u8>, String> {
+    let path = find_font(name).ok_or_else(|| format!("Can't find font {:?}", name))?;
+    fs::read(&path).map_err(|err| format!("Can't read font {}: {}", path.display(), err))
+}
+
+#[test]
+fn test_find_family() {
+    let dir = crate::utils::temp_path("bim-fonts", "");
+    let nested = dir.join("truetype").join("dejavu");
+    fs::create_dir_all(&nested).unwrap();
+    for name in &[
+        "DejaVuSansMono-Bold.ttf",
+        "DejaVuSansMono.ttf",
+        "Inconsolata-Regular.otf",
+        "Inconsolata-Bold.otf",
+        "NotoSansCJK.ttc",
+    ] {
+        fs::write(nested.join(name), b"").unwrap();
+    }
+    let dirs = vec![dir.join("missing"), dir.clone()];
+
+    assert_eq!(
+        Some(nested.join("DejaVuSansMono.ttf")),
+        find_family_in("DejaVu Sans Mono", &dirs)
+    );
+    assert_eq!(
+        Some(nested.join("Inconsolata-Regular.otf")),
+        find_family_in("inconsolata", &dirs)
+    );
+    assert_eq!(None, find_family_in("Noto Sans CJK", &dirs));
+    assert_eq!(None, find_family_in("Comic Sans", &dirs));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
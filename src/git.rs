@@ -0,0 +1,52 @@
+// For when bim is git's $EDITOR
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub const COMMIT_MESSAGE_FILES: &[&str] = &["COMMIT_EDITMSG", "MERGE_MSG", "TAG_EDITMSG"];
+pub const REBASE_TODO_FILE: &str = "git-rebase-todo";
+// Git leaves out everything below this line, which is where the diff goes
+// with `git commit -v`
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+pub fn is_message_file(filename: &str) -> bool {
+    Path::new(filename)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name == REBASE_TODO_FILE || COMMIT_MESSAGE_FILES.contains(&name))
+        .unwrap_or(false)
+}
+
+// Nothing but comments and blank lines
+pub fn is_empty_message(text: &str) -> bool {
+    text.lines()
+        .take_while(|line| *line != SCISSORS)
+        .all(|line| line.trim().is_empty() || line.starts_with('#'))
+}
+
+// Git gives up when the message is left empty, and so should its editor,
+// so that scripts waiting on it can tell
+pub fn check_message(filename: &str) -> Result<(), Box<dyn Error>> {
+    let text = fs::read_to_string(filename).unwrap_or_default();
+    if is_empty_message(&text) {
+        Err(format!("{} was left empty", filename).into())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_empty_messages() {
+    assert!(is_message_file(".git/COMMIT_EDITMSG"));
+    assert!(is_message_file(".git/rebase-merge/git-rebase-todo"));
+    assert!(!is_message_file("src/git.rs"));
+
+    assert!(is_empty_message(""));
+    assert!(is_empty_message("\n# Please enter the commit message\n#\n"));
+    assert!(is_empty_message(&format!(
+        "\n# comment\n{}\ndiff --git a/x b/x\n",
+        SCISSORS
+    )));
+    assert!(!is_empty_message("Fix #12\n\n# comment\n"));
+}
@@ -0,0 +1,199 @@
+// Searching every file in the project, on another thread so a big project
+// doesn't freeze everything. The hits come back a file at a time, as lines
+// like "src/main.rs:42:7: fn main() {" for a results buffer to list.
+
+use crate::paths::walk_files;
+use crate::regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    // "/fo+/" is a regex, anything else is looked for as it is
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let regex = text
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty());
+        match regex {
+            Some(pattern) => Regex::new(pattern)
+                .map(Pattern::Regex)
+                .map_err(|err| format!("Bad regex {}: {}", pattern, err)),
+            None if text.is_empty() => Err("grep for what?".to_string()),
+            None => Ok(Pattern::Literal(text.to_string())),
+        }
+    }
+
+    // The char column of the first match in line
+    fn find(&self, line: &str) -> Option<usize> {
+        match self {
+            Pattern::Literal(needle) => line
+                .find(needle.as_str())
+                .map(|at| line[..at].chars().count()),
+            Pattern::Regex(regex) => regex.find_all(line).first().map(|found| found.start),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub path: PathBuf,
+    pub row: usize,
+    pub col: usize,
+    pub line: String,
+}
+
+impl Hit {
+    // Counting from 1, as compilers and grep do
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.row + 1,
+            self.col + 1,
+            self.line
+        )
+    }
+}
+
+// The "file:line:col" a results line starts with
+pub fn hit_location(line: &str) -> Option<&str> {
+    let (location, _) = line.split_once(": ")?;
+    let mut parts = location.rsplitn(3, ':');
+    let numbers = parts
+        .by_ref()
+        .take(2)
+        .all(|number| number.parse::<usize>().is_ok());
+    match parts.next() {
+        Some(file) if numbers && !file.is_empty() => Some(location),
+        _ => None,
+    }
+}
+
+pub struct Grep {
+    receiver: Receiver<Vec<Hit>>,
+    hits: usize,
+    files: usize,
+}
+
+impl Grep {
+    // Dropping it stops the search
+    pub fn spawn(root: &Path, pattern: Pattern) -> Self {
+        let root = root.to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || search_dir(&root, &pattern, &sender));
+        Self {
+            receiver,
+            hits: 0,
+            files: 0,
+        }
+    }
+
+    // The hits in the next file, and Disconnected once it's looked at them all
+    pub fn try_recv(&mut self) -> Result<Vec<Hit>, TryRecvError> {
+        let hits = self.receiver.try_recv()?;
+        self.hits += hits.len();
+        self.files += 1;
+        Ok(hits)
+    }
+
+    // How many hits, and in how many files, so far
+    pub fn found(&self) -> (usize, usize) {
+        (self.hits, self.files)
+    }
+}
+
+// Files that aren't UTF-8 are skipped. Stops once nobody's listening any
+// more.
+fn search_dir(dir: &Path, pattern: &Pattern, sender: &Sender<Vec<Hit>>) {
+    walk_files(dir, &mut |path| {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return true,
+        };
+        let hits = text
+            .lines()
+            .enumerate()
+            .filter_map(|(row, line)| {
+                pattern.find(line).map(|col| Hit {
+                    path: path.to_path_buf(),
+                    row,
+                    col,
+                    line: line.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        hits.is_empty() || sender.send(hits).is_ok()
+    });
+}
+
+#[test]
+fn test_hit_lines() {
+    let hit = Hit {
+        path: PathBuf::from("src/main.rs"),
+        row: 41,
+        col: 6,
+        line: "    let x: u8 = 1;".to_string(),
+    };
+    let line = hit.to_line();
+    assert_eq!("src/main.rs:42:7:     let x: u8 = 1;", line);
+    assert_eq!(Some("src/main.rs:42:7"), hit_location(&line));
+    assert_eq!(None, hit_location("not a hit: at all"));
+    assert_eq!(None, hit_location(":1:2: no file"));
+}
+
+#[test]
+fn test_grep_in_files() {
+    let root = crate::utils::temp_path("bim-grep", "");
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join("src/lib.rs"), "fn one() {}\nfn two() { one() }\n").unwrap();
+    fs::write(root.join("notes.txt"), "nothing\n").unwrap();
+    fs::write(root.join(".git/config"), "one\n").unwrap();
+    fs::write(root.join("long.txt"), "a".repeat(100_000)).unwrap();
+
+    let wait = |mut grep: Grep| {
+        let mut hits = vec![];
+        loop {
+            match grep.try_recv() {
+                Ok(more) => hits.extend(more),
+                Err(TryRecvError::Empty) => thread::sleep(std::time::Duration::from_millis(5)),
+                Err(TryRecvError::Disconnected) => return (hits, grep.found()),
+            }
+        }
+    };
+
+    let (hits, found) = wait(Grep::spawn(&root, Pattern::parse("one").unwrap()));
+    assert_eq!((2, 1), found);
+    assert_eq!(
+        vec![(0, 3), (1, 11)],
+        hits.iter()
+            .map(|hit| (hit.row, hit.col))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(root.join("src/lib.rs"), hits[0].path);
+
+    // However long a line is
+    let (hits, _) = wait(Grep::spawn(&root, Pattern::parse("/a.*b/").unwrap()));
+    assert!(hits.is_empty());
+
+    let (hits, _) = wait(Grep::spawn(&root, Pattern::parse(r"/t\w+\(/").unwrap()));
+    assert_eq!(
+        vec![(1, 3)],
+        hits.iter()
+            .map(|hit| (hit.row, hit.col))
+            .collect::<Vec<_>>()
+    );
+
+    assert!(Pattern::parse("").is_err());
+    assert!(Pattern::parse("/(/").is_err());
+    assert_eq!(Ok(Pattern::Literal("/".to_string())), Pattern::parse("/"));
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,111 @@
+use crate::fonts::read_font;
+use crate::unicode::char_width;
+use gfx_glyph::rusttype::Font;
+use gfx_glyph::FontId;
+
+const BUILT_IN_FONT: &[u8] = include_bytes!("iosevka-regular.ttf");
+// Installed fonts that have what the built in font is missing (CJK, emoji
+// and symbols), used when no fallbacks are set, if they're there
+const DEFAULT_FALLBACK_FONTS: [&str; 5] = [
+    "Noto Sans Mono CJK SC",
+    "Noto Sans CJK SC",
+    "Noto Emoji",
+    "DejaVu Sans Mono",
+    "Symbola",
+];
+
+fn load_font(name: &str) -> Result<Font<'static>, String> {
+    Font::from_bytes(read_font(name)?).map_err(|err| format!("{} isn't a font: {}", name, err))
+}
+
+// The font to draw text with, followed by the fallbacks for whatever
+// characters it doesn't have. Each font's FontId is its place in the list.
+pub fn load_fonts(font: Option<&str>, fallbacks: &[String]) -> Result<Vec<Font<'static>>, String> {
+    let mut fonts = vec![match font {
+        Some(name) => load_font(name)?,
+        None => Font::from_bytes(BUILT_IN_FONT).map_err(|err| err.to_string())?,
+    }];
+    if fallbacks.is_empty() {
+        fonts.extend(
+            DEFAULT_FALLBACK_FONTS
+                .iter()
+                .filter_map(|name| load_font(name).ok()),
+        );
+    } else {
+        for name in fallbacks {
+            fonts.push(load_font(name)?);
+        }
+    }
+    Ok(fonts)
+}
+
+// Splits text into runs that each need just the one font, the first in
+// fonts that has their characters. Characters none of them have stay in the
+// first font, as do marks that go on top of the character before them.
+pub fn font_runs<'t>(fonts: &[Font<'_>], text: &'t str) -> Vec<(&'t str, FontId)> {
+    runs_with(fonts.len(), text, |idx, c| fonts[idx].glyph(c).id().0 != 0)
+}
+
+fn runs_with(
+    num_fonts: usize,
+    text: &str,
+    has_glyph: impl Fn(usize, char) -> bool,
+) -> Vec<(&str, FontId)> {
+    if num_fonts < 2 {
+        return vec![(text, FontId(0))];
+    }
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    let mut run_font = None;
+    for (byte_idx, c) in text.char_indices() {
+        let font = if char_width(c) == 0 && run_font.is_some() {
+            run_font
+        } else {
+            Some((0..num_fonts).find(|&idx| has_glyph(idx, c)).unwrap_or(0))
+        };
+        if let Some(current) = run_font.filter(|current| Some(*current) != font) {
+            runs.push((&text[run_start..byte_idx], FontId(current)));
+            run_start = byte_idx;
+        }
+        run_font = font;
+    }
+    runs.push((&text[run_start..], FontId(run_font.unwrap_or(0))));
+    runs
+}
+
+#[test]
+fn test_font_runs() {
+    // The first font only has ASCII, the second has everything but emoji
+    let has_glyph = |idx: usize, c: char| match idx {
+        0 => c.is_ascii(),
+        _ => c != '🦀',
+    };
+    let runs = |text| runs_with(2, text, has_glyph);
+
+    assert_eq!(vec![("hello", FontId(0))], runs("hello"));
+    assert_eq!(vec![("", FontId(0))], runs(""));
+    assert_eq!(
+        vec![("say ", FontId(0)), ("日本", FontId(1)), (" ok", FontId(0))],
+        runs("say 日本 ok")
+    );
+    // Combining marks stay with what they're on, and nobody has the crab
+    assert_eq!(
+        vec![
+            ("e\u{0301}x", FontId(0)),
+            ("é", FontId(1)),
+            ("🦀", FontId(0))
+        ],
+        runs("e\u{0301}xé🦀")
+    );
+    assert_eq!(vec![("日本", FontId(0))], runs_with(1, "日本", has_glyph));
+}
+
+#[test]
+fn test_built_in_font_loads() {
+    let fonts = load_fonts(None, &[]).unwrap();
+    assert!(fonts[0].glyph('a').id().0 != 0);
+    assert_eq!(
+        vec![("plain text", FontId(0))],
+        font_runs(&fonts[..1], "plain text")
+    );
+}
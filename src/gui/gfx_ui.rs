@@ -1,24 +1,38 @@
 use crate::buffer::Buffer;
 use crate::debug_log::DebugLog;
+use crate::gui::font_chain::load_fonts;
 use crate::gui::gl_renderer::{create_bundle, GlRenderer};
 use crate::gui::persist_window_state::PersistWindowState;
 use crate::gui::window::Window;
 use crate::gui::{ColorFormat, DepthFormat};
+use crate::idle::PowerMode;
 use crate::options::Options;
+use crate::paths;
+use crate::pipe;
 use crate::BIM_VERSION;
 use gfx;
-use gfx_glyph::GlyphBrushBuilder;
 use glam::vec2;
 use glutin::dpi::LogicalSize;
 use glutin::Api::OpenGl;
 use glutin::{ContextBuilder, EventsLoop, GlProfile, GlRequest, Icon, WindowBuilder};
 use std::error::Error;
-use std::time::Instant;
+use std::io::Write;
+use std::time::{Duration, Instant};
 
 const XBIM_DEBUG_LOG: &str = ".xbim_debug";
+// While saving power a frame is drawn this often, unless input comes first
+const POWER_SAVING_FRAME_TIME: Duration = Duration::from_millis(250);
+// With nothing changed to draw, the clock's moved on this often, for the
+// cursor to blink and the like
+const IDLE_FRAME_TIME: Duration = Duration::from_millis(50);
+// How often events are looked for while waiting
+const WAIT_POLL_TIME: Duration = Duration::from_millis(10);
 
 pub fn run(options: Options) -> Result<(), Box<dyn Error>> {
-    let debug_log = DebugLog::new(XBIM_DEBUG_LOG);
+    let debug_log_filename = paths::debug_log_file()
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|| XBIM_DEBUG_LOG.to_string());
+    let debug_log = DebugLog::new(&debug_log_filename);
     debug_log.start()?;
     use crate::config::RunConfig::*;
 
@@ -72,21 +86,32 @@ pub fn run(options: Options) -> Result<(), Box<dyn Error>> {
     ))?;
 
     let quad_bundle = create_bundle(&mut factory, main_color, main_depth);
-    let fonts: Vec<&[u8]> = vec![include_bytes!("iosevka-regular.ttf")];
-
-    let glyph_brush = GlyphBrushBuilder::using_fonts_bytes(fonts)
-        .initial_cache_size((512, 512))
-        .depth_test(gfx::preset::depth::LESS_EQUAL_WRITE)
-        .build(factory.clone());
+    let fonts = load_fonts(options.font.as_deref(), &options.fallback_fonts).or_else(|err| {
+        eprintln!("{}", err);
+        load_fonts(None, &[])
+    })?;
 
     let encoder: gfx::Encoder<_, _> = factory.create_command_buffer().into();
 
-    let mut renderer = GlRenderer::new(glyph_brush, encoder, device, quad_bundle, window_dim);
+    let mut renderer = GlRenderer::new(fonts, factory, encoder, device, quad_bundle, window_dim);
 
     let mut buffer = Buffer::default();
-    if let RunOpenFiles(filenames) = &options.run_type {
-        buffer.open(&filenames[0])?;
+    buffer.set_virtual_edit(options.virtual_edit);
+    buffer.set_auto_close(options.auto_close);
+    buffer.set_trim_trailing_whitespace(options.trim_trailing_whitespace);
+    buffer.set_indent_options(options.indent.clone());
+    buffer.guard_open(options.large_file_len);
+    buffer.highlight_lazily_over(options.lazy_highlight_len);
+    if let Some(bytes) = &options.piped_in {
+        buffer.open_bytes(bytes);
+    } else if let RunOpenFiles(filenames) = &options.run_type {
+        buffer.open_or_new_in_background(&filenames[0], &options.templates)?;
     }
+    let mut stdout = if options.pipe_out {
+        Some(pipe::take_stdout()?)
+    } else {
+        None
+    };
 
     let mut window = Window::new(
         &mut renderer,
@@ -94,7 +119,6 @@ pub fn run(options: Options) -> Result<(), Box<dyn Error>> {
         gfx_window,
         window_dim,
         logical_size,
-        28.0,
         dpi,
         buffer,
         persist_window_state,
@@ -118,9 +142,31 @@ pub fn run(options: Options) -> Result<(), Box<dyn Error>> {
             });
 
             window.update_dt(elapsed);
-            window.render(&mut renderer)?;
+            let drawn = window.needs_redraw();
+            if drawn {
+                window.render(&mut renderer)?;
+            }
 
             window.end_frame();
+
+            // Swapping buffers waits on vsync, so without a frame drawn it's
+            // events that are waited on instead
+            let wait_for = match window.power_mode() {
+                PowerMode::Saving => Some(POWER_SAVING_FRAME_TIME),
+                PowerMode::Normal if !drawn => Some(IDLE_FRAME_TIME),
+                PowerMode::Normal => None,
+            };
+            if let Some(wait_for) = wait_for {
+                let wait_start = Instant::now();
+                let mut woken = false;
+                while !woken && wait_start.elapsed() < wait_for {
+                    std::thread::sleep(WAIT_POLL_TIME);
+                    event_loop.poll_events(|event| {
+                        woken = true;
+                        let _ = window.update(&mut renderer, event);
+                    });
+                }
+            }
         }
     }
 
@@ -150,5 +196,14 @@ pub fn run(options: Options) -> Result<(), Box<dyn Error>> {
         });
     }
 
+    window.save_session();
+    window.log_all_stats();
+    window.remove_swap_files();
+
+    if let (Some(stdout), Some(buffer)) = (stdout.as_mut(), window.focused_buffer()) {
+        buffer.write_text(stdout)?;
+        stdout.flush()?;
+    }
+
     Ok(())
 }
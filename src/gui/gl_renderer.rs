@@ -1,11 +1,18 @@
+use crate::gui::font_chain::font_runs;
 use crate::gui::transforms::Transforms;
 use crate::gui::{ColorFormat, DepthFormat};
 use crate::rect::Rect;
+use crate::renderer::{Renderer, Text, TextAlign};
 use gfx::handle::{DepthStencilView, RenderTargetView};
 use gfx::traits::FactoryExt;
 use gfx::{self, *};
-use gfx_glyph::GlyphBrush;
-use glam::{Mat4, Vec2};
+use gfx_glyph::rusttype::Font;
+use gfx_glyph::{
+    GlyphBrush, GlyphBrushBuilder, GlyphCruncher, HorizontalAlign, Layout, Scale, SectionText,
+    VariedSection, VerticalAlign,
+};
+use glam::{vec2, vec3, Mat4, Vec2};
+use std::error::Error;
 
 const QUAD: [Vertex; 4] = [
     Vertex { pos: [-1.0, 1.0] },
@@ -76,8 +83,20 @@ pub fn draw<R, C>(
     quad_bundle.encode(encoder);
 }
 
+fn build_glyph_brush<'a>(
+    fonts: Vec<Font<'a>>,
+    factory: &gfx_device_gl::Factory,
+) -> GlyphBrush<'a, gfx_device_gl::Resources, gfx_device_gl::Factory> {
+    GlyphBrushBuilder::using_fonts(fonts)
+        .initial_cache_size((512, 512))
+        .depth_test(gfx::preset::depth::LESS_EQUAL_WRITE)
+        .build(factory.clone())
+}
+
 pub struct GlRenderer<'a> {
     pub glyph_brush: GlyphBrush<'a, gfx_device_gl::Resources, gfx_device_gl::Factory>,
+    // Kept to make the glyph brush again when the fonts change
+    factory: gfx_device_gl::Factory,
     pub encoder: Encoder<gfx_device_gl::Resources, gfx_device_gl::CommandBuffer>,
     pub device: gfx_device_gl::Device,
     pub quad_bundle:
@@ -87,7 +106,8 @@ pub struct GlRenderer<'a> {
 
 impl<'a> GlRenderer<'a> {
     pub fn new(
-        glyph_brush: GlyphBrush<'a, gfx_device_gl::Resources, gfx_device_gl::Factory>,
+        fonts: Vec<Font<'a>>,
+        factory: gfx_device_gl::Factory,
         encoder: Encoder<gfx_device_gl::Resources, gfx_device_gl::CommandBuffer>,
         device: gfx_device_gl::Device,
         quad_bundle: pso::bundle::Bundle<
@@ -97,7 +117,8 @@ impl<'a> GlRenderer<'a> {
         window_dim: Vec2,
     ) -> Self {
         Self {
-            glyph_brush,
+            glyph_brush: build_glyph_brush(fonts, &factory),
+            factory,
             encoder,
             device,
             quad_bundle,
@@ -105,11 +126,51 @@ impl<'a> GlRenderer<'a> {
         }
     }
 
+    // The first font is the one text is drawn with, the rest are fallbacks
+    pub fn set_fonts(&mut self, fonts: Vec<Font<'a>>) {
+        self.glyph_brush = build_glyph_brush(fonts, &self.factory);
+    }
+
     pub fn resize(&mut self, window_dim: Vec2) {
         self.transforms.window_dim = window_dim;
     }
 
-    pub fn draw_quad(&mut self, color: [f32; 3], rect: Rect, z: f32) {
+    // Each span is split into runs for the fonts that have its characters
+    fn section<'t>(&self, text: &Text<'t>) -> VariedSection<'t> {
+        let fonts = self.glyph_brush.fonts();
+        let scale = Scale::uniform(text.scale);
+        let section_texts = text
+            .spans
+            .iter()
+            .flat_map(|span| {
+                font_runs(fonts, span.text)
+                    .into_iter()
+                    .map(move |(run, font_id)| SectionText {
+                        text: run,
+                        scale,
+                        color: span.color,
+                        font_id,
+                    })
+            })
+            .collect();
+        let layout = match text.align {
+            TextAlign::TopLeft => Layout::default(),
+            TextAlign::Centre => Layout::default()
+                .h_align(HorizontalAlign::Center)
+                .v_align(VerticalAlign::Center),
+        };
+        VariedSection {
+            bounds: text.bounds.into(),
+            screen_position: text.position.into(),
+            text: section_texts,
+            z: text.z,
+            layout,
+        }
+    }
+}
+
+impl<'a> Renderer for GlRenderer<'a> {
+    fn draw_quad(&mut self, color: [f32; 3], rect: Rect, z: f32) {
         let transform = self.transforms.transform_for_quad(rect);
         draw(
             &mut self.encoder,
@@ -119,4 +180,39 @@ impl<'a> GlRenderer<'a> {
             z,
         );
     }
+
+    fn draw_text(&mut self, text: Text<'_>) -> Result<(), Box<dyn Error>> {
+        let section = self.section(&text);
+        match text.line_spacing {
+            Some(line_spacing) => self.glyph_brush.queue_custom_layout(section, &line_spacing),
+            None => self.glyph_brush.queue(section),
+        }
+
+        // The transform is in GL coordinates, which span the whole window
+        let out_color = &self.quad_bundle.data.out_color;
+        let (_, window_height, ..) = out_color.get_dimensions();
+        let lift = vec3(0.0, text.lifted / (f32::from(window_height) / 2.0), 0.0);
+        let transform = Mat4::from_translation(lift)
+            * Mat4::from_cols_array_2d(&gfx_glyph::default_transform(out_color));
+        self.glyph_brush
+            .use_queue()
+            .transform(transform.to_cols_array_2d())
+            .depth_target(&self.quad_bundle.data.out_depth)
+            .draw(&mut self.encoder, &self.quad_bundle.data.out_color)?;
+        Ok(())
+    }
+
+    fn measure(&mut self, text: &Text<'_>) -> Option<Vec2> {
+        let section = self.section(text);
+        let pixel_bounds = match text.line_spacing {
+            Some(line_spacing) => self
+                .glyph_brush
+                .pixel_bounds_custom_layout(section, &line_spacing),
+            None => self.glyph_brush.pixel_bounds(section),
+        }?;
+        Some(vec2(
+            (pixel_bounds.max.x - pixel_bounds.min.x) as f32,
+            (pixel_bounds.max.y - pixel_bounds.min.y) as f32,
+        ))
+    }
 }
@@ -76,6 +76,12 @@ pub fn keyboard_event_to_keycode(event: KeyboardInput) -> Option<Key> {
         #[allow(clippy::collapsible_if)]
         match event.virtual_keycode {
             Some(VirtualKeyCode::Escape) => Some(Key::Escape),
+            Some(VirtualKeyCode::Left) if event.modifiers.ctrl => Some(Key::ControlArrowLeft),
+            Some(VirtualKeyCode::Right) if event.modifiers.ctrl => Some(Key::ControlArrowRight),
+            Some(VirtualKeyCode::Up) if event.modifiers.ctrl => Some(Key::ControlArrowUp),
+            Some(VirtualKeyCode::Down) if event.modifiers.ctrl => Some(Key::ControlArrowDown),
+            Some(VirtualKeyCode::Back) if event.modifiers.ctrl => Some(Key::ControlBackspace),
+            Some(VirtualKeyCode::Delete) if event.modifiers.ctrl => Some(Key::ControlDelete),
             Some(VirtualKeyCode::Left) => Some(Key::ArrowLeft),
             Some(VirtualKeyCode::Right) => Some(Key::ArrowRight),
             Some(VirtualKeyCode::Up) => Some(Key::ArrowUp),
@@ -87,7 +93,10 @@ pub fn keyboard_event_to_keycode(event: KeyboardInput) -> Option<Key> {
             Some(VirtualKeyCode::Back) => Some(Key::Backspace),
             Some(VirtualKeyCode::Delete) => Some(Key::Delete),
             Some(VirtualKeyCode::Return) => Some(Key::Return),
+            Some(VirtualKeyCode::Tab) if event.modifiers.shift => Some(Key::ShiftTab),
+            Some(VirtualKeyCode::Tab) => Some(Key::Tab),
             Some(VirtualKeyCode::F11) => Some(Key::Function(11)),
+            Some(VirtualKeyCode::F12) => Some(Key::Function(12)),
             Some(VirtualKeyCode::LControl) => None,
             Some(VirtualKeyCode::RControl) => None,
             Some(VirtualKeyCode::LAlt) => None,
@@ -97,7 +106,14 @@ pub fn keyboard_event_to_keycode(event: KeyboardInput) -> Option<Key> {
             Some(keycode) => {
                 if event.modifiers.ctrl || event.modifiers.alt || event.modifiers.logo {
                     if event.modifiers.ctrl && !event.modifiers.alt && !event.modifiers.logo {
-                        if let Some(virtual_char) = KEYCODE_TO_CHAR.get(&keycode).cloned() {
+                        if let Some(mut virtual_char) = KEYCODE_TO_CHAR.get(&keycode).cloned() {
+                            if event.modifiers.shift && virtual_char.is_ascii_lowercase() {
+                                virtual_char = virtual_char.to_ascii_uppercase();
+                            } else if event.modifiers.shift && virtual_char == '5' {
+                                virtual_char = '%';
+                            } else if event.modifiers.shift && virtual_char == ';' {
+                                virtual_char = ':';
+                            }
                             // FIXME: shift+virtual_keycode
                             // } else if keycode == VirtualKeyCode::Equals
                             //     && event.modifiers.shift
@@ -129,3 +145,33 @@ pub fn keyboard_event_to_keycode(event: KeyboardInput) -> Option<Key> {
         None
     }
 }
+
+#[cfg(test)]
+fn ctrl_shift_press(keycode: VirtualKeyCode) -> KeyboardInput {
+    KeyboardInput {
+        scancode: 0,
+        state: ElementState::Pressed,
+        virtual_keycode: Some(keycode),
+        modifiers: glutin::ModifiersState {
+            shift: true,
+            ctrl: true,
+            alt: false,
+            logo: false,
+        },
+    }
+}
+
+#[test]
+fn test_ctrl_colon_opens_the_command_line() {
+    use crate::action::{Action, WindowAction};
+    use crate::keymap::{MapOrAction, DEFAULT_KEYMAP};
+
+    let key = keyboard_event_to_keycode(ctrl_shift_press(VirtualKeyCode::Semicolon));
+    assert_eq!(Some(Key::Control(Some(':'))), key);
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnWindow(
+            WindowAction::CommandLine
+        ))),
+        DEFAULT_KEYMAP.lookup(&key.unwrap())
+    );
+}
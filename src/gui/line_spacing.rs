@@ -0,0 +1,41 @@
+use crate::line_spacing::LineSpacing;
+use gfx_glyph::rusttype::{point, Rect};
+use gfx_glyph::{
+    FontId, FontMap, GlyphPositioner, Layout, PositionedGlyph, SectionGeometry, SectionText,
+};
+
+type Color = [f32; 4];
+
+// gfx_glyph lays the lines out and then they're spread apart
+impl GlyphPositioner for LineSpacing {
+    fn calculate_glyphs<'font, F: FontMap<'font>>(
+        &self,
+        fonts: &F,
+        geometry: &SectionGeometry,
+        sections: &[SectionText<'_>],
+    ) -> Vec<(PositionedGlyph<'font>, Color, FontId)> {
+        let (top, bottom) = (
+            geometry.screen_position.1,
+            geometry.screen_position.1 + geometry.bounds.1,
+        );
+        Layout::default()
+            .calculate_glyphs(fonts, geometry, sections)
+            .into_iter()
+            .filter_map(|(mut glyph, color, font_id)| {
+                let position = glyph.position();
+                let y = top + self.spaced_y(position.y - top);
+                // Lines pushed down past the bounds would draw over
+                // whatever is below the section
+                if y > bottom {
+                    return None;
+                }
+                glyph.set_position(point(position.x, y));
+                Some((glyph, color, font_id))
+            })
+            .collect()
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect<f32> {
+        Layout::default().bounds_rect(geometry)
+    }
+}
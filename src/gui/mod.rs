@@ -1,10 +1,13 @@
-mod animation;
-mod container;
+mod font_chain;
 pub mod gfx_ui;
 mod gl_renderer;
 mod keycode_to_char;
-mod pane;
+mod line_spacing;
 mod persist_window_state;
+mod profiler;
+pub mod replay;
+mod tab_bar;
+mod tooltip;
 mod transforms;
 mod window;
 
@@ -1,7 +1,9 @@
+use crate::paths;
 use glutin::dpi::LogicalPosition;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Read;
+use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize)]
 pub struct PersistWindowState {
@@ -13,7 +15,12 @@ impl PersistWindowState {
     pub fn save(&self) {
         match serde_yaml::to_string(self) {
             Ok(config_string) => {
-                fs::write(Self::config_filename(), config_string).unwrap();
+                let filename = Self::config_filename();
+                if let Err(e) = paths::create_parent(&filename)
+                    .and_then(|_| fs::write(&filename, config_string))
+                {
+                    println!("Error saving config to {}: {:?}", filename.display(), e);
+                }
             }
             Err(e) => {
                 println!("Error saving config to string: {:?}", e);
@@ -39,8 +46,8 @@ impl PersistWindowState {
         Self::default()
     }
 
-    fn config_filename() -> String {
-        String::from(".bim_persist_state.yaml")
+    fn config_filename() -> PathBuf {
+        paths::window_state_file().unwrap_or_else(|| PathBuf::from(".bim_persist_state.yaml"))
     }
 }
 
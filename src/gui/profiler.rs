@@ -0,0 +1,291 @@
+use crate::rect::RectBuilder;
+use crate::renderer::{Renderer, Text};
+use crate::theme::Theme;
+use glam::{vec2, Vec2};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+// How many frames the graph goes back
+const FRAMES_KEPT: usize = 120;
+// A frame at 60Hz, as the window waits on vsync to swap its buffers
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+const BAR_WIDTH: f32 = 2.0;
+// How tall a bar taking the whole budget is, and how far over it they go
+const BUDGET_HEIGHT: f32 = 30.0;
+const MAX_HEIGHT: f32 = BUDGET_HEIGHT * 2.0;
+
+// What a frame spends its time on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Phase {
+    // Handling events and moving the clock on
+    Update,
+    // Drawing everything
+    Render,
+    // Swapping the buffers, which is where it waits for vsync
+    Present,
+}
+
+const PHASES: [Phase; 3] = [Phase::Update, Phase::Render, Phase::Present];
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Update => "update",
+            Phase::Render => "render",
+            Phase::Present => "present",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Frame {
+    phases: [Duration; 3],
+    // Since the frame before started, sleeping to save power and all
+    interval: Duration,
+}
+
+impl Frame {
+    // The work the frame did. The time between frames when the window's
+    // idling doesn't count against it.
+    fn total(&self) -> Duration {
+        self.phases.iter().sum()
+    }
+
+    fn is_slow(&self) -> bool {
+        self.total() > FRAME_BUDGET
+    }
+}
+
+// Times each frame's phases, to draw over everything else where the time's
+// going. It's toggled with a GuiAction, and only times frames while shown.
+pub struct Profiler {
+    visible: bool,
+    frames: VecDeque<Frame>,
+    current: Frame,
+    frame_started: Option<Instant>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            frames: VecDeque::with_capacity(FRAMES_KEPT),
+            current: Frame::default(),
+            frame_started: None,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.frames.clear();
+        self.current = Frame::default();
+        self.frame_started = None;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn start_frame(&mut self) {
+        if !self.visible {
+            return;
+        }
+        let now = Instant::now();
+        let interval = self
+            .frame_started
+            .map(|started| now - started)
+            .unwrap_or_default();
+        self.frame_started = Some(now);
+        self.current = Frame {
+            interval,
+            ..Frame::default()
+        };
+    }
+
+    // Adds to the time the frame's spent on phase, which can happen more
+    // than once a frame, like updating for each event
+    pub fn add(&mut self, phase: Phase, duration: Duration) {
+        if self.visible {
+            self.current.phases[phase as usize] += duration;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if !self.visible || self.frame_started.is_none() {
+            return;
+        }
+        if self.frames.len() == FRAMES_KEPT {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(self.current);
+    }
+
+    fn fps(&self) -> f32 {
+        let intervals = self
+            .frames
+            .iter()
+            .map(|frame| frame.interval)
+            .sum::<Duration>();
+        if intervals.is_zero() {
+            return 0.0;
+        }
+        // The first frame has nothing before it to be an interval from
+        let counted = self.frames.iter().filter(|frame| !frame.interval.is_zero());
+        counted.count() as f32 / intervals.as_secs_f32()
+    }
+
+    // The last frame's phases, and how the frames in the graph have done
+    pub fn summary(&self) -> String {
+        let last = self.frames.back().copied().unwrap_or_default();
+        let phases = PHASES
+            .iter()
+            .map(|phase| format!("{} {}", phase.name(), millis(last.phases[*phase as usize])))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let slow = self.frames.iter().filter(|frame| frame.is_slow()).count();
+        format!(
+            "{:.0} fps  frame {}\n{}\n{} of {} frames over {}",
+            self.fps(),
+            millis(last.total()),
+            phases,
+            slow,
+            self.frames.len(),
+            millis(FRAME_BUDGET)
+        )
+    }
+
+    // In the top right corner, the summary over a graph of the frames'
+    // times, with a line across where the budget is. Frames over it are
+    // drawn in the error colour.
+    pub fn render(
+        &self,
+        renderer: &mut dyn Renderer,
+        theme: &Theme,
+        window_dim: Vec2,
+        scale: f32,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.visible {
+            return Ok(());
+        }
+        let _guard = flame::start_guard("render profiler");
+
+        let summary = self.summary();
+        let graph_width = FRAMES_KEPT as f32 * BAR_WIDTH;
+        let text = Text {
+            bounds: vec2(f32::INFINITY, f32::INFINITY),
+            scale,
+            z: 0.1,
+            ..Text::plain(&summary, theme.popup_fg.rgba())
+        };
+        let text_bounds = renderer.measure(&text).unwrap_or_default();
+        let width = graph_width.max(text_bounds.x());
+        let top_left = vec2(window_dim.x() - width - 12.0, 8.0);
+        let panel = RectBuilder::new()
+            .top_left(top_left - vec2(4.0, 4.0))
+            .bounds(vec2(width, text_bounds.y() + MAX_HEIGHT + 4.0) + vec2(8.0, 8.0))
+            .build();
+        renderer.draw_quad(theme.popup_bg.rgb(), panel, 0.15);
+        renderer.draw_text(Text {
+            position: top_left,
+            ..text
+        })?;
+
+        let baseline = top_left.y() + text_bounds.y() + 4.0 + MAX_HEIGHT;
+        let budget_line = RectBuilder::new()
+            .top_left(vec2(top_left.x(), baseline - BUDGET_HEIGHT))
+            .bounds(vec2(graph_width, 1.0))
+            .build();
+        renderer.draw_quad(theme.popup_outline.rgb(), budget_line, 0.05);
+        for (index, (height, slow)) in self.bars().into_iter().enumerate() {
+            let colour = if slow {
+                theme.diagnostic_error
+            } else {
+                theme.diagnostic_info
+            };
+            let bar = RectBuilder::new()
+                .top_left(vec2(
+                    top_left.x() + index as f32 * BAR_WIDTH,
+                    baseline - height,
+                ))
+                .bounds(vec2(BAR_WIDTH, height))
+                .build();
+            renderer.draw_quad(colour.rgb(), bar, 0.1);
+        }
+
+        Ok(())
+    }
+
+    // Each frame's height in the graph, oldest first, and whether it went
+    // over budget. Really slow frames are cut off at the top.
+    fn bars(&self) -> Vec<(f32, bool)> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let fraction = frame.total().as_secs_f32() / FRAME_BUDGET.as_secs_f32();
+                ((fraction * BUDGET_HEIGHT).min(MAX_HEIGHT), frame.is_slow())
+            })
+            .collect()
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f32() * 1000.0)
+}
+
+#[cfg(test)]
+use crate::renderer::RecordingRenderer;
+
+#[cfg(test)]
+fn frame(profiler: &mut Profiler, update: u64, render: u64, present: u64) {
+    profiler.start_frame();
+    profiler.add(Phase::Update, Duration::from_millis(update));
+    profiler.add(Phase::Render, Duration::from_millis(render));
+    profiler.add(Phase::Present, Duration::from_millis(present));
+    profiler.end_frame();
+}
+
+#[test]
+fn test_timing_frames() {
+    let mut profiler = Profiler::default();
+    // Nothing's timed while it's hidden
+    frame(&mut profiler, 1, 2, 3);
+    assert!(profiler.frames.is_empty());
+
+    profiler.toggle();
+    frame(&mut profiler, 1, 4, 10);
+    frame(&mut profiler, 2, 40, 1);
+    // Part of the next frame, which hasn't finished
+    profiler.add(Phase::Update, Duration::from_millis(1));
+    assert_eq!(
+        "update 2.0ms  render 40.0ms  present 1.0ms",
+        profiler.summary().lines().nth(1).unwrap()
+    );
+    assert!(profiler.summary().ends_with("1 of 2 frames over 16.7ms"));
+    let bars = profiler.bars();
+    assert!((bars[0].0 - BUDGET_HEIGHT * 15.0 / 16.667).abs() < 0.01);
+    assert!(!bars[0].1);
+    assert_eq!((MAX_HEIGHT, true), bars[1]);
+
+    let theme = Theme::default();
+    let mut renderer = RecordingRenderer::default();
+    profiler
+        .render(&mut renderer, &theme, vec2(800.0, 600.0), 16.0)
+        .unwrap();
+    assert_eq!(
+        1,
+        renderer.quads_coloured(theme.diagnostic_error.rgb()).len()
+    );
+    assert_eq!(
+        1,
+        renderer.quads_coloured(theme.diagnostic_info.rgb()).len()
+    );
+
+    for _ in 0..FRAMES_KEPT {
+        frame(&mut profiler, 1, 1, 1);
+    }
+    assert_eq!(FRAMES_KEPT, profiler.frames.len());
+    assert!(profiler.summary().ends_with("0 of 120 frames over 16.7ms"));
+}
@@ -0,0 +1,48 @@
+use crate::action::Action;
+use crate::buffer::Buffer;
+use crate::options::Options;
+use crate::pane::Pane;
+use crate::recorder::read_log;
+use std::error::Error;
+
+// Re-runs a recorded action log against filename without opening a window.
+// Gui and window actions need a real window so they're skipped.
+pub fn run(
+    filename: Option<&str>,
+    log_filename: &str,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let entries = read_log(log_filename)?;
+
+    let mut buffer = Buffer::default();
+    buffer.set_auto_close(options.auto_close);
+    buffer.set_trim_trailing_whitespace(options.trim_trailing_whitespace);
+    buffer.set_indent_options(options.indent.clone());
+    if let Some(filename) = filename {
+        buffer.open_or_new(filename, &options.templates)?;
+    }
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let revision = pane.buffer.revision();
+        if revision != entry.rev {
+            println!(
+                "Action {} was recorded at revision {} but the buffer is at revision {}",
+                i, entry.rev, revision
+            );
+        }
+        match entry.action.clone() {
+            Action::OnBuffer(buffer_action) => pane.update_buffer(buffer_action),
+            Action::OnPane(pane_action) => pane.do_action(pane_action),
+            action => println!("Skipping {:?} while replaying", action),
+        }
+    }
+
+    println!(
+        "Replayed {} actions, buffer is at revision {}",
+        entries.len(),
+        pane.buffer.revision()
+    );
+
+    Ok(())
+}
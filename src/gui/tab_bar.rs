@@ -0,0 +1,88 @@
+use crate::rect::RectBuilder;
+use crate::renderer::{Renderer, Text};
+use crate::tabs::TabLabel;
+use crate::theme::Theme;
+use glam::{vec2, Vec2};
+use std::error::Error;
+
+// Either side of each tab's label
+const TAB_PADDING: f32 = 8.0;
+const TAB_BAR_Z: f32 = 0.5;
+
+// Along the top of the window, with the tab being shown picked out the way
+// the focused pane's status line is
+pub fn render(
+    renderer: &mut dyn Renderer,
+    theme: &Theme,
+    tabs: &[TabLabel],
+    bounds: Vec2,
+    scale: f32,
+) -> Result<(), Box<dyn Error>> {
+    let _guard = flame::start_guard("render tab bar");
+
+    let bar = RectBuilder::new()
+        .top_left(vec2(0.0, 0.0))
+        .bounds(bounds)
+        .build();
+    renderer.draw_quad(theme.status_unfocused_bg.rgb(), bar, TAB_BAR_Z);
+
+    let mut left = 0.0;
+    for tab in tabs {
+        let label = tab.to_string();
+        let colour = if tab.active {
+            theme.status_fg
+        } else {
+            theme.status_unfocused_fg()
+        };
+        let text = Text {
+            position: vec2(left + TAB_PADDING, 0.0),
+            bounds: vec2(f32::INFINITY, bounds.y()),
+            scale,
+            z: TAB_BAR_Z - 0.1,
+            ..Text::plain(&label, colour.rgba())
+        };
+        let text_width = renderer
+            .measure(&text)
+            .map(|size| size.x())
+            .unwrap_or_default();
+        let tab_width = text_width + TAB_PADDING * 2.0;
+        if tab.active {
+            let rect = RectBuilder::new()
+                .top_left(vec2(left, 0.0))
+                .bounds(vec2(tab_width, bounds.y()))
+                .build();
+            renderer.draw_quad(theme.status_bg.rgb(), rect, TAB_BAR_Z - 0.05);
+        }
+        renderer.draw_text(text)?;
+        left += tab_width;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_rendering_tabs() {
+    use crate::renderer::RecordingRenderer;
+    use std::path::Path;
+
+    use crate::colours::Colour;
+
+    let theme = Theme {
+        status_unfocused_bg: Colour::rgb_from_int_tuple((40, 40, 40)),
+        ..Theme::default()
+    };
+    let tabs = [
+        TabLabel::new(1, Path::new("/code/bim"), false, false),
+        TabLabel::new(2, Path::new("/notes"), true, true),
+    ];
+    let mut renderer = RecordingRenderer::default();
+    render(&mut renderer, &theme, &tabs, vec2(800.0, 20.0), 16.0).unwrap();
+    assert_eq!(vec!["1 bim", "2 notes [+]"], renderer.texts());
+    assert_eq!(1, renderer.quads_coloured(theme.status_bg.rgb()).len());
+    assert_eq!(
+        1,
+        renderer
+            .quads_coloured(theme.status_unfocused_bg.rgb())
+            .len()
+    );
+}
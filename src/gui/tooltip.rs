@@ -0,0 +1,79 @@
+use glam::{vec2, Vec2};
+use std::time::Duration;
+
+pub struct Tooltip {
+    delay: Duration,
+    hovered_for: Duration,
+    looked_up: bool,
+    pub position: Vec2,
+    pub text: Option<String>,
+}
+
+impl Tooltip {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            hovered_for: Duration::default(),
+            looked_up: false,
+            position: vec2(0.0, 0.0),
+            text: None,
+        }
+    }
+
+    // The mouse moved, so start waiting all over again
+    pub fn reset(&mut self, position: Vec2) {
+        self.position = position;
+        self.hovered_for = Duration::default();
+        self.looked_up = false;
+        self.text = None;
+    }
+
+    // Hide until the mouse moves again
+    pub fn dismiss(&mut self) {
+        self.looked_up = true;
+        self.text = None;
+    }
+
+    // Returns true once the mouse has rested for long enough to look up what's under it
+    pub fn tick(&mut self, duration: Duration) -> bool {
+        if self.looked_up {
+            return false;
+        }
+        self.hovered_for += duration;
+        if self.hovered_for >= self.delay {
+            self.looked_up = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn show(&mut self, text: Option<String>) {
+        self.text = text;
+    }
+}
+
+#[test]
+fn test_tooltip_waits_for_delay() {
+    let mut tooltip = Tooltip::new(Duration::from_millis(500));
+    tooltip.reset(vec2(10.0, 10.0));
+    assert!(!tooltip.tick(Duration::from_millis(300)));
+    assert!(tooltip.tick(Duration::from_millis(300)));
+    assert!(
+        !tooltip.tick(Duration::from_millis(300)),
+        "only looked up once"
+    );
+    tooltip.show(Some(String::from("hello")));
+    assert_eq!(Some(String::from("hello")), tooltip.text);
+}
+
+#[test]
+fn test_tooltip_dismissed_until_moved() {
+    let mut tooltip = Tooltip::new(Duration::from_millis(500));
+    tooltip.show(Some(String::from("hello")));
+    tooltip.dismiss();
+    assert_eq!(None, tooltip.text);
+    assert!(!tooltip.tick(Duration::from_millis(600)));
+    tooltip.reset(vec2(1.0, 1.0));
+    assert!(tooltip.tick(Duration::from_millis(600)));
+}
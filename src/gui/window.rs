@@ -1,45 +1,40 @@
-use crate::action::{Action, BufferAction, GuiAction, PaneAction, WindowAction};
-use crate::buffer::{Buffer, FileSaveStatus};
-use crate::colours::Colour;
-use crate::config::{RunConfig, BIM_QUIT_TIMES};
+use crate::action::{Action, GuiAction, PaneAction, WindowAction};
+use crate::buffer::Buffer;
 use crate::debug_log::DebugLog;
-use crate::gui::container::Container;
+use crate::editor_core::EditorCore;
+use crate::gui::font_chain::load_fonts;
 use crate::gui::gl_renderer::GlRenderer;
 use crate::gui::keycode_to_char;
-use crate::gui::pane::Pane;
 use crate::gui::persist_window_state::PersistWindowState;
+use crate::gui::profiler::{Phase, Profiler};
+use crate::gui::tab_bar;
+use crate::gui::tooltip::Tooltip;
+use crate::idle::{Idle, PowerMode};
+use crate::instance::InstanceServer;
 use crate::keycodes::{is_printable, Key};
-use crate::keymap::{Keymap, MapOrAction};
-use crate::mouse::MouseMove;
+use crate::mouse::{Clicks, MouseMove};
 use crate::options::Options;
 use crate::rect::RectBuilder;
-use crate::status::Status;
+use crate::renderer::{Renderer, Text, TextAlign};
+use crate::stats::EditStats;
+use crate::status::Level;
 use flame;
 use gfx::Device;
-use gfx_glyph::{
-    GlyphCruncher, HorizontalAlign, Layout, Scale, Section, SectionText, VariedSection,
-    VerticalAlign,
-};
+use gfx_glyph::rusttype::Font;
+use gfx_glyph::{GlyphCruncher, Scale, SectionText, VariedSection};
 use glam::{vec2, Vec2};
 use glutin::dpi::{LogicalPosition, LogicalSize};
 use glutin::{
     ElementState, Event, MonitorId, MouseScrollDelta, PossiblyCurrent, WindowEvent, WindowedContext,
 };
-use lazy_static::lazy_static;
 use std::error::Error;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(PartialEq, Debug)]
 enum InternalAction {
     ResizeWindow,
 }
 
-lazy_static! {
-    static ref POPUP_BG: Colour = Colour::rgb_from_int_tuple((51, 0, 102));
-    static ref POPUP_OUTLINE: Colour = Colour::rgb_from_int_tuple((240, 240, 240));
-    pub static ref BG_COLOR: Colour = Colour::rgb_from_int_tuple((41, 42, 68));
-}
-
 pub struct Window<'a> {
     monitor: MonitorId,
     window: WindowedContext<PossiblyCurrent>,
@@ -50,16 +45,26 @@ pub struct Window<'a> {
     ui_scale: f32,
     resized: bool,
     pub fullscreen: bool,
-    container: Container<'a>,
-    quit_times: i8,
     running: bool,
     pub in_focus: bool,
-    pub status_message: Option<Status>,
     persist_window_state: PersistWindowState,
     debug_log: DebugLog<'a>,
     action_queue: Vec<InternalAction>,
-    options: Options,
-    current_map: Keymap,
+    tooltip: Tooltip,
+    idle: Idle,
+    mouse_down: bool,
+    clicks: Clicks,
+    // Fonts to switch to, once there's a renderer to give them to
+    pending_fonts: Option<Vec<Font<'static>>>,
+    mouse_hidden: bool,
+    instance_server: Option<InstanceServer>,
+    // Something the window draws itself has changed, like the tooltip, or
+    // an event came in that might have changed anything
+    damaged: bool,
+    profiler: Profiler,
+    // What the OS window was last called
+    title: String,
+    core: EditorCore<'a>,
 }
 
 impl<'a> Window<'a> {
@@ -69,14 +74,12 @@ impl<'a> Window<'a> {
         window: WindowedContext<PossiblyCurrent>,
         window_dim: Vec2,
         logical_size: LogicalSize,
-        font_size: f32,
         ui_scale: f32,
         buffer: Buffer<'a>,
         persist_window_state: PersistWindowState,
         debug_log: DebugLog<'a>,
         options: Options,
     ) -> Result<Self, Box<dyn Error>> {
-        let pane = Pane::new(font_size, ui_scale, buffer, true);
         let mut gui_window = Self {
             monitor,
             window,
@@ -84,38 +87,38 @@ impl<'a> Window<'a> {
             logical_size,
             mouse_position: vec2(0.0, 0.0),
             ui_scale,
-            font_size,
+            font_size: options.font_size,
             resized: true,
             fullscreen: false,
-            container: Container::single(window_dim, vec2(0.0, 0.0), pane),
-            quit_times: BIM_QUIT_TIMES + 1,
             running: true,
             in_focus: true,
-            status_message: None,
             persist_window_state,
             debug_log,
             action_queue: vec![],
-            options: options.clone(),
-            current_map: options.keymap.clone(),
+            tooltip: Tooltip::new(options.tooltip_delay),
+            idle: Idle::new(options.save_power_after),
+            mouse_down: false,
+            clicks: Clicks::default(),
+            pending_fonts: None,
+            mouse_hidden: false,
+            instance_server: None,
+            damaged: true,
+            profiler: Profiler::default(),
+            title: String::from("bim"),
+            core: EditorCore::new(buffer, options, window_dim, ui_scale)?,
         };
-        gui_window.open_files()?;
+        if gui_window.core.options.single_instance {
+            match InstanceServer::start() {
+                Ok(server) => gui_window.instance_server = Some(server),
+                Err(e) => println!("Not listening for files from other bims: {}", e),
+            }
+        }
         gui_window.recalculate_glyph_sizes(renderer);
         Ok(gui_window)
     }
 
-    fn open_files(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut files = Vec::new();
-        if let RunConfig::RunOpenFiles(ref filenames) = self.options.run_type {
-            if filenames.len() > 1 {
-                for filename in &filenames[1..] {
-                    files.push(String::from(filename));
-                }
-            }
-        }
-        for file in files {
-            self.split_vertically_with_filename(&file)?;
-        }
-        Ok(())
+    pub fn save_session(&self) {
+        self.core.save_session();
     }
 
     fn handle_actions(&mut self, renderer: &mut GlRenderer<'_>) {
@@ -147,6 +150,10 @@ impl<'a> Window<'a> {
         if self.has_resized() {
             let _guard = flame::start_guard("recalculate_glyph_sized");
 
+            if let Some(fonts) = self.pending_fonts.take() {
+                renderer.set_fonts(fonts);
+            }
+
             let test_section = VariedSection {
                 bounds: self.window_dim.into(),
                 screen_position: (0.0, 0.0),
@@ -182,8 +189,22 @@ impl<'a> Window<'a> {
         }
     }
 
-    pub fn split_vertically_with_filename(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
-        self.container.split_vertically(Some(filename))
+    // Catches up with a setting the core has changed
+    fn option_changed(&mut self, setting: &str) {
+        // Spacing and fonts change how big the glyphs are laid out
+        self.resized = true;
+        match setting {
+            "font_size" => {
+                self.font_size = self.core.options.font_size;
+                self.core.update_gui(GuiAction::SetFontSize(self.font_size));
+            }
+            "font" => {
+                if let Some(font) = self.core.options.font.clone() {
+                    self.set_font(&font);
+                }
+            }
+            _ => {}
+        }
     }
 
     pub fn update(
@@ -191,16 +212,27 @@ impl<'a> Window<'a> {
         renderer: &mut GlRenderer<'a>,
         event: Event,
     ) -> Result<(), Box<dyn Error>> {
+        let started = Instant::now();
         match event {
             Event::WindowEvent { event, .. } => {
+                // Moving the mouse around only changes what's drawn if
+                // it's dragging or the tooltip goes, which say so themselves
+                if !matches!(event, WindowEvent::CursorMoved { .. }) {
+                    self.damaged = true;
+                }
                 match event {
                     WindowEvent::CursorMoved { position, .. } => {
                         self.update_mouse_position(position.into())
                     }
                     WindowEvent::MouseInput {
                         state: ElementState::Pressed,
+                        modifiers,
+                        ..
+                    } => self.mouse_click(modifiers.shift, modifiers.alt),
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
                         ..
-                    } => self.mouse_click(),
+                    } => self.mouse_release(),
                     WindowEvent::MouseWheel {
                         delta: MouseScrollDelta::LineDelta(delta_x, delta_y),
                         ..
@@ -260,7 +292,12 @@ impl<'a> Window<'a> {
                         self.persist_window_state.logical_position = new_logical_position;
                         self.persist_window_state.save();
                     }
-                    WindowEvent::Focused(in_focus) => self.in_focus = in_focus,
+                    WindowEvent::Focused(in_focus) => {
+                        self.in_focus = in_focus;
+                        if !in_focus {
+                            self.set_mouse_hidden(false);
+                        }
+                    }
                     _ => (),
                 };
             }
@@ -269,84 +306,146 @@ impl<'a> Window<'a> {
 
         self.handle_actions(renderer);
         self.recalculate_glyph_sizes(renderer);
+        self.profiler.add(Phase::Update, started.elapsed());
 
         Ok(())
     }
 
     pub fn update_dt(&mut self, duration: Duration) {
-        if let Some(status) = self.status_message.as_mut() {
-            if !status.is_valid() {
-                self.status_message = None;
+        let started = Instant::now();
+        if self.tooltip.tick(duration) {
+            let hover_info = self
+                .core
+                .container
+                .hover_info(self.physical_mouse_position());
+            self.damaged |= hover_info.is_some();
+            self.tooltip.show(hover_info);
+        }
+        self.core.tick(duration);
+        self.do_frontend_actions();
+        self.open_sent_files();
+        if self.idle.tick(duration) {
+            self.core.container.idle_work();
+        }
+        self.core.container.set_power_mode(self.power_mode());
+        self.profiler.add(Phase::Update, started.elapsed());
+    }
+
+    pub fn power_mode(&self) -> PowerMode {
+        if self.core.container.is_busy() {
+            PowerMode::Normal
+        } else {
+            self.idle.power_mode()
+        }
+    }
+
+    // Files from `bim somefile` while this one is running open in new panes
+    fn open_sent_files(&mut self) {
+        let files = match self.instance_server.as_ref() {
+            Some(server) => server.poll(),
+            None => return,
+        };
+        if files.is_empty() {
+            return;
+        }
+        self.damaged = true;
+        for file in files {
+            if let Err(e) = self.core.split_vertically_with_filename(&file) {
+                self.core
+                    .show_message(Level::Error, format!("Couldn't open {}: {}", file, e));
             }
         }
-        self.container.update_dt(duration);
+        self.window.window().show();
+    }
+
+    // Whether anything's changed since the last frame was drawn. Without
+    // the profiler showing, frames where nothing has aren't drawn at all.
+    pub fn needs_redraw(&self) -> bool {
+        self.damaged || self.resized || self.profiler.is_visible() || self.core.needs_redraw()
     }
 
     pub fn render(&mut self, renderer: &mut GlRenderer<'a>) -> Result<(), Box<dyn Error>> {
-        renderer
-            .encoder
-            .clear(&renderer.quad_bundle.data.out_color, BG_COLOR.rgba());
+        let started = Instant::now();
+        self.damaged = false;
+        self.core.clear_damage();
+        renderer.encoder.clear(
+            &renderer.quad_bundle.data.out_color,
+            self.core.theme.background.rgba(),
+        );
         renderer
             .encoder
             .clear_depth(&renderer.quad_bundle.data.out_depth, 1.0);
 
         {
             let _guard = flame::start_guard("render buffer");
-            self.container.render(renderer)?;
+            self.core.container.render(renderer)?;
+        }
+
+        let tabs = self.core.tab_labels();
+        if tabs.len() > 1 {
+            let bounds = vec2(self.window_dim.x(), self.core.tab_bar_height());
+            tab_bar::render(renderer, &self.core.theme, &tabs, bounds, self.font_scale())?;
         }
 
-        if let Some(status_msg) = &self.status_message {
+        self.render_tooltip(renderer)?;
+
+        if let Some(status_msg) = self.core.status_message() {
             let _guard = flame::start_guard("render popup text");
 
-            let layout = Layout::default()
-                .h_align(HorizontalAlign::Center)
-                .v_align(VerticalAlign::Center);
             let popup_bounds: Vec2 = self.window_dim - vec2(40.0, 40.0);
             let popup_pos = vec2(self.window_dim.x() / 2.0, self.window_dim.y() / 2.0);
-            let popup_section = Section {
-                bounds: popup_bounds.into(),
-                screen_position: popup_pos.into(),
-                text: &status_msg.message,
-                color: [224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0, 1.0],
-                scale: Scale::uniform(self.font_scale() * 2.0),
+            let popup_text = Text {
+                bounds: popup_bounds,
+                position: popup_pos,
+                scale: self.font_scale() * 2.0,
                 z: 0.5,
-                layout,
-                ..Section::default()
+                align: TextAlign::Centre,
+                ..Text::plain(status_msg, self.core.theme.popup_fg.rgba())
             };
 
-            if let Some(msg_bounds) = renderer.glyph_brush.pixel_bounds(popup_section) {
-                let width = msg_bounds.max.x - msg_bounds.min.x;
-                let height = msg_bounds.max.y - msg_bounds.min.y;
+            if let Some(msg_bounds) = renderer.measure(&popup_text) {
                 // Add some padding to the bg quad
-                let text_bounds = vec2(width as f32, height as f32) + vec2(4.0, 4.0);
+                let text_bounds = msg_bounds + vec2(4.0, 4.0);
 
                 let popup_outline = RectBuilder::new()
                     .center(popup_pos)
                     .bounds(text_bounds + vec2(10.0, 10.0))
                     .build();
 
-                renderer.draw_quad(POPUP_OUTLINE.rgb(), popup_outline, 0.6); // Z???
+                // Warnings and errors stand out from the usual messages
+                let outline = match self.core.status_level() {
+                    Some(Level::Error) => self.core.theme.diagnostic_error,
+                    Some(Level::Warn) => self.core.theme.diagnostic_warning,
+                    _ => self.core.theme.popup_outline,
+                };
+                renderer.draw_quad(outline.rgb(), popup_outline, 0.6); // Z???
                 let popup_rect = RectBuilder::new()
                     .center(popup_pos)
                     .bounds(text_bounds)
                     .build();
-                renderer.draw_quad(POPUP_BG.rgb(), popup_rect, 0.6); // Z??
+                renderer.draw_quad(self.core.theme.popup_bg.rgb(), popup_rect, 0.6);
+                // Z??
             }
 
-            renderer.glyph_brush.queue(popup_section);
-            renderer
-                .glyph_brush
-                .use_queue()
-                .depth_target(&renderer.quad_bundle.data.out_depth)
-                .draw(&mut renderer.encoder, &renderer.quad_bundle.data.out_color)?;
+            renderer.draw_text(popup_text)?;
         }
 
+        self.profiler.render(
+            renderer,
+            &self.core.theme,
+            self.window_dim,
+            self.font_scale(),
+        )?;
+
         flame::start("encoder.flush");
         renderer.encoder.flush(&mut renderer.device);
         flame::end("encoder.flush");
+        self.profiler.add(Phase::Render, started.elapsed());
+        let swapping = Instant::now();
         flame::start("swap_buffers");
         self.window.swap_buffers()?;
         flame::end("swap_buffers");
+        self.profiler.add(Phase::Present, swapping.elapsed());
         flame::start("device.cleanup");
         renderer.device.cleanup();
         flame::end("device.cleanup");
@@ -354,6 +453,33 @@ impl<'a> Window<'a> {
         Ok(())
     }
 
+    fn render_tooltip(&self, renderer: &mut GlRenderer<'a>) -> Result<(), Box<dyn Error>> {
+        if let Some(text) = &self.tooltip.text {
+            let _guard = flame::start_guard("render tooltip");
+
+            let tooltip_pos = self.tooltip.position + vec2(16.0, 16.0);
+            let tooltip_text = Text {
+                bounds: self.window_dim - tooltip_pos,
+                position: tooltip_pos,
+                scale: self.font_scale(),
+                z: 0.3,
+                ..Text::plain(text, self.core.theme.popup_fg.rgba())
+            };
+
+            if let Some(text_bounds) = renderer.measure(&tooltip_text) {
+                let tooltip_rect = RectBuilder::new()
+                    .top_left(tooltip_pos - vec2(4.0, 4.0))
+                    .bounds(text_bounds + vec2(8.0, 8.0))
+                    .build();
+                renderer.draw_quad(self.core.theme.popup_bg.rgb(), tooltip_rect, 0.35);
+            }
+
+            renderer.draw_text(tooltip_text)?;
+        }
+
+        Ok(())
+    }
+
     #[cfg(feature = "event-callbacks")]
     pub fn update_and_render(
         &mut self,
@@ -364,7 +490,9 @@ impl<'a> Window<'a> {
 
         self.update(renderer, event)?;
 
-        self.render(renderer)?;
+        if self.needs_redraw() {
+            self.render(renderer)?;
+        }
 
         self.end_frame();
 
@@ -375,20 +503,42 @@ impl<'a> Window<'a> {
         self.resized
     }
 
-    fn should_quit(&self) -> bool {
-        self.quit_times <= 0
+    // For when bim's closing, as every buffer is closed along with it
+    pub fn log_all_stats(&self) {
+        self.log_stats(&self.core.all_stats());
+    }
+
+    fn log_stats(&self, stats: &[(String, EditStats)]) {
+        if !self.core.options.log_stats {
+            return;
+        }
+        for (name, stats) in stats {
+            let _ = self
+                .debug_log
+                .debugln_timestamped(&format!("Closed {}: {}", name, stats));
+        }
+    }
+
+    pub fn remove_swap_files(&mut self) {
+        self.core.remove_swap_files();
+    }
+
+    pub fn focused_buffer(&self) -> Option<&Buffer<'a>> {
+        self.core.container.focused_buffer()
     }
 
     pub fn keep_running(&self) -> bool {
-        self.running && !self.should_quit()
+        self.running && !self.core.should_quit()
     }
 
     pub fn start_frame(&mut self) {
         flame::start("frame");
+        self.profiler.start_frame();
         self.resized = false;
     }
 
     pub fn end_frame(&mut self) {
+        self.profiler.end_frame();
         flame::end_collapse("frame");
     }
 
@@ -410,6 +560,24 @@ impl<'a> Window<'a> {
 
     pub fn update_mouse_position(&mut self, mouse: (f64, f64)) {
         self.mouse_position = vec2(mouse.0 as f32, mouse.1 as f32);
+        self.idle.input();
+        self.set_mouse_hidden(false);
+        let physical_position = self.physical_mouse_position();
+        if self.tooltip.text.is_some() {
+            self.damaged = true;
+        }
+        self.tooltip.reset(physical_position);
+        if self.mouse_down {
+            self.core.container.mouse_drag(physical_position);
+        }
+    }
+
+    // The pointer gets out of the way while typing, until the mouse moves
+    fn set_mouse_hidden(&mut self, hidden: bool) {
+        if self.mouse_hidden != hidden {
+            self.window.window().hide_cursor(hidden);
+            self.mouse_hidden = hidden;
+        }
     }
 
     fn physical_mouse_position(&self) -> Vec2 {
@@ -421,36 +589,64 @@ impl<'a> Window<'a> {
         vec2(real_position.x as f32, real_position.y as f32)
     }
 
-    pub fn mouse_click(&mut self) {
-        self.container.mouse_click(self.physical_mouse_position());
+    // Alt-dragging selects a block rather than a run of text
+    pub fn mouse_click(&mut self, extend_selection: bool, block_select: bool) {
+        self.idle.input();
+        self.mouse_down = true;
+        let location = self.physical_mouse_position();
+        let count = self.clicks.click(Instant::now(), location);
+        self.core
+            .container
+            .mouse_click(location, extend_selection, block_select, count);
+    }
+
+    pub fn mouse_release(&mut self) {
+        self.mouse_down = false;
+        self.core.container.mouse_release();
     }
 
     pub fn mouse_scroll(&mut self, mouse_move: MouseMove) {
-        self.container
+        self.idle.input();
+        self.core
+            .container
             .mouse_scroll(self.physical_mouse_position(), mouse_move);
     }
 
     pub fn inc_font_size(&mut self) {
         self.font_size += 1.0;
         self.resized = true;
-        self.container
-            .update_gui(GuiAction::SetFontSize(self.font_size));
+        self.core.update_gui(GuiAction::SetFontSize(self.font_size));
     }
 
     pub fn dec_font_size(&mut self) {
         self.font_size -= 1.0;
         self.resized = true;
-        self.container
-            .update_gui(GuiAction::SetFontSize(self.font_size));
+        self.core.update_gui(GuiAction::SetFontSize(self.font_size));
+    }
+
+    // Keeps the same fallbacks, which the new font might need fewer of
+    fn set_font(&mut self, name: &str) {
+        match load_fonts(Some(name), &self.core.options.fallback_fonts) {
+            Ok(fonts) => {
+                self.pending_fonts = Some(fonts);
+                // So the glyphs get measured again
+                self.resized = true;
+            }
+            Err(err) => self
+                .core
+                .show_message(Level::Error, format!("Can't load font! {}", err)),
+        }
     }
 
     fn print_info(&mut self) {
         println!("window_dim: {:?}", self.window_dim);
         println!("mouse_position: {:?}", self.mouse_position);
-        self.container.do_pane_action(PaneAction::PrintDebugInfo);
+        println!("power_mode: {}", self.power_mode());
+        self.core
+            .container
+            .do_pane_action(PaneAction::PrintDebugInfo);
     }
 
-    // FIXME: shouldn't be a window handling these - should be a GUI/GuiEditor abstraction
     fn do_gui_action(&mut self, action: GuiAction) {
         use GuiAction::*;
 
@@ -459,121 +655,53 @@ impl<'a> Window<'a> {
                 flame::dump_html(&mut std::fs::File::create("flame-graph.html").unwrap())
                     .unwrap_or(())
             }
+            ToggleProfiler => self.profiler.toggle(),
             DecFontSize => self.dec_font_size(),
             IncFontSize => self.inc_font_size(),
-            Quit => self.try_quit(),
-            UpdateSize(_, _) => {}
-            SetFontSize(_) => {}
-            SetUiScale(_) => {}
-            SetLineHeight(_) => {}
-            SetCharacterWidth(_) => {}
+            SetFont(name) => self.set_font(&name),
             PrintInfo => self.print_info(),
+            _ => {}
         }
     }
 
-    fn do_pane_action(&mut self, action: PaneAction) {
-        self.container.do_pane_action(action);
-    }
-
-    fn run_action(&mut self, action: Action) {
-        match action {
-            Action::OnGui(gui_action) => self.do_gui_action(gui_action),
-            Action::OnWindow(window_action) => self.do_window_action(window_action),
-            Action::OnPane(pane_action) => self.do_pane_action(pane_action),
-            Action::OnBuffer(buffer_action) => self.handle_buffer_action(buffer_action),
-        }
-    }
-
-    pub fn handle_key(&mut self, key: Key) {
-        let mut handled = false;
-
-        if let Some(map_or_action) = self.current_map.lookup(&key) {
-            handled = true;
-
-            match map_or_action {
-                MapOrAction::Map(keymap) => {
-                    println!("Key: {:?} puts us into map: {:?}", key, keymap);
-                    self.current_map = keymap;
+    // What the core left for the window to do
+    fn do_frontend_actions(&mut self) {
+        for action in self.core.take_frontend_actions() {
+            match action {
+                Action::OnGui(gui_action) => self.do_gui_action(gui_action),
+                Action::OnWindow(WindowAction::ToggleFullscreen) => {
+                    let monitor = self.monitor.clone();
+                    self.toggle_fullscreen(monitor);
                 }
-                MapOrAction::Action(action) => {
-                    println!("Action: {:?}", action);
-                    self.run_action(action);
-                    self.current_map = self.options.keymap.clone(); // FIXME: don't do this unless it's required
+                Action::OnWindow(WindowAction::SetOption(setting, _)) => {
+                    self.option_changed(&setting)
                 }
+                _ => {}
             }
         }
-
-        if !handled {
-            println!("Key: {:?} wasn't handled by the keymap!!!", key);
-            self.current_map = self.options.keymap.clone(); // FIXME: only if needed
-        }
-
-        self.check();
-    }
-
-    pub fn check(&mut self) {
-        let actions = self.container.check();
-        for action in actions {
-            self.do_window_action(action);
-        }
-    }
-
-    fn do_window_action(&mut self, window_action: WindowAction) {
-        match window_action {
-            WindowAction::SaveFile => self.save_file(),
-            WindowAction::SaveFileAs(filename) => self.save_file_as(filename),
-            WindowAction::FocusPane(direction) => self.container.focus_pane(direction),
-            WindowAction::ToggleFullscreen => {
-                let monitor = self.monitor.clone();
-                self.toggle_fullscreen(monitor);
-            }
-            WindowAction::SplitVertically => {
-                let _ = self.container.split_vertically(None);
-            }
-        }
-    }
-
-    fn handle_buffer_action(&mut self, action: BufferAction) {
-        self.container.update_current_buffer(action);
-    }
-
-    fn save_file_as(&mut self, filename: String) {
-        self.container
-            .update_current_buffer(BufferAction::SetFilename(filename));
-        self.save_file();
+        let closed = self.core.take_closed_stats();
+        self.log_stats(&closed);
+        self.update_title();
     }
 
-    fn save_file(&mut self) {
-        if let Some(save_status) = self.container.save_file() {
-            match save_status {
-                Ok(FileSaveStatus::Saved(bytes_saved)) => {
-                    self.set_status_msg(format!("{} bytes written to disk", bytes_saved))
-                }
-                Ok(_) => {}
-                Err(err) => {
-                    self.set_status_msg(format!("Can't save! Error: {}", err));
-                }
-            }
+    // Following the focused buffer as it's switched, renamed and changed
+    fn update_title(&mut self) {
+        let title = self.core.window_title();
+        if title != self.title {
+            self.window.window().set_title(&title);
+            self.title = title;
         }
     }
 
-    fn try_quit(&mut self) {
-        if self.options.show_quit_warning() && self.container.is_dirty() {
-            self.quit_times -= 1;
-            self.set_status_msg(format!(
-                "{} {} {} {}",
-                "WARNING! File has unsaved changes.",
-                "Press Ctrl-Q",
-                self.quit_times,
-                "more times to quit"
-            ));
-        } else {
-            self.quit_times = 0;
+    pub fn handle_key(&mut self, key: Key) {
+        self.tooltip.dismiss();
+        self.idle.input();
+        if self.core.options.hide_mouse_while_typing {
+            self.set_mouse_hidden(true);
         }
-    }
 
-    fn set_status_msg(&mut self, msg: String) {
-        self.status_message = Some(Status::new_with_timeout(msg, Duration::from_secs(5)));
+        self.core.handle_key(key);
+        self.do_frontend_actions();
     }
 
     pub fn resize(&mut self, logical_size: LogicalSize) {
@@ -584,7 +712,7 @@ impl<'a> Window<'a> {
         self.window_dim = vec2(dimensions.0.into(), dimensions.1.into());
         self.resized = true;
         renderer.resize(self.window_dim);
-        self.container
+        self.core
             .update_gui(GuiAction::UpdateSize(self.window_dim, vec2(0.0, 0.0)));
     }
 
@@ -592,16 +720,15 @@ impl<'a> Window<'a> {
         println!("DPI changed: {}", dpi);
         // FIXME: why do we need dpi AND ui_scale?
         self.ui_scale = dpi;
-        self.container.update_gui(GuiAction::SetUiScale(dpi));
+        self.core.update_gui(GuiAction::SetUiScale(dpi));
     }
 
     pub fn set_line_height(&mut self, line_height: f32) {
-        self.container
-            .update_gui(GuiAction::SetLineHeight(line_height));
+        self.core.update_gui(GuiAction::SetLineHeight(line_height));
     }
 
     pub fn set_character_width(&mut self, character_width: f32) {
-        self.container
+        self.core
             .update_gui(GuiAction::SetCharacterWidth(character_width));
     }
 }
@@ -0,0 +1,119 @@
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    // The cursor's line shows its own number, the rest their distance from it
+    Relative,
+}
+
+impl LineNumbers {
+    pub fn next(self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
+        }
+    }
+}
+
+const MIN_DIGITS: usize = 3;
+
+// The line numbers of the rows on screen. The text is only rebuilt when
+// something it depends on changes, which for absolute numbers doesn't
+// include the cursor moving.
+#[derive(Debug, Default)]
+pub struct Gutter {
+    mode: LineNumbers,
+    built_for: Option<(usize, usize, Option<usize>, usize)>,
+    text: String,
+}
+
+impl Gutter {
+    pub fn mode(&self) -> LineNumbers {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: LineNumbers) {
+        self.mode = mode;
+        self.built_for = None;
+        self.text.clear();
+    }
+
+    // Columns taken by the gutter, including a space before the text
+    pub fn width(&self, num_lines: usize) -> usize {
+        if self.mode == LineNumbers::Off {
+            0
+        } else {
+            num_lines.to_string().len().max(MIN_DIGITS) + 1
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        first_row: usize,
+        screen_rows: usize,
+        cursor_row: usize,
+        num_lines: usize,
+    ) {
+        if self.mode == LineNumbers::Off {
+            return;
+        }
+        let cursor_dependency = if self.mode == LineNumbers::Relative {
+            Some(cursor_row)
+        } else {
+            None
+        };
+        let key = (first_row, screen_rows, cursor_dependency, num_lines);
+        if self.built_for == Some(key) {
+            return;
+        }
+        self.built_for = Some(key);
+
+        let digits = self.width(num_lines) - 1;
+        self.text.clear();
+        let last_row = (first_row + screen_rows + 1).min(num_lines);
+        for row in first_row..last_row {
+            let label = match self.mode {
+                LineNumbers::Relative if row > cursor_row => row - cursor_row,
+                LineNumbers::Relative if row < cursor_row => cursor_row - row,
+                _ => row + 1,
+            };
+            let _ = writeln!(self.text, "{:>width$}", label, width = digits);
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[test]
+fn test_absolute_numbers() {
+    let mut gutter = Gutter::default();
+    gutter.set_mode(LineNumbers::Absolute);
+    gutter.update(8, 2, 9, 1200);
+    assert_eq!("   9\n  10\n  11\n", gutter.text());
+    assert_eq!(5, gutter.width(1200));
+}
+
+#[test]
+fn test_relative_numbers() {
+    let mut gutter = Gutter::default();
+    gutter.set_mode(LineNumbers::Relative);
+    gutter.update(0, 4, 2, 4);
+    assert_eq!("  2\n  1\n  3\n  1\n", gutter.text());
+    gutter.update(0, 4, 3, 4);
+    assert_eq!("  3\n  2\n  1\n  4\n", gutter.text());
+}
+
+#[test]
+fn test_off_has_no_width() {
+    let mut gutter = Gutter::default();
+    assert_eq!(0, gutter.width(100));
+    gutter.update(0, 10, 0, 100);
+    assert_eq!("", gutter.text());
+    assert_eq!(LineNumbers::Absolute, gutter.mode().next());
+}
@@ -0,0 +1,44 @@
+const BYTES_PER_ROW: usize = 16;
+
+// Lines like `hexdump -C` prints them: the offset, the bytes in hex in two
+// groups of eight, then the bytes that are printable ASCII as themselves
+pub fn dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let mut row = format!("{:08x} ", idx * BYTES_PER_ROW);
+            for col in 0..BYTES_PER_ROW {
+                if col % 8 == 0 {
+                    row.push(' ');
+                }
+                match chunk.get(col) {
+                    Some(byte) => row.push_str(&format!("{:02x} ", byte)),
+                    None => row.push_str("   "),
+                }
+            }
+            row.push('|');
+            for byte in chunk {
+                row.push(if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                });
+            }
+            row.push_str("|\n");
+            row
+        })
+        .collect()
+}
+
+#[test]
+fn test_dump() {
+    assert_eq!(
+        vec![
+            "00000000  7f 45 4c 46 02 01 01 00  68 69 20 74 68 65 72 65 |.ELF....hi there|\n",
+            "00000010  0a 21                                            |.!|\n",
+        ],
+        dump(b"\x7FELF\x02\x01\x01\0hi there\n!")
+    );
+    assert!(dump(b"").is_empty());
+}
@@ -1,3 +1,4 @@
+use crate::theme::Theme;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
@@ -11,7 +12,10 @@ pub enum Highlight {
     MultilineComment,
     Keyword1,
     Keyword2,
+    Warning,
     Cursor,
+    MatchingBracket,
+    TrailingWhitespace,
 }
 
 impl Default for Highlight {
@@ -35,23 +39,15 @@ lazy_static! {
         m.insert(MultilineComment, 36);
         m.insert(Keyword1, 33);
         m.insert(Keyword2, 32);
+        m.insert(Warning, 91);
+        m.insert(MatchingBracket, 93);
+        m.insert(TrailingWhitespace, 101);
         m
     };
 }
 
-pub fn highlight_to_color(hl: Highlight) -> [f32; 4] {
-    use self::Highlight::*;
-
-    match hl {
-        Normal => [232.0 / 255.0, 230.0 / 255.0, 237.0 / 255.0, 1.0],
-        Number => [221.0 / 255.0, 119.0 / 255.0, 85.0 / 255.0, 1.0],
-        String => [191.0 / 255.0, 156.0 / 255.0, 249.0 / 255.0, 1.0],
-        Comment | MultilineComment => [86.0 / 255.0, 211.0 / 255.0, 194.0 / 255.0, 1.0],
-        Keyword1 => [242.0 / 255.0, 231.0 / 255.0, 183.0 / 255.0, 1.0],
-        Keyword2 => [4.0 / 255.0, 219.0 / 255.0, 181.0 / 255.0, 1.0],
-        Cursor => [245.0 / 255.0, 3.0 / 255.0, 3.0 / 255.0, 1.0],
-        SearchMatch => [1.0, 102.0 / 255.0, 102.0 / 255.0, 1.0],
-    }
+pub fn highlight_to_color(theme: &Theme, hl: Highlight) -> [f32; 4] {
+    theme.highlight(hl).rgba()
 }
 
 #[derive(Copy, Clone, PartialEq, Debug, Default)]
@@ -0,0 +1,96 @@
+use std::fmt;
+#[cfg(feature = "gui")]
+use std::time::Duration;
+
+#[cfg(feature = "gui")]
+const IDLE_AFTER: u64 = 500;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PowerMode {
+    Normal,
+    // Nothing has happened for a while, so the cursor stops blinking and
+    // frames are only drawn a few times a second
+    #[cfg(feature = "gui")]
+    Saving,
+}
+
+impl fmt::Display for PowerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerMode::Normal => write!(f, "normal"),
+            #[cfg(feature = "gui")]
+            PowerMode::Saving => write!(f, "power saving"),
+        }
+    }
+}
+
+// Tracks how long it's been since the user did anything, so that
+// low-priority maintenance only runs when it won't add input latency
+#[cfg(feature = "gui")]
+pub struct Idle {
+    idle_after: Duration,
+    save_power_after: Option<Duration>,
+    since_input: Duration,
+}
+
+#[cfg(feature = "gui")]
+impl Default for Idle {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Idle {
+    pub fn new(save_power_after: Option<Duration>) -> Self {
+        Self {
+            idle_after: Duration::from_millis(IDLE_AFTER),
+            save_power_after,
+            since_input: Duration::default(),
+        }
+    }
+
+    pub fn input(&mut self) {
+        self.since_input = Duration::default();
+    }
+
+    // Advances the clock, returning true if idle work may run this frame
+    pub fn tick(&mut self, duration: Duration) -> bool {
+        self.since_input += duration;
+        self.since_input >= self.idle_after
+    }
+
+    pub fn power_mode(&self) -> PowerMode {
+        match self.save_power_after {
+            Some(after) if self.since_input >= after => PowerMode::Saving,
+            _ => PowerMode::Normal,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_idle_after_quiet_period() {
+    let mut idle = Idle::default();
+    assert!(!idle.tick(Duration::from_millis(300)));
+    assert!(idle.tick(Duration::from_millis(300)));
+    assert!(idle.tick(Duration::from_millis(16)));
+    idle.input();
+    assert!(!idle.tick(Duration::from_millis(16)));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_power_saving_after_long_quiet_period() {
+    let mut idle = Idle::new(Some(Duration::from_secs(10)));
+    idle.tick(Duration::from_secs(9));
+    assert_eq!(PowerMode::Normal, idle.power_mode());
+    idle.tick(Duration::from_secs(1));
+    assert_eq!(PowerMode::Saving, idle.power_mode());
+    idle.input();
+    assert_eq!(PowerMode::Normal, idle.power_mode());
+
+    let mut idle = Idle::default();
+    idle.tick(Duration::from_secs(3600));
+    assert_eq!(PowerMode::Normal, idle.power_mode());
+}
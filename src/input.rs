@@ -17,6 +17,10 @@ impl<'a> Input<'a> {
         Self::new(prompt, PromptAction::SaveFile, grab_cursor)
     }
 
+    pub fn new_switch_buffer_input(prompt: &str) -> Self {
+        Self::new(prompt, PromptAction::SwitchBuffer, true)
+    }
+
     pub fn type_char(&mut self, typed_char: char) {
         self.prompt.type_char(typed_char);
     }
@@ -25,6 +29,10 @@ impl<'a> Input<'a> {
         self.prompt.del_char();
     }
 
+    pub fn set_input(&mut self, input: &str) {
+        self.prompt.set_input(input);
+    }
+
     pub fn done(&mut self) {
         self.prompt.done();
     }
@@ -41,6 +49,7 @@ impl<'a> Input<'a> {
         Some(self.next_action)
     }
 
+    #[cfg(feature = "gui")]
     pub fn display_text(&self) -> &str {
         self.prompt.as_string()
     }
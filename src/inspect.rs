@@ -0,0 +1,90 @@
+// Describes the character at the start of text, like vim's ga: its codepoint,
+// UTF-8 encoding, any combining characters that follow it to make up one
+// grapheme, and where it is in the buffer
+pub fn describe_char(text: &str, char_offset: usize, byte_offset: usize) -> Option<String> {
+    let mut chars = text.chars();
+    let first = chars.next()?;
+    let combining: Vec<char> = chars.take_while(|c| is_combining(*c)).collect();
+
+    let mut utf8 = [0; 4];
+    let bytes = first
+        .encode_utf8(&mut utf8)
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut description = format!(
+        "{} U+{:04X} dec {} hex 0x{:x} | UTF-8: {} | char {} byte {}",
+        printable(first),
+        first as u32,
+        first as u32,
+        first as u32,
+        bytes,
+        char_offset,
+        byte_offset
+    );
+    if !combining.is_empty() {
+        let grapheme = std::iter::once(first)
+            .chain(combining)
+            .map(|c| format!("U+{:04X}", c as u32))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        description.push_str(&format!(" | grapheme: {}", grapheme));
+    }
+    Some(description)
+}
+
+fn printable(c: char) -> String {
+    if c.is_control() {
+        format!("{:?}", c)
+    } else {
+        format!("'{}'", c)
+    }
+}
+
+// Combining marks, joiners and variation selectors attach to the previous char
+fn is_combining(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200D
+        | 0x20D0..=0x20FF
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F
+        | 0x1F3FB..=0x1F3FF
+        | 0xE0100..=0xE01EF)
+}
+
+#[test]
+fn test_describe_ascii() {
+    assert_eq!(
+        Some("'a' U+0061 dec 97 hex 0x61 | UTF-8: 61 | char 3 byte 3".to_string()),
+        describe_char("abc", 3, 3)
+    );
+}
+
+#[test]
+fn test_describe_multibyte_and_combining() {
+    assert_eq!(
+        Some(
+            "'e' U+0065 dec 101 hex 0x65 | UTF-8: 65 | char 0 byte 0 | grapheme: U+0065 + U+0301"
+                .to_string()
+        ),
+        describe_char("e\u{301}x", 0, 0)
+    );
+    assert_eq!(
+        Some("'€' U+20AC dec 8364 hex 0x20ac | UTF-8: e2 82 ac | char 1 byte 2".to_string()),
+        describe_char("€", 1, 2)
+    );
+}
+
+#[test]
+fn test_describe_control_and_empty() {
+    assert_eq!(
+        Some("'\\n' U+000A dec 10 hex 0xa | UTF-8: 0a | char 0 byte 0".to_string()),
+        describe_char("\n", 0, 0)
+    );
+    assert_eq!(None, describe_char("", 0, 0));
+}
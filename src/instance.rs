@@ -0,0 +1,326 @@
+// Lets `bim somefile` hand the file over to a bim that's already running
+// instead of starting another one. The running bim listens on a unix
+// socket, or a named pipe on Windows, with a thread that takes in what
+// other bims send so that the UI never waits on them.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+#[cfg(unix)]
+use self::unix::{connect, default_address, Listener};
+#[cfg(windows)]
+use self::windows::{connect, default_address, Listener};
+
+// However much a sender has to say, the rest is ignored
+const MAX_BYTES: u64 = 64 * 1024;
+const MAX_FILES: usize = 100;
+
+pub struct InstanceServer {
+    files: Receiver<String>,
+    address: PathBuf,
+    stopped: Arc<AtomicBool>,
+    listening: Option<JoinHandle<()>>,
+}
+
+impl InstanceServer {
+    pub fn start() -> io::Result<Self> {
+        match default_address() {
+            Some(address) => Self::listen(address),
+            None => Err(no_socket_path()),
+        }
+    }
+
+    fn listen(address: PathBuf) -> io::Result<Self> {
+        let mut listener = Listener::bind(&address)?;
+        let (sender, files) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopping = stopped.clone();
+        let listening = thread::spawn(move || {
+            while let Ok(stream) = listener.accept() {
+                if stopping.load(Ordering::SeqCst) {
+                    return;
+                }
+                // Each sender gets a thread of its own so one that hangs
+                // doesn't hold up the rest
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for file in read_files(stream) {
+                        if sender.send(file).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            files,
+            address,
+            stopped,
+            listening: Some(listening),
+        })
+    }
+
+    // The files other bims have sent since last time
+    pub fn poll(&self) -> Vec<String> {
+        self.files.try_iter().collect()
+    }
+}
+
+impl Drop for InstanceServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // The listening thread is waiting for someone to connect, so that
+        // has to happen for it to see it's been stopped
+        if connect(&self.address).is_ok() {
+            if let Some(listening) = self.listening.take() {
+                let _ = listening.join();
+            }
+        }
+    }
+}
+
+// A sender that goes quiet without hanging up times out, which ends its
+// lines
+fn read_files(stream: impl Read) -> Vec<String> {
+    BufReader::new(stream.take(MAX_BYTES))
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.is_empty())
+        .take(MAX_FILES)
+        .collect()
+}
+
+// Gives the files to the running bim, if there is one
+pub fn send_to_running(files: &[String]) -> bool {
+    match default_address() {
+        Some(address) => send_files(&address, files).is_ok(),
+        None => false,
+    }
+}
+
+// The running bim could be anywhere, so it gets absolute paths
+fn send_files(address: &Path, files: &[String]) -> io::Result<()> {
+    let mut stream = connect(address)?;
+    let current_dir = std::env::current_dir()?;
+    for file in files {
+        writeln!(stream, "{}", current_dir.join(file).display())?;
+    }
+    stream.flush()
+}
+
+fn no_socket_path() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "there's nowhere to put a socket for other bims to find",
+    )
+}
+
+#[cfg(unix)]
+mod unix {
+    use crate::paths;
+    use std::fs;
+    use std::io;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    const SOCKET: &str = "bim.sock";
+    // Senders write everything straight away, so this is only for ones
+    // that hang
+    const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+    pub fn default_address() -> Option<PathBuf> {
+        paths::runtime_file(SOCKET)
+    }
+
+    pub fn connect(path: &Path) -> io::Result<UnixStream> {
+        UnixStream::connect(path)
+    }
+
+    pub struct Listener {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl Listener {
+        pub fn bind(path: &Path) -> io::Result<Self> {
+            paths::create_parent(path)?;
+            // Left behind by a bim that didn't get to clean up after itself
+            if path.exists() && UnixStream::connect(path).is_err() {
+                fs::remove_file(path)?;
+            }
+            Ok(Self {
+                listener: UnixListener::bind(path)?,
+                path: path.to_path_buf(),
+            })
+        }
+
+        pub fn accept(&mut self) -> io::Result<UnixStream> {
+            let (stream, _) = self.listener.accept()?;
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            Ok(stream)
+        }
+    }
+
+    impl Drop for Listener {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn test_address() -> PathBuf {
+        crate::utils::temp_path("bim-instance", ".sock")
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::env;
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+    use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW};
+    use winapi::um::winbase::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_INBOUND, PIPE_READMODE_BYTE,
+        PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    const PIPE_BUFFER: u32 = 4096;
+
+    // Named pipes are seen by everyone on the machine, so each user has
+    // their own
+    pub fn default_address() -> Option<PathBuf> {
+        let user = env::var("USERNAME").ok()?;
+        Some(PathBuf::from(format!(r"\\.\pipe\bim-{}", user)))
+    }
+
+    pub fn connect(name: &Path) -> io::Result<File> {
+        OpenOptions::new().write(true).open(name)
+    }
+
+    // There's a new instance of the pipe for each sender, with the next
+    // one made as soon as the last one's taken
+    pub struct Listener {
+        name: Vec<u16>,
+        waiting: File,
+    }
+
+    impl Listener {
+        pub fn bind(name: &Path) -> io::Result<Self> {
+            let name = name
+                .as_os_str()
+                .encode_wide()
+                .chain(iter::once(0))
+                .collect::<Vec<_>>();
+            // Fails when another bim has the pipe already
+            let waiting = create_instance(&name, FILE_FLAG_FIRST_PIPE_INSTANCE)?;
+            Ok(Self { name, waiting })
+        }
+
+        pub fn accept(&mut self) -> io::Result<File> {
+            let connected =
+                unsafe { ConnectNamedPipe(self.waiting.as_raw_handle() as _, ptr::null_mut()) };
+            // Someone who connects before there's a wait for them still counts
+            if connected == 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                    return Err(err);
+                }
+            }
+            let next = create_instance(&self.name, 0)?;
+            Ok(std::mem::replace(&mut self.waiting, next))
+        }
+    }
+
+    fn create_instance(name: &[u16], flags: u32) -> io::Result<File> {
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_INBOUND | flags,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                0,
+                PIPE_BUFFER,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(unsafe { File::from_raw_handle(handle as _) })
+        }
+    }
+
+    #[cfg(test)]
+    pub fn test_address() -> PathBuf {
+        PathBuf::from(format!(r"\\.\pipe\bim-instance-{}", std::process::id()))
+    }
+}
+
+#[cfg(test)]
+fn wait_for(server: &InstanceServer, count: usize) -> Vec<String> {
+    let mut files = vec![];
+    for _ in 0..2_000 {
+        files.extend(server.poll());
+        if files.len() >= count {
+            break;
+        }
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    files
+}
+
+#[test]
+fn test_files_are_sent_to_the_server() {
+    #[cfg(unix)]
+    use self::unix::test_address;
+    #[cfg(windows)]
+    use self::windows::test_address;
+
+    let address = test_address();
+    let server = InstanceServer::listen(address.clone()).unwrap();
+    assert!(server.poll().is_empty());
+
+    send_files(
+        &address,
+        &["/tmp/one.txt".to_string(), "two.txt".to_string()],
+    )
+    .unwrap();
+    let files = wait_for(&server, 2);
+    assert_eq!(2, files.len());
+    assert_eq!("/tmp/one.txt", files[0]);
+    assert!(Path::new(&files[1]).is_absolute());
+
+    // Senders that hang or go on and on don't hold everything up
+    let hanging = connect(&address).unwrap();
+    assert!(server.poll().is_empty());
+    send_files(&address, &["/tmp/three.txt".to_string()]).unwrap();
+    assert_eq!(vec!["/tmp/three.txt".to_string()], wait_for(&server, 1));
+    drop(hanging);
+    let many = (0..MAX_FILES * 2)
+        .map(|idx| format!("/tmp/{}.txt\n", idx))
+        .collect::<String>();
+    connect(&address)
+        .unwrap()
+        .write_all(many.as_bytes())
+        .unwrap();
+    assert_eq!(MAX_FILES, wait_for(&server, MAX_FILES).len());
+    thread::sleep(std::time::Duration::from_millis(20));
+    assert!(server.poll().is_empty());
+
+    drop(server);
+    assert!(!address.exists());
+    assert!(send_files(&address, &[]).is_err());
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Key {
     ArrowLeft,
@@ -12,12 +14,86 @@ pub enum Key {
     Return,
     Backspace,
     Escape,
+    Tab,
+    ShiftTab,
+    ControlArrowLeft,
+    ControlArrowRight,
+    ControlArrowUp,
+    ControlArrowDown,
+    ControlDelete,
+    ControlBackspace,
     Control(Option<char>),
     Function(u8),
     Other(char),
     TypedChar, // any typed char, not specific
 }
 
+// Names for keys as they're written in the config file, like "C-x", "F5"
+// or "PageUp". Anything else that's a single character is that character.
+const KEY_NAMES: [(Key, &str); 21] = [
+    (Key::ArrowLeft, "Left"),
+    (Key::ArrowRight, "Right"),
+    (Key::ArrowUp, "Up"),
+    (Key::ArrowDown, "Down"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::Delete, "Delete"),
+    (Key::Return, "Return"),
+    (Key::Backspace, "Backspace"),
+    (Key::Escape, "Escape"),
+    (Key::Tab, "Tab"),
+    (Key::ShiftTab, "S-Tab"),
+    (Key::ControlArrowLeft, "C-Left"),
+    (Key::ControlArrowRight, "C-Right"),
+    (Key::ControlArrowUp, "C-Up"),
+    (Key::ControlArrowDown, "C-Down"),
+    (Key::ControlDelete, "C-Delete"),
+    (Key::ControlBackspace, "C-Backspace"),
+    (Key::Control(Some(' ')), "C-Space"),
+];
+
+impl Key {
+    pub fn parse(name: &str) -> Option<Key> {
+        if let Some((key, _)) = KEY_NAMES.iter().find(|(_, key_name)| *key_name == name) {
+            return Some(*key);
+        }
+        if let Some(rest) = name.strip_prefix("C-") {
+            return single_char(rest).map(|c| Key::Control(Some(c)));
+        }
+        if let Some(number) = name.strip_prefix('F').filter(|number| !number.is_empty()) {
+            if let Ok(number) = number.parse() {
+                return Some(Key::Function(number));
+            }
+        }
+        single_char(name).map(Key::Other)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((_, name)) = KEY_NAMES.iter().find(|(key, _)| key == self) {
+            return write!(f, "{}", name);
+        }
+        match self {
+            Key::Control(Some(c)) => write!(f, "C-{}", c),
+            Key::Control(None) => write!(f, "C-"),
+            Key::Function(number) => write!(f, "F{}", number),
+            Key::Other(c) => write!(f, "{}", c),
+            _ => write!(f, "any character"),
+        }
+    }
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
 // TODO: Replace this with something sensible
 pub fn is_printable(key: char) -> bool {
     if key.is_control() {
@@ -66,3 +142,16 @@ pub fn is_printable(key: char) -> bool {
 
     true
 }
+
+#[test]
+fn test_key_names() {
+    for name in &[
+        "C-x", "C-=", "F5", "PageUp", "Escape", "C-Left", "S-Tab", "v", "?",
+    ] {
+        let key = Key::parse(name).expect("a key");
+        assert_eq!(*name, key.to_string());
+    }
+    assert_eq!(Some(Key::Other('F')), Key::parse("F"));
+    assert_eq!(None, Key::parse("C-xy"));
+    assert_eq!(None, Key::parse("Hyper"));
+}
@@ -3,6 +3,7 @@ use crate::commands::*;
 use crate::keycodes::Key;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum MapOrAction {
@@ -39,135 +40,962 @@ impl Keymap {
             }
         })
     }
+
+    // Binds the last of keys to action, with the keys before it leading
+    // through nested keymaps, which are made as needed. A prefix that was
+    // bound to an action becomes a keymap instead.
+    pub fn bind(&mut self, keys: &[Key], action: Action) {
+        match keys {
+            [] => {}
+            [key] => {
+                self.bindings.insert(*key, MapOrAction::Action(action));
+            }
+            [key, rest @ ..] => {
+                let entry = self.bindings.entry(*key).or_insert_with(|| {
+                    MapOrAction::Map(Keymap {
+                        bindings: HashMap::new(),
+                    })
+                });
+                if let MapOrAction::Action(_) = entry {
+                    *entry = MapOrAction::Map(Keymap {
+                        bindings: HashMap::new(),
+                    });
+                }
+                if let MapOrAction::Map(keymap) = entry {
+                    keymap.bind(rest, action);
+                }
+            }
+        }
+    }
+
+    // Says whether there was anything bound to keys to take away
+    pub fn unbind(&mut self, keys: &[Key]) -> bool {
+        match keys {
+            [] => false,
+            [key] => self.bindings.remove(key).is_some(),
+            [key, rest @ ..] => match self.bindings.get_mut(key) {
+                Some(MapOrAction::Map(keymap)) => keymap.unbind(rest),
+                _ => false,
+            },
+        }
+    }
+
+    // Every binding as "keys  action", including the ones in nested
+    // keymaps, sorted by their keys
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = self
+            .all_bindings()
+            .into_iter()
+            .map(|(keys, action)| format!("{:<12} {}", keys, action_name(action)))
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines
+    }
+
+    // The shortest chord that runs action, if anything does
+    pub fn keys_for(&self, action: &Action) -> Option<String> {
+        self.all_bindings()
+            .into_iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(keys, _)| keys)
+            .min_by_key(|keys| (keys.split(' ').count(), keys.len(), keys.clone()))
+    }
+
+    fn all_bindings(&self) -> Vec<(String, &Action)> {
+        let mut bindings = vec![];
+        self.bindings_into("", &mut bindings);
+        bindings
+    }
+
+    fn bindings_into<'k>(&'k self, prefix: &str, bindings: &mut Vec<(String, &'k Action)>) {
+        for (key, map_or_action) in self.bindings.iter() {
+            let keys = format!("{}{}", prefix, key);
+            match map_or_action {
+                MapOrAction::Map(keymap) => keymap.bindings_into(&format!("{} ", keys), bindings),
+                MapOrAction::Action(action) => bindings.push((keys, action)),
+            }
+        }
+    }
+}
+
+// Keys separated by spaces, like "C-w v"
+pub fn parse_chord(chord: &str) -> Result<Vec<Key>, String> {
+    let keys = chord
+        .split_whitespace()
+        .map(|name| Key::parse(name).ok_or_else(|| format!("unknown key {:?}", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        Err("no keys to bind".to_string())
+    } else {
+        Ok(keys)
+    }
+}
+
+// Actions go by the names they have in the code, e.g. "SplitVertically",
+// or "FocusPane: Left" for the ones that need more to go on
+pub fn parse_action(name: &str) -> Result<Action, String> {
+    serde_yaml::from_str(name)
+        .map(Action::OnGui)
+        .or_else(|_| serde_yaml::from_str(name).map(Action::OnWindow))
+        .or_else(|_| serde_yaml::from_str(name).map(Action::OnPane))
+        .or_else(|_| serde_yaml::from_str(name).map(Action::OnBuffer))
+        .map_err(|_| format!("unknown action {:?}", name))
+}
+
+fn action_name(action: &Action) -> String {
+    match action {
+        Action::OnGui(action) => format!("{:?}", action),
+        Action::OnWindow(action) => format!("{:?}", action),
+        Action::OnPane(action) => format!("{:?}", action),
+        Action::OnBuffer(action) => format!("{:?}", action),
+    }
+}
+
+// Which keys a frontend is able to report. Terminals can't tell Ctrl-Shift-F
+// from Ctrl-F, so bindings on shifted control chords need a fallback there.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KeyCapabilities {
+    Full,
+    Terminal,
+}
+
+// Two characters typed in quick succession that stand in for another key,
+// like "jk" for Escape. If the second never arrives the first is typed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TypedChord {
+    pub first: char,
+    pub second: char,
+    pub key: Key,
+    pub timeout: Duration,
+}
+
+// Walks keys through the (possibly nested) keymap, so that every frontend
+// resolves chords like Ctrl-W v the same way
+#[derive(Clone, Debug)]
+pub struct KeyDispatcher {
+    root: Keymap,
+    current: Option<Keymap>,
+    capabilities: KeyCapabilities,
+    typed_chord: Option<TypedChord>,
+    waiting_for: Option<Duration>,
+}
+
+impl KeyDispatcher {
+    pub fn new(root: Keymap, capabilities: KeyCapabilities) -> Self {
+        Self {
+            root,
+            current: None,
+            capabilities,
+            typed_chord: None,
+            waiting_for: None,
+        }
+    }
+
+    pub fn set_typed_chord(&mut self, typed_chord: Option<TypedChord>) {
+        self.typed_chord = typed_chord;
+        self.waiting_for = None;
+    }
+
+    // Advances the typed chord's clock, returning the first key's action if
+    // the second didn't arrive in time
+    pub fn tick(&mut self, duration: Duration) -> Vec<Action> {
+        if let (Some(waited), Some(chord)) = (self.waiting_for, self.typed_chord) {
+            let waited = waited + duration;
+            if waited >= chord.timeout {
+                self.waiting_for = None;
+                return self
+                    .dispatch_key(Key::Other(chord.first))
+                    .into_iter()
+                    .collect();
+            }
+            self.waiting_for = Some(waited);
+        }
+        vec![]
+    }
+
+    fn lookup(&self, key: &Key) -> Option<MapOrAction> {
+        let keymap = self.current.as_ref().unwrap_or(&self.root);
+        keymap
+            .lookup(key)
+            .or_else(|| match (self.capabilities, key) {
+                (KeyCapabilities::Terminal, Key::Control(Some(c))) if c.is_ascii_lowercase() => {
+                    keymap.lookup(&Key::Control(Some(c.to_ascii_uppercase())))
+                }
+                _ => None,
+            })
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.root
+    }
+
+    pub fn is_in_chord(&self) -> bool {
+        self.current.is_some()
+    }
+
+    // Returns the actions to run, usually just the one that key completes.
+    // A held back first key of a typed chord is let through if the chord
+    // isn't finished.
+    pub fn dispatch(&mut self, key: Key) -> Vec<Action> {
+        let mut actions = vec![];
+        if let Some(chord) = self.typed_chord {
+            if self.waiting_for.take().is_some() {
+                if key == Key::Other(chord.second) {
+                    return self.dispatch_key(chord.key).into_iter().collect();
+                }
+                actions.extend(self.dispatch_key(Key::Other(chord.first)));
+            }
+            // So "jjk" still ends in the chord
+            if key == Key::Other(chord.first) && self.current.is_none() {
+                self.waiting_for = Some(Duration::default());
+                return actions;
+            }
+        }
+        actions.extend(self.dispatch_key(key));
+        actions
+    }
+
+    fn dispatch_key(&mut self, key: Key) -> Option<Action> {
+        match self.lookup(&key) {
+            Some(MapOrAction::Map(keymap)) => {
+                println!("Key: {:?} puts us into map: {:?}", key, keymap);
+                self.current = Some(keymap);
+                None
+            }
+            Some(MapOrAction::Action(action)) => {
+                println!("Action: {:?}", action);
+                self.current = None;
+                Some(action)
+            }
+            None => {
+                println!("Key: {:?} wasn't handled by the keymap!!!", key);
+                self.current = None;
+                None
+            }
+        }
+    }
+}
+
+// Each key only gets one binding, so adding one that's there already is a
+// mistake rather than a way of replacing it
+fn bind(bindings: &mut HashMap<Key, MapOrAction>, key: Key, binding: MapOrAction) {
+    if let Some(old) = bindings.insert(key, binding) {
+        panic!("{} is bound twice, the first time to {:?}", key, old);
+    }
 }
 
 lazy_static! {
     pub static ref DEFAULT_KEYMAP: Keymap = {
         let mut bindings = HashMap::new();
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::ArrowLeft,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
                 MoveCursor::left(1),
             ))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::ArrowRight,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
                 MoveCursor::right(1),
             ))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::ArrowUp,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(MoveCursor::up(
                 1,
             )))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::ArrowDown,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
                 MoveCursor::down(1),
             ))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::PageDown,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
                 MoveCursor::page_down(1),
             ))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::PageUp,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
                 MoveCursor::page_up(1),
             ))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Home,
             MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
                 MoveCursor::home(),
             ))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::End,
             MapOrAction::Action(Action::OnBuffer(
                 BufferAction::MoveCursor(MoveCursor::end()),
             )),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
+            Key::ControlArrowLeft,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
+                MoveCursor::word_left(1),
+            ))),
+        );
+        bind(
+            &mut bindings,
+            Key::ControlArrowRight,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
+                MoveCursor::word_right(1),
+            ))),
+        );
+        bind(
+            &mut bindings,
             Key::Delete,
             MapOrAction::Action(Action::OnBuffer(BufferAction::DeleteChar(Direction::Right))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
+            Key::ControlDelete,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::DeleteWord(Direction::Right))),
+        );
+        bind(
+            &mut bindings,
+            Key::ControlBackspace,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::DeleteWord(Direction::Left))),
+        );
+        bind(
+            &mut bindings,
             Key::Backspace,
             MapOrAction::Action(Action::OnBuffer(BufferAction::DeleteChar(Direction::Left))),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Return,
             MapOrAction::Action(Action::OnBuffer(BufferAction::InsertNewlineAndReturn)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
+            Key::Tab,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::InsertIndent)),
+        );
+        bind(
+            &mut bindings,
+            Key::ShiftTab,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::PrevSnippetStop)),
+        );
+        bind(
+            &mut bindings,
             Key::TypedChar,
             MapOrAction::Action(Action::OnBuffer(BufferAction::InsertTypedChar)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('p')),
             MapOrAction::Action(Action::OnGui(GuiAction::DumpFlameGraph)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('-')),
             MapOrAction::Action(Action::OnGui(GuiAction::DecFontSize)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('+')),
             MapOrAction::Action(Action::OnGui(GuiAction::IncFontSize)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('=')),
             MapOrAction::Action(Action::OnGui(GuiAction::IncFontSize)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('q')),
             MapOrAction::Action(Action::OnGui(GuiAction::Quit)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
+            Key::Function(12),
+            MapOrAction::Action(Action::OnGui(GuiAction::ToggleProfiler)),
+        );
+        bind(
+            &mut bindings,
             Key::Function(11),
             MapOrAction::Action(Action::OnWindow(WindowAction::ToggleFullscreen)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('m')),
             MapOrAction::Action(Action::OnGui(GuiAction::PrintInfo)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some(' ')),
             MapOrAction::Action(Action::OnBuffer(BufferAction::CloneCursor)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
+            Key::Control(Some('%')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::JumpToMatchingBracket)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('y')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::Complete)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('/')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ToggleComment)),
+        );
+        // Like vim's Ctrl-A and Ctrl-X
+        bind(
+            &mut bindings,
+            Key::Control(Some('a')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::IncrementNumber(1))),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('x')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::IncrementNumber(-1))),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('D')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::DuplicateLines)),
+        );
+        bind(
+            &mut bindings,
+            Key::ControlArrowUp,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::MoveLines(Direction::Up))),
+        );
+        bind(
+            &mut bindings,
+            Key::ControlArrowDown,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::MoveLines(Direction::Down))),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('J')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::JoinLines)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('d')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::AddCursorAtNextMatch)),
+        );
+        bind(
+            &mut bindings,
+            Key::Escape,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ClearCursors)),
+        );
+        bind(
+            &mut bindings,
             Key::Control(Some('f')),
             MapOrAction::Action(Action::OnBuffer(BufferAction::StartSearch)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
+            Key::Control(Some('F')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::StartSearchInSelection)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('r')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ToggleRegexSearch)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('b')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ToggleSearchAllBuffers)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('h')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::StartReplace)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('l')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ToggleSelection)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('e')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ToggleVirtualEdit)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('z')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::Undo)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('Z')),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::Redo)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('n')),
+            MapOrAction::Action(Action::OnPane(PaneAction::CycleLineNumbers)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('t')),
+            MapOrAction::Action(Action::OnPane(PaneAction::ToggleFollow)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('T')),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ReopenClosedPane)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('P')),
+            MapOrAction::Action(Action::OnWindow(WindowAction::CommandPalette)),
+        );
+        // Like vim's :
+        bind(
+            &mut bindings,
+            Key::Control(Some(':')),
+            MapOrAction::Action(Action::OnWindow(WindowAction::CommandLine)),
+        );
+        bind(
+            &mut bindings,
             Key::Control(Some('v')),
             MapOrAction::Action(Action::OnWindow(WindowAction::SplitVertically)),
         );
-        bindings.insert(
+        bind(
+            &mut bindings,
             Key::Control(Some('s')),
             MapOrAction::Action(Action::OnWindow(WindowAction::SaveFile)),
         );
+        bind(
+            &mut bindings,
+            Key::Control(Some('o')),
+            MapOrAction::Action(Action::OnPane(PaneAction::StartOpenFile)),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('S')),
+            MapOrAction::Action(Action::OnPane(PaneAction::StartSaveAs)),
+        );
 
         let mut window_bindings = HashMap::new();
-        window_bindings.insert(
+        bind(
+            &mut window_bindings,
             Key::ArrowRight,
             MapOrAction::Action(Action::OnWindow(WindowAction::FocusPane(Direction::Right))),
         );
-        window_bindings.insert(
+        bind(
+            &mut window_bindings,
             Key::ArrowLeft,
             MapOrAction::Action(Action::OnWindow(WindowAction::FocusPane(Direction::Left))),
         );
+        bind(
+            &mut window_bindings,
+            Key::ArrowUp,
+            MapOrAction::Action(Action::OnWindow(WindowAction::FocusPane(Direction::Up))),
+        );
+        bind(
+            &mut window_bindings,
+            Key::ArrowDown,
+            MapOrAction::Action(Action::OnWindow(WindowAction::FocusPane(Direction::Down))),
+        );
+        // Like vim, > and < for wider and narrower, + and - for taller and shorter
+        for (key, direction) in [
+            ('>', Direction::Right),
+            ('<', Direction::Left),
+            ('+', Direction::Down),
+            ('-', Direction::Up),
+        ] {
+            bind(
+                &mut window_bindings,
+                Key::Other(key),
+                MapOrAction::Action(Action::OnWindow(WindowAction::ResizePane(direction))),
+            );
+        }
+        bind(
+            &mut window_bindings,
+            Key::Other('s'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::SplitHorizontally)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('v'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::SplitVertically)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('c'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ClosePane)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('n'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::NextBuffer)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('p'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::PrevBuffer)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('b'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::PickBuffer)),
+        );
+        // Like vim's gt and gT, but next to the other window keys
+        bind(
+            &mut window_bindings,
+            Key::Other('t'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::NewTab)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other(']'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::NextTab)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('['),
+            MapOrAction::Action(Action::OnWindow(WindowAction::PrevTab)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('T'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::CloseTab)),
+        );
+        bind(
+            &mut window_bindings,
+            Key::Other('m'),
+            MapOrAction::Action(Action::OnGui(GuiAction::ToggleMinimap)),
+        );
         let window_keymap = Keymap {
             bindings: window_bindings,
         };
 
-        bindings.insert(Key::Control(Some('w')), MapOrAction::Map(window_keymap));
+        bind(&mut bindings, Key::Control(Some('w')), MapOrAction::Map(window_keymap));
+
+        let mut goto_bindings = HashMap::new();
+        bind(
+            &mut goto_bindings,
+            Key::Home,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
+                MoveCursor::top(),
+            ))),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::End,
+            MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
+                MoveCursor::bottom(),
+            ))),
+        );
+        // Ctrl-G 5 jumps halfway through the buffer, Ctrl-G 0 to the very end
+        for digit in 0..=9 {
+            let percentage = if digit == 0 { 100 } else { digit * 10 };
+            if let Some(digit_char) = std::char::from_digit(digit as u32, 10) {
+                bind(
+                    &mut goto_bindings,
+                    Key::Other(digit_char),
+                    MapOrAction::Action(Action::OnBuffer(BufferAction::MoveCursor(
+                        MoveCursor::percent(percentage),
+                    ))),
+                );
+            }
+        }
+        // Like vim's ga
+        bind(
+            &mut goto_bindings,
+            Key::Other('a'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::InspectChar)),
+        );
+        // Like vim's gu, gU and g~, with c to capitalise each word
+        for (key, case) in [
+            ('u', Case::Lower),
+            ('U', Case::Upper),
+            ('~', Case::Toggle),
+            ('c', Case::Title),
+        ] {
+            bind(
+                &mut goto_bindings,
+                Key::Other(key),
+                MapOrAction::Action(Action::OnBuffer(BufferAction::ChangeCase(case))),
+            );
+        }
+        bind(
+            &mut goto_bindings,
+            Key::Other('n'),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ClearSearchHighlight)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('?'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ListBindings)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('s'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ShowStats)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('m'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ShowMessages)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('r'),
+            MapOrAction::Action(Action::OnPane(PaneAction::StartReplaceInFiles)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('R'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ApplyReplaceInFiles)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('t'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::PickTheme)),
+        );
+        // Like vim's gd and K
+        bind(
+            &mut goto_bindings,
+            Key::Other('d'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::GotoDefinition)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('h'),
+            MapOrAction::Action(Action::OnWindow(WindowAction::ShowHover)),
+        );
+        bind(
+            &mut goto_bindings,
+            Key::Other('b'),
+            MapOrAction::Action(Action::OnBuffer(BufferAction::ToggleBlockSelection)),
+        );
+        let goto_keymap = Keymap {
+            bindings: goto_bindings,
+        };
+
+        bind(&mut bindings, Key::Control(Some('g')), MapOrAction::Map(goto_keymap));
+
+        // Ctrl-K a marks where the cursor is as a, and Ctrl-J a jumps back
+        let mut mark_bindings = HashMap::new();
+        let mut jump_bindings = HashMap::new();
+        for name in ('a'..='z').chain('A'..='Z') {
+            bind(
+                &mut mark_bindings,
+                Key::Other(name),
+                MapOrAction::Action(Action::OnWindow(WindowAction::SetMark(name))),
+            );
+            bind(
+                &mut jump_bindings,
+                Key::Other(name),
+                MapOrAction::Action(Action::OnWindow(WindowAction::JumpToMark(name))),
+            );
+        }
+        bind(
+            &mut bindings,
+            Key::Control(Some('k')),
+            MapOrAction::Map(Keymap {
+                bindings: mark_bindings,
+            }),
+        );
+        bind(
+            &mut bindings,
+            Key::Control(Some('j')),
+            MapOrAction::Map(Keymap {
+                bindings: jump_bindings,
+            }),
+        );
 
         Keymap { bindings }
     };
 }
+
+#[test]
+fn test_goto_percentage_bindings() {
+    let goto_map = match DEFAULT_KEYMAP.lookup(&Key::Control(Some('g'))) {
+        Some(MapOrAction::Map(keymap)) => keymap,
+        other => panic!("expected Ctrl-G to be a keymap, got {:?}", other),
+    };
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnBuffer(
+            BufferAction::MoveCursor(MoveCursor::percent(50))
+        ))),
+        goto_map.lookup(&Key::Other('5'))
+    );
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnBuffer(
+            BufferAction::MoveCursor(MoveCursor::bottom())
+        ))),
+        goto_map.lookup(&Key::Other('0'))
+    );
+}
+
+#[test]
+fn test_dispatcher_follows_chords() {
+    let mut dispatcher = KeyDispatcher::new(DEFAULT_KEYMAP.clone(), KeyCapabilities::Full);
+    assert!(dispatcher.dispatch(Key::Control(Some('g'))).is_empty());
+    assert!(dispatcher.is_in_chord());
+    assert_eq!(
+        vec![Action::OnBuffer(
+            BufferAction::MoveCursor(MoveCursor::top())
+        )],
+        dispatcher.dispatch(Key::Home)
+    );
+    assert!(!dispatcher.is_in_chord());
+}
+
+#[test]
+fn test_dispatcher_unhandled_key_leaves_chord() {
+    let mut dispatcher = KeyDispatcher::new(DEFAULT_KEYMAP.clone(), KeyCapabilities::Full);
+    dispatcher.dispatch(Key::Control(Some('g')));
+    assert!(dispatcher.dispatch(Key::Function(12)).is_empty());
+    assert!(!dispatcher.is_in_chord());
+}
+
+#[test]
+fn test_terminal_falls_back_to_shifted_control_chords() {
+    let mut bindings = HashMap::new();
+    bindings.insert(
+        Key::Control(Some('T')),
+        MapOrAction::Action(Action::OnWindow(WindowAction::ReopenClosedPane)),
+    );
+    let keymap = Keymap { bindings };
+
+    let mut gui = KeyDispatcher::new(keymap.clone(), KeyCapabilities::Full);
+    assert!(gui.dispatch(Key::Control(Some('t'))).is_empty());
+
+    let mut terminal = KeyDispatcher::new(keymap, KeyCapabilities::Terminal);
+    assert_eq!(
+        vec![Action::OnWindow(WindowAction::ReopenClosedPane)],
+        terminal.dispatch(Key::Control(Some('t')))
+    );
+}
+
+#[cfg(test)]
+fn jk_dispatcher() -> KeyDispatcher {
+    let mut bindings = HashMap::new();
+    bindings.insert(
+        Key::TypedChar,
+        MapOrAction::Action(Action::OnBuffer(BufferAction::InsertTypedChar)),
+    );
+    bindings.insert(
+        Key::Escape,
+        MapOrAction::Action(Action::OnWindow(WindowAction::ReopenClosedPane)),
+    );
+    let mut dispatcher = KeyDispatcher::new(Keymap { bindings }, KeyCapabilities::Full);
+    dispatcher.set_typed_chord(Some(TypedChord {
+        first: 'j',
+        second: 'k',
+        key: Key::Escape,
+        timeout: Duration::from_millis(200),
+    }));
+    dispatcher
+}
+
+#[test]
+fn test_typed_chord_within_timeout() {
+    let mut dispatcher = jk_dispatcher();
+    assert!(dispatcher.dispatch(Key::Other('j')).is_empty());
+    assert!(dispatcher.tick(Duration::from_millis(100)).is_empty());
+    assert_eq!(
+        vec![Action::OnWindow(WindowAction::ReopenClosedPane)],
+        dispatcher.dispatch(Key::Other('k'))
+    );
+    assert_eq!(
+        vec![Action::OnBuffer(BufferAction::InsertChar('k'))],
+        dispatcher.dispatch(Key::Other('k'))
+    );
+}
+
+#[test]
+fn test_typed_chord_types_first_key_when_unfinished() {
+    let mut dispatcher = jk_dispatcher();
+    dispatcher.dispatch(Key::Other('j'));
+    assert_eq!(
+        vec![
+            Action::OnBuffer(BufferAction::InsertChar('j')),
+            Action::OnBuffer(BufferAction::InsertChar('x')),
+        ],
+        dispatcher.dispatch(Key::Other('x'))
+    );
+
+    dispatcher.dispatch(Key::Other('j'));
+    assert_eq!(
+        vec![Action::OnBuffer(BufferAction::InsertChar('j'))],
+        dispatcher.dispatch(Key::Other('j'))
+    );
+    assert_eq!(
+        vec![Action::OnBuffer(BufferAction::InsertChar('j'))],
+        dispatcher.tick(Duration::from_millis(250))
+    );
+    assert_eq!(
+        vec![Action::OnBuffer(BufferAction::InsertChar('k'))],
+        dispatcher.dispatch(Key::Other('k'))
+    );
+}
+
+#[test]
+fn test_binding_chords() {
+    let mut keymap = DEFAULT_KEYMAP.clone();
+    let save = parse_action("SaveFile").unwrap();
+    keymap.bind(&parse_chord("C-x C-s").unwrap(), save.clone());
+    keymap.bind(
+        &parse_chord("C-w h").unwrap(),
+        parse_action("FocusPane: Left").unwrap(),
+    );
+    assert!(keymap.unbind(&parse_chord("C-w v").unwrap()));
+    assert!(!keymap.unbind(&parse_chord("C-w v").unwrap()));
+
+    let mut dispatcher = KeyDispatcher::new(keymap.clone(), KeyCapabilities::Full);
+    dispatcher.dispatch(Key::Control(Some('x')));
+    assert_eq!(vec![save], dispatcher.dispatch(Key::Control(Some('s'))));
+    dispatcher.dispatch(Key::Control(Some('w')));
+    assert_eq!(
+        vec![Action::OnWindow(WindowAction::FocusPane(Direction::Left))],
+        dispatcher.dispatch(Key::Other('h'))
+    );
+
+    let description = keymap.describe();
+    assert!(description.contains(&"C-x C-s      SaveFile".to_string()));
+    assert!(!description.iter().any(|line| line.starts_with("C-w v ")));
+    assert_eq!(
+        Err("unknown action \"Explode\"".to_string()),
+        parse_action("Explode")
+    );
+    assert_eq!(
+        Err("unknown key \"Hyper\"".to_string()),
+        parse_chord("C-x Hyper")
+    );
+}
+
+#[test]
+fn test_no_key_is_bound_twice() {
+    // Building the keymap panics on a key that's bound twice
+    lazy_static::initialize(&DEFAULT_KEYMAP);
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnBuffer(
+            BufferAction::CloneCursor
+        ))),
+        DEFAULT_KEYMAP.lookup(&Key::Control(Some(' ')))
+    );
+    assert_eq!(
+        Some(MapOrAction::Action(Action::OnBuffer(
+            BufferAction::Complete
+        ))),
+        DEFAULT_KEYMAP.lookup(&Key::Control(Some('y')))
+    );
+}
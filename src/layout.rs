@@ -0,0 +1,568 @@
+use crate::commands::Direction;
+use crate::rect::{Rect, RectBuilder};
+use glam::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+// Like vim: a vertical split puts panes side by side, a horizontal split
+// stacks them on top of each other
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Arrangement {
+    VSplit,
+    HSplit,
+}
+
+impl Default for Arrangement {
+    fn default() -> Self {
+        Self::VSplit
+    }
+}
+
+// The smallest share of a split that moving a divider leaves a child, on
+// top of the smallest size it's given
+const MIN_SHARE: f32 = 0.05;
+
+// Where each pane goes. Panes are referred to by their index in the
+// container's list of panes, and splits can nest to any depth. Each child
+// of a split has a share of its space, and the shares add up to 1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Layout {
+    Pane(usize),
+    Split(Arrangement, Vec<Layout>, Vec<f32>),
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::even(Arrangement::default(), vec![])
+    }
+}
+
+// The line between two children of a split. path leads from the top of
+// the layout down to the split, and the divider sits before the child at
+// child_idx.
+#[derive(Clone, Debug)]
+pub struct Divider {
+    path: Vec<usize>,
+    child_idx: usize,
+    pub arrangement: Arrangement,
+    pub rect: Rect,
+    // Where the split starts and how long it is, across the divider
+    start: f32,
+    length: f32,
+}
+
+impl Divider {
+    #[cfg(feature = "gui")]
+    pub fn is_same(&self, other: &Divider) -> bool {
+        self.path == other.path && self.child_idx == other.child_idx
+    }
+
+    // The part of location that moves the divider
+    pub fn along(&self, location: Vec2) -> f32 {
+        match self.arrangement {
+            Arrangement::VSplit => location.x(),
+            Arrangement::HSplit => location.y(),
+        }
+    }
+
+    // Where the divider is now, as an x or a y like along
+    pub fn position(&self) -> f32 {
+        self.along(self.rect.top_left)
+    }
+}
+
+fn even_shares(count: usize) -> Vec<f32> {
+    vec![1.0 / count as f32; count]
+}
+
+impl Layout {
+    // A split with the space divided evenly between the children
+    pub fn even(arrangement: Arrangement, children: Vec<Layout>) -> Self {
+        let shares = even_shares(children.len());
+        Layout::Split(arrangement, children, shares)
+    }
+
+    // Splits the pane at target so that new_pane sits after it. Splitting
+    // in the same direction as the enclosing split adds to that split
+    // rather than nesting another one.
+    pub fn split(&mut self, target: usize, new_pane: usize, arrangement: Arrangement) -> bool {
+        match self {
+            Layout::Pane(idx) if *idx == target => {
+                *self = Layout::even(
+                    arrangement,
+                    vec![Layout::Pane(target), Layout::Pane(new_pane)],
+                );
+                true
+            }
+            Layout::Pane(_) => false,
+            Layout::Split(split_arrangement, children, shares) => {
+                if *split_arrangement == arrangement {
+                    if let Some(pos) = children
+                        .iter()
+                        .position(|child| *child == Layout::Pane(target))
+                    {
+                        children.insert(pos + 1, Layout::Pane(new_pane));
+                        *shares = even_shares(children.len());
+                        return true;
+                    }
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.split(target, new_pane, arrangement))
+            }
+        }
+    }
+
+    // Whether this lays out each of num_panes panes exactly once, with a
+    // share for every child, as one read back from a file might not
+    pub fn fits(&self, num_panes: usize) -> bool {
+        let mut panes = vec![];
+        if !self.collect_panes(&mut panes) {
+            return false;
+        }
+        panes.sort_unstable();
+        panes == (0..num_panes).collect::<Vec<_>>()
+    }
+
+    fn collect_panes(&self, panes: &mut Vec<usize>) -> bool {
+        match self {
+            Layout::Pane(idx) => {
+                panes.push(*idx);
+                true
+            }
+            Layout::Split(_, children, shares) => {
+                !children.is_empty()
+                    && children.len() == shares.len()
+                    && children.iter().all(|child| child.collect_panes(panes))
+            }
+        }
+    }
+
+    // The arrangement of the split the pane is in
+    pub fn arrangement_of(&self, pane_idx: usize) -> Option<Arrangement> {
+        match self {
+            Layout::Pane(_) => None,
+            Layout::Split(arrangement, children, _) => {
+                if children.contains(&Layout::Pane(pane_idx)) {
+                    Some(*arrangement)
+                } else {
+                    children
+                        .iter()
+                        .find_map(|child| child.arrangement_of(pane_idx))
+                }
+            }
+        }
+    }
+
+    // Takes the pane out, renumbering the panes after it to match the
+    // container removing it from its list. Splits left with one child are
+    // replaced by that child, and the rest share out the space evenly.
+    pub fn remove(&mut self, pane_idx: usize) {
+        match self {
+            Layout::Pane(idx) => {
+                if *idx > pane_idx {
+                    *idx -= 1;
+                }
+            }
+            Layout::Split(_, children, shares) => {
+                let before = children.len();
+                children.retain(|child| *child != Layout::Pane(pane_idx));
+                if children.len() != before {
+                    *shares = even_shares(children.len());
+                }
+                for child in children.iter_mut() {
+                    child.remove(pane_idx);
+                }
+                if children.len() == 1 {
+                    *self = children.remove(0);
+                }
+            }
+        }
+    }
+
+    // Divides the space between the children of each split by their shares
+    pub fn pane_rects(&self, position: Vec2, bounds: Vec2) -> Vec<(usize, Rect)> {
+        let mut rects = vec![];
+        self.collect_rects(position, bounds, &mut rects, &mut vec![], &mut vec![]);
+        rects
+    }
+
+    // Lines between the children of every split
+    #[cfg(feature = "gui")]
+    pub fn borders(&self, position: Vec2, bounds: Vec2) -> Vec<Rect> {
+        self.dividers(position, bounds)
+            .into_iter()
+            .map(|divider| divider.rect)
+            .collect()
+    }
+
+    pub fn dividers(&self, position: Vec2, bounds: Vec2) -> Vec<Divider> {
+        let mut dividers = vec![];
+        self.collect_rects(position, bounds, &mut vec![], &mut dividers, &mut vec![]);
+        dividers
+    }
+
+    // The divider within grab pixels of location, if there is one
+    #[cfg(feature = "gui")]
+    pub fn divider_at(
+        &self,
+        position: Vec2,
+        bounds: Vec2,
+        location: Vec2,
+        grab: f32,
+    ) -> Option<Divider> {
+        self.dividers(position, bounds).into_iter().find(|divider| {
+            let start = divider.rect.top_left - vec2(grab, grab);
+            let end = divider.rect.top_left + divider.rect.bounds + vec2(grab, grab);
+            location.x() >= start.x()
+                && location.y() >= start.y()
+                && location.x() < end.x()
+                && location.y() < end.y()
+        })
+    }
+
+    // The divider that resizing the pane in arrangement moves, and whether
+    // it's on the pane's far side. That's the one after it, or the one
+    // before it for the last pane in a split. Where splits in the same
+    // direction nest, it's the innermost one's.
+    pub fn divider_beside(
+        &self,
+        pane_idx: usize,
+        arrangement: Arrangement,
+        position: Vec2,
+        bounds: Vec2,
+    ) -> Option<(Divider, bool)> {
+        let path = self.path_to(pane_idx)?;
+        for depth in (0..path.len()).rev() {
+            let children = match self.at(&path[..depth]) {
+                Some(Layout::Split(split, children, _)) if *split == arrangement => children,
+                _ => continue,
+            };
+            let child_idx = path[depth];
+            let (divider_idx, far) = if child_idx + 1 < children.len() {
+                (child_idx + 1, true)
+            } else {
+                (child_idx, false)
+            };
+            return self
+                .dividers(position, bounds)
+                .into_iter()
+                .find(|divider| divider.path == path[..depth] && divider.child_idx == divider_idx)
+                .map(|divider| (divider, far));
+        }
+        None
+    }
+
+    // The children to follow from the top of the layout to reach the pane
+    fn path_to(&self, pane_idx: usize) -> Option<Vec<usize>> {
+        match self {
+            Layout::Pane(idx) if *idx == pane_idx => Some(vec![]),
+            Layout::Pane(_) => None,
+            Layout::Split(_, children, _) => {
+                children.iter().enumerate().find_map(|(child_idx, child)| {
+                    let mut path = child.path_to(pane_idx)?;
+                    path.insert(0, child_idx);
+                    Some(path)
+                })
+            }
+        }
+    }
+
+    fn at(&self, path: &[usize]) -> Option<&Layout> {
+        match path.split_first() {
+            None => Some(self),
+            Some((child_idx, rest)) => match self {
+                Layout::Pane(_) => None,
+                Layout::Split(_, children, _) => children.get(*child_idx)?.at(rest),
+            },
+        }
+    }
+
+    // Moves the divider to at (an x for a vertical split, a y for a
+    // horizontal one), taking the space from the children either side of
+    // it. Neither of them gets squashed below min_size, or MIN_SHARE.
+    pub fn move_divider(&mut self, divider: &Divider, at: f32, min_size: f32) -> bool {
+        let shares = match self.shares_at(&divider.path) {
+            Some(shares) => shares,
+            None => return false,
+        };
+        let idx = divider.child_idx;
+        if idx == 0 || idx >= shares.len() || divider.length <= 0.0 {
+            return false;
+        }
+        let before: f32 = shares[..idx - 1].iter().sum();
+        let pair = shares[idx - 1] + shares[idx];
+        let mut min_share = (min_size / divider.length).max(MIN_SHARE);
+        // Without room for two panes that big, they're just kept from vanishing
+        if pair < min_share * 2.0 {
+            min_share = MIN_SHARE;
+        }
+        if pair < min_share * 2.0 {
+            return false;
+        }
+        let fraction = ((at - divider.start) / divider.length)
+            .max(before + min_share)
+            .min(before + pair - min_share);
+        shares[idx - 1] = fraction - before;
+        shares[idx] = pair - shares[idx - 1];
+        true
+    }
+
+    fn shares_at(&mut self, path: &[usize]) -> Option<&mut Vec<f32>> {
+        match self {
+            Layout::Pane(_) => None,
+            Layout::Split(_, children, shares) => match path.split_first() {
+                None => Some(shares),
+                Some((child_idx, rest)) => children.get_mut(*child_idx)?.shares_at(rest),
+            },
+        }
+    }
+
+    fn collect_rects(
+        &self,
+        position: Vec2,
+        bounds: Vec2,
+        rects: &mut Vec<(usize, Rect)>,
+        dividers: &mut Vec<Divider>,
+        path: &mut Vec<usize>,
+    ) {
+        match self {
+            Layout::Pane(idx) => rects.push((
+                *idx,
+                RectBuilder::new().top_left(position).bounds(bounds).build(),
+            )),
+            Layout::Split(arrangement, children, shares) => {
+                let (start, length) = match arrangement {
+                    Arrangement::VSplit => (position.x(), bounds.x()),
+                    Arrangement::HSplit => (position.y(), bounds.y()),
+                };
+                let mut child_position = position;
+                for (child_idx, (child, share)) in children.iter().zip(shares).enumerate() {
+                    let (child_bounds, step, divider_bounds) = match arrangement {
+                        Arrangement::VSplit => (
+                            vec2(bounds.x() * share, bounds.y()),
+                            vec2(bounds.x() * share, 0.0),
+                            vec2(1.0, bounds.y()),
+                        ),
+                        Arrangement::HSplit => (
+                            vec2(bounds.x(), bounds.y() * share),
+                            vec2(0.0, bounds.y() * share),
+                            vec2(bounds.x(), 1.0),
+                        ),
+                    };
+                    if child_idx > 0 {
+                        dividers.push(Divider {
+                            path: path.clone(),
+                            child_idx,
+                            arrangement: *arrangement,
+                            rect: RectBuilder::new()
+                                .top_left(child_position)
+                                .bounds(divider_bounds)
+                                .build(),
+                            start,
+                            length,
+                        });
+                    }
+                    path.push(child_idx);
+                    child.collect_rects(child_position, child_bounds, rects, dividers, path);
+                    path.pop();
+                    child_position += step;
+                }
+            }
+        }
+    }
+}
+
+// The pane next to from in direction: the closest one on that side that
+// lines up with it, preferring the one that lines up the most
+pub fn neighbour(rects: &[(usize, Rect)], from: usize, direction: Direction) -> Option<usize> {
+    let (_, from_rect) = rects.iter().find(|(idx, _)| *idx == from)?;
+    let from_start = from_rect.top_left;
+    let from_end = from_rect.top_left + from_rect.bounds;
+
+    rects
+        .iter()
+        .filter(|(idx, _)| *idx != from)
+        .filter_map(|(idx, rect)| {
+            let start = rect.top_left;
+            let end = rect.top_left + rect.bounds;
+            let (gap, overlap) = match direction {
+                Direction::Left => (from_start.x() - end.x(), overlap_y(from_rect, rect)),
+                Direction::Right => (start.x() - from_end.x(), overlap_y(from_rect, rect)),
+                Direction::Up => (from_start.y() - end.y(), overlap_x(from_rect, rect)),
+                Direction::Down => (start.y() - from_end.y(), overlap_x(from_rect, rect)),
+            };
+            if gap > -0.5 && overlap > 0.0 {
+                Some((*idx, gap, overlap))
+            } else {
+                None
+            }
+        })
+        .min_by(|(_, gap_a, overlap_a), (_, gap_b, overlap_b)| {
+            gap_a
+                .partial_cmp(gap_b)
+                .unwrap()
+                .then(overlap_b.partial_cmp(overlap_a).unwrap())
+        })
+        .map(|(idx, _, _)| idx)
+}
+
+fn overlap_x(a: &Rect, b: &Rect) -> f32 {
+    f32::min(a.top_left.x() + a.bounds.x(), b.top_left.x() + b.bounds.x())
+        - f32::max(a.top_left.x(), b.top_left.x())
+}
+
+fn overlap_y(a: &Rect, b: &Rect) -> f32 {
+    f32::min(a.top_left.y() + a.bounds.y(), b.top_left.y() + b.bounds.y())
+        - f32::max(a.top_left.y(), b.top_left.y())
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_nested_splits() {
+    let mut layout = Layout::Pane(0);
+    assert!(layout.split(0, 1, Arrangement::VSplit));
+    assert!(layout.split(1, 2, Arrangement::HSplit));
+    assert!(layout.split(0, 3, Arrangement::VSplit));
+    assert_eq!(
+        Layout::even(
+            Arrangement::VSplit,
+            vec![
+                Layout::Pane(0),
+                Layout::Pane(3),
+                Layout::even(Arrangement::HSplit, vec![Layout::Pane(1), Layout::Pane(2)]),
+            ]
+        ),
+        layout
+    );
+
+    let rects = layout.pane_rects(vec2(0.0, 0.0), vec2(90.0, 60.0));
+    let rect_of = |pane: usize| rects.iter().find(|(idx, _)| *idx == pane).unwrap().1;
+    assert_eq!(vec2(30.0, 0.0), rect_of(3).top_left);
+    assert_eq!(vec2(60.0, 30.0), rect_of(2).top_left);
+    assert_eq!(vec2(30.0, 30.0), rect_of(2).bounds);
+    assert_eq!(3, layout.borders(vec2(0.0, 0.0), vec2(90.0, 60.0)).len());
+
+    layout.remove(1);
+    assert_eq!(
+        Layout::even(
+            Arrangement::VSplit,
+            vec![Layout::Pane(0), Layout::Pane(2), Layout::Pane(1)]
+        ),
+        layout
+    );
+}
+
+#[test]
+fn test_neighbour_in_each_direction() {
+    let mut layout = Layout::Pane(0);
+    layout.split(0, 1, Arrangement::VSplit);
+    layout.split(1, 2, Arrangement::HSplit);
+    let rects = layout.pane_rects(vec2(0.0, 0.0), vec2(100.0, 100.0));
+
+    assert_eq!(Some(1), neighbour(&rects, 0, Direction::Right));
+    assert_eq!(None, neighbour(&rects, 0, Direction::Left));
+    assert_eq!(None, neighbour(&rects, 0, Direction::Up));
+    assert_eq!(Some(2), neighbour(&rects, 1, Direction::Down));
+    assert_eq!(Some(1), neighbour(&rects, 2, Direction::Up));
+    assert_eq!(Some(0), neighbour(&rects, 2, Direction::Left));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_moving_dividers() {
+    let mut layout = Layout::Pane(0);
+    layout.split(0, 1, Arrangement::VSplit);
+    layout.split(1, 2, Arrangement::HSplit);
+    let (position, bounds) = (vec2(0.0, 0.0), vec2(100.0, 100.0));
+
+    let divider = layout
+        .divider_at(position, bounds, vec2(52.0, 10.0), 3.0)
+        .unwrap();
+    assert_eq!(Arrangement::VSplit, divider.arrangement);
+    assert!(layout.move_divider(&divider, 70.0, 0.0));
+    let rects = layout.pane_rects(position, bounds);
+    let rect_of = |pane: usize| rects.iter().find(|(idx, _)| *idx == pane).unwrap().1;
+    assert_eq!(vec2(70.0, 100.0), rect_of(0).bounds);
+    assert_eq!(vec2(70.0, 50.0), rect_of(2).top_left);
+
+    // The nested divider is found where it now is, and can't squash a pane
+    // out of existence
+    let divider = layout
+        .divider_at(position, bounds, vec2(80.0, 51.0), 3.0)
+        .unwrap();
+    assert_eq!(Arrangement::HSplit, divider.arrangement);
+    assert!(layout.move_divider(&divider, 200.0, 0.0));
+    let rects = layout.pane_rects(position, bounds);
+    let rect_of = |pane: usize| rects.iter().find(|(idx, _)| *idx == pane).unwrap().1;
+    let squashed = rect_of(2).bounds;
+    assert_eq!((30.0, 5.0), (squashed.x().round(), squashed.y().round()));
+
+    assert!(layout
+        .divider_at(position, bounds, vec2(20.0, 20.0), 3.0)
+        .is_none());
+}
+
+#[test]
+fn test_resizing_panes() {
+    let mut layout = Layout::Pane(0);
+    layout.split(0, 1, Arrangement::VSplit);
+    layout.split(1, 2, Arrangement::HSplit);
+    let (position, bounds) = (vec2(0.0, 0.0), vec2(100.0, 100.0));
+    let beside = |layout: &Layout, pane: usize, arrangement: Arrangement| {
+        layout
+            .divider_beside(pane, arrangement, position, bounds)
+            .map(|(divider, far)| (divider.arrangement, divider.position(), far))
+    };
+
+    assert_eq!(
+        Some((Arrangement::VSplit, 50.0, true)),
+        beside(&layout, 0, Arrangement::VSplit)
+    );
+    // The last pane in a split is resized from its near side
+    assert_eq!(
+        Some((Arrangement::VSplit, 50.0, false)),
+        beside(&layout, 1, Arrangement::VSplit)
+    );
+    assert_eq!(
+        Some((Arrangement::HSplit, 50.0, true)),
+        beside(&layout, 1, Arrangement::HSplit)
+    );
+    assert_eq!(
+        Some((Arrangement::HSplit, 50.0, false)),
+        beside(&layout, 2, Arrangement::HSplit)
+    );
+    assert_eq!(None, beside(&layout, 0, Arrangement::HSplit));
+
+    // Panes are kept at least min_size
+    let (divider, _) = layout
+        .divider_beside(0, Arrangement::VSplit, position, bounds)
+        .unwrap();
+    assert!(layout.move_divider(&divider, 95.0, 20.0));
+    assert_eq!(
+        Some((Arrangement::VSplit, 80.0, true)),
+        beside(&layout, 0, Arrangement::VSplit)
+    );
+    // Without room for two panes that big, only MIN_SHARE is kept
+    assert!(layout.move_divider(&divider, 97.0, 60.0));
+    assert_eq!(
+        Some((Arrangement::VSplit, 95.0, true)),
+        beside(&layout, 0, Arrangement::VSplit)
+    );
+}
+
+#[test]
+fn test_layout_fits() {
+    let layout = Layout::even(
+        Arrangement::VSplit,
+        vec![
+            Layout::Pane(1),
+            Layout::even(Arrangement::HSplit, vec![Layout::Pane(0), Layout::Pane(2)]),
+        ],
+    );
+    assert!(layout.fits(3));
+    assert!(!layout.fits(2));
+    assert!(!layout.fits(4));
+    assert!(Layout::Pane(0).fits(1));
+    assert!(!Layout::even(Arrangement::VSplit, vec![Layout::Pane(0), Layout::Pane(0)]).fits(2));
+    assert!(!Layout::Split(Arrangement::HSplit, vec![Layout::Pane(0)], vec![]).fits(1));
+}
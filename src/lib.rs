@@ -1,25 +1,76 @@
-mod action;
+pub mod action;
+mod animation;
 pub mod buffer;
+mod buffer_list;
+mod changed_rows;
 mod colours;
 mod commands;
+mod completion;
 pub mod config;
+mod container;
 mod cursor;
 pub mod debug_log;
+pub mod editor_core;
+mod encoding;
+mod ex;
+mod external;
+mod follow;
+pub mod fonts;
+pub mod git;
+mod grep;
+pub mod gutter;
+mod hex;
 pub mod highlight;
+mod idle;
 mod input;
-mod keycodes;
-mod keymap;
+mod inspect;
+pub mod instance;
+pub mod keycodes;
+pub mod keymap;
+mod layout;
+#[cfg(feature = "gui")]
+mod line_spacing;
+mod loader;
+mod location;
+pub mod lsp;
+pub mod marks;
+mod minimap;
 mod mouse;
 pub mod options;
+mod palette;
+mod pane;
+pub mod paste;
+pub mod paths;
+pub mod pipe;
 mod prompt;
+pub mod recorder;
 pub mod rect;
+mod regex;
+#[cfg(feature = "gui")]
+mod renderer;
+mod replace_in_files;
 mod row;
+mod scrollbar;
 mod search;
+mod selection;
+mod session;
+pub mod snippet;
+mod stats;
 mod status;
-mod status_line;
+pub mod status_line;
+mod swap;
 mod syntax;
+pub mod tabs;
+pub mod template;
+pub mod theme;
+mod undo;
+mod unicode;
 pub mod utils;
+mod viewport;
+mod watch;
 
+#[cfg(feature = "gui")]
 pub mod gui;
+pub use editor_core::EditorCore;
 
 pub const BIM_VERSION: &str = "0.0.1";
@@ -0,0 +1,53 @@
+use std::hash::{Hash, Hasher};
+
+// Text is laid out like the default layout, then the lines are spread
+// apart so each one takes line_height * spacing, with the glyphs centred in
+// the extra space. line_height is the font's own line height, as measured
+// by the window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineSpacing {
+    line_height: f32,
+    spacing: f32,
+}
+
+impl LineSpacing {
+    pub fn new(line_height: f32, spacing: f32) -> Self {
+        Self {
+            line_height,
+            spacing,
+        }
+    }
+
+    fn leading(&self) -> f32 {
+        self.line_height * (self.spacing - 1.0)
+    }
+
+    // Where a glyph with its baseline at y (relative to the top of the
+    // section) ends up
+    pub fn spaced_y(&self, y: f32) -> f32 {
+        if self.line_height <= 0.0 {
+            return y;
+        }
+        let line = (y / self.line_height).floor();
+        y + line * self.leading() + self.leading() / 2.0
+    }
+}
+
+impl Hash for LineSpacing {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.line_height.to_bits().hash(state);
+        self.spacing.to_bits().hash(state);
+    }
+}
+
+#[test]
+fn test_line_spacing_spreads_lines() {
+    let spacing = LineSpacing::new(10.0, 1.5);
+    // Baselines of the first three lines
+    assert_eq!(10.5, spacing.spaced_y(8.0));
+    assert_eq!(25.5, spacing.spaced_y(18.0));
+    assert_eq!(40.5, spacing.spaced_y(28.0));
+
+    let unspaced = LineSpacing::new(10.0, 1.0);
+    assert_eq!(18.0, unspaced.spaced_y(18.0));
+}
@@ -0,0 +1,133 @@
+use crate::encoding::{Decoder, Encoding};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+const BLOCK_LEN: usize = 64 * 1024;
+
+// What the loading thread sends back
+pub enum Loaded {
+    Started(Encoding),
+    // Whole lines, apart from maybe the last one in the file
+    Rows(Vec<String>),
+    Failed(String),
+}
+
+// Reads a file on another thread and hands it over a batch of lines at a
+// time, so a big file doesn't freeze everything while it loads
+pub struct Loader {
+    receiver: Receiver<(Loaded, u64)>,
+    file_len: u64,
+    bytes_loaded: u64,
+}
+
+impl Loader {
+    pub fn spawn(file: File) -> io::Result<Self> {
+        let file_len = file.metadata()?.len();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(err) = load(file, &sender) {
+                let _ = sender.send((Loaded::Failed(err.to_string()), 0));
+            }
+        });
+        Ok(Self {
+            receiver,
+            file_len,
+            bytes_loaded: 0,
+        })
+    }
+
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    // Disconnected means the whole file has been handed over
+    pub fn try_recv(&mut self) -> Result<Loaded, TryRecvError> {
+        let (loaded, bytes_read) = self.receiver.try_recv()?;
+        self.bytes_loaded = self.bytes_loaded.max(bytes_read);
+        Ok(loaded)
+    }
+
+    // How much of the file has been handed over, from 0 to 100
+    pub fn percent_loaded(&self) -> u64 {
+        (self.bytes_loaded * 100)
+            .checked_div(self.file_len)
+            .unwrap_or(100)
+    }
+}
+
+// Each message goes with how many bytes of the file had been read by then
+fn load(mut file: File, sender: &Sender<(Loaded, u64)>) -> io::Result<()> {
+    let mut block = vec![0; BLOCK_LEN];
+    let encoding = detect_encoding(&mut file, &mut block)?;
+    if sender.send((Loaded::Started(encoding), 0)).is_err() {
+        return Ok(());
+    }
+    let mut decoder = Decoder::new(encoding);
+    let mut partial_line = String::new();
+    let mut bytes_read = 0;
+    loop {
+        let len = read_block(&mut file, &mut block)?;
+        bytes_read += len as u64;
+        let last = len < block.len();
+        partial_line.push_str(&decoder.decode(&block[..len], last));
+        let mut rows = partial_line
+            .split_inclusive('\n')
+            .map(String::from)
+            .collect::<Vec<_>>();
+        partial_line = match rows.last() {
+            Some(row) if !last && !row.ends_with('\n') => rows.pop().unwrap_or_default(),
+            _ => String::new(),
+        };
+        // Nobody's listening once the buffer has been closed or reopened
+        if !rows.is_empty() && sender.send((Loaded::Rows(rows), bytes_read)).is_err() {
+            return Ok(());
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+// The start of the file settles most things, but anything later on that
+// isn't UTF-8 makes it Latin-1, the same as when the whole file is read at
+// once. So it's all looked through first, and then read again from the
+// start.
+fn detect_encoding(file: &mut File, block: &mut [u8]) -> io::Result<Encoding> {
+    let mut len = read_block(file, block)?;
+    let mut encoding = Encoding::detect_start(&block[..len]);
+    let mut pending = vec![];
+    while encoding == Encoding::Utf8 && len > 0 {
+        pending.extend_from_slice(&block[..len]);
+        match std::str::from_utf8(&pending) {
+            Ok(_) => pending.clear(),
+            // The rest of the character is in the next block
+            Err(err) if err.error_len().is_none() => {
+                pending.drain(..err.valid_up_to());
+            }
+            Err(_) => encoding = Encoding::Latin1,
+        }
+        len = read_block(file, block)?;
+    }
+    // The file ended partway through a character
+    if encoding == Encoding::Utf8 && !pending.is_empty() {
+        encoding = Encoding::Latin1;
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok(encoding)
+}
+
+// Fills block unless the end of the file comes first
+fn read_block(file: &mut File, block: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < block.len() {
+        match file.read(&mut block[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
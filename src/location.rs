@@ -0,0 +1,59 @@
+use std::path::Path;
+
+// Splits "file.rs:42:7" or "file.rs:42" (as compilers and grep print them)
+// into the file and a row and column counting from 0. A file whose name
+// really does end like that is left alone.
+pub fn split_location(arg: &str) -> (&str, Option<(usize, usize)>) {
+    if Path::new(arg).exists() {
+        return (arg, None);
+    }
+    let trimmed = arg.strip_suffix(':').unwrap_or(arg);
+    let (rest, last) = match split_number(trimmed) {
+        Some(split) => split,
+        None => return (arg, None),
+    };
+    match split_number(rest) {
+        Some((file, line)) => (file, Some((line - 1, last - 1))),
+        None => (rest, Some((last - 1, 0))),
+    }
+}
+
+// "rest:n" where n counts from 1
+fn split_number(text: &str) -> Option<(&str, usize)> {
+    let (rest, number) = text.rsplit_once(':')?;
+    let number = number.parse::<usize>().ok().filter(|number| *number > 0)?;
+    if rest.is_empty() {
+        None
+    } else {
+        Some((rest, number))
+    }
+}
+
+#[test]
+fn test_split_location() {
+    assert_eq!(
+        ("src/main.rs", Some((41, 6))),
+        split_location("src/main.rs:42:7")
+    );
+    assert_eq!(
+        ("src/main.rs", Some((41, 0))),
+        split_location("src/main.rs:42")
+    );
+    assert_eq!(
+        ("src/main.rs", Some((41, 6))),
+        split_location("src/main.rs:42:7:")
+    );
+    assert_eq!(("src/main.rs", None), split_location("src/main.rs"));
+    assert_eq!(("notes:", None), split_location("notes:"));
+    assert_eq!(("notes:0", None), split_location("notes:0"));
+    assert_eq!((":12", None), split_location(":12"));
+}
+
+#[test]
+fn test_existing_files_keep_their_names() {
+    let path = crate::utils::temp_path("bim-location", ":3");
+    std::fs::write(&path, "").unwrap();
+    let arg = path.to_string_lossy().to_string();
+    assert_eq!((arg.as_str(), None), split_location(&arg));
+    std::fs::remove_file(&path).unwrap();
+}
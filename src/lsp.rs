@@ -0,0 +1,739 @@
+// A language server client, for diagnostics, going to definitions and hover
+// text. Client is just the protocol, messages in and out, and
+// LanguageServer runs a server and passes messages between it and a Client.
+// Buffers are sent whole every time they change, which every server
+// understands, rather than as edits.
+
+use crate::paths;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long a server gets to exit by itself before it's killed
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Most severe first
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    fn from_lsp(severity: Option<u64>) -> Self {
+        match severity {
+            Some(2) => Severity::Warning,
+            Some(3) => Severity::Information,
+            Some(4) => Severity::Hint,
+            _ => Severity::Error,
+        }
+    }
+}
+
+// Where a server says something's wrong. Columns are in UTF-16 code units,
+// the way servers count them, and a diagnostic that runs over more than one
+// line is only shown on the first, to the end of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub row: usize,
+    pub start_col: usize,
+    pub end_col: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn from_lsp(value: &Value) -> Option<Self> {
+        let (row, start_col) = position(&value["range"]["start"])?;
+        let (end_row, end_col) = position(&value["range"]["end"])?;
+        Some(Self {
+            row,
+            start_col,
+            end_col: if end_row == row { Some(end_col) } else { None },
+            severity: Severity::from_lsp(value["severity"].as_u64()),
+            message: value["message"].as_str()?.to_string(),
+        })
+    }
+
+    // The chars of line it covers, at least one of them even if the server
+    // gave an empty range
+    pub fn char_cols(&self, line: &str) -> Range<usize> {
+        let start = utf16_to_char_col(line, self.start_col);
+        let end = match self.end_col {
+            Some(end_col) => utf16_to_char_col(line, end_col),
+            None => line.chars().count(),
+        };
+        start..end.max(start + 1)
+    }
+}
+
+// What the server's said, for the editor to act on
+#[derive(Clone, Debug, PartialEq)]
+pub enum LspEvent {
+    Diagnostics(PathBuf, Vec<Diagnostic>),
+    // The file and row and (UTF-16) column something is defined at
+    Definition(PathBuf, usize, usize),
+    Hover(String),
+    // A definition or hover text was asked for and there wasn't one
+    NotFound(&'static str),
+    Failed(String),
+}
+
+// Requests still waiting on an answer
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Request {
+    Initialize,
+    Definition,
+    Hover,
+    Shutdown,
+}
+
+pub struct Client {
+    root: PathBuf,
+    next_id: u64,
+    pending: HashMap<u64, Request>,
+    // The server can't be sent anything else until it's answered initialize
+    initialized: bool,
+    held: Vec<Value>,
+    outgoing: Vec<Value>,
+    // The version sent for each open file, and the buffer revision it was
+    open: HashMap<PathBuf, (i64, u64)>,
+}
+
+impl Client {
+    pub fn new(root: &Path) -> Self {
+        let mut client = Self {
+            root: root.to_path_buf(),
+            next_id: 1,
+            pending: HashMap::new(),
+            initialized: false,
+            held: vec![],
+            outgoing: vec![],
+            open: HashMap::new(),
+        };
+        client.request(
+            Request::Initialize,
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": path_to_uri(root),
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": { "didSave": false },
+                        "publishDiagnostics": {},
+                        "definition": {},
+                        "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    },
+                },
+            }),
+        );
+        client
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Tells the server about path's text, the first time it's seen and then
+    // whenever its revision moves on. text is only called when it's needed.
+    pub fn sync<F>(&mut self, path: &Path, language_id: &str, revision: u64, text: F)
+    where
+        F: FnOnce() -> String,
+    {
+        let uri = path_to_uri(path);
+        match self.open.get(path).copied() {
+            None => {
+                self.open.insert(path.to_path_buf(), (1, revision));
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id,
+                            "version": 1,
+                            "text": text(),
+                        },
+                    }),
+                );
+            }
+            Some((version, sent)) if sent != revision => {
+                let version = version + 1;
+                self.open.insert(path.to_path_buf(), (version, revision));
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": uri, "version": version },
+                        "contentChanges": [{ "text": text() }],
+                    }),
+                );
+            }
+            Some(_) => {}
+        }
+    }
+
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.open.contains_key(path)
+    }
+
+    pub fn definition(&mut self, path: &Path, row: usize, col: usize) {
+        let params = text_document_position(path, row, col);
+        self.request(Request::Definition, "textDocument/definition", params);
+    }
+
+    pub fn hover(&mut self, path: &Path, row: usize, col: usize) {
+        let params = text_document_position(path, row, col);
+        self.request(Request::Hover, "textDocument/hover", params);
+    }
+
+    // Asks the server to finish up, then to exit without waiting to hear
+    // back, as bim's on its way out
+    pub fn shutdown(&mut self) {
+        if self.initialized {
+            self.request(Request::Shutdown, "shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+        }
+    }
+
+    // The messages to send the server, in order
+    pub fn take_outgoing(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    // Something the server sent
+    pub fn receive(&mut self, message: &Value) -> Option<LspEvent> {
+        let id = message["id"].as_u64();
+        if let Some(method) = message["method"].as_str() {
+            // A request from the server, which gets an answer even though
+            // there's nothing bim can do for it
+            if !message["id"].is_null() {
+                self.outgoing.push(json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "result": Value::Null,
+                }));
+                return None;
+            }
+            return match method {
+                "textDocument/publishDiagnostics" => {
+                    let params = &message["params"];
+                    let path = uri_to_path(params["uri"].as_str()?)?;
+                    let diagnostics = params["diagnostics"]
+                        .as_array()?
+                        .iter()
+                        .filter_map(Diagnostic::from_lsp)
+                        .collect();
+                    Some(LspEvent::Diagnostics(path, diagnostics))
+                }
+                _ => None,
+            };
+        }
+
+        let request = self.pending.remove(&id?)?;
+        if let Some(error) = message.get("error") {
+            let error = error["message"].as_str().unwrap_or("unknown error");
+            return Some(LspEvent::Failed(error.to_string()));
+        }
+        let result = &message["result"];
+        match request {
+            Request::Initialize => {
+                self.initialized = true;
+                self.outgoing.push(notification("initialized", json!({})));
+                self.outgoing.append(&mut self.held);
+                None
+            }
+            Request::Definition => {
+                Some(definition_from(result).unwrap_or(LspEvent::NotFound("definition")))
+            }
+            Request::Hover => Some(match hover_text(&result["contents"]) {
+                Some(text) if !text.trim().is_empty() => LspEvent::Hover(text),
+                _ => LspEvent::NotFound("hover text"),
+            }),
+            Request::Shutdown => None,
+        }
+    }
+
+    fn request(&mut self, request: Request, method: &str, params: Value) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, request);
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        if request == Request::Initialize {
+            self.outgoing.push(message);
+        } else {
+            self.send(message);
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(notification(method, params));
+    }
+
+    fn send(&mut self, message: Value) {
+        if self.initialized {
+            self.outgoing.push(message);
+        } else {
+            self.held.push(message);
+        }
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    let mut message = json!({ "jsonrpc": "2.0", "method": method });
+    if !params.is_null() {
+        message["params"] = params;
+    }
+    message
+}
+
+fn text_document_position(path: &Path, row: usize, col: usize) -> Value {
+    json!({
+        "textDocument": { "uri": path_to_uri(path) },
+        "position": { "line": row, "character": col },
+    })
+}
+
+fn position(value: &Value) -> Option<(usize, usize)> {
+    Some((
+        value["line"].as_u64()? as usize,
+        value["character"].as_u64()? as usize,
+    ))
+}
+
+// Definitions come as one location or a list of them, as either Locations
+// or LocationLinks. The first one will do.
+fn definition_from(result: &Value) -> Option<LspEvent> {
+    let location = match result {
+        Value::Array(locations) => locations.first()?,
+        location => location,
+    };
+    let uri = location["uri"]
+        .as_str()
+        .or_else(|| location["targetUri"].as_str())?;
+    let range = if location["targetSelectionRange"].is_object() {
+        &location["targetSelectionRange"]
+    } else {
+        &location["range"]
+    };
+    let (row, col) = position(&range["start"])?;
+    Some(LspEvent::Definition(uri_to_path(uri)?, row, col))
+}
+
+// Hover contents can be a string, a MarkupContent, a MarkedString with its
+// language, or a list of any of those
+fn hover_text(contents: &Value) -> Option<String> {
+    match contents {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter_map(hover_text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        Value::Object(_) => contents["value"].as_str().map(String::from),
+        _ => None,
+    }
+}
+
+// Every character that isn't safe in a URI path is percent encoded
+pub fn path_to_uri(path: &Path) -> String {
+    let path = paths::absolute_path(path);
+    let mut path = path.to_string_lossy().replace('\\', "/");
+    // Windows paths start with their drive, C:/ becomes /C:/
+    if !path.starts_with('/') {
+        path.insert(0, '/');
+    }
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = vec![];
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' && after.len() >= 2 {
+            let hex = std::str::from_utf8(&after[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // Back from /C:/ to C:/
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+// The char index in line of a column counted in UTF-16 code units
+pub fn utf16_to_char_col(line: &str, utf16_col: usize) -> usize {
+    let mut units = 0;
+    for (idx, c) in line.chars().enumerate() {
+        if units >= utf16_col {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+pub fn char_to_utf16_col(line: &str, char_col: usize) -> usize {
+    line.chars().take(char_col).map(char::len_utf16).sum()
+}
+
+// Frames a message as the protocol wants, with a Content-Length header
+pub fn encode_message(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut bytes = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    bytes.extend_from_slice(body.as_bytes());
+    bytes
+}
+
+// The next message from reader, or None once there aren't any more
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let content_length = content_length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length"))?;
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// A server running in the background. What it sends is read on its own
+// thread, to be picked up with poll, and what it's sent is written on
+// another, so a server that stops reading can't hold up the editor.
+pub struct LanguageServer {
+    client: Client,
+    child: Child,
+    outgoing: Option<Sender<Value>>,
+    incoming: Receiver<Value>,
+    write_errors: Receiver<io::Error>,
+}
+
+impl LanguageServer {
+    // Runs command, with any arguments split on whitespace, for the project
+    // at root
+    pub fn spawn(command: &str, root: &Path) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no command given"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let (outgoing, to_write) = mpsc::channel::<Value>();
+        let (error_sender, write_errors) = mpsc::channel();
+        thread::spawn(move || {
+            for message in to_write {
+                let written = stdin
+                    .write_all(&encode_message(&message))
+                    .and_then(|_| stdin.flush());
+                if let Err(err) = written {
+                    let _ = error_sender.send(err);
+                    break;
+                }
+            }
+        });
+        let mut server = Self {
+            client: Client::new(root),
+            child,
+            outgoing: Some(outgoing),
+            incoming,
+            write_errors,
+        };
+        server.flush();
+        Ok(server)
+    }
+
+    pub fn client(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    // Handles everything the server's sent since last time, and sends it
+    // whatever's waiting to go
+    pub fn poll(&mut self) -> Vec<LspEvent> {
+        let mut events = vec![];
+        while let Ok(message) = self.incoming.try_recv() {
+            events.extend(self.client.receive(&message));
+        }
+        self.flush();
+        while let Ok(err) = self.write_errors.try_recv() {
+            events.push(LspEvent::Failed(format!("Can't write to server: {}", err)));
+        }
+        events
+    }
+
+    // Hands whatever's waiting to go to the writer thread. Once that's hit
+    // an error the messages are dropped, the error turning up in poll.
+    fn flush(&mut self) {
+        for message in self.client.take_outgoing() {
+            if let Some(outgoing) = &self.outgoing {
+                let _ = outgoing.send(message);
+            }
+        }
+    }
+}
+
+impl Drop for LanguageServer {
+    // Tells the server to exit and closes its stdin once that's written,
+    // giving it a moment to go by itself before it's killed
+    fn drop(&mut self) {
+        self.client.shutdown();
+        self.flush();
+        self.outgoing = None;
+        let started = Instant::now();
+        while let Ok(None) = self.child.try_wait() {
+            if started.elapsed() >= EXIT_TIMEOUT {
+                let _ = self.child.kill();
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_messages_are_framed() {
+    let message = json!({ "jsonrpc": "2.0", "id": 1, "result": null });
+    let mut bytes = encode_message(&message);
+    let body = message.to_string();
+    assert_eq!(
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes(),
+        bytes
+    );
+    bytes.extend(encode_message(&json!({ "method": "exit" })));
+
+    let mut reader = io::Cursor::new(bytes);
+    assert_eq!(Some(message), read_message(&mut reader).unwrap());
+    assert_eq!(
+        Some(json!({ "method": "exit" })),
+        read_message(&mut reader).unwrap()
+    );
+    assert_eq!(None, read_message(&mut reader).unwrap());
+}
+
+#[test]
+fn test_nothing_is_sent_until_initialized() {
+    let mut client = Client::new(Path::new("/project"));
+    let path = Path::new("/project/src/main.rs");
+    client.sync(path, "rust", 0, || "fn main() {}\n".to_string());
+    client.hover(path, 0, 3);
+    let sent = client.take_outgoing();
+    assert_eq!(1, sent.len());
+    assert_eq!("initialize", sent[0]["method"]);
+    assert_eq!("file:///project", sent[0]["params"]["rootUri"]);
+
+    let answer = json!({ "jsonrpc": "2.0", "id": sent[0]["id"], "result": {} });
+    assert_eq!(None, client.receive(&answer));
+    let methods = client
+        .take_outgoing()
+        .iter()
+        .map(|message| message["method"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec!["initialized", "textDocument/didOpen", "textDocument/hover"],
+        methods
+    );
+}
+
+#[test]
+fn test_only_changed_buffers_are_sent() {
+    let mut client = Client::new(Path::new("/project"));
+    client.initialized = true;
+    let path = Path::new("/project/notes.rb");
+    client.sync(path, "ruby", 3, || "puts 1\n".to_string());
+    client.sync(path, "ruby", 3, || panic!("nothing's changed"));
+    client.sync(path, "ruby", 4, || "puts 2\n".to_string());
+    let sent = client.take_outgoing();
+    assert_eq!(3, sent.len());
+    assert_eq!("textDocument/didChange", sent[2]["method"]);
+    assert_eq!(2, sent[2]["params"]["textDocument"]["version"]);
+    assert_eq!("puts 2\n", sent[2]["params"]["contentChanges"][0]["text"]);
+    assert!(client.is_open(path));
+}
+
+#[test]
+fn test_receiving_diagnostics_definitions_and_hovers() {
+    let mut client = Client::new(Path::new("/project"));
+    client.initialized = true;
+    client.take_outgoing();
+
+    let diagnostics = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": "file:///project/my%20file.rs",
+            "diagnostics": [{
+                "range": {
+                    "start": { "line": 2, "character": 4 },
+                    "end": { "line": 2, "character": 9 },
+                },
+                "severity": 2,
+                "message": "unused variable",
+            }],
+        },
+    });
+    assert_eq!(
+        Some(LspEvent::Diagnostics(
+            PathBuf::from("/project/my file.rs"),
+            vec![Diagnostic {
+                row: 2,
+                start_col: 4,
+                end_col: Some(9),
+                severity: Severity::Warning,
+                message: "unused variable".to_string(),
+            }]
+        )),
+        client.receive(&diagnostics)
+    );
+
+    client.definition(Path::new("/project/main.rs"), 5, 10);
+    let id = client.take_outgoing()[0]["id"].clone();
+    let answer = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": [{
+            "targetUri": "file:///project/lib.rs",
+            "targetRange": { "start": { "line": 0, "character": 0 }, "end": { "line": 9, "character": 1 } },
+            "targetSelectionRange": { "start": { "line": 3, "character": 7 }, "end": { "line": 3, "character": 10 } },
+        }],
+    });
+    assert_eq!(
+        Some(LspEvent::Definition(PathBuf::from("/project/lib.rs"), 3, 7)),
+        client.receive(&answer)
+    );
+
+    client.hover(Path::new("/project/main.rs"), 5, 10);
+    let id = client.take_outgoing()[0]["id"].clone();
+    let answer = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": { "contents": { "kind": "markdown", "value": "```rust\nfn lib()\n```" } },
+    });
+    assert_eq!(
+        Some(LspEvent::Hover("```rust\nfn lib()\n```".to_string())),
+        client.receive(&answer)
+    );
+
+    client.hover(Path::new("/project/main.rs"), 0, 0);
+    let id = client.take_outgoing()[0]["id"].clone();
+    assert_eq!(
+        Some(LspEvent::NotFound("hover text")),
+        client.receive(&json!({ "jsonrpc": "2.0", "id": id, "result": null }))
+    );
+
+    // Requests from the server are always answered
+    let request =
+        json!({ "jsonrpc": "2.0", "id": "abc", "method": "window/workDoneProgress/create" });
+    assert_eq!(None, client.receive(&request));
+    assert_eq!("abc", client.take_outgoing()[0]["id"]);
+}
+
+#[test]
+fn test_columns_and_uris() {
+    assert_eq!(2, utf16_to_char_col("a😀b", 3));
+    assert_eq!(3, char_to_utf16_col("é😀b", 2));
+    assert_eq!(3, utf16_to_char_col("abc", 10));
+    assert_eq!(
+        "file:///a%20b/c%23.rs",
+        path_to_uri(Path::new("/a b/c#.rs"))
+    );
+    assert_eq!(
+        Some(PathBuf::from("/a b/c#.rs")),
+        uri_to_path("file:///a%20b/c%23.rs")
+    );
+    assert_eq!(
+        Some(PathBuf::from("C:/src/x.rs")),
+        uri_to_path("file:///C:/src/x.rs")
+    );
+    assert_eq!(None, uri_to_path("untitled:1"));
+
+    let diagnostic = Diagnostic {
+        row: 0,
+        start_col: 3,
+        end_col: Some(3),
+        severity: Severity::Error,
+        message: String::new(),
+    };
+    assert_eq!(2..3, diagnostic.char_cols("a😀bc"));
+    let to_end = Diagnostic {
+        end_col: None,
+        ..diagnostic
+    };
+    assert_eq!(2..4, to_end.char_cols("a😀bc"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_a_server_that_never_reads_holds_nothing_up() {
+    let mut server = LanguageServer::spawn("sleep 30", Path::new(".")).unwrap();
+    let started = Instant::now();
+    // Far more than fits in a pipe, none of it read
+    let text = "x".repeat(1 << 20);
+    server.client().initialized = true;
+    server
+        .client()
+        .sync(Path::new("/project/main.rs"), "rust", 0, || text.clone());
+    server.poll();
+    drop(server);
+    assert!(started.elapsed() < EXIT_TIMEOUT + Duration::from_secs(2));
+}
@@ -1,19 +1,110 @@
 #![windows_subsystem = "windows"]
 
-use bim::config::RunConfig;
-use bim::gui::gfx_ui;
+use bim::config::{self, RunConfig};
+use bim::git;
+#[cfg(feature = "gui")]
+use bim::gui::{gfx_ui, replay};
+use bim::gutter::LineNumbers;
+use bim::instance;
 use bim::options::Options;
-use std::{env, error::Error};
+use bim::pipe;
+use bim::theme::Theme;
+use std::{env, error::Error, time::Duration};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut options = Options::default();
+    // Flags on the command line override the config file
+    if !env::args().any(|arg| arg == "--no-rc") {
+        for problem in config::load_rc(&mut options) {
+            println!("{}", problem);
+        }
+        for problem in config::load_snippets(&mut options) {
+            println!("{}", problem);
+        }
+        for problem in config::load_templates(&mut options) {
+            println!("{}", problem);
+        }
+    }
     let mut files = Vec::new();
+    let mut replay_log = None;
 
-    for arg in env::args().skip(1) {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "--debug" => options.run_type = RunConfig::Debug,
+            "--no-rc" => {}
             "--no-quit-warning" => options.no_quit_warning = true,
             "-O" => options.vsplit = true,
+            "--virtual-edit" => options.virtual_edit = true,
+            "--no-auto-close" => options.auto_close = false,
+            "--line-numbers" => options.line_numbers = LineNumbers::Absolute,
+            "--relative-line-numbers" => options.line_numbers = LineNumbers::Relative,
+            "--dim-unfocused" => options.dim_unfocused = true,
+            "--minimap" => options.minimap = true,
+            "--restore-session" => options.restore_session = true,
+            "--hide-mouse-while-typing" => options.hide_mouse_while_typing = true,
+            "--single-instance" => options.single_instance = true,
+            "--stdout" => options.pipe_out = true,
+            "--log-stats" => options.log_stats = true,
+            "-" => options.piped_in = Some(pipe::read_stdin()?),
+            "--record" => options.record_actions = args.next(),
+            "--replay" => replay_log = args.next(),
+            "--escape-chord" => {
+                let chord = args.next().unwrap_or_default();
+                let mut chars = chord.chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some(first), Some(second), None) => {
+                        options.escape_chord = Some((first, second))
+                    }
+                    _ => println!("--escape-chord needs two characters, got {:?}", chord),
+                }
+            }
+            "--line-spacing" => {
+                let spacing = args.next().and_then(|spacing| spacing.parse().ok());
+                if let Some(spacing) = spacing.filter(|spacing: &f32| *spacing > 0.0) {
+                    options.spacing.line_spacing = spacing;
+                }
+            }
+            "--padding" => {
+                if let Some(padding) = args.next().and_then(|padding| padding.parse().ok()) {
+                    options.spacing.left_padding = padding;
+                    options.spacing.top_padding = padding;
+                }
+            }
+            "--max-line-length" => {
+                options.max_line_length = args
+                    .next()
+                    .and_then(|len| len.parse().ok())
+                    .filter(|len| *len > 0);
+            }
+            "--large-file-mb" => {
+                if let Some(mb) = args.next() {
+                    match config::parse_megabytes(&mb) {
+                        Ok(len) => options.large_file_len = len,
+                        Err(err) => eprintln!("--large-file-mb: {}", err),
+                    }
+                }
+            }
+            "--save-power-after" => {
+                if let Some(secs) = args.next().and_then(|secs| secs.parse().ok()) {
+                    options.save_power_after = Some(Duration::from_secs(secs))
+                        .filter(|after| *after > Duration::default());
+                }
+            }
+            "--font" => options.font = args.next(),
+            "--theme" => {
+                if let Some(name) = args.next() {
+                    match Theme::load(&name) {
+                        Ok(theme) => options.theme = theme,
+                        Err(err) => eprintln!("{}", err),
+                    }
+                }
+            }
+            "--escape-chord-timeout" => {
+                if let Some(ms) = args.next().and_then(|ms| ms.parse().ok()) {
+                    options.escape_chord_timeout = Duration::from_millis(ms);
+                }
+            }
             _ => {
                 if !arg.starts_with("-") {
                     // i.e. not a flag
@@ -23,11 +114,60 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    if let Some(log_filename) = replay_log {
+        return replay(files.first().map(String::as_str), &log_filename, &options);
+    }
+
+    // Piped in text can't be handed over, so it needs a bim of its own
+    if options.single_instance
+        && options.piped_in.is_none()
+        && !files.is_empty()
+        && instance::send_to_running(&files)
+    {
+        return Ok(());
+    }
+
+    let message_file = files
+        .first()
+        .filter(|file| git::is_message_file(file))
+        .cloned();
+
     if files.len() > 0 {
         options.run_type = RunConfig::RunOpenFiles(files);
     }
 
-    gfx_ui::run(options)?;
+    run(options)?;
+
+    match message_file {
+        Some(file) => git::check_message(&file),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "gui")]
+fn replay(file: Option<&str>, log_filename: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    replay::run(file, log_filename, options)
+}
+
+#[cfg(feature = "gui")]
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    gfx_ui::run(options)
+}
+
+// Every frontend is optional, but so far the gui is the only one
+#[cfg(not(feature = "gui"))]
+const NO_FRONTEND: &str = "bim was built without a frontend, rebuild it with the gui feature";
+
+#[cfg(not(feature = "gui"))]
+fn replay(
+    _file: Option<&str>,
+    _log_filename: &str,
+    _options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    Err(NO_FRONTEND.into())
+}
 
-    Ok(())
+#[cfg(not(feature = "gui"))]
+fn run(_options: Options) -> Result<(), Box<dyn Error>> {
+    Err(NO_FRONTEND.into())
 }
@@ -0,0 +1,91 @@
+// Named places in a buffer to go back to, like vim's marks. Lowercase
+// letters belong to the file they're set in, while each uppercase one can
+// only be in one file at a time, so going to it can mean switching files.
+// Names that aren't letters are left for other features to keep places
+// under, and aren't shown.
+
+use crate::cursor::{Cursor, CursorT};
+use std::collections::BTreeMap;
+
+pub fn is_global(name: char) -> bool {
+    name.is_ascii_uppercase()
+}
+
+// The marks that can be set from the keyboard
+pub fn is_settable(name: char) -> bool {
+    name.is_ascii_alphabetic()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Marks {
+    marks: BTreeMap<char, Cursor>,
+}
+
+impl Marks {
+    pub fn set(&mut self, name: char, at: Cursor) {
+        self.marks
+            .insert(name, Cursor::new(at.text_row(), at.text_col()));
+    }
+
+    pub fn get(&self, name: char) -> Option<Cursor> {
+        self.marks.get(&name).copied()
+    }
+
+    pub fn remove(&mut self, name: char) -> Option<Cursor> {
+        self.marks.remove(&name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, Cursor)> + '_ {
+        self.marks.iter().map(|(name, at)| (*name, *at))
+    }
+
+    // The ones set from the keyboard, which are the ones worth showing
+    pub fn settable(&self) -> impl Iterator<Item = (char, Cursor)> + '_ {
+        self.iter().filter(|(name, _)| is_settable(*name))
+    }
+
+    // Keeps marks with their text when old_rows rows from at on are swapped
+    // for new_rows rows, out of num_lines before. Marks on rows that went
+    // end up on the last of the new ones, and marks past the end stay put,
+    // so loading a file in bit by bit leaves them where they were set.
+    pub fn rows_replaced(&mut self, at: usize, old_rows: usize, new_rows: usize, num_lines: usize) {
+        for mark in self.marks.values_mut() {
+            let row = mark.text_row().max(0) as usize;
+            if row < at || row >= num_lines {
+                continue;
+            }
+            let moved = if row >= at + old_rows {
+                row + new_rows - old_rows
+            } else {
+                at + (row - at).min(new_rows.saturating_sub(1))
+            };
+            mark.text_row = moved as i32;
+        }
+    }
+}
+
+#[test]
+fn test_marks_move_with_their_rows() {
+    let mut marks = Marks::default();
+    marks.set('a', Cursor::new(1, 4));
+    marks.set('b', Cursor::new(5, 0));
+    marks.set('C', Cursor::new(9, 2));
+    marks.set('\'', Cursor::new(3, 0));
+
+    // A row added at 2, then rows 4 to 6 taken down to one
+    marks.rows_replaced(2, 0, 1, 8);
+    marks.rows_replaced(4, 3, 1, 9);
+    assert_eq!(Some(Cursor::new(1, 4)), marks.get('a'));
+    assert_eq!(Some(Cursor::new(4, 0)), marks.get('b'));
+    assert_eq!(Some(Cursor::new(4, 0)), marks.get('\''));
+    // Was past the end, so stays where it is
+    assert_eq!(Some(Cursor::new(9, 2)), marks.get('C'));
+
+    assert_eq!(
+        vec!['C', 'a', 'b'],
+        marks.settable().map(|(name, _)| name).collect::<Vec<_>>()
+    );
+    assert!(is_global('C') && !is_global('a'));
+    assert_eq!(Some(Cursor::new(1, 4)), marks.remove('a'));
+    assert_eq!(None, marks.get('a'));
+}
@@ -0,0 +1,89 @@
+use std::ops::Range;
+
+// How many columns of text the minimap has room for, past which lines are cut off
+const MINIMAP_COLUMNS: usize = 100;
+// Each character of the buffer is a sliver this wide, and each row a line
+// this tall, before scaling for the display
+const MINIMAP_CHAR_WIDTH: f32 = 1.0;
+const MINIMAP_ROW_HEIGHT: f32 = 2.0;
+
+// The shrunk down view of the buffer down the right of a pane
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Minimap {
+    char_width: f32,
+    row_height: f32,
+}
+
+impl Minimap {
+    pub fn new(ui_scale: f32) -> Self {
+        Self {
+            char_width: MINIMAP_CHAR_WIDTH * ui_scale,
+            row_height: MINIMAP_ROW_HEIGHT * ui_scale,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn columns(&self) -> usize {
+        MINIMAP_COLUMNS
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn char_width(&self) -> f32 {
+        self.char_width
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn row_height(&self) -> f32 {
+        self.row_height
+    }
+
+    pub fn width(&self) -> f32 {
+        MINIMAP_COLUMNS as f32 * self.char_width
+    }
+
+    fn rows_that_fit(&self, height: f32) -> usize {
+        if self.row_height <= 0.0 {
+            0
+        } else {
+            (height / self.row_height).max(0.0).floor() as usize
+        }
+    }
+
+    // The rows shown in a minimap height tall. When they don't all fit it
+    // scrolls along with the viewport, as far through the buffer as that is,
+    // so the rows on screen are always in it.
+    pub fn rows(&self, height: f32, num_lines: usize, scroll_fraction: f32) -> Range<usize> {
+        let fit = self.rows_that_fit(height);
+        if num_lines <= fit {
+            return 0..num_lines;
+        }
+        let first = ((num_lines - fit) as f32 * scroll_fraction.clamp(0.0, 1.0)).round() as usize;
+        first..first + fit
+    }
+
+    // The row drawn y down from the top of the minimap, when it's showing rows
+    pub fn row_at(&self, y: f32, rows: Range<usize>) -> Option<usize> {
+        if rows.is_empty() || self.row_height <= 0.0 {
+            return None;
+        }
+        let row = rows.start + (y.max(0.0) / self.row_height).floor() as usize;
+        Some(row.min(rows.end - 1))
+    }
+}
+
+#[test]
+fn test_minimap_rows() {
+    let minimap = Minimap::new(1.0);
+    // Short buffers fit in whole
+    assert_eq!(0..50, minimap.rows(400.0, 50, 0.0));
+    assert_eq!(0..50, minimap.rows(400.0, 50, 1.0));
+    // Long ones scroll along with the viewport
+    assert_eq!(0..200, minimap.rows(400.0, 1_000, 0.0));
+    assert_eq!(400..600, minimap.rows(400.0, 1_000, 0.5));
+    assert_eq!(800..1_000, minimap.rows(400.0, 1_000, 1.0));
+
+    assert_eq!(Some(400), minimap.row_at(0.0, 400..600));
+    assert_eq!(Some(405), minimap.row_at(11.0, 400..600));
+    assert_eq!(Some(599), minimap.row_at(1_000.0, 400..600));
+    assert_eq!(None, minimap.row_at(10.0, 0..0));
+}
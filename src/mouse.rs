@@ -1,7 +1,87 @@
 use glam::Vec2;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "gui")]
+use std::time::{Duration, Instant};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MouseMove {
     Pixels(Vec2),
     Lines(Vec2),
 }
+
+// How soon after the last click the next one has to come to add to it, and
+// how near to it, in pixels
+#[cfg(feature = "gui")]
+const MULTI_CLICK_TIME: Duration = Duration::from_millis(500);
+#[cfg(feature = "gui")]
+const MULTI_CLICK_DISTANCE: f32 = 4.0;
+
+#[cfg(feature = "gui")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClickCount {
+    Single,
+    Double,
+    Triple,
+}
+
+// Counts clicks that follow each other quickly enough in the same place.
+// A fourth starts counting all over again.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct Clicks {
+    last: Option<(Instant, Vec2, ClickCount)>,
+}
+
+#[cfg(feature = "gui")]
+impl Clicks {
+    pub fn click(&mut self, at: Instant, location: Vec2) -> ClickCount {
+        let count = match self.last {
+            Some((last_at, last_location, count))
+                if at.duration_since(last_at) <= MULTI_CLICK_TIME
+                    && (location - last_location).length() <= MULTI_CLICK_DISTANCE =>
+            {
+                match count {
+                    ClickCount::Single => ClickCount::Double,
+                    ClickCount::Double => ClickCount::Triple,
+                    ClickCount::Triple => ClickCount::Single,
+                }
+            }
+            _ => ClickCount::Single,
+        };
+        self.last = Some((at, location, count));
+        count
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_click_count() {
+    use glam::vec2;
+
+    let start = Instant::now();
+    let after = |millis| start + Duration::from_millis(millis);
+    let mut clicks = Clicks::default();
+    assert_eq!(ClickCount::Single, clicks.click(start, vec2(10.0, 10.0)));
+    assert_eq!(
+        ClickCount::Double,
+        clicks.click(after(200), vec2(11.0, 10.0))
+    );
+    assert_eq!(
+        ClickCount::Triple,
+        clicks.click(after(400), vec2(11.0, 12.0))
+    );
+    assert_eq!(
+        ClickCount::Single,
+        clicks.click(after(600), vec2(11.0, 12.0))
+    );
+    // Too slow
+    assert_eq!(
+        ClickCount::Single,
+        clicks.click(after(1_200), vec2(11.0, 12.0))
+    );
+    // Too far
+    assert_eq!(
+        ClickCount::Single,
+        clicks.click(after(1_300), vec2(40.0, 12.0))
+    );
+}
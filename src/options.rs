@@ -1,12 +1,194 @@
-use crate::config::RunConfig;
+use crate::config::{RunConfig, TAB_STOP};
+use crate::gutter::LineNumbers;
 use crate::keymap::{Keymap, DEFAULT_KEYMAP};
+use crate::snippet::{Snippet, DEFAULT_SNIPPETS};
+use crate::status_line::{StatusTarget, DEFAULT_STATUS_FORMAT};
+use crate::syntax::Syntax;
+use crate::template::{Template, DEFAULT_TEMPLATES};
+use crate::theme::Theme;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const DEFAULT_TOOLTIP_DELAY: u64 = 800;
+const DEFAULT_ESCAPE_CHORD_TIMEOUT: u64 = 200;
+const DEFAULT_FONT_SIZE: f32 = 28.0;
+const DEFAULT_INDENT_WIDTH: usize = 4;
+const DEFAULT_LARGE_FILE_LEN: u64 = 256 * 1024 * 1024;
+const DEFAULT_LAZY_HIGHLIGHT_LEN: u64 = 16 * 1024 * 1024;
+const DEFAULT_SAVE_POWER_AFTER: u64 = 10;
+const DEFAULT_GUIDES: [usize; 2] = [80, 120];
+
+// Space around and between the lines of text in each pane. line_spacing
+// multiplies the font's line height, so 1.2 gives 20% more room per line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spacing {
+    pub left_padding: f32,
+    pub top_padding: f32,
+    pub line_spacing: f32,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Self {
+            left_padding: 12.0,
+            top_padding: 0.0,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+// Which spaces and tabs are shown up: those at the ends of lines, or all
+// of them as faint marks too
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ShowWhitespace {
+    Off,
+    #[default]
+    Trailing,
+    All,
+}
+
+// How the cursor's drawn: a block the character under it shows through,
+// a bar before that character, or a line under it
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum CursorStyle {
+    #[default]
+    Block,
+    Bar,
+    Underline,
+}
+
+// The columns lines are drawn down the pane at, to see how long lines are
+// getting. Those set for a filetype win over the global ones, and none at
+// all turns them off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Guides {
+    pub global: Vec<usize>,
+    pub filetypes: HashMap<String, Vec<usize>>,
+}
+
+impl Default for Guides {
+    fn default() -> Self {
+        Self {
+            global: DEFAULT_GUIDES.to_vec(),
+            filetypes: HashMap::new(),
+        }
+    }
+}
+
+impl Guides {
+    pub fn for_filetype(&self, filetype: &str) -> &[usize] {
+        self.filetypes.get(filetype).unwrap_or(&self.global)
+    }
+}
+
+// What the Tab key puts in, and how wide tab characters are drawn
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Indentation {
+    // Indent with spaces rather than tab characters
+    pub expand_tabs: bool,
+    pub tab_width: usize,
+    // How far the Tab key indents with spaces
+    pub indent_width: usize,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            expand_tabs: true,
+            tab_width: TAB_STOP,
+            indent_width: DEFAULT_INDENT_WIDTH,
+        }
+    }
+}
+
+// Indentation everywhere, and for the filetypes that have their own. Those
+// set for a filetype win over its syntax's defaults, which win over global.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndentOptions {
+    pub global: Indentation,
+    pub filetypes: HashMap<String, Indentation>,
+}
+
+impl IndentOptions {
+    pub fn for_syntax(&self, syntax: Option<&Syntax<'_>>) -> Indentation {
+        syntax
+            .and_then(|syntax| {
+                self.filetypes
+                    .get(syntax.filetype)
+                    .or(syntax.indentation.as_ref())
+            })
+            .cloned()
+            .unwrap_or(self.global)
+    }
+
+    // The filetype's settings, to change, starting from what it has now
+    pub fn for_filetype_mut(&mut self, filetype: &str) -> &mut Indentation {
+        let current = self.for_syntax(Syntax::for_filetype(filetype));
+        self.filetypes
+            .entry(filetype.to_string())
+            .or_insert(current)
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Options {
+    pub font_size: f32,
     pub no_quit_warning: bool,
     pub vsplit: bool,
     pub run_type: RunConfig,
     pub keymap: Keymap,
+    pub templates: Vec<Template>,
+    // Looked through in order, so the built in ones come before the user's
+    pub snippets: Vec<Snippet>,
+    pub tooltip_delay: Duration,
+    pub record_actions: Option<String>,
+    pub virtual_edit: bool,
+    // Typing a bracket or quote puts in the one that closes it too
+    pub auto_close: bool,
+    pub show_whitespace: ShowWhitespace,
+    pub cursor_style: CursorStyle,
+    // How long the cursor's shown and then hidden for, if it blinks at all
+    pub cursor_blink: Option<Duration>,
+    // Saving takes the spaces off the ends of lines changed since last time
+    pub trim_trailing_whitespace: bool,
+    pub line_numbers: LineNumbers,
+    pub escape_chord: Option<(char, char)>,
+    pub escape_chord_timeout: Duration,
+    pub spacing: Spacing,
+    pub indent: IndentOptions,
+    pub dim_unfocused: bool,
+    pub minimap: bool,
+    // Open the files and splits from when bim last quit, if no files are given
+    pub restore_session: bool,
+    pub hide_mouse_while_typing: bool,
+    pub max_line_length: Option<usize>,
+    pub guides: Guides,
+    // Offer completions once a word's this long, without asking
+    pub complete_after: Option<usize>,
+    // Which segments the status line shows, in order
+    pub status_format: Vec<StatusTarget>,
+    pub single_instance: bool,
+    // Whatever was piped in with `bim -`, to open as a scratch buffer
+    pub piped_in: Option<Vec<u8>>,
+    // Write the focused buffer to stdout on the way out
+    pub pipe_out: bool,
+    // Write each buffer's edit stats to the debug log when it's closed
+    pub log_stats: bool,
+    // Saving creates any missing directories without asking first
+    pub create_dirs: bool,
+    // Files bigger than this are asked about before they're opened
+    pub large_file_len: Option<u64>,
+    // Files bigger than this only have the rows on screen highlighted
+    pub lazy_highlight_len: Option<u64>,
+    // How long without input before the cursor stops blinking and frames slow down
+    pub save_power_after: Option<Duration>,
+    pub theme: Theme,
+    // The font text is drawn with, the built in one if there isn't one
+    pub font: Option<String>,
+    // Fonts to draw whatever characters the font doesn't have, tried in order
+    pub fallback_fonts: Vec<String>,
+    // The command to run a language server with, by filetype
+    pub language_servers: HashMap<String, String>,
 }
 
 impl Options {
@@ -18,10 +200,46 @@ impl Options {
 impl Default for Options {
     fn default() -> Self {
         Self {
+            font_size: DEFAULT_FONT_SIZE,
             no_quit_warning: false,
             vsplit: false,
             run_type: RunConfig::default(),
             keymap: DEFAULT_KEYMAP.clone(),
+            templates: DEFAULT_TEMPLATES.clone(),
+            snippets: DEFAULT_SNIPPETS.clone(),
+            tooltip_delay: Duration::from_millis(DEFAULT_TOOLTIP_DELAY),
+            record_actions: None,
+            virtual_edit: false,
+            auto_close: true,
+            show_whitespace: ShowWhitespace::default(),
+            cursor_style: CursorStyle::default(),
+            cursor_blink: Some(Duration::from_millis(500)),
+            trim_trailing_whitespace: false,
+            line_numbers: LineNumbers::default(),
+            escape_chord: None,
+            escape_chord_timeout: Duration::from_millis(DEFAULT_ESCAPE_CHORD_TIMEOUT),
+            spacing: Spacing::default(),
+            indent: IndentOptions::default(),
+            dim_unfocused: false,
+            minimap: false,
+            restore_session: false,
+            hide_mouse_while_typing: false,
+            max_line_length: None,
+            guides: Guides::default(),
+            complete_after: None,
+            status_format: DEFAULT_STATUS_FORMAT.to_vec(),
+            single_instance: false,
+            piped_in: None,
+            pipe_out: false,
+            log_stats: false,
+            create_dirs: false,
+            theme: Theme::default(),
+            font: None,
+            fallback_fonts: Vec::new(),
+            language_servers: HashMap::new(),
+            large_file_len: Some(DEFAULT_LARGE_FILE_LEN),
+            lazy_highlight_len: Some(DEFAULT_LAZY_HIGHLIGHT_LEN),
+            save_power_after: Some(Duration::from_secs(DEFAULT_SAVE_POWER_AFTER)),
         }
     }
 }
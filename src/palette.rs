@@ -0,0 +1,242 @@
+use crate::keymap::{parse_action, Keymap};
+
+// The actions the command palette offers, by the names they're bound by in
+// the config file. Ones that need more to go on than a name are asked for
+// by the actions that start a prompt for them.
+const COMMANDS: [&str; 76] = [
+    "SaveFile",
+    "CommandLine",
+    "SplitVertically",
+    "SplitHorizontally",
+    "ClosePane",
+    "ReopenClosedPane",
+    "FocusPane: Left",
+    "FocusPane: Right",
+    "FocusPane: Up",
+    "FocusPane: Down",
+    "ResizePane: Right",
+    "ResizePane: Left",
+    "ResizePane: Down",
+    "ResizePane: Up",
+    "NewTab",
+    "CloseTab",
+    "NextTab",
+    "PrevTab",
+    "NextBuffer",
+    "PrevBuffer",
+    "PickBuffer",
+    "ToggleFullscreen",
+    "ToggleMinimap",
+    "IncFontSize",
+    "DecFontSize",
+    "PickTheme",
+    "PickFiletype",
+    "StartGotoLine",
+    "StartOpenFile",
+    "StartSaveAs",
+    "StartRename",
+    "SetNewline: Unix",
+    "SetNewline: Dos",
+    "CycleLineNumbers",
+    "ToggleFollow",
+    "InspectChar",
+    "ListBindings",
+    "ShowStats",
+    "ShowMessages",
+    "GotoDefinition",
+    "ShowHover",
+    "StartSearch",
+    "ClearSearchHighlight",
+    "StartSearchInSelection",
+    "ToggleRegexSearch",
+    "ToggleSearchAllBuffers",
+    "StartReplace",
+    "StartReplaceInFiles",
+    "ApplyReplaceInFiles",
+    "ToggleSelection",
+    "ToggleBlockSelection",
+    "CutBlock",
+    "PasteBlock",
+    "ToggleVirtualEdit",
+    "ToggleComment",
+    "DuplicateLines",
+    "MoveLines: Up",
+    "MoveLines: Down",
+    "JoinLines",
+    "ChangeCase: Upper",
+    "ChangeCase: Lower",
+    "ChangeCase: Title",
+    "ChangeCase: Toggle",
+    "IncrementNumber: 1",
+    "IncrementNumber: -1",
+    "Complete",
+    "HighlightFully",
+    "JumpToMatchingBracket",
+    "CloneCursor",
+    "AddCursorAtNextMatch",
+    "ClearCursors",
+    "Undo",
+    "Redo",
+    "DumpFlameGraph",
+    "ToggleProfiler",
+    "Quit",
+];
+
+// How many matches the palette lists under the prompt
+#[cfg(feature = "gui")]
+pub const PALETTE_ROWS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub name: String,
+    // The keys it's bound to, if it is
+    pub keys: Option<String>,
+}
+
+// Every command, and which of the ones matching what's been typed is picked
+#[derive(Clone, Debug)]
+pub struct Palette {
+    commands: Vec<Command>,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(keymap: &Keymap) -> Self {
+        let commands = COMMANDS
+            .iter()
+            .map(|name| Command {
+                name: name.to_string(),
+                keys: parse_action(name)
+                    .ok()
+                    .and_then(|action| keymap.keys_for(&action)),
+            })
+            .collect();
+        Self {
+            commands,
+            selected: 0,
+        }
+    }
+
+    // The commands query picks out, best first
+    pub fn matches(&self, query: &str) -> Vec<&Command> {
+        let mut scored = self
+            .commands
+            .iter()
+            .filter_map(|command| fuzzy_score(query, &command.name).map(|score| (score, command)))
+            .collect::<Vec<_>>();
+        scored.sort_by_key(|(score, command)| (-score, command.name.len()));
+        scored.into_iter().map(|(_, command)| command).collect()
+    }
+
+    // Where the picked command is in the matches for query
+    pub fn selected(&self, query: &str) -> usize {
+        self.selected
+            .min(self.matches(query).len().saturating_sub(1))
+    }
+
+    pub fn chosen(&self, query: &str) -> Option<&Command> {
+        self.matches(query).get(self.selected(query)).copied()
+    }
+
+    pub fn select_next(&mut self, query: &str) {
+        self.selected = (self.selected(query) + 1).min(self.matches(query).len().saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self, query: &str) {
+        self.selected = self.selected(query).saturating_sub(1);
+    }
+
+    // Typing changes the matches, so the best one is picked again
+    pub fn reset_selection(&mut self) {
+        self.selected = 0;
+    }
+}
+
+fn starts_word(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()),
+    }
+}
+
+// How well query matches name, if every character of it is there in order,
+// ignoring case and spaces. Characters that follow on from the last match or
+// start a word ("sv" for SplitVertically) count for more.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut name_chars = name.chars().enumerate();
+    let mut prev = None;
+    let mut last_match = None;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        loop {
+            let (idx, c) = name_chars.next()?;
+            let was = prev.replace(c);
+            if c.to_lowercase().eq(wanted.to_lowercase()) {
+                score += 1;
+                if starts_word(was, c) {
+                    score += 3;
+                }
+                if last_match.is_some() && last_match == idx.checked_sub(1) {
+                    score += 2;
+                }
+                last_match = Some(idx);
+                break;
+            }
+        }
+    }
+    Some(score)
+}
+
+#[test]
+fn test_fuzzy_score() {
+    assert_eq!(Some(0), fuzzy_score("", "SaveFile"));
+    assert_eq!(None, fuzzy_score("xyz", "SaveFile"));
+    assert_eq!(None, fuzzy_score("fs", "SaveFile"));
+    assert!(fuzzy_score("save", "SaveFile").is_some());
+    assert!(fuzzy_score("sv", "SplitVertically") > fuzzy_score("sv", "ShowStats"));
+    assert!(
+        fuzzy_score("split v", "SplitVertically") > fuzzy_score("split v", "SplitHorizontally")
+    );
+}
+
+#[test]
+fn test_palette_matches_and_keys() {
+    use crate::keymap::DEFAULT_KEYMAP;
+
+    let mut palette = Palette::new(&DEFAULT_KEYMAP);
+    for command in palette.commands.iter() {
+        assert!(parse_action(&command.name).is_ok(), "{}", command.name);
+    }
+    let names = |query| {
+        palette
+            .matches(query)
+            .iter()
+            .map(|command| command.name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(COMMANDS.len(), names("").len());
+    assert_eq!(Some(&"SplitVertically"), names("splitv").first());
+    assert_eq!(Some(&"ToggleMinimap"), names("mini").first());
+
+    let minimap = palette.chosen("mini").unwrap();
+    assert_eq!(Some("C-w m".to_string()), minimap.keys);
+    assert_eq!(
+        Some("C-s".to_string()),
+        palette.chosen("savefile").unwrap().keys
+    );
+
+    let splits = names("split vert");
+    assert_eq!(vec!["SplitVertically"], splits);
+    palette.select_next("split vert");
+    assert_eq!(0, palette.selected("split vert"));
+    palette.select_next("");
+    palette.select_next("");
+    assert_eq!(2, palette.selected(""));
+    palette.select_prev("");
+    assert_eq!(1, palette.selected(""));
+    // Fewer matches than that pick the last of them
+    assert_eq!(0, palette.selected("split vert"));
+    palette.reset_selection();
+    assert_eq!("Undo", palette.chosen("undo").unwrap().name);
+    assert_eq!(None, palette.chosen("xyzzy"));
+}
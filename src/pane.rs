@@ -0,0 +1,5191 @@
+use crate::action::{BufferAction, GuiAction, PaneAction, WindowAction};
+use crate::animation::Animation;
+#[cfg(feature = "gui")]
+use crate::animation::AnimationState;
+use crate::buffer::{Buffer, FileSaveStatus, OpenAs, OpenWarning};
+use crate::buffer_list::BufferList;
+#[cfg(feature = "gui")]
+use crate::colours::Colour;
+use crate::commands::{Case, Direction, MoveCursor, MoveUnit, SearchDirection};
+use crate::completion::{self, Completion};
+use crate::cursor::{Cursor, CursorT};
+use crate::encoding::Encoding;
+use crate::follow::Follow;
+use crate::grep;
+use crate::gutter::{Gutter, LineNumbers};
+#[cfg(feature = "gui")]
+use crate::highlight::highlight_to_color;
+use crate::highlight::Highlight;
+use crate::highlight::HighlightedSection;
+use crate::idle::PowerMode;
+use crate::input::Input;
+#[cfg(feature = "gui")]
+use crate::line_spacing::LineSpacing;
+use crate::marks;
+use crate::minimap::Minimap;
+use crate::mouse::MouseMove;
+use crate::options::{CursorStyle, Guides, ShowWhitespace, Spacing};
+use crate::palette::Palette;
+#[cfg(feature = "gui")]
+use crate::palette::PALETTE_ROWS;
+use crate::paths;
+use crate::prompt::PromptAction;
+use crate::rect::{Rect, RectBuilder};
+#[cfg(feature = "gui")]
+use crate::renderer::{Renderer, Text, TextSpan};
+use crate::row::{Newline, Row};
+use crate::scrollbar::{self, Scrollbar};
+use crate::search::{ReplaceStage, Search};
+use crate::selection::Selection;
+use crate::session::PaneSession;
+use crate::snippet::{self, Expansion, Snippet};
+use crate::stats::EditStats;
+use crate::status::Level;
+use crate::status_line::{StatusLine, StatusTarget};
+use crate::swap::Autosave;
+use crate::syntax::{Syntax, SYNTAXES};
+use crate::theme::Theme;
+#[cfg(feature = "gui")]
+use crate::utils::char_position_to_byte_position;
+use crate::viewport::{row_at_percentage, Viewport};
+use crate::watch::Watch;
+use glam::{vec2, Vec2};
+#[cfg(feature = "gui")]
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+const CURSOR_BLINK_INTERVAL: u64 = 500;
+#[cfg(feature = "gui")]
+const IDLE_TRIM_ROWS: usize = 200;
+// Lines per second to scroll for each line the mouse is dragged past the edge
+const DRAG_SCROLL_SPEED: f32 = 15.0;
+// How far the text of a dimmed pane fades towards its background
+#[cfg(feature = "gui")]
+const DIM_AMOUNT: f32 = 0.4;
+// Rows either side of the screen that have highlighted sections made too,
+// so scrolling a little way doesn't need them making again
+const SECTIONS_MARGIN: usize = 100;
+// The minimap goes over the text, in front of the line highlight and behind
+// the cursor and whatever else is drawn in front of the text
+#[cfg(feature = "gui")]
+const MINIMAP_Z: f32 = 0.9;
+// The scrollbar's over the text and the minimap
+#[cfg(feature = "gui")]
+const SCROLLBAR_Z: f32 = 0.75;
+// Rows either side of the cursor that words to complete with are taken from
+const COMPLETION_SCAN_ROWS: usize = 2000;
+
+// A buffer that's open in a pane but not being shown, remembering where it
+// was scrolled to
+struct ParkedBuffer<'a> {
+    buffer: Buffer<'a>,
+    row_offset: f32,
+    col_offset: f32,
+}
+
+pub struct Pane<'a> {
+    pub buffer: Buffer<'a>,
+    buffers: BufferList<ParkedBuffer<'a>>,
+    pub highlighted_sections: Vec<HighlightedSection>,
+    // Which rows highlighted_sections are for, and how many rows the buffer
+    // had when they last caught up
+    sections_rows: Range<usize>,
+    sections_num_lines: usize,
+    pub status_line: StatusLine,
+    pub prompt: Option<Input<'a>>,
+    // The commands to pick from while the command palette's prompt is up
+    palette: Option<Palette>,
+    // What to replace in files, while asking what to replace it with
+    files_needle: Option<String>,
+    // The words on offer to finish the one at the cursor, while the popup's up
+    completion: Option<Completion>,
+    complete_after: Option<usize>,
+    // The "file:line:col" of the search result Enter was pressed on
+    jump_to: Option<String>,
+    // Something to tell whoever's looking, like a search not finding anything
+    message: Option<(Level, String)>,
+    // The rows of the last block cut, for PasteBlock
+    cut_block: Vec<String>,
+    snippets: Rc<Vec<Snippet>>,
+    show_whitespace: ShowWhitespace,
+    guides: Guides,
+    // The snippet being filled in, while Tab goes between its stops
+    expansion: Option<Expansion>,
+    // The snippets with the prefix Tab was pressed after, to pick from
+    snippet_choices: Vec<Snippet>,
+    pub search: Option<Search>,
+    // The last search finished with Enter, whose matches stay highlighted
+    // until they're cleared
+    search_highlight: Option<Search>,
+    follow: Option<Follow>,
+    watch: Watch,
+    autosave: Autosave,
+    drag: Option<Vec2>,
+    gutter: Gutter,
+    focused: bool,
+    dim_unfocused: bool,
+    show_minimap: bool,
+    scrollbar: Scrollbar,
+    max_line_length: Option<usize>,
+    pub bounds: Vec2,
+    position: Vec2,
+    // The font's own line height, line_height has the spacing applied
+    font_line_height: f32,
+    line_height: f32,
+    spacing: Spacing,
+    character_width: f32,
+    pub font_size: f32,
+    pub ui_scale: f32,
+    left_padding: f32,
+    viewport: Viewport,
+    cursor_animation: Animation,
+    // How often the cursor blinks, if it does
+    cursor_blink: Option<Duration>,
+    cursor_style: CursorStyle,
+    // Something on show has changed since the pane was last drawn
+    damaged: bool,
+    theme: Rc<Theme>,
+    power_mode: PowerMode,
+    idle_trim_row: usize,
+    #[cfg(feature = "gui")]
+    sweep_revision: u64,
+    trimmed_revision: Option<u64>,
+}
+
+impl<'a> Default for Pane<'a> {
+    fn default() -> Self {
+        Self {
+            buffer: Buffer::default(),
+            buffers: BufferList::default(),
+            highlighted_sections: Vec::new(),
+            sections_rows: 0..0,
+            sections_num_lines: 0,
+            status_line: StatusLine::default(),
+            prompt: None,
+            palette: None,
+            files_needle: None,
+            completion: None,
+            complete_after: None,
+            jump_to: None,
+            message: None,
+            cut_block: vec![],
+            snippets: Rc::new(vec![]),
+            show_whitespace: ShowWhitespace::default(),
+            guides: Guides::default(),
+            expansion: None,
+            snippet_choices: vec![],
+            search: None,
+            search_highlight: None,
+            follow: None,
+            watch: Watch::default(),
+            autosave: Autosave::default(),
+            drag: None,
+            gutter: Gutter::default(),
+            focused: false,
+            dim_unfocused: false,
+            show_minimap: false,
+            scrollbar: Scrollbar::default(),
+            max_line_length: None,
+            bounds: vec2(0.0, 0.0),
+            position: vec2(0.0, 0.0),
+            font_line_height: 0.0,
+            line_height: 0.0,
+            spacing: Spacing::default(),
+            character_width: 0.0,
+            font_size: 0.0,
+            ui_scale: 0.0,
+            left_padding: Spacing::default().left_padding,
+            viewport: Viewport::default(),
+            cursor_animation: Animation::new(Duration::from_millis(CURSOR_BLINK_INTERVAL)),
+            cursor_blink: Some(Duration::from_millis(CURSOR_BLINK_INTERVAL)),
+            cursor_style: CursorStyle::default(),
+            damaged: true,
+            theme: Rc::new(Theme::default()),
+            power_mode: PowerMode::Normal,
+            idle_trim_row: 0,
+            #[cfg(feature = "gui")]
+            sweep_revision: 0,
+            trimmed_revision: None,
+        }
+    }
+}
+
+impl<'a> Pane<'a> {
+    pub fn new(font_size: f32, ui_scale: f32, buffer: Buffer<'a>, focused: bool) -> Self {
+        let mut pane = Self {
+            buffer,
+            font_size,
+            ui_scale,
+            focused,
+            ..Pane::default()
+        };
+        pane.update();
+        pane
+    }
+
+    pub fn scroll_fraction(&self) -> f32 {
+        self.viewport.scroll_fraction(self.buffer.num_lines())
+    }
+
+    pub fn set_scroll_fraction(&mut self, fraction: f32) {
+        self.viewport
+            .set_scroll_fraction(fraction, self.buffer.num_lines());
+        if !self.is_cursor_onscreen() {
+            self.move_cursor_onscreen();
+        }
+        self.update_status_line();
+    }
+
+    fn set_search(&mut self, search: Option<Search>) {
+        if search.is_some() {
+            self.search_highlight = None;
+        }
+        self.search = search;
+    }
+
+    fn set_prompt(&mut self, prompt: Option<Input<'a>>) {
+        self.prompt = prompt;
+    }
+
+    // Background maintenance, run a slice at a time while the user is idle.
+    // Sweeps the buffer trimming spare row capacity, once per revision.
+    #[cfg(feature = "gui")]
+    pub fn idle_work(&mut self) {
+        let revision = self.buffer.revision();
+        if self.trimmed_revision == Some(revision) {
+            return;
+        }
+        if self.idle_trim_row == 0 {
+            self.sweep_revision = revision;
+        }
+        self.idle_trim_row = self.buffer.trim_rows(self.idle_trim_row, IDLE_TRIM_ROWS);
+        if self.idle_trim_row == 0 {
+            self.trimmed_revision = Some(self.sweep_revision);
+        }
+    }
+
+    pub fn do_action(&mut self, action: PaneAction) {
+        use PaneAction::*;
+
+        // The popup's for the word at the cursor, which clicking moves from
+        if matches!(
+            action,
+            MouseClick(_)
+                | MouseShiftClick(_)
+                | MouseAltClick(_)
+                | MouseDoubleClick(_)
+                | MouseTripleClick(_)
+        ) {
+            self.completion = None;
+        }
+
+        match action {
+            UpdateSize(bounds, position) => self.update_size(bounds, position),
+            MouseScroll(delta) => self.mouse_scroll(delta),
+            MouseClick(location) => self.mouse_click(location),
+            MouseShiftClick(location) => self.mouse_shift_click(location),
+            MouseAltClick(location) => self.mouse_alt_click(location),
+            MouseDoubleClick(location) => {
+                self.mouse_select_at(location, Buffer::select_word_at_cursor)
+            }
+            MouseTripleClick(location) => self.mouse_select_at(location, |buffer| {
+                buffer.select_line_at_cursor();
+                true
+            }),
+            MouseDrag(location) => self.mouse_drag(location),
+            MouseRelease => self.mouse_release(),
+            ScrollToFraction(fraction) => self.set_scroll_fraction(fraction),
+            ToggleFollow => self.toggle_follow(),
+            CycleLineNumbers => self.set_line_numbers(self.line_numbers().next()),
+            PrintDebugInfo => self.print_info(),
+            StartReplaceInFiles => self.start_prompt(Input::new(
+                "Replace in files",
+                PromptAction::ReplaceInFilesNeedle,
+                true,
+            )),
+            PickFiletype => self.pick_filetype(),
+            StartGotoLine => {
+                self.start_prompt(Input::new("Go to line[:col]", PromptAction::GotoLine, true))
+            }
+            StartOpenFile => {
+                self.start_prompt(Input::new("Open file", PromptAction::OpenFile, true))
+            }
+            StartSaveAs => self.start_filename_prompt("Save file as", PromptAction::SaveFile),
+            StartRename => self.start_filename_prompt("Rename to", PromptAction::RenameFile),
+        }
+    }
+
+    fn scroll(&mut self) {
+        if self.line_height > 0.0 {
+            self.viewport.scroll_to_row(self.buffer.cursor.text_row());
+        }
+        if self.character_width > 0.0 {
+            let cursor_col = self.buffer.text_cursor_to_render(
+                self.buffer.cursor.text_col(),
+                self.buffer.cursor.text_row(),
+            );
+            self.viewport
+                .scroll_to_col(cursor_col.max(0) as usize, self.screen_cols());
+        }
+    }
+
+    fn print_info(&self) {
+        println!("status_height: {}", self.line_height);
+        println!("inner: ({}, {})", self.inner_width(), self.inner_height());
+        println!(
+            "cursor on screen: {:?}",
+            self.onscreen_cursor(&self.buffer.cursor)
+        );
+        println!("screen_rows: {}", self.viewport.screen_rows());
+        println!("scrolled: {:.0}%", self.scroll_fraction() * 100.0);
+        println!("bounds: {:?}", self.bounds);
+        println!("position: {:?}", self.position);
+    }
+
+    fn update_status_line(&mut self) {
+        self.damaged = true;
+        let filename = self.buffer.title();
+        self.status_line.filename = if self.buffers.len() > 1 {
+            format!(
+                "{} [{}/{}]",
+                filename,
+                self.buffers.current() + 1,
+                self.buffers.len()
+            )
+        } else {
+            filename
+        };
+        self.status_line.filetype = self.buffer.get_filetype();
+        self.status_line.cursor = format!(
+            "{}:{}",
+            self.buffer.cursor.text_row() + 1,
+            self.buffer.cursor.text_col() + 1,
+        );
+        self.status_line.encoding = self.buffer.encoding().to_string();
+        self.status_line.newline = self.buffer.newline_name().to_string();
+        self.status_line.long_lines = match self.long_lines().len() {
+            0 => String::new(),
+            1 => "1 long line".to_string(),
+            count => format!("{} long lines", count),
+        };
+        self.status_line.loading = self.buffer.loading_status().unwrap_or_default();
+        self.status_line.dirty = if self.buffer.is_dirty() {
+            "modified".to_string()
+        } else {
+            String::new()
+        };
+        let num_lines = self.buffer.num_lines();
+        self.status_line.num_lines = match num_lines {
+            1 => "1 line".to_string(),
+            count => format!("{} lines", count),
+        };
+        let row = (self.buffer.cursor.text_row() as usize + 1).min(num_lines.max(1));
+        self.status_line.percent = format!("{}%", row * 100 / num_lines.max(1));
+        self.status_line.mode = self.mode_name().to_string();
+    }
+
+    // What's taking the keys instead of the buffer, if anything
+    fn mode_name(&self) -> &'static str {
+        if self.prompt.is_some() {
+            "prompt"
+        } else if let Some(search) = &self.search {
+            if search.replace_stage().is_some() {
+                "replace"
+            } else {
+                "search"
+            }
+        } else if self
+            .buffer
+            .selection
+            .is_some_and(|selection| selection.is_block())
+        {
+            "block"
+        } else if self.buffer.selection.is_some() {
+            "select"
+        } else {
+            ""
+        }
+    }
+
+    fn long_lines(&self) -> Vec<usize> {
+        self.max_line_length
+            .map(|max_len| self.buffer.long_lines(max_len))
+            .unwrap_or_default()
+    }
+
+    fn set_highlighted_sections(&mut self, mut highlighted_sections: Vec<HighlightedSection>) {
+        self.highlighted_sections.clear();
+        self.highlighted_sections.append(&mut highlighted_sections);
+    }
+
+    fn update_cursor(&mut self) {
+        if !self.top_prompt_visible() {
+            self.buffer.update_selection();
+        }
+        if self.buffer.update_matching_bracket() {
+            self.update_highlighted_sections();
+        }
+        self.update_screen_rows();
+        self.scroll();
+        self.update_visible_sections();
+        self.update_status_line();
+    }
+
+    fn mouse_scroll(&mut self, delta: MouseMove) {
+        match delta {
+            MouseMove::Lines(lines) => {
+                self.scroll_window_vertically(lines.y());
+                self.scroll_window_horizontally(lines.x());
+            }
+            MouseMove::Pixels(pixels) => {
+                self.scroll_window_vertically(f32::ceil(pixels.y() / self.line_height));
+                self.scroll_window_horizontally(pixels.x() / self.character_width);
+            }
+        }
+        self.update_cursor();
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn new_search(&self) -> Search {
+        Search::new(self.viewport.row_offset(), self.viewport.col_offset())
+    }
+
+    fn restore_from_search(&mut self, search: Search) {
+        self.viewport
+            .set_row_offset(search.saved_row_offset(), self.buffer.num_lines());
+        self.viewport.set_col_offset(search.saved_col_offset());
+    }
+
+    fn move_cursor<F>(&mut self, func: F)
+    where
+        F: Fn(&mut Cursor),
+    {
+        self.buffer.cursor.change(func);
+        self.cursor_animation.cancel();
+    }
+
+    fn update_size(&mut self, bounds: Vec2, position: Vec2) {
+        self.bounds = bounds;
+        self.position = position;
+        self.update_screen_rows();
+        self.scroll();
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn section_texts(&self, focused: bool) -> Vec<TextSpan<'_>> {
+        let _guard = flame::start_guard("highlighted_sections -> section_texts");
+
+        let mut section_texts = vec![];
+        let mut push = |text, color| section_texts.push(TextSpan { text, color });
+
+        let (cursor_text_row, cursor_text_col) = self.cursor();
+        // Only a block's drawn over the character, which then needs to
+        // stand out from it
+        let rcursor_idx = self
+            .buffer
+            .text_cursor_to_render_idx(cursor_text_col as i32, cursor_text_row as i32)
+            .filter(|_| self.cursor_style == CursorStyle::Block && self.cursor_shown(focused));
+        // Where each row starts once it's scrolled sideways, and the room to
+        // leave for a wide character that's only partly on screen
+        let first_col = self.viewport.first_col();
+        let mut row_start: Option<(usize, usize)> = None;
+        let mut pending_pad = 0;
+        for highlighted_section in self.highlighted_sections.iter() {
+            if highlighted_section.text_row as i32
+                > self.viewport.screen_rows() + self.viewport.first_row()
+            {
+                break;
+            }
+            if (highlighted_section.text_row as i32) < self.viewport.first_row() {
+                continue;
+            }
+
+            let hl = highlighted_section.highlight;
+            let row = &self.buffer.rows[highlighted_section.text_row];
+            let start_idx = match row_start {
+                Some((row_idx, start_idx)) if row_idx == highlighted_section.text_row => start_idx,
+                _ => {
+                    let (start_idx, pad) = if first_col > 0 {
+                        row.render_idx_at_column(first_col)
+                    } else {
+                        (0, 0)
+                    };
+                    row_start = Some((highlighted_section.text_row, start_idx));
+                    pending_pad = pad;
+                    start_idx
+                }
+            };
+            if highlighted_section.last_col_idx < start_idx {
+                continue;
+            }
+            for _ in 0..pending_pad {
+                push(
+                    " ",
+                    self.text_colour(highlight_to_color(&self.theme, hl), focused),
+                );
+            }
+            pending_pad = 0;
+            let first_idx = highlighted_section.first_col_idx.max(start_idx);
+            let row_text = &row.render;
+            let first_col_byte = char_position_to_byte_position(row_text, first_idx);
+            let last_col_byte =
+                char_position_to_byte_position(row_text, highlighted_section.last_col_idx);
+            let render_text = &row_text[first_col_byte..=last_col_byte];
+            let cursor_in_section = rcursor_idx.filter(|idx| {
+                highlighted_section.text_row == cursor_text_row
+                    && first_idx <= *idx
+                    && highlighted_section.last_col_idx >= *idx
+            });
+            if let Some(rcursor_idx) = cursor_in_section {
+                let cursor_offset = rcursor_idx - first_idx;
+                let cursor_byte_offset = char_position_to_byte_position(render_text, cursor_offset);
+                let next_byte_offset =
+                    char_position_to_byte_position(render_text, cursor_offset + 1);
+                push(
+                    &render_text[0..cursor_byte_offset],
+                    self.text_colour(highlight_to_color(&self.theme, hl), focused),
+                );
+                push(
+                    &render_text[cursor_byte_offset..next_byte_offset],
+                    self.text_colour(highlight_to_color(&self.theme, Highlight::Cursor), focused),
+                );
+                push(
+                    &render_text[next_byte_offset..],
+                    self.text_colour(highlight_to_color(&self.theme, hl), focused),
+                );
+            } else {
+                push(
+                    render_text,
+                    self.text_colour(highlight_to_color(&self.theme, hl), focused),
+                );
+            };
+        }
+        section_texts
+    }
+
+    pub fn onscreen_cursor<C>(&self, cursor: &C) -> Rect
+    where
+        C: CursorT,
+    {
+        let rcursor_x = self
+            .buffer
+            .text_cursor_to_render(cursor.text_col(), cursor.text_row());
+        let cursor_width = self.character_width;
+        let cursor_height = self.line_height;
+
+        let cursor_y = cursor.text_row() as f32;
+        let cursor_x = rcursor_x as f32 - self.viewport.first_col() as f32;
+        let x_on_screen = (cursor_width * cursor_x) + self.left_padding;
+        let y_on_screen =
+            (cursor_height * (cursor_y - self.viewport.row_offset())) + self.top_padding();
+        RectBuilder::new()
+            .bounds(vec2(cursor_width, cursor_height))
+            .top_left(self.position + vec2(x_on_screen, y_on_screen))
+            .build()
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_status_text(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        _position: Vec2,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let status_bg = if focused {
+            self.theme.status_bg
+        } else {
+            self.theme.status_unfocused_bg
+        };
+        let status_fg = if focused {
+            self.theme.status_fg
+        } else {
+            self.theme.status_unfocused_fg()
+        };
+
+        let status_rect = RectBuilder::new()
+            .top_left(vec2(
+                self.position.x(),
+                self.position.y() + self.bounds.y() - self.line_height,
+            ))
+            .bounds(vec2(self.bounds.x(), self.line_height))
+            .build();
+        {
+            let _guard = flame::start_guard("render status quad");
+            // Render status background
+            renderer.draw_quad(status_bg.rgb(), status_rect, 0.5);
+        }
+
+        {
+            let _guard = flame::start_guard("render status text");
+            let status_text = self.status_text();
+            renderer.draw_text(Text {
+                bounds,
+                position: status_rect.top_left,
+                scale: self.font_scale(),
+                z: 0.5,
+                line_spacing: Some(self.line_spacing()),
+                ..Text::plain(&status_text, status_fg.rgba())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn is_dimmed(&self, focused: bool) -> bool {
+        self.dim_unfocused && !focused
+    }
+
+    // Text in a dimmed pane fades towards the background
+    #[cfg(feature = "gui")]
+    fn text_colour(&self, colour: [f32; 4], focused: bool) -> [f32; 4] {
+        if self.is_dimmed(focused) {
+            Colour::new(colour[0], colour[1], colour[2], colour[3])
+                .mix(&self.theme.dimmed(), DIM_AMOUNT)
+                .rgba()
+        } else {
+            colour
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_dimmed_background(&self, renderer: &mut dyn Renderer, focused: bool) {
+        if self.is_dimmed(focused) {
+            let pane_rect = RectBuilder::new()
+                .top_left(self.position)
+                .bounds(self.bounds)
+                .build();
+            renderer.draw_quad(self.theme.dimmed().rgb(), pane_rect, 1.0);
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_highlight_line(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let _guard = flame::start_guard("render highlight line");
+
+        let hl_colour = if focused {
+            self.theme.line_highlight
+        } else {
+            self.theme.line_highlight_unfocused()
+        };
+        let cursor_rect = self.onscreen_cursor(&self.buffer.cursor);
+        let highlight_line_rect = RectBuilder::new()
+            .bounds(vec2(bounds.x(), self.line_height))
+            .top_left(vec2(position.x(), cursor_rect.top_left.y()))
+            .build();
+        renderer.draw_quad(hl_colour.rgb(), highlight_line_rect, 1.0);
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_selection(&self, renderer: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        let selection = match self.buffer.selection {
+            Some(selection) if !selection.is_empty() => selection,
+            _ => return Ok(()),
+        };
+        let _guard = flame::start_guard("render selection");
+
+        let first_row = i32::max(selection.start().text_row(), self.viewport.first_row());
+        let last_row = i32::min(
+            selection.end().text_row(),
+            self.viewport.first_row() + self.viewport.screen_rows(),
+        );
+        for row in first_row..=last_row {
+            let row_len = self.buffer.line_len(row).unwrap_or(0) as i32;
+            if let Some((first_col, last_col)) = selection.cols_in_row(row, row_len) {
+                let first_rect = self.onscreen_cursor(&Cursor::new(row, first_col));
+                let last_rect = self.onscreen_cursor(&Cursor::new(row, last_col));
+                // Selected newlines are shown as a single extra column
+                let newline_width = if selection.is_block() {
+                    // A block with nothing in it yet still shows where it is
+                    2.0
+                } else if row < selection.end().text_row() {
+                    self.character_width
+                } else {
+                    0.0
+                };
+                // Anything scrolled off to the left isn't drawn
+                let left = first_rect.top_left.x().max(self.text_left());
+                let width = last_rect.top_left.x() - left + newline_width;
+                if width > 0.0 {
+                    let rect = RectBuilder::new()
+                        .bounds(vec2(width, self.line_height))
+                        .top_left(vec2(left, first_rect.top_left.y()))
+                        .build();
+                    renderer.draw_quad(self.theme.selection.rgb(), rect, 1.0);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Shades whatever runs past the maximum line length
+    #[cfg(feature = "gui")]
+    fn render_long_lines(&self, renderer: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        let max_len = match self.max_line_length {
+            Some(max_len) => max_len,
+            None => return Ok(()),
+        };
+        let _guard = flame::start_guard("render long lines");
+
+        let right_edge = self.position.x() + self.bounds.x() - self.minimap_width();
+        let first_row = self.viewport.first_row();
+        for row in first_row..=first_row + self.viewport.screen_rows() {
+            let render_len = match self.buffer.render_len(row) {
+                Some(render_len) if render_len > max_len => render_len,
+                _ => continue,
+            };
+            let row_rect = self.onscreen_cursor(&Cursor::new(row, 0));
+            let x = (row_rect.top_left.x() + max_len as f32 * self.character_width)
+                .max(self.text_left());
+            let width = f32::min(
+                (render_len - max_len) as f32 * self.character_width,
+                right_edge - x,
+            );
+            if width > 0.0 {
+                let rect = RectBuilder::new()
+                    .bounds(vec2(width, self.line_height))
+                    .top_left(vec2(x, row_rect.top_left.y()))
+                    .build();
+                renderer.draw_quad(self.theme.long_line.rgb(), rect, 1.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Spaces left at the ends of lines have a background to show they're
+    // there, apart from where the cursor is, so typing a space doesn't flash
+    // it up. With all of them shown, every space and tab gets a faint mark.
+    #[cfg(feature = "gui")]
+    fn render_whitespace(
+        &self,
+        renderer: &mut dyn Renderer,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.show_whitespace == ShowWhitespace::Off {
+            return Ok(());
+        }
+        let _guard = flame::start_guard("render whitespace");
+
+        let (cursor_row, cursor_col) = self.cursor();
+        let first_row = self.viewport.first_row().max(0) as usize;
+        let rows = first_row
+            ..(first_row + self.viewport.screen_rows() as usize + 1).min(self.buffer.num_lines());
+        for row in rows.clone() {
+            let line = &self.buffer.rows[row];
+            if !line.hl.contains(&Highlight::TrailingWhitespace) {
+                continue;
+            }
+            let start = line.trailing_whitespace_start();
+            if row == cursor_row && cursor_col >= start {
+                continue;
+            }
+            let start_rect = self.onscreen_cursor(&Cursor::new(row as i32, start as i32));
+            let end_rect = self.onscreen_cursor(&Cursor::new(row as i32, line.size as i32));
+            let left = start_rect.top_left.x().max(self.text_left());
+            let width = end_rect.top_left.x() - left;
+            if width > 0.0 {
+                let rect = RectBuilder::new()
+                    .bounds(vec2(width, self.line_height))
+                    .top_left(vec2(left, start_rect.top_left.y()))
+                    .build();
+                renderer.draw_quad(self.theme.trailing_whitespace.rgb(), rect, 1.0);
+            }
+        }
+
+        if self.show_whitespace != ShowWhitespace::All {
+            return Ok(());
+        }
+        let first_col = self.viewport.first_col();
+        let marks = rows
+            .map(|row| {
+                self.buffer.rows[row]
+                    .whitespace_marks()
+                    .chars()
+                    .skip(first_col)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let padding = vec2(self.left_padding, self.top_padding());
+        renderer.draw_text(Text {
+            bounds: self.bounds - vec2(self.minimap_width(), 0.0) - padding,
+            position: self.position + padding,
+            scale: self.font_scale(),
+            z: 1.0,
+            line_spacing: Some(self.line_spacing()),
+            lifted: self.screen_position_vertical_offset(),
+            ..Text::plain(
+                &marks,
+                self.text_colour(self.theme.whitespace.rgba(), focused),
+            )
+        })
+    }
+
+    // Squiggles under whatever the language server's found, and a mark down
+    // the left edge beside each row with any, in the colour of the worst
+    #[cfg(feature = "gui")]
+    fn render_diagnostics(&self, renderer: &mut dyn Renderer) {
+        let diagnostics = self.buffer.diagnostics();
+        if diagnostics.is_empty() {
+            return;
+        }
+        let _guard = flame::start_guard("render diagnostics");
+
+        let squiggle_height = (self.line_height / 12.0).max(1.0).round();
+        let mut marks = BTreeMap::new();
+        for diagnostic in diagnostics {
+            let row = diagnostic.row as i32;
+            if !self.viewport.contains_row(row) {
+                continue;
+            }
+            let line = match self.buffer.rows.get(diagnostic.row) {
+                Some(line) => line.as_str(),
+                None => continue,
+            };
+            let cols = diagnostic.char_cols(line);
+            let colour = self.theme.diagnostic(diagnostic.severity);
+            let start_rect = self.onscreen_cursor(&Cursor::new(row, cols.start as i32));
+            let end_rect = self.onscreen_cursor(&Cursor::new(row, cols.end as i32));
+            let left = start_rect.top_left.x().max(self.text_left());
+            let width = end_rect.top_left.x() - left;
+            if width > 0.0 {
+                let rect = RectBuilder::new()
+                    .bounds(vec2(width, squiggle_height))
+                    .top_left(vec2(
+                        left,
+                        start_rect.top_left.y() + self.line_height - squiggle_height,
+                    ))
+                    .build();
+                renderer.draw_quad(colour.rgb(), rect, 0.3);
+            }
+            let worst = marks.entry(row).or_insert(diagnostic.severity);
+            *worst = (*worst).min(diagnostic.severity);
+        }
+
+        let mark_width = (self.spacing.left_padding / 4.0).max(2.0);
+        for (row, severity) in marks {
+            let row_rect = self.onscreen_cursor(&Cursor::new(row, 0));
+            let rect = RectBuilder::new()
+                .bounds(vec2(mark_width, self.line_height))
+                .top_left(vec2(self.position.x(), row_rect.top_left.y()))
+                .build();
+            renderer.draw_quad(self.theme.diagnostic(severity).rgb(), rect, 0.3);
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_follow_highlights(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(follow) = self.follow.as_ref() {
+            let _guard = flame::start_guard("render follow highlights");
+
+            for row in follow.recent_rows() {
+                if !self.viewport.contains_row(row as i32) {
+                    continue;
+                }
+                let row_rect = self.onscreen_cursor(&Cursor::new(row as i32, 0));
+                let rect = RectBuilder::new()
+                    .bounds(vec2(bounds.x(), self.line_height))
+                    .top_left(vec2(position.x(), row_rect.top_left.y()))
+                    .build();
+                renderer.draw_quad(self.theme.follow_new_line.rgb(), rect, 1.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_cursors(
+        &self,
+        renderer: &mut dyn Renderer,
+        _bounds: Vec2,
+        _position: Vec2,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let _guard = flame::start_guard("render cursors");
+
+        if self.cursor_shown(focused) {
+            let cursor_bg = if focused {
+                self.theme.cursor
+            } else {
+                self.theme.cursor_unfocused
+            };
+
+            let cursor_rect = self.onscreen_cursor(&self.buffer.cursor);
+            if cursor_rect.top_left.x() >= self.text_left() {
+                renderer.draw_quad(cursor_bg.rgb(), self.cursor_shape(cursor_rect), 0.2);
+            }
+        }
+
+        for other_cursor in self.buffer.extra_cursors() {
+            let other_cursor_rect = self.onscreen_cursor(other_cursor);
+            if other_cursor_rect.top_left.x() >= self.text_left() {
+                let shape = self.cursor_shape(other_cursor_rect);
+                renderer.draw_quad(self.theme.other_cursor.rgb(), shape, 0.2);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_text(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let _guard = flame::start_guard("render buffer text");
+
+        let padding = vec2(self.left_padding, self.top_padding());
+        let text_pos = padding + position;
+        let inner_bounds = bounds - padding;
+
+        renderer.draw_text(Text {
+            spans: self.section_texts(focused),
+            bounds: inner_bounds,
+            position: text_pos,
+            scale: self.font_scale(),
+            z: 1.0,
+            line_spacing: Some(self.line_spacing()),
+            lifted: self.screen_position_vertical_offset(),
+            ..Text::default()
+        })
+    }
+
+    // The track down the right of the text with the thumb in it, while the
+    // pane's scrolling or the thumb's held
+    #[cfg(feature = "gui")]
+    fn render_scrollbar(&self, renderer: &mut dyn Renderer) {
+        if !self.scrollbar.is_shown() {
+            return;
+        }
+        let (thumb_top, thumb_height) = match self.scrollbar_thumb() {
+            Some(thumb) => thumb,
+            None => return,
+        };
+        let width = scrollbar::width(self.ui_scale);
+        let left = self.position.x() + self.scrollbar_left();
+        let track = RectBuilder::new()
+            .top_left(vec2(left, self.position.y() + self.top_padding()))
+            .bounds(vec2(width, self.inner_height()))
+            .build();
+        renderer.draw_quad(self.theme.line_highlight.rgb(), track, SCROLLBAR_Z);
+        let thumb_colour = if self.scrollbar.grab().is_some() {
+            self.theme.popup_outline
+        } else {
+            self.theme.gutter_fg
+        };
+        let thumb = RectBuilder::new()
+            .top_left(vec2(left, self.position.y() + thumb_top))
+            .bounds(vec2(width, thumb_height))
+            .build();
+        renderer.draw_quad(thumb_colour.rgb(), thumb, SCROLLBAR_Z - 0.05);
+    }
+
+    // The buffer in miniature, each run of characters a sliver of its
+    // highlight's colour, behind which the rows on screen are picked out
+    #[cfg(feature = "gui")]
+    fn render_minimap(
+        &self,
+        renderer: &mut dyn Renderer,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let minimap = match self.minimap() {
+            Some(minimap) => minimap,
+            None => return Ok(()),
+        };
+        let _guard = flame::start_guard("render minimap");
+
+        let left = self.position.x() + self.bounds.x() - minimap.width();
+        let top = self.position.y() + self.top_padding();
+        let background = if self.is_dimmed(focused) {
+            self.theme.dimmed()
+        } else {
+            self.theme.background
+        };
+        let background_rect = RectBuilder::new()
+            .top_left(vec2(left, top))
+            .bounds(vec2(minimap.width(), self.inner_height()))
+            .build();
+        renderer.draw_quad(background.rgb(), background_rect, MINIMAP_Z);
+
+        let rows = self.minimap_rows();
+        let viewport_colour = if focused {
+            self.theme.line_highlight
+        } else {
+            self.theme.line_highlight_unfocused()
+        };
+        let viewport_top = (self.viewport.row_offset() - rows.start as f32).max(0.0);
+        let viewport_rect = RectBuilder::new()
+            .top_left(vec2(left, top + viewport_top * minimap.row_height()))
+            .bounds(vec2(
+                minimap.width(),
+                self.viewport.screen_rows() as f32 * minimap.row_height(),
+            ))
+            .build();
+        renderer.draw_quad(viewport_colour.rgb(), viewport_rect, MINIMAP_Z - 0.05);
+
+        let first_idx = self
+            .highlighted_sections
+            .partition_point(|section| section.text_row < rows.start);
+        for section in self.highlighted_sections[first_idx..].iter() {
+            if section.text_row >= rows.end {
+                break;
+            }
+            let colour = self.theme.highlight(section.highlight);
+            let colour = if self.is_dimmed(focused) {
+                colour.mix(&self.theme.dimmed(), DIM_AMOUNT)
+            } else {
+                colour
+            };
+            let y = top + (section.text_row - rows.start) as f32 * minimap.row_height();
+            let chars = self.buffer.rows[section.text_row]
+                .render
+                .chars()
+                .enumerate()
+                .skip(section.first_col_idx)
+                .take(section.last_col_idx + 1 - section.first_col_idx)
+                .take_while(|(col, _)| *col < minimap.columns());
+            // Each run of non-blank characters is one sliver
+            let mut run_start = None;
+            for (col, c) in chars.chain(std::iter::once((section.last_col_idx + 1, ' '))) {
+                match (run_start, c.is_whitespace()) {
+                    (None, false) => run_start = Some(col),
+                    (Some(start), true) => {
+                        let col_end = col.min(minimap.columns());
+                        let rect = RectBuilder::new()
+                            .top_left(vec2(left + start as f32 * minimap.char_width(), y))
+                            .bounds(vec2(
+                                (col_end - start) as f32 * minimap.char_width(),
+                                minimap.row_height() * 0.75,
+                            ))
+                            .build();
+                        renderer.draw_quad(colour.rgb(), rect, MINIMAP_Z - 0.1);
+                        run_start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_gutter(
+        &self,
+        renderer: &mut dyn Renderer,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.gutter.mode() == LineNumbers::Off {
+            return Ok(());
+        }
+        let _guard = flame::start_guard("render gutter");
+
+        let gutter_pos = self.position + vec2(self.spacing.left_padding / 2.0, self.top_padding());
+        renderer.draw_text(Text {
+            bounds: vec2(self.left_padding, self.bounds.y()) - vec2(0.0, self.top_padding()),
+            position: gutter_pos,
+            scale: self.font_scale(),
+            z: 1.0,
+            line_spacing: Some(self.line_spacing()),
+            lifted: self.screen_position_vertical_offset(),
+            ..Text::plain(
+                self.gutter.text(),
+                self.text_colour(self.theme.gutter_fg.rgba(), focused),
+            )
+        })
+    }
+
+    // Each mark's letter beside its row, the first in order if there's more
+    // than one on a row
+    #[cfg(feature = "gui")]
+    fn render_marks(
+        &self,
+        renderer: &mut dyn Renderer,
+        focused: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.marks_width() == 0 {
+            return Ok(());
+        }
+        let _guard = flame::start_guard("render marks");
+
+        let first_row = self.viewport.first_row().max(0) as usize;
+        let last_row = first_row + self.viewport.screen_rows().max(0) as usize + 1;
+        let mut letters = vec![' '; last_row - first_row];
+        for (name, at) in self.buffer.marks().settable() {
+            let row = at.text_row().max(0) as usize;
+            if (first_row..last_row).contains(&row) && letters[row - first_row] == ' ' {
+                letters[row - first_row] = name;
+            }
+        }
+        let text = letters
+            .iter()
+            .map(|letter| format!("{}\n", letter))
+            .collect::<String>();
+        let gutter_width = self.gutter.width(self.buffer.num_lines()) as f32 * self.character_width;
+        let marks_pos = self.position
+            + vec2(
+                self.spacing.left_padding / 2.0 + gutter_width,
+                self.top_padding(),
+            );
+        renderer.draw_text(Text {
+            bounds: vec2(self.left_padding, self.bounds.y()) - vec2(0.0, self.top_padding()),
+            position: marks_pos,
+            scale: self.font_scale(),
+            z: 1.0,
+            line_spacing: Some(self.line_spacing()),
+            lifted: self.screen_position_vertical_offset(),
+            ..Text::plain(
+                &text,
+                self.text_colour(self.theme.gutter_fg.rgba(), focused),
+            )
+        })
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_lines(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+    ) -> Result<(), Box<dyn Error>> {
+        let _guard = flame::start_guard("render lines");
+
+        let first_col = self.viewport.first_col();
+        for line in self.guide_cols().iter().filter(|line| **line >= first_col) {
+            let x_in_bounds = (line - first_col) as f32 * self.character_width;
+            if x_in_bounds < bounds.x() {
+                let x_on_screen = position.x() + x_in_bounds;
+                let rect = RectBuilder::new()
+                    .bounds(vec2(1.0, bounds.y()))
+                    .top_left(vec2(x_on_screen, 0.0))
+                    .build();
+                renderer.draw_quad(self.theme.line_col.rgb(), rect, 0.2);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_search(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(search) = self.search.as_ref() {
+            let _guard = flame::start_guard("render top left search prompt");
+
+            let search_text = search.as_string();
+            renderer.draw_text(Text {
+                bounds,
+                position: position + vec2(0.0, self.spacing.top_padding),
+                scale: self.font_scale(),
+                z: 0.5,
+                line_spacing: Some(self.line_spacing()),
+                ..Text::plain(&search_text, [0.7, 0.6, 0.5, 1.0])
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn render_prompt(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(top_left_text) = self.prompt.as_ref().map(|prompt| prompt.display_text()) {
+            let _guard = flame::start_guard("render top left prompt text");
+
+            let text_position: Vec2 = position + vec2(0.0, self.top_padding());
+            renderer.draw_text(Text {
+                bounds,
+                position: text_position,
+                scale: self.font_scale(),
+                z: 0.5,
+                line_spacing: Some(self.line_spacing()),
+                ..Text::plain(top_left_text, [0.7, 0.6, 0.5, 1.0])
+            })?;
+
+            self.render_palette(
+                renderer,
+                bounds,
+                text_position + vec2(0.0, self.line_height),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // The command palette's best matches, listed under its prompt with the
+    // keys they're bound to
+    #[cfg(feature = "gui")]
+    fn render_palette(
+        &self,
+        renderer: &mut dyn Renderer,
+        bounds: Vec2,
+        position: Vec2,
+    ) -> Result<(), Box<dyn Error>> {
+        let (palette, query) = match (&self.palette, &self.prompt) {
+            (Some(palette), Some(prompt)) => (palette, prompt.input()),
+            _ => return Ok(()),
+        };
+        let matches = palette.matches(query);
+        let shown = &matches[..matches.len().min(PALETTE_ROWS)];
+        if shown.is_empty() {
+            return Ok(());
+        }
+        let _guard = flame::start_guard("render command palette");
+
+        let list_rect = RectBuilder::new()
+            .top_left(vec2(self.position.x(), position.y()))
+            .bounds(vec2(self.bounds.x(), shown.len() as f32 * self.line_height))
+            .build();
+        renderer.draw_quad(self.theme.status_bg.rgb(), list_rect, 0.55);
+        let selected_rect = RectBuilder::new()
+            .top_left(vec2(
+                self.position.x(),
+                position.y() + palette.selected(query) as f32 * self.line_height,
+            ))
+            .bounds(vec2(self.bounds.x(), self.line_height))
+            .build();
+        renderer.draw_quad(self.theme.line_highlight.rgb(), selected_rect, 0.54);
+
+        let text = shown
+            .iter()
+            .map(|command| {
+                format!(
+                    "{:<28} {}",
+                    command.name,
+                    command.keys.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        renderer.draw_text(Text {
+            bounds,
+            position,
+            scale: self.font_scale(),
+            z: 0.5,
+            line_spacing: Some(self.line_spacing()),
+            ..Text::plain(&text, self.theme.status_fg.rgba())
+        })
+    }
+
+    // The words on offer, in a popup under the word being finished, or over
+    // it if there isn't room below
+    #[cfg(feature = "gui")]
+    fn render_completion(&self, renderer: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        let completion = match &self.completion {
+            Some(completion) => completion,
+            None => return Ok(()),
+        };
+        let _guard = flame::start_guard("render completion");
+
+        let (first, shown) = completion.shown();
+        let (start_col, _) = self.buffer.word_before_cursor();
+        let word_rect = self.onscreen_cursor(&Cursor::new(
+            self.buffer.cursor.text_row(),
+            start_col as i32,
+        ));
+        let widest = shown
+            .iter()
+            .map(|word| word.chars().count())
+            .max()
+            .unwrap_or(0);
+        let size = vec2(
+            (widest + 2) as f32 * self.character_width,
+            shown.len() as f32 * self.line_height,
+        );
+        let bottom = self.position.y() + self.bounds.y() - self.bottom_padding();
+        let below = word_rect.top_left.y() + self.line_height;
+        let top = if below + size.y() > bottom {
+            word_rect.top_left.y() - size.y()
+        } else {
+            below
+        };
+        let top_left = vec2(word_rect.top_left.x() - self.character_width, top);
+
+        let popup_rect = RectBuilder::new().top_left(top_left).bounds(size).build();
+        renderer.draw_quad(self.theme.popup_bg.rgb(), popup_rect, 0.45);
+        let selected_rect = RectBuilder::new()
+            .top_left(
+                top_left
+                    + vec2(
+                        0.0,
+                        (completion.selected() - first) as f32 * self.line_height,
+                    ),
+            )
+            .bounds(vec2(size.x(), self.line_height))
+            .build();
+        renderer.draw_quad(self.theme.selection.rgb(), selected_rect, 0.44);
+
+        let text = shown.join("\n");
+        renderer.draw_text(Text {
+            bounds: size,
+            position: top_left + vec2(self.character_width, 0.0),
+            scale: self.font_scale(),
+            z: 0.4,
+            line_spacing: Some(self.line_spacing()),
+            ..Text::plain(&text, self.theme.popup_fg.rgba())
+        })
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn render(&self, renderer: &mut dyn Renderer, focused: bool) -> Result<(), Box<dyn Error>> {
+        let padded_position = self.position + vec2(self.left_padding, 0.0);
+        let new_bounds = self.bounds - vec2(self.left_padding, 0.0);
+        let text_bounds = self.bounds - vec2(self.minimap_width(), 0.0);
+
+        self.render_dimmed_background(renderer, focused);
+        self.render_highlight_line(renderer, text_bounds, self.position, focused)?;
+        self.render_follow_highlights(renderer, text_bounds, self.position)?;
+        self.render_long_lines(renderer)?;
+        self.render_whitespace(renderer, focused)?;
+        self.render_selection(renderer)?;
+        self.render_diagnostics(renderer);
+        self.render_text(renderer, text_bounds, self.position, focused)?;
+        self.render_minimap(renderer, focused)?;
+        self.render_scrollbar(renderer);
+        self.render_gutter(renderer, focused)?;
+        self.render_marks(renderer, focused)?;
+        self.render_cursors(renderer, new_bounds, padded_position, focused)?;
+        self.render_lines(renderer, new_bounds, padded_position)?;
+        self.render_completion(renderer)?;
+        self.render_prompt(renderer, new_bounds, padded_position)?;
+        self.render_search(renderer, new_bounds, padded_position)?;
+        self.render_status_text(renderer, self.bounds, self.position, focused)?;
+
+        Ok(())
+    }
+
+    pub fn update_gui(&mut self, action: GuiAction) {
+        use GuiAction::*;
+
+        match action {
+            UpdateSize(bounds, position) => self.update_size(bounds, position),
+            SetFontSize(font_size) => self.set_font_size(font_size),
+            SetUiScale(dpi) => self.set_ui_scale(dpi),
+            SetLineHeight(line_height) => self.set_line_height(line_height),
+            SetCharacterWidth(character_width) => self.set_character_width(character_width),
+            ToggleMinimap => self.toggle_minimap(),
+            SetFont(_) => {}
+            DumpFlameGraph => {}
+            ToggleProfiler => {}
+            DecFontSize => {}
+            IncFontSize => {}
+            Quit => {}
+            ForceQuit => {}
+            PrintInfo => {}
+        }
+    }
+
+    pub fn set_ui_scale(&mut self, dpi: f32) {
+        self.ui_scale = dpi;
+    }
+
+    pub fn set_line_height(&mut self, height: f32) {
+        self.font_line_height = height;
+        self.line_height = height * self.spacing.line_spacing;
+        self.update_font_metrics();
+    }
+
+    pub fn spacing(&self) -> Spacing {
+        self.spacing
+    }
+
+    pub fn set_spacing(&mut self, spacing: Spacing) {
+        self.spacing = spacing;
+        self.update_gutter();
+        self.set_line_height(self.font_line_height);
+    }
+
+    #[cfg(feature = "gui")]
+    fn line_spacing(&self) -> LineSpacing {
+        LineSpacing::new(self.font_line_height, self.spacing.line_spacing)
+    }
+
+    pub fn set_character_width(&mut self, width: f32) {
+        self.character_width = width;
+        self.update_font_metrics();
+    }
+
+    fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size;
+        self.update_font_metrics();
+    }
+
+    fn inner_width(&self) -> f32 {
+        self.bounds.x() - self.left_padding - self.minimap_width()
+    }
+
+    fn inner_height(&self) -> f32 {
+        self.bounds.y() - self.bottom_padding() - self.top_padding()
+    }
+
+    // How many columns of text fit across
+    fn screen_cols(&self) -> usize {
+        if self.character_width <= 0.0 {
+            return 0;
+        }
+        (self.inner_width() / self.character_width).max(0.0).floor() as usize
+    }
+
+    // Where the text starts, past the gutter
+    #[cfg(feature = "gui")]
+    fn text_left(&self) -> f32 {
+        self.position.x() + self.left_padding
+    }
+
+    fn minimap(&self) -> Option<Minimap> {
+        if self.show_minimap {
+            Some(Minimap::new(self.ui_scale))
+        } else {
+            None
+        }
+    }
+
+    fn minimap_width(&self) -> f32 {
+        self.minimap().map(|minimap| minimap.width()).unwrap_or(0.0)
+    }
+
+    // The rows in the minimap, none if it isn't shown
+    fn minimap_rows(&self) -> Range<usize> {
+        match self.minimap() {
+            Some(minimap) => minimap.rows(
+                self.inner_height(),
+                self.buffer.num_lines(),
+                self.scroll_fraction(),
+            ),
+            None => 0..0,
+        }
+    }
+
+    // The row under location if it's over the minimap
+    fn minimap_row_at(&self, location: Vec2) -> Option<usize> {
+        let minimap = self.minimap()?;
+        let top = self.top_padding();
+        if location.x() < self.bounds.x() - minimap.width()
+            || location.x() >= self.bounds.x()
+            || location.y() < top
+            || location.y() >= top + self.inner_height()
+        {
+            return None;
+        }
+        minimap.row_at(location.y() - top, self.minimap_rows())
+    }
+
+    // The thumb's top and height, down from the top of the pane, if the
+    // buffer's long enough to scroll
+    fn scrollbar_thumb(&self) -> Option<(f32, f32)> {
+        let (top, height) = scrollbar::thumb(
+            self.inner_height(),
+            self.viewport.screen_rows().max(0) as usize,
+            self.buffer.num_lines(),
+            self.scroll_fraction(),
+            self.ui_scale,
+        )?;
+        Some((self.top_padding() + top, height))
+    }
+
+    // Where the scrollbar starts across the pane, just left of the minimap
+    fn scrollbar_left(&self) -> f32 {
+        self.bounds.x() - self.minimap_width() - scrollbar::width(self.ui_scale)
+    }
+
+    // Whether location's on the scrollbar, which can only be clicked while it's up
+    fn scrollbar_contains(&self, location: Vec2) -> bool {
+        let top = self.top_padding();
+        self.scrollbar.is_shown()
+            && self.scrollbar_thumb().is_some()
+            && location.x() >= self.scrollbar_left()
+            && location.x() < self.bounds.x() - self.minimap_width()
+            && location.y() >= top
+            && location.y() < top + self.inner_height()
+    }
+
+    // Clicking the thumb picks it up where it was clicked. Clicking the
+    // track either side jumps there, with the thumb picked up by its middle.
+    fn click_scrollbar(&mut self, y: f32) {
+        let (top, height) = match self.scrollbar_thumb() {
+            Some(thumb) => thumb,
+            None => return,
+        };
+        let grab = if y >= top && y < top + height {
+            y - top
+        } else {
+            height / 2.0
+        };
+        self.scrollbar.start_drag(grab);
+        self.drag_scrollbar_to(y - grab);
+    }
+
+    // Scrolls so the thumb's top is at top, down from the top of the pane
+    fn drag_scrollbar_to(&mut self, top: f32) {
+        if let Some((_, height)) = self.scrollbar_thumb() {
+            let track_top = top - self.top_padding();
+            self.set_scroll_fraction(scrollbar::fraction_at(
+                track_top,
+                self.inner_height(),
+                height,
+            ));
+        }
+    }
+
+    // Scrolls so row is in the middle of the screen, if it's that far in
+    fn scroll_to_minimap_row(&mut self, row: usize) {
+        let num_lines = self.buffer.num_lines();
+        let screen_rows = self.viewport.screen_rows().max(0) as usize;
+        let max_offset = num_lines.saturating_sub(screen_rows);
+        let row_offset = row.saturating_sub(screen_rows / 2).min(max_offset);
+        self.viewport.set_row_offset(row_offset as f32, num_lines);
+        if !self.is_cursor_onscreen() {
+            self.move_cursor_onscreen();
+        }
+        self.update_status_line();
+        self.update_visible_sections();
+    }
+
+    // How many columns and rows of text would fit if the pane were bounds
+    #[cfg(feature = "gui")]
+    pub fn text_size_in(&self, bounds: Vec2) -> (usize, usize) {
+        if self.character_width <= 0.0 || self.line_height <= 0.0 {
+            return (0, 0);
+        }
+        let width = bounds.x() - self.left_padding - self.minimap_width();
+        let height = bounds.y() - self.bottom_padding() - self.top_padding();
+        (
+            (width / self.character_width).max(0.0).floor() as usize,
+            (height / self.line_height).max(0.0).floor() as usize,
+        )
+    }
+
+    #[cfg(feature = "gui")]
+    fn font_scale(&self) -> f32 {
+        self.ui_scale * self.font_size
+    }
+
+    fn top_padding(&self) -> f32 {
+        if self.top_prompt_visible() {
+            self.spacing.top_padding + self.line_height // if search is on
+        } else {
+            self.spacing.top_padding
+        }
+    }
+
+    fn bottom_padding(&self) -> f32 {
+        self.line_height // status line
+    }
+
+    #[cfg(feature = "gui")]
+    fn screen_position_vertical_offset(&self) -> f32 {
+        self.viewport.row_offset().fract() * self.line_height
+    }
+
+    fn cursor_from_mouse_position(&self, mouse: Vec2) -> (i32, i32) {
+        let row_on_screen = ((mouse.y() - self.top_padding()) / self.line_height
+            + self.viewport.row_offset())
+        .floor() as i32;
+        let col_on_screen = ((mouse.x() - self.left_padding) / self.character_width).floor() as i32
+            + self.viewport.first_col() as i32;
+        (col_on_screen, row_on_screen)
+    }
+
+    fn move_cursor_to_mouse_position(&mut self, mouse: Vec2) {
+        self.buffer.seal_undo_step();
+        let cursor = self.cursor_from_mouse_position(mouse);
+        let clicked_line = i32::min((self.buffer.num_lines() as i32) - 1, cursor.1);
+        let clicked_line_length = self.buffer.line_len(clicked_line).unwrap_or(0) as i32;
+        let clicked_line_x = if self.buffer.virtual_edit() {
+            i32::max(0, cursor.0)
+        } else {
+            i32::min(clicked_line_length, cursor.0)
+        };
+        let move_y = clicked_line - self.buffer.cursor.text_row();
+        let move_x = clicked_line_x - self.buffer.cursor.text_col();
+        self.buffer.cursor.change(|cursor| {
+            cursor.text_row += move_y;
+            cursor.text_col += move_x;
+        });
+        self.update_cursor();
+    }
+
+    // What to show in a tooltip when the mouse rests over location: what the
+    // language server said about the text there, or else the whole of a
+    // line that's too wide for the pane
+    #[cfg(feature = "gui")]
+    pub fn hover_info(&self, location: Vec2) -> Option<String> {
+        if self.character_width <= 0.0 || self.line_height <= 0.0 {
+            return None;
+        }
+        let (col_on_screen, row_on_screen) = self.cursor_from_mouse_position(location);
+        if row_on_screen < 0 || col_on_screen < 0 {
+            return None;
+        }
+        let row = self.buffer.rows.get(row_on_screen as usize)?;
+        let col = row.render_cursor_to_text(col_on_screen as usize);
+        let messages = self
+            .buffer
+            .diagnostics()
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.row == row_on_screen as usize
+                    && diagnostic.char_cols(row.as_str()).contains(&col)
+            })
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        if !messages.is_empty() {
+            return Some(messages.join("\n"));
+        }
+        let visible_cols = self.screen_cols();
+        let line = row.rendered_str().trim_end();
+        if line.chars().count() > visible_cols {
+            Some(line.to_string())
+        } else {
+            None
+        }
+    }
+
+    // Clicks on the status line, the scrollbar or the minimap are for them,
+    // not the text
+    fn click_outside_text(&mut self, location: Vec2) -> bool {
+        if let Some(target) = self.status_target_at(location) {
+            self.click_status(target);
+            true
+        } else if self.scrollbar_contains(location) {
+            self.click_scrollbar(location.y());
+            true
+        } else if let Some(row) = self.minimap_row_at(location) {
+            self.scroll_to_minimap_row(row);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn mouse_click(&mut self, location: Vec2) {
+        println!("mouse click: {:?}", location);
+        if self.click_outside_text(location) {
+            return;
+        }
+        self.buffer.clear_selection();
+        self.buffer.clear_extra_cursors();
+        self.move_cursor_to_mouse_position(location);
+        self.drag = Some(location);
+    }
+
+    // Double and triple clicks select what's around location all at once,
+    // so there's nothing to drag out afterwards
+    fn mouse_select_at(&mut self, location: Vec2, select: fn(&mut Buffer<'a>) -> bool) {
+        if self.click_outside_text(location) {
+            return;
+        }
+        self.buffer.clear_extra_cursors();
+        self.move_cursor_to_mouse_position(location);
+        self.drag = None;
+        if select(&mut self.buffer) {
+            self.update_cursor();
+        }
+    }
+
+    // Extends the selection (starting one from the cursor if need be) to location
+    fn mouse_shift_click(&mut self, location: Vec2) {
+        if self.buffer.selection.is_none() {
+            self.buffer.start_selection();
+        }
+        self.move_cursor_to_mouse_position(location);
+        self.drag = Some(location);
+    }
+
+    fn mouse_alt_click(&mut self, location: Vec2) {
+        self.mouse_click(location);
+        if self.drag.is_some() {
+            self.buffer.start_block_selection();
+        }
+    }
+
+    fn mouse_drag(&mut self, location: Vec2) {
+        if let Some(grab) = self.scrollbar.grab() {
+            self.drag_scrollbar_to(location.y() - grab);
+            return;
+        }
+        if self.drag.is_none() {
+            return;
+        }
+        self.drag = Some(location);
+        if self.buffer.selection.is_none() {
+            self.buffer.start_selection();
+        }
+        self.drag_cursor_to(location);
+    }
+
+    fn mouse_release(&mut self) {
+        self.drag = None;
+        self.scrollbar.end_drag();
+        if self.buffer.selection.map(|s| s.is_empty()).unwrap_or(false) {
+            self.buffer.clear_selection();
+        }
+    }
+
+    // Moves the cursor to the text nearest location, which may be off the pane
+    fn drag_cursor_to(&mut self, location: Vec2) {
+        if self.line_height <= 0.0 || self.character_width <= 0.0 {
+            return;
+        }
+        let top = self.top_padding();
+        let bottom = top + self.inner_height() - self.line_height;
+        let text_location = vec2(
+            location.x().max(self.left_padding),
+            location.y().max(top).min(bottom.max(top)),
+        );
+        self.move_cursor_to_mouse_position(text_location);
+    }
+
+    // How many lines per second to scroll while dragging past the top or bottom
+    fn drag_scroll_speed(&self, location: Vec2) -> f32 {
+        let top = self.top_padding();
+        let bottom = top + self.inner_height();
+        let past_edge = if location.y() < top {
+            location.y() - top
+        } else if location.y() > bottom {
+            location.y() - bottom
+        } else {
+            0.0
+        };
+        past_edge / self.line_height * DRAG_SCROLL_SPEED
+    }
+
+    fn update_drag(&mut self, duration: Duration) {
+        if let Some(location) = self.drag {
+            if self.line_height <= 0.0 || self.buffer.selection.is_none() {
+                return;
+            }
+            let speed = self.drag_scroll_speed(location);
+            if speed != 0.0 {
+                let lines = speed * duration.as_secs_f32();
+                self.viewport.scroll_by(lines, 0.0, self.buffer.num_lines());
+                self.drag_cursor_to(location);
+            }
+        }
+    }
+
+    fn is_cursor_onscreen(&self) -> bool {
+        self.viewport.contains_row(self.buffer.cursor.text_row())
+    }
+
+    fn update_screen_rows(&mut self) {
+        self.viewport
+            .set_screen_rows((self.inner_height() / self.line_height).floor() as i32);
+    }
+
+    fn update_font_metrics(&mut self) {
+        self.update_screen_rows();
+        self.scroll();
+    }
+
+    fn scroll_window_vertically(&mut self, amount: f32) {
+        self.viewport
+            .scroll_by(amount, 0.0, self.buffer.num_lines());
+        if !self.is_cursor_onscreen() {
+            self.move_cursor_onscreen();
+        }
+    }
+
+    fn scroll_window_horizontally(&mut self, amount: f32) {
+        self.viewport
+            .scroll_by(0.0, amount, self.buffer.num_lines());
+    }
+
+    pub fn update_dt(&mut self, duration: Duration) {
+        if self.power_mode == PowerMode::Normal
+            && self.cursor_blink.is_some()
+            && self.cursor_animation.add_duration(duration)
+        {
+            self.damaged = true;
+        }
+        // New rows fade out as the file's followed
+        let fading = self
+            .follow
+            .as_ref()
+            .is_some_and(|follow| follow.recent_rows().next().is_some());
+        if fading || self.is_busy() {
+            self.damaged = true;
+        }
+        if self.scrollbar.update(duration, self.viewport.row_offset()) {
+            self.damaged = true;
+        }
+        self.update_follow(duration);
+        self.update_watch(duration);
+        self.update_held_open();
+        self.update_loading();
+        self.update_autosave(duration);
+        if self.focused {
+            self.buffer.add_time_focused(duration);
+        }
+        self.update_drag(duration);
+        self.update_gutter();
+        self.update_visible_sections();
+    }
+
+    pub fn line_numbers(&self) -> LineNumbers {
+        self.gutter.mode()
+    }
+
+    pub fn dim_unfocused(&self) -> bool {
+        self.dim_unfocused
+    }
+
+    pub fn set_dim_unfocused(&mut self, dim_unfocused: bool) {
+        self.dim_unfocused = dim_unfocused;
+    }
+
+    pub fn show_minimap(&self) -> bool {
+        self.show_minimap
+    }
+
+    // Where this pane is in its file, if it has one, to save in the session
+    pub fn session(&self) -> Option<PaneSession> {
+        let filename = self.buffer.filename.clone()?;
+        Some(PaneSession {
+            filename,
+            cursor_row: self.buffer.cursor.text_row(),
+            cursor_col: self.buffer.cursor.text_col(),
+            row_offset: self.viewport.row_offset(),
+            col_offset: self.viewport.col_offset(),
+            marks: self
+                .buffer
+                .marks()
+                .settable()
+                .filter(|(name, _)| !marks::is_global(*name))
+                .map(|(name, at)| (name, (at.text_row(), at.text_col())))
+                .collect(),
+        })
+    }
+
+    // Puts the cursor and scrolling back the way they were saved
+    pub fn restore_session(&mut self, saved: &PaneSession) {
+        self.buffer
+            .cursor
+            .move_to(saved.cursor_row, saved.cursor_col);
+        self.buffer.check_cursor();
+        for (name, (row, col)) in saved.marks.iter() {
+            self.buffer.set_mark_at(*name, Cursor::new(*row, *col));
+        }
+        self.viewport
+            .set_row_offset(saved.row_offset, self.buffer.num_lines());
+        self.viewport.set_col_offset(saved.col_offset);
+        if !self.is_cursor_onscreen() {
+            self.scroll();
+        }
+        self.update_status_line();
+        self.update_visible_sections();
+    }
+
+    pub fn set_show_minimap(&mut self, show_minimap: bool) {
+        self.show_minimap = show_minimap;
+        self.update_visible_sections();
+    }
+
+    fn toggle_minimap(&mut self) {
+        self.set_show_minimap(!self.show_minimap);
+    }
+
+    pub fn set_theme(&mut self, theme: Rc<Theme>) {
+        self.theme = theme;
+    }
+
+    // The cursor stays solid while saving power, so nothing needs redrawing
+    #[cfg(feature = "gui")]
+    pub fn set_power_mode(&mut self, power_mode: PowerMode) {
+        if self.power_mode != power_mode {
+            self.power_mode = power_mode;
+            self.damaged = true;
+            self.cursor_animation.cancel();
+            self.status_line.power_mode = match power_mode {
+                PowerMode::Normal => String::new(),
+                PowerMode::Saving => power_mode.to_string(),
+            };
+        }
+    }
+
+    // Whether the pane needs drawing again, which stops being so once it has
+    pub fn is_damaged(&self) -> bool {
+        self.damaged
+    }
+
+    pub fn clear_damage(&mut self) {
+        self.damaged = false;
+    }
+
+    // Work that the pane does a bit of every frame, which slowing down would hold up
+    pub fn is_busy(&self) -> bool {
+        self.buffer.is_loading() || self.drag.is_some()
+    }
+
+    pub fn status_format(&self) -> &[StatusTarget] {
+        &self.status_line.format
+    }
+
+    pub fn set_status_format(&mut self, format: &[StatusTarget]) {
+        self.status_line.format = format.to_vec();
+    }
+
+    pub fn max_line_length(&self) -> Option<usize> {
+        self.max_line_length
+    }
+
+    pub fn set_max_line_length(&mut self, max_line_length: Option<usize>) {
+        self.max_line_length = max_line_length;
+        self.update_status_line();
+    }
+
+    pub fn complete_after(&self) -> Option<usize> {
+        self.complete_after
+    }
+
+    pub fn set_complete_after(&mut self, complete_after: Option<usize>) {
+        self.complete_after = complete_after;
+    }
+
+    pub fn guides(&self) -> &Guides {
+        &self.guides
+    }
+
+    pub fn set_guides(&mut self, guides: Guides) {
+        self.guides = guides;
+    }
+
+    // Where the lines go down this pane, for its buffer's filetype
+    #[cfg(feature = "gui")]
+    fn guide_cols(&self) -> &[usize] {
+        self.guides.for_filetype(&self.buffer.get_filetype())
+    }
+
+    pub fn show_whitespace(&self) -> ShowWhitespace {
+        self.show_whitespace
+    }
+
+    pub fn set_show_whitespace(&mut self, show_whitespace: ShowWhitespace) {
+        self.show_whitespace = show_whitespace;
+    }
+
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    pub fn set_cursor_style(&mut self, cursor_style: CursorStyle) {
+        self.cursor_style = cursor_style;
+    }
+
+    pub fn cursor_blink(&self) -> Option<Duration> {
+        self.cursor_blink
+    }
+
+    // A cursor that doesn't blink stays shown
+    pub fn set_cursor_blink(&mut self, cursor_blink: Option<Duration>) {
+        if cursor_blink != self.cursor_blink {
+            self.cursor_blink = cursor_blink;
+            if let Some(interval) = cursor_blink {
+                self.cursor_animation = Animation::new(interval);
+            }
+            self.cursor_animation.cancel();
+        }
+    }
+
+    // Whether the cursor's to be drawn this frame, as only the focused
+    // pane's blinks
+    #[cfg(feature = "gui")]
+    fn cursor_shown(&self, focused: bool) -> bool {
+        !focused || self.cursor_animation.state == AnimationState::Show
+    }
+
+    // The cursor's shape where rect is the character it's on
+    #[cfg(feature = "gui")]
+    fn cursor_shape(&self, rect: Rect) -> Rect {
+        let thickness = (2.0 * self.ui_scale).max(1.0);
+        match self.cursor_style {
+            CursorStyle::Block => rect,
+            CursorStyle::Bar => RectBuilder::new()
+                .top_left(rect.top_left)
+                .bounds(vec2(thickness, rect.bounds.y()))
+                .build(),
+            CursorStyle::Underline => RectBuilder::new()
+                .top_left(rect.top_left + vec2(0.0, rect.bounds.y() - thickness))
+                .bounds(vec2(rect.bounds.x(), thickness))
+                .build(),
+        }
+    }
+
+    pub fn snippets(&self) -> Rc<Vec<Snippet>> {
+        self.snippets.clone()
+    }
+
+    pub fn set_snippets(&mut self, snippets: Rc<Vec<Snippet>>) {
+        self.snippets = snippets;
+    }
+
+    pub fn set_line_numbers(&mut self, line_numbers: LineNumbers) {
+        self.gutter.set_mode(line_numbers);
+        self.update_gutter();
+    }
+
+    // A column for the letters of any marks goes after the line numbers
+    fn marks_width(&self) -> usize {
+        if self.buffer.marks().settable().next().is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    // Cheap to call every frame, the gutter only rebuilds when it needs to
+    fn update_gutter(&mut self) {
+        let num_lines = self.buffer.num_lines();
+        self.gutter.update(
+            self.viewport.first_row().max(0) as usize,
+            self.viewport.screen_rows().max(0) as usize,
+            self.buffer.cursor.text_row().max(0) as usize,
+            num_lines,
+        );
+        let columns = self.gutter.width(num_lines) + self.marks_width();
+        self.left_padding = self.spacing.left_padding + columns as f32 * self.character_width;
+    }
+
+    fn toggle_follow(&mut self) {
+        if self.follow.is_some() {
+            self.follow = None;
+        } else if self.buffer.filename.is_some() {
+            self.follow = Some(Follow::default());
+        }
+        self.update_status_line();
+    }
+
+    fn update_follow(&mut self, duration: Duration) {
+        let poll = match self.follow.as_mut() {
+            Some(follow) => follow.tick(duration),
+            None => return,
+        };
+        if !poll {
+            return;
+        }
+
+        // Only stick to the bottom if the end of the file was already in view
+        let last_row = self.buffer.num_lines() as i32 - 1;
+        let pinned = last_row < 0 || self.viewport.contains_row(last_row);
+        match self.buffer.read_appended() {
+            Ok(0) => {}
+            Ok(added) => {
+                let num_lines = self.buffer.num_lines();
+                if let Some(follow) = self.follow.as_mut() {
+                    follow.mark_new_rows(num_lines - added, added);
+                }
+                if pinned {
+                    self.move_cursor(|cursor| {
+                        cursor.text_row = num_lines as i32 - 1;
+                        cursor.text_col = 0;
+                    });
+                }
+                self.mark_buffer_changed();
+                self.update_cursor();
+            }
+            Err(err) => println!("Error following file: {}", err),
+        }
+    }
+
+    // Following a file already keeps up with it changing
+    fn update_watch(&mut self, duration: Duration) {
+        if !self.watch.tick(duration)
+            || self.follow.is_some()
+            || self.prompt.is_some()
+            || self.buffer.is_loading()
+        {
+            return;
+        }
+        if let Some(stamp) = self.buffer.disk_change() {
+            if self.watch.notice(stamp) {
+                self.start_prompt(Input::new(
+                    &format!("{} changed on disk, reload it? (y/n)", self.buffer.name()),
+                    PromptAction::ReloadChanged,
+                    true,
+                ));
+            }
+        }
+    }
+
+    fn reload_changed(&mut self, answer: &str) {
+        if answer.starts_with('y') || answer.starts_with('Y') {
+            if let Err(err) = self.buffer.reload() {
+                println!("Error reloading file: {}", err);
+            }
+            self.mark_buffer_changed();
+            self.update_cursor();
+            self.update_status_line();
+        } else {
+            self.buffer.keep_in_memory();
+        }
+    }
+
+    // Asks how to open a file that was too big, or too binary, to just open
+    fn update_held_open(&mut self) {
+        if self.prompt.is_some() {
+            return;
+        }
+        let why = match self.buffer.held_open() {
+            Some(OpenWarning::Large(len)) => {
+                format!("{} is {}MB", self.buffer.name(), len / (1024 * 1024))
+            }
+            Some(OpenWarning::Binary) => format!("{} looks binary", self.buffer.name()),
+            None => return,
+        };
+        self.start_prompt(Input::new(
+            &format!("{}, open it (r)ead-only, as (h)ex, or (c)ancel?", why),
+            PromptAction::OpenHeld,
+            true,
+        ));
+    }
+
+    fn open_held(&mut self, answer: &str) {
+        let opened = match answer.chars().next() {
+            Some('r') | Some('R') => self.buffer.open_held(OpenAs::ReadOnly),
+            Some('h') | Some('H') => self.buffer.open_held(OpenAs::Hex),
+            _ => {
+                self.buffer.cancel_held();
+                Ok(())
+            }
+        };
+        if let Err(err) = opened {
+            println!("Error opening file: {}", err);
+            self.buffer.cancel_held();
+        }
+        self.mark_buffer_changed();
+        self.update_cursor();
+        self.update_status_line();
+    }
+
+    fn update_autosave(&mut self, duration: Duration) {
+        if self.prompt.is_none() {
+            if let Some(path) = self.buffer.leftover_swap() {
+                self.start_prompt(Input::new(
+                    &format!(
+                        "Found unsaved changes in {}, recover them? (y/n)",
+                        path.display()
+                    ),
+                    PromptAction::RecoverSwap,
+                    true,
+                ));
+                return;
+            }
+        }
+        // Parked buffers' changes are kept too, not just the one on show
+        if self.autosave.tick(duration) {
+            let parked = self.buffers.parked_mut().map(|parked| &mut parked.buffer);
+            for buffer in std::iter::once(&mut self.buffer).chain(parked) {
+                if let Err(err) = buffer.write_swap() {
+                    println!("Error writing swap file: {}", err);
+                }
+            }
+        }
+    }
+
+    fn recover_swap(&mut self, answer: &str) {
+        if answer.starts_with('y') || answer.starts_with('Y') {
+            if let Err(err) = self.buffer.recover_swap() {
+                println!("Error recovering swap file: {}", err);
+            }
+            self.mark_buffer_changed();
+            self.update_cursor();
+            self.update_status_line();
+        } else {
+            self.buffer.discard_swap();
+        }
+    }
+
+    // The buffer listing the hits of search number id, shown or parked
+    pub fn results_buffer_mut(&mut self, id: usize) -> Option<&mut Buffer<'a>> {
+        if self.buffer.results() == Some(id) {
+            return Some(&mut self.buffer);
+        }
+        self.buffers
+            .parked_mut()
+            .map(|parked| &mut parked.buffer)
+            .find(|buffer| buffer.results() == Some(id))
+    }
+
+    // The buffer in this pane with path open, shown or parked
+    pub fn buffer_for_path_mut(&mut self, path: &Path) -> Option<&mut Buffer<'a>> {
+        if self.buffer.is_for_path(path) {
+            return Some(&mut self.buffer);
+        }
+        self.buffers
+            .parked_mut()
+            .map(|parked| &mut parked.buffer)
+            .find(|buffer| buffer.is_for_path(path))
+    }
+
+    // Every buffer in the pane by name, shown or parked
+    pub fn buffer_stats(&self) -> Vec<(String, EditStats)> {
+        let mut stats = vec![(self.buffer.name(), self.buffer.stats().clone())];
+        for parked in self.buffers.parked() {
+            stats.push((parked.buffer.name(), parked.buffer.stats().clone()));
+        }
+        stats
+    }
+
+    // For when bim's closing normally, so nothing needs recovering
+    pub fn remove_swap_files(&mut self) {
+        self.buffer.remove_swap();
+        for parked in self.buffers.parked_mut() {
+            parked.buffer.remove_swap();
+        }
+    }
+
+    fn update_loading(&mut self) {
+        if !self.buffer.is_loading() {
+            return;
+        }
+        let first_new_row = self.buffer.num_lines() as i32;
+        let cursor = self.buffer.cursor.current();
+        let added = self.buffer.poll_loading();
+        // Highlighting goes over the whole buffer, so only redo it while the
+        // new rows are on screen and once at the end
+        let on_screen = first_new_row < self.viewport.first_row() + self.viewport.screen_rows();
+        if !self.buffer.is_loading() || (added > 0 && on_screen) {
+            self.mark_buffer_changed();
+        }
+        // It was opened at a line that's only just arrived
+        if self.buffer.cursor.current() != cursor {
+            self.update_cursor();
+        }
+        self.update_status_line();
+    }
+
+    // Every buffer in the pane, the one being shown first
+    pub fn all_buffers(&self) -> impl Iterator<Item = &Buffer<'a>> {
+        std::iter::once(&self.buffer).chain(self.buffers.parked().map(|parked| &parked.buffer))
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.buffer.is_dirty()
+            || (0..self.buffers.len())
+                .filter_map(|idx| self.buffers.get(idx))
+                .any(|parked| parked.buffer.is_dirty())
+    }
+
+    // Opens another buffer in this pane without showing it
+    pub fn add_buffer(&mut self, buffer: Buffer<'a>) {
+        self.buffers.add(ParkedBuffer {
+            buffer,
+            row_offset: 0.0,
+            col_offset: 0.0,
+        });
+        self.update_status_line();
+    }
+
+    // Opens another buffer in this pane and shows it straight away
+    pub fn show_buffer(&mut self, buffer: Buffer<'a>) {
+        self.add_buffer(buffer);
+        self.switch_to_buffer(self.buffers.len() - 1);
+    }
+
+    pub fn next_buffer(&mut self) {
+        self.switch_to_buffer(self.buffers.next_index());
+    }
+
+    pub fn prev_buffer(&mut self) {
+        self.switch_to_buffer(self.buffers.prev_index());
+    }
+
+    fn switch_to_buffer(&mut self, index: usize) {
+        if index == self.buffers.current() || index >= self.buffers.len() {
+            return;
+        }
+        self.completion = None;
+        if self.search.is_some() {
+            self.stop_search();
+        }
+        self.buffer.seal_undo_step();
+        let shown = ParkedBuffer {
+            buffer: std::mem::take(&mut self.buffer),
+            row_offset: self.viewport.row_offset(),
+            col_offset: self.viewport.col_offset(),
+        };
+        let parked = self.buffers.switch_to(index, shown);
+        self.buffer = parked.buffer;
+        self.viewport
+            .set_row_offset(parked.row_offset, self.buffer.num_lines());
+        self.viewport.set_col_offset(parked.col_offset);
+        self.follow = None;
+        self.drag = None;
+        self.idle_trim_row = 0;
+        self.trimmed_revision = None;
+        self.update();
+        self.update_cursor();
+    }
+
+    // Every buffer open in the pane as "1 name", with a * on the one shown
+    fn buffer_names(&self) -> Vec<String> {
+        (0..self.buffers.len())
+            .map(|idx| {
+                let (buffer, marker) = match self.buffers.get(idx) {
+                    Some(parked) => (&parked.buffer, ""),
+                    None => (&self.buffer, "*"),
+                };
+                format!("{}{} {}", idx + 1, marker, buffer.title())
+            })
+            .collect()
+    }
+
+    pub fn pick_buffer(&mut self) {
+        let prompt = format!("{} | switch to", self.buffer_names().join("  "));
+        self.start_prompt(Input::new_switch_buffer_input(&prompt));
+    }
+
+    fn pick_filetype(&mut self) {
+        let filetypes = SYNTAXES
+            .iter()
+            .map(|syntax| syntax.filetype)
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.start_prompt(Input::new(
+            &format!("Filetype ({})", filetypes),
+            PromptAction::SetFiletype,
+            true,
+        ));
+    }
+
+    pub fn start_command_line(&mut self) {
+        self.start_prompt(Input::new(":", PromptAction::ExCommand, true));
+    }
+
+    pub fn start_command_palette(&mut self, palette: Palette) {
+        self.start_prompt(Input::new("Command", PromptAction::RunCommand, true));
+        self.palette = Some(palette);
+    }
+
+    // Up and down pick between the command palette's matches while it's up
+    fn move_palette_selection(&mut self, direction: Direction) -> bool {
+        match (self.palette.as_mut(), self.prompt.as_ref()) {
+            (Some(palette), Some(prompt)) => {
+                if direction == Direction::Up {
+                    palette.select_prev(prompt.input());
+                } else {
+                    palette.select_next(prompt.input());
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Closing the pane would lose the changes to the buffers named
+    pub fn confirm_close(&mut self, names: &[String]) {
+        self.start_prompt(Input::new(
+            &format!("{} not saved, close anyway? (y/n)", names.join(", ")),
+            PromptAction::ClosePane,
+            true,
+        ));
+    }
+
+    pub fn pick_theme(&mut self, names: &[String]) {
+        self.start_prompt(Input::new(
+            &format!("Theme ({})", names.join(", ")),
+            PromptAction::SetTheme,
+            true,
+        ));
+    }
+
+    // Switches to the buffer numbered choice (from 1), or failing that the
+    // first one with choice in its name
+    fn switch_to_buffer_named(&mut self, choice: &str) {
+        let choice = choice.trim();
+        if choice.is_empty() {
+            return;
+        }
+        let index = match choice.parse::<usize>() {
+            Ok(number) if number > 0 => Some(number - 1),
+            _ => (0..self.buffers.len()).find(|&idx| {
+                let buffer = match self.buffers.get(idx) {
+                    Some(parked) => &parked.buffer,
+                    None => &self.buffer,
+                };
+                buffer.name().contains(choice)
+            }),
+        };
+        if let Some(index) = index {
+            self.switch_to_buffer(index);
+        }
+    }
+
+    // Shows the buffer in this pane with path open, if there is one
+    pub fn switch_to_path(&mut self, path: &Path) -> bool {
+        if self.buffer.is_for_path(path) {
+            return true;
+        }
+        let index = (0..self.buffers.len()).find(|&idx| {
+            self.buffers
+                .get(idx)
+                .map(|parked| parked.buffer.is_for_path(path))
+                .unwrap_or(false)
+        });
+        match index {
+            Some(index) => {
+                self.switch_to_buffer(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Moves the cursor to row and col, once the buffer's loaded that far
+    pub fn goto(&mut self, row: usize, col: usize) {
+        self.buffer.seal_undo_step();
+        self.buffer.goto(row, col);
+        self.update_cursor();
+    }
+
+    pub fn jump_to_mark(&mut self, name: char) -> bool {
+        self.buffer.seal_undo_step();
+        let jumped = self.buffer.jump_to_mark(name);
+        self.update_cursor();
+        jumped
+    }
+
+    // Puts what a filter command printed where the rows it was fed were
+    pub fn replace_rows_with_text(&mut self, rows: Range<usize>, text: &str) {
+        self.completion = None;
+        self.buffer.replace_rows_with_text(rows, text);
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    fn update(&mut self) {
+        self.update_highlighted_sections();
+        self.update_status_line();
+    }
+
+    fn cursor(&self) -> (usize, usize) {
+        (
+            self.buffer.cursor.text_row() as usize,
+            self.buffer.cursor.text_col() as usize,
+        )
+    }
+
+    pub fn update_buffer(&mut self, action: BufferAction) {
+        use BufferAction::*;
+
+        // While the completion popup's up, up and down pick from it and Tab
+        // puts the pick in. Anything but typing more of the word closes it.
+        if self.completion.is_some() {
+            match &action {
+                MoveCursor(movement) if movement.unit == MoveUnit::Rows => {
+                    return self.move_completion_selection(movement.direction);
+                }
+                InsertIndent => return self.accept_completion(),
+                ClearCursors => {
+                    self.completion = None;
+                    return;
+                }
+                InsertChar(_) | DeleteChar(Direction::Left) | Complete => {}
+                _ => self.completion = None,
+            }
+        }
+        // A snippet's filled in by typing, and going anywhere else is done
+        // with it
+        if !matches!(
+            action,
+            InsertChar(_) | DeleteChar(_) | Paste(_) | InsertIndent | PrevSnippetStop
+        ) {
+            self.expansion = None;
+        }
+
+        match action {
+            InsertNewlineAndReturn => self.insert_newline_and_return(),
+            InsertChar(typed_char) => self.insert_char(typed_char),
+            Paste(text) => self.paste(&text),
+            InsertIndent => self.insert_indent(),
+            DeleteChar(direction) => self.delete_char(direction),
+            DeleteWord(direction) => self.delete_word(direction),
+            CloneCursor => self.clone_cursor(),
+            AddCursorAtNextMatch => self.add_cursor_at_next_match(),
+            ClearCursors => self.buffer.clear_extra_cursors(),
+            ToggleComment => self.toggle_comment(),
+            DuplicateLines => {
+                self.edit_rows(|buffer, first, last| buffer.duplicate_rows(first, last))
+            }
+            MoveLines(direction) => {
+                self.edit_rows(|buffer, first, last| buffer.move_rows(first, last, direction))
+            }
+            ChangeCase(case) => self.change_case(case),
+            IncrementNumber(amount) => self.increment_number(amount),
+            JoinLines => {
+                self.edit_rows(|buffer, first, last| buffer.join_rows(first, last));
+                self.buffer.selection = None;
+            }
+            Complete => self.complete(),
+            HighlightFully => self.buffer.highlight_fully(),
+            PrevSnippetStop => self.prev_snippet_stop(),
+            JumpToMatchingBracket => {
+                self.buffer.seal_undo_step();
+                self.jump_to_matching_bracket();
+            }
+            MoveCursor(movement) => {
+                self.buffer.seal_undo_step();
+                self.do_cursor_movement(movement)
+            }
+            SetFilename(filename) => self.set_filename(filename),
+            SetFiletype(filetype) => self.buffer.set_filetype(&filetype),
+            StartSearch => self.start_search(),
+            ClearSearchHighlight => self.clear_search_highlight(),
+            StartSearchInSelection => self.start_search_in_selection(),
+            ToggleRegexSearch => self.toggle_regex_search(),
+            ToggleSearchAllBuffers => self.toggle_search_all_buffers(),
+            SetNewline(newline) => self.set_newline(newline),
+            StartReplace => self.start_replace(),
+            ToggleSelection => self.toggle_selection(),
+            ToggleBlockSelection => self.toggle_block_selection(),
+            CutBlock => self.cut_block(),
+            PasteBlock => self.paste_block(),
+            ToggleVirtualEdit => self.toggle_virtual_edit(),
+            Undo => self.undo(),
+            Redo => self.redo(),
+            InsertTypedChar => {
+                panic!("Insert typed char received in DrawState.update_buffer, this should not happen!");
+            }
+        }
+    }
+
+    // The rows that are drawn, including the one partly scrolled on
+    fn onscreen_rows(&self) -> Range<usize> {
+        let num_lines = self.buffer.num_lines();
+        let first_row = self.viewport.first_row().max(0) as usize;
+        let screen_rows = self.viewport.screen_rows().max(0) as usize;
+        first_row.min(num_lines)..(first_row + screen_rows + 1).min(num_lines)
+    }
+
+    // The rows drawn as text, along with those in the minimap when it's shown
+    fn drawn_rows(&self) -> Range<usize> {
+        let onscreen = self.onscreen_rows();
+        let minimap_rows = self.minimap_rows();
+        if minimap_rows.is_empty() {
+            onscreen
+        } else {
+            onscreen.start.min(minimap_rows.start)..onscreen.end.max(minimap_rows.end)
+        }
+    }
+
+    // The rows drawn, and a margin either side of them. A buffer that's
+    // only highlighted as it's shown keeps to the rows on screen.
+    fn visible_section_rows(&self) -> Range<usize> {
+        if self.buffer.highlights_lazily() {
+            return self.onscreen_rows();
+        }
+        let drawn = self.drawn_rows();
+        let num_lines = self.buffer.num_lines();
+        drawn.start.saturating_sub(SECTIONS_MARGIN)..(drawn.end + SECTIONS_MARGIN).min(num_lines)
+    }
+
+    // Only the rows around the screen get sections, as the rest aren't drawn
+    fn update_highlighted_sections(&mut self) {
+        let rows = self.visible_section_rows();
+        if self.buffer.highlights_lazily() {
+            self.buffer.highlight_rows(rows.clone());
+        }
+        self.highlight_search_matches(rows.clone());
+        let mut highlighted_sections = Vec::new();
+        for row_idx in rows.clone() {
+            push_row_sections(
+                &mut highlighted_sections,
+                row_idx,
+                &self.buffer.rows[row_idx],
+            );
+        }
+        self.set_highlighted_sections(highlighted_sections);
+        // Everything's caught up, so there's nothing left to do for the
+        // rows that had changed
+        self.buffer.take_changed_rows();
+        self.sections_rows = rows;
+        self.sections_num_lines = self.buffer.num_lines();
+    }
+
+    // Makes sections for whatever's scrolled into view, once it's past the
+    // margin the last ones were made with
+    fn update_visible_sections(&mut self) {
+        let drawn = if self.buffer.highlights_lazily() {
+            self.onscreen_rows()
+        } else {
+            self.drawn_rows()
+        };
+        if self.sections_num_lines != self.buffer.num_lines()
+            || drawn.start < self.sections_rows.start
+            || drawn.end > self.sections_rows.end
+        {
+            self.update_highlighted_sections();
+        }
+    }
+
+    // Rebuilds the sections of only the rows that have changed, moving the
+    // ones after them up or down if rows have come or gone
+    fn update_changed_sections(&mut self) {
+        let num_lines = self.buffer.num_lines();
+        let changed = match self.buffer.take_changed_rows() {
+            Some(changed) if changed.old_num_lines == self.sections_num_lines => changed,
+            Some(_) => return self.update_highlighted_sections(),
+            None => return,
+        };
+        let old_end = changed.old_end(num_lines);
+        // Where the rows with sections have moved to, taking in all of the
+        // changed rows if they'd only partly had sections
+        let moved = |row: usize, inside: usize| {
+            if row <= changed.first {
+                row
+            } else if row >= old_end {
+                row - old_end + changed.end
+            } else {
+                inside
+            }
+        };
+        let rows = moved(self.sections_rows.start, changed.first)
+            ..moved(self.sections_rows.end, changed.end);
+        let rebuild = changed.first.max(rows.start)..changed.end.min(rows.end);
+        if rebuild.len() > self.visible_section_rows().len() {
+            // Quicker to start again from what's on screen
+            return self.update_highlighted_sections();
+        }
+        if self.buffer.highlights_lazily() {
+            self.buffer.highlight_rows(rebuild.clone());
+        }
+        self.highlight_search_matches(rebuild.clone());
+
+        let sections = &mut self.highlighted_sections;
+        let first_idx = sections.partition_point(|section| section.text_row < changed.first);
+        let end_idx = sections.partition_point(|section| section.text_row < old_end);
+        for section in sections[end_idx..].iter_mut() {
+            section.text_row = section.text_row - old_end + changed.end;
+        }
+        let mut changed_sections = Vec::new();
+        for row_idx in rebuild {
+            push_row_sections(&mut changed_sections, row_idx, &self.buffer.rows[row_idx]);
+        }
+        sections.splice(first_idx..end_idx, changed_sections);
+        self.sections_rows = rows;
+        self.sections_num_lines = num_lines;
+        self.update_visible_sections();
+    }
+
+    fn do_cursor_movement(&mut self, movement: MoveCursor) {
+        use crate::commands::Direction::*;
+        use crate::commands::MoveUnit::*;
+
+        let page_size = self.viewport.screen_rows() as usize;
+        let num_lines = self.buffer.num_lines();
+
+        if movement.unit == Rows && self.move_palette_selection(movement.direction) {
+            return;
+        }
+
+        match movement {
+            MoveCursor {
+                unit: Rows,
+                direction: Up,
+                amount,
+            } => {
+                if let Some(search) = self.search.as_mut() {
+                    search.go_backwards();
+                } else {
+                    self.move_cursor(|cursor| {
+                        let max_amount = cursor.text_row();
+                        let possible_amount = std::cmp::min(amount as i32, max_amount);
+                        cursor.text_row -= possible_amount;
+                    });
+                }
+            }
+            MoveCursor {
+                unit: Rows,
+                direction: Down,
+                amount,
+            } => {
+                if let Some(search) = self.search.as_mut() {
+                    search.go_forwards();
+                } else {
+                    self.move_cursor(|cursor| {
+                        let max_movement = num_lines as i32 - 1 - cursor.text_row();
+                        let possible_amount = std::cmp::min(amount as i32, max_movement);
+                        cursor.text_row += possible_amount;
+                    });
+                }
+            }
+            MoveCursor {
+                unit: Cols,
+                direction: Left,
+                amount,
+            } => {
+                if let Some(search) = self.search.as_mut() {
+                    search.go_backwards();
+                } else {
+                    let mut new_cursor = self.buffer.cursor.current();
+                    let mut left_amount = amount as i32;
+                    while left_amount > 0 {
+                        if new_cursor.text_col != 0 {
+                            new_cursor.text_col -= 1;
+                        } else if new_cursor.text_row > 0 {
+                            new_cursor.text_row -= 1;
+                            new_cursor.text_col =
+                                self.buffer.line_len(new_cursor.text_row).unwrap_or(0) as i32;
+                        } else {
+                            break;
+                        }
+                        left_amount -= 1;
+                    }
+                    self.move_cursor(|cursor| {
+                        cursor.text_col = new_cursor.text_col();
+                        cursor.text_row = new_cursor.text_row();
+                    });
+                }
+            }
+            MoveCursor {
+                unit: Cols,
+                direction: Right,
+                amount,
+            } => {
+                if let Some(search) = self.search.as_mut() {
+                    search.go_forwards();
+                } else {
+                    let mut new_cursor = self.buffer.cursor.current();
+                    let mut right_amount = amount as i32;
+                    let num_lines = self.buffer.num_lines() as i32;
+                    while right_amount > 0 {
+                        if let Some(row_size) = self.buffer.line_len(new_cursor.text_row) {
+                            if new_cursor.text_col < row_size as i32 || self.buffer.virtual_edit() {
+                                new_cursor.text_col += 1;
+                            } else if new_cursor.text_col == row_size as i32
+                                && new_cursor.text_row < num_lines - 1
+                            {
+                                new_cursor.text_row += 1;
+                                new_cursor.text_col = 0;
+                            } else {
+                                break;
+                            }
+                            right_amount -= 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    self.move_cursor(|cursor| {
+                        cursor.text_col = new_cursor.text_col();
+                        cursor.text_row = new_cursor.text_row();
+                    });
+                }
+            }
+            MoveCursor {
+                unit: Words,
+                direction,
+                amount,
+            } => {
+                let mut new_cursor = self.buffer.cursor.current();
+                for _ in 0..amount {
+                    let (row, col) = self.buffer.word_boundary(
+                        new_cursor.text_row,
+                        new_cursor.text_col,
+                        direction,
+                    );
+                    new_cursor.text_row = row;
+                    new_cursor.text_col = col;
+                }
+                self.move_cursor(|cursor| {
+                    cursor.text_col = new_cursor.text_col();
+                    cursor.text_row = new_cursor.text_row();
+                });
+            }
+            MoveCursor {
+                unit: Start,
+                direction: Left,
+                ..
+            } => self.buffer.cursor.change(|cursor| cursor.text_col = 0),
+            MoveCursor {
+                unit: End,
+                direction: Right,
+                ..
+            } => {
+                let new_x = self
+                    .buffer
+                    .line_len(self.buffer.cursor.text_row())
+                    .unwrap_or(0) as i32;
+                self.move_cursor(|cursor| {
+                    cursor.text_col = new_x;
+                });
+            }
+            MoveCursor {
+                unit: Pages,
+                direction: Down,
+                amount,
+            } => {
+                let amount = amount * page_size;
+                self.do_cursor_movement(MoveCursor::down(amount));
+            }
+            MoveCursor {
+                unit: Pages,
+                direction: Up,
+                amount,
+            } => {
+                let amount = amount * page_size;
+                self.do_cursor_movement(MoveCursor::up(amount));
+            }
+            MoveCursor {
+                unit: Percent,
+                amount,
+                ..
+            } => {
+                let new_row = row_at_percentage(amount, num_lines);
+                self.move_cursor(|cursor| {
+                    cursor.text_row = new_row;
+                    cursor.text_col = 0;
+                });
+            }
+            _ => {}
+        }
+        self.buffer.check_cursor();
+        self.update_cursor();
+    }
+
+    fn move_cursor_onscreen(&mut self) {
+        let row_offset = self.viewport.first_row();
+        // Scrolling up and down leaves the text where it was sideways, even
+        // if that's with the cursor out of sight
+        let col_offset = self.viewport.col_offset();
+        self.move_cursor(|cursor| {
+            cursor.text_row = row_offset;
+        });
+        self.viewport.set_col_offset(col_offset);
+    }
+
+    // Leaves a cursor behind where this one is, to edit at as well
+    fn clone_cursor(&mut self) {
+        let cursor = self.buffer.cursor.current();
+        self.buffer.add_cursor(cursor.text_row, cursor.text_col);
+        self.update_cursor();
+    }
+
+    fn add_cursor_at_next_match(&mut self) {
+        if self.buffer.add_cursor_at_next_match() {
+            if let Some(added) = self.buffer.extra_cursors().last() {
+                self.viewport.scroll_to_row(added.text_row);
+            }
+        }
+    }
+
+    fn jump_to_matching_bracket(&mut self) {
+        let cursor = self.buffer.cursor.current();
+        let matched = self
+            .buffer
+            .matching_bracket(cursor.text_row as usize, cursor.text_col as usize);
+        if let Some((_, (row, col))) = matched {
+            self.move_cursor(|cursor| {
+                cursor.text_row = row as i32;
+                cursor.text_col = col as i32;
+            });
+            self.update_cursor();
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.buffer.undo() {
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.buffer.redo() {
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn toggle_virtual_edit(&mut self) {
+        let virtual_edit = !self.buffer.virtual_edit();
+        self.buffer.set_virtual_edit(virtual_edit);
+        self.update_cursor();
+    }
+
+    fn delete_char(&mut self, direction: Direction) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.del_char();
+            if let Some(palette) = self.palette.as_mut() {
+                palette.reset_selection();
+            }
+            return;
+        }
+        if let Some(search) = self.search.as_mut() {
+            search.del_char();
+            return;
+        }
+
+        // Deleting a block takes out what's in it, while one with nothing in
+        // it is just somewhere to delete from on each of its rows
+        if let Some(taken) = self.buffer.take_block(false) {
+            if taken.iter().any(|text| !text.is_empty()) {
+                self.mark_buffer_changed();
+                self.update_cursor();
+                return;
+            }
+        }
+        self.track_snippet_edit(|pane| {
+            if direction == Direction::Right {
+                pane.buffer
+                    .at_every_cursor(Buffer::delete_char_forward_at_cursor);
+            } else {
+                pane.buffer.at_every_cursor(Buffer::delete_char_at_cursor);
+            }
+        });
+        self.mark_buffer_changed();
+        self.update_cursor();
+        self.update_completion(false);
+    }
+
+    fn delete_word(&mut self, direction: Direction) {
+        if self.prompt.is_some() || self.search.is_some() {
+            return;
+        }
+        self.buffer
+            .at_every_cursor(|buffer| buffer.delete_word_at_cursor(direction));
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    fn insert_newline_and_return(&mut self) {
+        if let Some(prompt) = &mut self.prompt {
+            prompt.done();
+            return;
+        }
+        if let Some(search) = &mut self.search {
+            match search.replace_stage() {
+                None => search.stop(false),
+                Some(ReplaceStage::Needle) => search.next_replace_stage(),
+                Some(ReplaceStage::Replacement) => {
+                    search.next_replace_stage();
+                    self.find_replace_match(0, 0);
+                }
+                Some(ReplaceStage::Confirm) => self.replace_one(),
+            }
+            return;
+        }
+        if self.buffer.results().is_some() {
+            let row = self.buffer.cursor.text_row().max(0) as usize;
+            self.jump_to = self
+                .buffer
+                .rows
+                .get(row)
+                .and_then(|row| grep::hit_location(row.as_str()))
+                .map(str::to_string);
+            return;
+        }
+        self.buffer
+            .at_every_cursor(Buffer::insert_newline_and_return);
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    fn insert_char(&mut self, typed_char: char) {
+        if let Some(prompt) = &mut self.prompt {
+            prompt.type_char(typed_char);
+            if let Some(palette) = self.palette.as_mut() {
+                palette.reset_selection();
+            }
+            return;
+        }
+        if let Some(search) = &mut self.search {
+            if search.replace_stage() == Some(ReplaceStage::Confirm) {
+                self.confirm_replace(typed_char);
+            } else {
+                search.push_char(typed_char);
+            }
+            return;
+        }
+
+        // Typing over a block puts what's typed on each of its rows instead
+        self.buffer.take_block(true);
+        self.clear_snippet_default();
+        self.track_snippet_edit(|pane| {
+            pane.buffer
+                .at_every_cursor(|buffer| buffer.type_char_at_cursor(typed_char))
+        });
+        self.mark_buffer_changed();
+        self.update_cursor();
+        self.update_completion(true);
+    }
+
+    // Prompts and searches are one line, so they get the text without its
+    // line breaks
+    fn paste(&mut self, text: &str) {
+        if self.prompt.is_some() || self.search.is_some() {
+            for c in text.chars().filter(|c| *c != '\r' && *c != '\n') {
+                self.insert_char(c);
+            }
+            return;
+        }
+        self.buffer.seal_undo_step();
+        self.buffer.take_block(true);
+        self.clear_snippet_default();
+        self.track_snippet_edit(|pane| {
+            pane.buffer
+                .at_every_cursor(|buffer| buffer.paste_at_cursor(text))
+        });
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    // Asks which snippet's meant when more than one has the prefix
+    fn expand_snippet(&mut self) -> bool {
+        if !self.buffer.extra_cursors().is_empty() || self.buffer.selection.is_some() {
+            return false;
+        }
+        let (start, prefix) = self.buffer.word_before_cursor();
+        let filetype = self.buffer.get_filetype();
+        let mut matching = snippet::for_prefix(&self.snippets, &filetype, &prefix)
+            .cloned()
+            .collect::<Vec<_>>();
+        match matching.len() {
+            0 => false,
+            1 => {
+                self.insert_snippet(start, &matching.remove(0));
+                true
+            }
+            _ => {
+                let choices = matching
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, snippet)| match snippet.description.as_str() {
+                        "" => format!("{}) {}", idx + 1, snippet.body.lines().next().unwrap_or("")),
+                        description => format!("{}) {}", idx + 1, description),
+                    })
+                    .collect::<Vec<_>>();
+                self.start_prompt(Input::new(
+                    &format!("{} | which {} snippet", choices.join("  "), prefix),
+                    PromptAction::PickSnippet,
+                    true,
+                ));
+                self.snippet_choices = matching;
+                true
+            }
+        }
+    }
+
+    fn pick_snippet(&mut self, choice: &str) {
+        let choices = std::mem::take(&mut self.snippet_choices);
+        let picked = choice
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .and_then(|idx| choices.get(idx));
+        if let Some(snippet) = picked {
+            let (start, _) = self.buffer.word_before_cursor();
+            self.insert_snippet(start, snippet);
+        }
+    }
+
+    // Swaps the prefix from start to the cursor for snippet, indented to
+    // match the row it's on, and goes to its first stop
+    fn insert_snippet(&mut self, start: usize, snippet: &Snippet) {
+        let row = self.buffer.cursor.text_row().max(0) as usize;
+        let indent = self
+            .buffer
+            .rows
+            .get(row)
+            .map(|line| {
+                line.as_str()
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+        let (text, stops) = snippet.expand(&indent, &self.buffer.indent_unit());
+        self.buffer.replace_before_cursor(start, &text);
+        self.expansion = Some(Expansion::new(row, start, stops));
+        self.mark_buffer_changed();
+        self.goto_snippet_stop();
+    }
+
+    fn prev_snippet_stop(&mut self) {
+        if let Some(expansion) = self.expansion.as_mut() {
+            if expansion.prev_stop().is_some() {
+                self.goto_snippet_stop();
+            }
+        }
+    }
+
+    // Selects the stop's default, to show it'll be typed over, and is done
+    // with the snippet once it's at the last stop
+    fn goto_snippet_stop(&mut self) {
+        let (stop, finished) = match self.expansion.as_ref() {
+            Some(expansion) => (expansion.current(), expansion.is_finished()),
+            None => return,
+        };
+        if finished {
+            self.expansion = None;
+        }
+        self.buffer.clear_selection();
+        let (row, col) = (stop.row as i32, stop.col as i32);
+        if stop.len > 0 && !finished {
+            self.buffer.selection = Some(Selection::new(Cursor::new(row, col)));
+        }
+        self.buffer
+            .cursor
+            .move_to_without_history(row, col + stop.len as i32);
+        self.buffer.update_selection();
+        self.update_cursor();
+    }
+
+    // Typing at a stop that's still got its default in takes it out first
+    fn clear_snippet_default(&mut self) {
+        let (row, col) = self.cursor();
+        if let Some(stop) = self
+            .expansion
+            .as_mut()
+            .and_then(|expansion| expansion.take_default(row, col))
+        {
+            self.buffer.clear_selection();
+            self.buffer.replace_before_cursor(stop.col, "");
+        }
+    }
+
+    // Moves the snippet's stops along with an edit on the cursor's row.
+    // Edits that add or take away rows, or happen elsewhere, end it.
+    fn track_snippet_edit(&mut self, edit: impl FnOnce(&mut Self)) {
+        if self.expansion.is_none() {
+            return edit(self);
+        }
+        let (row, col) = self.cursor();
+        let num_lines = self.buffer.num_lines();
+        let row_len = self.buffer.line_len(row as i32).unwrap_or(0);
+        edit(self);
+        let (row_after, col_after) = self.cursor();
+        if row_after != row || self.buffer.num_lines() != num_lines {
+            self.expansion = None;
+            return;
+        }
+        let cols = self.buffer.line_len(row as i32).unwrap_or(0) as isize - row_len as isize;
+        if let Some(expansion) = self.expansion.as_mut() {
+            expansion.edited(row, col.min(col_after), cols);
+        }
+    }
+
+    // Offers the words that could finish the one at the cursor, if there
+    // are any
+    fn complete(&mut self) {
+        if self.prompt.is_some() || self.search.is_some() {
+            return;
+        }
+        let (_, prefix) = self.buffer.word_before_cursor();
+        let cursor_row = self.buffer.cursor.text_row().max(0) as usize;
+        let first_row = cursor_row.saturating_sub(COMPLETION_SCAN_ROWS);
+        let last_row = (cursor_row + COMPLETION_SCAN_ROWS).min(self.buffer.num_lines());
+        let lines = self.buffer.rows[first_row.min(last_row)..last_row]
+            .iter()
+            .map(|row| row.as_str())
+            .collect::<Vec<_>>();
+        let words = completion::candidates(
+            &lines,
+            cursor_row - first_row,
+            &self.buffer.keywords(),
+            &prefix,
+        );
+        self.completion = Completion::new(&prefix, words);
+    }
+
+    // Keeps the popup up to date as the word changes, and brings it up
+    // once the word's long enough if that's been asked for
+    fn update_completion(&mut self, typed: bool) {
+        let (_, prefix) = self.buffer.word_before_cursor();
+        let long_enough = typed
+            && self
+                .complete_after
+                .map(|length| prefix.chars().count() >= length)
+                .unwrap_or(false);
+        if long_enough || (self.completion.is_some() && !prefix.is_empty()) {
+            self.complete();
+        } else {
+            self.completion = None;
+        }
+    }
+
+    fn move_completion_selection(&mut self, direction: Direction) {
+        if let Some(completion) = self.completion.as_mut() {
+            match direction {
+                Direction::Up => completion.select_prev(),
+                _ => completion.select_next(),
+            }
+        }
+    }
+
+    fn accept_completion(&mut self) {
+        if let Some(completion) = self.completion.take() {
+            self.buffer.complete_word_at_cursor(completion.chosen());
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    // Tab goes on to the next stop of a snippet that's being filled in, or
+    // puts in the snippet whose prefix is before the cursor, before it
+    // indents
+    fn insert_indent(&mut self) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            // Finishes off the path of a file to open
+            if matches!(prompt.next_action, PromptAction::OpenFile) {
+                if let Some(path) = paths::complete_path(prompt.input()) {
+                    prompt.set_input(&path);
+                }
+            }
+            return;
+        }
+        if self.search.is_some() {
+            return;
+        }
+        if let Some(expansion) = self.expansion.as_mut() {
+            if expansion.next_stop().is_some() {
+                return self.goto_snippet_stop();
+            }
+        }
+        if self.expand_snippet() {
+            return;
+        }
+        self.buffer.at_every_cursor(Buffer::insert_indent_at_cursor);
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    fn run_search(&mut self) {
+        let mut update_search = false;
+
+        if let Some(search) = self.search.clone() {
+            let mut last_match = self.search_buffer(&search, search.last_match());
+            if search.all_buffers() && self.buffers.len() > 1 {
+                // Having come back round to the top (or bottom) of this
+                // buffer, it's the next buffer's turn
+                if let Some((_, last_row)) = search.last_match() {
+                    let wrapped = match (last_match, search.direction()) {
+                        (None, _) => true,
+                        (Some((_, row)), SearchDirection::Forwards) => row <= last_row,
+                        (Some((_, row)), SearchDirection::Backwards) => row >= last_row,
+                    };
+                    if wrapped {
+                        last_match = self.search_other_buffers(&search);
+                    }
+                }
+            }
+            let buffer_name = self.buffer.name();
+            let match_count = self.count_search_matches(&search, last_match);
+            if let Some(search) = self.search.as_mut() {
+                search.set_last_match(last_match);
+                search.set_buffer_name(buffer_name);
+                search.set_match_count(match_count);
+            }
+            update_search = true;
+        }
+
+        if update_search {
+            self.update_search();
+        }
+    }
+
+    fn search_buffer(
+        &mut self,
+        search: &Search,
+        last_match: Option<(usize, usize)>,
+    ) -> Option<(usize, usize)> {
+        match search.compile() {
+            None => self.buffer.search_within(
+                last_match,
+                search.direction(),
+                search.needle(),
+                search.scope(),
+            ),
+            Some(Ok(regex)) => self.buffer.search_regex_within(
+                last_match,
+                search.direction(),
+                &regex,
+                search.scope(),
+            ),
+            Some(Err(_)) => {
+                self.buffer.clear_search_overlay();
+                None
+            }
+        }
+    }
+
+    fn count_search_matches(
+        &self,
+        search: &Search,
+        current: Option<(usize, usize)>,
+    ) -> Option<(usize, usize)> {
+        let regex = match search.compile() {
+            Some(Ok(regex)) => Some(regex),
+            Some(Err(_)) => return None,
+            None => None,
+        };
+        Some(
+            self.buffer
+                .count_matches(search.needle(), regex.as_ref(), search.scope(), current),
+        )
+    }
+
+    // Marks every match in rows of the search going on, or else the last
+    // one finished with, so they show up as well as the one the cursor's on
+    fn highlight_search_matches(&mut self, rows: Range<usize>) {
+        let search = match self.search.as_ref().or(self.search_highlight.as_ref()) {
+            Some(search) if search.replace_stage().is_none() && !search.needle().is_empty() => {
+                search
+            }
+            _ => return,
+        };
+        let regex = match search.compile() {
+            Some(Ok(regex)) => Some(regex),
+            Some(Err(_)) => return,
+            None => None,
+        };
+        let (needle, scope) = (search.needle().to_string(), search.scope());
+        self.buffer
+            .highlight_matches(rows, &needle, regex.as_ref(), scope);
+    }
+
+    fn clear_search_highlight(&mut self) {
+        self.search_highlight = None;
+        self.buffer.clear_search_overlay();
+        for parked in self.buffers.parked_mut() {
+            parked.buffer.clear_search_overlay();
+        }
+        self.damaged = true;
+        self.update_highlighted_sections();
+    }
+
+    // Shows each of the other buffers in turn until one has a match,
+    // ending up back at this one if none of them do
+    fn search_other_buffers(&mut self, search: &Search) -> Option<(usize, usize)> {
+        for _ in 0..self.buffers.len() {
+            let index = match search.direction() {
+                SearchDirection::Forwards => self.buffers.next_index(),
+                SearchDirection::Backwards => self.buffers.prev_index(),
+            };
+            self.buffer.clear_search_overlay();
+            let searching = self.search.take();
+            self.switch_to_buffer(index);
+            self.search = searching;
+            if let Some(found) = self.search_buffer(search, None) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    fn update_search(&mut self) {
+        self.update_cursor();
+        self.update_highlighted_sections();
+    }
+
+    fn start_search(&mut self) {
+        self.set_search(Some(self.new_search()));
+        self.buffer.cursor.save_cursor();
+        self.update_search();
+    }
+
+    fn start_search_in_selection(&mut self) {
+        match self.buffer.selection {
+            Some(selection) if !selection.is_empty() => {
+                self.set_search(Some(self.new_search().within(selection)));
+                self.buffer.cursor.save_cursor();
+                self.update_search();
+            }
+            _ => self.start_search(),
+        }
+    }
+
+    fn start_replace(&mut self) {
+        self.set_search(Some(self.new_search().replacing()));
+        self.buffer.cursor.save_cursor();
+        self.update_search();
+    }
+
+    // Answers the question asked about the current match
+    fn confirm_replace(&mut self, answer: char) {
+        match answer {
+            'y' => self.replace_one(),
+            'n' => {
+                if let Some((row, _, end)) = self.search.as_ref().and_then(Search::replace_match) {
+                    self.find_replace_match(row, end);
+                }
+            }
+            'a' => self.replace_all(),
+            'q' => self.finish_replace(),
+            _ => {}
+        }
+    }
+
+    // Moves on to the next match from col on row, finishing if there are
+    // no more
+    fn find_replace_match(&mut self, row: usize, col: usize) {
+        let search = match self.search.clone() {
+            Some(search) => search,
+            None => return,
+        };
+        let found = match search.compile() {
+            None => self
+                .buffer
+                .find_replace_match(row, col, search.needle(), None),
+            Some(Ok(regex)) => {
+                self.buffer
+                    .find_replace_match(row, col, search.needle(), Some(&regex))
+            }
+            Some(Err(_)) => None,
+        };
+        if let Some(search) = self.search.as_mut() {
+            search.set_replace_match(found);
+        }
+        if found.is_none() {
+            self.finish_replace();
+        }
+        self.update_search();
+    }
+
+    fn replace_one(&mut self) {
+        let search = match self.search.as_mut() {
+            Some(search) => search,
+            None => return,
+        };
+        if let Some((row, start, end)) = search.replace_match() {
+            let replacement = search.replacement().to_string();
+            search.add_replaced(1);
+            self.buffer.replace_text(row, start, end, &replacement);
+            self.mark_buffer_changed();
+            self.find_replace_match(row, start + replacement.chars().count());
+        }
+    }
+
+    fn replace_all(&mut self) {
+        let search = match self.search.clone() {
+            Some(search) => search,
+            None => return,
+        };
+        if let Some((row, start, _)) = search.replace_match() {
+            let regex = match search.compile() {
+                Some(Ok(regex)) => Some(regex),
+                Some(Err(_)) => return,
+                None => None,
+            };
+            self.buffer.replace_all_from(
+                row,
+                start,
+                search.needle(),
+                regex.as_ref(),
+                search.replacement(),
+            );
+            self.mark_buffer_changed();
+        }
+        self.finish_replace();
+        self.update_search();
+    }
+
+    fn finish_replace(&mut self) {
+        if let Some(search) = self.search.as_mut() {
+            search.stop(false);
+        }
+    }
+
+    // Only does anything while searching
+    fn toggle_search_all_buffers(&mut self) {
+        if let Some(search) = self.search.as_mut() {
+            search.toggle_all_buffers();
+            self.update_search();
+        }
+    }
+
+    // Only does anything while searching
+    fn toggle_regex_search(&mut self) {
+        if let Some(search) = self.search.as_mut() {
+            search.toggle_regex();
+            self.update_search();
+        }
+    }
+
+    // The rows the selection covers, not counting one it only just reaches
+    // the start of
+    fn selected_rows(&self) -> Option<(usize, usize)> {
+        let selection = self
+            .buffer
+            .selection
+            .filter(|selection| !selection.is_empty())?;
+        let (start, end) = (selection.start(), selection.end());
+        let last_row = if end.text_col() == 0 && end.text_row() > start.text_row() {
+            end.text_row() - 1
+        } else {
+            end.text_row()
+        };
+        Some((start.text_row() as usize, last_row as usize))
+    }
+
+    // Makes a line-wise edit to the selected rows, or the cursor's row
+    fn edit_rows(&mut self, edit: impl FnOnce(&mut Buffer<'a>, usize, usize) -> bool) {
+        if self.prompt.is_some() || self.search.is_some() {
+            return;
+        }
+        let (first_row, last_row) = self.selected_rows().unwrap_or_else(|| {
+            let row = self.buffer.cursor.text_row() as usize;
+            (row, row)
+        });
+        self.buffer.clear_extra_cursors();
+        if edit(&mut self.buffer, first_row, last_row) {
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn change_case(&mut self, case: Case) {
+        if self.prompt.is_some() || self.search.is_some() {
+            return;
+        }
+        if self.buffer.change_case(case) {
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn increment_number(&mut self, amount: i64) {
+        if self.prompt.is_some() || self.search.is_some() {
+            return;
+        }
+        self.buffer.seal_undo_step();
+        if self.buffer.increment_number(amount) {
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    // Comments the selected rows, or else the rows with cursors on
+    fn toggle_comment(&mut self) {
+        if self.prompt.is_some() || self.search.is_some() {
+            return;
+        }
+        let changed = match self.selected_rows() {
+            Some((first_row, last_row)) => self.buffer.toggle_comment(first_row, last_row),
+            None => {
+                let mut changed = false;
+                self.buffer.at_every_cursor(|buffer| {
+                    let row = buffer.cursor.text_row() as usize;
+                    changed |= buffer.toggle_comment(row, row);
+                });
+                changed
+            }
+        };
+        if changed {
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn toggle_block_selection(&mut self) {
+        if self
+            .buffer
+            .selection
+            .is_some_and(|selection| selection.is_block())
+        {
+            self.buffer.clear_selection();
+        } else {
+            self.buffer.start_block_selection();
+        }
+    }
+
+    // Takes the block out, to go back in wherever PasteBlock's next used
+    fn cut_block(&mut self) {
+        if let Some(taken) = self.buffer.take_block(false) {
+            self.cut_block = taken;
+            self.buffer.clear_extra_cursors();
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn paste_block(&mut self) {
+        if self.cut_block.is_empty() {
+            return;
+        }
+        self.buffer.clear_extra_cursors();
+        self.buffer.insert_block(&self.cut_block);
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    fn toggle_selection(&mut self) {
+        self.buffer.toggle_selection();
+        self.update_cursor();
+    }
+
+    fn stop_search(&mut self) {
+        self.set_search(None);
+        self.buffer.clear_search_overlay();
+        self.update_highlighted_sections();
+        self.update_cursor();
+    }
+
+    fn mark_buffer_changed(&mut self) {
+        self.damaged = true;
+        self.update_changed_sections();
+    }
+
+    #[cfg(feature = "gui")]
+    fn status_text(&self) -> String {
+        self.status_line.text(self.follow.is_some())
+    }
+
+    // The status line is drawn along the bottom row of the pane
+    fn status_target_at(&self, location: Vec2) -> Option<StatusTarget> {
+        if self.line_height <= 0.0 || self.character_width <= 0.0 {
+            return None;
+        }
+        let status_top = self.bounds.y() - self.line_height;
+        if location.y() < status_top || location.y() >= self.bounds.y() || location.x() < 0.0 {
+            return None;
+        }
+        let col = (location.x() / self.character_width).floor() as usize;
+        self.status_line.target_at(self.follow.is_some(), col)
+    }
+
+    fn click_status(&mut self, target: StatusTarget) {
+        match target {
+            StatusTarget::Filetype => self.pick_filetype(),
+            StatusTarget::Cursor => self.do_action(PaneAction::StartGotoLine),
+            StatusTarget::Newline => self.start_prompt(Input::new(
+                "Line endings (LF or CRLF)",
+                PromptAction::ConvertNewlines,
+                true,
+            )),
+            StatusTarget::Encoding => self.start_prompt(Input::new(
+                "Encoding (UTF-8, UTF-8 BOM, UTF-16LE, UTF-16BE or Latin-1)",
+                PromptAction::ConvertEncoding,
+                true,
+            )),
+            StatusTarget::LongLines => self.goto_next_long_line(),
+            StatusTarget::Filename
+            | StatusTarget::Dirty
+            | StatusTarget::Percent
+            | StatusTarget::NumLines
+            | StatusTarget::Mode
+            | StatusTarget::Loading
+            | StatusTarget::Following
+            | StatusTarget::PowerSaving => {}
+        }
+    }
+
+    // Puts the cursor where the next long line goes over the limit,
+    // wrapping around to the top of the buffer
+    fn goto_next_long_line(&mut self) {
+        let max_len = match self.max_line_length {
+            Some(max_len) => max_len,
+            None => return,
+        };
+        let long_lines = self.long_lines();
+        let current_row = self.buffer.cursor.text_row() as usize;
+        let next_row = long_lines
+            .iter()
+            .find(|row| **row > current_row)
+            .or_else(|| long_lines.first());
+        if let Some(&row) = next_row {
+            let col = self.buffer.render_cursor_to_text(max_len, row as i32);
+            self.buffer.seal_undo_step();
+            self.move_cursor(|cursor| {
+                cursor.text_row = row as i32;
+                cursor.text_col = col;
+            });
+            self.buffer.check_cursor();
+            self.update_cursor();
+        }
+    }
+
+    // A new name can mean a new filetype, and a different file to watch
+    fn set_filename(&mut self, filename: String) {
+        self.buffer.set_filename(filename);
+        self.watch = Watch::default();
+        self.update();
+    }
+
+    // Starts off with the name the buffer has now, to change
+    fn start_filename_prompt(&mut self, prompt: &str, action: PromptAction) {
+        let mut input = Input::new(prompt, action, true);
+        if self.buffer.filename.is_some() {
+            input.set_input(&self.buffer.name());
+        }
+        self.start_prompt(input);
+    }
+
+    fn set_filetype_named(&mut self, filetype: &str) {
+        if Syntax::for_filetype(filetype.trim()).is_some() {
+            self.buffer.set_filetype(filetype.trim());
+            self.update();
+        }
+    }
+
+    // Takes "line" or "line:col", both counting from 1
+    fn goto_line(&mut self, input: &str) {
+        let mut parts = input.trim().splitn(2, ':');
+        let row = match parts.next().and_then(|row| row.trim().parse::<i32>().ok()) {
+            Some(row) if row > 0 => row - 1,
+            _ => return,
+        };
+        let col = parts
+            .next()
+            .and_then(|col| col.trim().parse::<i32>().ok())
+            .map(|col| (col - 1).max(0))
+            .unwrap_or(0);
+        let last_row = (self.buffer.num_lines() as i32 - 1).max(0);
+        self.buffer.seal_undo_step();
+        self.move_cursor(|cursor| {
+            cursor.text_row = row.min(last_row);
+            cursor.text_col = col;
+        });
+        self.buffer.check_cursor();
+        self.update_cursor();
+    }
+
+    fn convert_newlines(&mut self, input: &str) {
+        match input.trim().to_lowercase().as_str() {
+            "lf" | "unix" => self.set_newline(Newline::Unix),
+            "crlf" | "dos" => self.set_newline(Newline::Dos),
+            _ => {}
+        }
+    }
+
+    fn convert_encoding(&mut self, input: &str) {
+        if let Some(encoding) = Encoding::parse(input) {
+            self.buffer.set_encoding(encoding);
+            self.mark_buffer_changed();
+            self.update_cursor();
+        }
+    }
+
+    fn set_newline(&mut self, newline: Newline) {
+        self.buffer.convert_newlines(newline.as_str());
+        self.mark_buffer_changed();
+        self.update_cursor();
+    }
+
+    fn start_prompt(&mut self, prompt: Input<'a>) {
+        self.set_prompt(Some(prompt));
+        self.buffer.cursor.save_cursor();
+        self.update_cursor();
+    }
+
+    fn top_prompt_visible(&self) -> bool {
+        self.prompt.is_some() || self.search.is_some()
+    }
+
+    fn stop_prompt(&mut self) {
+        self.set_prompt(None);
+        self.palette = None;
+        self.buffer.cursor.restore_saved();
+        self.update_cursor();
+    }
+
+    fn check_prompt(&mut self) -> Option<WindowAction> {
+        let (next_action, input) = match self.prompt.as_ref() {
+            Some(prompt) if prompt.is_done() => {
+                (prompt.next_action(), String::from(prompt.input()))
+            }
+            // Backing out of opening a held file is saying not to open it
+            Some(prompt) if prompt.is_cancelled() => match prompt.next_action() {
+                Some(PromptAction::OpenHeld) => (prompt.next_action(), String::new()),
+                _ => (None, String::new()),
+            },
+            _ => return None,
+        };
+        let command = self
+            .palette
+            .as_ref()
+            .and_then(|palette| palette.chosen(&input))
+            .map(|command| command.name.clone());
+        self.stop_prompt();
+
+        match next_action {
+            Some(PromptAction::SaveFile) => return Some(WindowAction::SaveFileAs(input)),
+            Some(PromptAction::SwitchBuffer) => self.switch_to_buffer_named(&input),
+            Some(PromptAction::SetFiletype) => self.set_filetype_named(&input),
+            Some(PromptAction::GotoLine) => self.goto_line(&input),
+            Some(PromptAction::ConvertNewlines) => self.convert_newlines(&input),
+            Some(PromptAction::ConvertEncoding) => self.convert_encoding(&input),
+            Some(PromptAction::ReloadChanged) => self.reload_changed(&input),
+            Some(PromptAction::RecoverSwap) => self.recover_swap(&input),
+            Some(PromptAction::OpenHeld) => self.open_held(&input),
+            Some(PromptAction::PickSnippet) => self.pick_snippet(&input),
+            Some(PromptAction::ExCommand) if !input.trim().is_empty() => {
+                return Some(WindowAction::RunExCommand(input));
+            }
+            Some(PromptAction::RunCommand) => {
+                if let Some(name) = command {
+                    return Some(WindowAction::RunCommand(name));
+                }
+            }
+            Some(PromptAction::SetTheme) if !input.trim().is_empty() => {
+                return Some(WindowAction::SetTheme(input.trim().to_string()));
+            }
+            Some(PromptAction::OpenFile) if !input.trim().is_empty() => {
+                return Some(WindowAction::OpenFile(input.trim().to_string()));
+            }
+            Some(PromptAction::RenameFile) if !input.trim().is_empty() => {
+                return Some(WindowAction::RenameFile(input.trim().to_string()));
+            }
+            Some(PromptAction::CreateDirectory)
+                if input.starts_with('y') || input.starts_with('Y') =>
+            {
+                return Some(WindowAction::SaveFileCreatingDirectory);
+            }
+            Some(PromptAction::ClosePane) if input.starts_with('y') || input.starts_with('Y') => {
+                return Some(WindowAction::ForceClosePane);
+            }
+            Some(PromptAction::ReplaceInFilesNeedle) if !input.is_empty() => {
+                self.start_prompt(Input::new(
+                    &format!("Replace {} in files with", input),
+                    PromptAction::ReplaceInFilesWith,
+                    true,
+                ));
+                self.files_needle = Some(input);
+            }
+            Some(PromptAction::ReplaceInFilesWith) => {
+                if let Some(needle) = self.files_needle.take() {
+                    return Some(WindowAction::PreviewReplaceInFiles(needle, input));
+                }
+            }
+            Some(PromptAction::CreateDirectory)
+            | Some(PromptAction::ReplaceInFilesNeedle)
+            | Some(PromptAction::SetTheme)
+            | Some(PromptAction::OpenFile)
+            | Some(PromptAction::RenameFile)
+            | Some(PromptAction::ClosePane)
+            | Some(PromptAction::ExCommand) => {}
+            None => {}
+        }
+        None
+    }
+
+    fn check_search(&mut self) {
+        if let Some(search) = self.search.clone() {
+            if search.run_search() {
+                if search.incremental() {
+                    self.run_search();
+                }
+            } else {
+                if search.restore_cursor() {
+                    self.buffer.cursor.restore_saved();
+                    self.restore_from_search(search);
+                } else {
+                    self.report_failed_search(&search);
+                    // Finding something with Enter leaves all its matches
+                    // highlighted
+                    if search.last_match().is_some() && search.replace_stage().is_none() {
+                        self.search_highlight = Some(search);
+                    }
+                }
+                self.stop_search();
+            }
+        }
+    }
+
+    // Says why a search that's been finished with didn't get anywhere
+    fn report_failed_search(&mut self, search: &Search) {
+        if search.needle().is_empty()
+            || search.last_match().is_some()
+            || search.replace_stage().is_some()
+        {
+            return;
+        }
+        self.message = Some(match search.compile() {
+            Some(Err(err)) => (
+                Level::Error,
+                format!("Bad pattern {}: {}", search.needle(), err),
+            ),
+            _ => (Level::Warn, format!("Not found: {}", search.needle())),
+        });
+    }
+
+    pub fn check(&mut self) -> Vec<WindowAction> {
+        let mut actions = vec![];
+
+        if let Some(window_action) = self.check_prompt() {
+            actions.push(window_action);
+        }
+        if let Some(location) = self.jump_to.take() {
+            actions.push(WindowAction::GotoLocation(location));
+        }
+        self.check_search();
+        if let Some((level, message)) = self.message.take() {
+            actions.push(WindowAction::ShowMessage(level, message));
+        }
+
+        actions
+    }
+
+    pub fn save_file(&mut self) -> Result<FileSaveStatus, Box<dyn Error>> {
+        // FIXME: this has nothing to do with drawing/rendering, MOVE
+        let file_save_status = self.buffer.save_file()?;
+        self.update_status_line();
+        match &file_save_status {
+            FileSaveStatus::NoFilename => {
+                self.start_prompt(Input::new_save_file_input("Save file as", true));
+            }
+            FileSaveStatus::MissingDirectory(dir) => {
+                self.start_prompt(Input::new(
+                    &format!("{} doesn't exist, create it? (y/n)", dir.display()),
+                    PromptAction::CreateDirectory,
+                    true,
+                ));
+            }
+            FileSaveStatus::Saved(_) => {}
+        }
+        Ok(file_save_status)
+    }
+}
+
+// Sections of the same highlight along row, leaving out the empty Normal
+// one a row would otherwise start with
+fn push_row_sections(sections: &mut Vec<HighlightedSection>, row_idx: usize, row: &Row<'_>) {
+    let mut first_char_seen = false;
+    let mut current_section = HighlightedSection::default();
+    current_section.text_row = row_idx;
+    let mut overlay = row.overlay.iter();
+
+    for (col_idx, hl) in row.hl.iter().enumerate() {
+        let char_overlay: Option<Highlight> = overlay.next().cloned().unwrap_or_else(|| None);
+        let overlay_or_hl = char_overlay.unwrap_or_else(|| *hl);
+        if current_section.highlight == overlay_or_hl {
+            current_section.last_col_idx = col_idx;
+        } else {
+            if first_char_seen {
+                sections.push(current_section);
+            }
+            current_section.highlight = overlay_or_hl;
+            current_section.first_col_idx = col_idx;
+            current_section.last_col_idx = col_idx;
+        }
+        first_char_seen = true;
+    }
+
+    if first_char_seen {
+        sections.push(current_section);
+    }
+}
+
+// A pane 40 columns across and 11 rows down, the last being the status line
+#[cfg(test)]
+fn test_pane(buffer: Buffer<'_>) -> Pane<'_> {
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.set_line_height(10.0);
+    pane.set_character_width(5.0);
+    pane.do_action(PaneAction::UpdateSize(vec2(200.0, 110.0), vec2(0.0, 0.0)));
+    pane
+}
+
+#[test]
+fn test_update_highlighted_sections() {
+    use crate::highlight::Highlight;
+
+    let mut buffer = Buffer::default();
+    buffer.set_filename("testfile.c".to_string());
+    buffer.append_row("#include <ctype.h>\r\n");
+    buffer.append_row("#define KILO_VERSION \"0.0.1\"\r\n");
+    buffer.append_row("enum SomeEnum {};\r\n");
+    let mut pane = Pane::new(18.0, 1.0, buffer, true);
+    pane.update_highlighted_sections();
+    let expected_highlights = vec![
+        HighlightedSection {
+            highlight: Highlight::Normal,
+            text_row: 0,
+            first_col_idx: 0,
+            last_col_idx: 18,
+        },
+        HighlightedSection {
+            highlight: Highlight::Normal,
+            text_row: 1,
+            first_col_idx: 0,
+            last_col_idx: 20,
+        },
+        HighlightedSection {
+            highlight: Highlight::String,
+            text_row: 1,
+            first_col_idx: 21,
+            last_col_idx: 27,
+        },
+        HighlightedSection {
+            highlight: Highlight::Normal,
+            text_row: 1,
+            first_col_idx: 28,
+            last_col_idx: 28,
+        },
+        HighlightedSection {
+            highlight: Highlight::Keyword1,
+            text_row: 2,
+            first_col_idx: 0,
+            last_col_idx: 3,
+        },
+        HighlightedSection {
+            highlight: Highlight::Normal,
+            text_row: 2,
+            first_col_idx: 4,
+            last_col_idx: 17,
+        },
+    ];
+    assert_eq!(expected_highlights, pane.highlighted_sections);
+}
+
+#[test]
+fn test_typing_only_rebuilds_changed_sections() {
+    let mut buffer = Buffer::default();
+    buffer.set_filename("big.c".to_string());
+    for i in 0..20_000 {
+        buffer.append_row(&format!("int x{} = {}; // \"{}\"\n", i, i, i));
+    }
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.viewport.set_screen_rows(40);
+    pane.viewport.set_row_offset(9_990.0, 20_000);
+    pane.update_highlighted_sections();
+    pane.move_cursor(|cursor| {
+        cursor.text_row = 10_000;
+        cursor.text_col = 4;
+    });
+    let all_sections = |pane: &Pane<'_>| {
+        let mut sections = Vec::new();
+        for row_idx in pane.sections_rows.clone() {
+            push_row_sections(&mut sections, row_idx, &pane.buffer.rows[row_idx]);
+        }
+        sections
+    };
+
+    let mut actions = vec![BufferAction::InsertChar('y')];
+    actions.push(BufferAction::InsertNewlineAndReturn);
+    // Opening a comment changes how every row after it is highlighted
+    actions.push(BufferAction::InsertChar('/'));
+    actions.push(BufferAction::InsertChar('*'));
+    actions.push(BufferAction::DeleteChar(Direction::Left));
+    actions.push(BufferAction::DeleteChar(Direction::Left));
+    actions.push(BufferAction::DeleteChar(Direction::Left));
+    actions.push(BufferAction::Undo);
+    for action in actions {
+        let action_name = format!("{:?}", action);
+        pane.update_buffer(action);
+        assert_eq!(
+            all_sections(&pane),
+            pane.highlighted_sections,
+            "after {}",
+            action_name
+        );
+        assert!(pane.sections_rows.contains(&9_990));
+        assert!(pane.sections_rows.contains(&10_030));
+    }
+}
+
+#[test]
+fn test_sections_only_for_rows_near_the_screen() {
+    let text: String = (0..10_000).map(|i| format!("line {}\n", i)).collect();
+    let mut pane = Pane::new(12.0, 1.0, Buffer::with_text(&text), true);
+    pane.viewport.set_screen_rows(20);
+    pane.update_highlighted_sections();
+    assert_eq!(0..121, pane.sections_rows);
+    assert_eq!(
+        Some(120),
+        pane.highlighted_sections.last().map(|s| s.text_row)
+    );
+
+    // Scrolling a little stays within the margin
+    pane.viewport.scroll_by(50.0, 0.0, 10_000);
+    pane.update_visible_sections();
+    assert_eq!(0..121, pane.sections_rows);
+
+    pane.viewport.scroll_by(5_000.0, 0.0, 10_000);
+    pane.update_visible_sections();
+    assert_eq!(4_950..5_171, pane.sections_rows);
+    assert_eq!(
+        Some(4_950),
+        pane.highlighted_sections.first().map(|s| s.text_row)
+    );
+}
+
+#[test]
+fn test_lazy_highlighting_keeps_to_the_screen() {
+    let path = crate::utils::temp_path("bim-lazy", ".rs");
+    let text: String = (0..1_000).map(|i| format!("let x = {};\n", i)).collect();
+    std::fs::write(&path, &text).unwrap();
+    let mut buffer = Buffer::default();
+    buffer.highlight_lazily_over(Some(1024));
+    buffer.open(&path.to_string_lossy()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.viewport.set_screen_rows(20);
+    pane.viewport.scroll_by(500.0, 0.0, 1_000);
+    pane.update_highlighted_sections();
+
+    let onscreen = pane.onscreen_rows();
+    assert_eq!(onscreen, pane.sections_rows);
+    assert!(pane.buffer.rows[onscreen.start].is_highlighted());
+    assert!(!pane.buffer.rows[onscreen.start - 1].is_highlighted());
+    assert!(!pane.buffer.rows[onscreen.end].is_highlighted());
+
+    pane.update_buffer(BufferAction::HighlightFully);
+    pane.update_changed_sections();
+    assert!(!pane.buffer.highlights_lazily());
+    assert!(pane.buffer.rows.iter().all(|row| row.is_highlighted()));
+    assert!(pane.sections_rows.len() > onscreen.len());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_minimap_click_scrolls_to_row() {
+    let text: String = (0..10_000).map(|i| format!("line {}\n", i)).collect();
+    let mut pane = Pane::new(12.0, 1.0, Buffer::with_text(&text), true);
+    pane.update_gui(GuiAction::SetLineHeight(20.0));
+    pane.update_gui(GuiAction::SetCharacterWidth(10.0));
+    pane.update_gui(GuiAction::UpdateSize(vec2(800.0, 640.0), vec2(0.0, 0.0)));
+    let full_width = pane.inner_width();
+    pane.update_gui(GuiAction::ToggleMinimap);
+    let minimap = Minimap::new(1.0);
+    assert_eq!(full_width - minimap.width(), pane.inner_width());
+
+    // The minimap's rows get sections along with the ones on screen
+    let minimap_rows = pane.minimap_rows();
+    assert_eq!(0, minimap_rows.start);
+    assert!(minimap_rows.end > pane.onscreen_rows().end);
+    assert!(pane.sections_rows.end >= minimap_rows.end);
+
+    let row_y = |row: usize| pane.top_padding() + (row as f32 + 0.5) * minimap.row_height();
+    let location = vec2(800.0 - minimap.width() / 2.0, row_y(200));
+    pane.do_action(PaneAction::MouseClick(location));
+    let screen_rows = pane.viewport.screen_rows();
+    assert_eq!(200 - screen_rows / 2, pane.viewport.first_row());
+    assert!(pane.is_cursor_onscreen());
+    assert!(pane.drag.is_none());
+
+    pane.update_gui(GuiAction::ToggleMinimap);
+    assert_eq!(full_width, pane.inner_width());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_scrollbar_click_and_drag() {
+    use crate::renderer::RecordingRenderer;
+
+    let text: String = (0..1_000).map(|i| format!("line {}\n", i)).collect();
+    let mut pane = Pane::new(12.0, 1.0, Buffer::with_text(&text), true);
+    pane.update_gui(GuiAction::SetLineHeight(20.0));
+    pane.update_gui(GuiAction::SetCharacterWidth(10.0));
+    pane.update_gui(GuiAction::UpdateSize(vec2(800.0, 640.0), vec2(0.0, 0.0)));
+    let theme = Theme::default();
+    let thumbs = |pane: &Pane<'_>| {
+        let mut renderer = RecordingRenderer::default();
+        pane.render(&mut renderer, true).unwrap();
+        renderer.quads_coloured(theme.gutter_fg.rgb()).len()
+            + renderer.quads_coloured(theme.popup_outline.rgb()).len()
+    };
+    let x = 800.0 - scrollbar::width(1.0) / 2.0;
+
+    // It's hidden, so clicking there is clicking the text
+    pane.update_dt(Duration::from_millis(10));
+    assert_eq!(0, thumbs(&pane));
+    pane.do_action(PaneAction::MouseClick(vec2(x, 100.0)));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(0.0, pane.viewport.row_offset());
+
+    // Scrolling brings it up
+    pane.set_scroll_fraction(0.5);
+    pane.clear_damage();
+    pane.update_dt(Duration::from_millis(10));
+    assert!(pane.is_damaged());
+    assert_eq!(1, thumbs(&pane));
+
+    // Clicking the track near the bottom jumps there
+    let bottom = pane.top_padding() + pane.inner_height();
+    pane.do_action(PaneAction::MouseClick(vec2(x, bottom - 1.0)));
+    assert_eq!(1.0, pane.scroll_fraction());
+    assert!(pane.is_cursor_onscreen());
+    assert!(pane.drag.is_none());
+
+    // Then dragging the thumb scrolls back up
+    pane.do_action(PaneAction::MouseDrag(vec2(x, 0.0)));
+    assert_eq!(0.0, pane.scroll_fraction());
+    assert!(pane.buffer.selection.is_none());
+    pane.update_dt(Duration::from_secs(5));
+    assert_eq!(1, thumbs(&pane));
+    pane.do_action(PaneAction::MouseRelease);
+
+    // It goes once the pane's been still a while
+    pane.clear_damage();
+    pane.update_dt(Duration::from_secs(5));
+    assert!(pane.is_damaged());
+    assert_eq!(0, thumbs(&pane));
+}
+
+#[test]
+fn test_update_highlighted_sections_no_syntax() {
+    use crate::highlight::Highlight;
+
+    let mut buffer = Buffer::default();
+    buffer.set_filename("testfile.txt".to_string());
+    buffer.append_row("This is a test file\r\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.update_highlighted_sections();
+    let expected_highlights = vec![HighlightedSection {
+        highlight: Highlight::Normal,
+        text_row: 0,
+        first_col_idx: 0,
+        last_col_idx: 19,
+    }];
+    assert_eq!(expected_highlights, pane.highlighted_sections);
+}
+
+#[test]
+fn test_drag_past_bottom_scrolls_selection() {
+    let mut buffer = Buffer::default();
+    for _ in 0..100 {
+        buffer.append_row("some text on a line\n");
+    }
+    let mut pane = test_pane(buffer);
+
+    pane.do_action(PaneAction::MouseClick(vec2(20.0, 5.0)));
+    pane.do_action(PaneAction::MouseDrag(vec2(20.0, 150.0)));
+    assert_eq!(0, pane.viewport.first_row());
+    assert_eq!(9, pane.buffer.cursor.text_row());
+
+    pane.update_dt(Duration::from_millis(200));
+    assert_eq!(15, pane.viewport.first_row());
+    assert_eq!(24, pane.buffer.cursor.text_row());
+    let selection = pane.buffer.selection.expect("dragging selects");
+    assert_eq!(0, selection.start().text_row());
+
+    pane.do_action(PaneAction::MouseRelease);
+    pane.update_dt(Duration::from_millis(200));
+    assert_eq!(15, pane.viewport.first_row());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_scrolling_sideways_to_the_cursor() {
+    let line: String = (0..10).map(|_| "0123456789").collect();
+    let mut buffer = Buffer::default();
+    buffer.append_row(&format!("{}\n", line));
+    buffer.append_row("short\n");
+    let mut pane = test_pane(buffer);
+    let screen_cols = pane.screen_cols();
+    assert!(screen_cols < 100);
+
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::end()));
+    let first_col = pane.viewport.first_col();
+    assert_eq!(101 - screen_cols, first_col);
+    let cursor_x = pane.onscreen_cursor(&pane.buffer.cursor).top_left.x();
+    assert_eq!(pane.text_left() + (screen_cols - 1) as f32 * 5.0, cursor_x);
+    // The text drawn starts from the first column on screen
+    pane.update_highlighted_sections();
+    let text: String = pane
+        .section_texts(true)
+        .iter()
+        .map(|section| section.text)
+        .collect();
+    assert_eq!(format!("{}\n\n", &line[first_col..]), text);
+
+    // Clicks land on the columns they're over
+    pane.do_action(PaneAction::MouseClick(vec2(
+        pane.left_padding + 2.5 * 5.0,
+        5.0,
+    )));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!((0, first_col + 2), pane.cursor());
+    assert_eq!(first_col, pane.viewport.first_col());
+
+    // Scrolling down keeps to the same columns
+    pane.do_action(PaneAction::MouseScroll(MouseMove::Lines(vec2(0.0, 1.0))));
+    assert_eq!(first_col, pane.viewport.first_col());
+
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::home()));
+    assert_eq!(0, pane.viewport.first_col());
+}
+
+#[test]
+fn test_double_and_triple_click_select_word_and_line() {
+    let mut buffer = Buffer::default();
+    for _ in 0..10 {
+        buffer.append_row("some text on a line\n");
+    }
+    let mut pane = test_pane(buffer);
+    // In the middle of "text" on the second row
+    let location = vec2(pane.left_padding + 6.5 * 5.0, pane.top_padding() + 15.0);
+    let selected = |pane: &Pane<'_>| {
+        pane.buffer.selection.map(|selection| {
+            let (start, end) = (selection.start(), selection.end());
+            (
+                (start.text_row(), start.text_col()),
+                (end.text_row(), end.text_col()),
+            )
+        })
+    };
+
+    pane.do_action(PaneAction::MouseClick(location));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(None, selected(&pane));
+
+    pane.do_action(PaneAction::MouseDoubleClick(location));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(Some(((1, 5), (1, 9))), selected(&pane));
+    assert_eq!((1, 9), pane.cursor());
+
+    pane.do_action(PaneAction::MouseTripleClick(location));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(Some(((1, 0), (2, 0))), selected(&pane));
+
+    // Just past the end of a word still picks it
+    pane.do_action(PaneAction::MouseDoubleClick(vec2(
+        pane.left_padding + 4.5 * 5.0,
+        pane.top_padding() + 35.0,
+    )));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(Some(((3, 0), (3, 4))), selected(&pane));
+}
+
+#[test]
+fn test_shift_click_extends_selection_from_cursor() {
+    let mut buffer = Buffer::default();
+    for _ in 0..10 {
+        buffer.append_row("some text on a line\n");
+    }
+    let mut pane = test_pane(buffer);
+
+    pane.do_action(PaneAction::MouseClick(vec2(32.0, 15.0)));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(None, pane.buffer.selection);
+
+    pane.do_action(PaneAction::MouseShiftClick(vec2(52.0, 45.0)));
+    pane.do_action(PaneAction::MouseRelease);
+    let selection = pane.buffer.selection.expect("shift click selects");
+    assert_eq!(Cursor::new(1, 4), selection.start());
+    assert_eq!(Cursor::new(4, 8), selection.end());
+
+    pane.do_action(PaneAction::MouseShiftClick(vec2(12.0, 5.0)));
+    let selection = pane.buffer.selection.expect("shift click extends");
+    assert_eq!(Cursor::new(0, 0), selection.start());
+    assert_eq!(Cursor::new(1, 4), selection.end());
+}
+
+#[test]
+fn test_spacing_moves_cursor_and_clicks_together() {
+    let mut buffer = Buffer::default();
+    for _ in 0..10 {
+        buffer.append_row("some text on a line\n");
+    }
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.set_line_height(10.0);
+    pane.set_character_width(5.0);
+    pane.set_spacing(Spacing {
+        left_padding: 20.0,
+        top_padding: 10.0,
+        line_spacing: 1.5,
+    });
+    pane.do_action(PaneAction::UpdateSize(vec2(200.0, 110.0), vec2(0.0, 0.0)));
+    assert_eq!(5, pane.viewport.screen_rows());
+
+    pane.do_action(PaneAction::MouseClick(vec2(36.0, 41.0)));
+    pane.do_action(PaneAction::MouseRelease);
+    assert_eq!(Cursor::new(2, 3), pane.buffer.cursor.current());
+    let cursor_rect = pane.onscreen_cursor(&pane.buffer.cursor);
+    assert_eq!(vec2(35.0, 40.0), cursor_rect.top_left);
+    assert_eq!(vec2(5.0, 15.0), cursor_rect.bounds);
+}
+
+#[test]
+fn test_parked_buffers_are_autosaved() {
+    let first_path = crate::utils::temp_path("bim-autosave-first", ".txt");
+    let second_path = crate::utils::temp_path("bim-autosave-second", ".txt");
+    let first_name = first_path.to_string_lossy().to_string();
+    let second_name = second_path.to_string_lossy().to_string();
+    std::fs::write(&first_path, "first\n").unwrap();
+    std::fs::write(&second_path, "second\n").unwrap();
+    let mut first = Buffer::default();
+    first.open(&first_name).unwrap();
+    let mut second = Buffer::default();
+    second.open(&second_name).unwrap();
+
+    let mut pane = Pane::new(12.0, 1.0, first, true);
+    pane.update_dt(Duration::from_millis(16));
+    pane.update_buffer(BufferAction::InsertChar('!'));
+    pane.add_buffer(second);
+    pane.next_buffer();
+    assert_eq!(Some(second_name), pane.buffer.filename);
+    pane.update_dt(Duration::from_secs(5));
+    let swap_path = crate::swap::swap_path(&first_name);
+    assert!(std::fs::read_to_string(&swap_path)
+        .unwrap()
+        .ends_with("\n!first\n"));
+
+    pane.remove_swap_files();
+    assert!(!swap_path.exists());
+    std::fs::remove_file(&first_path).unwrap();
+    std::fs::remove_file(&second_path).unwrap();
+}
+
+#[test]
+fn test_switching_buffers_keeps_their_place() {
+    let mut first = Buffer::default();
+    first.set_filename("first.txt".to_string());
+    for _ in 0..50 {
+        first.append_row("first buffer\n");
+    }
+    let mut second = Buffer::default();
+    second.set_filename("second.txt".to_string());
+    second.append_row("second buffer\n");
+
+    let mut pane = test_pane(first);
+    pane.add_buffer(second);
+    assert_eq!("first.txt [New] [+] [1/2]", pane.status_line.filename);
+
+    pane.move_cursor(|cursor| cursor.text_row = 30);
+    pane.update_cursor();
+    let first_row = pane.viewport.first_row();
+
+    pane.next_buffer();
+    assert_eq!("second.txt", pane.buffer.name());
+    assert_eq!(0, pane.viewport.first_row());
+    assert_eq!("second.txt [New] [+] [2/2]", pane.status_line.filename);
+
+    pane.next_buffer();
+    assert_eq!("first.txt", pane.buffer.name());
+    assert_eq!(30, pane.buffer.cursor.text_row());
+    assert_eq!(first_row, pane.viewport.first_row());
+
+    pane.switch_to_buffer_named("second");
+    assert_eq!("second.txt", pane.buffer.name());
+    pane.switch_to_buffer_named("1");
+    assert_eq!("first.txt", pane.buffer.name());
+    assert_eq!(
+        vec!["1* first.txt [New] [+]", "2 second.txt [New] [+]"],
+        pane.buffer_names()
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_status_line_clicks_open_prompts() {
+    let mut buffer = Buffer::default();
+    for _ in 0..20 {
+        buffer.append_row("some text on a line\n");
+    }
+    let mut pane = test_pane(buffer);
+    assert_eq!(
+        "[No Name] [+] | no ft | 1:1 | UTF-8 | LF",
+        pane.status_text()
+    );
+
+    // "1:1" starts at col 24, and the status line is the bottom row
+    pane.do_action(PaneAction::MouseClick(vec2(122.0, 105.0)));
+    assert!(pane.prompt.is_some());
+    for c in "12:5".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    pane.check();
+    assert!(pane.prompt.is_none());
+    assert_eq!(Cursor::new(11, 4), pane.buffer.cursor.current());
+
+    pane.do_action(PaneAction::MouseClick(vec2(197.0, 105.0)));
+    for c in "crlf".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    pane.check();
+    assert_eq!("CRLF", pane.status_line.newline);
+
+    // "UTF-8" starts at col 31, after "12:5"
+    pane.do_action(PaneAction::MouseClick(vec2(157.0, 105.0)));
+    for c in "latin-1".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    pane.check();
+    assert_eq!(Encoding::Latin1, pane.buffer.encoding());
+    assert_eq!("Latin-1", pane.status_line.encoding);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_save_as_prompt_and_new_filetype() {
+    let mut buffer = Buffer::default();
+    buffer.set_filename("notes.txt".to_string());
+    buffer.append_row("fn main() {}\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    assert_eq!("no ft", pane.status_line.filetype);
+
+    // It starts with the name the buffer has, to edit
+    pane.do_action(PaneAction::StartRename);
+    let prompt = pane.prompt.as_ref().unwrap();
+    assert_eq!("Rename to: notes.txt", prompt.display_text());
+    for _ in 0.."txt".len() {
+        pane.update_buffer(BufferAction::DeleteChar(Direction::Left));
+    }
+    for c in "rs".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    assert_eq!(
+        vec![WindowAction::RenameFile("notes.rs".to_string())],
+        pane.check()
+    );
+
+    pane.update_buffer(BufferAction::SetFilename("notes.rs".to_string()));
+    assert_eq!("Rust", pane.status_line.filetype);
+    assert_eq!("notes.rs", pane.buffer.name());
+}
+
+#[test]
+fn test_set_newline_converts_every_row() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("one\n");
+    buffer.append_row("two\r\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.update_buffer(BufferAction::SetNewline(Newline::Dos));
+    assert_eq!("CRLF", pane.status_line.newline);
+    assert_eq!("one\r\n", pane.buffer.rows[0].as_str());
+    assert!(pane.buffer.is_dirty());
+}
+
+#[test]
+fn test_replace_confirms_each_match() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("cat cat\n");
+    buffer.append_row("cat\n");
+    buffer.append_row("cat\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    let type_text = |pane: &mut Pane<'_>, text: &str| {
+        for c in text.chars() {
+            pane.update_buffer(BufferAction::InsertChar(c));
+            pane.check();
+        }
+        pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+        pane.check();
+    };
+
+    pane.update_buffer(BufferAction::StartReplace);
+    type_text(&mut pane, "cat");
+    type_text(&mut pane, "dog");
+    assert_eq!(Cursor::new(0, 0), pane.buffer.cursor.current());
+
+    pane.update_buffer(BufferAction::InsertChar('y'));
+    pane.check();
+    assert_eq!("dog cat\n", pane.buffer.rows[0].as_str());
+    assert_eq!(Cursor::new(0, 4), pane.buffer.cursor.current());
+
+    pane.update_buffer(BufferAction::InsertChar('n'));
+    pane.check();
+    assert_eq!(Cursor::new(1, 0), pane.buffer.cursor.current());
+
+    pane.update_buffer(BufferAction::InsertChar('a'));
+    pane.check();
+    assert!(pane.search.is_none());
+    assert_eq!("dog cat\n", pane.buffer.rows[0].as_str());
+    assert_eq!("dog\n", pane.buffer.rows[1].as_str());
+    assert_eq!("dog\n", pane.buffer.rows[2].as_str());
+    assert!(pane.buffer.is_dirty());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_dimming_unfocused_text() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("some text\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.update_highlighted_sections();
+    let colours = |pane: &Pane<'_>, focused: bool| {
+        pane.section_texts(focused)
+            .iter()
+            .map(|section| section.color)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(colours(&pane, true), colours(&pane, false));
+
+    pane.set_dim_unfocused(true);
+    let focused = colours(&pane, true);
+    let unfocused = colours(&pane, false);
+    assert_eq!(focused.len(), unfocused.len());
+    assert!(focused
+        .iter()
+        .zip(unfocused.iter())
+        .all(|(focused, unfocused)| focused != unfocused));
+}
+
+#[test]
+fn test_long_lines_are_counted_and_visited() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("short\n");
+    buffer.append_row("this line is too long\n");
+    buffer.append_row("\ttabbed line\n");
+    buffer.append_row("fits\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.update_status_line();
+    assert_eq!("", pane.status_line.long_lines);
+
+    pane.set_max_line_length(Some(10));
+    assert_eq!("2 long lines", pane.status_line.long_lines);
+
+    pane.click_status(StatusTarget::LongLines);
+    assert_eq!(
+        (1, 10),
+        (pane.buffer.cursor.text_row(), pane.buffer.cursor.text_col())
+    );
+    // The tab takes up the first 8 columns
+    pane.click_status(StatusTarget::LongLines);
+    assert_eq!(
+        (2, 3),
+        (pane.buffer.cursor.text_row(), pane.buffer.cursor.text_col())
+    );
+    pane.click_status(StatusTarget::LongLines);
+    assert_eq!(1, pane.buffer.cursor.text_row());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_search_continues_into_other_buffers() {
+    let mut first = Buffer::default();
+    first.set_filename("first".to_string());
+    first.append_row("a needle\n");
+    first.append_row("nothing\n");
+    let mut second = Buffer::default();
+    second.set_filename("second".to_string());
+    second.append_row("no match\n");
+    let mut third = Buffer::default();
+    third.set_filename("third".to_string());
+    third.append_row("\n");
+    third.append_row("needle again\n");
+    let mut pane = Pane::new(12.0, 1.0, first, true);
+    pane.add_buffer(second);
+    pane.add_buffer(third);
+
+    pane.update_buffer(BufferAction::StartSearch);
+    pane.update_buffer(BufferAction::ToggleSearchAllBuffers);
+    for c in "needle".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+        pane.check();
+    }
+    assert_eq!(Cursor::new(0, 2), pane.buffer.cursor.current());
+
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::down(1)));
+    pane.check();
+    assert_eq!("third", pane.buffer.name());
+    assert_eq!(Cursor::new(1, 0), pane.buffer.cursor.current());
+    let search = pane.search.as_ref().expect("still searching");
+    assert!(search.as_string().ends_with("[third]"));
+
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::down(1)));
+    pane.check();
+    assert_eq!("first", pane.buffer.name());
+    assert_eq!(Cursor::new(0, 2), pane.buffer.cursor.current());
+}
+
+#[test]
+fn test_command_palette_runs_the_picked_command() {
+    use crate::keymap::DEFAULT_KEYMAP;
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("some text\n");
+    buffer.append_row("more text\n");
+    let mut pane = test_pane(buffer);
+
+    pane.start_command_palette(Palette::new(&DEFAULT_KEYMAP));
+    for c in "split".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    // Moving down picks the next match rather than moving the cursor
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::down(1)));
+    assert_eq!(0, pane.buffer.cursor.text_row());
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    assert_eq!(
+        vec![WindowAction::RunCommand("SplitHorizontally".to_string())],
+        pane.check()
+    );
+    assert!(pane.prompt.is_none());
+    assert!(pane.palette.is_none());
+
+    // Nothing matching runs nothing
+    pane.start_command_palette(Palette::new(&DEFAULT_KEYMAP));
+    for c in "xyzzy".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    assert!(pane.check().is_empty());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_command_line_prompt() {
+    let mut pane = Pane::new(12.0, 1.0, Buffer::default(), true);
+    pane.start_command_line();
+    assert_eq!(
+        Some(":"),
+        pane.prompt.as_ref().map(|prompt| prompt.display_text())
+    );
+    for c in "set minimap".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    assert_eq!(
+        vec![WindowAction::RunExCommand("set minimap".to_string())],
+        pane.check()
+    );
+    assert!(pane.prompt.is_none());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_render_draws_through_the_renderer() {
+    use crate::renderer::{DrawCommand, RecordingRenderer};
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("some text\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.update_gui(GuiAction::UpdateSize(vec2(200.0, 100.0), vec2(0.0, 0.0)));
+    pane.update_highlighted_sections();
+
+    let mut renderer = RecordingRenderer::default();
+    pane.render(&mut renderer, true).unwrap();
+    let texts = renderer.texts();
+    assert!(texts.iter().any(|text| text.contains("some text")));
+    assert!(texts.contains(&pane.status_text().as_str()));
+    let status_quads = renderer.quads_coloured(pane.theme.status_bg.rgb());
+    assert_eq!(1, status_quads.len());
+    match status_quads[0] {
+        DrawCommand::Quad { top_left, z, .. } => {
+            assert_eq!(100.0 - pane.line_height, top_left.y());
+            assert_eq!(0.5, *z);
+        }
+        DrawCommand::Text { .. } => unreachable!(),
+    }
+
+    let mut unfocused = RecordingRenderer::default();
+    pane.render(&mut unfocused, false).unwrap();
+    assert_eq!(
+        1,
+        unfocused
+            .quads_coloured(pane.theme.status_unfocused_bg.rgb())
+            .len()
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_diagnostics_are_drawn_and_hovered() {
+    use crate::lsp::{Diagnostic, Severity};
+    use crate::renderer::{DrawCommand, RecordingRenderer};
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("let x = 1;\n");
+    buffer.append_row("let y = x;\n");
+    buffer.set_diagnostics(vec![
+        Diagnostic {
+            row: 1,
+            start_col: 4,
+            end_col: Some(5),
+            severity: Severity::Warning,
+            message: "unused variable `y`".to_string(),
+        },
+        Diagnostic {
+            row: 1,
+            start_col: 8,
+            end_col: Some(9),
+            severity: Severity::Error,
+            message: "mismatched types".to_string(),
+        },
+    ]);
+    let pane = test_pane(buffer);
+
+    let mut renderer = RecordingRenderer::default();
+    pane.render(&mut renderer, true).unwrap();
+    let warning = pane.theme.diagnostic_warning.rgb();
+    match renderer.quads_coloured(warning)[..] {
+        [DrawCommand::Quad {
+            top_left, bounds, ..
+        }] => {
+            assert_eq!(pane.left_padding + 4.0 * 5.0, top_left.x());
+            assert_eq!(5.0, bounds.x());
+        }
+        ref other => panic!("expected one squiggle, got {:?}", other),
+    }
+    // The row's mark is the error's colour, as that's worse
+    assert_eq!(
+        2,
+        renderer
+            .quads_coloured(pane.theme.diagnostic_error.rgb())
+            .len()
+    );
+
+    let over_x = vec2(pane.left_padding + 8.5 * 5.0, pane.top_padding() + 15.0);
+    assert_eq!(
+        Some("mismatched types".to_string()),
+        pane.hover_info(over_x)
+    );
+    let over_let = vec2(pane.left_padding + 1.5 * 5.0, pane.top_padding() + 15.0);
+    assert_eq!(None, pane.hover_info(over_let));
+}
+
+#[test]
+fn test_editing_a_block() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("a = 1\n");
+    buffer.append_row("bb = 2\n");
+    buffer.append_row("c\n");
+    let mut pane = test_pane(buffer);
+    let texts = |pane: &Pane<'_>| {
+        pane.buffer
+            .rows
+            .iter()
+            .map(|row| row.as_str().trim_end().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // With nothing in it, typing goes in on every row
+    pane.buffer.cursor.move_to(0, 1);
+    pane.update_buffer(BufferAction::ToggleBlockSelection);
+    assert_eq!("block", pane.mode_name());
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::down(2)));
+    pane.update_buffer(BufferAction::InsertChar(':'));
+    assert_eq!(vec!["a: = 1", "b:b = 2", "c:"], texts(&pane));
+    pane.update_buffer(BufferAction::DeleteChar(Direction::Left));
+    assert_eq!(vec!["a = 1", "bb = 2", "c"], texts(&pane));
+
+    // Cut out and put back somewhere else
+    pane.update_buffer(BufferAction::ClearCursors);
+    pane.buffer.cursor.move_to(0, 2);
+    pane.update_buffer(BufferAction::ToggleBlockSelection);
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::down(1)));
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::right(2)));
+    pane.update_buffer(BufferAction::CutBlock);
+    assert_eq!(vec!["a 1", "bb 2", "c"], texts(&pane));
+    pane.buffer.cursor.move_to(2, 1);
+    pane.update_buffer(BufferAction::PasteBlock);
+    assert_eq!(vec!["a 1", "bb 2", "c=", "  ="], texts(&pane));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_marks_are_shown_and_saved() {
+    use crate::renderer::RecordingRenderer;
+
+    let mut buffer = Buffer::default();
+    for line in ["one\n", "two\n", "three\n"] {
+        buffer.append_row(line);
+    }
+    buffer.filename = Some("marked.txt".to_string());
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.set_line_height(10.0);
+    pane.set_character_width(5.0);
+    pane.do_action(PaneAction::UpdateSize(vec2(200.0, 50.0), vec2(0.0, 0.0)));
+    pane.update_gutter();
+    let text_left = pane.left_padding;
+
+    pane.buffer.cursor.move_to(1, 2);
+    pane.buffer.set_mark('a');
+    pane.buffer.set_mark('Z');
+    pane.update_gutter();
+    assert_eq!(text_left + 5.0, pane.left_padding);
+    let mut renderer = RecordingRenderer::default();
+    pane.render(&mut renderer, true).unwrap();
+    assert!(renderer
+        .texts()
+        .iter()
+        .any(|text| text.starts_with(" \nZ\n \n")));
+
+    // Typing a line above moves it down with its row
+    pane.buffer.cursor.move_to(0, 0);
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    assert_eq!(Some(Cursor::new(2, 2)), pane.buffer.mark('a'));
+
+    // Only the file's own marks go in the session
+    let saved = pane.session().unwrap();
+    assert_eq!(
+        vec![('a', (2, 2))],
+        saved.marks.clone().into_iter().collect::<Vec<_>>()
+    );
+    pane.buffer.remove_mark('a');
+    pane.restore_session(&saved);
+    assert!(pane.jump_to_mark('a'));
+    assert_eq!(2, pane.buffer.cursor.text_row());
+    assert!(!pane.jump_to_mark('b'));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_completing_words() {
+    use crate::renderer::RecordingRenderer;
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("let counter = country;\n");
+    buffer.append_row("\n");
+    let mut pane = test_pane(buffer);
+    pane.buffer.cursor.move_to(1, 0);
+
+    // Only asking brings it up, until it's set to come up by itself
+    for c in "cou".chars() {
+        pane.update_buffer(BufferAction::InsertChar(c));
+    }
+    assert_eq!(None, pane.completion);
+    pane.update_buffer(BufferAction::Complete);
+    let words = |pane: &Pane<'_>| pane.completion.as_ref().map(|c| c.words.clone());
+    assert_eq!(
+        Some(vec!["counter".to_string(), "country".to_string()]),
+        words(&pane)
+    );
+    pane.update_buffer(BufferAction::ClearCursors);
+    assert_eq!(None, pane.completion);
+
+    pane.set_complete_after(Some(4));
+    pane.update_buffer(BufferAction::InsertChar('n'));
+    pane.update_buffer(BufferAction::InsertChar('t'));
+    assert_eq!(
+        Some(vec!["counter".to_string(), "country".to_string()]),
+        words(&pane)
+    );
+    let mut renderer = RecordingRenderer::default();
+    pane.render(&mut renderer, true).unwrap();
+    assert!(renderer.texts().contains(&"counter\ncountry"));
+
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::down(1)));
+    pane.update_buffer(BufferAction::InsertIndent);
+    assert_eq!(None, pane.completion);
+    assert_eq!("country", pane.buffer.rows[1].as_str().trim_end());
+    assert_eq!(7, pane.buffer.cursor.text_col());
+
+    // Typing something that isn't part of a word closes it
+    pane.buffer.cursor.move_to(1, 3);
+    pane.update_buffer(BufferAction::Complete);
+    assert!(pane.completion.is_some());
+    pane.update_buffer(BufferAction::MoveCursor(MoveCursor::right(1)));
+    assert_eq!(None, pane.completion);
+}
+
+#[test]
+fn test_filling_in_snippets() {
+    let mut pane = test_pane(Buffer::default());
+    pane.set_snippets(Rc::new(vec![
+        Snippet::new("all", "fn", "", "fn ${1:name}($2) {\n\t$0\n}"),
+        Snippet::new("all", "if", "An if", "if $1 {\n\t$0\n}"),
+        Snippet::new(
+            "all",
+            "if",
+            "An if let",
+            "if let ${1:Some(x)} = $2 {\n\t$0\n}",
+        ),
+    ]));
+    let texts = |pane: &Pane<'_>| {
+        pane.buffer
+            .rows
+            .iter()
+            .map(|row| row.as_str().trim_end().to_string())
+            .collect::<Vec<_>>()
+    };
+    let selected = |pane: &Pane<'_>| {
+        pane.buffer.selection.map(|selection| {
+            let (start, end) = (selection.start(), selection.end());
+            let row = pane.buffer.rows[start.text_row() as usize].as_str();
+            row.chars()
+                .skip(start.text_col() as usize)
+                .take((end.text_col() - start.text_col()) as usize)
+                .collect::<String>()
+        })
+    };
+    let type_in = |pane: &mut Pane<'_>, text: &str| {
+        for c in text.chars() {
+            pane.update_buffer(BufferAction::InsertChar(c));
+        }
+    };
+
+    // The default's selected, and typing goes over it
+    type_in(&mut pane, "    fn");
+    pane.update_buffer(BufferAction::InsertIndent);
+    assert_eq!(vec!["    fn name() {", "", "    }"], texts(&pane));
+    assert_eq!(Some("name".to_string()), selected(&pane));
+    type_in(&mut pane, "add");
+    pane.update_buffer(BufferAction::InsertIndent);
+    type_in(&mut pane, "a: u8");
+    assert_eq!("    fn add(a: u8) {", texts(&pane)[0]);
+
+    // Back to the name, and on to the end, after which Tab indents again
+    pane.update_buffer(BufferAction::PrevSnippetStop);
+    assert_eq!(Some("add".to_string()), selected(&pane));
+    pane.update_buffer(BufferAction::InsertIndent);
+    pane.update_buffer(BufferAction::InsertIndent);
+    assert_eq!((1, 8), pane.cursor());
+    assert!(pane.expansion.is_none());
+
+    // Two with the same prefix are picked between
+    pane.buffer.cursor.move_to(2, 5);
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    type_in(&mut pane, "if");
+    pane.update_buffer(BufferAction::InsertIndent);
+    assert!(pane.prompt.is_some());
+    type_in(&mut pane, "2");
+    pane.update_buffer(BufferAction::InsertNewlineAndReturn);
+    pane.check();
+    assert_eq!("if let Some(x) =  {", texts(&pane)[3]);
+    assert_eq!(Some("Some(x)".to_string()), selected(&pane));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_showing_whitespace() {
+    use crate::renderer::RecordingRenderer;
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("a b  \n");
+    buffer.append_row("\tc \n");
+    let mut pane = test_pane(buffer);
+    let trailing = |pane: &Pane<'_>| {
+        let mut renderer = RecordingRenderer::default();
+        pane.render(&mut renderer, true).unwrap();
+        renderer
+            .quads_coloured(pane.theme.trailing_whitespace.rgb())
+            .len()
+    };
+
+    // Not where the cursor is, as that's where it's being typed
+    assert_eq!(2, trailing(&pane));
+    pane.buffer.cursor.move_to(0, 5);
+    assert_eq!(1, trailing(&pane));
+
+    pane.set_show_whitespace(ShowWhitespace::All);
+    let mut renderer = RecordingRenderer::default();
+    pane.render(&mut renderer, true).unwrap();
+    assert!(renderer
+        .texts()
+        .contains(&format!(" · ··\n→{} ·", " ".repeat(7)).as_str()));
+
+    pane.set_show_whitespace(ShowWhitespace::Off);
+    assert_eq!(0, trailing(&pane));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_cursor_styles() {
+    use crate::renderer::{DrawCommand, RecordingRenderer};
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("abc\n");
+    let mut pane = test_pane(buffer);
+    pane.buffer.cursor.move_to(0, 1);
+    let cursor = |pane: &Pane<'_>| {
+        let mut renderer = RecordingRenderer::default();
+        pane.render(&mut renderer, true).unwrap();
+        match renderer.quads_coloured(pane.theme.cursor.rgb())[..] {
+            [DrawCommand::Quad {
+                top_left, bounds, ..
+            }] => Some((*top_left, *bounds)),
+            _ => None,
+        }
+    };
+    let inverted = |pane: &Pane<'_>| pane.section_texts(true).iter().any(|span| span.text == "b");
+
+    let (block_at, block) = cursor(&pane).unwrap();
+    assert_eq!(vec2(5.0, 10.0), block);
+    assert!(inverted(&pane));
+
+    pane.set_cursor_style(CursorStyle::Bar);
+    assert_eq!(Some((block_at, vec2(2.0, 10.0))), cursor(&pane));
+    assert!(!inverted(&pane));
+
+    pane.set_cursor_style(CursorStyle::Underline);
+    assert_eq!(
+        Some((block_at + vec2(0.0, 8.0), vec2(5.0, 2.0))),
+        cursor(&pane)
+    );
+
+    // Blinking off hides it, unless it doesn't blink
+    pane.set_cursor_style(CursorStyle::Block);
+    pane.update_dt(Duration::from_millis(CURSOR_BLINK_INTERVAL + 1));
+    assert_eq!(None, cursor(&pane));
+    assert!(!inverted(&pane));
+    pane.set_cursor_blink(None);
+    pane.update_dt(Duration::from_millis(CURSOR_BLINK_INTERVAL + 1));
+    assert!(cursor(&pane).is_some());
+    assert!(inverted(&pane));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_guides_for_the_filetype() {
+    use crate::renderer::RecordingRenderer;
+
+    let mut buffer = Buffer::default();
+    buffer.append_row("fn main() {}\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    pane.set_line_height(10.0);
+    pane.set_character_width(5.0);
+    pane.do_action(PaneAction::UpdateSize(vec2(700.0, 110.0), vec2(0.0, 0.0)));
+    let guides = |pane: &Pane<'_>| {
+        let mut renderer = RecordingRenderer::default();
+        pane.render(&mut renderer, true).unwrap();
+        renderer.quads_coloured(pane.theme.line_col.rgb()).len()
+    };
+
+    assert_eq!(2, guides(&pane));
+    let mut options = Guides {
+        global: vec![40],
+        ..Guides::default()
+    };
+    options.filetypes.insert("Rust".to_string(), vec![]);
+    pane.set_guides(options);
+    assert_eq!(1, guides(&pane));
+    pane.buffer.set_filetype("Rust");
+    assert_eq!(0, guides(&pane));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_tracking_damage() {
+    let mut buffer = Buffer::default();
+    buffer.append_row("text\n");
+    let mut pane = Pane::new(12.0, 1.0, buffer, true);
+    assert!(pane.is_damaged());
+    pane.clear_damage();
+
+    pane.update_dt(Duration::from_millis(100));
+    assert!(!pane.is_damaged());
+    // The cursor blinking off has to be drawn
+    pane.update_dt(Duration::from_millis(CURSOR_BLINK_INTERVAL));
+    assert!(pane.is_damaged());
+    pane.clear_damage();
+
+    pane.update_buffer(BufferAction::InsertChar('x'));
+    assert!(pane.is_damaged());
+    pane.clear_damage();
+
+    // Nothing blinks while saving power
+    pane.set_power_mode(PowerMode::Saving);
+    pane.clear_damage();
+    pane.update_dt(Duration::from_millis(CURSOR_BLINK_INTERVAL * 2));
+    assert!(!pane.is_damaged());
+}
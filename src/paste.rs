@@ -0,0 +1,45 @@
+// Bracketed paste: terminals that have it turned on wrap whatever's pasted
+// in these, so it can go in all at once as a paste instead of arriving as a
+// long run of typing (with every newline auto-indented).
+pub const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+pub const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PasteInput {
+    // Everything that was pasted, and how many bytes of input it took up
+    Pasted(String, usize),
+    // The paste has started but not all of it has been read yet
+    Incomplete,
+}
+
+// Reads a paste from the start of input, if there's one there
+pub fn parse_bracketed_paste(input: &str) -> Option<PasteInput> {
+    let rest = input.strip_prefix(PASTE_START)?;
+    match rest.find(PASTE_END) {
+        Some(end) => Some(PasteInput::Pasted(
+            rest[..end].to_string(),
+            PASTE_START.len() + end + PASTE_END.len(),
+        )),
+        None => Some(PasteInput::Incomplete),
+    }
+}
+
+#[test]
+fn test_parse_bracketed_paste() {
+    assert_eq!(
+        Some(PasteInput::Pasted("fn main() {\r\n}".to_string(), 26)),
+        parse_bracketed_paste("\x1b[200~fn main() {\r\n}\x1b[201~x")
+    );
+    assert_eq!(
+        Some(PasteInput::Pasted(String::new(), 12)),
+        parse_bracketed_paste("\x1b[200~\x1b[201~")
+    );
+    assert_eq!(
+        Some(PasteInput::Incomplete),
+        parse_bracketed_paste("\x1b[200~half of it")
+    );
+    assert_eq!(None, parse_bracketed_paste("typed \x1b[200~"));
+}
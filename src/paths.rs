@@ -0,0 +1,429 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "bim";
+
+// The XDG base directories, which macOS and Windows have their own
+// versions of
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BaseDir {
+    Config,
+    Data,
+    State,
+}
+
+fn home(var: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    var("HOME")
+        .or_else(|| var("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// var looks up environment variables, so tests can make some up
+fn base_dir(kind: BaseDir, var: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    if cfg!(windows) {
+        let dir = match kind {
+            BaseDir::Config | BaseDir::Data => var("APPDATA"),
+            BaseDir::State => var("LOCALAPPDATA"),
+        };
+        return dir.map(PathBuf::from);
+    }
+    let (xdg_var, xdg_default) = match kind {
+        BaseDir::Config => ("XDG_CONFIG_HOME", ".config"),
+        BaseDir::Data => ("XDG_DATA_HOME", ".local/share"),
+        BaseDir::State => ("XDG_STATE_HOME", ".local/state"),
+    };
+    // The spec says to ignore relative paths
+    if let Some(dir) = var(xdg_var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Some(dir);
+    }
+    let home = home(var)?;
+    if cfg!(target_os = "macos") {
+        Some(home.join("Library").join("Application Support"))
+    } else {
+        Some(home.join(xdg_default))
+    }
+}
+
+fn file_in(kind: BaseDir, name: &str) -> Option<PathBuf> {
+    let var = |name: &str| env::var_os(name);
+    base_dir(kind, &var).map(|dir| dir.join(APP_DIR).join(name))
+}
+
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    file_in(BaseDir::Config, name)
+}
+
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    file_in(BaseDir::Data, name)
+}
+
+pub fn state_file(name: &str) -> Option<PathBuf> {
+    file_in(BaseDir::State, name)
+}
+
+// For sockets and the like, which go in XDG_RUNTIME_DIR when there is one
+pub fn runtime_file(name: &str) -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(APP_DIR).join(name))
+        .or_else(|| state_file(name))
+}
+
+// Where snippets are kept, a file for each filetype
+pub fn snippets_dir() -> Option<PathBuf> {
+    config_file("snippets")
+}
+
+// Where templates for new files are kept, named for the extension they're
+// for, e.g. new.rs
+pub fn templates_dir() -> Option<PathBuf> {
+    config_file("templates")
+}
+
+// Where colour schemes are kept, a file for each
+pub fn themes_dir() -> Option<PathBuf> {
+    config_file("themes")
+}
+
+// Where installed fonts are kept, the user's own before the system's
+pub fn font_dirs() -> Vec<PathBuf> {
+    let var = |name: &str| env::var_os(name);
+    let mut dirs = Vec::new();
+    if let Some(data) = base_dir(BaseDir::Data, &var) {
+        dirs.push(data.join("fonts"));
+    }
+    if let Some(home) = home(&var) {
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join("Library").join("Fonts"));
+    }
+    if let Some(windows) = var("WINDIR") {
+        dirs.push(PathBuf::from(windows).join("Fonts"));
+    }
+    for dir in &[
+        "/usr/local/share/fonts",
+        "/usr/share/fonts",
+        "/Library/Fonts",
+        "/System/Library/Fonts",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+// The config file, which used to be ~/.bimrc
+pub fn rc_file() -> Option<PathBuf> {
+    let path = config_file("bimrc")?;
+    let var = |name: &str| env::var_os(name);
+    if let Some(home) = home(&var) {
+        migrate(&home.join(".bimrc"), &path);
+    }
+    Some(path)
+}
+
+// Where the window was, which used to be kept in the working directory
+pub fn window_state_file() -> Option<PathBuf> {
+    let path = state_file("window_state.yaml")?;
+    migrate(Path::new(".bim_persist_state.yaml"), &path);
+    Some(path)
+}
+
+// The files, cursors and splits open when bim last quit
+pub fn session_file() -> Option<PathBuf> {
+    state_file("session.yaml")
+}
+
+// The debug log, which used to be written to the working directory
+pub fn debug_log_file() -> Option<PathBuf> {
+    let path = state_file("debug.log")?;
+    migrate(Path::new(".xbim_debug"), &path);
+    Some(path)
+}
+
+// The whole of path, from the working directory as it is now, so that it
+// still means the same file once that's changed
+pub fn absolute_path(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    // Leaves out any "." along the way
+    path.components().collect()
+}
+
+// A path as it's shown, from the working directory when it's in there
+pub fn display_path(path: &str) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            Path::new(path)
+                .strip_prefix(dir)
+                .ok()
+                .map(|relative| relative.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| path.to_string())
+}
+
+// Puts the home directory in place of a ~ at the start of path
+pub fn expand_tilde(path: &str) -> String {
+    let var = |name: &str| env::var_os(name);
+    expand_tilde_with(path, &var)
+}
+
+fn expand_tilde_with(path: &str, var: &dyn Fn(&str) -> Option<OsString>) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => match home(var) {
+            Some(home) => format!("{}{}", home.display(), rest),
+            None => path.to_string(),
+        },
+        _ => path.to_string(),
+    }
+}
+
+// Fills in as much of partial as every file it could be has in common, the
+// way a shell does on Tab. When it can only be a directory it gets a slash
+// on the end, to go on into. A ~ is left as it was typed.
+pub fn complete_path(partial: &str) -> Option<String> {
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(idx) => partial.split_at(idx + 1),
+        None => ("", partial),
+    };
+    let expanded = expand_tilde(dir);
+    let search_dir = if expanded.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(&expanded)
+    };
+    let names = fs::read_dir(search_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden files only come up once the . has been typed
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            Some((name, entry.path().is_dir()))
+        })
+        .collect::<Vec<_>>();
+    match names.as_slice() {
+        [] => None,
+        [(name, true)] => Some(format!("{}{}/", dir, name)),
+        [(name, false)] => Some(format!("{}{}", dir, name)),
+        [(first, _), rest @ ..] => {
+            let common = first
+                .char_indices()
+                .find(|(idx, c)| {
+                    rest.iter()
+                        .any(|(name, _)| !name.get(*idx..).is_some_and(|tail| tail.starts_with(*c)))
+                })
+                .map(|(idx, _)| &first[..idx])
+                .unwrap_or(first);
+            Some(format!("{}{}", dir, common))
+        }
+    }
+}
+
+// Makes sure there's a directory to write path into
+pub fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+// Not worth looking through when searching a project, and nothing in them
+// should be replaced
+pub const SKIP_DIRS: &[&str] = &["target", "node_modules"];
+
+// Calls visit with each file under dir, in name order, until it returns
+// false. Hidden files and directories like .git are skipped, as is
+// anything in SKIP_DIRS. Symlinked directories aren't followed, so a link
+// back up the tree can't go round forever or turn up the same files twice.
+pub fn walk_files(dir: &Path, visit: &mut dyn FnMut(&Path) -> bool) -> bool {
+    let mut entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(Result::ok).collect::<Vec<_>>(),
+        Err(_) => return true,
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || SKIP_DIRS.contains(&name.as_str()) {
+            continue;
+        }
+        let path = entry.path();
+        let path = path.strip_prefix("./").unwrap_or(&path);
+        let keep_going = match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk_files(path, visit),
+            Ok(kind) if kind.is_symlink() && path.is_dir() => true,
+            Ok(_) => visit(path),
+            Err(_) => true,
+        };
+        if !keep_going {
+            return false;
+        }
+    }
+    true
+}
+
+// Moves a file from where bim used to keep it, unless there's already one
+// in the new place. Failing to doesn't stop bim starting, it just says so.
+fn migrate(old: &Path, new: &Path) {
+    if new.exists() || !old.is_file() {
+        return;
+    }
+    let moved = create_parent(new).and_then(|_| {
+        // Renaming doesn't work across filesystems
+        fs::rename(old, new).or_else(|_| fs::copy(old, new).and_then(|_| fs::remove_file(old)))
+    });
+    match moved {
+        Ok(()) => println!("Moved {} to {}", old.display(), new.display()),
+        Err(err) => println!(
+            "Couldn't move {} to {}: {}",
+            old.display(),
+            new.display(),
+            err
+        ),
+    }
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_base_dirs_follow_xdg() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| OsString::from(value))
+        }
+    };
+
+    let home_only = env(&[("HOME", "/home/me")]);
+    assert_eq!(
+        Some(PathBuf::from("/home/me/.config")),
+        base_dir(BaseDir::Config, &home_only)
+    );
+    assert_eq!(
+        Some(PathBuf::from("/home/me/.local/share")),
+        base_dir(BaseDir::Data, &home_only)
+    );
+    assert_eq!(
+        Some(PathBuf::from("/home/me/.local/state")),
+        base_dir(BaseDir::State, &home_only)
+    );
+
+    let xdg = env(&[
+        ("HOME", "/home/me"),
+        ("XDG_CONFIG_HOME", "/xdg/config"),
+        ("XDG_STATE_HOME", "relative/state"),
+    ]);
+    assert_eq!(
+        Some(PathBuf::from("/xdg/config")),
+        base_dir(BaseDir::Config, &xdg)
+    );
+    assert_eq!(
+        Some(PathBuf::from("/home/me/.local/state")),
+        base_dir(BaseDir::State, &xdg)
+    );
+
+    assert_eq!(None, base_dir(BaseDir::Config, &env(&[])));
+}
+
+#[test]
+fn test_migrate_moves_old_files() {
+    let dir = crate::utils::temp_path("bim-migrate", "");
+    let old = dir.join("old");
+    let new = dir.join("nested").join("new");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&old, "settings").unwrap();
+
+    migrate(&old, &new);
+    assert!(!old.exists());
+    assert_eq!("settings", fs::read_to_string(&new).unwrap());
+
+    // A file that's already been moved isn't replaced
+    fs::write(&old, "stale").unwrap();
+    migrate(&old, &new);
+    assert_eq!("settings", fs::read_to_string(&new).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_expand_tilde() {
+    let var = |name: &str| match name {
+        "HOME" => Some(OsString::from("/home/me")),
+        _ => None,
+    };
+    assert_eq!("/home/me/notes.txt", expand_tilde_with("~/notes.txt", &var));
+    assert_eq!("/home/me", expand_tilde_with("~", &var));
+    // Other users' homes, and tildes anywhere else, are left alone
+    assert_eq!("~you/notes.txt", expand_tilde_with("~you/notes.txt", &var));
+    assert_eq!("a/~/b", expand_tilde_with("a/~/b", &var));
+    assert_eq!("~/notes.txt", expand_tilde_with("~/notes.txt", &|_| None));
+}
+
+#[test]
+fn test_complete_path() {
+    let dir = crate::utils::temp_path("bim-complete", "");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("readme.md"), "").unwrap();
+    fs::write(dir.join("report_one.txt"), "").unwrap();
+    fs::write(dir.join("report_two.txt"), "").unwrap();
+    fs::write(dir.join(".hidden"), "").unwrap();
+    let path = |name: &str| format!("{}/{}", dir.display(), name);
+
+    assert_eq!(Some(path("src/")), complete_path(&path("s")));
+    assert_eq!(Some(path("readme.md")), complete_path(&path("rea")));
+    // As far as the files it could be have in common
+    assert_eq!(Some(path("re")), complete_path(&path("r")));
+    assert_eq!(Some(path("report_")), complete_path(&path("rep")));
+    assert_eq!(Some(path(".hidden")), complete_path(&path(".")));
+    assert_eq!(None, complete_path(&path("nothing")));
+    assert_eq!(None, complete_path(&path("src/x")));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_walk_files() {
+    let dir = crate::utils::temp_path("bim-walk", "");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::create_dir_all(dir.join("target")).unwrap();
+    fs::write(dir.join("src").join("lib.rs"), "").unwrap();
+    fs::write(dir.join("readme.md"), "").unwrap();
+    fs::write(dir.join(".git").join("HEAD"), "").unwrap();
+    fs::write(dir.join("target").join("bim"), "").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("src").join("loop")).unwrap();
+
+    let mut files = vec![];
+    assert!(walk_files(&dir, &mut |path| {
+        files.push(path.to_path_buf());
+        true
+    }));
+    assert_eq!(
+        vec![dir.join("readme.md"), dir.join("src").join("lib.rs")],
+        files
+    );
+
+    // It stops as soon as it's told to
+    let mut seen = 0;
+    assert!(!walk_files(&dir, &mut |_| {
+        seen += 1;
+        false
+    }));
+    assert_eq!(1, seen);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,37 @@
+// Lets bim sit in the middle of a shell pipeline, as in
+// `somecmd | bim - --stdout | othercmd`. Whatever's piped in is opened as
+// a scratch buffer, and the buffer is written back out on the way out.
+
+use std::io::{self, Read, Write};
+
+pub fn read_stdin() -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    io::stdin().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Sets the real stdout aside for the buffer to be written to later, so
+// everything else bim prints goes to stderr and stays out of the pipeline
+#[cfg(unix)]
+pub fn take_stdout() -> io::Result<Box<dyn Write>> {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+
+    io::stdout().flush()?;
+    let fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stdout = unsafe { File::from_raw_fd(fd) };
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Box::new(stdout))
+}
+
+// There's no moving stdout about here, so anything else printed ends up
+// in the output too
+#[cfg(not(unix))]
+pub fn take_stdout() -> io::Result<Box<dyn Write>> {
+    Ok(Box::new(io::stdout()))
+}
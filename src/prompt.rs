@@ -4,6 +4,24 @@ use crate::row::Row;
 #[derive(Copy, Clone)]
 pub enum PromptAction {
     SaveFile,
+    SwitchBuffer,
+    SetFiletype,
+    GotoLine,
+    ConvertNewlines,
+    ConvertEncoding,
+    ReloadChanged,
+    RecoverSwap,
+    CreateDirectory,
+    OpenHeld,
+    SetTheme,
+    ReplaceInFilesNeedle,
+    ReplaceInFilesWith,
+    RunCommand,
+    ExCommand,
+    PickSnippet,
+    OpenFile,
+    RenameFile,
+    ClosePane,
 }
 
 #[derive(PartialEq)]
@@ -61,11 +79,23 @@ impl<'a> Prompt<'a> {
         self.finished = true;
     }
 
+    // Replaces what's been typed, like when it's been completed
+    pub fn set_input(&mut self, input: &str) {
+        while self.row.as_str().len() > self.prompt_length {
+            self.row.pop_char();
+        }
+        for c in input.chars() {
+            self.row.append_char(c);
+        }
+    }
+
+    #[cfg(feature = "gui")]
     pub fn as_string(&self) -> &str {
         &self.row.render.trim_matches(char::is_control)
     }
 }
 
+#[cfg(feature = "gui")]
 #[test]
 fn test_prompt() {
     let mut prompt = Prompt::new("Save file as", true);
@@ -74,4 +104,7 @@ fn test_prompt() {
     prompt.type_char('h');
     assert_eq!("Save file as: h", prompt.as_string());
     assert_eq!("h", prompt.input());
+    prompt.set_input("src/");
+    assert_eq!("Save file as: src/", prompt.as_string());
+    assert_eq!("src/", prompt.input());
 }
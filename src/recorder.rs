@@ -0,0 +1,96 @@
+use crate::action::Action;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::time::Instant;
+
+// One line of the log: when the action ran (ms since recording started),
+// the revision of the focused buffer just before it ran, and the action
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedAction {
+    pub ms: u128,
+    pub rev: u64,
+    pub action: Action,
+}
+
+// Appends every dispatched action to a file, one JSON object per line
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(filename)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, action: &Action, revision: u64) -> Result<(), Box<dyn Error>> {
+        let entry = RecordedAction {
+            ms: self.started.elapsed().as_millis(),
+            rev: revision,
+            action: action.clone(),
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        // Flush every time, the log is most useful when we're about to crash
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read_log(filename: &str) -> Result<Vec<RecordedAction>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(filename)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+#[test]
+fn test_record_and_read_log() {
+    use crate::action::{BufferAction, PaneAction};
+    use crate::commands::MoveCursor;
+
+    let mut filename = std::env::temp_dir();
+    filename.push(format!("bim-test-record-{}.log", std::process::id()));
+    let filename = filename.to_str().unwrap().to_string();
+
+    let actions = vec![
+        Action::OnBuffer(BufferAction::InsertChar('x')),
+        Action::OnBuffer(BufferAction::MoveCursor(MoveCursor::percent(50))),
+        Action::OnPane(PaneAction::ScrollToFraction(0.5)),
+    ];
+    {
+        let mut recorder = Recorder::create(&filename).unwrap();
+        for (rev, action) in actions.iter().enumerate() {
+            recorder.record(action, rev as u64).unwrap();
+        }
+    }
+
+    let entries = read_log(&filename).unwrap();
+    let _ = std::fs::remove_file(&filename);
+    assert_eq!(
+        actions,
+        entries
+            .iter()
+            .map(|entry| entry.action.clone())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![0, 1, 2],
+        entries.iter().map(|entry| entry.rev).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,667 @@
+use std::error::Error;
+use std::fmt;
+
+// A small regular expression engine, enough for searching a line at a
+// time. Supports literals, ., [classes], \d \w \s (and their negations),
+// \b, ^ and $, groups (capturing and (?:...)), | and the * + ? {m,n}
+// repetitions, each with a lazy ? variant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegexError {
+    message: String,
+    at: usize,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.at)
+    }
+}
+
+impl Error for RegexError {}
+
+// Where a match (and each of its groups) starts and ends, in chars
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub groups: Vec<Option<(usize, usize)>>,
+}
+
+impl Match {
+    // The first group that took part in the match, or else the whole match
+    pub fn focus(&self) -> (usize, usize) {
+        self.groups
+            .iter()
+            .flatten()
+            .next()
+            .copied()
+            .unwrap_or((self.start, self.end))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(from, to) => c >= from && c <= to,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => is_word_char(c) != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Where each group (after the whole match, as group 0) starts and ends
+type Slots = Vec<Option<usize>>;
+
+// The most a {m,n} can ask for, each one is another copy of what's repeated
+const MAX_REPEAT: usize = 1000;
+
+// What a pattern compiles down to. Split tries its first branch before its
+// second, which is what makes repetitions greedy or lazy and alternatives
+// go in order.
+#[derive(Clone, Debug, PartialEq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Save(usize),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+impl Inst {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Inst::Char(expected) => c == *expected,
+            Inst::Any => true,
+            Inst::Class(items, negated) => items.iter().any(|item| item.matches(c)) != *negated,
+            _ => false,
+        }
+    }
+}
+
+fn split(greedy: bool, more: usize, done: usize) -> Inst {
+    if greedy {
+        Inst::Split(more, done)
+    } else {
+        Inst::Split(done, more)
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(items, negated) => program.push(Inst::Class(items.clone(), *negated)),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::WordBoundary(negated) => program.push(Inst::WordBoundary(*negated)),
+        Node::Group(inner, index) => {
+            if let Some(index) = index {
+                program.push(Inst::Save(2 * index + 2));
+            }
+            compile(inner, program);
+            if let Some(index) = index {
+                program.push(Inst::Save(2 * index + 3));
+            }
+        }
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program);
+            }
+        }
+        Node::Alt(options) => {
+            // Every option but the last splits off the ones after it, and
+            // they all jump past the rest when they're done
+            let mut jumps = vec![];
+            for option in &options[..options.len() - 1] {
+                let at = program.len();
+                program.push(Inst::Jump(0));
+                compile(option, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[at] = Inst::Split(at + 1, program.len());
+            }
+            compile(&options[options.len() - 1], program);
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            match max {
+                None => {
+                    let at = program.len();
+                    program.push(Inst::Jump(0));
+                    compile(node, program);
+                    program.push(Inst::Jump(at));
+                    program[at] = split(*greedy, at + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Jump(0));
+                        compile(node, program);
+                    }
+                    let end = program.len();
+                    for at in splits {
+                        program[at] = split(*greedy, at + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The places a match could be up to, in the order they'd be tried
+struct Threads {
+    list: Vec<(usize, Slots)>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Self {
+            list: vec![],
+            seen: vec![false; len],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, RegexError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        };
+        let node = parser.parse_alt()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched )"));
+        }
+        let mut program = vec![Inst::Save(0)];
+        compile(&node, &mut program);
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+        Ok(Self {
+            program,
+            groups: parser.groups,
+        })
+    }
+
+    // The leftmost match starting at or after from. Every way the pattern
+    // could go is stepped through the text together, a char at a time, so
+    // it's one pass however long the line is.
+    pub fn find_at(&self, text: &[char], from: usize) -> Option<Match> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut found: Option<Slots> = None;
+        for pos in from..=text.len() {
+            // Starting here comes after anything that started earlier
+            if found.is_none() {
+                let slots = vec![None; 2 * self.groups + 2];
+                self.add_thread(&mut current, 0, text, pos, slots);
+            } else if current.list.is_empty() {
+                break;
+            }
+            for (pc, slots) in current.list.drain(..) {
+                match &self.program[pc] {
+                    // Anything after this would've been tried later
+                    Inst::Match => {
+                        found = Some(slots);
+                        break;
+                    }
+                    inst => {
+                        if text.get(pos).is_some_and(|&c| inst.matches(c)) {
+                            self.add_thread(&mut next, pc + 1, text, pos + 1, slots);
+                        }
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        found.map(|slots| Match {
+            start: slots[0].unwrap_or(from),
+            end: slots[1].unwrap_or(from),
+            groups: (0..self.groups)
+                .map(|group| match (slots[2 * group + 2], slots[2 * group + 3]) {
+                    (Some(start), Some(end)) => Some((start, end)),
+                    _ => None,
+                })
+                .collect(),
+        })
+    }
+
+    // Every match that doesn't overlap an earlier one, skipping empty ones
+    pub fn find_all(&self, text: &str) -> Vec<Match> {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut matches = vec![];
+        let mut from = 0;
+        while let Some(found) = self.find_at(&chars, from) {
+            if found.end > found.start {
+                from = found.end;
+                matches.push(found);
+            } else {
+                from = found.start + 1;
+            }
+        }
+        matches
+    }
+
+    // Follows pc through everything that doesn't use up a char, adding a
+    // thread for each char (or the match) that can be reached at pos. A
+    // stack rather than recursion, first branches first.
+    fn add_thread(
+        &self,
+        threads: &mut Threads,
+        pc: usize,
+        text: &[char],
+        pos: usize,
+        slots: Slots,
+    ) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if std::mem::replace(&mut threads.seen[pc], true) {
+                continue;
+            }
+            match &self.program[pc] {
+                Inst::Jump(to) => stack.push((*to, slots)),
+                Inst::Split(first, second) => {
+                    stack.push((*second, slots.clone()));
+                    stack.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(pos);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Start => {
+                    if pos == 0 {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::End => {
+                    if pos == text.len() {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::WordBoundary(negated) => {
+                    let before = pos > 0 && is_word_char(text[pos - 1]);
+                    let after = pos < text.len() && is_word_char(text[pos]);
+                    if (before != after) != *negated {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                _ => threads.list.push((pc, slots)),
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> RegexError {
+        RegexError {
+            message: message.to_string(),
+            at: self.pos,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, RegexError> {
+        let mut options = vec![self.parse_concat()?];
+        while self.eat('|') {
+            options.push(self.parse_concat()?);
+        }
+        if options.len() == 1 {
+            Ok(options.remove(0))
+        } else {
+            Ok(Node::Alt(options))
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            nodes.push(self.parse_repeat()?);
+        }
+        match nodes.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(nodes.remove(0)),
+            _ => Ok(Node::Concat(nodes)),
+        }
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, RegexError> {
+        let atom = self.parse_atom()?;
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.parse_counts() {
+                Some(counts) => counts,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        if self.chars[self.pos] != '{' {
+            self.pos += 1;
+        } else {
+            self.skip_counts();
+        }
+        if matches!(atom, Node::Start | Node::End | Node::WordBoundary(_)) {
+            return Err(self.error("nothing to repeat"));
+        }
+        if min.max(max.unwrap_or(0)) > MAX_REPEAT {
+            return Err(self.error("too many repeats"));
+        }
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    fn skip_counts(&mut self) {
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == '}' {
+                break;
+            }
+        }
+    }
+
+    // Reads {m}, {m,} or {m,n} without moving past it. Anything else is
+    // treated as a literal {.
+    fn parse_counts(&self) -> Option<(usize, Option<usize>)> {
+        let rest = self.chars[self.pos..].iter().collect::<String>();
+        let close = rest.find('}')?;
+        let inside = &rest[1..close];
+        let counts = match inside.find(',') {
+            None => {
+                let count = inside.parse().ok()?;
+                (count, Some(count))
+            }
+            Some(comma) => {
+                let min = inside[..comma].parse().ok()?;
+                let max = &inside[comma + 1..];
+                if max.is_empty() {
+                    (min, None)
+                } else {
+                    (min, Some(max.parse().ok()?))
+                }
+            }
+        };
+        Some(counts)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, RegexError> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("unexpected end")),
+        };
+        self.pos += 1;
+        match c {
+            '(' => {
+                let index = if self.eat('?') {
+                    if !self.eat(':') {
+                        return Err(self.error("unknown group type"));
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups - 1)
+                };
+                let inner = self.parse_alt()?;
+                if !self.eat(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                Ok(Node::Group(Box::new(inner), index))
+            }
+            '[' => self.parse_class(),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '*' | '+' | '?' => Err(self.error("nothing to repeat")),
+            '\\' => self.parse_escape(),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, RegexError> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("trailing \\")),
+        };
+        self.pos += 1;
+        Ok(match c {
+            'b' => Node::WordBoundary(false),
+            'B' => Node::WordBoundary(true),
+            _ => match class_escape(c) {
+                Some(item) => Node::Class(vec![item], false),
+                None => Node::Char(literal_escape(c)),
+            },
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Node, RegexError> {
+        let negated = self.eat('^');
+        let mut items = vec![];
+        let mut first = true;
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unclosed [")),
+            };
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let from = if c == '\\' {
+                let escaped = match self.peek() {
+                    Some(escaped) => escaped,
+                    None => return Err(self.error("unclosed [")),
+                };
+                self.pos += 1;
+                if let Some(item) = class_escape(escaped) {
+                    items.push(item);
+                    continue;
+                }
+                literal_escape(escaped)
+            } else {
+                c
+            };
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.pos + 1).map(|&c| c != ']') == Some(true);
+            if is_range {
+                self.pos += 1;
+                let to = match self.peek() {
+                    Some('\\') => {
+                        self.pos += 1;
+                        self.peek().map(literal_escape)
+                    }
+                    to => to,
+                };
+                let to = to.ok_or_else(|| self.error("unclosed ["))?;
+                self.pos += 1;
+                if to < from {
+                    return Err(self.error("backwards range"));
+                }
+                items.push(ClassItem::Range(from, to));
+            } else {
+                items.push(ClassItem::Range(from, from));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+}
+
+fn class_escape(c: char) -> Option<ClassItem> {
+    match c {
+        'd' => Some(ClassItem::Digit(false)),
+        'D' => Some(ClassItem::Digit(true)),
+        'w' => Some(ClassItem::Word(false)),
+        'W' => Some(ClassItem::Word(true)),
+        's' => Some(ClassItem::Space(false)),
+        'S' => Some(ClassItem::Space(true)),
+        _ => None,
+    }
+}
+
+fn literal_escape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+fn spans(pattern: &str, text: &str) -> Vec<(usize, usize)> {
+    Regex::new(pattern)
+        .unwrap()
+        .find_all(text)
+        .iter()
+        .map(|found| (found.start, found.end))
+        .collect()
+}
+
+#[test]
+fn test_regex_basics() {
+    assert_eq!(vec![(4, 7)], spans("b.r", "foo bar"));
+    assert_eq!(vec![(0, 3), (4, 6)], spans("[a-z]+", "abc de"));
+    assert_eq!(vec![(4, 6)], spans(r"\d+", "abc 42"));
+    assert_eq!(vec![(0, 3)], spans("^foo", "foo foo"));
+    assert_eq!(vec![(4, 7)], spans("foo$", "foo foo"));
+    assert_eq!(vec![(0, 3), (4, 7)], spans("cat|dog", "cat dog"));
+    assert_eq!(vec![(0, 1), (4, 5)], spans(r"\bf", "foo foo"));
+    assert_eq!(vec![(0, 4)], spans("a{2,}b", "aaab"));
+    assert_eq!(vec![(0, 3)], spans("a{3}", "aaaa"));
+    assert_eq!(vec![(0, 3)], spans("x{y", "x{y"));
+    assert_eq!(vec![(1, 2)], spans("[^a]", "ab"));
+    assert_eq!(vec![(0, 3)], spans(r"\(.\)", "(x)"));
+    assert!(spans("z*", "abc").is_empty());
+}
+
+#[test]
+fn test_regex_backtracks_and_is_lazy() {
+    assert_eq!(vec![(0, 11)], spans("<.*>", "<a> and <b>"));
+    assert_eq!(vec![(0, 3), (8, 11)], spans("<.*?>", "<a> and <b>"));
+    assert_eq!(vec![(0, 4)], spans("(a|ab)(c|bcd)d?", "abcdxx"));
+    assert_eq!(vec![(0, 4)], spans("(a*)*b", "aaab"));
+}
+
+#[test]
+fn test_regex_captures() {
+    let regex = Regex::new(r"fn (\w+)\((\w*)\)").unwrap();
+    let found = regex.find_all("pub fn main() {}");
+    assert_eq!(1, found.len());
+    assert_eq!((4, 13), (found[0].start, found[0].end));
+    assert_eq!(vec![Some((7, 11)), Some((12, 12))], found[0].groups);
+    assert_eq!((7, 11), found[0].focus());
+
+    let found = Regex::new("(?:x)y").unwrap().find_all("xy");
+    assert_eq!((0, 2), found[0].focus());
+}
+
+#[test]
+fn test_regex_on_long_lines() {
+    let line = "a".repeat(100_000);
+    assert!(spans("a.*b", &line).is_empty());
+    assert!(spans("(a|b)*c", &line).is_empty());
+    let line = format!("{}b", line);
+    assert_eq!(vec![(0, 100_001)], spans("a.*b", &line));
+    assert_eq!(vec![(0, 100_001)], spans("(?:a+?)*b", &line));
+}
+
+#[test]
+fn test_regex_errors() {
+    assert!(Regex::new("(abc").is_err());
+    assert!(Regex::new("abc)").is_err());
+    assert!(Regex::new("[abc").is_err());
+    assert!(Regex::new("*a").is_err());
+    assert!(Regex::new("[z-a]").is_err());
+    assert!(Regex::new("a\\").is_err());
+    assert!(Regex::new("a{2000}").is_err());
+}
@@ -0,0 +1,153 @@
+use crate::line_spacing::LineSpacing;
+use crate::rect::Rect;
+use glam::{vec2, Vec2};
+use std::error::Error;
+
+// A run of text all in the one colour
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextSpan<'t> {
+    pub text: &'t str,
+    pub color: [f32; 4],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextAlign {
+    // position is the top left of the text
+    TopLeft,
+    // position is the middle of the text
+    Centre,
+}
+
+// Text to lay out from position, cut off at bounds. Lines are spread apart
+// by line_spacing when there is one, and lifted moves the lot up that many
+// pixels, for a row that's partly scrolled off the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text<'t> {
+    pub spans: Vec<TextSpan<'t>>,
+    pub position: Vec2,
+    pub bounds: Vec2,
+    pub scale: f32,
+    pub z: f32,
+    pub align: TextAlign,
+    pub line_spacing: Option<LineSpacing>,
+    pub lifted: f32,
+}
+
+impl<'t> Default for Text<'t> {
+    fn default() -> Self {
+        Self {
+            spans: vec![],
+            position: vec2(0.0, 0.0),
+            bounds: vec2(f32::INFINITY, f32::INFINITY),
+            scale: 16.0,
+            z: 1.0,
+            align: TextAlign::TopLeft,
+            line_spacing: None,
+            lifted: 0.0,
+        }
+    }
+}
+
+impl<'t> Text<'t> {
+    // Text in just the one colour
+    pub fn plain(text: &'t str, color: [f32; 4]) -> Self {
+        Self {
+            spans: vec![TextSpan { text, color }],
+            ..Self::default()
+        }
+    }
+}
+
+// What panes and the container draw with, so they don't need to know how
+// it ends up on screen. Smaller z is in front.
+pub trait Renderer {
+    fn draw_quad(&mut self, color: [f32; 3], rect: Rect, z: f32);
+
+    fn draw_text(&mut self, text: Text<'_>) -> Result<(), Box<dyn Error>>;
+
+    // How much room text takes up once it's laid out, if there's anything
+    // in it to see
+    fn measure(&mut self, text: &Text<'_>) -> Option<Vec2>;
+}
+
+// Something drawn, as a RecordingRenderer saw it
+#[cfg(test)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    Quad {
+        color: [f32; 3],
+        top_left: Vec2,
+        bounds: Vec2,
+        z: f32,
+    },
+    Text {
+        text: String,
+        position: Vec2,
+        z: f32,
+    },
+}
+
+// Keeps everything it's asked to draw, for tests to look through. Every
+// character is measured as half the scale wide and the scale high.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingRenderer {
+    pub commands: Vec<DrawCommand>,
+}
+
+#[cfg(test)]
+impl RecordingRenderer {
+    pub fn texts(&self) -> Vec<&str> {
+        self.commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::Text { text, .. } => Some(text.as_str()),
+                DrawCommand::Quad { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn quads_coloured(&self, color: [f32; 3]) -> Vec<&DrawCommand> {
+        self.commands
+            .iter()
+            .filter(|command| match command {
+                DrawCommand::Quad { color: quad, .. } => *quad == color,
+                DrawCommand::Text { .. } => false,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Renderer for RecordingRenderer {
+    fn draw_quad(&mut self, color: [f32; 3], rect: Rect, z: f32) {
+        self.commands.push(DrawCommand::Quad {
+            color,
+            top_left: rect.top_left,
+            bounds: rect.bounds,
+            z,
+        });
+    }
+
+    fn draw_text(&mut self, text: Text<'_>) -> Result<(), Box<dyn Error>> {
+        self.commands.push(DrawCommand::Text {
+            text: text.spans.iter().map(|span| span.text).collect(),
+            position: text.position,
+            z: text.z,
+        });
+        Ok(())
+    }
+
+    fn measure(&mut self, text: &Text<'_>) -> Option<Vec2> {
+        let joined: String = text.spans.iter().map(|span| span.text).collect();
+        let lines = joined.lines().collect::<Vec<_>>();
+        let widest = lines.iter().map(|line| line.chars().count()).max()?;
+        if widest == 0 {
+            return None;
+        }
+        Some(vec2(
+            widest as f32 * text.scale / 2.0,
+            lines.len() as f32 * text.scale,
+        ))
+    }
+}
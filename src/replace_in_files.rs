@@ -0,0 +1,153 @@
+// Replacing text across a whole project. The matches are shown as a
+// preview first, which can be edited to leave some of them out, and then
+// the preview is applied to the files' buffers.
+
+use crate::grep::Hit;
+use std::path::PathBuf;
+
+const NEEDLE: &str = "Replace: ";
+const REPLACEMENT: &str = "With: ";
+const INCLUDED: &str = "[x] ";
+const EXCLUDED: &str = "[ ] ";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    // Row index and text of each row with a match
+    pub rows: Vec<(usize, String)>,
+}
+
+// What's left to do once the preview's been looked over
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub needle: String,
+    pub replacement: String,
+    pub files: Vec<(PathBuf, Vec<usize>)>,
+}
+
+impl FileMatches {
+    // A file's worth of hits from grep
+    pub fn from_hits(hits: &[Hit]) -> Option<Self> {
+        let path = hits.first()?.path.clone();
+        let rows = hits.iter().map(|hit| (hit.row, hit.line.clone())).collect();
+        Some(Self { path, rows })
+    }
+}
+
+pub fn preview(needle: &str, replacement: &str, matches: &[FileMatches]) -> String {
+    let mut text = format!(
+        "{}{}\n{}{}\n# Change [x] to [ ] to leave a line out, then ApplyReplaceInFiles\n",
+        NEEDLE, needle, REPLACEMENT, replacement
+    );
+    for file in matches {
+        text.push_str(&format!("\n{}\n", file.path.display()));
+        for (row, line) in &file.rows {
+            text.push_str(&format!("{}{}: {}\n", INCLUDED, row + 1, line));
+        }
+    }
+    text
+}
+
+// Reads back a preview, which may have been edited
+pub fn parse_preview(text: &str) -> Result<Plan, String> {
+    let mut lines = text.lines();
+    let needle = lines
+        .next()
+        .and_then(|line| line.strip_prefix(NEEDLE))
+        .ok_or("This isn't a replace in files preview")?;
+    let replacement = lines
+        .next()
+        .and_then(|line| line.strip_prefix(REPLACEMENT))
+        .ok_or("The preview doesn't say what to replace with")?;
+    let mut files: Vec<(PathBuf, Vec<usize>)> = vec![];
+    for line in lines {
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with(EXCLUDED) {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix(INCLUDED) {
+            let row = rest
+                .split(':')
+                .next()
+                .and_then(|number| number.parse::<usize>().ok())
+                .filter(|number| *number > 0)
+                .ok_or_else(|| format!("No line number in {:?}", line))?;
+            match files.last_mut() {
+                Some((_, rows)) => rows.push(row - 1),
+                None => return Err(format!("{:?} isn't under a file", line)),
+            }
+        } else {
+            files.push((PathBuf::from(line), vec![]));
+        }
+    }
+    files.retain(|(_, rows)| !rows.is_empty());
+    Ok(Plan {
+        needle: needle.to_string(),
+        replacement: replacement.to_string(),
+        files,
+    })
+}
+
+#[test]
+fn test_preview_round_trip() {
+    let matches = vec![
+        FileMatches {
+            path: PathBuf::from("src/one.rs"),
+            rows: vec![(0, "let old = 1;".to_string()), (4, "old()".to_string())],
+        },
+        FileMatches {
+            path: PathBuf::from("two.txt"),
+            rows: vec![(2, "old news".to_string())],
+        },
+    ];
+    let text = preview("old", "new", &matches);
+    let plan = parse_preview(&text).unwrap();
+    assert_eq!("old", plan.needle);
+    assert_eq!("new", plan.replacement);
+    assert_eq!(
+        vec![
+            (PathBuf::from("src/one.rs"), vec![0, 4]),
+            (PathBuf::from("two.txt"), vec![2])
+        ],
+        plan.files
+    );
+
+    // Unticked lines are left out, along with files that have none left
+    let edited = text.replace("[x] 5:", "[ ] 5:").replace("[x] 3:", "[ ] 3:");
+    let plan = parse_preview(&edited).unwrap();
+    assert_eq!(vec![(PathBuf::from("src/one.rs"), vec![0])], plan.files);
+
+    assert!(parse_preview("some other buffer\n").is_err());
+}
+
+#[test]
+fn test_find_in_files() {
+    use crate::grep::{Grep, Pattern};
+    use std::fs;
+    use std::sync::mpsc::TryRecvError;
+
+    let dir = crate::utils::temp_path("bim-replace", "");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(
+        dir.join("src").join("lib.rs"),
+        "fn old() {}\nfn other() {}\n",
+    )
+    .unwrap();
+    fs::write(dir.join(".git").join("HEAD"), "old\n").unwrap();
+    fs::write(dir.join("binary"), b"old\xFF\n").unwrap();
+
+    let mut grep = Grep::spawn(&dir, Pattern::Literal("old".to_string()));
+    let mut found = vec![];
+    loop {
+        match grep.try_recv() {
+            Ok(hits) => found.extend(FileMatches::from_hits(&hits)),
+            Err(TryRecvError::Empty) => std::thread::sleep(std::time::Duration::from_millis(5)),
+            Err(TryRecvError::Disconnected) => break,
+        }
+    }
+    assert_eq!(1, found.len());
+    assert_eq!(dir.join("src").join("lib.rs"), found[0].path);
+    assert_eq!(vec![(0, "fn old() {}".to_string())], found[0].rows);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,8 +1,11 @@
 use crate::config::TAB_STOP;
 use crate::highlight::Highlight;
 use crate::syntax::Syntax;
+use crate::unicode::ColumnWidths;
 use crate::utils::char_position_to_byte_position;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::ops::Range;
 use std::rc::Weak;
 
 const SEPARATORS: &str = ",.()+-/*=~%<>[];";
@@ -15,6 +18,7 @@ pub const DEFAULT_NEWLINE_STR: &str = DOS_NEWLINE;
 pub const DEFAULT_NEWLINE_STR: &str = UNIX_NEWLINE;
 
 #[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Newline {
     Unix,
     Dos,
@@ -31,37 +35,53 @@ impl fmt::Display for Newline {
     }
 }
 
+impl Newline {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Unix => UNIX_NEWLINE,
+            Newline::Dos => DOS_NEWLINE,
+            Newline::Unknown => DEFAULT_NEWLINE_STR,
+        }
+    }
+}
+
 #[cfg(windows)]
 pub const DEFAULT_NEWLINE: Newline = Newline::Dos;
 #[cfg(not(windows))]
 pub const DEFAULT_NEWLINE: Newline = Newline::Unix;
 
+// Where a character of the text ends up: render_idx is which character of
+// the rendered string it is (what hl and overlay are indexed by) and
+// render_cursor is the column it's drawn at. Tabs and wide characters take
+// up more than one column, and combining marks none at all.
 struct RenderCursor {
     text_cursor: i32,
+    render_idx: i32,
     render_cursor: i32,
 }
 
-impl RenderCursor {
-    fn new(text_cursor: i32, render_cursor: i32) -> Self {
-        Self {
-            text_cursor,
-            render_cursor,
-        }
-    }
-}
-
 struct RenderCursorIter<'a> {
     text_cursor: i32,
+    render_idx: i32,
     render_cursor: i32,
+    // The column of the last character that took up any room, which is
+    // where anything joined onto it is drawn
+    cluster_cursor: i32,
+    tab_width: i32,
+    widths: ColumnWidths,
     source: std::str::Chars<'a>,
 }
 
 impl<'a> RenderCursorIter<'a> {
-    fn new(source: std::str::Chars<'a>) -> Self {
+    fn new(source: std::str::Chars<'a>, tab_width: usize) -> Self {
         Self {
             source,
             text_cursor: 0,
+            render_idx: 0,
             render_cursor: 0,
+            cluster_cursor: 0,
+            tab_width: tab_width as i32,
+            widths: ColumnWidths::default(),
         }
     }
 }
@@ -70,31 +90,92 @@ impl<'a> Iterator for RenderCursorIter<'a> {
     type Item = RenderCursor;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(source_char) = self.source.next() {
-            let item = RenderCursor::new(self.text_cursor, self.render_cursor);
-            if source_char == '\t' {
-                self.render_cursor +=
-                    (TAB_STOP as i32 - 1) - (self.render_cursor % TAB_STOP as i32);
-            }
-            self.render_cursor += 1;
-            self.text_cursor += 1;
-            Some(item)
+        let source_char = self.source.next()?;
+        let (render_len, width) = if source_char == '\t' {
+            let spaces = self.tab_width - (self.render_cursor % self.tab_width);
+            (spaces, spaces)
         } else {
-            None
+            (1, self.widths.width_of(source_char) as i32)
+        };
+        if width > 0 {
+            self.cluster_cursor = self.render_cursor;
+        }
+        let item = RenderCursor {
+            text_cursor: self.text_cursor,
+            render_idx: self.render_idx,
+            render_cursor: self.cluster_cursor,
+        };
+        self.render_cursor += width;
+        self.render_idx += render_len;
+        self.text_cursor += 1;
+        Some(item)
+    }
+}
+
+// A number in the text, from its minus sign or 0x to the end of its digits
+struct NumberAt {
+    start: usize,
+    digits: Range<usize>,
+    hex: bool,
+    negative: bool,
+}
+
+// The number starting at chars[at], if there is one. A minus sign counts
+// unless it's straight after a word, like the one in x-1.
+fn number_at(chars: &[char], at: usize) -> Option<NumberAt> {
+    let digits_end = |from: usize, is_digit: fn(&char) -> bool| {
+        (from..chars.len())
+            .find(|&idx| !is_digit(&chars[idx]))
+            .unwrap_or(chars.len())
+    };
+    let after_word = at > 0 && (chars[at - 1].is_alphanumeric() || chars[at - 1] == '_');
+    match (chars.get(at), chars.get(at + 1), chars.get(at + 2)) {
+        (Some('0'), Some('x' | 'X'), Some(c)) if c.is_ascii_hexdigit() => Some(NumberAt {
+            start: at,
+            digits: at + 2..digits_end(at + 2, char::is_ascii_hexdigit),
+            hex: true,
+            negative: false,
+        }),
+        (Some('-'), Some(c), _) if c.is_ascii_digit() && !after_word => Some(NumberAt {
+            start: at,
+            digits: at + 1..digits_end(at + 1, char::is_ascii_digit),
+            hex: false,
+            negative: true,
+        }),
+        (Some(c), _, _) if c.is_ascii_digit() => Some(NumberAt {
+            start: at,
+            digits: at..digits_end(at, char::is_ascii_digit),
+            hex: false,
+            negative: false,
+        }),
+        _ => None,
+    }
+}
+
+// The first number the cursor at col is on or before
+fn find_number(chars: &[char], col: usize) -> Option<NumberAt> {
+    let mut at = 0;
+    while at < chars.len() {
+        match number_at(chars, at) {
+            Some(number) if number.digits.end > col => return Some(number),
+            Some(number) => at = number.digits.end,
+            None => at += 1,
         }
     }
+    None
 }
 
-#[derive(Default)]
 pub struct Row<'a> {
     chars: String,
     pub size: usize,
     pub render: String,
     rsize: usize,
+    width: usize,
     pub hl: Vec<Highlight>,
     pub overlay: Vec<Option<Highlight>>,
     syntax: Weak<Option<&'a Syntax<'a>>>,
     pub hl_open_comment: bool,
+    tab_width: usize,
 }
 
 impl<'a> PartialEq for Row<'a> {
@@ -103,6 +184,7 @@ impl<'a> PartialEq for Row<'a> {
             && self.size == other.size
             && self.render == other.render
             && self.rsize == other.rsize
+            && self.width == other.width
             && self.hl == other.hl
             && self.overlay == other.overlay
             && self.hl_open_comment == other.hl_open_comment
@@ -122,23 +204,25 @@ impl<'a> fmt::Debug for Row<'a> {
 }
 
 impl<'a> Row<'a> {
-    pub fn new(text: &str, syntax: Weak<Option<&'a Syntax<'a>>>) -> Self {
+    pub fn new(text: &str, syntax: Weak<Option<&'a Syntax<'a>>>, tab_width: usize) -> Self {
         let mut row = Row {
             chars: String::new(),
             size: 0,
             render: String::new(),
             rsize: 0,
+            width: 0,
             hl: vec![],
             overlay: vec![],
             hl_open_comment: false,
             syntax,
+            tab_width,
         };
         row.set_text(text);
         row
     }
 
     pub fn new_wo_syntax(text: &str) -> Self {
-        Self::new(text, Weak::new())
+        Self::new(text, Weak::new(), TAB_STOP)
     }
 
     pub fn set_text(&mut self, text: &str) {
@@ -152,6 +236,14 @@ impl<'a> Row<'a> {
         self.update();
     }
 
+    // Tabs are drawn out to the next multiple of tab_width columns
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        if self.tab_width != tab_width {
+            self.tab_width = tab_width;
+            self.update();
+        }
+    }
+
     pub fn get_indent(&self) -> i32 {
         let mut indent = 0;
 
@@ -175,6 +267,12 @@ impl<'a> Row<'a> {
         indent
     }
 
+    // The spaces and tabs the row starts with, whatever the syntax
+    pub fn indent_text(&self) -> &str {
+        let text = self.as_str();
+        &text[..text.len() - text.trim_start_matches([' ', '\t']).len()]
+    }
+
     pub fn set_indent(&mut self, indent: i32) {
         let cur_indent = self.get_indent();
         if cur_indent < indent {
@@ -201,23 +299,29 @@ impl<'a> Row<'a> {
     fn update_render(&mut self) {
         self.render.clear();
         let mut rsize = 0;
+        let mut width = 0;
+        let mut widths = ColumnWidths::default();
         for source_char in self.chars.chars() {
             if source_char == '\t' {
                 self.render.push(' ');
                 rsize += 1;
-                while rsize % TAB_STOP != 0 {
+                width += 1;
+                while width % self.tab_width != 0 {
                     self.render.push(' ');
                     rsize += 1;
+                    width += 1;
                 }
             } else if source_char == '\n' || source_char == '\r' {
                 continue;
             } else {
                 self.render.push(source_char);
                 rsize += 1;
+                width += widths.width_of(source_char);
             }
         }
         self.render.push('\n'); // Internally use unix line endings ignoring source line endings
         self.rsize = rsize;
+        self.width = width;
     }
 
     fn is_separator(&self, c: char) -> bool {
@@ -232,7 +336,71 @@ impl<'a> Row<'a> {
         }
     }
 
+    // Left to be highlighted when it's next shown
+    pub fn clear_highlight(&mut self) {
+        self.hl.clear();
+    }
+
+    pub fn is_highlighted(&self) -> bool {
+        !self.hl.is_empty() || self.render.is_empty()
+    }
+
     pub fn update_syntax_highlight(&mut self, previous_ml_comment: bool) -> bool {
+        let in_comment = self.highlight_syntax(previous_ml_comment);
+        self.highlight_trailing_whitespace();
+        in_comment
+    }
+
+    // Spaces and tabs at the end of the line are picked out whatever
+    // they're in, so they can be shown up
+    fn highlight_trailing_whitespace(&mut self) {
+        let trailing = self
+            .render
+            .trim_end_matches('\n')
+            .chars()
+            .rev()
+            .take_while(|c| *c == ' ')
+            .count();
+        for hl in self
+            .hl
+            .iter_mut()
+            .take(self.rsize)
+            .skip(self.rsize - trailing)
+        {
+            *hl = Highlight::TrailingWhitespace;
+        }
+    }
+
+    // Where the spaces and tabs at the end of the line start
+    pub fn trailing_whitespace_start(&self) -> usize {
+        let text = self.as_str().trim_end_matches(['\r', '\n']);
+        text.trim_end_matches([' ', '\t']).chars().count()
+    }
+
+    // A line as wide as the row is on screen, with a mark to go under each
+    // space and tab and spaces under everything else
+    pub fn whitespace_marks(&self) -> String {
+        let starts = self
+            .to_render_cursor_iter()
+            .map(|at| at.render_cursor as usize)
+            .collect::<Vec<_>>();
+        let mut marks = String::new();
+        for (idx, c) in self.as_str().chars().take(self.size).enumerate() {
+            let width = starts.get(idx + 1).copied().unwrap_or(self.width) - starts[idx];
+            if width == 0 {
+                continue;
+            }
+            marks.push(match c {
+                ' ' => '·',
+                '\t' => '→',
+                _ => ' ',
+            });
+            marks.extend(std::iter::repeat_n(' ', width - 1));
+        }
+        marks
+    }
+
+    fn highlight_syntax(&mut self, previous_ml_comment: bool) -> bool {
         use crate::highlight::Highlight::*;
 
         self.hl.clear();
@@ -241,7 +409,7 @@ impl<'a> Row<'a> {
             .upgrade()
             .unwrap_or_else(|| std::rc::Rc::new(None));
         if syntax.is_none() {
-            for _ in 0..=self.rsize {
+            for _ in self.render.chars() {
                 self.hl.push(Normal);
             }
             return false;
@@ -271,7 +439,11 @@ impl<'a> Row<'a> {
                 continue;
             }
 
-            if syntax.highlight_singleline_comments() && in_string.is_none() && !in_comment {
+            if syntax.highlight_singleline_comments()
+                && in_string.is_none()
+                && !in_comment
+                && (idx == 0 || !syntax.comment_lines_only())
+            {
                 let rest_of_line = &self.render[idx..];
                 if rest_of_line.starts_with(syntax.singleline_comment_start) {
                     for _ in idx..self.rsize {
@@ -349,14 +521,73 @@ impl<'a> Row<'a> {
         in_comment
     }
 
+    // Picks out plain text from render_col on, leaving anything that's
+    // already highlighted alone
+    pub fn highlight_from(&mut self, render_col: usize, highlight: Highlight) {
+        let from = self
+            .to_render_cursor_iter()
+            .find(|render_cursor| render_cursor.render_cursor >= render_col as i32)
+            .map(|render_cursor| render_cursor.render_idx as usize)
+            .unwrap_or(self.rsize);
+        for (hl, c) in self.hl.iter_mut().zip(self.render.chars()).skip(from) {
+            if *hl == Highlight::Normal && c != '\n' && c != '\r' {
+                *hl = highlight;
+            }
+        }
+    }
+
     pub fn clear_overlay_search(&mut self) {
+        self.clear_overlay_highlight(Highlight::SearchMatch);
+    }
+
+    // Takes highlight off the overlay, leaving anything else on it
+    pub fn clear_overlay_highlight(&mut self, highlight: Highlight) {
         for elem in self.overlay.iter_mut() {
-            *elem = None;
+            if *elem == Some(highlight) {
+                *elem = None;
+            }
         }
     }
 
+    pub fn overlay_at(&self, c_idx: usize) -> Option<Highlight> {
+        let render_idx = self.text_cursor_to_render_idx(c_idx)?;
+        self.overlay.get(render_idx).cloned().flatten()
+    }
+
+    pub fn set_overlay_at(&mut self, c_idx: usize, highlight: Highlight) {
+        let render_idx = match self.text_cursor_to_render_idx(c_idx) {
+            Some(render_idx) => render_idx,
+            None => return,
+        };
+        if let Some(elem) = self.overlay.get_mut(render_idx) {
+            *elem = Some(highlight);
+        }
+    }
+
+    // Each character of the text (without the newline), with how it's
+    // highlighted
+    pub fn chars_with_highlights(&self) -> Vec<(char, Highlight)> {
+        self.as_str()
+            .chars()
+            .zip(self.to_render_cursor_iter())
+            .take(self.size)
+            .map(|(c, render_cursor)| {
+                let hl = self.hl.get(render_cursor.render_idx as usize);
+                (c, hl.cloned().unwrap_or_default())
+            })
+            .collect()
+    }
+
+    // Marks the text from begin up to end as the search match
     pub fn set_overlay_search(&mut self, begin: usize, end: usize) {
         self.clear_overlay_search();
+        self.add_overlay_search(begin, end);
+    }
+
+    // Marks another match, leaving the others marked
+    pub fn add_overlay_search(&mut self, begin: usize, end: usize) {
+        let begin = self.text_cursor_to_render_idx(begin).unwrap_or(self.rsize);
+        let end = self.text_cursor_to_render_idx(end).unwrap_or(self.rsize);
         for x in begin..end {
             if let Some(elem) = self.overlay.get_mut(x) {
                 *elem = Some(Highlight::SearchMatch);
@@ -365,7 +596,7 @@ impl<'a> Row<'a> {
     }
 
     fn to_render_cursor_iter(&self) -> RenderCursorIter<'_> {
-        RenderCursorIter::new(self.as_str().chars())
+        RenderCursorIter::new(self.as_str().chars(), self.tab_width)
     }
 
     pub fn text_cursor_to_render(&self, c_idx: i32) -> i32 {
@@ -375,13 +606,45 @@ impl<'a> Row<'a> {
             .unwrap_or(0)
     }
 
+    // The first character at or after the column r_idx
     pub fn render_cursor_to_text(&self, r_idx: usize) -> usize {
         self.to_render_cursor_iter()
-            .find(|render_cursor| render_cursor.render_cursor == r_idx as i32)
+            .find(|render_cursor| render_cursor.render_cursor >= r_idx as i32)
             .map(|render_cursor| render_cursor.text_cursor)
             .unwrap_or(0) as usize
     }
 
+    // Which character of the rendered string c_idx is
+    pub fn text_cursor_to_render_idx(&self, c_idx: usize) -> Option<usize> {
+        self.to_render_cursor_iter()
+            .find(|render_cursor| render_cursor.text_cursor == c_idx as i32)
+            .map(|render_cursor| render_cursor.render_idx as usize)
+    }
+
+    // Which character of the text is at r_idx in the rendered string
+    pub fn render_idx_to_text(&self, r_idx: usize) -> usize {
+        self.to_render_cursor_iter()
+            .find(|render_cursor| render_cursor.render_idx >= r_idx as i32)
+            .map(|render_cursor| render_cursor.text_cursor)
+            .unwrap_or(0) as usize
+    }
+
+    // The first character of the rendered string that starts at or after
+    // column col, and how many columns after col that is (when a wide
+    // character straddles col). The newline is never skipped.
+    pub fn render_idx_at_column(&self, col: usize) -> (usize, usize) {
+        let mut widths = ColumnWidths::default();
+        let mut column = 0;
+        for (r_idx, c) in self.render.chars().enumerate() {
+            let width = widths.width_of(c);
+            if (column >= col && width > 0) || c == '\n' {
+                return (r_idx, column.saturating_sub(col));
+            }
+            column += width;
+        }
+        (self.rsize, 0)
+    }
+
     fn render_cursor_to_byte_position(&self, at: usize) -> usize {
         char_position_to_byte_position(&self.chars, at)
     }
@@ -445,16 +708,81 @@ impl<'a> Row<'a> {
         self.chars.as_str()
     }
 
+    // Gives back memory left over from edits that made the row shorter
+    pub fn trim_capacity(&mut self) {
+        self.chars.shrink_to_fit();
+        self.render.shrink_to_fit();
+        self.hl.shrink_to_fit();
+        self.overlay.shrink_to_fit();
+    }
+
     #[allow(dead_code)]
     pub fn rendered_str(&self) -> &str {
         self.render.as_str()
     }
 
+    // How many columns the row takes up on screen, with tabs expanded
+    pub fn render_len(&self) -> usize {
+        self.width
+    }
+
     pub fn index_of(&self, needle: &str) -> Option<usize> {
         self.render
             .find(needle)
             .map(|at| self.byte_position_to_char_position(at))
     }
+
+    // Adds amount to the number at or after col, which is decimal or hex
+    // starting 0x, keeping any leading zeros and the case of hex digits.
+    // Gives back the new text and the column of the number's last digit.
+    pub fn add_to_number(&self, col: usize, amount: i64) -> Option<(String, usize)> {
+        let line = self.chars.trim_end_matches(['\r', '\n']);
+        let chars: Vec<char> = line.chars().collect();
+        let number = find_number(&chars, col)?;
+        let digits: String = chars[number.digits.clone()].iter().collect();
+        let (from, changed) = if number.hex {
+            // Hex is unsigned, so it wraps round rather than going negative
+            let value = u64::from_str_radix(&digits, 16)
+                .ok()?
+                .wrapping_add(amount as u64);
+            let width = digits.len();
+            let hex = if digits.chars().any(|c| c.is_ascii_uppercase()) {
+                format!("{:0width$X}", value)
+            } else {
+                format!("{:0width$x}", value)
+            };
+            (number.digits.start, hex)
+        } else {
+            let value: i128 = digits.parse().ok()?;
+            let value = if number.negative { -value } else { value } + i128::from(amount);
+            let width = if digits.starts_with('0') {
+                digits.len()
+            } else {
+                1
+            };
+            let sign = if value < 0 { "-" } else { "" };
+            (
+                number.start,
+                format!("{}{:0width$}", sign, value.unsigned_abs()),
+            )
+        };
+        let head: String = chars[..from].iter().collect();
+        let tail: String = chars[number.digits.end..].iter().collect();
+        let last_col = from + changed.chars().count() - 1;
+        let text = format!("{}{}{}{}", head, changed, tail, &self.chars[line.len()..]);
+        Some((text, last_col))
+    }
+
+    pub fn indices_of(&self, needle: &str) -> Vec<usize> {
+        // Nothing's been typed to look for yet
+        if needle.is_empty() {
+            return vec![];
+        }
+        self.render
+            .match_indices(needle)
+            .map(|(at, _)| self.byte_position_to_char_position(at))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -640,6 +968,42 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_wide_and_combining_chars() {
+        let row = Row::new_wo_syntax("日本x\r\n");
+        assert_eq!(5, row.render_len());
+        assert_eq!(2, row.text_cursor_to_render(1));
+        assert_eq!(4, row.text_cursor_to_render(2));
+        assert_eq!(1, row.render_cursor_to_text(2));
+        assert_eq!(Some(2), row.text_cursor_to_render_idx(2));
+
+        let row = Row::new_wo_syntax("cafe\u{301}s\r\n");
+        assert_eq!(5, row.render_len());
+        // The accent is drawn on the e it's combined with
+        assert_eq!(3, row.text_cursor_to_render(4));
+        assert_eq!(4, row.text_cursor_to_render(5));
+        assert_eq!(5, row.render_cursor_to_text(4));
+
+        let mut row = Row::new_wo_syntax("\t日x\r\n");
+        row.set_tab_width(4);
+        assert_eq!(6, row.text_cursor_to_render(2));
+        assert_eq!(Some(5), row.text_cursor_to_render_idx(2));
+    }
+
+    #[test]
+    fn test_render_idx_at_column() {
+        let row = Row::new_wo_syntax("ab日本cafe\u{301}s\r\n");
+        assert_eq!((0, 0), row.render_idx_at_column(0));
+        assert_eq!((2, 0), row.render_idx_at_column(2));
+        // Half way through a wide character starts after it, a column late
+        assert_eq!((3, 1), row.render_idx_at_column(3));
+        // The accent stays with its e
+        assert_eq!((7, 0), row.render_idx_at_column(9));
+        assert_eq!((9, 0), row.render_idx_at_column(10));
+        // Past the end there's still the newline
+        assert_eq!((10, 0), row.render_idx_at_column(40));
+    }
+
     #[test]
     fn test_index_of() {
         {
@@ -655,6 +1019,23 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_tab_width() {
+        let mut row = Row::new_wo_syntax("a\tb\r\n");
+        assert_eq!("a       b\n", row.rendered_str());
+        row.set_tab_width(4);
+        assert_eq!("a   b\n", row.rendered_str());
+        assert_eq!(4, row.text_cursor_to_render(2));
+        assert_eq!(2, row.render_cursor_to_text(4));
+    }
+
+    #[test]
+    fn test_indices_of() {
+        let row = Row::new_wo_syntax("\t£lots and lots\r\n");
+        assert_eq!(vec![9, 18], row.indices_of("lots"));
+        assert!(row.indices_of("nothing").is_empty());
+    }
+
     #[test]
     fn test_highlight_normal() {
         let (mut row, _rc) = row_with_syntax("  normal\r\n", "C");
@@ -755,7 +1136,7 @@ pub mod test {
         use crate::syntax::SyntaxSetting::*;
         let syntax = Syntax::new("test").flag(HighlightComments);
         let rc = Rc::new(Some(&syntax));
-        let mut row = Row::new("nothing // and a comment\r\n", Rc::downgrade(&rc));
+        let mut row = Row::new("nothing // and a comment\r\n", Rc::downgrade(&rc), TAB_STOP);
         row.update_syntax_highlight(false);
         let mut highlights = vec![Highlight::Normal; 24];
         highlights.push(Highlight::Normal); // newline
@@ -928,6 +1309,29 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_indent_text() {
+        assert_eq!("", Row::new_wo_syntax("int a = 0;\n").indent_text());
+        assert_eq!(" \t ", Row::new_wo_syntax(" \t int a;\n").indent_text());
+        assert_eq!("    ", Row::new_wo_syntax("    \n").indent_text());
+    }
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let mut row = Row::new_wo_syntax("a\tb \t\n");
+        row.update_syntax_highlight(false);
+        assert_eq!(3, row.trailing_whitespace_start());
+        // The tab's drawn as spaces out to the next tab stop
+        let mut highlights = vec![Highlight::Normal; 9];
+        highlights.extend([Highlight::TrailingWhitespace; 7]);
+        highlights.push(Highlight::Normal);
+        assert_eq!(highlights, row.hl);
+        assert_eq!(
+            format!(" →{}·→", " ".repeat(7)),
+            row.whitespace_marks().trim_end()
+        );
+    }
+
     #[test]
     fn test_set_indent() {
         {
@@ -942,4 +1346,45 @@ pub mod test {
             assert_eq!("  int a = 0;\r\n", row.as_str());
         }
     }
+
+    #[test]
+    fn test_add_to_number() {
+        let add = |text: &str, col: usize, amount: i64| {
+            Row::new_wo_syntax(text)
+                .add_to_number(col, amount)
+                .map(|(text, col)| (text.trim_end().to_string(), col))
+        };
+        let changed = |text: &str, col: usize| Some((text.to_string(), col));
+        assert_eq!(changed("x = 42;", 5), add("x = 41;\n", 0, 1));
+        assert_eq!(changed("x = 40;", 5), add("x = 41;", 4, -1));
+        // The number after the cursor, not the one before it
+        assert_eq!(changed("1 + 3", 4), add("1 + 2\n", 1, 1));
+        assert_eq!(None, add("1 + 2\n", 5, 1));
+        assert_eq!(None, add("no numbers\n", 0, 1));
+
+        // Negative numbers, and going either side of zero
+        assert_eq!(changed("a = -4", 5), add("a = -5", 0, 1));
+        assert_eq!(changed("a = 1", 4), add("a = -1", 5, 2));
+        assert_eq!(changed("a = -1", 5), add("a = 0", 0, -1));
+        // Unless the minus is taking one thing from another
+        assert_eq!(changed("x-2", 2), add("x-1", 0, 1));
+
+        // Leading zeros are kept, as wide as they were
+        assert_eq!(changed("v010", 3), add("v009", 0, 1));
+        assert_eq!(changed("-007", 3), add("-008", 0, 1));
+        assert_eq!(changed("100", 2), add("099", 0, 1));
+
+        // Hex keeps the case of its digits and wraps round
+        assert_eq!(changed("0x100", 4), add("0xff", 3, 1));
+        assert_eq!(changed("0xFE", 3), add("0xFF", 0, -1));
+        assert_eq!(changed("0x0a", 3), add("0x09", 0, 1));
+        assert_eq!(changed("0xffffffffffffffff", 17), add("0x0", 0, -1));
+        // 0x with nothing after it is just a zero
+        assert_eq!(changed("1x", 0), add("0x", 0, 1));
+
+        // Characters before it that take more than a byte, and the line
+        // ending, are left as they were
+        let row = Row::new_wo_syntax("£ 10\r\n");
+        assert_eq!(Some(("£ 11\r\n".to_string(), 3)), row.add_to_number(0, 1));
+    }
 }
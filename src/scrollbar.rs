@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+// How wide the bar is before scaling for the display
+const SCROLLBAR_WIDTH: f32 = 8.0;
+// However long the buffer is, the thumb's kept big enough to grab
+const MIN_THUMB_HEIGHT: f32 = 16.0;
+// How long the bar stays up once the pane's stopped scrolling
+const HIDE_AFTER: Duration = Duration::from_millis(1500);
+
+pub fn width(ui_scale: f32) -> f32 {
+    SCROLLBAR_WIDTH * ui_scale
+}
+
+// Where the thumb is in a track height tall, as its top and its height.
+// There's nothing to scroll, and so no thumb, when the buffer fits on screen.
+pub fn thumb(
+    height: f32,
+    screen_rows: usize,
+    num_lines: usize,
+    scroll_fraction: f32,
+    ui_scale: f32,
+) -> Option<(f32, f32)> {
+    if num_lines <= screen_rows || height <= 0.0 {
+        return None;
+    }
+    let thumb_height = (height * screen_rows as f32 / num_lines as f32)
+        .max(MIN_THUMB_HEIGHT * ui_scale)
+        .min(height);
+    let top = (height - thumb_height) * scroll_fraction.clamp(0.0, 1.0);
+    Some((top, thumb_height))
+}
+
+// How far to scroll for the thumb's top to be top down the track
+pub fn fraction_at(top: f32, height: f32, thumb_height: f32) -> f32 {
+    let travel = height - thumb_height;
+    if travel <= 0.0 {
+        0.0
+    } else {
+        (top / travel).clamp(0.0, 1.0)
+    }
+}
+
+// Whether the bar down the right of a pane is up. It comes up when the pane
+// scrolls and goes again once it's been still a while, unless it's held.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scrollbar {
+    // Since the pane last scrolled, while the bar's up
+    shown_for: Option<Duration>,
+    row_offset: f32,
+    // How far down the thumb it was grabbed, while it's being dragged
+    grab: Option<f32>,
+}
+
+impl Scrollbar {
+    pub fn is_shown(&self) -> bool {
+        self.shown_for.is_some()
+    }
+
+    pub fn grab(&self) -> Option<f32> {
+        self.grab
+    }
+
+    pub fn start_drag(&mut self, grab: f32) {
+        self.grab = Some(grab);
+        self.shown_for = Some(Duration::ZERO);
+    }
+
+    pub fn end_drag(&mut self) {
+        self.grab = None;
+    }
+
+    // Moves the clock on, with where the pane's scrolled to now. True if
+    // the bar's come up or gone.
+    pub fn update(&mut self, duration: Duration, row_offset: f32) -> bool {
+        if row_offset != self.row_offset {
+            self.row_offset = row_offset;
+            let appeared = self.shown_for.is_none();
+            self.shown_for = Some(Duration::ZERO);
+            return appeared;
+        }
+        match self.shown_for.as_mut() {
+            Some(shown_for) if self.grab.is_none() => {
+                *shown_for += duration;
+                if *shown_for >= HIDE_AFTER {
+                    self.shown_for = None;
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn test_scrollbar_thumb() {
+    // A short buffer doesn't scroll
+    assert_eq!(None, thumb(400.0, 40, 30, 0.0, 1.0));
+    assert_eq!(Some((0.0, 40.0)), thumb(400.0, 40, 400, 0.0, 1.0));
+    assert_eq!(Some((180.0, 40.0)), thumb(400.0, 40, 400, 0.5, 1.0));
+    assert_eq!(Some((360.0, 40.0)), thumb(400.0, 40, 400, 1.0, 1.0));
+    // Long ones have a thumb that's still big enough to grab
+    assert_eq!(Some((384.0, 16.0)), thumb(400.0, 40, 100_000, 1.0, 1.0));
+    assert_eq!(Some((368.0, 32.0)), thumb(400.0, 40, 100_000, 1.0, 2.0));
+
+    assert_eq!(0.5, fraction_at(180.0, 400.0, 40.0));
+    assert_eq!(1.0, fraction_at(500.0, 400.0, 40.0));
+    assert_eq!(0.0, fraction_at(-10.0, 400.0, 40.0));
+
+    let mut scrollbar = Scrollbar::default();
+    assert!(!scrollbar.update(Duration::from_millis(100), 0.0));
+    assert!(!scrollbar.is_shown());
+    assert!(scrollbar.update(Duration::from_millis(100), 10.0));
+    assert!(scrollbar.is_shown());
+    assert!(!scrollbar.update(Duration::from_millis(1000), 10.0));
+    assert!(scrollbar.update(Duration::from_millis(1000), 10.0));
+    assert!(!scrollbar.is_shown());
+
+    // It stays up for as long as it's dragged
+    scrollbar.start_drag(5.0);
+    assert!(!scrollbar.update(Duration::from_secs(10), 10.0));
+    assert!(scrollbar.is_shown());
+    scrollbar.end_drag();
+    assert!(scrollbar.update(HIDE_AFTER, 10.0));
+}
@@ -1,4 +1,24 @@
 use crate::commands::SearchDirection;
+use crate::regex::{Regex, RegexError};
+use crate::selection::Selection;
+
+// Replacing goes through typing the needle, then the replacement, then
+// going through the matches one at a time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReplaceStage {
+    Needle,
+    Replacement,
+    Confirm,
+}
+
+#[derive(Clone, PartialEq)]
+struct Replace {
+    stage: ReplaceStage,
+    replacement: String,
+    // Row, start and end columns of the match waiting to be confirmed
+    current: Option<(usize, usize, usize)>,
+    replaced: usize,
+}
 
 #[derive(Clone, PartialEq)]
 pub struct Search {
@@ -9,6 +29,14 @@ pub struct Search {
     restore_cursor: bool,
     saved_row_offset: f32,
     saved_col_offset: f32,
+    scope: Option<Selection>,
+    regex: bool,
+    replace: Option<Replace>,
+    // Carry on into the pane's other buffers after the last match
+    all_buffers: bool,
+    buffer_name: Option<String>,
+    // Which match the search is on and how many there are in the buffer
+    match_count: Option<(usize, usize)>,
 }
 
 impl Search {
@@ -21,11 +49,133 @@ impl Search {
             restore_cursor: false,
             saved_row_offset,
             saved_col_offset,
+            scope: None,
+            regex: false,
+            replace: None,
+            all_buffers: false,
+            buffer_name: None,
+            match_count: None,
         }
     }
 
+    pub fn within(mut self, scope: Selection) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    pub fn replacing(mut self) -> Self {
+        self.replace = Some(Replace {
+            stage: ReplaceStage::Needle,
+            replacement: String::new(),
+            current: None,
+            replaced: 0,
+        });
+        self
+    }
+
+    #[cfg(feature = "gui")]
     pub fn as_string(&self) -> String {
-        format!("Search ({}): {}", self.direction, self.needle)
+        if let Some(replace) = self.replace.as_ref() {
+            return self.replace_string(replace);
+        }
+        let kind = match (self.regex, self.all_buffers) {
+            (false, false) => "Search",
+            (true, false) => "Regex search",
+            (false, true) => "Search all buffers",
+            (true, true) => "Regex search all buffers",
+        };
+        let mut prompt = if self.scope.is_some() {
+            format!(
+                "{} in selection ({}): {}",
+                kind, self.direction, self.needle
+            )
+        } else {
+            format!("{} ({}): {}", kind, self.direction, self.needle)
+        };
+        let compiled = self.compile();
+        if !self.needle.is_empty() && !matches!(compiled, Some(Err(_))) {
+            match self.match_count {
+                Some((_, 0)) => prompt.push_str(" (no matches)"),
+                Some((index, total)) => {
+                    prompt = format!("{} (match {} of {})", prompt, index, total)
+                }
+                None => {}
+            }
+        }
+        if let Some(buffer_name) = self.buffer_name.as_ref().filter(|_| self.all_buffers) {
+            prompt = format!("{} [{}]", prompt, buffer_name);
+        }
+        match compiled {
+            Some(Err(err)) if !self.needle.is_empty() => format!("{} ({})", prompt, err),
+            _ => prompt,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn replace_string(&self, replace: &Replace) -> String {
+        match replace.stage {
+            ReplaceStage::Needle => {
+                let kind = if self.regex {
+                    "Regex replace"
+                } else {
+                    "Replace"
+                };
+                let prompt = format!("{}: {}", kind, self.needle);
+                match self.compile() {
+                    Some(Err(err)) if !self.needle.is_empty() => format!("{} ({})", prompt, err),
+                    _ => prompt,
+                }
+            }
+            ReplaceStage::Replacement => {
+                format!("Replace {} with: {}", self.needle, replace.replacement)
+            }
+            ReplaceStage::Confirm => {
+                let prompt = format!(
+                    "Replace {} with {}? (y)es (n)o (a)ll (q)uit",
+                    self.needle, replace.replacement
+                );
+                if replace.replaced > 0 {
+                    format!("{}, {} replaced", prompt, replace.replaced)
+                } else {
+                    prompt
+                }
+            }
+        }
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.regex = !self.regex;
+        self.last_match = None;
+    }
+
+    // Searching all buffers doesn't mix with searching a selection or
+    // replacing, which are both about the buffer being shown
+    pub fn toggle_all_buffers(&mut self) {
+        if self.scope.is_none() && self.replace.is_none() {
+            self.all_buffers = !self.all_buffers;
+        }
+    }
+
+    pub fn all_buffers(&self) -> bool {
+        self.all_buffers
+    }
+
+    // Which buffer the last match was in, for searching all buffers
+    pub fn set_buffer_name(&mut self, buffer_name: String) {
+        self.buffer_name = Some(buffer_name);
+    }
+
+    // The needle as a regex, if this is a regex search
+    pub fn compile(&self) -> Option<Result<Regex, RegexError>> {
+        if self.regex {
+            Some(Regex::new(&self.needle))
+        } else {
+            None
+        }
+    }
+
+    pub fn scope(&self) -> Option<Selection> {
+        self.scope
     }
 
     pub fn last_match(&self) -> Option<(usize, usize)> {
@@ -40,6 +190,51 @@ impl Search {
         self.run_search
     }
 
+    // Whether matches should follow the needle as it's typed. Once a
+    // replace has its needle, the pane moves between matches itself.
+    pub fn incremental(&self) -> bool {
+        self.replace_stage()
+            .map(|stage| stage == ReplaceStage::Needle)
+            .unwrap_or(true)
+    }
+
+    pub fn replace_stage(&self) -> Option<ReplaceStage> {
+        self.replace.as_ref().map(|replace| replace.stage)
+    }
+
+    // Moves on to typing the replacement, or from there to confirming
+    pub fn next_replace_stage(&mut self) {
+        if let Some(replace) = self.replace.as_mut() {
+            replace.stage = match replace.stage {
+                ReplaceStage::Needle => ReplaceStage::Replacement,
+                _ => ReplaceStage::Confirm,
+            };
+        }
+    }
+
+    pub fn replacement(&self) -> &str {
+        self.replace
+            .as_ref()
+            .map(|replace| replace.replacement.as_str())
+            .unwrap_or("")
+    }
+
+    pub fn replace_match(&self) -> Option<(usize, usize, usize)> {
+        self.replace.as_ref().and_then(|replace| replace.current)
+    }
+
+    pub fn set_replace_match(&mut self, current: Option<(usize, usize, usize)>) {
+        if let Some(replace) = self.replace.as_mut() {
+            replace.current = current;
+        }
+    }
+
+    pub fn add_replaced(&mut self, count: usize) {
+        if let Some(replace) = self.replace.as_mut() {
+            replace.replaced += count;
+        }
+    }
+
     pub fn needle(&self) -> &str {
         &self.needle
     }
@@ -70,11 +265,38 @@ impl Search {
     }
 
     pub fn push_char(&mut self, character: char) {
+        if let Some(replace) = self.replace.as_mut() {
+            match replace.stage {
+                ReplaceStage::Needle => {}
+                ReplaceStage::Replacement => {
+                    replace.replacement.push(character);
+                    return;
+                }
+                ReplaceStage::Confirm => return,
+            }
+        }
         self.needle.push(character);
         self.last_match = None;
     }
 
     pub fn del_char(&mut self) {
+        if let Some(replace) = self.replace.as_mut() {
+            match replace.stage {
+                ReplaceStage::Needle => {}
+                ReplaceStage::Replacement => {
+                    // Deleting past the start of the replacement goes back
+                    // to editing the needle
+                    if replace.replacement.pop().is_none() {
+                        replace.stage = ReplaceStage::Needle;
+                    }
+                    return;
+                }
+                ReplaceStage::Confirm => {
+                    self.run_search = false;
+                    return;
+                }
+            }
+        }
         if self.needle.pop().is_some() {
             self.last_match = None;
         } else {
@@ -85,4 +307,90 @@ impl Search {
     pub fn set_last_match(&mut self, last_match: Option<(usize, usize)>) {
         self.last_match = last_match;
     }
+
+    pub fn set_match_count(&mut self, match_count: Option<(usize, usize)>) {
+        self.match_count = match_count;
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_search_prompt_shows_scope() {
+    use crate::cursor::Cursor;
+
+    let mut search = Search::new(0.0, 0.0);
+    search.push_char('x');
+    assert_eq!("Search (Forwards): x", search.as_string());
+    let search = search.within(Selection::new(Cursor::new(1, 1)));
+    assert_eq!("Search in selection (Forwards): x", search.as_string());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_search_prompt_counts_matches() {
+    let mut search = Search::new(0.0, 0.0);
+    search.set_match_count(Some((0, 0)));
+    assert_eq!("Search (Forwards): ", search.as_string());
+    search.push_char('x');
+    assert_eq!("Search (Forwards): x (no matches)", search.as_string());
+    search.set_match_count(Some((2, 5)));
+    assert_eq!("Search (Forwards): x (match 2 of 5)", search.as_string());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_regex_search_prompt() {
+    let mut search = Search::new(0.0, 0.0);
+    search.toggle_regex();
+    search.push_char('(');
+    assert!(search
+        .as_string()
+        .starts_with("Regex search (Forwards): ( (unclosed group"));
+    search.push_char(')');
+    assert_eq!("Regex search (Forwards): ()", search.as_string());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_replace_prompt_stages() {
+    let mut search = Search::new(0.0, 0.0).replacing();
+    search.push_char('a');
+    assert_eq!("Replace: a", search.as_string());
+    assert!(search.incremental());
+
+    search.next_replace_stage();
+    search.push_char('b');
+    assert_eq!("a", search.needle());
+    assert_eq!("Replace a with: b", search.as_string());
+    search.del_char();
+    search.del_char();
+    assert_eq!(Some(ReplaceStage::Needle), search.replace_stage());
+
+    search.next_replace_stage();
+    search.push_char('c');
+    search.next_replace_stage();
+    assert!(!search.incremental());
+    search.add_replaced(2);
+    assert_eq!(
+        "Replace a with c? (y)es (n)o (a)ll (q)uit, 2 replaced",
+        search.as_string()
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_search_all_buffers_prompt() {
+    let mut search = Search::new(0.0, 0.0);
+    search.push_char('x');
+    search.set_buffer_name("main.rs".to_string());
+    assert_eq!("Search (Forwards): x", search.as_string());
+    search.toggle_all_buffers();
+    assert_eq!(
+        "Search all buffers (Forwards): x [main.rs]",
+        search.as_string()
+    );
+
+    let mut search = Search::new(0.0, 0.0).replacing();
+    search.toggle_all_buffers();
+    assert!(!search.all_buffers());
 }
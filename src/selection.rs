@@ -0,0 +1,168 @@
+use crate::cursor::{Cursor, CursorT};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Selection {
+    anchor: Cursor,
+    head: Cursor,
+    // Covers the same columns on every row, between anchor and head,
+    // rather than running on from one row to the next
+    block: bool,
+}
+
+fn is_before(a: &Cursor, b: &Cursor) -> bool {
+    (a.text_row, a.text_col) < (b.text_row, b.text_col)
+}
+
+impl Selection {
+    pub fn new(anchor: Cursor) -> Self {
+        Self {
+            anchor,
+            head: anchor,
+            block: false,
+        }
+    }
+
+    pub fn new_block(anchor: Cursor) -> Self {
+        Self {
+            block: true,
+            ..Self::new(anchor)
+        }
+    }
+
+    pub fn is_block(&self) -> bool {
+        self.block
+    }
+
+    // The columns a block covers, as [first_col, last_col)
+    pub fn block_cols(&self) -> (i32, i32) {
+        let (anchor_col, head_col) = (self.anchor.text_col(), self.head.text_col());
+        (anchor_col.min(head_col), anchor_col.max(head_col))
+    }
+
+    pub fn extend_to(&mut self, head: Cursor) {
+        self.head = head;
+    }
+
+    // For when the rows it covers have been moved up or down
+    pub fn shift_rows(&mut self, rows: i32) {
+        self.anchor.text_row += rows;
+        self.head.text_row += rows;
+    }
+
+    // For when the text at and after col on row has got longer or shorter
+    pub fn shift_cols(&mut self, row: i32, col: i32, cols: i32) {
+        for cursor in [&mut self.anchor, &mut self.head] {
+            if cursor.text_row == row && cursor.text_col >= col {
+                cursor.text_col += cols;
+            }
+        }
+    }
+
+    pub fn anchor(&self) -> Cursor {
+        self.anchor
+    }
+
+    pub fn start(&self) -> Cursor {
+        if is_before(&self.head, &self.anchor) {
+            self.head
+        } else {
+            self.anchor
+        }
+    }
+
+    pub fn end(&self) -> Cursor {
+        if is_before(&self.head, &self.anchor) {
+            self.anchor
+        } else {
+            self.head
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    pub fn contains_row(&self, row: i32) -> bool {
+        row >= self.start().text_row() && row <= self.end().text_row()
+    }
+
+    // Does the range [first_col, last_col) on row sit entirely inside the selection?
+    pub fn contains_range(&self, row: i32, first_col: i32, last_col: i32) -> bool {
+        if self.block {
+            let (block_first, block_last) = self.block_cols();
+            return self.contains_row(row) && first_col >= block_first && last_col <= block_last;
+        }
+        let first = Cursor::new(row, first_col);
+        let last = Cursor::new(row, last_col);
+        !is_before(&first, &self.start()) && !is_before(&self.end(), &last)
+    }
+
+    // The selected columns of a row, as [first_col, last_col), given the row's length
+    pub fn cols_in_row(&self, row: i32, row_len: i32) -> Option<(i32, i32)> {
+        if !self.contains_row(row) {
+            return None;
+        }
+        if self.block {
+            return Some(self.block_cols());
+        }
+        let start = self.start();
+        let end = self.end();
+        let first_col = if start.text_row() == row {
+            start.text_col()
+        } else {
+            0
+        };
+        let last_col = if end.text_row() == row {
+            end.text_col()
+        } else {
+            row_len
+        };
+        Some((first_col, last_col))
+    }
+}
+
+#[test]
+fn test_selection_is_ordered() {
+    let mut selection = Selection::new(Cursor::new(3, 4));
+    assert!(selection.is_empty());
+    selection.extend_to(Cursor::new(1, 2));
+    assert!(!selection.is_empty());
+    assert_eq!(Cursor::new(1, 2), selection.start());
+    assert_eq!(Cursor::new(3, 4), selection.end());
+    assert_eq!(Cursor::new(3, 4), selection.anchor());
+}
+
+#[test]
+fn test_selection_contains_range() {
+    let mut selection = Selection::new(Cursor::new(0, 5));
+    selection.extend_to(Cursor::new(2, 3));
+    assert!(selection.contains_range(0, 5, 10));
+    assert!(!selection.contains_range(0, 4, 10));
+    assert!(selection.contains_range(1, 0, 80));
+    assert!(selection.contains_range(2, 0, 3));
+    assert!(!selection.contains_range(2, 0, 4));
+    assert!(!selection.contains_range(3, 0, 1));
+}
+
+#[test]
+fn test_selection_cols_in_row() {
+    let mut selection = Selection::new(Cursor::new(0, 5));
+    selection.extend_to(Cursor::new(2, 3));
+    assert_eq!(Some((5, 12)), selection.cols_in_row(0, 12));
+    assert_eq!(Some((0, 7)), selection.cols_in_row(1, 7));
+    assert_eq!(Some((0, 3)), selection.cols_in_row(2, 9));
+    assert_eq!(None, selection.cols_in_row(3, 9));
+}
+
+#[test]
+fn test_block_selection() {
+    let mut selection = Selection::new_block(Cursor::new(1, 6));
+    selection.extend_to(Cursor::new(3, 2));
+    assert!(selection.is_block());
+    assert_eq!((2, 6), selection.block_cols());
+    assert_eq!(Some((2, 6)), selection.cols_in_row(1, 12));
+    assert_eq!(Some((2, 6)), selection.cols_in_row(2, 1));
+    assert_eq!(None, selection.cols_in_row(4, 12));
+    assert!(selection.contains_range(2, 3, 5));
+    assert!(!selection.contains_range(2, 0, 5));
+}
@@ -0,0 +1,70 @@
+use crate::layout::Layout;
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+// A pane's file, and where it was in it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaneSession {
+    pub filename: String,
+    pub cursor_row: i32,
+    pub cursor_col: i32,
+    pub row_offset: f32,
+    pub col_offset: f32,
+    // The file's own marks, as (row, col), which sessions saved before
+    // there were marks don't have
+    #[serde(default)]
+    pub marks: BTreeMap<char, (i32, i32)>,
+}
+
+// What was open when bim quit, to carry on from with --restore-session.
+// The layout refers to panes by their place in panes, like the container's.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub panes: Vec<PaneSession>,
+    pub layout: Layout,
+    pub focused: usize,
+}
+
+impl Session {
+    pub fn save(&self) {
+        match serde_yaml::to_string(self) {
+            Ok(session_string) => {
+                let filename = Self::session_filename();
+                if let Err(e) = paths::create_parent(&filename)
+                    .and_then(|_| fs::write(&filename, session_string))
+                {
+                    println!("Error saving session to {}: {:?}", filename.display(), e);
+                }
+            }
+            Err(e) => {
+                println!("Error saving session to string: {:?}", e);
+            }
+        }
+    }
+
+    pub fn restore() -> Option<Self> {
+        match fs::File::open(Self::session_filename()) {
+            Ok(mut f) => {
+                let mut session = String::new();
+                match f.read_to_string(&mut session) {
+                    Ok(_) => match serde_yaml::from_str::<Self>(&session) {
+                        Ok(session) => return Some(session),
+                        Err(e) => println!("Error de-serializing session: {:?}", e),
+                    },
+                    Err(e) => println!("Error reading session file: {:?}", e),
+                }
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => println!("Error opening session file: {:?}", e),
+        }
+        None
+    }
+
+    fn session_filename() -> PathBuf {
+        paths::session_file().unwrap_or_else(|| PathBuf::from(".bim_session.yaml"))
+    }
+}
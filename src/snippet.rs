@@ -0,0 +1,452 @@
+// Snippets: a prefix that Tab swaps for a bigger piece of text, with places
+// in it to fill in that Tab and Shift-Tab go between. They're kept a file
+// per filetype in the snippets directory, e.g. Rust.snippets, written the
+// way vim's snipMate has them, with each body line indented by a tab:
+//
+//     snippet fn A function
+//     	fn ${1:name}($2) {
+//     		$0
+//     	}
+//
+// $1, $2... are gone to in order, ${1:default} starts out filled in with
+// default, and $0 is where the cursor's left (the end, if there isn't one).
+// \$ is a plain dollar sign. Snippets in all.snippets work in any file.
+
+use crate::syntax::SYNTAXES;
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::Path;
+
+pub const ALL_FILETYPES: &str = "all";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+    pub filetype: String,
+    pub prefix: String,
+    pub description: String,
+    pub body: String,
+}
+
+// Where a stop is, in chars, with the rows counted from wherever the
+// snippet starts (and the cols too, on its first row)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stop {
+    pub row: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+impl Snippet {
+    pub fn new(filetype: &str, prefix: &str, description: &str, body: &str) -> Self {
+        Self {
+            filetype: filetype.to_string(),
+            prefix: prefix.to_string(),
+            description: description.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn applies_to(&self, filetype: &str) -> bool {
+        self.filetype == ALL_FILETYPES || self.filetype.eq_ignore_ascii_case(filetype)
+    }
+
+    // The body with its placeholders filled in with their defaults, and its
+    // stops in the order they're gone to. Lines after the first start with
+    // indent, and tabs at the start of lines become unit, so it's indented
+    // like the rest of the buffer.
+    pub fn expand(&self, indent: &str, unit: &str) -> (String, Vec<Stop>) {
+        let mut text = String::new();
+        let mut stops: Vec<(usize, Stop)> = vec![];
+        let mut defaults: Vec<(usize, String)> = vec![];
+        let (mut row, mut col) = (0, 0);
+        for (line_idx, line) in self.body.split('\n').enumerate() {
+            if line_idx > 0 {
+                text.push('\n');
+                row += 1;
+                col = 0;
+                if !line.is_empty() {
+                    text.push_str(indent);
+                    col += indent.chars().count();
+                }
+            }
+            let tabs = line.chars().take_while(|c| *c == '\t').count();
+            text.push_str(&unit.repeat(tabs));
+            col += unit.chars().count() * tabs;
+
+            let mut chars = line[tabs..].chars().peekable();
+            while let Some(c) = chars.next() {
+                let placeholder = match (c, chars.peek()) {
+                    ('\\', Some('$')) => {
+                        chars.next();
+                        None
+                    }
+                    ('$', Some('{')) => {
+                        let rest = chars.clone().skip(1).collect::<String>();
+                        parse_braced(&rest).map(|(number, default, used)| {
+                            for _ in 0..used + 1 {
+                                chars.next();
+                            }
+                            (number, default)
+                        })
+                    }
+                    ('$', Some(next)) if next.is_ascii_digit() => {
+                        let mut number = 0;
+                        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                            number = number * 10 + digit as usize;
+                            chars.next();
+                        }
+                        Some((number, String::new()))
+                    }
+                    _ => {
+                        text.push(c);
+                        col += 1;
+                        continue;
+                    }
+                };
+                match placeholder {
+                    Some((number, mut default)) => {
+                        // Only the first of a number is a stop, the rest
+                        // are just its default
+                        match defaults.iter().find(|(seen, _)| *seen == number) {
+                            Some((_, first)) if default.is_empty() => default = first.clone(),
+                            Some(_) => {}
+                            None => {
+                                let len = default.chars().count();
+                                stops.push((number, Stop { row, col, len }));
+                                defaults.push((number, default.clone()));
+                            }
+                        }
+                        let len = default.chars().count();
+                        text.push_str(&default);
+                        col += len;
+                    }
+                    None => {
+                        text.push('$');
+                        col += 1;
+                    }
+                }
+            }
+        }
+        if !stops.iter().any(|(number, _)| *number == 0) {
+            stops.push((0, Stop { row, col, len: 0 }));
+        }
+        // $0 goes last
+        stops.sort_by_key(|(number, _)| number.wrapping_sub(1));
+        (text, stops.into_iter().map(|(_, stop)| stop).collect())
+    }
+}
+
+// "1:default}..." into 1, "default" and how many chars that took up
+fn parse_braced(rest: &str) -> Option<(usize, String, usize)> {
+    let end = rest.find('}')?;
+    let inside = &rest[..end];
+    let (number, default) = inside.split_once(':').unwrap_or((inside, ""));
+    let number = number.parse().ok()?;
+    Some((number, default.to_string(), rest[..=end].chars().count()))
+}
+
+pub fn for_prefix<'s>(
+    snippets: &'s [Snippet],
+    filetype: &'s str,
+    prefix: &'s str,
+) -> impl Iterator<Item = &'s Snippet> {
+    snippets
+        .iter()
+        .filter(move |snippet| snippet.prefix == prefix && snippet.applies_to(filetype))
+}
+
+// The snippets in a snippets file for filetype
+pub fn parse(filetype: &str, contents: &str) -> Result<Vec<Snippet>, String> {
+    let mut snippets = vec![];
+    let mut current: Option<(Snippet, Vec<&str>)> = None;
+    let mut finish = |current: Option<(Snippet, Vec<&str>)>| {
+        if let Some((mut snippet, mut lines)) = current {
+            while lines.last() == Some(&"") {
+                lines.pop();
+            }
+            snippet.body = lines.join("\n");
+            snippets.push(snippet);
+        }
+    };
+    for (line_idx, line) in contents.lines().enumerate() {
+        if let Some(body_line) = line.strip_prefix('\t') {
+            match current.as_mut() {
+                Some((_, lines)) => lines.push(body_line),
+                None => return Err(format!("line {}: indented outside a snippet", line_idx + 1)),
+            }
+        } else if let Some(header) = line.strip_prefix("snippet ") {
+            let (prefix, description) =
+                header.trim().split_once(' ').unwrap_or((header.trim(), ""));
+            finish(current.take());
+            current = Some((
+                Snippet::new(filetype, prefix, description.trim(), ""),
+                vec![],
+            ));
+        } else if line.trim().is_empty() {
+            if let Some((_, lines)) = current.as_mut() {
+                lines.push("");
+            }
+        } else if !line.starts_with('#') {
+            return Err(format!(
+                "line {}: expected snippet or a line indented with a tab, got {:?}",
+                line_idx + 1,
+                line
+            ));
+        }
+    }
+    finish(current);
+    Ok(snippets)
+}
+
+// Every snippets file in dir, named for the filetype it's for, along with
+// what was wrong with any that couldn't be read
+pub fn load_dir(dir: &Path) -> (Vec<Snippet>, Vec<String>) {
+    let mut snippets = vec![];
+    let mut problems = vec![];
+    let mut paths = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "snippets"))
+            .collect::<Vec<_>>(),
+        Err(_) => return (snippets, problems),
+    };
+    paths.sort();
+    for path in paths {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let filetype = if stem == ALL_FILETYPES {
+            Some(ALL_FILETYPES)
+        } else {
+            SYNTAXES
+                .iter()
+                .find(|syntax| syntax.filetype.eq_ignore_ascii_case(&stem))
+                .map(|syntax| syntax.filetype)
+        };
+        let result = match filetype {
+            Some(filetype) => fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| parse(filetype, &contents)),
+            None => Err(format!("unknown filetype {:?}", stem)),
+        };
+        match result {
+            Ok(found) => snippets.extend(found),
+            Err(problem) => problems.push(format!("{}: {}", path.display(), problem)),
+        }
+    }
+    (snippets, problems)
+}
+
+// The stops of a snippet that's been put in a buffer, where they've got
+// to as it's been typed in, and which one the cursor's at
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expansion {
+    stops: Vec<Stop>,
+    current: usize,
+    // The current stop's default hasn't been typed over yet
+    fresh: bool,
+}
+
+impl Expansion {
+    // The stops are as expand gave them, for a snippet put in at (row, col)
+    pub fn new(row: usize, col: usize, stops: Vec<Stop>) -> Self {
+        let stops = stops
+            .into_iter()
+            .map(|stop| Stop {
+                row: row + stop.row,
+                col: if stop.row == 0 {
+                    col + stop.col
+                } else {
+                    stop.col
+                },
+                len: stop.len,
+            })
+            .collect();
+        Self {
+            stops,
+            current: 0,
+            fresh: true,
+        }
+    }
+
+    pub fn current(&self) -> Stop {
+        self.stops[self.current]
+    }
+
+    // Once it's got to $0 there's nowhere else to go
+    pub fn is_finished(&self) -> bool {
+        self.current + 1 >= self.stops.len()
+    }
+
+    pub fn next_stop(&mut self) -> Option<Stop> {
+        if self.is_finished() {
+            return None;
+        }
+        self.current += 1;
+        self.fresh = true;
+        Some(self.current())
+    }
+
+    pub fn prev_stop(&mut self) -> Option<Stop> {
+        self.current = self.current.checked_sub(1)?;
+        self.fresh = true;
+        Some(self.current())
+    }
+
+    // Typing at the end of a stop that's still got its default in takes
+    // the default out first. Gives back where it was.
+    pub fn take_default(&mut self, row: usize, col: usize) -> Option<Stop> {
+        let stop = self.current();
+        if !self.fresh || stop.len == 0 || (row, col) != (stop.row, stop.col + stop.len) {
+            return None;
+        }
+        self.edited(row, stop.col, -(stop.len as isize));
+        Some(stop)
+    }
+
+    // Keeps up with cols chars being typed (or taken out, if it's below 0)
+    // at col on row
+    pub fn edited(&mut self, row: usize, col: usize, cols: isize) {
+        self.fresh = false;
+        let current = self.current;
+        for (idx, stop) in self.stops.iter_mut().enumerate() {
+            if stop.row != row {
+                continue;
+            }
+            let changed = cols.unsigned_abs();
+            if cols >= 0 {
+                if idx == current && (stop.col..=stop.col + stop.len).contains(&col) {
+                    stop.len += changed;
+                } else if stop.col >= col {
+                    stop.col += changed;
+                }
+            } else if idx == current && col >= stop.col && col + changed <= stop.col + stop.len {
+                stop.len -= changed;
+            } else if stop.col >= col + changed {
+                stop.col -= changed;
+            }
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref DEFAULT_SNIPPETS: Vec<Snippet> = vec![
+        Snippet::new("Rust", "fn", "A function", "fn ${1:name}($2) {\n\t$0\n}"),
+        Snippet::new(
+            "Rust",
+            "test",
+            "A test",
+            "#[test]\nfn ${1:test_name}() {\n\t$0\n}"
+        ),
+    ];
+}
+
+#[test]
+fn test_expanding_placeholders() {
+    let snippet = Snippet::new("Rust", "fn", "", "fn ${1:name}($2) -> \\$ {\n\t$0\n}");
+    let (text, stops) = snippet.expand("  ", "    ");
+    assert_eq!("fn name() -> $ {\n      \n  }", text);
+    assert_eq!(
+        vec![
+            Stop {
+                row: 0,
+                col: 3,
+                len: 4
+            },
+            Stop {
+                row: 0,
+                col: 8,
+                len: 0
+            },
+            Stop {
+                row: 1,
+                col: 6,
+                len: 0
+            },
+        ],
+        stops
+    );
+
+    // Without a $0 the end is the last stop
+    let (text, stops) = Snippet::new("all", "x", "", "${1:a}$1 $5").expand("", "\t");
+    assert_eq!("aa ", text);
+    assert_eq!(
+        vec![(0, 0, 1), (0, 3, 0), (0, 3, 0)],
+        stops
+            .iter()
+            .map(|stop| (stop.row, stop.col, stop.len))
+            .collect::<Vec<_>>()
+    );
+    assert!(snippet.applies_to("rust") && !snippet.applies_to("C"));
+}
+
+#[test]
+fn test_parsing_snippets_files() {
+    let snippets = parse(
+        "Rust",
+        "# Mine\nsnippet pr Print it\n\tprintln!(\"{}\", $1);\n\nsnippet if\n\tif $1 {\n\n\t\t$0\n\t}\n\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            Snippet::new("Rust", "pr", "Print it", "println!(\"{}\", $1);"),
+            Snippet::new("Rust", "if", "", "if $1 {\n\n\t$0\n}"),
+        ],
+        snippets
+    );
+    assert_eq!(
+        Err("line 2: indented outside a snippet".to_string()),
+        parse("Rust", "# nothing yet\n\toops\n")
+    );
+    assert!(parse("Rust", "snippet x\nnot indented\n").is_err());
+}
+
+#[test]
+fn test_stops_follow_typing() {
+    let (_, stops) = Snippet::new("all", "x", "", "(${1:ab}, $2)").expand("", "\t");
+    let mut expansion = Expansion::new(3, 4, stops);
+    assert_eq!(
+        Stop {
+            row: 3,
+            col: 5,
+            len: 2
+        },
+        expansion.current()
+    );
+
+    // Typing over the default, then a char more than it had
+    assert_eq!(None, expansion.take_default(3, 5));
+    let default = expansion.take_default(3, 7).unwrap();
+    assert_eq!(2, default.len);
+    expansion.edited(3, 5, 3);
+    assert_eq!(
+        Stop {
+            row: 3,
+            col: 5,
+            len: 3
+        },
+        expansion.current()
+    );
+    assert_eq!(
+        Some(Stop {
+            row: 3,
+            col: 10,
+            len: 0
+        }),
+        expansion.next_stop()
+    );
+    assert!(!expansion.is_finished());
+    assert_eq!(
+        Some(Stop {
+            row: 3,
+            col: 11,
+            len: 0
+        }),
+        expansion.next_stop()
+    );
+    assert!(expansion.is_finished());
+    assert_eq!(None, expansion.next_stop());
+    assert_eq!(10, expansion.prev_stop().unwrap().col);
+}
@@ -0,0 +1,61 @@
+use crate::undo::EditKind;
+use std::fmt;
+use std::time::Duration;
+
+// What's been done to a buffer since bim started, for anyone who likes to
+// keep track of their editing
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EditStats {
+    pub chars_typed: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub time_focused: Duration,
+}
+
+impl EditStats {
+    // An edit that turned before_lines rows into after_lines rows
+    pub fn record_edit(&mut self, kind: EditKind, before_lines: usize, after_lines: usize) {
+        if kind == EditKind::InsertChar {
+            self.chars_typed += 1;
+        }
+        if after_lines > before_lines {
+            self.lines_added += after_lines - before_lines;
+        } else {
+            self.lines_removed += before_lines - after_lines;
+        }
+    }
+
+    pub fn add_time_focused(&mut self, duration: Duration) {
+        self.time_focused += duration;
+    }
+}
+
+impl fmt::Display for EditStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.time_focused.as_secs();
+        write!(
+            f,
+            "{} chars typed, +{} -{} lines, focused for {}m {}s",
+            self.chars_typed,
+            self.lines_added,
+            self.lines_removed,
+            secs / 60,
+            secs % 60
+        )
+    }
+}
+
+#[test]
+fn test_edit_stats() {
+    let mut stats = EditStats::default();
+    stats.record_edit(EditKind::InsertChar, 1, 1);
+    stats.record_edit(EditKind::InsertChar, 1, 1);
+    stats.record_edit(EditKind::InsertNewline, 1, 2);
+    stats.record_edit(EditKind::JoinRow, 2, 1);
+    stats.record_edit(EditKind::ReplaceRows, 5, 2);
+    stats.add_time_focused(Duration::from_secs(125));
+    assert_eq!(
+        "2 chars typed, +1 -4 lines, focused for 2m 5s",
+        stats.to_string()
+    );
+}
@@ -1,5 +1,28 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
 use std::time::{Duration, Instant};
 
+// How many messages are kept to look back through
+const HISTORY_LEN: usize = 500;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        })
+    }
+}
+
 #[derive(PartialEq, Eq)]
 struct Timeout {
     start_time: Instant,
@@ -22,6 +45,7 @@ impl Timeout {
 #[derive(PartialEq, Eq)]
 pub struct Status {
     pub message: String,
+    pub level: Level,
     timeout: Option<Timeout>,
 }
 
@@ -29,10 +53,22 @@ impl Status {
     pub fn new_with_timeout(message: String, duration: Duration) -> Self {
         Status {
             message,
+            level: Level::Info,
             timeout: Some(Timeout::new(duration)),
         }
     }
 
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    // Longer messages, like everything a command printed to stderr, only
+    // show their first line. The rest is in the history.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
     pub fn is_valid(&self) -> bool {
         self.timeout
             .as_ref()
@@ -40,3 +76,55 @@ impl Status {
             .unwrap_or(true)
     }
 }
+
+// Every message that's been shown, oldest first, so ones that went by too
+// quickly can be read again
+#[derive(Default)]
+pub struct Messages {
+    history: VecDeque<(Level, String)>,
+}
+
+impl Messages {
+    pub fn push(&mut self, level: Level, message: &str) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((level, message.to_string()));
+    }
+
+    // One line per message, with the lines after a message's first
+    // indented under it
+    pub fn describe(&self) -> String {
+        let mut text = String::new();
+        for (level, message) in &self.history {
+            let mut lines = message.lines();
+            text.push_str(&format!("{}: {}\n", level, lines.next().unwrap_or("")));
+            for line in lines {
+                text.push_str(&format!("    {}\n", line));
+            }
+        }
+        text
+    }
+}
+
+#[test]
+fn test_message_history() {
+    let mut messages = Messages::default();
+    assert_eq!("", messages.describe());
+    messages.push(Level::Info, "10 bytes written to disk");
+    messages.push(
+        Level::Error,
+        "sort exited with 2\nsort: bad flag\nusage: sort",
+    );
+    assert_eq!(
+        "info: 10 bytes written to disk\n\
+         error: sort exited with 2\n    sort: bad flag\n    usage: sort\n",
+        messages.describe()
+    );
+
+    for n in 0..HISTORY_LEN {
+        messages.push(Level::Warn, &format!("No matches for {}", n));
+    }
+    assert_eq!(HISTORY_LEN, messages.describe().lines().count());
+    assert!(messages.describe().starts_with("warn: No matches for 0\n"));
+}
@@ -1,7 +1,253 @@
-#[derive(Clone, Default)]
+const SEPARATOR: &str = " | ";
+
+// The parts of the status line, so clicks on them can be told apart
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StatusTarget {
+    Filename,
+    Dirty,
+    Loading,
+    Filetype,
+    Cursor,
+    Percent,
+    NumLines,
+    Encoding,
+    Newline,
+    LongLines,
+    Mode,
+    Following,
+    PowerSaving,
+}
+
+// Names for the segments as they're written in the status_line setting
+const SEGMENT_NAMES: [(StatusTarget, &str); 13] = [
+    (StatusTarget::Filename, "filename"),
+    (StatusTarget::Dirty, "dirty"),
+    (StatusTarget::Loading, "loading"),
+    (StatusTarget::Filetype, "filetype"),
+    (StatusTarget::Cursor, "cursor"),
+    (StatusTarget::Percent, "percent"),
+    (StatusTarget::NumLines, "lines"),
+    (StatusTarget::Encoding, "encoding"),
+    (StatusTarget::Newline, "newline"),
+    (StatusTarget::LongLines, "long_lines"),
+    (StatusTarget::Mode, "mode"),
+    (StatusTarget::Following, "following"),
+    (StatusTarget::PowerSaving, "power"),
+];
+
+pub const DEFAULT_STATUS_FORMAT: [StatusTarget; 9] = [
+    StatusTarget::Filename,
+    StatusTarget::Loading,
+    StatusTarget::Filetype,
+    StatusTarget::Cursor,
+    StatusTarget::Encoding,
+    StatusTarget::Newline,
+    StatusTarget::LongLines,
+    StatusTarget::Following,
+    StatusTarget::PowerSaving,
+];
+
+// A format is the segment names in order, separated by commas, like
+// "filename, dirty, cursor, percent"
+pub fn parse_status_format(format: &str) -> Result<Vec<StatusTarget>, String> {
+    format
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            SEGMENT_NAMES
+                .iter()
+                .find(|(_, segment_name)| *segment_name == name)
+                .map(|(target, _)| *target)
+                .ok_or_else(|| format!("no status line segment called {:?}", name))
+        })
+        .collect()
+}
+
+// What the segments say. Empty ones aren't shown, and the format says
+// which of them are shown in which order.
+#[derive(Clone)]
 pub struct StatusLine {
+    pub format: Vec<StatusTarget>,
     pub filename: String,
+    // Left empty unless there are unsaved changes
+    pub dirty: String,
+    // Left empty unless the file is still being read in
+    pub loading: String,
     pub num_lines: String,
     pub filetype: String,
     pub cursor: String,
+    // How far through the file the cursor is
+    pub percent: String,
+    pub encoding: String,
+    pub newline: String,
+    // Left empty when no lines are over the maximum length
+    pub long_lines: String,
+    // Left empty unless a prompt, search or selection is underway
+    pub mode: String,
+    // Left empty unless bim has slowed down to save power
+    pub power_mode: String,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self {
+            format: DEFAULT_STATUS_FORMAT.to_vec(),
+            filename: String::new(),
+            dirty: String::new(),
+            loading: String::new(),
+            num_lines: String::new(),
+            filetype: String::new(),
+            cursor: String::new(),
+            percent: String::new(),
+            encoding: String::new(),
+            newline: String::new(),
+            long_lines: String::new(),
+            mode: String::new(),
+            power_mode: String::new(),
+        }
+    }
+}
+
+impl StatusLine {
+    fn segment_text(&self, target: StatusTarget, following: bool) -> &str {
+        match target {
+            StatusTarget::Filename => &self.filename,
+            StatusTarget::Dirty => &self.dirty,
+            StatusTarget::Loading => &self.loading,
+            StatusTarget::Filetype => &self.filetype,
+            StatusTarget::Cursor => &self.cursor,
+            StatusTarget::Percent => &self.percent,
+            StatusTarget::NumLines => &self.num_lines,
+            StatusTarget::Encoding => &self.encoding,
+            StatusTarget::Newline => &self.newline,
+            StatusTarget::LongLines => &self.long_lines,
+            StatusTarget::Mode => &self.mode,
+            StatusTarget::Following if following => "following",
+            StatusTarget::Following => "",
+            StatusTarget::PowerSaving => &self.power_mode,
+        }
+    }
+
+    pub fn segments(&self, following: bool) -> Vec<(StatusTarget, &str)> {
+        self.format
+            .iter()
+            .map(|target| (*target, self.segment_text(*target, following)))
+            .filter(|(_, text)| !text.is_empty())
+            .collect()
+    }
+
+    pub fn text(&self, following: bool) -> String {
+        self.segments(following)
+            .iter()
+            .map(|(_, text)| *text)
+            .collect::<Vec<_>>()
+            .join(SEPARATOR)
+    }
+
+    // What's drawn at col (counting chars from the left of the status line)
+    pub fn target_at(&self, following: bool, col: usize) -> Option<StatusTarget> {
+        let mut start = 0;
+        for (target, text) in self.segments(following) {
+            let end = start + text.chars().count();
+            if col >= start && col < end {
+                return Some(target);
+            }
+            start = end + SEPARATOR.len();
+        }
+        None
+    }
+}
+
+#[test]
+fn test_status_target_at() {
+    let status_line = StatusLine {
+        filename: "main.rs".to_string(),
+        filetype: "rust".to_string(),
+        cursor: "12:4".to_string(),
+        newline: "LF".to_string(),
+        ..StatusLine::default()
+    };
+    assert_eq!("main.rs | rust | 12:4 | LF", status_line.text(false));
+    assert_eq!(
+        Some(StatusTarget::Filename),
+        status_line.target_at(false, 0)
+    );
+    assert_eq!(None, status_line.target_at(false, 8));
+    assert_eq!(
+        Some(StatusTarget::Filetype),
+        status_line.target_at(false, 10)
+    );
+    assert_eq!(Some(StatusTarget::Cursor), status_line.target_at(false, 20));
+    assert_eq!(
+        Some(StatusTarget::Newline),
+        status_line.target_at(false, 24)
+    );
+    assert_eq!(None, status_line.target_at(false, 26));
+    assert_eq!(
+        Some(StatusTarget::Following),
+        status_line.target_at(true, 30)
+    );
+}
+
+#[test]
+fn test_long_lines_segment() {
+    let mut status_line = StatusLine {
+        filename: "main.rs".to_string(),
+        filetype: "rust".to_string(),
+        cursor: "12:4".to_string(),
+        newline: "LF".to_string(),
+        ..StatusLine::default()
+    };
+    status_line.long_lines = "3 long lines".to_string();
+    assert_eq!(
+        "main.rs | rust | 12:4 | LF | 3 long lines",
+        status_line.text(false)
+    );
+    assert_eq!(
+        Some(StatusTarget::LongLines),
+        status_line.target_at(false, 29)
+    );
+}
+
+#[test]
+fn test_loading_segment() {
+    let status_line = StatusLine {
+        filename: "big.log".to_string(),
+        loading: "loading 42%".to_string(),
+        filetype: "no ft".to_string(),
+        cursor: "1:1".to_string(),
+        newline: "LF".to_string(),
+        ..StatusLine::default()
+    };
+    assert_eq!(
+        "big.log | loading 42% | no ft | 1:1 | LF",
+        status_line.text(false)
+    );
+    assert_eq!(
+        Some(StatusTarget::Loading),
+        status_line.target_at(false, 10)
+    );
+}
+
+#[test]
+fn test_status_format() {
+    let mut status_line = StatusLine {
+        filename: "main.rs".to_string(),
+        dirty: "modified".to_string(),
+        cursor: "12:4".to_string(),
+        percent: "40%".to_string(),
+        num_lines: "30 lines".to_string(),
+        ..StatusLine::default()
+    };
+    status_line.format = parse_status_format("cursor, filename,dirty, lines, percent").unwrap();
+    assert_eq!(
+        "12:4 | main.rs | modified | 30 lines | 40%",
+        status_line.text(false)
+    );
+    assert_eq!(Some(StatusTarget::Dirty), status_line.target_at(false, 20));
+    assert_eq!(
+        Err("no status line segment called \"colour\"".to_string()),
+        parse_status_format("filename, colour")
+    );
 }
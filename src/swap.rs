@@ -0,0 +1,143 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+const AUTOSAVE_INTERVAL: u64 = 4000;
+// Swap files start with a line saying which bim wrote them
+const HEADER: &str = "bim-swap ";
+
+// Where unsaved changes to filename are kept in case bim doesn't get to
+// save them, next to the file so they're easy to find
+pub fn swap_path(filename: &str) -> PathBuf {
+    let path = Path::new(filename);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.bim-swap", name))
+}
+
+pub fn write_header(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{}{}", HEADER, process::id())
+}
+
+// Splits a swap file into the pid of the bim that wrote it, if it says,
+// and the text
+pub fn split_header(bytes: &[u8]) -> (Option<u32>, &[u8]) {
+    let pid_and_text = bytes.strip_prefix(HEADER.as_bytes()).and_then(|rest| {
+        let end = rest.iter().position(|&byte| byte == b'\n')?;
+        let pid = std::str::from_utf8(&rest[..end]).ok()?.parse().ok()?;
+        Some((Some(pid), &rest[end + 1..]))
+    });
+    pid_and_text.unwrap_or((None, bytes))
+}
+
+// Whether the swap file at path belongs to another bim that's still
+// running, which it's still writing to and will clean up itself
+pub fn held_elsewhere(path: &Path) -> bool {
+    match fs::read(path).map(|bytes| split_header(&bytes).0) {
+        Ok(Some(pid)) => pid != process::id() && is_running(pid),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let pid = pid as libc::pid_t;
+    // Signal 0 only checks there's a process to send it to
+    pid > 0
+        && (unsafe { libc::kill(pid, 0) } == 0
+            || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use winapi::shared::minwindef::FALSE;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if handle.is_null() {
+            return false;
+        }
+        let mut code = 0;
+        let got = GetExitCodeProcess(handle, &mut code);
+        CloseHandle(handle);
+        got != 0 && code == STILL_ACTIVE
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+// How often a pane writes its buffer's unsaved changes to the swap file
+pub struct Autosave {
+    interval: Duration,
+    since_save: Duration,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(AUTOSAVE_INTERVAL),
+            since_save: Duration::default(),
+        }
+    }
+}
+
+impl Autosave {
+    // Advances the clock, returning true when it's time to save again
+    pub fn tick(&mut self, duration: Duration) -> bool {
+        self.since_save += duration;
+        if self.since_save >= self.interval {
+            self.since_save = Duration::default();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[test]
+fn test_swap_path() {
+    assert_eq!(
+        PathBuf::from("src/.main.rs.bim-swap"),
+        swap_path("src/main.rs")
+    );
+    assert_eq!(PathBuf::from(".notes.bim-swap"), swap_path("notes"));
+}
+
+#[test]
+fn test_swap_header() {
+    let mut bytes = vec![];
+    write_header(&mut bytes).unwrap();
+    bytes.extend_from_slice(b"text\n");
+    assert_eq!((Some(process::id()), &b"text\n"[..]), split_header(&bytes));
+    // Swap files from before there was a header are all text
+    assert_eq!((None, &b"text\n"[..]), split_header(b"text\n"));
+    assert_eq!((None, &b"bim-swap x\n"[..]), split_header(b"bim-swap x\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_held_elsewhere() {
+    let path = crate::utils::temp_path(".bim-held", ".bim-swap");
+    let mut other = process::Command::new("sleep").arg("30").spawn().unwrap();
+    fs::write(&path, format!("{}{}\ntext\n", HEADER, other.id())).unwrap();
+    assert!(held_elsewhere(&path));
+    other.kill().unwrap();
+    other.wait().unwrap();
+    assert!(!held_elsewhere(&path));
+
+    // This bim's own are never somebody else's
+    fs::write(&path, format!("{}{}\ntext\n", HEADER, process::id())).unwrap();
+    assert!(!held_elsewhere(&path));
+    fs::remove_file(&path).unwrap();
+}
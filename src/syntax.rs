@@ -1,4 +1,6 @@
+use crate::git;
 use crate::highlight::Highlight;
+use crate::options::Indentation;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::path::Path;
@@ -9,6 +11,8 @@ pub enum SyntaxSetting {
     HighlightStrings,
     HighlightComments,
     HighlightKeywords,
+    // Single line comments only count at the very start of a line
+    CommentLinesOnly,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -20,6 +24,16 @@ pub struct Syntax<'a> {
     pub multiline_comment_end: &'a str,
     keywords: HashMap<Highlight, Vec<&'a str>>,
     flags: Vec<SyntaxSetting>,
+    // How long the first line can be before the rest of it is a warning
+    pub subject_line_length: Option<usize>,
+    // Indentation for the filetype, when it's not the usual
+    pub indentation: Option<Indentation>,
+    // What a line ends with, or the keyword it starts with, for the line
+    // after it to go in a level
+    indent_after: Vec<&'a str>,
+    indent_keywords: Vec<&'a str>,
+    // What a line can start with to come back out a level
+    dedent_on: Vec<&'a str>,
 }
 
 impl<'a> Syntax<'a> {
@@ -32,6 +46,11 @@ impl<'a> Syntax<'a> {
             multiline_comment_end: "",
             keywords: HashMap::new(),
             flags: Vec::new(),
+            subject_line_length: None,
+            indentation: None,
+            indent_after: Vec::new(),
+            indent_keywords: Vec::new(),
+            dedent_on: Vec::new(),
         }
     }
 
@@ -45,7 +64,6 @@ impl<'a> Syntax<'a> {
         SYNTAXES.iter().find(|syntax| syntax.filetype == filetype)
     }
 
-    #[allow(dead_code)]
     pub fn filematch(mut self, filematch: &'a str) -> Syntax<'_> {
         self.filematches.push(filematch);
         self
@@ -84,6 +102,16 @@ impl<'a> Syntax<'a> {
         self
     }
 
+    // Every keyword, for completing words with
+    pub fn all_keywords(&self) -> Vec<&'a str> {
+        [Highlight::Keyword1, Highlight::Keyword2]
+            .iter()
+            .filter_map(|highlight| self.keywords.get(highlight))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
     pub fn singleline_comment_start(mut self, singleline: &'a str) -> Syntax<'_> {
         self.singleline_comment_start = singleline;
         self
@@ -99,6 +127,59 @@ impl<'a> Syntax<'a> {
         self
     }
 
+    pub fn subject_line_length(mut self, len: usize) -> Syntax<'a> {
+        self.subject_line_length = Some(len);
+        self
+    }
+
+    pub fn indentation(mut self, expand_tabs: bool, width: usize) -> Syntax<'a> {
+        self.indentation = Some(Indentation {
+            expand_tabs,
+            tab_width: width,
+            indent_width: width,
+        });
+        self
+    }
+
+    pub fn indent_after(mut self, endings: &'a [&'a str]) -> Syntax<'a> {
+        self.indent_after.extend(endings);
+        self
+    }
+
+    pub fn indent_keywords(mut self, keywords: &'a [&'a str]) -> Syntax<'a> {
+        self.indent_keywords.extend(keywords);
+        self
+    }
+
+    pub fn dedent_on(mut self, starts: &'a [&'a str]) -> Syntax<'a> {
+        self.dedent_on.extend(starts);
+        self
+    }
+
+    // Whether the line after code, a line with its comments taken out,
+    // should go in a level. Words have to be whole words, so "undo" doesn't
+    // end with "do".
+    pub fn indents_after(&self, code: &str) -> bool {
+        let code = code.trim();
+        let first_word = code.split(|c: char| !is_word_char(c)).next().unwrap_or("");
+        self.indent_keywords.contains(&first_word)
+            || self.indent_after.iter().any(|ending| {
+                code.strip_suffix(ending).is_some_and(|before| {
+                    !ending.starts_with(is_word_char) || !before.ends_with(is_word_char)
+                })
+            })
+    }
+
+    // What a line (without its indentation) that comes out a level starts
+    // with, like a closing brace
+    pub fn dedent_start(&self, text: &str) -> Option<&'a str> {
+        self.dedent_on.iter().copied().find(|start| {
+            text.strip_prefix(start).is_some_and(|after| {
+                !start.ends_with(is_word_char) || !after.starts_with(is_word_char)
+            })
+        })
+    }
+
     pub fn flag(mut self, flag: SyntaxSetting) -> Syntax<'a> {
         self.flags.push(flag);
         self
@@ -117,6 +198,10 @@ impl<'a> Syntax<'a> {
             && !self.singleline_comment_start.is_empty()
     }
 
+    pub fn comment_lines_only(&self) -> bool {
+        self.flags.contains(&SyntaxSetting::CommentLinesOnly)
+    }
+
     pub fn highlight_multiline_comments(&self) -> bool {
         self.flags.contains(&SyntaxSetting::HighlightComments)
             && !self.multiline_comment_start.is_empty()
@@ -140,8 +225,11 @@ impl<'a> Syntax<'a> {
         None
     }
 
+    // Matches starting with a dot are extensions, anything else has to be
+    // the whole file name
     pub fn matches_filename(&self, filename: &str) -> bool {
-        let ext = Path::new(filename).extension();
+        let path = Path::new(filename);
+        let ext = path.extension();
         self.filematches.iter().any(|filematch| {
             if filematch.starts_with('.') {
                 ext.map(|e1| {
@@ -153,12 +241,18 @@ impl<'a> Syntax<'a> {
                 })
                 .unwrap_or(false)
             } else {
-                false
+                path.file_name()
+                    .map(|name| name == *filematch)
+                    .unwrap_or(false)
             }
         })
     }
 }
 
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
 lazy_static! {
     pub static ref SYNTAXES: Vec<Syntax<'static>> = {
         use self::SyntaxSetting::*;
@@ -178,7 +272,9 @@ lazy_static! {
                     "int", "long", "double", "float", "char", "unsigned", "signed", "void",
                 ])
                 .flag(HighlightNumbers)
-                .flag(HighlightStrings),
+                .flag(HighlightStrings)
+                .indent_after(&["{", "(", "["])
+                .dedent_on(&["}", ")", "]"]),
             Syntax::new("Rust")
                 .filematches(&[".rs"])
                 .flag(HighlightComments)
@@ -194,7 +290,10 @@ lazy_static! {
                     "i8", "i32", "i64", "u32", "u64", "f32", "f64", "str", "&str", "u8", "Self",
                 ])
                 .flag(HighlightNumbers)
-                .flag(HighlightStrings),
+                .flag(HighlightStrings)
+                .indentation(true, 4)
+                .indent_after(&["{", "(", "["])
+                .dedent_on(&["}", ")", "]"]),
             Syntax::new("Ruby")
                 .filematches(&[".rb"])
                 .flag(HighlightComments)
@@ -207,7 +306,32 @@ lazy_static! {
                 ])
                 .keywords2(&[])
                 .flag(HighlightNumbers)
-                .flag(HighlightStrings),
+                .flag(HighlightStrings)
+                .indentation(true, 2)
+                .indent_after(&["do", "{", "(", "["])
+                .indent_keywords(&[
+                    "def", "class", "module", "if", "unless", "while", "until", "case", "begin",
+                    "else", "elsif", "when", "rescue", "ensure",
+                ])
+                .dedent_on(&[
+                    "end", "else", "elsif", "when", "rescue", "ensure", "}", ")", "]",
+                ]),
+            Syntax::new("gitcommit")
+                .filematches(git::COMMIT_MESSAGE_FILES)
+                .flag(HighlightComments)
+                .flag(CommentLinesOnly)
+                .singleline_comment_start("#")
+                .subject_line_length(50),
+            Syntax::new("gitrebase")
+                .filematch(git::REBASE_TODO_FILE)
+                .flag(HighlightComments)
+                .flag(CommentLinesOnly)
+                .singleline_comment_start("#")
+                .flag(HighlightKeywords)
+                .keywords1(&[
+                    "pick", "reword", "edit", "squash", "fixup", "drop", "exec", "break", "label",
+                    "reset", "merge",
+                ]),
         ]
     };
 }
@@ -219,6 +343,17 @@ fn test_matches_filename() {
     assert!(!syntax.matches_filename("test.r"));
 }
 
+#[test]
+fn test_matches_whole_filename() {
+    let syntax = Syntax::new("gitcommit").filematch("COMMIT_EDITMSG");
+    assert!(syntax.matches_filename(".git/COMMIT_EDITMSG"));
+    assert!(!syntax.matches_filename("COMMIT_EDITMSG.bak"));
+    assert_eq!(
+        Some("gitrebase"),
+        Syntax::for_filename(".git/rebase-merge/git-rebase-todo").map(|syntax| syntax.filetype)
+    );
+}
+
 #[test]
 fn test_highlight_numbers() {
     let syntax = Syntax::new("test").flag(SyntaxSetting::HighlightNumbers);
@@ -299,3 +434,21 @@ fn test_highlight_multiline_comments() {
         .multiline_comment_end("*/");
     assert!(!syntax.highlight_multiline_comments());
 }
+
+#[test]
+fn test_indenting_blocks() {
+    let rust = Syntax::for_filetype("Rust").unwrap();
+    assert!(rust.indents_after("fn main() {"));
+    assert!(rust.indents_after("    let x = foo(  "));
+    assert!(!rust.indents_after("let x = 1;"));
+    assert_eq!(Some("}"), rust.dedent_start("} else {"));
+    assert_eq!(None, rust.dedent_start("x }"));
+
+    let ruby = Syntax::for_filetype("Ruby").unwrap();
+    assert!(ruby.indents_after("def call(x)"));
+    assert!(ruby.indents_after("items.each do"));
+    assert!(!ruby.indents_after("undo"));
+    assert!(!ruby.indents_after("definitely = 1"));
+    assert_eq!(Some("end"), ruby.dedent_start("end"));
+    assert_eq!(None, ruby.dedent_start("ending = 2"));
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+use std::path::Path;
+
+// A tab as the tab bar shows it, for each frontend to draw its own way
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabLabel {
+    pub number: usize,
+    // The directory the tab's working in, without the rest of its path
+    pub name: String,
+    pub dirty: bool,
+    pub active: bool,
+}
+
+impl TabLabel {
+    pub fn new(number: usize, dir: &Path, dirty: bool, active: bool) -> Self {
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| dir.display().to_string());
+        Self {
+            number,
+            name,
+            dirty,
+            active,
+        }
+    }
+}
+
+impl fmt::Display for TabLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dirty = if self.dirty { " [+]" } else { "" };
+        write!(f, "{} {}{}", self.number, self.name, dirty)
+    }
+}
+
+#[test]
+fn test_tab_labels() {
+    let label = TabLabel::new(1, Path::new("/home/me/code/bim"), false, true);
+    assert_eq!("1 bim", label.to_string());
+    let label = TabLabel::new(2, Path::new("/home/me/notes"), true, false);
+    assert_eq!("2 notes [+]", label.to_string());
+    assert_eq!(
+        "3 /",
+        TabLabel::new(3, Path::new("/"), false, false).to_string()
+    );
+}
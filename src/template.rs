@@ -0,0 +1,189 @@
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::Path;
+
+pub const CURSOR_MARKER: &str = "{cursor}";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    filematch: String,
+    text: String,
+}
+
+impl Template {
+    pub fn new(filematch: &str, text: &str) -> Self {
+        Self {
+            filematch: filematch.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn matches_filename(&self, filename: &str) -> bool {
+        let wanted_ext = self.filematch.trim_start_matches('.');
+        Path::new(filename)
+            .extension()
+            .map(|ext| ext.to_string_lossy() == wanted_ext)
+            .unwrap_or(false)
+    }
+
+    // Fills in the placeholders for filename, returning the text to insert
+    // and the (row, col) the cursor should finish at, if the template says
+    pub fn expand(&self, filename: &str) -> (String, Option<(usize, usize)>) {
+        let path = Path::new(filename);
+        let basename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let guard = include_guard(&basename);
+        let expanded = self
+            .text
+            .replace("{filename}", &basename)
+            .replace("{stem}", &stem)
+            .replace("{guard}", &guard);
+
+        if let Some(at) = expanded.find(CURSOR_MARKER) {
+            let before = &expanded[..at];
+            let row = before.matches('\n').count();
+            let col = before
+                .rsplit('\n')
+                .next()
+                .map(|line| line.chars().count())
+                .unwrap_or(0);
+            (expanded.replacen(CURSOR_MARKER, "", 1), Some((row, col)))
+        } else {
+            (expanded, None)
+        }
+    }
+}
+
+fn include_guard(basename: &str) -> String {
+    let mut guard: String = basename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    guard.push('_');
+    guard
+}
+
+pub fn for_filename<'t>(templates: &'t [Template], filename: &str) -> Option<&'t Template> {
+    templates
+        .iter()
+        .find(|template| template.matches_filename(filename))
+}
+
+// Every template in dir, each one for files with the same extension as it,
+// along with what was wrong with any that couldn't be read
+pub fn load_dir(dir: &Path) -> (Vec<Template>, Vec<String>) {
+    let mut templates = vec![];
+    let mut problems = vec![];
+    let mut paths = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>(),
+        Err(_) => return (templates, problems),
+    };
+    paths.sort();
+    for path in paths {
+        let result = match path.extension() {
+            Some(ext) => fs::read_to_string(&path)
+                .map(|text| Template::new(&format!(".{}", ext.to_string_lossy()), &text))
+                .map_err(|err| err.to_string()),
+            None => Err("no extension to say which files it's for".to_string()),
+        };
+        match result {
+            Ok(template) => templates.push(template),
+            Err(problem) => problems.push(format!("{}: {}", path.display(), problem)),
+        }
+    }
+    (templates, problems)
+}
+
+// Adds the user's templates, which take the place of any for the same files
+pub fn add_templates(templates: &mut Vec<Template>, added: Vec<Template>) {
+    for template in added {
+        templates.retain(|existing| existing.filematch != template.filematch);
+        templates.push(template);
+    }
+}
+
+lazy_static! {
+    pub static ref DEFAULT_TEMPLATES: Vec<Template> = vec![
+        Template::new(".rs", "//! {cursor}\n"),
+        Template::new(
+            ".h",
+            "#ifndef {guard}\n#define {guard}\n\n{cursor}\n\n#endif // {guard}\n"
+        ),
+    ];
+}
+
+#[test]
+fn test_include_guard() {
+    assert_eq!("BUFFER_H_", include_guard("buffer.h"));
+    assert_eq!("MY_LIB_V2_H_", include_guard("my-lib.v2.h"));
+}
+
+#[test]
+fn test_for_filename() {
+    let template = for_filename(&DEFAULT_TEMPLATES, "src/thing.rs");
+    assert_eq!(Some(&DEFAULT_TEMPLATES[0]), template);
+    assert_eq!(None, for_filename(&DEFAULT_TEMPLATES, "thing.c"));
+}
+
+#[test]
+fn test_expand_header_template() {
+    let template = for_filename(&DEFAULT_TEMPLATES, "include/row.h").unwrap();
+    let (text, cursor) = template.expand("include/row.h");
+    assert_eq!(
+        "#ifndef ROW_H_\n#define ROW_H_\n\n\n\n#endif // ROW_H_\n",
+        text
+    );
+    assert_eq!(Some((3, 0)), cursor);
+}
+
+#[test]
+fn test_expand_without_cursor() {
+    let template = Template::new(".rb", "# {filename} ({stem})\n");
+    assert_eq!(
+        ("# thing.rb (thing)\n".to_string(), None),
+        template.expand("lib/thing.rb")
+    );
+}
+
+#[test]
+fn test_user_templates_replace_the_defaults() {
+    let dir = crate::utils::temp_path("bim-templates", "");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("new.h"), "#pragma once\n{cursor}\n").unwrap();
+    fs::write(dir.join("new.py"), "#!/usr/bin/env python3\n").unwrap();
+    fs::write(dir.join("README"), "no extension\n").unwrap();
+
+    let (found, problems) = load_dir(&dir);
+    assert_eq!(2, found.len());
+    assert_eq!(1, problems.len());
+    let mut templates = DEFAULT_TEMPLATES.clone();
+    add_templates(&mut templates, found);
+    assert_eq!(3, templates.len());
+    assert_eq!(
+        ("#pragma once\n\n".to_string(), Some((1, 0))),
+        for_filename(&templates, "row.h").unwrap().expand("row.h")
+    );
+    assert!(for_filename(&templates, "main.py").is_some());
+    assert_eq!(
+        Some(&DEFAULT_TEMPLATES[0]),
+        for_filename(&templates, "main.rs")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
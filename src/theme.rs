@@ -0,0 +1,307 @@
+use crate::colours::Colour;
+use crate::highlight::Highlight;
+use crate::lsp::Severity;
+use crate::paths;
+use std::fs;
+
+pub const DEFAULT_THEME: &str = "default";
+const BUILT_IN_THEMES: [&str; 2] = [DEFAULT_THEME, "light"];
+
+// Every colour bim draws with, UI and syntax alike
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub background: Colour,
+    pub line_highlight: Colour,
+    pub popup_bg: Colour,
+    pub popup_outline: Colour,
+    pub popup_fg: Colour,
+    pub status_bg: Colour,
+    pub status_unfocused_bg: Colour,
+    pub status_fg: Colour,
+    pub cursor: Colour,
+    pub cursor_unfocused: Colour,
+    pub other_cursor: Colour,
+    pub selection: Colour,
+    pub follow_new_line: Colour,
+    pub gutter_fg: Colour,
+    pub long_line: Colour,
+    // Behind spaces at the ends of lines, and the faint marks for spaces and
+    // tabs when they're all shown
+    pub trailing_whitespace: Colour,
+    pub whitespace: Colour,
+    pub line_col: Colour,
+    pub pane_border: Colour,
+    pub divider_preview: Colour,
+    pub normal: Colour,
+    pub number: Colour,
+    pub string: Colour,
+    pub comment: Colour,
+    pub keyword1: Colour,
+    pub keyword2: Colour,
+    pub warning: Colour,
+    pub cursor_text: Colour,
+    pub search_match: Colour,
+    pub matching_bracket: Colour,
+    // Squiggles and gutter marks for what language servers find
+    pub diagnostic_error: Colour,
+    pub diagnostic_warning: Colour,
+    pub diagnostic_info: Colour,
+}
+
+fn rgb(red: i32, green: i32, blue: i32) -> Colour {
+    Colour::rgb_from_int_tuple((red, green, blue))
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let background = rgb(41, 42, 68);
+        Self {
+            name: DEFAULT_THEME.to_string(),
+            background,
+            line_highlight: background.lighten(0.2),
+            popup_bg: rgb(51, 0, 102),
+            popup_outline: rgb(240, 240, 240),
+            popup_fg: rgb(224, 224, 224),
+            status_bg: rgb(215, 0, 135),
+            status_unfocused_bg: rgb(215, 0, 135),
+            status_fg: rgb(255, 255, 255),
+            cursor: rgb(250, 250, 250),
+            cursor_unfocused: rgb(150, 150, 150),
+            other_cursor: rgb(255, 165, 0),
+            selection: rgb(90, 70, 140),
+            follow_new_line: rgb(40, 90, 60),
+            gutter_fg: rgb(120, 120, 140),
+            long_line: rgb(110, 40, 40),
+            trailing_whitespace: rgb(150, 50, 70),
+            whitespace: rgb(75, 76, 105),
+            line_col: rgb(0, 0, 0),
+            pane_border: rgb(0, 250, 0),
+            divider_preview: rgb(0, 125, 0),
+            normal: rgb(232, 230, 237),
+            number: rgb(221, 119, 85),
+            string: rgb(191, 156, 249),
+            comment: rgb(86, 211, 194),
+            keyword1: rgb(242, 231, 183),
+            keyword2: rgb(4, 219, 181),
+            warning: rgb(255, 85, 85),
+            cursor_text: rgb(245, 3, 3),
+            search_match: rgb(255, 102, 102),
+            matching_bracket: rgb(255, 214, 0),
+            diagnostic_error: rgb(255, 85, 85),
+            diagnostic_warning: rgb(230, 180, 60),
+            diagnostic_info: rgb(100, 160, 230),
+        }
+    }
+}
+
+impl Theme {
+    fn light() -> Self {
+        Self {
+            name: "light".to_string(),
+            background: rgb(250, 250, 245),
+            line_highlight: rgb(235, 235, 226),
+            popup_bg: rgb(230, 225, 245),
+            popup_outline: rgb(60, 60, 60),
+            popup_fg: rgb(30, 30, 30),
+            cursor: rgb(40, 40, 50),
+            cursor_unfocused: rgb(150, 150, 150),
+            other_cursor: rgb(255, 140, 0),
+            selection: rgb(200, 190, 235),
+            follow_new_line: rgb(200, 235, 210),
+            gutter_fg: rgb(140, 140, 160),
+            long_line: rgb(245, 200, 200),
+            trailing_whitespace: rgb(240, 170, 180),
+            whitespace: rgb(205, 205, 200),
+            line_col: rgb(210, 210, 210),
+            pane_border: rgb(0, 160, 0),
+            divider_preview: rgb(0, 200, 0),
+            normal: rgb(40, 40, 50),
+            number: rgb(170, 60, 20),
+            string: rgb(120, 60, 170),
+            comment: rgb(40, 130, 120),
+            keyword1: rgb(150, 90, 0),
+            keyword2: rgb(0, 120, 110),
+            warning: rgb(200, 30, 30),
+            cursor_text: rgb(250, 250, 250),
+            search_match: rgb(220, 50, 50),
+            matching_bracket: rgb(200, 120, 0),
+            diagnostic_error: rgb(200, 30, 30),
+            diagnostic_warning: rgb(200, 130, 0),
+            diagnostic_info: rgb(40, 100, 200),
+            ..Self::default()
+        }
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        match name {
+            DEFAULT_THEME => Some(Self::default()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    // A theme file in the themes directory, or failing that a built-in one
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(path) = paths::themes_dir().map(|dir| dir.join(name)) {
+            if path.is_file() {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+                return Self::parse(name, &contents)
+                    .map_err(|problem| format!("{}: {}", path.display(), problem));
+            }
+        }
+        Self::built_in(name).ok_or_else(|| format!("no theme called {:?}", name))
+    }
+
+    // The built-in themes and any in the themes directory
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = BUILT_IN_THEMES
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        if let Some(Ok(entries)) = paths::themes_dir().map(fs::read_dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    // Lines are "colour = #rrggbb", and lines starting with # are comments.
+    // "base = light" starts from another built-in theme, otherwise colours
+    // that aren't given are the default theme's.
+    pub fn parse(name: &str, contents: &str) -> Result<Self, String> {
+        let mut theme = Self::default();
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let problem = |problem: String| format!("line {}: {}", line_idx + 1, problem);
+            let (setting, value) = match line.find('=') {
+                Some(at) => (line[..at].trim(), line[at + 1..].trim()),
+                None => {
+                    return Err(problem(format!(
+                        "expected colour = #rrggbb, got {:?}",
+                        line
+                    )))
+                }
+            };
+            if setting == "base" {
+                theme = Self::built_in(value)
+                    .ok_or_else(|| problem(format!("no built-in theme called {:?}", value)))?;
+                continue;
+            }
+            let colour = Colour::from_hex(value)
+                .ok_or_else(|| problem(format!("expected #rrggbb, got {:?}", value)))?;
+            *theme
+                .colour_mut(setting)
+                .ok_or_else(|| problem(format!("unknown colour {:?}", setting)))? = colour;
+        }
+        theme.name = name.to_string();
+        Ok(theme)
+    }
+
+    fn colour_mut(&mut self, name: &str) -> Option<&mut Colour> {
+        Some(match name {
+            "background" => &mut self.background,
+            "line_highlight" => &mut self.line_highlight,
+            "popup_bg" => &mut self.popup_bg,
+            "popup_outline" => &mut self.popup_outline,
+            "popup_fg" => &mut self.popup_fg,
+            "status_bg" => &mut self.status_bg,
+            "status_unfocused_bg" => &mut self.status_unfocused_bg,
+            "status_fg" => &mut self.status_fg,
+            "cursor" => &mut self.cursor,
+            "cursor_unfocused" => &mut self.cursor_unfocused,
+            "other_cursor" => &mut self.other_cursor,
+            "selection" => &mut self.selection,
+            "follow_new_line" => &mut self.follow_new_line,
+            "gutter_fg" => &mut self.gutter_fg,
+            "long_line" => &mut self.long_line,
+            "trailing_whitespace" => &mut self.trailing_whitespace,
+            "whitespace" => &mut self.whitespace,
+            "line_col" => &mut self.line_col,
+            "pane_border" => &mut self.pane_border,
+            "divider_preview" => &mut self.divider_preview,
+            "normal" => &mut self.normal,
+            "number" => &mut self.number,
+            "string" => &mut self.string,
+            "comment" => &mut self.comment,
+            "keyword1" => &mut self.keyword1,
+            "keyword2" => &mut self.keyword2,
+            "warning" => &mut self.warning,
+            "cursor_text" => &mut self.cursor_text,
+            "search_match" => &mut self.search_match,
+            "matching_bracket" => &mut self.matching_bracket,
+            "diagnostic_error" => &mut self.diagnostic_error,
+            "diagnostic_warning" => &mut self.diagnostic_warning,
+            "diagnostic_info" => &mut self.diagnostic_info,
+            _ => return None,
+        })
+    }
+
+    pub fn highlight(&self, hl: Highlight) -> Colour {
+        use Highlight::*;
+
+        match hl {
+            Normal | TrailingWhitespace => self.normal,
+            Number => self.number,
+            String => self.string,
+            Comment | MultilineComment => self.comment,
+            Keyword1 => self.keyword1,
+            Keyword2 => self.keyword2,
+            Warning => self.warning,
+            Cursor => self.cursor_text,
+            SearchMatch => self.search_match,
+            MatchingBracket => self.matching_bracket,
+        }
+    }
+
+    pub fn diagnostic(&self, severity: Severity) -> Colour {
+        match severity {
+            Severity::Error => self.diagnostic_error,
+            Severity::Warning => self.diagnostic_warning,
+            Severity::Information | Severity::Hint => self.diagnostic_info,
+        }
+    }
+
+    pub fn status_unfocused_fg(&self) -> Colour {
+        self.status_fg.darken(0.2)
+    }
+
+    pub fn line_highlight_unfocused(&self) -> Colour {
+        self.line_highlight.darken(0.1)
+    }
+
+    pub fn dimmed(&self) -> Colour {
+        self.background.darken(0.04)
+    }
+}
+
+#[test]
+fn test_parse_theme() {
+    let theme = Theme::parse(
+        "mine",
+        "# comments are fine\n\
+         base = light\n\
+         keyword1 = #ff0000\n",
+    )
+    .unwrap();
+    assert_eq!("mine", theme.name);
+    assert_eq!(Theme::light().background, theme.background);
+    assert_eq!(rgb(255, 0, 0), theme.highlight(Highlight::Keyword1));
+    assert_eq!(
+        Err("line 1: unknown colour \"keyword3\"".to_string()),
+        Theme::parse("bad", "keyword3 = #000000")
+    );
+    assert_eq!(
+        Err("line 1: expected #rrggbb, got \"red\"".to_string()),
+        Theme::parse("bad", "normal = red")
+    );
+    assert!(Theme::load("light").is_ok());
+}
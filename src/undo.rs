@@ -0,0 +1,192 @@
+use crate::cursor::Cursor;
+
+const MAX_UNDO_STEPS: usize = 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EditKind {
+    InsertChar,
+    DeleteChar,
+    InsertNewline,
+    JoinRow,
+    ReplaceRows,
+}
+
+// An edit replaced the rows starting at row, which read before, with after
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub row: usize,
+    pub col: usize,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl Edit {
+    // Does next carry on typing (or deleting) from where this edit finished?
+    fn continued_by(&self, next: &Edit) -> bool {
+        if self.row != next.row || self.before.len() != 1 || next.before.len() != 1 {
+            return false;
+        }
+        match (self.kind, next.kind) {
+            (EditKind::InsertChar, EditKind::InsertChar) => next.col == self.col + 1,
+            (EditKind::DeleteChar, EditKind::DeleteChar) => {
+                next.col + 1 == self.col || next.col == self.col
+            }
+            _ => false,
+        }
+    }
+}
+
+// One undo step: any number of edits, undone and redone together
+#[derive(Clone, Debug, PartialEq)]
+pub struct UndoStep {
+    pub edits: Vec<Edit>,
+    pub cursor_before: Cursor,
+    pub cursor_after: Cursor,
+}
+
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    undo: Vec<UndoStep>,
+    redo: Vec<UndoStep>,
+    sealed: bool,
+    grouping: bool,
+}
+
+impl UndoHistory {
+    // cursor is where the cursor was before the edit
+    pub fn record(&mut self, edit: Edit, cursor: Cursor) {
+        self.redo.clear();
+        if !self.sealed {
+            if let Some(step) = self.undo.last_mut() {
+                if let Some(last_edit) = step.edits.last_mut() {
+                    if last_edit.continued_by(&edit) {
+                        last_edit.after = edit.after;
+                        last_edit.col = edit.col;
+                        return;
+                    }
+                }
+                if self.grouping {
+                    step.edits.push(edit);
+                    return;
+                }
+            }
+        }
+        self.sealed = false;
+        self.undo.push(UndoStep {
+            edits: vec![edit],
+            cursor_before: cursor,
+            cursor_after: cursor,
+        });
+        if self.undo.len() > MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+    }
+
+    // Stops the next edit being grouped with the last one, e.g. when the
+    // cursor is moved between them
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    // Everything recorded until end_group is one step, like the same edit
+    // made at several cursors
+    pub fn start_group(&mut self) {
+        self.sealed = true;
+        self.grouping = true;
+    }
+
+    pub fn end_group(&mut self) {
+        self.sealed = true;
+        self.grouping = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.sealed = false;
+    }
+
+    // cursor is where the cursor is now, which is where redo will put it back
+    pub fn undo(&mut self, cursor: Cursor) -> Option<UndoStep> {
+        let mut step = self.undo.pop()?;
+        step.cursor_after = cursor;
+        self.redo.push(step.clone());
+        self.sealed = true;
+        Some(step)
+    }
+
+    pub fn redo(&mut self) -> Option<UndoStep> {
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        self.sealed = true;
+        Some(step)
+    }
+}
+
+#[cfg(test)]
+fn edit(kind: EditKind, col: usize, before: &str, after: &str) -> Edit {
+    Edit {
+        kind,
+        row: 0,
+        col,
+        before: vec![before.to_string()],
+        after: vec![after.to_string()],
+    }
+}
+
+#[test]
+fn test_typing_is_grouped() {
+    let mut history = UndoHistory::default();
+    history.record(
+        edit(EditKind::InsertChar, 0, "\n", "a\n"),
+        Cursor::new(0, 0),
+    );
+    history.record(
+        edit(EditKind::InsertChar, 1, "a\n", "ab\n"),
+        Cursor::new(0, 1),
+    );
+    let step = history.undo(Cursor::new(0, 2)).unwrap();
+    assert_eq!(1, step.edits.len());
+    assert_eq!(vec!["\n".to_string()], step.edits[0].before);
+    assert_eq!(vec!["ab\n".to_string()], step.edits[0].after);
+    assert_eq!(Cursor::new(0, 0), step.cursor_before);
+    assert_eq!(None, history.undo(Cursor::new(0, 0)));
+}
+
+#[test]
+fn test_seal_and_kind_change_split_steps() {
+    let mut history = UndoHistory::default();
+    history.record(
+        edit(EditKind::InsertChar, 0, "\n", "a\n"),
+        Cursor::new(0, 0),
+    );
+    history.seal();
+    history.record(
+        edit(EditKind::InsertChar, 1, "a\n", "ab\n"),
+        Cursor::new(0, 1),
+    );
+    history.record(
+        edit(EditKind::DeleteChar, 2, "ab\n", "a\n"),
+        Cursor::new(0, 2),
+    );
+    assert!(history.undo(Cursor::new(0, 1)).is_some());
+    assert!(history.undo(Cursor::new(0, 1)).is_some());
+    assert!(history.undo(Cursor::new(0, 1)).is_some());
+    assert!(history.undo(Cursor::new(0, 1)).is_none());
+}
+
+#[test]
+fn test_new_edit_clears_redo() {
+    let mut history = UndoHistory::default();
+    history.record(
+        edit(EditKind::InsertChar, 0, "\n", "a\n"),
+        Cursor::new(0, 0),
+    );
+    history.undo(Cursor::new(0, 1));
+    history.record(
+        edit(EditKind::InsertChar, 0, "\n", "b\n"),
+        Cursor::new(0, 0),
+    );
+    assert_eq!(None, history.redo());
+}
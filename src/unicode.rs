@@ -0,0 +1,190 @@
+pub const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+// Marks that draw on top of the character before them, along with the
+// invisible characters that only change how their neighbours look
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2),
+    (0x05C4, 0x05C5),
+    (0x05C7, 0x05C7),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06DC),
+    (0x06DF, 0x06E4),
+    (0x06E7, 0x06E8),
+    (0x06EA, 0x06ED),
+    (0x0711, 0x0711),
+    (0x0730, 0x074A),
+    (0x07A6, 0x07B0),
+    (0x0900, 0x0902),
+    (0x093A, 0x093A),
+    (0x093C, 0x093C),
+    (0x0941, 0x0948),
+    (0x094D, 0x094D),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x20D0, 0x20FF),
+    (0x302A, 0x302F),
+    (0x3099, 0x309A),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    // Skin tone modifiers, which only recolour the emoji before them
+    (0x1F3FB, 0x1F3FF),
+    (0xE0100, 0xE01EF),
+];
+
+// East Asian wide and fullwidth characters and emoji, which take up two
+// columns
+const DOUBLE_WIDTH: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x2329, 0x232A),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x16FE0, 0x16FE4),
+    (0x17000, 0x18AFF),
+    (0x1B000, 0x1B2FF),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F200, 0x1F251),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F900, 0x1F9FF),
+    (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let c = c as u32;
+    ranges
+        .binary_search_by(|&(first, last)| {
+            if last < c {
+                std::cmp::Ordering::Less
+            } else if first > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+// How many columns c takes up on its own. Control characters keep the one
+// column they've always had, tabs are up to whoever's laying out the row.
+pub fn char_width(c: char) -> usize {
+    if c.is_control() {
+        1
+    } else if in_ranges(c, ZERO_WIDTH) {
+        0
+    } else if in_ranges(c, DOUBLE_WIDTH) {
+        2
+    } else {
+        1
+    }
+}
+
+// Gives the columns of a run of characters one at a time. Anything joined
+// onto the character before it (a combining mark, or whatever follows a
+// zero width joiner) is part of the same grapheme so takes no more room.
+#[derive(Default)]
+pub struct ColumnWidths {
+    after_joiner: bool,
+}
+
+impl ColumnWidths {
+    pub fn width_of(&mut self, c: char) -> usize {
+        let joined = self.after_joiner;
+        self.after_joiner = c == ZERO_WIDTH_JOINER;
+        if joined {
+            0
+        } else {
+            char_width(c)
+        }
+    }
+}
+
+#[test]
+fn test_char_width() {
+    assert_eq!(1, char_width('a'));
+    assert_eq!(1, char_width('£'));
+    assert_eq!(2, char_width('日'));
+    assert_eq!(2, char_width('한'));
+    assert_eq!(2, char_width('Ａ'));
+    assert_eq!(2, char_width('🦀'));
+    assert_eq!(0, char_width('\u{0301}'));
+    assert_eq!(0, char_width('\u{200B}'));
+    assert_eq!(1, char_width('\n'));
+}
+
+#[test]
+fn test_column_widths() {
+    let str_width = |text: &str| {
+        let mut widths = ColumnWidths::default();
+        text.chars().map(|c| widths.width_of(c)).sum::<usize>()
+    };
+    assert_eq!(5, str_width("hello"));
+    assert_eq!(4, str_width("日本"));
+    assert_eq!(4, str_width("cafe\u{0301}"));
+    // A family emoji is four people joined into one
+    assert_eq!(
+        2,
+        str_width("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}")
+    );
+    assert_eq!(2, str_width("\u{1F44D}\u{1F3FD}"));
+}
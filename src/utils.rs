@@ -1,3 +1,10 @@
 pub fn char_position_to_byte_position(input: &str, at: usize) -> usize {
     input.chars().take(at).map(|c| c.len_utf8()).sum()
 }
+
+// A path in the temp directory for a test, with the pid in the name so test
+// runs going at once don't trip over each other's files
+#[cfg(test)]
+pub fn temp_path(prefix: &str, suffix: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("{}-{}{}", prefix, std::process::id(), suffix))
+}
@@ -0,0 +1,162 @@
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Viewport {
+    row_offset: f32,
+    col_offset: f32,
+    screen_rows: i32,
+}
+
+impl Viewport {
+    pub fn row_offset(&self) -> f32 {
+        self.row_offset
+    }
+
+    pub fn col_offset(&self) -> f32 {
+        self.col_offset
+    }
+
+    pub fn screen_rows(&self) -> i32 {
+        self.screen_rows
+    }
+
+    pub fn first_row(&self) -> i32 {
+        self.row_offset.floor() as i32
+    }
+
+    // The first column drawn, text is only ever scrolled a whole column at a time
+    pub fn first_col(&self) -> usize {
+        self.col_offset.floor() as usize
+    }
+
+    pub fn set_screen_rows(&mut self, screen_rows: i32) {
+        self.screen_rows = screen_rows;
+    }
+
+    pub fn contains_row(&self, row: i32) -> bool {
+        row >= self.first_row() && row < self.first_row() + self.screen_rows
+    }
+
+    pub fn set_row_offset(&mut self, row_offset: f32, num_lines: usize) {
+        let max_offset = f32::max(0.0, num_lines as f32 - 1.0);
+        self.row_offset = row_offset.clamp(0.0, max_offset);
+    }
+
+    pub fn set_col_offset(&mut self, col_offset: f32) {
+        self.col_offset = col_offset.max(0.0);
+    }
+
+    pub fn scroll_by(&mut self, rows: f32, cols: f32, num_lines: usize) {
+        self.set_row_offset(self.row_offset + rows, num_lines);
+        self.set_col_offset(self.col_offset + cols);
+    }
+
+    // Moves the viewport the minimum amount needed to show `row`
+    pub fn scroll_to_row(&mut self, row: i32) {
+        if row >= self.row_offset.floor() as i32 + self.screen_rows {
+            self.row_offset = (row - self.screen_rows + 1) as f32;
+        }
+
+        if row < self.row_offset.ceil() as i32 {
+            self.row_offset = row as f32;
+        }
+    }
+
+    // Moves the viewport the minimum amount needed to show the column `col`,
+    // when `screen_cols` columns fit across
+    pub fn scroll_to_col(&mut self, col: usize, screen_cols: usize) {
+        let first_col = self.first_col();
+        if col < first_col {
+            self.col_offset = col as f32;
+        } else if screen_cols > 0 && col >= first_col + screen_cols {
+            self.col_offset = (col + 1 - screen_cols) as f32;
+        }
+    }
+
+    // How far through the buffer the top of the viewport is, from 0.0 to 1.0
+    pub fn scroll_fraction(&self, num_lines: usize) -> f32 {
+        let max_offset = num_lines as f32 - self.screen_rows as f32;
+        if max_offset <= 0.0 {
+            0.0
+        } else {
+            (self.row_offset / max_offset).min(1.0)
+        }
+    }
+
+    pub fn set_scroll_fraction(&mut self, fraction: f32, num_lines: usize) {
+        let max_offset = f32::max(0.0, num_lines as f32 - self.screen_rows as f32);
+        let fraction = fraction.clamp(0.0, 1.0);
+        self.set_row_offset((max_offset * fraction).floor(), num_lines);
+    }
+}
+
+pub fn row_at_percentage(percentage: usize, num_lines: usize) -> i32 {
+    if num_lines == 0 {
+        return 0;
+    }
+    let percentage = percentage.min(100);
+    let last_line = num_lines - 1;
+    ((last_line * percentage + 50) / 100) as i32
+}
+
+#[test]
+fn test_row_at_percentage() {
+    assert_eq!(0, row_at_percentage(0, 0));
+    assert_eq!(0, row_at_percentage(50, 1));
+    assert_eq!(0, row_at_percentage(0, 101));
+    assert_eq!(50, row_at_percentage(50, 101));
+    assert_eq!(100, row_at_percentage(100, 101));
+    assert_eq!(100, row_at_percentage(250, 101));
+}
+
+#[test]
+fn test_scroll_to_row() {
+    let mut viewport = Viewport::default();
+    viewport.set_screen_rows(10);
+    viewport.scroll_to_row(5);
+    assert_eq!(0.0, viewport.row_offset());
+    viewport.scroll_to_row(15);
+    assert_eq!(6.0, viewport.row_offset());
+    assert!(viewport.contains_row(15));
+    viewport.scroll_to_row(2);
+    assert_eq!(2.0, viewport.row_offset());
+}
+
+#[test]
+fn test_set_row_offset_is_clamped() {
+    let mut viewport = Viewport::default();
+    viewport.set_row_offset(-3.0, 20);
+    assert_eq!(0.0, viewport.row_offset());
+    viewport.set_row_offset(30.0, 20);
+    assert_eq!(19.0, viewport.row_offset());
+    viewport.set_col_offset(-1.0);
+    assert_eq!(0.0, viewport.col_offset());
+}
+
+#[test]
+fn test_scroll_fraction() {
+    let mut viewport = Viewport::default();
+    viewport.set_screen_rows(10);
+    assert_eq!(0.0, viewport.scroll_fraction(5));
+    viewport.set_scroll_fraction(0.5, 110);
+    assert_eq!(50.0, viewport.row_offset());
+    assert_eq!(0.5, viewport.scroll_fraction(110));
+    viewport.set_scroll_fraction(1.0, 110);
+    assert_eq!(1.0, viewport.scroll_fraction(110));
+}
+
+#[test]
+fn test_scroll_to_col() {
+    let mut viewport = Viewport::default();
+    viewport.scroll_to_col(70, 80);
+    assert_eq!(0, viewport.first_col());
+    viewport.scroll_to_col(80, 80);
+    assert_eq!(1, viewport.first_col());
+    viewport.scroll_to_col(130, 80);
+    assert_eq!(51, viewport.first_col());
+    viewport.scroll_to_col(60, 80);
+    assert_eq!(51, viewport.first_col());
+    viewport.scroll_to_col(10, 80);
+    assert_eq!(10, viewport.first_col());
+    // Part way through a column still starts at that column
+    viewport.set_col_offset(12.6);
+    assert_eq!(12, viewport.first_col());
+}
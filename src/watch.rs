@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: u64 = 1000;
+
+// Enough about a file on disk to tell when something else has written to it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DiskStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+// Keeps an eye on a pane's file, so that changes made by other programs
+// don't go unnoticed (or get saved over)
+pub struct Watch {
+    poll_interval: Duration,
+    since_poll: Duration,
+    // The change that's already been asked about, so it's only asked once
+    noticed: Option<DiskStamp>,
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(POLL_INTERVAL),
+            since_poll: Duration::default(),
+            noticed: None,
+        }
+    }
+}
+
+impl Watch {
+    // Advances the clock, returning true when the file should be checked again
+    pub fn tick(&mut self, duration: Duration) -> bool {
+        self.since_poll += duration;
+        if self.since_poll >= self.poll_interval {
+            self.since_poll = Duration::default();
+            true
+        } else {
+            false
+        }
+    }
+
+    // True the first time a change is seen
+    pub fn notice(&mut self, stamp: DiskStamp) -> bool {
+        if self.noticed == Some(stamp) {
+            false
+        } else {
+            self.noticed = Some(stamp);
+            true
+        }
+    }
+}
+
+#[test]
+fn test_changes_are_noticed_once() {
+    let mut watch = Watch::default();
+    assert!(!watch.tick(Duration::from_millis(600)));
+    assert!(watch.tick(Duration::from_millis(600)));
+
+    let stamp = DiskStamp {
+        modified: None,
+        len: 10,
+    };
+    assert!(watch.notice(stamp));
+    assert!(!watch.notice(stamp));
+    assert!(watch.notice(DiskStamp { len: 11, ..stamp }));
+}
@@ -0,0 +1,88 @@
+// Driving the editor the way a frontend does, with no window at all
+
+use bim::action::{Action, BufferAction, WindowAction};
+use bim::buffer::Buffer;
+use bim::keycodes::Key;
+use bim::options::Options;
+use bim::EditorCore;
+use glam::vec2;
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+fn editor(buffer: Buffer<'_>) -> EditorCore<'_> {
+    EditorCore::new(buffer, Options::default(), vec2(800.0, 600.0), 1.0).unwrap()
+}
+
+fn type_text(editor: &mut EditorCore<'_>, text: &str) {
+    for c in text.chars() {
+        editor.handle_key(Key::Other(c));
+    }
+}
+
+#[test]
+fn test_typing_and_undoing() {
+    let mut editor = editor(Buffer::default());
+    type_text(&mut editor, "hello");
+    editor.handle_key(Key::Return);
+    type_text(&mut editor, "world");
+    assert_eq!(Some("hello\nworld\n".to_string()), editor.contents());
+    assert!(editor.is_dirty());
+
+    editor.run_action(Action::OnBuffer(BufferAction::Undo));
+    assert_eq!(Some("hello\n\n".to_string()), editor.contents());
+    editor.run_action(Action::OnBuffer(BufferAction::Redo));
+    assert_eq!(Some("hello\nworld\n".to_string()), editor.contents());
+}
+
+#[test]
+fn test_opening_editing_and_saving_a_file() {
+    let path = env::temp_dir().join(format!("bim-core-{}.txt", std::process::id()));
+    let filename = path.to_string_lossy().to_string();
+    fs::write(&path, "one\ntwo\n").unwrap();
+    let mut buffer = Buffer::default();
+    buffer.open(&filename).unwrap();
+    let mut editor = editor(buffer);
+    assert_eq!(format!("{} - bim", filename), editor.window_title());
+
+    type_text(&mut editor, "zero ");
+    editor.run_action(Action::OnWindow(WindowAction::SaveFile));
+    editor.tick(Duration::from_millis(5));
+    assert!(!editor.is_dirty());
+    assert_eq!("zero one\ntwo\n", fs::read_to_string(&path).unwrap());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_panes_tabs_and_quitting() {
+    let mut editor = editor(Buffer::default());
+    editor.run_action(Action::OnWindow(WindowAction::SplitVertically));
+    assert_eq!(2, editor.num_panes());
+    editor.run_action(Action::OnWindow(WindowAction::NewTab));
+    assert_eq!(2, editor.tab_labels().len());
+    assert_eq!(1, editor.num_panes());
+
+    // Closing the new tab's only pane goes back to the first tab
+    editor.run_action(Action::OnWindow(WindowAction::ClosePane));
+    assert_eq!(1, editor.tab_labels().len());
+    assert_eq!(2, editor.num_panes());
+    editor.run_action(Action::OnWindow(WindowAction::ClosePane));
+    assert!(!editor.should_quit());
+    editor.run_action(Action::OnWindow(WindowAction::ClosePane));
+    assert!(editor.should_quit());
+}
+
+#[test]
+fn test_ex_commands() {
+    let mut editor = editor(Buffer::default());
+    type_text(&mut editor, "abc");
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(
+        ":nosuchcommand".to_string(),
+    )));
+    assert!(editor.status_message().is_some());
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(
+        ":q!".to_string(),
+    )));
+    assert!(editor.should_quit());
+}
@@ -0,0 +1,42 @@
+// Changing directory moves the whole process, so this runs on its own
+
+use bim::action::{Action, WindowAction};
+use bim::buffer::Buffer;
+use bim::keycodes::Key;
+use bim::options::Options;
+use bim::EditorCore;
+use glam::vec2;
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn test_open_files_stay_put_when_changing_directory() {
+    let root = env::temp_dir().join(format!("bim-cd-{}", std::process::id()));
+    let (first, second) = (root.join("first"), root.join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    fs::write(first.join("notes.txt"), "notes\n").unwrap();
+    env::set_current_dir(&first).unwrap();
+
+    let mut buffer = Buffer::default();
+    buffer.open("notes.txt").unwrap();
+    let mut editor = EditorCore::new(buffer, Options::default(), vec2(800.0, 600.0), 1.0).unwrap();
+    editor.run_action(Action::OnWindow(WindowAction::RunExCommand(format!(
+        ":cd {}",
+        second.display()
+    ))));
+    assert_eq!(second.canonicalize().unwrap(), env::current_dir().unwrap());
+
+    editor.handle_key(Key::Other('!'));
+    editor.run_action(Action::OnWindow(WindowAction::SaveFile));
+    editor.tick(Duration::from_millis(5));
+    assert!(!editor.is_dirty());
+    assert_eq!(
+        "!notes\n",
+        fs::read_to_string(first.join("notes.txt")).unwrap()
+    );
+    assert!(!second.join("notes.txt").exists());
+
+    fs::remove_dir_all(&root).unwrap();
+}